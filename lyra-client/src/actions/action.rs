@@ -1,17 +1,32 @@
 use crate::actions::helpers::ModuleData;
-use anyhow::Result;
+use crate::actions::nonce::NONCE_GENERATOR;
+use crate::config::LyraConfig;
+use crate::error::Result;
 use bigdecimal::BigDecimal;
 use ethers::abi::{AbiDecode, AbiEncode};
 use ethers::prelude::{
     Address, EthAbiCodec, EthAbiType, LocalWallet, Signature, Signer, I256, U256,
 };
 use ethers::utils::hex;
-use log::debug;
 pub use orderbook_types::types::orders::{
     Direction, LiquidityRole, OrderParams, OrderResponse, OrderStatus, OrderType, ReplaceParams,
     TimeInForce,
 };
 use serde::Deserialize;
+use tracing::debug;
+
+/// Lifetime of new signatures, override with SIGNATURE_EXPIRY_SEC
+const DEFAULT_SIGNATURE_EXPIRY_SEC: i64 = 600;
+/// Min signature lifetime the API accepts for new orders
+pub const MIN_SIGNATURE_EXPIRY_SEC: i64 = 300;
+
+/// Lifetime of new signatures. The API has no cancel-after timer, instead resting orders expire
+/// server-side with their signature, so this is the window of a dead man's switch: orders that
+/// are not re-signed within it expire even if the process froze or lost connectivity.
+pub fn signature_expiry_sec() -> i64 {
+    let expiry_sec = std::env::var("SIGNATURE_EXPIRY_SEC").ok().and_then(|s| s.parse().ok());
+    expiry_sec.unwrap_or(DEFAULT_SIGNATURE_EXPIRY_SEC).max(MIN_SIGNATURE_EXPIRY_SEC)
+}
 
 #[derive(Clone, Debug, Default, PartialEq, EthAbiType, EthAbiCodec)]
 pub struct ActionData {
@@ -28,8 +43,9 @@ pub struct ActionData {
 impl ActionData {
     fn get_nonce_and_expiry() -> (i64, i64) {
         let now = chrono::Utc::now();
-        let nonce = now.timestamp_micros();
-        let signature_expiry_sec = (now + chrono::Duration::seconds(600)).timestamp();
+        let nonce = NONCE_GENERATOR.next();
+        let signature_expiry_sec =
+            (now + chrono::Duration::seconds(signature_expiry_sec())).timestamp();
         (nonce, signature_expiry_sec)
     }
 
@@ -37,39 +53,38 @@ impl ActionData {
         module_data: T,
         subaccount_id: i64,
         signer_address: Address,
+        config: &LyraConfig,
     ) -> Result<ActionData> {
         let (nonce, signature_expiry_sec) = ActionData::get_nonce_and_expiry();
-        let module_addr = module_data.address();
+        let module_addr = module_data.address(config);
         let encoded_data = module_data.encode();
         debug!("encoded_data: {:?}", hex::encode(&encoded_data));
         let hashed_data = ethers::utils::keccak256(&encoded_data);
         debug!("encoded_data_hashed: {:?}", hex::encode(&hashed_data));
-        let owner = std::env::var("OWNER_PUBLIC_KEY").expect("OWNER_PUBLIC_KEY must be set");
-        let action_typehash =
-            std::env::var("ACTION_TYPEHASH").expect("ACTION_TYPEHASH must be set");
-        let action_typehash = hex::const_decode_to_array::<32>(action_typehash.as_bytes())?;
         Ok(ActionData {
-            action_typehash,
+            action_typehash: config.action_typehash,
             subaccount_id: subaccount_id.into(),
             nonce: nonce.into(),
             module: module_addr,
             data: hashed_data,
             expiry: signature_expiry_sec.into(),
-            owner: owner.parse()?,
+            owner: config.owner()?,
             signer: signer_address,
         })
     }
 
-    fn action_hash(self) -> [u8; 32] {
+    /// Struct hash of the action, i.e. keccak of its abi encoding
+    pub fn action_hash(self) -> [u8; 32] {
         let action_hash = ethers::utils::keccak256(self.encode());
         debug!("action_hash: {:?}", hex::encode(&action_hash));
         action_hash
     }
 
-    pub fn hash(self) -> [u8; 32] {
-        let domain_sep = std::env::var("DOMAIN_SEPARATOR").expect("DOMAIN_SEPARATOR must be set");
-        let domain_sep = hex::decode(domain_sep).expect("hex::decode failed for DOMAIN_SEPARATOR");
+    /// Typed data hash, the domain separator and typehash come from the config
+    /// (EIP-712 computed, or the env provided ones with the `legacy-signing` feature)
+    pub fn hash(self, config: &LyraConfig) -> [u8; 32] {
         let prefix = hex::decode("1901").expect("hex::decode failed for prefix");
+        let domain_sep = config.domain_separator.to_vec();
         let action_hash = self.action_hash();
         let hash = ethers::utils::keccak256(&[prefix, domain_sep, action_hash.into()].concat());
         debug!("typed_data_hash: {:?}", hex::encode(&hash));
@@ -1,14 +1,10 @@
-use crate::actions::helpers::{
-    get_asset_address, get_asset_decimals, get_manager_address, MarginType,
-};
+use crate::actions::helpers::{CollateralAsset, MarginType};
 use crate::utils::{decimal_to_i256, decimal_to_u256, decimal_to_u256_with_prec};
 
-use anyhow::Result;
+use crate::error::Result;
 use bigdecimal::BigDecimal;
 use ethers::abi::{AbiDecode, AbiEncode};
-use ethers::prelude::{
-    Address, EthAbiCodec, EthAbiType, LocalWallet, Signature, Signer, I256, U256,
-};
+use ethers::prelude::{Address, EthAbiCodec, EthAbiType, Signature, Signer, I256, U256};
 use ethers::utils::hex;
 pub use orderbook_types::types::orders::{
     Direction, LiquidityRole, OrderParams, OrderResponse, OrderStatus, OrderType, ReplaceParams,
@@ -18,9 +14,15 @@ use serde::Deserialize;
 
 use crate::actions::helpers::ModuleData;
 use crate::actions::ActionData;
+use crate::config::LyraConfig;
+use crate::signer::SignerProvider;
+use orderbook_types::generated::private_create_subaccount::{
+    MarginType as NewMarginType, PrivateCreateSubaccountParamsSchema,
+};
 use orderbook_types::generated::private_deposit::PrivateDepositParamsSchema;
 
 pub type DepositParams = PrivateDepositParamsSchema;
+pub type CreateSubaccountParams = PrivateCreateSubaccountParamsSchema;
 
 #[derive(Clone, Debug, Default, PartialEq, EthAbiType, EthAbiCodec)]
 pub struct DepositData {
@@ -30,28 +32,30 @@ pub struct DepositData {
 }
 
 impl DepositData {
-    pub fn new(amount: &BigDecimal, asset_name: &String, margin_type: MarginType) -> Result<Self> {
-        let asset_address = get_asset_address(&asset_name);
-        let asset_decimals = get_asset_decimals(&asset_name);
-        let manager_address = get_manager_address(&asset_name, margin_type);
+    pub fn new(
+        amount: &BigDecimal,
+        asset: &CollateralAsset,
+        margin_type: MarginType,
+        config: &LyraConfig,
+    ) -> Result<Self> {
         Ok(DepositData {
-            erc20_amount: decimal_to_u256_with_prec(amount.clone(), asset_decimals)?,
-            asset_address: asset_address.parse()?,
-            manager_address: manager_address.parse()?,
+            erc20_amount: decimal_to_u256_with_prec(amount.clone(), asset.decimals)?,
+            asset_address: asset.address,
+            manager_address: asset.manager_address(margin_type, config)?,
         })
     }
 }
 impl ModuleData for DepositData {
-    fn address(&self) -> Address {
-        let addr = std::env::var("DEPOSIT_ADDRESS").expect("DEPOSIT_ADDRESS must be set");
-        addr.parse().expect("DEPOSIT_ADDRESS must be a valid module address")
+    fn address(&self, config: &LyraConfig) -> Address {
+        config.deposit_address
     }
 }
 
 impl ActionData {
-    pub fn to_deposit_params(
+    pub async fn to_deposit_params(
         self,
-        signer: &LocalWallet,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
         amount: BigDecimal,
         asset_name: String,
     ) -> Result<DepositParams> {
@@ -62,20 +66,60 @@ impl ActionData {
             nonce: self.nonce.as_u64() as i64,
             signature_expiry_sec: self.expiry.as_u64() as i64,
             signer: hex::encode_prefixed(self.signer),
-            signature: signer.sign_hash(self.hash().into())?.to_string(),
+            signature: signer.sign_action(&self, config).await?.to_string(),
+        })
+    }
+
+    pub async fn to_create_subaccount_params(
+        self,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
+        amount: BigDecimal,
+        asset: &CollateralAsset,
+        margin_type: MarginType,
+    ) -> Result<CreateSubaccountParams> {
+        let (margin_type, currency) = match margin_type {
+            MarginType::Sm => (NewMarginType::Sm, None),
+            MarginType::Pm => (NewMarginType::Pm, Some(asset.currency.clone())),
+        };
+        Ok(CreateSubaccountParams {
+            amount,
+            asset_name: asset.asset_name.clone(),
+            currency,
+            margin_type,
+            nonce: self.nonce.as_u64() as i64,
+            signature_expiry_sec: self.expiry.as_u64() as i64,
+            signer: hex::encode_prefixed(self.signer),
+            signature: signer.sign_action(&self, config).await?.to_string(),
+            wallet: hex::encode_prefixed(self.owner),
         })
     }
 }
 
-pub fn new_deposit_params(
-    signer: &LocalWallet,
+pub async fn new_deposit_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
     subaccount_id: i64,
     amount: BigDecimal,
-    asset_name: String,
+    asset: &CollateralAsset,
     margin_type: MarginType,
 ) -> Result<DepositParams> {
-    let deposit_data = DepositData::new(&amount, &asset_name, margin_type)?;
-    let action_data = ActionData::new(deposit_data, subaccount_id, signer.address())?;
-    let params = action_data.to_deposit_params(signer, amount, asset_name)?;
+    let deposit_data = DepositData::new(&amount, asset, margin_type, config)?;
+    let action_data = ActionData::new(deposit_data, subaccount_id, signer.address(), config)?;
+    let asset_name = asset.asset_name.clone();
+    let params = action_data.to_deposit_params(signer, config, amount, asset_name).await?;
     Ok(params)
 }
+
+/// A deposit into subaccount 0, which creates a new subaccount with the manager of `margin_type`
+pub async fn new_create_subaccount_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
+    amount: BigDecimal,
+    asset: &CollateralAsset,
+    margin_type: MarginType,
+) -> Result<CreateSubaccountParams> {
+    let deposit_data = DepositData::new(&amount, asset, margin_type, config)?;
+    let action_data = ActionData::new(deposit_data, 0, signer.address(), config)?;
+    action_data.to_create_subaccount_params(signer, config, amount, asset, margin_type).await
+}
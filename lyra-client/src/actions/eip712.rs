@@ -0,0 +1,41 @@
+use crate::actions::ActionData;
+use crate::config::LyraConfig;
+use ethers::abi::AbiEncode;
+use ethers::prelude::{Address, U256};
+use ethers::types::transaction::eip712::{EIP712Domain, Eip712, Eip712Error};
+
+pub const ACTION_TYPE: &str = "Action(uint256 subaccountId,uint256 nonce,address module,bytes data,uint256 expiry,address owner,address signer)";
+const MATCHING_DOMAIN_NAME: &str = "Matching";
+const MATCHING_DOMAIN_VERSION: &str = "1.0";
+
+/// Domain of the Matching contract the actions are verified against
+pub fn matching_domain(chain_id: u64, matching_address: Address) -> EIP712Domain {
+    EIP712Domain {
+        name: Some(MATCHING_DOMAIN_NAME.to_string()),
+        version: Some(MATCHING_DOMAIN_VERSION.to_string()),
+        chain_id: Some(U256::from(chain_id)),
+        verifying_contract: Some(matching_address),
+        salt: None,
+    }
+}
+
+/// Implemented by hand rather than derived: `data` is the keccak of the abi encoded module
+/// data (e.g. `TradeData`), which is how EIP-712 encodes a dynamic `bytes` member.
+/// Signing goes through `SignerProvider::sign_action` with an explicit config, this impl uses
+/// `LyraConfig::current()` for the domain.
+impl Eip712 for ActionData {
+    type Error = Eip712Error;
+
+    fn domain(&self) -> Result<EIP712Domain, Self::Error> {
+        let config = LyraConfig::current().map_err(|e| Eip712Error::Message(e.to_string()))?;
+        Ok(matching_domain(config.chain_id, config.matching_address))
+    }
+
+    fn type_hash() -> Result<[u8; 32], Self::Error> {
+        Ok(ethers::utils::keccak256(ACTION_TYPE))
+    }
+
+    fn struct_hash(&self) -> Result<[u8; 32], Self::Error> {
+        Ok(ethers::utils::keccak256(self.clone().encode()))
+    }
+}
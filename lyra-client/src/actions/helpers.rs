@@ -1,34 +1,71 @@
+use crate::config::LyraConfig;
+use crate::error::{LyraError, Result};
+use crate::json_rpc::http_rpc;
+use anyhow::format_err;
 use ethers::prelude::Address;
 pub use orderbook_types::generated::private_get_subaccount::MarginType;
+use orderbook_types::generated::public_get_asset::{
+    PublicGetAssetParamsSchema, PublicGetAssetResponseSchema,
+};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
 
 pub trait ModuleData {
-    fn address(&self) -> Address;
+    fn address(&self, config: &LyraConfig) -> Address;
 }
 
 // deposit & withdrawal helpers
-pub fn get_asset_address(asset_name: &str) -> String {
-    let asset_prefix = match asset_name {
-        "USDC" => "CASH".to_string(),
-        _ => asset_name.to_uppercase() + "_BASE",
-    };
-    let erc20_env = format!("{}_ADDRESS", asset_prefix);
-    std::env::var(erc20_env.clone()).expect(&format!("{} must be set", erc20_env))
+
+static COLLATERAL_ASSETS: OnceLock<Mutex<HashMap<String, CollateralAsset>>> = OnceLock::new();
+
+/// On-chain details of an ERC-20 collateral, resolved from `public/get_asset`
+#[derive(Clone, Debug)]
+pub struct CollateralAsset {
+    pub asset_name: String,
+    pub address: Address, // Asset.sol address used in deposits and withdrawals
+    pub decimals: u32,
+    pub currency: String, // underlying currency, selects the portfolio margin manager
 }
 
-pub fn get_asset_decimals(asset_name: &str) -> u32 {
-    match asset_name {
-        "USDC" => 6,
-        "BTC" => 8,
-        "LBTC" => 8,
-        _ => 18,
+impl CollateralAsset {
+    /// Looks the asset up once per process, e.g. `WEETH` or `USDC`
+    pub async fn get(asset_name: &str) -> Result<CollateralAsset> {
+        let cache = COLLATERAL_ASSETS.get_or_init(|| Mutex::new(HashMap::new()));
+        if let Some(asset) = cache.lock().unwrap().get(asset_name) {
+            return Ok(asset.clone());
+        }
+        let params = PublicGetAssetParamsSchema { asset_name: asset_name.to_string() };
+        let res = http_rpc::<_, PublicGetAssetResponseSchema>("public/get_asset", params, None)
+            .await?
+            .into_result()?
+            .result;
+        let decimals = res
+            .erc20_details
+            .as_ref()
+            .ok_or(format_err!("{} is not an ERC-20 asset", asset_name))?
+            .decimals;
+        let asset = CollateralAsset {
+            asset_name: asset_name.to_string(),
+            address: res.address.parse().map_err(LyraError::deserialization)?,
+            decimals: decimals as u32,
+            currency: res.currency,
+        };
+        cache.lock().unwrap().insert(asset_name.to_string(), asset.clone());
+        Ok(asset)
     }
-}
 
-pub fn get_manager_address(asset_name: &str, margin_type: MarginType) -> String {
-    let manager_prefix = match margin_type {
-        MarginType::Pm => asset_name.to_uppercase() + "_PMRM",
-        MarginType::Sm => "SRM".to_string(),
-    };
-    let manager_env = format!("{}_ADDRESS", manager_prefix);
-    std::env::var(manager_env.clone()).expect(&format!("{} must be set", manager_env))
+    /// SRM for standard margin, otherwise the PMRM of the asset's currency
+    pub fn manager_address(&self, margin_type: MarginType, config: &LyraConfig) -> Result<Address> {
+        let manager = match margin_type {
+            MarginType::Sm => "SRM",
+            MarginType::Pm => self.currency.as_str(),
+        };
+        config.managers.get(manager).copied().ok_or_else(|| {
+            LyraError::Other(format_err!(
+                "No {:?} manager configured for {}",
+                margin_type,
+                self.asset_name
+            ))
+        })
+    }
 }
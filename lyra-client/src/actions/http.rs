@@ -0,0 +1,166 @@
+use crate::auth::sign_auth_header;
+use crate::error::{LyraError, Result};
+use crate::json_rpc::{http_rpc, Response, WsClient, WsClientExt};
+use crate::signer::SignerProvider;
+use orderbook_types::generated::private_cancel_by_instrument::{
+    PrivateCancelByInstrumentParamsSchema, PrivateCancelByInstrumentResponseSchema,
+};
+use orderbook_types::types::orders::{OrderParams, ReplaceParams, ReplaceResponse};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Orders resent over HTTP get a new reject timestamp, which is not part of the signed action
+const HTTP_REJECT_AFTER_SEC: i64 = 5;
+
+/// Sends a private RPC over HTTP with auth headers signed by `signer` instead of over the
+/// logged in websocket. Takes the same (signed) params as the websocket RPC.
+pub async fn http_private_rpc<P, R>(
+    signer: &impl SignerProvider,
+    method: &str,
+    params: P,
+) -> Result<Response<R>>
+where
+    P: Serialize + Debug,
+    R: for<'de> Deserialize<'de>,
+{
+    let headers = sign_auth_header(signer).await?;
+    http_rpc(method, params, Some(headers)).await
+}
+
+/// When the auction orders fall back from the websocket to HTTP, see `HttpFallback`
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpFallbackParams {
+    // Transport errors or timeouts in a row after which the websocket counts as degraded
+    #[serde(default = "default_max_ws_failures")]
+    pub max_ws_failures: u32,
+    // A degraded websocket is tried again after this long, orders go over HTTP until then
+    #[serde(default = "default_ws_retry_sec")]
+    pub ws_retry_sec: u64,
+}
+
+fn default_max_ws_failures() -> u32 {
+    2
+}
+
+fn default_ws_retry_sec() -> u64 {
+    60
+}
+
+#[derive(Debug, Default)]
+struct FallbackState {
+    ws_failures: u32,
+    degraded_since: Option<Instant>,
+}
+
+/// Sends orders, replaces and cancels over the websocket and resends the same signed params
+/// over HTTP when the websocket fails with a transport error or timeout. Once it failed
+/// `max_ws_failures` times in a row the websocket is skipped for `ws_retry_sec`. Resent orders
+/// keep their nonce, so an order the websocket did deliver is rejected instead of filling twice.
+#[derive(Debug)]
+pub struct HttpFallback {
+    pub params: HttpFallbackParams,
+    state: Mutex<FallbackState>,
+}
+
+impl HttpFallback {
+    pub fn new(params: HttpFallbackParams) -> Self {
+        Self { params, state: Mutex::new(FallbackState::default()) }
+    }
+
+    /// True while the websocket is degraded and not yet due for another try
+    pub fn is_degraded(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        let retry_after = Duration::from_secs(self.params.ws_retry_sec);
+        state.degraded_since.is_some_and(|since| since.elapsed() < retry_after)
+    }
+
+    fn ws_failed(&self, method: &str, e: &LyraError) {
+        let mut state = self.state.lock().unwrap();
+        state.ws_failures += 1;
+        warn!("{} over websocket failed with {} ({} in a row)", method, e, state.ws_failures);
+        if state.ws_failures >= self.params.max_ws_failures {
+            if state.degraded_since.is_none() {
+                warn!("Websocket degraded, sending orders over HTTP");
+            }
+            state.degraded_since = Some(Instant::now());
+        }
+    }
+
+    fn ws_succeeded(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.degraded_since.is_some() {
+            info!("Websocket recovered, sending orders over it again");
+        }
+        *state = FallbackState::default();
+    }
+
+    async fn send<P, R>(
+        &self,
+        client: &WsClient,
+        signer: &impl SignerProvider,
+        method: &str,
+        mut params: P,
+        refresh: impl FnOnce(&mut P),
+    ) -> Result<Response<R>>
+    where
+        P: Serialize + Debug + Clone,
+        R: for<'de> Deserialize<'de> + Debug + Serialize + Clone,
+    {
+        if !self.is_degraded() {
+            match client.send_rpc::<_, R>(method, params.clone()).await {
+                Ok(res) => {
+                    self.ws_succeeded();
+                    return Ok(res);
+                }
+                Err(e @ (LyraError::Transport(_) | LyraError::Timeout { .. })) => {
+                    self.ws_failed(method, &e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        refresh(&mut params);
+        info!("Sending {} over HTTP", method);
+        http_private_rpc(signer, method, params).await
+    }
+
+    pub async fn send_order<R>(
+        &self,
+        client: &WsClient,
+        signer: &impl SignerProvider,
+        params: OrderParams,
+    ) -> Result<Response<R>>
+    where
+        R: for<'de> Deserialize<'de> + Debug + Serialize + Clone,
+    {
+        let refresh = |params: &mut OrderParams| params.reject_timestamp = reject_timestamp();
+        self.send(client, signer, "private/order", params, refresh).await
+    }
+
+    pub async fn send_replace(
+        &self,
+        client: &WsClient,
+        signer: &impl SignerProvider,
+        params: ReplaceParams,
+    ) -> Result<Response<ReplaceResponse>> {
+        let refresh = |params: &mut ReplaceParams| params.reject_timestamp = reject_timestamp();
+        self.send(client, signer, "private/replace", params, refresh).await
+    }
+
+    pub async fn cancel_by_instrument(
+        &self,
+        client: &WsClient,
+        signer: &impl SignerProvider,
+        subaccount_id: i64,
+        instrument_name: String,
+    ) -> Result<Response<PrivateCancelByInstrumentResponseSchema>> {
+        let params = PrivateCancelByInstrumentParamsSchema { subaccount_id, instrument_name };
+        self.send(client, signer, "private/cancel_by_instrument", params, |_| {}).await
+    }
+}
+
+fn reject_timestamp() -> i64 {
+    (chrono::Utc::now() + chrono::Duration::seconds(HTTP_REJECT_AFTER_SEC)).timestamp_millis()
+}
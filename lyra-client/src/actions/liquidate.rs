@@ -1,12 +1,10 @@
 use crate::utils::{decimal_to_i256, decimal_to_u256, decimal_to_u256_with_prec};
 use std::str::FromStr;
 
-use anyhow::Result;
-use bigdecimal::BigDecimal;
+use crate::error::Result;
+use bigdecimal::{BigDecimal, One, RoundingMode, Zero};
 use ethers::abi::{AbiDecode, AbiEncode};
-use ethers::prelude::{
-    Address, EthAbiCodec, EthAbiType, LocalWallet, Signature, Signer, I256, U256,
-};
+use ethers::prelude::{Address, EthAbiCodec, EthAbiType, Signature, Signer, I256, U256};
 use ethers::utils::hex;
 pub use orderbook_types::types::orders::{
     Direction, LiquidityRole, OrderParams, OrderResponse, OrderStatus, OrderType, ReplaceParams,
@@ -15,6 +13,8 @@ pub use orderbook_types::types::orders::{
 
 use crate::actions::helpers::ModuleData;
 use crate::actions::ActionData;
+use crate::config::LyraConfig;
+use crate::signer::SignerProvider;
 use orderbook_types::types::liquidations::{AuctionDetailsSchema, LiquidationParams};
 
 #[derive(Clone, Debug, Default, PartialEq, EthAbiType, EthAbiCodec)]
@@ -46,16 +46,16 @@ impl LiquidateData {
     }
 }
 impl ModuleData for LiquidateData {
-    fn address(&self) -> Address {
-        let addr = std::env::var("LIQUIDATE_ADDRESS").expect("LIQUIDATE_ADDRESS must be set");
-        addr.parse().expect("LIQUIDATE_ADDRESS must be a valid module address")
+    fn address(&self, config: &LyraConfig) -> Address {
+        config.liquidate_address
     }
 }
 
 impl ActionData {
-    pub fn to_liquidate_params(
+    pub async fn to_liquidate_params(
         self,
-        signer: &LocalWallet,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
         liquidated_id: i64,
         percent_bid: BigDecimal,
         details: &AuctionDetailsSchema,
@@ -70,13 +70,14 @@ impl ActionData {
             nonce: self.nonce.as_u64() as i64,
             signature_expiry_sec: self.expiry.as_u64() as i64,
             signer: hex::encode_prefixed(self.signer),
-            signature: signer.sign_hash(self.hash().into())?.to_string(),
+            signature: signer.sign_action(&self, config).await?.to_string(),
         })
     }
 }
 
-pub fn new_liquidate_params(
-    signer: &LocalWallet,
+pub async fn new_liquidate_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
     subaccount_id: i64,
     liquidated_id: i64,
     percent_bid: BigDecimal,
@@ -89,7 +90,28 @@ pub fn new_liquidate_params(
         details.cash_transfer_with_buffer(),
         details.last_seen_trade_id,
     )?;
-    let action_data = ActionData::new(liquidate_data, subaccount_id, signer.address())?;
-    let params = action_data.to_liquidate_params(signer, liquidated_id, percent_bid, details)?;
+    let action_data = ActionData::new(liquidate_data, subaccount_id, signer.address(), config)?;
+    let params = action_data
+        .to_liquidate_params(signer, config, liquidated_id, percent_bid, details)
+        .await?;
     Ok(params)
 }
+
+/// Percent of the liquidated account to bid for (`percent_bid`) so that the estimated bid price
+/// stays within `max_cash`, assuming the price scales with the percent. Bids the max possible
+/// percent (1) without a cap, when the cap covers the whole auction or when the bidder is paid
+/// (insolvent auctions).
+pub fn max_percent_bid(
+    details: &AuctionDetailsSchema,
+    max_cash: Option<&BigDecimal>,
+) -> BigDecimal {
+    let max_cash = match max_cash {
+        Some(max_cash) if details.estimated_bid_price > BigDecimal::zero() => max_cash,
+        _ => return BigDecimal::one(),
+    };
+    let percent = &details.estimated_percent_bid * max_cash / &details.estimated_bid_price;
+    if percent >= details.estimated_percent_bid {
+        return BigDecimal::one();
+    }
+    percent.max(BigDecimal::zero()).with_scale_round(6, RoundingMode::Down)
+}
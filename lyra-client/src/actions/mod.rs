@@ -1,15 +1,25 @@
 pub mod action;
 pub mod deposit;
+pub mod eip712;
 mod helpers;
+pub mod http;
 pub mod liquidate;
+pub mod nonce;
 pub mod order;
 pub mod rfq;
+pub mod transfer;
+pub mod transfer_position;
 pub mod withdraw;
 
 pub use action::*;
 pub use deposit::*;
+pub use eip712::*;
 pub use helpers::*;
+pub use http::*;
 pub use liquidate::*;
+pub use nonce::*;
 pub use order::*;
 pub use rfq::*;
+pub use transfer::*;
+pub use transfer_position::*;
 pub use withdraw::*;
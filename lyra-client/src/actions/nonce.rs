@@ -0,0 +1,30 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Process wide nonce source shared by all signing helpers
+pub static NONCE_GENERATOR: NonceGenerator = NonceGenerator::new();
+
+/// Hands out microsecond timestamp nonces that are unique and strictly increasing across
+/// threads, even when several actions are signed within the same microsecond.
+#[derive(Debug, Default)]
+pub struct NonceGenerator {
+    last: AtomicI64,
+}
+
+impl NonceGenerator {
+    pub const fn new() -> Self {
+        Self { last: AtomicI64::new(0) }
+    }
+
+    pub fn next(&self) -> i64 {
+        let now = chrono::Utc::now().timestamp_micros();
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            let nonce = now.max(last + 1);
+            match self.last.compare_exchange_weak(last, nonce, Ordering::AcqRel, Ordering::Relaxed)
+            {
+                Ok(_) => return nonce,
+                Err(current) => last = current,
+            }
+        }
+    }
+}
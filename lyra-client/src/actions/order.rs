@@ -1,12 +1,12 @@
 use crate::actions::helpers::ModuleData;
 use crate::actions::ActionData;
+use crate::config::LyraConfig;
+use crate::error::{LyraError, Result};
+use crate::signer::SignerProvider;
 use crate::utils::{decimal_to_i256, decimal_to_u256, decimal_to_u256_with_prec};
-use anyhow::Result;
-use bigdecimal::BigDecimal;
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
 use ethers::abi::{AbiDecode, AbiEncode};
-use ethers::prelude::{
-    Address, EthAbiCodec, EthAbiType, LocalWallet, Signature, Signer, I256, U256,
-};
+use ethers::prelude::{Address, EthAbiCodec, EthAbiType, Signature, Signer, I256, U256};
 use ethers::utils::hex;
 pub use orderbook_types::types::orders::{
     Direction, LiquidityRole, OrderParams, OrderResponse, OrderStatus, OrderType, ReplaceParams,
@@ -14,9 +14,10 @@ pub use orderbook_types::types::orders::{
 };
 use orderbook_types::types::tickers::InstrumentTicker;
 use serde::Deserialize;
+use serde_json::{json, Value};
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct OrderArgs {
     pub amount: BigDecimal,
     pub limit_price: BigDecimal,
@@ -25,6 +26,75 @@ pub struct OrderArgs {
     pub order_type: OrderType,
     pub label: String,
     pub mmp: bool,
+    // Market-like orders only: caps the limit price at this distance from mark and refuses to
+    // send while the top of book is further away, see `OrderArgs::with_price_protection`
+    #[serde(default)]
+    pub max_slippage_bps: Option<u32>,
+}
+
+impl OrderArgs {
+    /// Market, IOC and FOK orders as well as limit orders crossing the top of book of the
+    /// ticker, i.e. orders taking liquidity at up to the limit price. Post-only orders never do.
+    pub fn is_market_like(&self, ticker: &InstrumentTicker) -> bool {
+        let crosses_book = match self.direction {
+            Direction::Buy => {
+                !ticker.best_ask_price.is_zero() && self.limit_price >= ticker.best_ask_price
+            }
+            Direction::Sell => {
+                !ticker.best_bid_price.is_zero() && self.limit_price <= ticker.best_bid_price
+            }
+        };
+        match self.time_in_force {
+            TimeInForce::PostOnly => false,
+            TimeInForce::Ioc | TimeInForce::Fok => true,
+            TimeInForce::Gtc => self.order_type == OrderType::Market || crosses_book,
+        }
+    }
+
+    /// Moves the limit price of market-like orders with `max_slippage_bps` within that distance
+    /// of the mark price (rounded inwards to the tick size). Errors with `PriceProtection` if the
+    /// top of book it would trade against is further from mark, e.g. a thin or dislocated book.
+    pub fn with_price_protection(self, ticker: &InstrumentTicker) -> Result<Self> {
+        let Some(max_slippage_bps) = self.max_slippage_bps else {
+            return Ok(self);
+        };
+        if !self.is_market_like(ticker) {
+            return Ok(self);
+        }
+        let mark = &ticker.mark_price;
+        let max_deviation = mark * BigDecimal::from(max_slippage_bps) / BigDecimal::from(10_000);
+        let (top_of_book, deviation) = match self.direction {
+            Direction::Buy => (&ticker.best_ask_price, &ticker.best_ask_price - mark),
+            Direction::Sell => (&ticker.best_bid_price, mark - &ticker.best_bid_price),
+        };
+        if !top_of_book.is_zero() && deviation > max_deviation {
+            return Err(LyraError::PriceProtection(format!(
+                "{} {} top of book {} is more than {} bps from mark {}",
+                self.direction, ticker.instrument_name, top_of_book, max_slippage_bps, mark
+            )));
+        }
+        let tick_size = &ticker.tick_size;
+        let limit_price = match self.direction {
+            Direction::Buy => {
+                let cap = round_to_tick(&(mark + &max_deviation), tick_size, RoundingMode::Down);
+                self.limit_price.clone().min(cap)
+            }
+            Direction::Sell => {
+                let floor = round_to_tick(&(mark - &max_deviation), tick_size, RoundingMode::Up);
+                self.limit_price.clone().max(floor)
+            }
+        };
+        let limit_price = limit_price.max(ticker.min_price.clone()).min(ticker.max_price.clone());
+        Ok(Self { limit_price, ..self })
+    }
+}
+
+/// Rounds the price to a multiple of the tick size
+pub fn round_to_tick(price: &BigDecimal, tick_size: &BigDecimal, mode: RoundingMode) -> BigDecimal {
+    if tick_size.is_zero() {
+        return price.clone();
+    }
+    (price / tick_size).with_scale_round(0, mode) * tick_size
 }
 
 #[derive(Clone, Debug, Default, PartialEq, EthAbiCodec, EthAbiType)]
@@ -47,7 +117,7 @@ impl TradeData {
         is_bid: bool,
     ) -> Result<Self> {
         Ok(Self {
-            asset_address: ticker.base_asset_address.parse()?,
+            asset_address: ticker.base_asset_address.parse().map_err(LyraError::deserialization)?,
             sub_id: ticker.base_asset_sub_id.parse::<u128>()?.into(),
             limit_price: decimal_to_i256(limit_price)?,
             amount: decimal_to_i256(amount)?,
@@ -56,21 +126,67 @@ impl TradeData {
             is_bid,
         })
     }
+
+    /// Trade data of already signed order params, i.e. with the max fee the order was signed with
+    pub fn from_order_params(ticker: &InstrumentTicker, params: &OrderParams) -> Result<Self> {
+        let mut trade_data = TradeData::new(
+            ticker,
+            params.subaccount_id,
+            params.limit_price.clone(),
+            params.amount.clone(),
+            params.direction.is_bid(),
+        )?;
+        trade_data.max_fee = decimal_to_u256(params.max_fee.clone())?;
+        Ok(trade_data)
+    }
+
+    /// The signed fields as they are abi encoded (prices and amounts with 18 decimals)
+    pub fn to_json(&self) -> Value {
+        json!({
+            "asset_address": hex::encode_prefixed(self.asset_address),
+            "sub_id": self.sub_id.to_string(),
+            "limit_price": self.limit_price.to_string(),
+            "amount": self.amount.to_string(),
+            "max_fee": self.max_fee.to_string(),
+            "subaccount_id": self.subaccount_id.to_string(),
+            "is_bid": self.is_bid,
+        })
+    }
 }
 
 impl ModuleData for TradeData {
-    fn address(&self) -> Address {
-        let addr = std::env::var("TRADE_ADDRESS").expect("TRADE_ADDRESS must be set");
-        addr.parse().expect("TRADE_ADDRESS must be a valid module address")
+    fn address(&self, config: &LyraConfig) -> Address {
+        config.trade_address
     }
 }
 
-pub fn new_order_params(
-    signer: &LocalWallet,
+/// Price one tick inside the best opposing quote, so that a post-only order rests on the book.
+/// Never more aggressive than `limit_price`, which is kept if there is no opposing quote.
+pub fn post_only_price(
+    ticker: &InstrumentTicker,
+    direction: Direction,
+    limit_price: &BigDecimal,
+) -> BigDecimal {
+    let price = match direction {
+        Direction::Buy if !ticker.best_ask_price.is_zero() => {
+            limit_price.clone().min(&ticker.best_ask_price - &ticker.tick_size)
+        }
+        Direction::Sell if !ticker.best_bid_price.is_zero() => {
+            limit_price.clone().max(&ticker.best_bid_price + &ticker.tick_size)
+        }
+        _ => limit_price.clone(),
+    };
+    price.max(ticker.min_price.clone()).min(ticker.max_price.clone())
+}
+
+pub async fn new_order_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
     ticker: &InstrumentTicker,
     subaccount_id: i64,
     args: OrderArgs,
 ) -> Result<OrderParams> {
+    let args = args.with_price_protection(ticker)?;
     let trade_data = TradeData::new(
         ticker,
         subaccount_id,
@@ -78,17 +194,19 @@ pub fn new_order_params(
         args.amount.clone(),
         args.direction.is_bid(),
     )?;
-    let order_action = ActionData::new(trade_data, subaccount_id, signer.address())?;
-    order_action.to_order_params(signer, ticker, args)
+    let order_action = ActionData::new(trade_data, subaccount_id, signer.address(), config)?;
+    order_action.to_order_params(signer, config, ticker, args).await
 }
 
-pub fn new_replace_params(
-    signer: &LocalWallet,
+pub async fn new_replace_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
     ticker: &InstrumentTicker,
     subaccount_id: i64,
     order_id_to_cancel: Uuid,
     args: OrderArgs,
 ) -> Result<ReplaceParams> {
+    let args = args.with_price_protection(ticker)?;
     let trade_data = TradeData::new(
         ticker,
         subaccount_id,
@@ -96,14 +214,15 @@ pub fn new_replace_params(
         args.amount.clone(),
         args.direction.is_bid(),
     )?;
-    let order_action = ActionData::new(trade_data, subaccount_id, signer.address())?;
-    order_action.to_replace_params(signer, ticker, order_id_to_cancel, args)
+    let order_action = ActionData::new(trade_data, subaccount_id, signer.address(), config)?;
+    order_action.to_replace_params(signer, config, ticker, order_id_to_cancel, args).await
 }
 
 impl ActionData {
-    pub fn to_order_params(
+    pub async fn to_order_params(
         self,
-        signer: &LocalWallet,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
         ticker: &InstrumentTicker,
         args: OrderArgs,
     ) -> Result<OrderParams> {
@@ -126,13 +245,14 @@ impl ActionData {
             reduce_only: false,
             replaced_order_id: None,
             referral_code: "".to_string(),
-            signature: signer.sign_hash(self.hash().into())?.to_string(),
+            signature: signer.sign_action(&self, config).await?.to_string(),
         })
     }
 
-    pub fn to_replace_params(
+    pub async fn to_replace_params(
         self,
-        signer: &LocalWallet,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
         ticker: &InstrumentTicker,
         order_id_to_cancel: Uuid,
         args: OrderArgs,
@@ -156,7 +276,7 @@ impl ActionData {
             reduce_only: false,
             replaced_order_id: None,
             referral_code: "".to_string(),
-            signature: signer.sign_hash(self.hash().into())?.to_string(),
+            signature: signer.sign_action(&self, config).await?.to_string(),
             expected_filled_amount: None,
             nonce_to_cancel: None,
             order_id_to_cancel: Some(order_id_to_cancel),
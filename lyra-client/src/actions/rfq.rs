@@ -1,15 +1,16 @@
 use crate::actions::helpers::ModuleData;
 use crate::actions::{ActionData, OrderArgs};
+use crate::config::LyraConfig;
+use crate::error::{LyraError, Result};
+use crate::signer::SignerProvider;
 use crate::utils::{decimal_to_i256, decimal_to_u256, decimal_to_u256_with_prec, u256_to_decimal};
-use anyhow::Result;
 use bigdecimal::{BigDecimal, Zero};
 use ethers::abi;
 use ethers::abi::{AbiDecode, AbiEncode, Tokenizable, Tokenize};
 use ethers::prelude::{
-    abigen, Address, EthAbiCodec, EthAbiType, EthEvent, LocalWallet, Signature, Signer, I256, U256,
+    abigen, Address, EthAbiCodec, EthAbiType, EthEvent, Signature, Signer, I256, U256,
 };
 use ethers::utils::hex;
-use log::{debug, info};
 pub use orderbook_types::types::orders::{
     Direction, LiquidityRole, OrderParams, OrderResponse, OrderStatus, OrderType, ReplaceParams,
     TimeInForce,
@@ -21,6 +22,7 @@ pub use orderbook_types::types::rfqs::{
 use orderbook_types::types::tickers::InstrumentTicker;
 use serde::Deserialize;
 use std::collections::HashMap;
+use tracing::{debug, info};
 use uuid::Uuid;
 
 fn get_rfq_max_fee(
@@ -52,7 +54,7 @@ impl LegData {
     ) -> Result<Self> {
         let sign = leg.direction.sign() * maker_direction.sign();
         Ok(Self {
-            asset_address: ticker.base_asset_address.parse()?,
+            asset_address: ticker.base_asset_address.parse().map_err(LyraError::deserialization)?,
             sub_id: ticker.base_asset_sub_id.parse::<u128>()?.into(),
             price: decimal_to_u256(leg.price.clone())?,
             amount: decimal_to_i256(leg.amount.clone() * sign)?,
@@ -118,16 +120,14 @@ pub struct ExecuteData {
 }
 
 impl ModuleData for QuoteData {
-    fn address(&self) -> Address {
-        let addr = std::env::var("RFQ_ADDRESS").expect("RFQ_ADDRESS must be set");
-        addr.parse().expect("RFQ_ADDRESS must be a valid module address")
+    fn address(&self, config: &LyraConfig) -> Address {
+        config.rfq_address
     }
 }
 
 impl ModuleData for ExecuteData {
-    fn address(&self) -> Address {
-        let addr = std::env::var("RFQ_ADDRESS").expect("RFQ_ADDRESS must be set");
-        addr.parse().expect("RFQ_ADDRESS must be a valid module address")
+    fn address(&self, config: &LyraConfig) -> Address {
+        config.rfq_address
     }
 }
 
@@ -138,33 +138,36 @@ pub struct QuoteArgs {
     pub legs: Vec<LegPriced>,
 }
 
-pub fn new_quote_params(
-    signer: &LocalWallet,
+pub async fn new_quote_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
     tickers: &HashMap<String, InstrumentTicker>,
     subaccount_id: i64,
     args: QuoteArgs,
 ) -> Result<QuoteParams> {
     let quote_data = QuoteData::from_legs(&args.legs, args.direction, &tickers)?;
-    let quote_action = ActionData::new(quote_data, subaccount_id, signer.address())?;
-    quote_action.to_quote_params(signer, &tickers, args)
+    let quote_action = ActionData::new(quote_data, subaccount_id, signer.address(), config)?;
+    quote_action.to_quote_params(signer, config, &tickers, args).await
 }
 
-pub fn new_execute_params(
-    signer: &LocalWallet,
+pub async fn new_execute_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
     tickers: &HashMap<String, InstrumentTicker>,
     subaccount_id: i64,
     quote: &QuoteResultPublic,
 ) -> Result<ExecuteQuoteParams> {
     let quote_data = QuoteData::from_quote_result(&quote, &tickers)?;
     let execute_data = quote_data.into_execute();
-    let execute_action = ActionData::new(execute_data, subaccount_id, signer.address())?;
-    execute_action.to_execute_params(signer, &tickers, &quote)
+    let execute_action = ActionData::new(execute_data, subaccount_id, signer.address(), config)?;
+    execute_action.to_execute_params(signer, config, &tickers, &quote).await
 }
 
 impl ActionData {
-    pub fn to_quote_params(
+    pub async fn to_quote_params(
         self,
-        signer: &LocalWallet,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
         tickers: &HashMap<String, InstrumentTicker>,
         args: QuoteArgs,
     ) -> Result<QuoteParams> {
@@ -180,12 +183,13 @@ impl ActionData {
             signer: hex::encode_prefixed(self.signer),
             mmp: false,
             rfq_id: args.rfq_id,
-            signature: signer.sign_hash(self.hash().into())?.to_string(),
+            signature: signer.sign_action(&self, config).await?.to_string(),
         })
     }
-    pub fn to_execute_params(
+    pub async fn to_execute_params(
         self,
-        signer: &LocalWallet,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
         tickers: &HashMap<String, InstrumentTicker>,
         quote: &QuoteResultPublic,
     ) -> Result<ExecuteQuoteParams> {
@@ -201,7 +205,7 @@ impl ActionData {
             rfq_id: quote.rfq_id,
             signature_expiry_sec: self.expiry.as_u64() as i64,
             signer: hex::encode_prefixed(self.signer),
-            signature: signer.sign_hash(self.hash().into())?.to_string(),
+            signature: signer.sign_action(&self, config).await?.to_string(),
         })
     }
 }
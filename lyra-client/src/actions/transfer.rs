@@ -0,0 +1,111 @@
+use crate::actions::helpers::{CollateralAsset, ModuleData};
+use crate::actions::ActionData;
+use crate::config::LyraConfig;
+use crate::error::Result;
+use crate::signer::SignerProvider;
+use crate::utils::decimal_to_i256;
+use bigdecimal::BigDecimal;
+use ethers::abi;
+use ethers::abi::{AbiEncode, Tokenize};
+use ethers::prelude::{Address, EthAbiType, Signer, I256, U256};
+use ethers::utils::hex;
+use orderbook_types::generated::private_transfer_erc20::{
+    PrivateTransferErc20ParamsSchema, SignatureDetailsSchema, TransferDetailsSchema,
+};
+
+pub type TransferParams = PrivateTransferErc20ParamsSchema;
+
+#[derive(Clone, Debug, Default, PartialEq, EthAbiType)]
+pub struct TransferAsset {
+    asset: Address,
+    sub_id: U256,
+    amount: I256, // 18 decimals, as the asset balances of a subaccount
+}
+
+/// Sender side of a `TransferModule` action, moves the assets to `to_subaccount_id`
+#[derive(Clone, Debug, Default, PartialEq, EthAbiType)]
+pub struct TransferData {
+    to_subaccount_id: U256,
+    manager_for_new_account: Address, // only used when transferring to a new subaccount (id 0)
+    transfers: Vec<TransferAsset>,
+}
+
+/// NOTE - encoded as a tuple like `QuoteData`, the derive gets the dynamic array wrong
+impl AbiEncode for TransferData {
+    fn encode(self) -> Vec<u8> {
+        let tokens = { abi::Token::Tuple(self.into_tokens()) };
+        abi::encode(&[tokens])
+    }
+}
+
+impl ModuleData for TransferData {
+    fn address(&self, config: &LyraConfig) -> Address {
+        config.transfer_address
+    }
+}
+
+/// Recipient side of a transfer, the recipient signs empty module data
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecipientTransferData;
+
+impl AbiEncode for RecipientTransferData {
+    fn encode(self) -> Vec<u8> {
+        vec![]
+    }
+}
+
+impl ModuleData for RecipientTransferData {
+    fn address(&self, config: &LyraConfig) -> Address {
+        config.transfer_address
+    }
+}
+
+impl ActionData {
+    pub async fn to_signature_details(
+        self,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
+    ) -> Result<SignatureDetailsSchema> {
+        Ok(SignatureDetailsSchema {
+            nonce: self.nonce.as_u64() as i64,
+            signature_expiry_sec: self.expiry.as_u64() as i64,
+            signer: hex::encode_prefixed(self.signer),
+            signature: signer.sign_action(&self, config).await?.to_string(),
+        })
+    }
+}
+
+/// Collateral transfer between two subaccounts of the same owner, signed for both sides
+pub async fn new_transfer_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
+    subaccount_id: i64,
+    recipient_subaccount_id: i64,
+    amount: BigDecimal,
+    asset: &CollateralAsset,
+) -> Result<TransferParams> {
+    let transfer = TransferAsset {
+        asset: asset.address,
+        sub_id: U256::zero(),
+        amount: decimal_to_i256(amount.clone())?,
+    };
+    let sender_data = TransferData {
+        to_subaccount_id: recipient_subaccount_id.into(),
+        manager_for_new_account: Address::zero(),
+        transfers: vec![transfer],
+    };
+    let sender_action = ActionData::new(sender_data, subaccount_id, signer.address(), config)?;
+    let recipient_action =
+        ActionData::new(RecipientTransferData, recipient_subaccount_id, signer.address(), config)?;
+    Ok(TransferParams {
+        subaccount_id,
+        recipient_subaccount_id,
+        sender_details: sender_action.to_signature_details(signer, config).await?,
+        recipient_details: recipient_action.to_signature_details(signer, config).await?,
+        transfer: TransferDetailsSchema {
+            address: hex::encode_prefixed(asset.address),
+            amount,
+            sub_id: 0,
+        },
+    })
+}
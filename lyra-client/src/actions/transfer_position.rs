@@ -0,0 +1,83 @@
+use crate::actions::{ActionData, TradeData};
+use crate::config::LyraConfig;
+use crate::error::Result;
+use crate::signer::SignerProvider;
+use bigdecimal::{BigDecimal, Signed, Zero};
+use ethers::prelude::Signer;
+use ethers::utils::hex;
+use orderbook_types::generated::private_transfer_position::{
+    Direction, PrivateTransferPositionParamsSchema, TradeModuleParamsSchema,
+};
+use orderbook_types::types::tickers::InstrumentTicker;
+
+pub type TransferPositionParams = PrivateTransferPositionParamsSchema;
+
+impl ActionData {
+    pub async fn to_trade_module_params(
+        self,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
+        ticker: &InstrumentTicker,
+        amount: BigDecimal,
+        limit_price: BigDecimal,
+        direction: Direction,
+    ) -> Result<TradeModuleParamsSchema> {
+        Ok(TradeModuleParamsSchema {
+            amount,
+            direction,
+            instrument_name: ticker.instrument_name.clone(),
+            limit_price,
+            max_fee: ticker.get_max_fee(),
+            nonce: self.nonce.as_u64() as i64,
+            signature_expiry_sec: self.expiry.as_u64() as i64,
+            signer: hex::encode_prefixed(self.signer),
+            subaccount_id: self.subaccount_id.as_u64() as i64,
+            signature: signer.sign_action(&self, config).await?.to_string(),
+        })
+    }
+}
+
+/// Signs both sides of moving `amount` of a position (negative for shorts) from `subaccount_id`
+/// (the maker) to `recipient_subaccount_id` (the taker) at `limit_price`, e.g. the mark price
+pub async fn new_transfer_position_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
+    ticker: &InstrumentTicker,
+    subaccount_id: i64,
+    recipient_subaccount_id: i64,
+    amount: BigDecimal,
+    limit_price: BigDecimal,
+) -> Result<TransferPositionParams> {
+    let is_long = amount > BigDecimal::zero();
+    let amount = amount.abs();
+    let (maker_direction, taker_direction) = match is_long {
+        true => (Direction::Sell, Direction::Buy),
+        false => (Direction::Buy, Direction::Sell),
+    };
+    let price = limit_price.clone();
+    let maker_data =
+        TradeData::new(ticker, subaccount_id, price.clone(), amount.clone(), !is_long)?;
+    let taker_data =
+        TradeData::new(ticker, recipient_subaccount_id, price, amount.clone(), is_long)?;
+    let maker_action = ActionData::new(maker_data, subaccount_id, signer.address(), config)?;
+    let taker_action =
+        ActionData::new(taker_data, recipient_subaccount_id, signer.address(), config)?;
+    let maker_params = maker_action
+        .to_trade_module_params(
+            signer,
+            config,
+            ticker,
+            amount.clone(),
+            limit_price.clone(),
+            maker_direction,
+        )
+        .await?;
+    let taker_params = taker_action
+        .to_trade_module_params(signer, config, ticker, amount, limit_price, taker_direction)
+        .await?;
+    Ok(TransferPositionParams {
+        maker_params,
+        taker_params,
+        wallet: hex::encode_prefixed(config.owner()?),
+    })
+}
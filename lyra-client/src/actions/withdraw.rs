@@ -1,11 +1,9 @@
 use crate::utils::{decimal_to_i256, decimal_to_u256, decimal_to_u256_with_prec};
 
-use anyhow::Result;
+use crate::error::Result;
 use bigdecimal::BigDecimal;
 use ethers::abi::{AbiDecode, AbiEncode};
-use ethers::prelude::{
-    Address, EthAbiCodec, EthAbiType, LocalWallet, Signature, Signer, I256, U256,
-};
+use ethers::prelude::{Address, EthAbiCodec, EthAbiType, Signature, Signer, I256, U256};
 use ethers::utils::hex;
 pub use orderbook_types::types::orders::{
     Direction, LiquidityRole, OrderParams, OrderResponse, OrderStatus, OrderType, ReplaceParams,
@@ -13,8 +11,10 @@ pub use orderbook_types::types::orders::{
 };
 use serde::Deserialize;
 
-use crate::actions::helpers::{get_asset_address, get_asset_decimals, ModuleData};
+use crate::actions::helpers::{CollateralAsset, ModuleData};
 use crate::actions::ActionData;
+use crate::config::LyraConfig;
+use crate::signer::SignerProvider;
 use orderbook_types::generated::private_withdraw::PrivateWithdrawParamsSchema;
 
 pub type WithdrawParams = PrivateWithdrawParamsSchema;
@@ -26,28 +26,25 @@ pub struct WithdrawalData {
 }
 
 impl WithdrawalData {
-    pub fn new(amount: &BigDecimal, asset_name: &String) -> Result<Self> {
-        let asset_address = get_asset_address(&asset_name);
-        let asset_decimals = get_asset_decimals(&asset_name);
-
+    pub fn new(amount: &BigDecimal, asset: &CollateralAsset) -> Result<Self> {
         Ok(WithdrawalData {
-            asset_address: asset_address.parse()?,
-            erc20_amount: decimal_to_u256_with_prec(amount.clone(), asset_decimals)?,
+            asset_address: asset.address,
+            erc20_amount: decimal_to_u256_with_prec(amount.clone(), asset.decimals)?,
         })
     }
 }
 
 impl ModuleData for WithdrawalData {
-    fn address(&self) -> Address {
-        let addr = std::env::var("WITHDRAWAL_ADDRESS").expect("WITHDRAWAL_ADDRESS must be set");
-        addr.parse().expect("WITHDRAWAL_ADDRESS must be a valid module address")
+    fn address(&self, config: &LyraConfig) -> Address {
+        config.withdrawal_address
     }
 }
 
 impl ActionData {
-    pub fn to_withdraw_params(
+    pub async fn to_withdraw_params(
         self,
-        signer: &LocalWallet,
+        signer: &impl SignerProvider,
+        config: &LyraConfig,
         amount: BigDecimal,
         asset_name: String,
     ) -> Result<WithdrawParams> {
@@ -58,19 +55,21 @@ impl ActionData {
             nonce: self.nonce.as_u64() as i64,
             signature_expiry_sec: self.expiry.as_u64() as i64,
             signer: hex::encode_prefixed(self.signer),
-            signature: signer.sign_hash(self.hash().into())?.to_string(),
+            signature: signer.sign_action(&self, config).await?.to_string(),
         })
     }
 }
 
-pub fn new_withdraw_params(
-    signer: &LocalWallet,
+pub async fn new_withdraw_params(
+    signer: &impl SignerProvider,
+    config: &LyraConfig,
     subaccount_id: i64,
     amount: BigDecimal,
-    asset_name: String,
+    asset: &CollateralAsset,
 ) -> Result<WithdrawParams> {
-    let withdrawal_data = WithdrawalData::new(&amount, &asset_name)?;
-    let action_data = ActionData::new(withdrawal_data, subaccount_id, signer.address())?;
-    let params = action_data.to_withdraw_params(signer, amount, asset_name)?;
+    let withdrawal_data = WithdrawalData::new(&amount, asset)?;
+    let action_data = ActionData::new(withdrawal_data, subaccount_id, signer.address(), config)?;
+    let asset_name = asset.asset_name.clone();
+    let params = action_data.to_withdraw_params(signer, config, amount, asset_name).await?;
     Ok(params)
 }
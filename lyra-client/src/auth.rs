@@ -1,44 +1,68 @@
+use crate::config::LyraConfig;
+use crate::error::Result;
 use crate::json_rpc::WsClient;
-use ethers::prelude::{LocalWallet, Signer};
+use crate::session::rotated_session_key;
+use crate::signer::{LyraSigner, SignerProvider};
+use ethers::prelude::Signer;
 use ethers::utils::hex;
-use log::info;
 use orderbook_types::generated::public_login::PublicLoginParamsSchema;
 use reqwest::header::HeaderMap;
 use serde_json::{json, Value};
+use tracing::info;
 
-pub async fn load_signer() -> LocalWallet {
-    load_signer_by_name("SESSION").await
+/// The session key of the current `LyraConfig`, falling back to the `SESSION` signer
+pub async fn load_signer() -> Result<LyraSigner> {
+    let config = LyraConfig::current()?;
+    session_signer(&config).await
 }
 
-pub async fn load_signer_by_name(name: &str) -> LocalWallet {
-    let env_name = format!("{name}_PRIVATE_KEY");
-    info!("Loading signer from env {}", &env_name);
-    let mut pk_str = std::env::var(env_name).unwrap();
-    let wallet = pk_str.parse::<LocalWallet>().unwrap();
-    wallet
+/// The key the session of `config` was rotated to, its configured key or the `SESSION` signer
+pub async fn session_signer(config: &LyraConfig) -> Result<LyraSigner> {
+    if let Some(rotated) = rotated_session_key(config) {
+        return Ok(rotated);
+    }
+    match &config.session_key {
+        Some(session_key) => Ok(session_key.clone()),
+        None => load_signer_by_name("SESSION").await,
+    }
 }
 
-async fn sign_auth_params(wallet: &LocalWallet) -> (String, String, String) {
+/// The signer selected by `{NAME}_SIGNER` (a local key by default), see `LyraSigner`
+pub async fn load_signer_by_name(name: &str) -> Result<LyraSigner> {
+    LyraSigner::load(name, LyraConfig::current()?.chain_id).await
+}
+
+async fn sign_auth_params(
+    wallet: &impl SignerProvider,
+    owner: &str,
+) -> Result<(String, String, String)> {
     let timestamp = chrono::Utc::now().timestamp_millis().to_string();
-    let signature = wallet.sign_message(&timestamp).await.unwrap();
-    (std::env::var("OWNER_PUBLIC_KEY").expect("OWNER_PUBLIC_KEY"), timestamp, signature.to_string())
+    let signature = wallet.sign_auth(&timestamp).await?;
+    Ok((owner.to_string(), timestamp, signature.to_string()))
+}
+
+fn current_owner() -> Result<String> {
+    Ok(hex::encode_prefixed(LyraConfig::current()?.owner()?))
 }
 
-pub async fn sign_auth_header(wallet: &LocalWallet) -> HeaderMap {
+pub async fn sign_auth_header(wallet: &impl SignerProvider) -> Result<HeaderMap> {
     let mut headers = HeaderMap::new();
-    let (address, timestamp, signature) = sign_auth_params(wallet).await;
+    let (address, timestamp, signature) = sign_auth_params(wallet, &current_owner()?).await?;
     headers.insert("X-LyraWallet", address.parse().unwrap());
     headers.insert("X-LyraTimestamp", timestamp.parse().unwrap());
     headers.insert("X-LyraSignature", signature.parse().unwrap());
-    headers
+    Ok(headers)
 }
 
-pub async fn get_auth_headers() -> HeaderMap {
-    let wallet = load_signer().await;
+pub async fn get_auth_headers() -> Result<HeaderMap> {
+    let wallet = load_signer().await?;
     sign_auth_header(&wallet).await
 }
 
-pub async fn sign_auth_msg(wallet: &LocalWallet) -> PublicLoginParamsSchema {
-    let (address, timestamp, signature) = sign_auth_params(wallet).await;
-    PublicLoginParamsSchema { wallet: address, timestamp, signature }
+pub async fn sign_auth_msg(
+    wallet: &impl SignerProvider,
+    owner: &str,
+) -> Result<PublicLoginParamsSchema> {
+    let (address, timestamp, signature) = sign_auth_params(wallet, owner).await?;
+    Ok(PublicLoginParamsSchema { wallet: address, timestamp, signature })
 }
@@ -2,10 +2,26 @@ use aws_secrets::{config_from_env, SSMParamExt};
 use aws_secrets::config::SdkConfig;
 use serde_json::{to_string, Value};
 use anyhow::Result;
-use log::info;
+use tracing::info;
 
 pub async fn get_secret(name: &str, config: Option<SdkConfig>) -> String {
     let aws_config = config.unwrap_or(config_from_env().await);
     let value = name.get_secure_string(&aws_config).await.expect("Secret not found");
     value
 }
+
+/// Writes (or overwrites) a secure string parameter, e.g. a rotated session key
+pub async fn put_secret(name: &str, value: &str, config: Option<SdkConfig>) -> Result<()> {
+    let aws_config = config.unwrap_or(config_from_env().await);
+    let client = aws_sdk_ssm::Client::new(&aws_config);
+    client
+        .put_parameter()
+        .name(name)
+        .value(value)
+        .r#type(aws_sdk_ssm::model::ParameterType::SecureString)
+        .overwrite(true)
+        .send()
+        .await?;
+    info!("Stored secret {}", name);
+    Ok(())
+}
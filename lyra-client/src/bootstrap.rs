@@ -0,0 +1,168 @@
+use crate::auth::load_signer_by_name;
+use crate::config::LyraConfig;
+use crate::json_rpc::{http_rpc, WsClient, WsClientExt};
+use crate::output::{print_result, OutputFormat};
+use crate::session::{ApiRegistrar, SessionKeyRotation, SessionKeyStore};
+use crate::subaccounts::create_subaccount_and_wait;
+use anyhow::{format_err, Result};
+use bigdecimal::BigDecimal;
+use clap::Args;
+use ethers::prelude::{Address, Signer};
+use ethers::utils::hex;
+use orderbook_types::generated::private_get_subaccount::MarginType;
+use orderbook_types::generated::public_create_account::{
+    PublicCreateAccountParamsSchema, PublicCreateAccountResponseSchema,
+};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+/// Sets up a staging owner for a vault executor: creates the exchange account of the OWNER
+/// signer, registers a new session key, requests testnet funds (with --faucet-url or
+/// FAUCET_URL) and creates a subaccount funded with the initial collateral from the wallet.
+/// Prints the env values to launch the executor with.
+#[derive(Args, Debug)]
+pub struct CliBootstrapTestnet {
+    /// Collateral of the new subaccount, e.g. USDC
+    #[arg(short, long)]
+    pub asset: String,
+
+    /// Initial deposit into the new subaccount
+    #[arg(long)]
+    pub amount: BigDecimal,
+
+    /// PM or SM
+    #[arg(short, long)]
+    pub margin_type: MarginType,
+
+    /// Lifetime of the session key
+    #[arg(long, default_value_t = 30 * 24 * 3600)]
+    pub expiry_sec: i64,
+
+    #[arg(long, default_value = "cockpit")]
+    pub label: String,
+
+    /// File to write the session private key to instead of the SESSION_KEY_NAME AWS parameter
+    #[arg(long)]
+    pub key_file: Option<PathBuf>,
+
+    /// Faucet to request testnet funds for the owner wallet from, FAUCET_URL by default.
+    /// Without one the wallet has to hold the initial collateral already.
+    #[arg(long)]
+    pub faucet_url: Option<String>,
+
+    /// Time for the faucet funds to arrive before creating the subaccount
+    #[arg(long, default_value_t = 30)]
+    pub faucet_wait_sec: u64,
+
+    /// Give up if the session key is not active by then
+    #[arg(long, default_value_t = 300)]
+    pub activation_timeout_sec: u64,
+}
+
+impl CliBootstrapTestnet {
+    pub async fn execute(&self, output: OutputFormat) -> Result<()> {
+        let config = LyraConfig::current()?;
+        if config.env != "staging" {
+            return Err(format_err!("bootstrap-testnet only runs with ENV=staging"));
+        }
+        let owner_signer = load_signer_by_name("OWNER").await?;
+        let owner = owner_signer.address();
+        let config = Arc::new((*config).clone().with_owner(owner));
+        LyraConfig::scope(config.clone(), async {
+            create_account(owner).await?;
+            self.request_funds(owner).await?;
+
+            let store = self.session_key_store()?;
+            let rotation = SessionKeyRotation {
+                lifetime_sec: self.expiry_sec,
+                label: self.label.clone(),
+                activation_timeout: Duration::from_secs(self.activation_timeout_sec),
+                store: store.clone(),
+            };
+            let registrar = ApiRegistrar::new(owner_signer, config.clone())?;
+            let session_key = rotation.register_new(&registrar).await?;
+
+            // the new key signs the deposit of the subaccount
+            let config = Arc::new((*config).clone().with_session_key(session_key.clone()));
+            let subaccount_id = LyraConfig::scope(config, async {
+                let client = WsClient::new_client().await?;
+                client.login().await?.into_result()?;
+                let amount = self.amount.clone();
+                let subaccount_id = create_subaccount_and_wait(
+                    &client,
+                    amount,
+                    self.asset.clone(),
+                    self.margin_type,
+                )
+                .await?;
+                anyhow::Ok(subaccount_id)
+            })
+            .await?;
+
+            let mut env = json!({
+                "ENV": "staging",
+                "OWNER_PUBLIC_KEY": hex::encode_prefixed(owner),
+                "SUBACCOUNT_ID": subaccount_id,
+            });
+            match store {
+                SessionKeyStore::Aws(name) => env["SESSION_KEY_NAME"] = Value::from(name),
+                SessionKeyStore::File(path) => {
+                    env["SESSION_PRIVATE_KEY"] = Value::from(format!("$(cat {})", path.display()))
+                }
+            }
+            let res = json!({
+                "session_public_key": hex::encode_prefixed(session_key.address()),
+                "env": env,
+            });
+            print_result("bootstrap-testnet", &res, output)
+        })
+        .await
+    }
+
+    fn session_key_store(&self) -> Result<SessionKeyStore> {
+        match &self.key_file {
+            Some(path) => Ok(SessionKeyStore::File(path.clone())),
+            None => Ok(SessionKeyStore::Aws(std::env::var("SESSION_KEY_NAME").map_err(|_| {
+                format_err!(
+                    "SESSION_KEY_NAME must be set to store the key in AWS, or use --key-file"
+                )
+            })?)),
+        }
+    }
+
+    /// Asks the faucet for testnet funds, skipped without a faucet
+    async fn request_funds(&self, owner: Address) -> Result<()> {
+        let faucet_url = self.faucet_url.clone().or(std::env::var("FAUCET_URL").ok());
+        let Some(faucet_url) = faucet_url else {
+            warn!("No faucet configured, the owner wallet has to hold the collateral already");
+            return Ok(());
+        };
+        let body = json!({ "wallet": hex::encode_prefixed(owner), "asset": self.asset });
+        let res = reqwest::Client::new().post(&faucet_url).json(&body).send().await?;
+        let status = res.status();
+        let text = res.text().await?;
+        if !status.is_success() {
+            return Err(format_err!("Faucet {} replied {}: {}", faucet_url, status, text));
+        }
+        info!("Faucet replied {}, waiting {}s for the funds", text, self.faucet_wait_sec);
+        tokio::time::sleep(Duration::from_secs(self.faucet_wait_sec)).await;
+        Ok(())
+    }
+}
+
+/// Creates the exchange account of the wallet, an existing account is kept
+async fn create_account(wallet: Address) -> Result<()> {
+    let params = PublicCreateAccountParamsSchema { wallet: hex::encode_prefixed(wallet) };
+    let res =
+        http_rpc::<_, PublicCreateAccountResponseSchema>("public/create_account", params, None)
+            .await?
+            .into_result();
+    match res {
+        Ok(_) => info!("Created account of {:?}", wallet),
+        Err(e) => warn!("Account of {:?} not created, assuming it exists: {}", wallet, e),
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use crate::error::{LyraError, Result};
+use anyhow::format_err;
+use std::fmt;
+use std::str::FromStr;
+
+/// Subscription channel of the websocket API, rendered to (and parsed from) the channel names
+/// the `subscribe` methods take, e.g. `Channel::orderbook("ETH-PERP", 10)` is
+/// `orderbook.ETH-PERP.1.10`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// `orderbook.{instrument}.{group}.{depth}`, depth is 1, 10, 20 or 100
+    Orderbook { instrument: String, group: u32, depth: u32 },
+    /// `ticker.{instrument}.{interval_ms}`, interval is 100 or 1000
+    Ticker { instrument: String, interval_ms: u32 },
+    /// `trades.{instrument}`, the public trades of the instrument
+    Trades { instrument: String },
+    /// `spot_feed.{currency}`
+    SpotFeed { currency: String },
+    /// `{subaccount_id}.orders`
+    Orders { subaccount_id: i64 },
+    /// `{subaccount_id}.balances`
+    Balances { subaccount_id: i64 },
+    /// `{subaccount_id}.trades` or `{subaccount_id}.trades.{tx_status}` (e.g. settled, reverted)
+    SubaccountTrades { subaccount_id: i64, tx_status: Option<String> },
+    /// `{subaccount_id}.quotes`
+    Quotes { subaccount_id: i64 },
+    /// `{wallet}.rfqs`, the RFQs sent to the (hex) wallet
+    Rfqs { wallet: String },
+}
+
+impl Channel {
+    /// Orderbook of the instrument without grouping
+    pub fn orderbook(instrument: &str, depth: u32) -> Self {
+        Channel::Orderbook { instrument: instrument.to_string(), group: 1, depth }
+    }
+
+    pub fn ticker(instrument: &str, interval_ms: u32) -> Self {
+        Channel::Ticker { instrument: instrument.to_string(), interval_ms }
+    }
+
+    pub fn trades(instrument: &str) -> Self {
+        Channel::Trades { instrument: instrument.to_string() }
+    }
+
+    pub fn orders(subaccount_id: i64) -> Self {
+        Channel::Orders { subaccount_id }
+    }
+
+    pub fn balances(subaccount_id: i64) -> Self {
+        Channel::Balances { subaccount_id }
+    }
+
+    pub fn subaccount_trades(subaccount_id: i64, tx_status: Option<&str>) -> Self {
+        Channel::SubaccountTrades { subaccount_id, tx_status: tx_status.map(str::to_string) }
+    }
+
+    pub fn rfqs(wallet: &str) -> Self {
+        Channel::Rfqs { wallet: wallet.to_string() }
+    }
+
+    /// Channel names of the channels, as taken by the `subscribe` methods
+    pub fn names<'a>(channels: impl IntoIterator<Item = &'a Channel>) -> Vec<String> {
+        channels.into_iter().map(Channel::to_string).collect()
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channel::Orderbook { instrument, group, depth } => {
+                write!(f, "orderbook.{}.{}.{}", instrument, group, depth)
+            }
+            Channel::Ticker { instrument, interval_ms } => {
+                write!(f, "ticker.{}.{}", instrument, interval_ms)
+            }
+            Channel::Trades { instrument } => write!(f, "trades.{}", instrument),
+            Channel::SpotFeed { currency } => write!(f, "spot_feed.{}", currency),
+            Channel::Orders { subaccount_id } => write!(f, "{}.orders", subaccount_id),
+            Channel::Balances { subaccount_id } => write!(f, "{}.balances", subaccount_id),
+            Channel::SubaccountTrades { subaccount_id, tx_status: None } => {
+                write!(f, "{}.trades", subaccount_id)
+            }
+            Channel::SubaccountTrades { subaccount_id, tx_status: Some(status) } => {
+                write!(f, "{}.trades.{}", subaccount_id, status)
+            }
+            Channel::Quotes { subaccount_id } => write!(f, "{}.quotes", subaccount_id),
+            Channel::Rfqs { wallet } => write!(f, "{}.rfqs", wallet),
+        }
+    }
+}
+
+impl FromStr for Channel {
+    type Err = LyraError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        let invalid = || LyraError::Other(format_err!("Unknown channel {:?}", name));
+        let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+        let parts = name.split('.').collect::<Vec<_>>();
+        let channel = match parts.as_slice() {
+            ["orderbook", instrument, group, depth] => Channel::Orderbook {
+                instrument: instrument.to_string(),
+                group: number(group)?,
+                depth: number(depth)?,
+            },
+            ["ticker", instrument, interval] => Channel::Ticker {
+                instrument: instrument.to_string(),
+                interval_ms: number(interval)?,
+            },
+            ["trades", instrument] => Channel::Trades { instrument: instrument.to_string() },
+            ["spot_feed", currency] => Channel::SpotFeed { currency: currency.to_string() },
+            [wallet, "rfqs"] => Channel::Rfqs { wallet: wallet.to_string() },
+            [subaccount_id, rest @ ..] => {
+                let subaccount_id = subaccount_id.parse::<i64>().map_err(|_| invalid())?;
+                match rest {
+                    ["orders"] => Channel::Orders { subaccount_id },
+                    ["balances"] => Channel::Balances { subaccount_id },
+                    ["trades"] => Channel::SubaccountTrades { subaccount_id, tx_status: None },
+                    ["trades", status] => Channel::SubaccountTrades {
+                        subaccount_id,
+                        tx_status: Some(status.to_string()),
+                    },
+                    ["quotes"] => Channel::Quotes { subaccount_id },
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(channel)
+    }
+}
+
+impl From<Channel> for String {
+    fn from(channel: Channel) -> Self {
+        channel.to_string()
+    }
+}
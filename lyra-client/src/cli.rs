@@ -1,11 +1,14 @@
 use crate::actions::QuoteArgs;
 use crate::actions::{new_quote_params, OrderArgs};
-use crate::json_rpc::{http_rpc, Notification, WsClient, WsClientExt};
+use crate::channels::Channel;
+use crate::json_rpc::{Notification, WsClient, WsClientExt};
 use anyhow::{format_err, Result};
 use bigdecimal::RoundingMode::Down;
 use bigdecimal::{BigDecimal, One, Zero};
 use clap::{Args, Parser, Subcommand};
 use comfy_table::Table;
+use ethers::prelude::Signer;
+use ethers::utils::hex;
 use futures::{future::FutureExt, StreamExt};
 
 use crossterm::cursor::MoveTo;
@@ -18,26 +21,48 @@ use crossterm::{
     execute,
 };
 
+use crate::auth::{load_signer, load_signer_by_name};
+use crate::bootstrap::CliBootstrapTestnet;
+use crate::config::LyraConfig;
+use crate::decode::CliDecodeOrder;
+use crate::history::CliHistory;
+use crate::liquidations::{bid_and_wait, get_liquidatable_subaccounts};
+use crate::market_history::CliMarketHistory;
+use crate::output::{print_error, print_result, OutputFormat};
+use crate::presets::{merge_params, CliPreset, Preset};
+use crate::repl::CliRepl;
+use crate::rfq_maker::{cached_tickers, RfqMaker, RfqMakerParams};
+use crate::session::{ApiRegistrar, SessionKeyRotation, SessionKeyStore};
+use crate::subaccounts::{
+    change_margin_type, create_subaccount_and_wait, transfer_and_wait, transfer_position,
+};
+use crate::tickers::TickerCache;
 use crate::utils::await_tx_settlement;
 use crossterm::event::KeyEvent;
-use log::{error, info, warn};
 use orderbook_types::generated::channel_orderbook_instrument_name_group_depth::OrderbookInstrumentNameGroupDepthPublisherDataSchema;
+use orderbook_types::generated::private_cancel::PrivateCancelParamsSchema;
+use orderbook_types::generated::private_cancel_all::PrivateCancelAllParamsSchema;
+use orderbook_types::generated::private_cancel_by_label::PrivateCancelByLabelParamsSchema;
 use orderbook_types::generated::private_get_subaccount::{
-    PrivateGetSubaccount, PrivateGetSubaccountParamsSchema, PrivateGetSubaccountResponseSchema,
+    MarginType, PrivateGetSubaccount, PrivateGetSubaccountParamsSchema,
+    PrivateGetSubaccountResponseSchema,
 };
+use orderbook_types::generated::private_set_mmp_config::PrivateSetMmpConfigParamsSchema;
 use orderbook_types::generated::public_login::PublicLoginResponseSchema;
 use orderbook_types::types::liquidations::{
     AuctionState, AuctionsWatchData, AuctionsWatchResultSchema,
 };
+use orderbook_types::types::orders::{Direction, TradeNotificationData};
 use orderbook_types::types::rfqs::{PollQuotesResponse, PollQuotesResult, QuoteResultPublic};
-use orderbook_types::types::tickers::{InstrumentTicker, TickerResponse};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::io::stdout;
 use std::io::{stdin, BufRead, BufReader};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
 
 pub type OrderbookData = OrderbookInstrumentNameGroupDepthPublisherDataSchema;
 
@@ -47,24 +72,49 @@ pub type OrderbookData = OrderbookInstrumentNameGroupDepthPublisherDataSchema;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+
+    /// How results are written to stdout
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Pretty)]
+    pub output: OutputFormat,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Command {
     Rpc(CliRpc),
     Sub(CliSub),
+    SubFills(CliSubFills),
     Auctions(CliAuctions),
     Orderbook(CliOrderbook),
+    History(CliHistory),
+    MarketHistory(CliMarketHistory),
+    RotateSessionKey(CliRotateSessionKey),
+    Subaccount(CliSubaccount),
+    Mmp(CliMmp),
+    DecodeOrder(CliDecodeOrder),
+    Liquidate(CliLiquidate),
+    BootstrapTestnet(CliBootstrapTestnet),
+    Repl(CliRepl),
+    Preset(CliPreset),
+    RfqMaker(CliRfqMaker),
 }
 
 #[derive(Args, Debug)]
 pub struct CliRpc {
-    /// The RPC method to call
+    /// The RPC method to call, the method of the preset by default
     #[arg(short, long)]
-    pub method: String,
+    pub method: Option<String>,
 
     #[clap(flatten)]
     pub params: ParamsOrInline,
+
+    /// Skip the confirmation prompt for private/cancel_all
+    #[arg(long)]
+    pub confirm: bool,
+
+    /// Keep cancel on disconnect enabled for the wallet, so the orders of private calls are
+    /// cancelled once the CLI exits (disabled by default)
+    #[arg(long)]
+    pub cancel_on_disconnect: bool,
 }
 
 #[derive(Args, Debug)]
@@ -73,7 +123,7 @@ pub struct CliSub {
 }
 
 impl CliSub {
-    pub async fn subscribe(&self) -> Result<()> {
+    pub async fn subscribe(&self, output: OutputFormat) -> Result<()> {
         info!("Starting market task");
         let channels = serde_json::from_str::<Vec<String>>(&self.channels)?;
         let client = WsClient::new_client().await?;
@@ -83,7 +133,72 @@ impl CliSub {
         }
         client
             .subscribe(channels, |d: Notification<Value>| async move {
-                info!("{}", serde_json::to_string_pretty(&d)?);
+                print_result("subscription", &serde_json::to_value(d)?, output)
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// Running log of fills for a subaccount, e.g. to monitor vault auctions live
+#[derive(Args, Debug)]
+pub struct CliSubFills {
+    #[arg(short, long)]
+    pub subaccount: i64,
+}
+
+/// Cumulative signed amount and fees per instrument since the command started
+#[derive(Default, Debug)]
+struct FillTotals {
+    filled_amount: BigDecimal,
+    fees: BigDecimal,
+}
+
+impl CliSubFills {
+    pub async fn subscribe(&self, output: OutputFormat) -> Result<()> {
+        info!("Starting fills task");
+        let channels = vec![Channel::subaccount_trades(self.subaccount, None).to_string()];
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        let totals = Arc::new(Mutex::new(HashMap::<String, FillTotals>::new()));
+        client
+            .subscribe(channels, |d: Notification<TradeNotificationData>| async {
+                let mut totals = totals.lock().await;
+                for trade in d.params.data {
+                    let total = totals.entry(trade.instrument_name.clone()).or_default();
+                    let signed_amount = match trade.direction {
+                        Direction::Buy => trade.trade_amount.clone(),
+                        Direction::Sell => -trade.trade_amount.clone(),
+                    };
+                    total.filled_amount += signed_amount;
+                    total.fees += trade.trade_fee.clone();
+                    let fill = json!({
+                        "timestamp": trade.timestamp,
+                        "instrument_name": trade.instrument_name,
+                        "direction": trade.direction.to_string(),
+                        "trade_amount": trade.trade_amount,
+                        "trade_price": trade.trade_price,
+                        "trade_fee": trade.trade_fee,
+                        "realized_pnl": trade.realized_pnl,
+                        "liquidity_role": trade.liquidity_role,
+                        "cumulative_filled_amount": total.filled_amount,
+                        "cumulative_fees": total.fees,
+                    });
+                    match output {
+                        OutputFormat::Json => println!("{}", serde_json::to_string(&fill)?),
+                        _ => println!(
+                            "{} {} {} @ {} fee {} pnl {} | cumulative {} fees {}",
+                            trade.instrument_name,
+                            trade.direction,
+                            trade.trade_amount,
+                            trade.trade_price,
+                            trade.trade_fee,
+                            trade.realized_pnl,
+                            total.filled_amount,
+                            total.fees,
+                        ),
+                    }
+                }
                 Ok(())
             })
             .await?;
@@ -91,6 +206,29 @@ impl CliSub {
     }
 }
 
+/// Quotes the RFQs sent to the wallet from the subaccount until stopped
+#[derive(Args, Debug)]
+pub struct CliRfqMaker {
+    #[arg(short, long)]
+    pub subaccount_id: i64,
+
+    /// Spread model as JSON, e.g. '{"spread_bps": 30, "min_spread": 0.5, "quote_ttl_ms": 1500}'
+    #[arg(short, long, default_value = "{}")]
+    pub params: String,
+
+    /// Log the quotes instead of sending them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl CliRfqMaker {
+    pub async fn run(&self) -> Result<()> {
+        let params = serde_json::from_str::<RfqMakerParams>(&self.params)?;
+        let maker = RfqMaker::new(self.subaccount_id, params, cached_tickers(), self.dry_run);
+        Ok(maker.run().await?)
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct CliAuctions {
     #[arg(short, long)]
@@ -352,6 +490,285 @@ impl CliAuctions {
     }
 }
 
+/// Replaces the session key with a new one registered by the OWNER signer, which has to be the
+/// owner wallet. The new key is stored in the SESSION_KEY_NAME AWS parameter (or --key-file)
+/// before the current key is revoked.
+#[derive(Args, Debug)]
+pub struct CliRotateSessionKey {
+    /// Lifetime of the new key
+    #[arg(long, default_value_t = 30 * 24 * 3600)]
+    pub expiry_sec: i64,
+
+    #[arg(long, default_value = "cockpit")]
+    pub label: String,
+
+    /// File to write the new private key to instead of AWS
+    #[arg(long)]
+    pub key_file: Option<PathBuf>,
+
+    /// Give up (keeping the current key) if the new key is not active by then
+    #[arg(long, default_value_t = 300)]
+    pub activation_timeout_sec: u64,
+}
+
+impl CliRotateSessionKey {
+    pub async fn rotate(&self, output: OutputFormat) -> Result<()> {
+        let config = LyraConfig::current()?;
+        let owner_signer = load_signer_by_name("OWNER").await?;
+        if owner_signer.address() != config.owner()? {
+            return Err(format_err!("OWNER signer is not the owner wallet {:?}", config.owner()?));
+        }
+        let store = match &self.key_file {
+            Some(path) => SessionKeyStore::File(path.clone()),
+            None => SessionKeyStore::Aws(std::env::var("SESSION_KEY_NAME").map_err(|_| {
+                format_err!(
+                    "SESSION_KEY_NAME must be set to store the key in AWS, or use --key-file"
+                )
+            })?),
+        };
+        let rotation = SessionKeyRotation {
+            lifetime_sec: self.expiry_sec,
+            label: self.label.clone(),
+            activation_timeout: tokio::time::Duration::from_secs(self.activation_timeout_sec),
+            store,
+        };
+        let old_key = load_signer().await?;
+        let registrar = ApiRegistrar::new(owner_signer, config)?;
+        let new_key = rotation.rotate(&registrar, &old_key).await?;
+        let res = json!({
+            "old_session_key": hex::encode_prefixed(old_key.address()),
+            "new_session_key": hex::encode_prefixed(new_key.address()),
+        });
+        print_result("rotate-session-key", &res, output)
+    }
+}
+
+/// Creates subaccounts, moves collateral between them or changes their margin type
+#[derive(Args, Debug)]
+pub struct CliSubaccount {
+    #[command(subcommand)]
+    pub command: SubaccountCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SubaccountCommand {
+    /// Creates a subaccount funded with a deposit from the owner wallet
+    Create {
+        #[arg(short, long)]
+        asset: String,
+        #[arg(long)]
+        amount: BigDecimal,
+        /// PM or SM
+        #[arg(short, long)]
+        margin_type: MarginType,
+    },
+    /// Moves collateral from one subaccount of the owner to another
+    Transfer {
+        #[arg(long)]
+        from: i64,
+        #[arg(long)]
+        to: i64,
+        #[arg(short, long)]
+        asset: String,
+        #[arg(long)]
+        amount: BigDecimal,
+    },
+    /// Moves a position to another subaccount of the owner without trading through the book
+    TransferPosition {
+        #[arg(long)]
+        from: i64,
+        #[arg(long)]
+        to: i64,
+        #[arg(short, long)]
+        instrument: String,
+        /// Negative for shorts, the whole position by default
+        #[arg(long, allow_hyphen_values = true)]
+        amount: Option<BigDecimal>,
+        /// The mark price by default
+        #[arg(long)]
+        price: Option<BigDecimal>,
+    },
+    /// Moves all collateral to a new subaccount of the margin type (funded with a deposit from
+    /// the owner wallet), positions and open orders have to be closed first
+    ChangeMarginType {
+        #[arg(short, long)]
+        subaccount: i64,
+        /// PM or SM
+        #[arg(short, long)]
+        margin_type: MarginType,
+        #[arg(short, long)]
+        asset: String,
+        #[arg(long)]
+        amount: BigDecimal,
+    },
+}
+
+impl CliSubaccount {
+    pub async fn execute(self, output: OutputFormat) -> Result<()> {
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        let res = match self.command {
+            SubaccountCommand::Create { asset, amount, margin_type } => {
+                let subaccount_id =
+                    create_subaccount_and_wait(&client, amount, asset, margin_type).await?;
+                json!({ "subaccount_id": subaccount_id })
+            }
+            SubaccountCommand::Transfer { from, to, asset, amount } => {
+                transfer_and_wait(&client, from, to, amount.clone(), asset.clone()).await?;
+                json!({ "from": from, "to": to, "asset_name": asset, "amount": amount })
+            }
+            SubaccountCommand::TransferPosition { from, to, instrument, amount, price } => {
+                let res = transfer_position(&client, from, to, &instrument, amount, price).await?;
+                serde_json::to_value(res)?
+            }
+            SubaccountCommand::ChangeMarginType { subaccount, margin_type, asset, amount } => {
+                let subaccount_id =
+                    change_margin_type(&client, subaccount, margin_type, amount, asset).await?;
+                json!({ "previous_subaccount_id": subaccount, "subaccount_id": subaccount_id })
+            }
+        };
+        print_result("subaccount", &res, output)
+    }
+}
+
+/// Lists and bids for liquidation auctions, see `liquidations` for running a liquidator bot
+#[derive(Args, Debug)]
+pub struct CliLiquidate {
+    #[command(subcommand)]
+    pub command: LiquidateCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LiquidateCommand {
+    /// Prints the ongoing auctions, the most profitable first
+    List {
+        /// How long to collect auctions from the auctions.watch channel
+        #[arg(long, default_value_t = 5)]
+        wait_sec: u64,
+    },
+    /// Bids for an auction and waits for the liquidation tx
+    Bid {
+        /// Bidding subaccount of the owner
+        #[arg(short, long)]
+        subaccount: i64,
+        /// Subaccount under liquidation
+        #[arg(short, long)]
+        liquidated: i64,
+        /// Caps the estimated bid price, the max possible percent is bid by default
+        #[arg(long)]
+        max_cash: Option<BigDecimal>,
+        #[arg(long, default_value_t = 5)]
+        wait_sec: u64,
+    },
+}
+
+impl CliLiquidate {
+    pub async fn execute(self, output: OutputFormat) -> Result<()> {
+        let res = match self.command {
+            LiquidateCommand::List { wait_sec } => {
+                let wait = tokio::time::Duration::from_secs(wait_sec);
+                serde_json::to_value(get_liquidatable_subaccounts(wait).await?)?
+            }
+            LiquidateCommand::Bid { subaccount, liquidated, max_cash, wait_sec } => {
+                let wait = tokio::time::Duration::from_secs(wait_sec);
+                let auctions = get_liquidatable_subaccounts(wait).await?;
+                let details = auctions
+                    .into_iter()
+                    .find(|a| a.subaccount_id == liquidated)
+                    .and_then(|a| a.details)
+                    .ok_or(format_err!("No ongoing auction of subaccount {}", liquidated))?;
+                let client = WsClient::new_client().await?;
+                client.login().await?.into_result()?;
+                let res = bid_and_wait(&client, subaccount, liquidated, &details, max_cash).await?;
+                serde_json::to_value(res)?
+            }
+        };
+        print_result("liquidate", &res, output)
+    }
+}
+
+/// Configures market maker protection, which freezes a subaccount's `mmp` orders of a currency
+/// once the limits are traded within the interval
+#[derive(Args, Debug)]
+pub struct CliMmp {
+    #[command(subcommand)]
+    pub command: MmpCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MmpCommand {
+    /// Sets the MMP limits of a currency, a zero interval disables MMP
+    Set {
+        #[arg(short, long)]
+        subaccount: i64,
+        #[arg(short, long)]
+        currency: String,
+        /// Window in which trades count towards the limits
+        #[arg(long)]
+        interval_ms: i64,
+        /// How long the subaccount stays frozen, 0 requires a manual reset
+        #[arg(long)]
+        frozen_time_ms: i64,
+        /// Max traded amount within the interval, 0 for no limit
+        #[arg(long, default_value_t = BigDecimal::zero())]
+        amount_limit: BigDecimal,
+        /// Max absolute delta traded within the interval, 0 for no limit
+        #[arg(long, default_value_t = BigDecimal::zero())]
+        delta_limit: BigDecimal,
+    },
+    /// Unfreezes the subaccount, for all currencies unless one is given
+    Reset {
+        #[arg(short, long)]
+        subaccount: i64,
+        #[arg(short, long)]
+        currency: Option<String>,
+    },
+    /// Prints the MMP configs of the subaccount
+    Get {
+        #[arg(short, long)]
+        subaccount: i64,
+        #[arg(short, long)]
+        currency: Option<String>,
+    },
+}
+
+impl CliMmp {
+    pub async fn execute(self, output: OutputFormat) -> Result<()> {
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        let res = match self.command {
+            MmpCommand::Set {
+                subaccount,
+                currency,
+                interval_ms,
+                frozen_time_ms,
+                amount_limit,
+                delta_limit,
+            } => {
+                let params = PrivateSetMmpConfigParamsSchema {
+                    currency,
+                    mmp_amount_limit: amount_limit,
+                    mmp_delta_limit: delta_limit,
+                    mmp_frozen_time: frozen_time_ms,
+                    mmp_interval: interval_ms,
+                    subaccount_id: subaccount,
+                };
+                let res = client.set_mmp_config(params).await?.into_result()?;
+                serde_json::to_value(res.result)?
+            }
+            MmpCommand::Reset { subaccount, currency } => {
+                let res = client.reset_mmp(subaccount, currency).await?.into_result()?;
+                serde_json::to_value(res.result)?
+            }
+            MmpCommand::Get { subaccount, currency } => {
+                let res = client.get_mmp_config(subaccount, currency).await?.into_result()?;
+                serde_json::to_value(res.result)?
+            }
+        };
+        print_result("mmp", &res, output)
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct CliOrderbook {
     /// The instrument name to get the orderbook for
@@ -362,7 +779,7 @@ pub struct CliOrderbook {
 impl CliOrderbook {
     pub async fn subscribe(&self) -> Result<()> {
         info!("Starting market task");
-        let channels: Vec<String> = vec![format!("orderbook.{}.1.10", self.instrument)];
+        let channels = vec![Channel::orderbook(&self.instrument, 10).to_string()];
         let client = WsClient::new_client().await?;
         client
             .subscribe(channels, |mut d: Notification<OrderbookData>| async move {
@@ -397,55 +814,80 @@ impl CliOrderbook {
 }
 
 #[derive(Debug, clap::Args)]
-#[group(required = true, multiple = false)]
+#[group(required = true, multiple = true)]
 pub struct ParamsOrInline {
     /// The path to the params file to read
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "preset")]
     pub file: Option<std::path::PathBuf>,
 
-    /// An inline json to use as params, e.g. '{"subaccount_id": 1}'
+    /// An inline json to use as params, e.g. '{"subaccount_id": 1}'. Merged over the params
+    /// of the file or preset if given as well.
     #[arg(short, long)]
     pub inline: Option<String>,
+
+    /// A preset saved with the preset command, e.g. weekly-roll
+    #[arg(long)]
+    pub preset: Option<String>,
 }
 
 impl CliRpc {
-    async fn params_to_value(&self) -> Result<Value> {
-        let params: String = match &self.params.inline {
-            Some(s) => s.clone(),
-            None => tokio::fs::read_to_string(&self.params.file.clone().unwrap()).await?,
+    /// The method and params to call, the inline params merged over the file or preset ones
+    async fn method_and_params(&self) -> Result<(String, Value)> {
+        let (preset_method, mut params) = match (&self.params.file, &self.params.preset) {
+            (Some(file), _) => {
+                let contents = tokio::fs::read_to_string(file).await?;
+                (None, serde_json::from_str::<Value>(&contents)?)
+            }
+            (None, Some(name)) => {
+                let preset = Preset::load(name).await?;
+                (preset.method, preset.params)
+            }
+            (None, None) => (None, json!({})),
         };
-        Ok(serde_json::from_str(&params)?)
+        if let Some(inline) = &self.params.inline {
+            merge_params(&mut params, serde_json::from_str(inline)?);
+        }
+        let method = self.method.clone().or(preset_method);
+        let method = method.ok_or(format_err!("No method, pass one or a preset that has one"))?;
+        Ok((method, params))
     }
 
-    pub async fn execute() -> Result<()> {
-        let args = Cli::parse();
+    pub async fn execute(args: Cli) -> Result<()> {
         info!("Parsed Request {:?}", args);
         match args.command {
-            Command::Rpc(rpc) => Self::call(rpc).await,
-            Command::Sub(sub) => sub.subscribe().await,
+            Command::Rpc(rpc) => Self::call(rpc, args.output).await,
+            Command::Sub(sub) => sub.subscribe(args.output).await,
+            Command::SubFills(sub) => sub.subscribe(args.output).await,
             Command::Auctions(a) => a.start().await,
             Command::Orderbook(ob) => ob.subscribe().await,
+            Command::History(history) => history.export(args.output).await,
+            Command::MarketHistory(history) => history.execute(args.output).await,
+            Command::RotateSessionKey(rotate) => rotate.rotate(args.output).await,
+            Command::Subaccount(subaccount) => subaccount.execute(args.output).await,
+            Command::Mmp(mmp) => mmp.execute(args.output).await,
+            Command::DecodeOrder(decode) => decode.execute(args.output).await,
+            Command::Liquidate(liquidate) => liquidate.execute(args.output).await,
+            Command::BootstrapTestnet(bootstrap) => bootstrap.execute(args.output).await,
+            Command::Repl(repl) => repl.run(args.output).await,
+            Command::Preset(preset) => preset.execute(args.output).await,
+            Command::RfqMaker(maker) => maker.run().await,
         }
     }
 
-    pub async fn call(args: CliRpc) -> Result<()> {
-        let params = args.params_to_value().await?;
+    /// Prints the result to stdout, returns an error if the RPC failed
+    pub async fn call(args: CliRpc, output: OutputFormat) -> Result<()> {
+        let (method, params) = args.method_and_params().await?;
+        info!("Calling {} with {}", method, params);
         let client = WsClient::new_client().await?;
-        if args.method.starts_with("private") {
+        if method.starts_with("private") {
             client.login().await?.into_result()?;
-            client.set_cancel_on_disconnect(false).await?.into_result()?;
+            client.set_cancel_on_disconnect(args.cancel_on_disconnect).await?.into_result()?;
         }
-        let res = match args.method.as_str() {
+        let res = match method.as_str() {
             "private/order" => {
                 let order_args = serde_json::from_value::<OrderArgs>(params.clone())?;
-                let ticker = http_rpc::<_, TickerResponse>(
-                    "public/get_ticker",
-                    json!({ "instrument_name": params["instrument_name"] }),
-                    None,
-                )
-                .await?
-                .into_result()?
-                .result;
+                let instrument_name = params["instrument_name"].as_str().unwrap_or_default();
+                let ticker = TickerCache::global().get(instrument_name).await?;
                 let subaccount_id: i64 = params["subaccount_id"].as_i64().unwrap();
                 let response =
                     client.send_order(&ticker, subaccount_id, order_args).await?.into_result();
@@ -485,19 +927,42 @@ impl CliRpc {
                     Err(response) => Err(response),
                 }
             }
-            "private/send_quote" => {
-                let quote_args = serde_json::from_value::<QuoteArgs>(params.clone())?;
-                let mut tickers = HashMap::<String, InstrumentTicker>::new();
-                for leg in quote_args.legs.iter() {
-                    let ticker = http_rpc::<_, TickerResponse>(
-                        "public/get_ticker",
-                        json!({ "instrument_name": leg.instrument_name }),
-                        None,
-                    )
+            "private/cancel" => {
+                let cancel = serde_json::from_value::<PrivateCancelParamsSchema>(params)?;
+                let response = client
+                    .cancel(cancel.subaccount_id, cancel.instrument_name, cancel.order_id)
                     .await?
-                    .into_result()?;
-                    tickers.insert(leg.instrument_name.clone(), ticker.result);
+                    .into_result();
+                match response {
+                    Ok(response) => Ok(serde_json::to_value(response)?),
+                    Err(response) => Err(response),
+                }
+            }
+            "private/cancel_all" => {
+                let cancel = serde_json::from_value::<PrivateCancelAllParamsSchema>(params)?;
+                if !args.confirm && !confirm_cancel_all(cancel.subaccount_id)? {
+                    info!("Cancel all aborted");
+                    return Ok(());
+                }
+                let response = client.cancel_all(cancel.subaccount_id).await?.into_result();
+                match response {
+                    Ok(response) => Ok(serde_json::to_value(response)?),
+                    Err(response) => Err(response),
+                }
+            }
+            "private/cancel_by_label" => {
+                let cancel = serde_json::from_value::<PrivateCancelByLabelParamsSchema>(params)?;
+                let response =
+                    client.cancel_by_label(cancel.subaccount_id, cancel.label).await?.into_result();
+                match response {
+                    Ok(response) => Ok(serde_json::to_value(response)?),
+                    Err(response) => Err(response),
                 }
+            }
+            "private/send_quote" => {
+                let quote_args = serde_json::from_value::<QuoteArgs>(params.clone())?;
+                let names = quote_args.legs.iter().map(|leg| leg.instrument_name.as_str());
+                let tickers = TickerCache::global().get_many(names).await?;
                 let subaccount_id: i64 = params["subaccount_id"].as_i64().unwrap();
                 client.send_quote(&tickers, subaccount_id, quote_args).await?.into_result()
             }
@@ -512,25 +977,27 @@ impl CliRpc {
                     .await?
                     .into_result()?;
                 let quote = &quote.result.quotes[0];
-                let mut tickers = HashMap::<String, InstrumentTicker>::new();
-                for leg in quote.legs.iter() {
-                    let ticker = http_rpc::<_, TickerResponse>(
-                        "public/get_ticker",
-                        json!({ "instrument_name": leg.instrument_name }),
-                        None,
-                    )
-                    .await?
-                    .into_result()?;
-                    tickers.insert(leg.instrument_name.clone(), ticker.result);
-                }
+                let names = quote.legs.iter().map(|leg| leg.instrument_name.as_str());
+                let tickers = TickerCache::global().get_many(names).await?;
                 client.send_execute(&tickers, subaccount_id, quote).await?.into_result()
             }
-            _ => client.send_rpc::<Value, Value>(&args.method, params).await?.into_result(),
+            _ => client.send_rpc::<Value, Value>(&method, params).await?.into_result(),
         };
         match res {
-            Ok(r) => info!("{}", serde_json::to_string_pretty(&r)?),
-            Err(e) => error!("Error: {:?}", e),
-        };
-        Ok(())
+            Ok(r) => print_result(&method, &r, output),
+            Err(e) => {
+                let e = anyhow::Error::from(e);
+                print_error(&e, output)?;
+                Err(e)
+            }
+        }
     }
 }
+
+/// Asks for an explicit "y" on stdin before cancelling every order on the subaccount
+pub fn confirm_cancel_all(subaccount_id: i64) -> Result<bool> {
+    println!("Cancel ALL open orders on subaccount {}? [y/N]", subaccount_id);
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
@@ -0,0 +1,170 @@
+#[cfg(not(feature = "legacy-signing"))]
+use crate::actions::{matching_domain, ActionData};
+use crate::signer::LyraSigner;
+use anyhow::{Error, Result};
+use ethers::prelude::{Address, LocalWallet};
+#[cfg(not(feature = "legacy-signing"))]
+use ethers::types::transaction::eip712::Eip712;
+#[cfg(feature = "legacy-signing")]
+use ethers::utils::hex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+
+static GLOBAL_CONFIG: OnceLock<Arc<LyraConfig>> = OnceLock::new();
+
+tokio::task_local! {
+    static SCOPED_CONFIG: Arc<LyraConfig>;
+}
+
+/// Protocol addresses, signing hashes and keys, loaded once and passed to the signing helpers
+/// and `WsClient` so that nothing reads (or mutates) env vars while signing.
+#[derive(Clone, Debug)]
+pub struct LyraConfig {
+    pub env: String,
+    pub chain_id: u64,
+    pub matching_address: Address,
+    pub trade_address: Address,
+    pub deposit_address: Address,
+    pub withdrawal_address: Address,
+    pub transfer_address: Address,
+    pub liquidate_address: Address,
+    pub rfq_address: Address,
+    // "SRM" and the portfolio margin manager of each currency (e.g. "ETH")
+    pub managers: HashMap<String, Address>,
+    pub action_typehash: [u8; 32],
+    pub domain_separator: [u8; 32],
+    pub owner: Option<Address>,
+    pub session_key: Option<LyraSigner>,
+    pub subaccount_id: Option<i64>,
+}
+
+impl LyraConfig {
+    /// Reads the `.env.constants.{env}` values (and keys if present), call after `setup_env`
+    pub fn from_env() -> Result<Self> {
+        let chain_id = env_var("CHAIN_ID")?.parse()?;
+        let matching_address = env_address("MATCHING_ADDRESS")?;
+        let (action_typehash, domain_separator) = signing_hashes(chain_id, matching_address)?;
+        Ok(LyraConfig {
+            env: std::env::var("ENV").unwrap_or_default(),
+            chain_id,
+            matching_address,
+            trade_address: env_address("TRADE_ADDRESS")?,
+            deposit_address: env_address("DEPOSIT_ADDRESS")?,
+            withdrawal_address: env_address("WITHDRAWAL_ADDRESS")?,
+            transfer_address: env_address("TRANSFER_ADDRESS")?,
+            liquidate_address: env_address("LIQUIDATE_ADDRESS")?,
+            rfq_address: env_address("RFQ_ADDRESS")?,
+            managers: env_managers()?,
+            action_typehash,
+            domain_separator,
+            owner: std::env::var("OWNER_PUBLIC_KEY").ok().map(|o| o.parse()).transpose()?,
+            session_key: std::env::var("SESSION_PRIVATE_KEY")
+                .ok()
+                .map(|k| k.parse::<LocalWallet>().map(LyraSigner::from))
+                .transpose()?,
+            subaccount_id: std::env::var("SUBACCOUNT_ID").ok().map(|s| s.parse()).transpose()?,
+        })
+    }
+
+    pub fn with_env(mut self, env: &str) -> Self {
+        self.env = env.to_string();
+        self
+    }
+
+    pub fn with_owner(mut self, owner: Address) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    pub fn with_session_key(mut self, session_key: LyraSigner) -> Self {
+        self.session_key = Some(session_key);
+        self
+    }
+
+    pub fn with_subaccount_id(mut self, subaccount_id: i64) -> Self {
+        self.subaccount_id = Some(subaccount_id);
+        self
+    }
+
+    pub fn owner(&self) -> Result<Address> {
+        self.owner.ok_or(Error::msg("Owner not set in LyraConfig"))
+    }
+
+    pub fn subaccount_id(&self) -> Result<i64> {
+        self.subaccount_id.ok_or(Error::msg("Subaccount id not set in LyraConfig"))
+    }
+
+    /// Installs the process wide config, fails if one was already installed or loaded
+    pub fn init(config: LyraConfig) -> Result<Arc<LyraConfig>> {
+        let config = Arc::new(config);
+        GLOBAL_CONFIG
+            .set(config.clone())
+            .map_err(|_| Error::msg("LyraConfig already initialized"))?;
+        Ok(config)
+    }
+
+    /// The config installed by `init`, or loaded from env on first use. Fails if it was not
+    /// installed and the env is incomplete, the next call tries to load it again.
+    pub fn global() -> Result<Arc<LyraConfig>> {
+        if let Some(config) = GLOBAL_CONFIG.get() {
+            return Ok(config.clone());
+        }
+        let config = Arc::new(LyraConfig::from_env()?);
+        // a concurrent `init` or first use wins
+        Ok(GLOBAL_CONFIG.get_or_init(|| config).clone())
+    }
+
+    /// The config of the enclosing `LyraConfig::scope` (e.g. one of several vaults running in
+    /// the same process), or `global()` outside of any scope
+    pub fn current() -> Result<Arc<LyraConfig>> {
+        match SCOPED_CONFIG.try_with(|config| config.clone()) {
+            Ok(config) => Ok(config),
+            Err(_) => LyraConfig::global(),
+        }
+    }
+
+    /// Runs the future with `current()` returning the given config. Note that tasks spawned
+    /// inside the future do not inherit the scope.
+    pub async fn scope<F: Future>(config: Arc<LyraConfig>, f: F) -> F::Output {
+        SCOPED_CONFIG.scope(config, f).await
+    }
+}
+
+fn env_var(name: &str) -> Result<String> {
+    std::env::var(name).map_err(|_| Error::msg(format!("{} must be set", name)))
+}
+
+fn env_address(name: &str) -> Result<Address> {
+    env_var(name)?
+        .parse()
+        .map_err(|e| Error::msg(format!("{} must be a valid address: {:?}", name, e)))
+}
+
+/// SRM_ADDRESS and every {CURRENCY}_PMRM_ADDRESS
+fn env_managers() -> Result<HashMap<String, Address>> {
+    let mut managers = HashMap::new();
+    for (name, _) in std::env::vars() {
+        let manager = match name.strip_suffix("_PMRM_ADDRESS") {
+            Some(currency) => currency.to_string(),
+            None if name == "SRM_ADDRESS" => "SRM".to_string(),
+            None => continue,
+        };
+        managers.insert(manager, env_address(&name)?);
+    }
+    Ok(managers)
+}
+
+#[cfg(feature = "legacy-signing")]
+fn signing_hashes(_chain_id: u64, _matching: Address) -> Result<([u8; 32], [u8; 32])> {
+    let action_typehash = env_var("ACTION_TYPEHASH")?;
+    let action_typehash = hex::const_decode_to_array::<32>(action_typehash.as_bytes())?;
+    let domain_separator = env_var("DOMAIN_SEPARATOR")?;
+    let domain_separator = hex::const_decode_to_array::<32>(domain_separator.as_bytes())?;
+    Ok((action_typehash, domain_separator))
+}
+
+#[cfg(not(feature = "legacy-signing"))]
+fn signing_hashes(chain_id: u64, matching: Address) -> Result<([u8; 32], [u8; 32])> {
+    Ok((ActionData::type_hash()?, matching_domain(chain_id, matching).separator()))
+}
@@ -0,0 +1,177 @@
+use crate::actions::{ActionData, TradeData, MIN_SIGNATURE_EXPIRY_SEC};
+use crate::config::LyraConfig;
+use crate::json_rpc::http_rpc;
+use crate::output::{print_result, OutputFormat};
+use anyhow::{format_err, Result};
+use bigdecimal::{BigDecimal, Zero};
+use clap::Args;
+use ethers::abi::AbiEncode;
+use ethers::prelude::{Address, Signature, H256};
+use ethers::utils::hex;
+use orderbook_types::types::orders::OrderParams;
+use orderbook_types::types::tickers::{InstrumentTicker, TickerResponse};
+use orderbook_types::types::RPCError;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::str::FromStr;
+use tracing::info;
+
+/// Recomputes the hashes of a signed order and checks that the signature recovers to its
+/// signer, to debug signature (and other) rejections of private/order
+#[derive(Args, Debug)]
+pub struct CliDecodeOrder {
+    /// JSON with the private/order params, either as is or as {"params": .., "error": ..} with
+    /// the error the server replied with
+    #[arg(short, long)]
+    pub file: PathBuf,
+
+    /// Owner wallet of the subaccount, OWNER_PUBLIC_KEY by default
+    #[arg(long)]
+    pub owner: Option<Address>,
+}
+
+impl CliDecodeOrder {
+    pub async fn execute(&self, output: OutputFormat) -> Result<()> {
+        let contents = tokio::fs::read_to_string(&self.file).await?;
+        let value = serde_json::from_str::<Value>(&contents)?;
+        let params = value.get("params").unwrap_or(&value).clone();
+        let params = serde_json::from_value::<OrderParams>(params)?;
+        let server_error = match value.get("error") {
+            Some(error) => Some(serde_json::from_value::<RPCError>(error.clone())?),
+            None => None,
+        };
+        let config = LyraConfig::current()?;
+        let owner = match self.owner {
+            Some(owner) => owner,
+            None => config.owner()?,
+        };
+        let ticker = http_rpc::<_, TickerResponse>(
+            "public/get_ticker",
+            json!({ "instrument_name": params.instrument_name }),
+            None,
+        )
+        .await?
+        .into_result()?
+        .result;
+        let res = decode_order(&params, &ticker, owner, &config, server_error.as_ref())?;
+        print_result("decode-order", &res, output)
+    }
+}
+
+/// Every intermediate hash of the order signature, the recovered signer and the findings
+fn decode_order(
+    params: &OrderParams,
+    ticker: &InstrumentTicker,
+    owner: Address,
+    config: &LyraConfig,
+    server_error: Option<&RPCError>,
+) -> Result<Value> {
+    let signer = Address::from_str(&params.signer)
+        .map_err(|e| format_err!("Invalid signer {}: {:?}", params.signer, e))?;
+    let trade_data = TradeData::from_order_params(ticker, params)?;
+    let encoded_trade_data = trade_data.clone().encode();
+    let trade_data_hash = ethers::utils::keccak256(&encoded_trade_data);
+    let action = ActionData {
+        action_typehash: config.action_typehash,
+        subaccount_id: params.subaccount_id.into(),
+        nonce: params.nonce.into(),
+        module: config.trade_address,
+        data: trade_data_hash,
+        expiry: params.signature_expiry_sec.into(),
+        owner,
+        signer,
+    };
+    let action_hash = action.clone().action_hash();
+    let typed_data_hash = action.clone().hash(config);
+    let signature = Signature::from_str(&params.signature)
+        .map_err(|e| format_err!("Invalid signature {}: {:?}", params.signature, e))?;
+    let recovered = signature.recover(H256::from(typed_data_hash)).ok();
+    info!("Recovered signer {:?}, expected {:?}", recovered, signer);
+
+    let signature_valid = recovered == Some(signer);
+    let mut findings = vec![];
+    if !signature_valid {
+        findings.push(format!(
+            "signature recovers to {}, not the signer {}: the signed fields differ from the \
+             params (compare the hashes with the signing side) or it was signed for another \
+             domain, owner or module",
+            recovered.map_or("nothing".to_string(), hex::encode_prefixed),
+            hex::encode_prefixed(signer)
+        ));
+    }
+    findings.extend(order_param_findings(params, ticker));
+    let server_vs_local = server_error.map(|e| {
+        let is_signature_error = e.message.to_lowercase().contains("signature");
+        match (is_signature_error, signature_valid) {
+            (true, true) => "the server rejected a signature that recovers locally: check that \
+                the owner matches the subaccount and the signer is a registered session key"
+                .to_string(),
+            (true, false) => "the server rejection matches the local recovery".to_string(),
+            (false, _) => {
+                "the server error is not about the signature, see the findings".to_string()
+            }
+        }
+    });
+
+    Ok(json!({
+        "instrument_name": params.instrument_name,
+        "trade_data": trade_data.to_json(),
+        "encoded_trade_data": hex::encode_prefixed(&encoded_trade_data),
+        "trade_data_hash": hex::encode_prefixed(trade_data_hash),
+        "action_data": {
+            "action_typehash": hex::encode_prefixed(action.action_typehash),
+            "subaccount_id": action.subaccount_id.to_string(),
+            "nonce": action.nonce.to_string(),
+            "module": hex::encode_prefixed(action.module),
+            "data": hex::encode_prefixed(action.data),
+            "expiry": action.expiry.to_string(),
+            "owner": hex::encode_prefixed(action.owner),
+            "signer": hex::encode_prefixed(action.signer),
+        },
+        "action_hash": hex::encode_prefixed(action_hash),
+        "domain_separator": hex::encode_prefixed(config.domain_separator),
+        "typed_data_hash": hex::encode_prefixed(typed_data_hash),
+        "recovered_signer": recovered.map(hex::encode_prefixed),
+        "signature_valid": signature_valid,
+        "findings": findings,
+        "server_error": server_error,
+        "server_vs_local": server_vs_local,
+    }))
+}
+
+/// Params the API rejects regardless of the signature
+fn order_param_findings(params: &OrderParams, ticker: &InstrumentTicker) -> Vec<String> {
+    let mut findings = vec![];
+    let now_sec = chrono::Utc::now().timestamp();
+    if params.signature_expiry_sec < now_sec + MIN_SIGNATURE_EXPIRY_SEC {
+        findings.push(format!(
+            "signature_expiry_sec is {}s from now, at least {}s are required",
+            params.signature_expiry_sec - now_sec,
+            MIN_SIGNATURE_EXPIRY_SEC
+        ));
+    }
+    if params.reject_timestamp < chrono::Utc::now().timestamp_millis() {
+        findings.push(format!("reject_timestamp {} has passed", params.reject_timestamp));
+    }
+    let is_multiple =
+        |value: &BigDecimal, step: &BigDecimal| step.is_zero() || (value % step).is_zero();
+    if !is_multiple(&params.limit_price, &ticker.tick_size) {
+        findings.push(format!(
+            "limit_price {} is not a multiple of the tick size {}",
+            params.limit_price, ticker.tick_size
+        ));
+    }
+    if !is_multiple(&params.amount, &ticker.amount_step) {
+        findings.push(format!(
+            "amount {} is not a multiple of the amount step {}",
+            params.amount, ticker.amount_step
+        ));
+    }
+    if params.amount < ticker.minimum_amount {
+        findings.push(format!(
+            "amount {} is below the minimum amount {}",
+            params.amount, ticker.minimum_amount
+        ));
+    }
+    findings
+}
@@ -0,0 +1,181 @@
+use crate::error::{LyraError, Result};
+use serde::Serialize;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+static WS_ENDPOINTS: OnceLock<Endpoints> = OnceLock::new();
+static HTTP_ENDPOINTS: OnceLock<Endpoints> = OnceLock::new();
+
+/// First cooldown of a failed endpoint, override with ENDPOINT_COOLDOWN_SEC.
+/// Doubled for every further consecutive failure, up to MAX_COOLDOWN_FACTOR times.
+const DEFAULT_COOLDOWN_SEC: u64 = 30;
+const MAX_COOLDOWN_FACTOR: u32 = 8;
+
+/// Health of one endpoint, served by `Endpoints::status`
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStatus {
+    pub url: String,
+    pub consecutive_failures: u32, // the health score, 0 is healthy
+    pub total_failures: u64,
+    pub cooldown_sec: u64, // left until the endpoint is preferred again
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    consecutive_failures: u32,
+    total_failures: u64,
+    cooldown_until: Option<Instant>,
+}
+
+/// Endpoint URLs of one transport in preference order, e.g.
+/// `WEBSOCKET_ADDRESS=wss://api.lyra.finance/ws,wss://api-eu.lyra.finance/ws`.
+/// Connections and requests go to the most preferred endpoint that is not cooling down after
+/// a failure, falling back to the one whose cooldown ends first if all are.
+#[derive(Debug)]
+pub struct Endpoints {
+    name: &'static str,
+    endpoints: Mutex<Vec<Endpoint>>,
+    cooldown: Duration,
+}
+
+impl Endpoints {
+    /// Websocket endpoints from WEBSOCKET_ADDRESS
+    pub fn ws() -> &'static Endpoints {
+        WS_ENDPOINTS.get_or_init(|| Endpoints::from_env("WEBSOCKET_ADDRESS"))
+    }
+
+    /// HTTP endpoints from HTTP_ADDRESS
+    pub fn http() -> &'static Endpoints {
+        HTTP_ENDPOINTS.get_or_init(|| Endpoints::from_env("HTTP_ADDRESS"))
+    }
+
+    fn from_env(name: &'static str) -> Self {
+        let urls = std::env::var(name).unwrap_or_else(|_| panic!("{} must be set", name));
+        let cooldown_sec = std::env::var("ENDPOINT_COOLDOWN_SEC").ok().and_then(|s| s.parse().ok());
+        Endpoints::new(
+            name,
+            &urls,
+            Duration::from_secs(cooldown_sec.unwrap_or(DEFAULT_COOLDOWN_SEC)),
+        )
+    }
+
+    /// Comma separated URLs, most preferred first
+    pub fn new(name: &'static str, urls: &str, cooldown: Duration) -> Self {
+        let endpoints = urls
+            .split(',')
+            .map(|url| url.trim().trim_end_matches('/'))
+            .filter(|url| !url.is_empty())
+            .map(|url| Endpoint {
+                url: url.to_string(),
+                consecutive_failures: 0,
+                total_failures: 0,
+                cooldown_until: None,
+            })
+            .collect::<Vec<_>>();
+        assert!(!endpoints.is_empty(), "{} has no endpoints", name);
+        Endpoints { name, endpoints: Mutex::new(endpoints), cooldown }
+    }
+
+    /// URLs to try in order: the available endpoints by preference, then the cooling down ones
+    /// by the end of their cooldown
+    pub fn candidates(&self) -> Vec<String> {
+        let now = Instant::now();
+        let endpoints = self.endpoints.lock().unwrap();
+        let (available, mut cooling): (Vec<_>, Vec<_>) =
+            endpoints.iter().partition(|e| e.cooldown_until.map_or(true, |t| t <= now));
+        cooling.sort_by_key(|e| e.cooldown_until);
+        available.into_iter().chain(cooling).map(|e| e.url.clone()).collect()
+    }
+
+    /// The endpoint to use now
+    pub fn preferred(&self) -> String {
+        self.candidates().remove(0)
+    }
+
+    pub fn report_success(&self, url: &str) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        if let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) {
+            if endpoint.consecutive_failures > 0 {
+                info!("{} endpoint {} recovered", self.name, url);
+            }
+            endpoint.consecutive_failures = 0;
+            endpoint.cooldown_until = None;
+        }
+    }
+
+    /// Puts the endpoint into its cooldown, so the next candidate is preferred meanwhile
+    pub fn report_failure(&self, url: &str) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let num_endpoints = endpoints.len();
+        if let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) {
+            endpoint.consecutive_failures += 1;
+            endpoint.total_failures += 1;
+            let factor =
+                2u32.saturating_pow(endpoint.consecutive_failures - 1).min(MAX_COOLDOWN_FACTOR);
+            let cooldown = self.cooldown * factor;
+            endpoint.cooldown_until = Some(Instant::now() + cooldown);
+            if num_endpoints > 1 {
+                warn!("{} endpoint {} failed, cooling down for {:?}", self.name, url, cooldown);
+            }
+        }
+    }
+
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        let now = Instant::now();
+        let endpoints = self.endpoints.lock().unwrap();
+        endpoints
+            .iter()
+            .map(|e| EndpointStatus {
+                url: e.url.clone(),
+                consecutive_failures: e.consecutive_failures,
+                total_failures: e.total_failures,
+                cooldown_sec: e
+                    .cooldown_until
+                    .map_or(0, |t| t.saturating_duration_since(now).as_secs()),
+            })
+            .collect()
+    }
+
+    /// Runs the attempt against the candidates in order until one succeeds, reporting each
+    /// outcome. Returns the error of the last candidate if all fail.
+    pub async fn try_each<T, Fut>(&self, attempt: impl FnMut(String) -> Fut) -> Result<T>
+    where
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        self.try_each_if(attempt, |_| true).await
+    }
+
+    /// Like `try_each`, but only fails over to the next candidate if `fail_over` holds for the
+    /// error, e.g. when the request may have been processed and must not be sent twice.
+    pub async fn try_each_if<T, E, Fut>(
+        &self,
+        mut attempt: impl FnMut(String) -> Fut,
+        fail_over: impl Fn(&E) -> bool,
+    ) -> Result<T>
+    where
+        Fut: std::future::Future<Output = std::result::Result<T, E>>,
+        E: Into<LyraError> + fmt::Display,
+    {
+        let mut last_error = None;
+        for url in self.candidates() {
+            match attempt(url.clone()).await {
+                Ok(res) => {
+                    self.report_success(&url);
+                    return Ok(res);
+                }
+                Err(e) => {
+                    warn!("{} endpoint {} failed with {}", self.name, url, e);
+                    self.report_failure(&url);
+                    if !fail_over(&e) {
+                        return Err(e.into());
+                    }
+                    last_error = Some(e.into());
+                }
+            }
+        }
+        Err(last_error.unwrap_or(LyraError::transport("No endpoints")))
+    }
+}
@@ -0,0 +1,191 @@
+use orderbook_types::types::{RPCError, RPCErrorResponse};
+use std::fmt;
+use tokio::time::Duration;
+
+pub type Result<T, E = LyraError> = std::result::Result<T, E>;
+
+/// Error codes of the typed `LyraError` variants, everything else becomes `LyraError::Rpc`
+pub const POST_ONLY_REJECT_CODE: i64 = 11008;
+const INSUFFICIENT_FUNDS_CODES: [i64; 2] = [11000, 11020];
+// invalid signature expiry, reject timestamp exceeded
+const SIGNATURE_EXPIRED_CODES: [i64; 2] = [11011, 11022];
+// already cancelled, filled or expired, does not exist
+const ORDER_NOT_OPEN_CODES: [i64; 4] = [11003, 11004, 11005, 11006];
+// invalid signature, wallet header mismatch / missing, private channel subscription failed,
+// unknown signer, missing wallet, session key not found / expired
+const UNAUTHORIZED_CODES: [i64; 8] = [14014, 14020, 14021, 14022, 14023, 14025, 14026, 14030];
+
+/// Errors of the lyra client, RPC errors are mapped by code so that callers can branch on them
+/// (e.g. retry on `SignatureExpired` but abort on `InsufficientFunds`). Converts into
+/// `anyhow::Error`, use `e.downcast_ref::<LyraError>()` to get it back.
+#[derive(Debug)]
+pub enum LyraError {
+    /// Socket or HTTP failure, e.g. a connection lost before a reply
+    Transport(String),
+    /// No reply within the timeout of the request, the request may still have been processed
+    Timeout {
+        method: String,
+        timeout: Duration,
+    },
+    /// Rejected by the server (or queued locally for too long) due to rate limits
+    RateLimited {
+        method: String,
+        retry_after: Duration,
+    },
+    InsufficientFunds(RPCError),
+    /// The signature or reject timestamp expired before the request was processed
+    SignatureExpired(RPCError),
+    /// The order is no longer open (cancelled, filled, expired) or never existed
+    OrderNotOpen(RPCError),
+    /// The session is not (or no longer) authorized for private requests
+    Unauthorized(RPCError),
+    /// A post-only order would have crossed the book
+    PostOnlyReject(RPCError),
+    /// Any other RPC error, see `code()`
+    Rpc(RPCError),
+    /// The order was not sent since the top of book is too far from mark, see `max_slippage_bps`
+    PriceProtection(String),
+    /// The action could not be signed, e.g. when not logged in
+    Signing(String),
+    /// A response or notification that does not match its schema
+    Deserialization(String),
+    /// Anything else, e.g. invalid params or config
+    Other(anyhow::Error),
+}
+
+impl LyraError {
+    /// The RPC error code, None for local errors
+    pub fn code(&self) -> Option<i64> {
+        self.rpc_error().map(|e| e.code)
+    }
+
+    pub fn rpc_error(&self) -> Option<&RPCError> {
+        match self {
+            LyraError::InsufficientFunds(e)
+            | LyraError::SignatureExpired(e)
+            | LyraError::OrderNotOpen(e)
+            | LyraError::Unauthorized(e)
+            | LyraError::PostOnlyReject(e)
+            | LyraError::Rpc(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn is_unauthorized_code(code: i64) -> bool {
+        UNAUTHORIZED_CODES.contains(&code)
+    }
+
+    /// False for failures that repeat until an operator steps in, e.g. missing funds
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, LyraError::InsufficientFunds(_) | LyraError::Deserialization(_))
+    }
+
+    pub fn transport(e: impl fmt::Display) -> Self {
+        LyraError::Transport(e.to_string())
+    }
+
+    pub fn signing(e: impl fmt::Display) -> Self {
+        LyraError::Signing(e.to_string())
+    }
+
+    pub fn deserialization(e: impl fmt::Display) -> Self {
+        LyraError::Deserialization(e.to_string())
+    }
+}
+
+impl fmt::Display for LyraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LyraError::Transport(e) => write!(f, "Transport error: {}", e),
+            LyraError::Timeout { method, timeout } => {
+                write!(f, "{} timed out after {} ms", method, timeout.as_millis())
+            }
+            LyraError::RateLimited { method, retry_after } => {
+                write!(f, "{} rate limited, retry after {} ms", method, retry_after.as_millis())
+            }
+            LyraError::InsufficientFunds(e)
+            | LyraError::SignatureExpired(e)
+            | LyraError::OrderNotOpen(e)
+            | LyraError::Unauthorized(e)
+            | LyraError::PostOnlyReject(e)
+            | LyraError::Rpc(e) => write!(f, "RPC error {}: {}", e.code, e.message),
+            LyraError::PriceProtection(e) => write!(f, "Price protection: {}", e),
+            LyraError::Signing(e) => write!(f, "Signing error: {}", e),
+            LyraError::Deserialization(e) => write!(f, "Deserialization error: {}", e),
+            LyraError::Other(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for LyraError {}
+
+impl From<RPCError> for LyraError {
+    fn from(e: RPCError) -> Self {
+        match e.code {
+            c if INSUFFICIENT_FUNDS_CODES.contains(&c) => LyraError::InsufficientFunds(e),
+            c if SIGNATURE_EXPIRED_CODES.contains(&c) => LyraError::SignatureExpired(e),
+            c if ORDER_NOT_OPEN_CODES.contains(&c) => LyraError::OrderNotOpen(e),
+            c if UNAUTHORIZED_CODES.contains(&c) => LyraError::Unauthorized(e),
+            POST_ONLY_REJECT_CODE => LyraError::PostOnlyReject(e),
+            _ => LyraError::Rpc(e),
+        }
+    }
+}
+
+impl From<RPCErrorResponse> for LyraError {
+    fn from(e: RPCErrorResponse) -> Self {
+        LyraError::from(e.error)
+    }
+}
+
+/// Keeps a `LyraError` that was wrapped into an `anyhow::Error` on the way
+impl From<anyhow::Error> for LyraError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<LyraError>() {
+            Ok(e) => e,
+            Err(e) => LyraError::Other(e),
+        }
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for LyraError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        LyraError::transport(e)
+    }
+}
+
+impl From<reqwest::Error> for LyraError {
+    fn from(e: reqwest::Error) -> Self {
+        LyraError::transport(e)
+    }
+}
+
+impl From<serde_json::Error> for LyraError {
+    fn from(e: serde_json::Error) -> Self {
+        LyraError::deserialization(e)
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for LyraError {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        LyraError::deserialization(e)
+    }
+}
+
+impl From<ethers::signers::WalletError> for LyraError {
+    fn from(e: ethers::signers::WalletError) -> Self {
+        LyraError::signing(e)
+    }
+}
+
+impl From<uuid::Error> for LyraError {
+    fn from(e: uuid::Error) -> Self {
+        LyraError::deserialization(e)
+    }
+}
+
+impl From<std::num::ParseIntError> for LyraError {
+    fn from(e: std::num::ParseIntError) -> Self {
+        LyraError::deserialization(e)
+    }
+}
@@ -0,0 +1,210 @@
+use crate::json_rpc::{WsClient, WsClientExt};
+use crate::output::{print_result, OutputFormat};
+use anyhow::{format_err, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, ValueEnum};
+use orderbook_types::generated::private_get_trade_history::{
+    PrivateGetTradeHistoryParamsSchema, PrivateGetTradeHistoryResponseSchema, TradeResponseSchema,
+};
+use serde_json::json;
+use std::collections::HashSet;
+use tracing::info;
+
+/// Max page size of private/get_trade_history
+const PAGE_SIZE: i64 = 1000;
+
+const HISTORY_COLUMNS: [&str; 17] = [
+    "timestamp",
+    "trade_id",
+    "order_id",
+    "subaccount_id",
+    "instrument_name",
+    "direction",
+    "trade_amount",
+    "trade_price",
+    "trade_fee",
+    "index_price",
+    "mark_price",
+    "realized_pnl",
+    "liquidity_role",
+    "label",
+    "is_transfer",
+    "tx_status",
+    "tx_hash",
+];
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum HistoryFormat {
+    #[default]
+    Csv,
+    /// Requires the `parquet` feature
+    Parquet,
+}
+
+/// Exports the fills of a subaccount (with fees, prices and timestamps) to a CSV or Parquet file
+#[derive(Args, Debug)]
+pub struct CliHistory {
+    #[arg(short, long)]
+    pub subaccount: i64,
+
+    /// Start of the range, as a date (2024-05-01), an RFC 3339 time or a timestamp in ms
+    #[arg(long)]
+    pub from: String,
+
+    /// End of the range (same formats as --from), defaults to now
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// Only export fills of this instrument
+    #[arg(long)]
+    pub instrument: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = HistoryFormat::Csv)]
+    pub format: HistoryFormat,
+
+    /// Output file, defaults to trades_{subaccount}.{csv,parquet}
+    #[arg(long)]
+    pub file: Option<String>,
+}
+
+/// Parses a date (midnight UTC), an RFC 3339 time or a timestamp in ms into ms since epoch
+pub(crate) fn parse_timestamp_ms(value: &str) -> Result<i64> {
+    if let Ok(ms) = value.parse::<i64>() {
+        return Ok(ms);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.timestamp_millis());
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format_err!("Invalid date or timestamp: {}", value))?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp_millis())
+}
+
+/// Serialized form of the generated enums, e.g. "buy" or "maker"
+fn enum_str<T: serde::Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+fn trade_row(trade: &TradeResponseSchema) -> Vec<String> {
+    vec![
+        trade.timestamp.to_string(),
+        trade.trade_id.clone(),
+        trade.order_id.clone(),
+        trade.subaccount_id.to_string(),
+        trade.instrument_name.clone(),
+        enum_str(&trade.direction),
+        trade.trade_amount.to_string(),
+        trade.trade_price.to_string(),
+        trade.trade_fee.to_string(),
+        trade.index_price.to_string(),
+        trade.mark_price.to_string(),
+        trade.realized_pnl.to_string(),
+        enum_str(&trade.liquidity_role),
+        trade.label.clone(),
+        trade.is_transfer.to_string(),
+        enum_str(&trade.tx_status),
+        trade.tx_hash.clone().unwrap_or_default(),
+    ]
+}
+
+impl CliHistory {
+    pub async fn export(&self, output: OutputFormat) -> Result<()> {
+        let from_timestamp = parse_timestamp_ms(&self.from)?;
+        // a fixed end keeps the pages stable while new fills come in
+        let to_timestamp = match &self.to {
+            Some(to) => parse_timestamp_ms(to)?,
+            None => Utc::now().timestamp_millis(),
+        };
+        if from_timestamp >= to_timestamp {
+            return Err(format_err!("--from must be before --to"));
+        }
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+
+        let mut trades = vec![];
+        let mut num_pages = 1;
+        let mut page = 1;
+        while page <= num_pages {
+            let params = PrivateGetTradeHistoryParamsSchema {
+                from_timestamp,
+                instrument_name: self.instrument.clone(),
+                order_id: None,
+                page,
+                page_size: PAGE_SIZE,
+                subaccount_id: self.subaccount,
+                to_timestamp,
+            };
+            let result = client
+                .send_rpc::<_, PrivateGetTradeHistoryResponseSchema>(
+                    "private/get_trade_history",
+                    params,
+                )
+                .await?
+                .into_result()?
+                .result;
+            num_pages = result.pagination.num_pages;
+            info!("Fetched page {} of {} with {} trades", page, num_pages, result.trades.len());
+            trades.extend(result.trades);
+            page += 1;
+        }
+        let mut seen = HashSet::new();
+        trades.retain(|t| seen.insert(t.trade_id.clone()));
+        trades.sort_by_key(|t| t.timestamp);
+
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => match self.format {
+                HistoryFormat::Csv => format!("trades_{}.csv", self.subaccount),
+                HistoryFormat::Parquet => format!("trades_{}.parquet", self.subaccount),
+            },
+        };
+        match self.format {
+            HistoryFormat::Csv => write_csv(&file, &trades)?,
+            HistoryFormat::Parquet => write_parquet(&file, &trades)?,
+        }
+        let summary = json!({ "file": file, "trades": trades.len() });
+        print_result("history", &summary, output)
+    }
+}
+
+fn write_csv(file: &str, trades: &[TradeResponseSchema]) -> Result<()> {
+    let mut writer = csv::Writer::from_path(file)?;
+    writer.write_record(HISTORY_COLUMNS)?;
+    for trade in trades {
+        writer.write_record(trade_row(trade))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Timestamps are stored as Int64 ms, everything else as strings to keep decimal precision
+#[cfg(feature = "parquet")]
+fn write_parquet(file: &str, trades: &[TradeResponseSchema]) -> Result<()> {
+    use arrow::array::{ArrayRef, Int64Array, StringArray};
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
+
+    let rows = trades.iter().map(trade_row).collect::<Vec<_>>();
+    let timestamps: ArrayRef =
+        Arc::new(Int64Array::from_iter_values(trades.iter().map(|t| t.timestamp)));
+    let mut columns = vec![(HISTORY_COLUMNS[0], timestamps)];
+    for (i, name) in HISTORY_COLUMNS.iter().enumerate().skip(1) {
+        let values: ArrayRef =
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r[i].as_str())));
+        columns.push((*name, values));
+    }
+    let batch = RecordBatch::try_from_iter(columns)?;
+    let mut writer = ArrowWriter::try_new(std::fs::File::create(file)?, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_file: &str, _trades: &[TradeResponseSchema]) -> Result<()> {
+    Err(format_err!("Parquet export requires building with --features parquet"))
+}
@@ -1,23 +1,25 @@
-use anyhow::{Error, Result};
-use bigdecimal::BigDecimal;
-use ethers::prelude::{LocalWallet, Signer};
+use anyhow::format_err;
+use bigdecimal::{BigDecimal, Zero};
+use ethers::prelude::Signer;
 use ethers::utils::hex;
+use futures::future::BoxFuture;
 use futures_util::{FutureExt, SinkExt, StreamExt};
-use log::{debug, error, info, warn};
-use reqwest::{header::HeaderMap, Client};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::future::Future;
 use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio::sync::Mutex;
-use tokio::time::Instant;
+use tokio::time::{Duration, Instant};
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::WebSocketStream;
 use tokio_tungstenite::{connect_async, tungstenite, MaybeTlsStream};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use orderbook_types::generated::private_cancel::{
@@ -29,28 +31,59 @@ use orderbook_types::generated::private_cancel_all::{
 use orderbook_types::generated::private_cancel_by_instrument::{
     PrivateCancelByInstrumentParamsSchema, PrivateCancelByInstrumentResponseSchema,
 };
+use orderbook_types::generated::private_cancel_by_label::{
+    PrivateCancelByLabelParamsSchema, PrivateCancelByLabelResponseSchema,
+};
+use orderbook_types::generated::private_create_subaccount::PrivateCreateSubaccountResponseSchema;
 use orderbook_types::generated::private_deposit::PrivateDepositResponseSchema;
+use orderbook_types::generated::private_get_mmp_config::{
+    PrivateGetMmpConfigParamsSchema, PrivateGetMmpConfigResponseSchema,
+};
+use orderbook_types::generated::private_get_open_orders::PrivateGetOpenOrdersParamsSchema;
+use orderbook_types::generated::private_get_orders::PrivateGetOrdersParamsSchema;
 use orderbook_types::generated::private_get_subaccount::MarginType;
+use orderbook_types::generated::private_get_trade_history::PrivateGetTradeHistoryParamsSchema;
+use orderbook_types::generated::private_reset_mmp::{
+    PrivateResetMmpParamsSchema, PrivateResetMmpResponseSchema,
+};
 use orderbook_types::generated::private_set_cancel_on_disconnect::{
     PrivateSetCancelOnDisconnectParamsSchema, PrivateSetCancelOnDisconnectResponseSchema,
 };
+use orderbook_types::generated::private_set_mmp_config::{
+    PrivateSetMmpConfigParamsSchema, PrivateSetMmpConfigResponseSchema,
+};
+use orderbook_types::generated::private_transfer_erc20::PrivateTransferErc20ResponseSchema;
+use orderbook_types::generated::private_transfer_position::PrivateTransferPositionResponseSchema;
 use orderbook_types::generated::private_withdraw::PrivateWithdrawResponseSchema;
 use orderbook_types::generated::public_login::PublicLoginResponseSchema;
 use orderbook_types::generated::subscribe::{SubscribeParamsSchema, SubscribeResponseSchema};
+use orderbook_types::generated::unsubscribe::UnsubscribeParamsSchema;
 use orderbook_types::types::liquidations::{
     AuctionDetailsSchema, LiquidationParams, SendLiquidateResponse,
 };
-use orderbook_types::types::orders::{ReplaceResponse, SendOrderResponse};
+use orderbook_types::types::orders::{
+    OrderResponse, ReplaceResponse, SendOrderResponse, SendOrderResult, TimeInForce, TradeResponse,
+};
 use orderbook_types::types::rfqs::{ExecuteQuoteParams, QuoteParams, QuoteResultPublic};
 use orderbook_types::types::tickers::InstrumentTicker;
-use orderbook_types::types::RPCErrorResponse;
+use orderbook_types::types::{RPCError, RPCErrorResponse};
 
 use crate::actions::{
-    new_deposit_params, new_execute_params, new_liquidate_params, new_order_params,
-    new_quote_params, new_replace_params, new_withdraw_params, DepositParams, OrderArgs,
-    OrderParams, QuoteArgs, ReplaceParams, WithdrawParams,
+    new_create_subaccount_params, new_deposit_params, new_execute_params, new_liquidate_params,
+    new_order_params, new_quote_params, new_replace_params, new_transfer_params,
+    new_transfer_position_params, new_withdraw_params, post_only_price, CollateralAsset,
+    CreateSubaccountParams, DepositParams, OrderArgs, OrderParams, QuoteArgs, ReplaceParams,
+    TransferParams, TransferPositionParams, WithdrawParams,
 };
-use crate::auth::{load_signer, sign_auth_msg};
+use crate::auth::{session_signer, sign_auth_msg};
+use crate::config::LyraConfig;
+use crate::endpoints::Endpoints;
+use crate::error::{LyraError, Result, POST_ONLY_REJECT_CODE};
+use crate::latency;
+use crate::rate_limit::{RateLimiter, RateLimits, RATE_LIMIT_ERROR_CODE};
+use crate::session::rotated_session_key;
+use crate::signer::LyraSigner;
+use crate::tickers::TickerCache;
 
 type SocketError = tungstenite::error::Error;
 
@@ -65,7 +98,7 @@ impl<T> Response<T> {
     pub fn into_result(self) -> Result<T> {
         match self {
             Response::Success(s) => Ok(s),
-            Response::Error(e) => Err(Error::new(e)),
+            Response::Error(e) => Err(LyraError::from(e)),
         }
     }
 }
@@ -83,17 +116,75 @@ pub struct NotificationParams<D> {
     pub data: D,
 }
 
+/// A request sent on the socket that has not received its reply (or timed out) yet
+#[derive(Debug, Clone)]
+pub struct InFlightRequest {
+    pub id: Uuid,
+    pub method: String,
+    pub sent_at: Instant,
+}
+
+/// Called after the client has reconnected, re-logged in and replayed its subscriptions.
+/// Use it to resync any state that may have missed notifications while disconnected.
+pub type ReconnectCallback = Arc<dyn Fn() -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+/// Called with the channels that went quiet beyond their stale threshold, before they are
+/// resubscribed. See `WsClientExt::set_stale_threshold`.
+pub type StaleCallback =
+    Arc<dyn Fn(Vec<String>) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
 pub struct WsClientState {
     socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    url: String, // endpoint of the socket, see `Endpoints::ws`
     messages: HashMap<Uuid, Value>,
+    in_flight: HashMap<Uuid, InFlightRequest>, // replies to other ids are dropped as late
+    request_timeout: Duration,
     notifications: Vec<Value>,
     owner: String,
-    signer: Option<LocalWallet>,
+    signer: Option<LyraSigner>,
+    config: Arc<LyraConfig>,
+    rate_limiter: Arc<RateLimiter>,
+
+    // Reconnect state
+    subscriptions: Vec<String>,
+    generation: u64,
+    auto_reconnect: bool,
+    reconnect_lock: Arc<Mutex<()>>,
+    on_reconnect: Option<ReconnectCallback>,
+    cancel_on_disconnect: Option<bool>, // last setting sent, re-applied after a reconnect
+
+    // Heartbeat state, checked while subscribed
+    last_message: HashMap<String, Instant>, // by channel, since the (re)subscription if none yet
+    stale_thresholds: HashMap<String, Duration>,
+    resubscribed: HashSet<String>, // resubscribed as stale and quiet since
+    on_stale: Option<StaleCallback>,
+
+    // Session state, bumped / reset on every login
+    session_started: Option<Instant>,
+    session_generation: u64,
+    session_lifetime: Duration,
+    session_lock: Arc<Mutex<()>>,
 }
 
 /// A "shareable" (thread safe) lyra websocket client.
 pub type WsClient = Arc<Mutex<WsClientState>>;
 
+const MAX_RECONNECT_BACKOFF_SEC: u64 = 64;
+/// Default time to wait for the reply of a request, override with WS_REQUEST_TIMEOUT_MS
+const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 5000;
+/// Assumed session lifetime after a login, override with SESSION_LIFETIME_SEC
+const DEFAULT_SESSION_LIFETIME_SEC: u64 = 3600;
+/// Re-login this long before the session expires
+const SESSION_REFRESH_MARGIN_SEC: u64 = 300;
+const BATCH_ORDER_CONCURRENCY: usize = 8;
+/// Time the matching engine gets to reject an order after it was sent
+const ORDER_REJECT_AFTER_SEC: i64 = 5;
+/// Grace period after the reject timestamp of an order before looking up whether it was accepted
+const ORDER_LOOKUP_DELAY_MS: i64 = 1000;
+/// Label prefix of orders sent without a label by `submit_order_idempotent`
+const CLIENT_ORDER_ID_PREFIX: &str = "coid-";
+const HEARTBEAT_CHECK_SEC: u64 = 1;
+
 /// An interface for the wrapped / shared lyra websocket client.
 #[allow(async_fn_in_trait)]
 pub trait WsClientExt
@@ -101,6 +192,9 @@ where
     Self: Sized,
 {
     async fn new_client() -> Result<Self>;
+    /// Same as `new_client` but signs with the given config instead of `LyraConfig::current()`
+    async fn new_client_with_config(config: Arc<LyraConfig>) -> Result<Self>;
+    async fn get_config(&self) -> Arc<LyraConfig>;
     async fn get_owner(&self) -> String;
     async fn get_signer(&self) -> String;
     async fn close(&self) -> Result<()>;
@@ -108,7 +202,43 @@ where
     /// A task that will forever send ping messages to the server at a given interval.
     /// Use with `select!` if you'd like to be able to cancel it.
     async fn ping_interval(&self, interval_sec: u64) -> Result<()>;
+    /// Reconnects the socket with exponential backoff, re-logs in (if logged in before) and
+    /// replays all subscribed channels. Requests in flight during the disconnect are lost.
+    async fn reconnect(&self) -> Result<()>;
+    /// Auto reconnect is enabled by default, disable it if the caller manages reconnects itself.
+    async fn set_auto_reconnect(&self, enabled: bool);
+    async fn set_on_reconnect(&self, callback: ReconnectCallback);
+    /// Channels without a message for longer than `threshold` are resubscribed while
+    /// `subscribe` runs, and the connection is reconnected if they stay quiet after that.
+    /// Only set it for channels that publish periodically (e.g. tickers), not for e.g. fills.
+    async fn set_stale_threshold(&self, channels: Vec<String>, threshold: Duration);
+    async fn set_on_stale(&self, callback: StaleCallback);
+    /// Time since the last message of each subscribed channel, or since its subscription
+    async fn channel_ages(&self) -> HashMap<String, Duration>;
+    /// Replaces the limits (from `RateLimits::from_env` by default) of all requests sent with
+    /// this client. Requests already queued finish with the old limits.
+    async fn set_rate_limits(&self, limits: RateLimits);
+    /// Replaces the default timeout of `send_rpc` for requests sent from now on
+    async fn set_request_timeout(&self, timeout: Duration);
+    /// Requests still waiting for their reply, oldest first
+    async fn in_flight_requests(&self) -> Vec<InFlightRequest>;
+    /// Queues the request until the rate limiter allows it and retries with backoff while the
+    /// server rejects it as rate limited. Fails with `RateLimited` once the queue would take
+    /// longer than `max_queue_ms` or the retries are exhausted.
+    /// Private requests re-login first if the session is about to expire, and are retried once
+    /// after a re-login if they still fail with an auth error.
     async fn send_rpc<P, R>(&self, method: &str, params: P) -> Result<Response<R>>
+    where
+        P: Serialize + Debug + Clone,
+        R: for<'de> Deserialize<'de> + Debug + Serialize + Clone;
+    /// Same as `send_rpc` but fails with `Timeout` if a reply takes longer than `timeout`.
+    /// Timed out requests are not retried since the server may have processed them.
+    async fn send_rpc_with_timeout<P, R>(
+        &self,
+        method: &str,
+        params: P,
+        timeout: Duration,
+    ) -> Result<Response<R>>
     where
         P: Serialize + Debug + Clone,
         R: for<'de> Deserialize<'de> + Debug + Serialize + Clone;
@@ -116,10 +246,14 @@ where
     async fn enable_cancel_on_disconnect(
         &self,
     ) -> Result<Response<PrivateSetCancelOnDisconnectResponseSchema>>;
+    /// Cancels all open orders of the wallet once a connection drops. The server keeps the
+    /// setting per wallet, the client re-applies it after every reconnect.
     async fn set_cancel_on_disconnect(
         &self,
         enabled: bool,
     ) -> Result<Response<PrivateSetCancelOnDisconnectResponseSchema>>;
+    /// The setting last accepted by the server, None if never set with this client
+    async fn get_cancel_on_disconnect(&self) -> Option<bool>;
     async fn deposit(
         &self,
         subaccount_id: i64,
@@ -133,12 +267,68 @@ where
         amount: BigDecimal,
         asset_name: String,
     ) -> Result<Response<PrivateWithdrawResponseSchema>>;
+    /// Creates a subaccount of `margin_type` funded with a deposit from the owner wallet
+    async fn create_subaccount(
+        &self,
+        amount: BigDecimal,
+        asset_name: String,
+        margin_type: MarginType,
+    ) -> Result<Response<PrivateCreateSubaccountResponseSchema>>;
+    /// Moves collateral between two subaccounts of the owner
+    async fn transfer_between_subaccounts(
+        &self,
+        subaccount_id: i64,
+        recipient_subaccount_id: i64,
+        amount: BigDecimal,
+        asset_name: String,
+    ) -> Result<Response<PrivateTransferErc20ResponseSchema>>;
+    /// Moves `amount` of a position (negative for shorts) to another subaccount of the owner at
+    /// `limit_price` without trading through the book
+    async fn transfer_position(
+        &self,
+        ticker: &InstrumentTicker,
+        subaccount_id: i64,
+        recipient_subaccount_id: i64,
+        amount: BigDecimal,
+        limit_price: BigDecimal,
+    ) -> Result<Response<PrivateTransferPositionResponseSchema>>;
     async fn send_order(
         &self,
         ticker: &InstrumentTicker,
         subaccount_id: i64,
         args: OrderArgs,
     ) -> Result<Response<SendOrderResponse>>;
+    /// Sends the order as post-only. On a post-only reject it is re-priced one tick inside the
+    /// best opposing quote of a fresh ticker and resent, at most `max_retries` times.
+    /// Returns the response of the last attempt, i.e. the resting order or the final reject.
+    async fn send_order_post_only_retry(
+        &self,
+        ticker: &InstrumentTicker,
+        subaccount_id: i64,
+        args: OrderArgs,
+        max_retries: u32,
+    ) -> Result<Response<SendOrderResponse>>;
+    /// Sends the order so that it can be retried after an ambiguous failure, i.e. a transport
+    /// error or timeout that leaves open whether the order was accepted. The order is signed
+    /// once and its nonce serves as the client order id (and as the label if it has none).
+    /// After an ambiguous failure the order's reject timestamp is waited out, then the nonce is
+    /// looked up in the open orders and the order history along with its trades. Only an order
+    /// that was not accepted is resent, at most `max_retries` times, with the same nonce so that
+    /// it can never fill twice.
+    async fn submit_order_idempotent(
+        &self,
+        ticker: &InstrumentTicker,
+        subaccount_id: i64,
+        args: OrderArgs,
+        max_retries: u32,
+    ) -> Result<SendOrderResult>;
+    /// Signs all orders concurrently up front, then pipelines them as individual `private/order` RPCs
+    /// with at most `BATCH_ORDER_CONCURRENCY` in flight. Results are in the input order.
+    async fn send_batch_orders(
+        &self,
+        subaccount_id: i64,
+        orders: Vec<(&InstrumentTicker, OrderArgs)>,
+    ) -> Result<Vec<Result<Response<SendOrderResponse>>>>;
     async fn send_replace(
         &self,
         ticker: &InstrumentTicker,
@@ -165,6 +355,12 @@ where
         percent_bid: BigDecimal,
         details: &AuctionDetailsSchema,
     ) -> Result<Response<SendLiquidateResponse>>;
+    async fn cancel(
+        &self,
+        subaccount_id: i64,
+        instrument_name: String,
+        order_id: Uuid,
+    ) -> Result<Response<PrivateCancelResponseSchema>>;
     async fn cancel_all(
         &self,
         subaccount_id: i64,
@@ -174,21 +370,49 @@ where
         subaccount_id: i64,
         instrument_name: String,
     ) -> Result<Response<PrivateCancelByInstrumentResponseSchema>>;
+    async fn cancel_by_label(
+        &self,
+        subaccount_id: i64,
+        label: String,
+    ) -> Result<Response<PrivateCancelByLabelResponseSchema>>;
+    /// Market maker protection of a currency: orders sent with `mmp` freeze the subaccount once
+    /// the amount or delta limits are traded within the interval
+    async fn set_mmp_config(
+        &self,
+        params: PrivateSetMmpConfigParamsSchema,
+    ) -> Result<Response<PrivateSetMmpConfigResponseSchema>>;
+    async fn get_mmp_config(
+        &self,
+        subaccount_id: i64,
+        currency: Option<String>,
+    ) -> Result<Response<PrivateGetMmpConfigResponseSchema>>;
+    /// Unfreezes the subaccount after an MMP trigger, for all currencies if None
+    async fn reset_mmp(
+        &self,
+        subaccount_id: i64,
+        currency: Option<String>,
+    ) -> Result<Response<PrivateResetMmpResponseSchema>>;
     async fn subscribe<Fut, Data>(
         &self,
         channels: Vec<String>,
         handler: impl FnMut(Data) -> Fut,
     ) -> Result<()>
     where
-        Fut: Future<Output = Result<()>>,
+        Fut: Future<Output = anyhow::Result<()>>,
         Data: for<'de> Deserialize<'de> + Debug;
 }
 
 impl WsClientExt for WsClient {
     async fn new_client() -> Result<Self> {
-        let client = WsClientState::new().await?;
+        WsClient::new_client_with_config(LyraConfig::current()?).await
+    }
+    async fn new_client_with_config(config: Arc<LyraConfig>) -> Result<Self> {
+        let client = WsClientState::new(config).await?;
         Ok(Arc::new(Mutex::new(client)))
     }
+    async fn get_config(&self) -> Arc<LyraConfig> {
+        self.lock().await.config.clone()
+    }
     async fn get_owner(&self) -> String {
         self.lock().await.owner.clone()
     }
@@ -205,45 +429,146 @@ impl WsClientExt for WsClient {
     async fn ping_interval(&self, interval_sec: u64) -> Result<()> {
         WsClientState::ping_interval(self.clone(), interval_sec).await
     }
+    async fn reconnect(&self) -> Result<()> {
+        let generation = self.lock().await.generation;
+        WsClientState::reconnect_with_backoff(self, generation).await
+    }
+    async fn set_auto_reconnect(&self, enabled: bool) {
+        self.lock().await.auto_reconnect = enabled;
+    }
+    async fn set_on_reconnect(&self, callback: ReconnectCallback) {
+        self.lock().await.on_reconnect = Some(callback);
+    }
+    async fn set_stale_threshold(&self, channels: Vec<String>, threshold: Duration) {
+        let mut client_guard = self.lock().await;
+        for channel in channels {
+            client_guard.stale_thresholds.insert(channel, threshold);
+        }
+    }
+    async fn set_on_stale(&self, callback: StaleCallback) {
+        self.lock().await.on_stale = Some(callback);
+    }
+    async fn channel_ages(&self) -> HashMap<String, Duration> {
+        let client_guard = self.lock().await;
+        let ages = client_guard.subscriptions.iter().filter_map(|channel| {
+            let last_message = client_guard.last_message.get(channel)?;
+            Some((channel.clone(), last_message.elapsed()))
+        });
+        ages.collect()
+    }
+    async fn set_rate_limits(&self, limits: RateLimits) {
+        self.lock().await.rate_limiter = Arc::new(RateLimiter::new(limits));
+    }
+    async fn set_request_timeout(&self, timeout: Duration) {
+        self.lock().await.request_timeout = timeout;
+    }
+    async fn in_flight_requests(&self) -> Vec<InFlightRequest> {
+        let mut requests = self.lock().await.in_flight.values().cloned().collect::<Vec<_>>();
+        requests.sort_by_key(|r| r.sent_at);
+        requests
+    }
     async fn send_rpc<P, R>(&self, method: &str, params: P) -> Result<Response<R>>
     where
         P: Serialize + Debug + Clone,
         R: for<'de> Deserialize<'de> + Debug + Serialize + Clone,
     {
-        info!(
-            "Sending: {}, params: {}",
-            method,
-            serde_json::to_string_pretty(&params).unwrap_or("could not serialize".into())
-        );
-        let this_id = WsClientState::send_to_socket(&self, method, params).await?;
-        let res = WsClientState::listen_and_wait_for::<R>(&self, this_id).await;
-        if let Ok(res) = &res {
-            match res {
-                Response::Success(s) => info!("Received RPC result"),
-                Response::Error(e) => error!("Received error: {:?}", e),
+        let timeout = self.lock().await.request_timeout;
+        self.send_rpc_with_timeout(method, params, timeout).await
+    }
+    async fn send_rpc_with_timeout<P, R>(
+        &self,
+        method: &str,
+        params: P,
+        timeout: Duration,
+    ) -> Result<Response<R>>
+    where
+        P: Serialize + Debug + Clone,
+        R: for<'de> Deserialize<'de> + Debug + Serialize + Clone,
+    {
+        let rate_limiter = self.lock().await.rate_limiter.clone();
+        let is_private = method.starts_with("private/");
+        let mut retry = 0;
+        let mut is_auth_retried = false;
+        loop {
+            if is_private {
+                WsClientState::refresh_session_if_expiring(self).await?;
             }
-        } else {
-            error!("Error decoding response {:?}", res);
+            let session_generation = self.lock().await.session_generation;
+            rate_limiter.acquire(method).await?;
+            let json_params =
+                serde_json::to_string(&params).unwrap_or("could not serialize".into());
+            info!(method, params = %json_params, "Sending RPC");
+            let sent_at = Instant::now();
+            let this_id = WsClientState::send_to_socket(&self, method, params.clone()).await?;
+            let res = WsClientState::listen_and_wait_for::<R>(&self, this_id, timeout).await;
+            if res.is_ok() {
+                latency::record(&latency::rpc_name(method), sent_at.elapsed());
+            }
+            match &res {
+                Ok(Response::Error(e)) if e.error.code == RATE_LIMIT_ERROR_CODE => {
+                    let retry_after = rate_limiter.limits().backoff(retry);
+                    if retry >= rate_limiter.limits().max_retries {
+                        error!(method, retry, "Still rate limited after the retries");
+                        let method = method.to_string();
+                        return Err(LyraError::RateLimited { method, retry_after });
+                    }
+                    let retry_after_ms = retry_after.as_millis() as u64;
+                    warn!(method, retry_after_ms, "Rate limited, retrying");
+                    // queued requests wait out the backoff as well
+                    rate_limiter.penalize(retry_after).await;
+                    retry += 1;
+                    continue;
+                }
+                Ok(Response::Error(e))
+                    if is_private
+                        && !is_auth_retried
+                        && LyraError::is_unauthorized_code(e.error.code) =>
+                {
+                    warn!(method, error = ?e.error, "Unauthorized, re-logging in");
+                    WsClientState::relogin_if_current(self, session_generation).await?;
+                    is_auth_retried = true;
+                    continue;
+                }
+                Ok(Response::Success(_)) => info!(method, "Received RPC result"),
+                Ok(Response::Error(e)) => error!(method, error = ?e, "Received RPC error"),
+                Err(e) => error!(method, error = ?e, "Error decoding RPC response"),
+            }
+            return res;
         }
-        res
     }
     async fn login(&self) -> Result<Response<PublicLoginResponseSchema>> {
-        let wallet = load_signer().await;
-        let owner = std::env::var("OWNER_PUBLIC_KEY").expect("OWNER_PUBLIC_KEY must be set");
-        let login_params = sign_auth_msg(&wallet).await;
+        let config = self.get_config().await;
+        let wallet = session_signer(&config).await?;
+        let owner = hex::encode_prefixed(config.owner()?);
+        let login_params = sign_auth_msg(&wallet, &owner).await?;
         WsClientState::set_signer(self, wallet).await;
         WsClientState::set_owner(self, owner).await;
-        self.send_rpc("public/login", login_params).await
+        let res = self.send_rpc("public/login", login_params).await?;
+        if let Response::Success(_) = &res {
+            WsClientState::session_started(self).await;
+        }
+        Ok(res)
     }
     async fn set_cancel_on_disconnect(
         &self,
         enabled: bool,
     ) -> Result<Response<PrivateSetCancelOnDisconnectResponseSchema>> {
-        self.send_rpc(
-            "private/set_cancel_on_disconnect",
-            PrivateSetCancelOnDisconnectParamsSchema { enabled, wallet: self.get_owner().await },
-        )
-        .await
+        let res = self
+            .send_rpc(
+                "private/set_cancel_on_disconnect",
+                PrivateSetCancelOnDisconnectParamsSchema {
+                    enabled,
+                    wallet: self.get_owner().await,
+                },
+            )
+            .await?;
+        if let Response::Success(_) = &res {
+            self.lock().await.cancel_on_disconnect = Some(enabled);
+        }
+        Ok(res)
+    }
+    async fn get_cancel_on_disconnect(&self) -> Option<bool> {
+        self.lock().await.cancel_on_disconnect
     }
     async fn enable_cancel_on_disconnect(
         &self,
@@ -272,6 +597,53 @@ impl WsClientExt for WsClient {
             WsClientState::new_signed_withdraw(self, subaccount_id, amount, asset_name).await?;
         self.send_rpc("private/withdraw", withdraw_params).await
     }
+    async fn create_subaccount(
+        &self,
+        amount: BigDecimal,
+        asset_name: String,
+        margin_type: MarginType,
+    ) -> Result<Response<PrivateCreateSubaccountResponseSchema>> {
+        let create_params =
+            WsClientState::new_signed_create_subaccount(self, amount, asset_name, margin_type)
+                .await?;
+        self.send_rpc("private/create_subaccount", create_params).await
+    }
+    async fn transfer_between_subaccounts(
+        &self,
+        subaccount_id: i64,
+        recipient_subaccount_id: i64,
+        amount: BigDecimal,
+        asset_name: String,
+    ) -> Result<Response<PrivateTransferErc20ResponseSchema>> {
+        let transfer_params = WsClientState::new_signed_transfer(
+            self,
+            subaccount_id,
+            recipient_subaccount_id,
+            amount,
+            asset_name,
+        )
+        .await?;
+        self.send_rpc("private/transfer_erc20", transfer_params).await
+    }
+    async fn transfer_position(
+        &self,
+        ticker: &InstrumentTicker,
+        subaccount_id: i64,
+        recipient_subaccount_id: i64,
+        amount: BigDecimal,
+        limit_price: BigDecimal,
+    ) -> Result<Response<PrivateTransferPositionResponseSchema>> {
+        let transfer_params = WsClientState::new_signed_transfer_position(
+            self,
+            ticker,
+            subaccount_id,
+            recipient_subaccount_id,
+            amount,
+            limit_price,
+        )
+        .await?;
+        self.send_rpc("private/transfer_position", transfer_params).await
+    }
     async fn send_order(
         &self,
         ticker: &InstrumentTicker,
@@ -280,7 +652,132 @@ impl WsClientExt for WsClient {
     ) -> Result<Response<SendOrderResponse>> {
         let order_params =
             WsClientState::new_signed_order(self, ticker, subaccount_id, args).await?;
-        self.send_rpc("private/order", order_params).await
+        let sent_at = Instant::now();
+        let res = self.send_rpc("private/order", order_params).await;
+        if res.is_ok() {
+            latency::record(latency::ORDER_ACK, sent_at.elapsed());
+        }
+        res
+    }
+    async fn send_order_post_only_retry(
+        &self,
+        ticker: &InstrumentTicker,
+        subaccount_id: i64,
+        args: OrderArgs,
+        max_retries: u32,
+    ) -> Result<Response<SendOrderResponse>> {
+        let mut args = OrderArgs { time_in_force: TimeInForce::PostOnly, ..args };
+        let mut ticker = ticker.clone();
+        let mut retry = 0;
+        loop {
+            let res = self.send_order(&ticker, subaccount_id, args.clone()).await?;
+            match &res {
+                Response::Error(e)
+                    if e.error.code == POST_ONLY_REJECT_CODE && retry < max_retries =>
+                {
+                    let cache = TickerCache::global();
+                    cache.invalidate(&ticker.instrument_name).await;
+                    ticker = cache.get(&ticker.instrument_name).await?;
+                    let price = post_only_price(&ticker, args.direction, &args.limit_price);
+                    warn!(
+                        instrument = %ticker.instrument_name,
+                        rejected_price = %args.limit_price,
+                        %price,
+                        "Post-only order rejected, retrying"
+                    );
+                    args.limit_price = price;
+                    retry += 1;
+                }
+                _ => return Ok(res),
+            }
+        }
+    }
+    async fn submit_order_idempotent(
+        &self,
+        ticker: &InstrumentTicker,
+        subaccount_id: i64,
+        args: OrderArgs,
+        max_retries: u32,
+    ) -> Result<SendOrderResult> {
+        let mut order_params =
+            WsClientState::new_signed_order(self, ticker, subaccount_id, args).await?;
+        // the label is not signed, so it can carry the nonce
+        if order_params.label.is_empty() {
+            order_params.label = format!("{}{}", CLIENT_ORDER_ID_PREFIX, order_params.nonce);
+        }
+        let mut retry = 0;
+        loop {
+            let sent_at = Instant::now();
+            let res = self.send_rpc::<_, SendOrderResponse>("private/order", order_params.clone());
+            let e = match res.await {
+                Ok(res) => {
+                    latency::record(latency::ORDER_ACK, sent_at.elapsed());
+                    return Ok(res.into_result()?.result);
+                }
+                Err(e @ (LyraError::Transport(_) | LyraError::Timeout { .. })) => e,
+                Err(e) => return Err(e),
+            };
+            warn!(
+                instrument = %order_params.instrument_name,
+                nonce = order_params.nonce,
+                error = %e,
+                "Order failed, looking it up"
+            );
+            // the order can not be accepted anymore once the reject timestamp passed
+            let lookup_at = order_params.reject_timestamp + ORDER_LOOKUP_DELAY_MS;
+            let wait_ms = lookup_at - chrono::Utc::now().timestamp_millis();
+            if wait_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(wait_ms as u64)).await;
+            }
+            if let Some(found) = find_order_by_nonce(self, &order_params).await? {
+                info!(
+                    nonce = order_params.nonce,
+                    order_id = %found.order.order_id,
+                    status = ?found.order.order_status,
+                    "Order was accepted"
+                );
+                return Ok(found);
+            }
+            if retry >= max_retries {
+                error!(nonce = order_params.nonce, retry, "Order not accepted after the retries");
+                return Err(e);
+            }
+            info!(nonce = order_params.nonce, "Order was not accepted, resending");
+            order_params.reject_timestamp = (chrono::Utc::now()
+                + chrono::Duration::seconds(ORDER_REJECT_AFTER_SEC))
+            .timestamp_millis();
+            retry += 1;
+        }
+    }
+    async fn send_batch_orders(
+        &self,
+        subaccount_id: i64,
+        orders: Vec<(&InstrumentTicker, OrderArgs)>,
+    ) -> Result<Vec<Result<Response<SendOrderResponse>>>> {
+        let (signer, config) = {
+            let client_guard = self.lock().await;
+            (client_guard.signer.clone(), client_guard.config.clone())
+        };
+        let signer = signer.ok_or(LyraError::signing("Not logged in or signer not set"))?;
+        let signing = orders.into_iter().map(|(ticker, args)| {
+            let signer = signer.clone();
+            let config = config.clone();
+            let ticker = ticker.clone();
+            tokio::spawn(async move {
+                new_order_params(&signer, &config, &ticker, subaccount_id, args).await
+            })
+        });
+        let order_params = futures::future::try_join_all(signing)
+            .await
+            .map_err(|e| LyraError::Other(e.into()))?
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        let results = futures::stream::iter(order_params)
+            .map(|params| self.send_rpc::<_, SendOrderResponse>("private/order", params))
+            .buffered(BATCH_ORDER_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+        Ok(results)
     }
     async fn send_replace(
         &self,
@@ -331,6 +828,15 @@ impl WsClientExt for WsClient {
         .await?;
         self.send_rpc("private/liquidate", liquidate_params).await
     }
+    async fn cancel(
+        &self,
+        subaccount_id: i64,
+        instrument_name: String,
+        order_id: Uuid,
+    ) -> Result<Response<PrivateCancelResponseSchema>> {
+        let cancel_params = PrivateCancelParamsSchema { instrument_name, order_id, subaccount_id };
+        self.send_rpc("private/cancel", cancel_params).await
+    }
     async fn cancel_all(
         &self,
         subaccount_id: i64,
@@ -347,28 +853,60 @@ impl WsClientExt for WsClient {
             PrivateCancelByInstrumentParamsSchema { subaccount_id, instrument_name };
         self.send_rpc("private/cancel_by_instrument", cancel_params).await
     }
+    async fn cancel_by_label(
+        &self,
+        subaccount_id: i64,
+        label: String,
+    ) -> Result<Response<PrivateCancelByLabelResponseSchema>> {
+        let cancel_params = PrivateCancelByLabelParamsSchema { label, subaccount_id };
+        self.send_rpc("private/cancel_by_label", cancel_params).await
+    }
+    async fn set_mmp_config(
+        &self,
+        params: PrivateSetMmpConfigParamsSchema,
+    ) -> Result<Response<PrivateSetMmpConfigResponseSchema>> {
+        self.send_rpc("private/set_mmp_config", params).await
+    }
+    async fn get_mmp_config(
+        &self,
+        subaccount_id: i64,
+        currency: Option<String>,
+    ) -> Result<Response<PrivateGetMmpConfigResponseSchema>> {
+        let params = PrivateGetMmpConfigParamsSchema { currency, subaccount_id };
+        self.send_rpc("private/get_mmp_config", params).await
+    }
+    async fn reset_mmp(
+        &self,
+        subaccount_id: i64,
+        currency: Option<String>,
+    ) -> Result<Response<PrivateResetMmpResponseSchema>> {
+        let params = PrivateResetMmpParamsSchema { currency, subaccount_id };
+        self.send_rpc("private/reset_mmp", params).await
+    }
     async fn subscribe<Fut, Data>(
         &self,
         channels: Vec<String>,
         handler: impl FnMut(Data) -> Fut,
     ) -> Result<()>
     where
-        Fut: Future<Output = Result<()>>,
+        Fut: Future<Output = anyhow::Result<()>>,
         Data: for<'de> Deserialize<'de> + Debug,
     {
-        let sub_params = SubscribeParamsSchema { channels };
+        let sub_params = SubscribeParamsSchema { channels: channels.clone() };
         let sub_res = self.send_rpc::<_, SubscribeResponseSchema>("subscribe", sub_params).await;
         match sub_res {
             Ok(Response::Success(success)) => {
                 for (channel, status) in success.result.status.iter() {
                     if status != "ok" {
-                        return Err(Error::msg(format!("Subscription error: {channel}")));
+                        return Err(LyraError::Other(format_err!("Subscription error: {channel}")));
                     }
                 }
+                WsClientState::add_subscriptions(&self, channels).await;
                 WsClientState::listen_forever(&self, handler).await
             }
             Ok(Response::Error(e)) => {
-                Err(Error::msg(format!("RPC error while subscribing: {:?}", e)))
+                error!("RPC error while subscribing: {:?}", e);
+                Err(LyraError::from(e))
             }
             Err(e) => Err(e),
         }
@@ -377,24 +915,256 @@ impl WsClientExt for WsClient {
 
 /// Private methods for WsClientState, used by the extension trait method implementations.
 impl WsClientState {
-    async fn new() -> Result<Self> {
-        let url = std::env::var("WEBSOCKET_ADDRESS").expect("WEBSOCKET_ADDRESS must be set");
-        let (socket, _) = connect_async(&url).await?;
+    async fn new(config: Arc<LyraConfig>) -> Result<Self> {
+        let rate_limiter = Arc::new(RateLimiter::new(RateLimits::from_env()?));
+        let session_lifetime_sec = match std::env::var("SESSION_LIFETIME_SEC") {
+            Ok(value) => value.parse()?,
+            Err(_) => DEFAULT_SESSION_LIFETIME_SEC,
+        };
+        let request_timeout_ms = match std::env::var("WS_REQUEST_TIMEOUT_MS") {
+            Ok(value) => value.parse()?,
+            Err(_) => DEFAULT_REQUEST_TIMEOUT_MS,
+        };
+        let (socket, url) = WsClientState::connect_to_endpoint().await?;
         info!("Connected to {}", &url);
         Ok(WsClientState {
             socket,
+            url,
             messages: HashMap::new(),
+            in_flight: HashMap::new(),
+            request_timeout: Duration::from_millis(request_timeout_ms),
             notifications: Vec::new(),
             owner: String::new(),
             signer: None,
+            config,
+            rate_limiter,
+            subscriptions: Vec::new(),
+            generation: 0,
+            auto_reconnect: true,
+            reconnect_lock: Arc::new(Mutex::new(())),
+            on_reconnect: None,
+            cancel_on_disconnect: None,
+            last_message: HashMap::new(),
+            stale_thresholds: HashMap::new(),
+            resubscribed: HashSet::new(),
+            on_stale: None,
+            session_started: None,
+            session_generation: 0,
+            session_lifetime: Duration::from_secs(session_lifetime_sec),
+            session_lock: Arc::new(Mutex::new(())),
         })
     }
 
-    async fn set_signer(client: &WsClient, signer: LocalWallet) {
+    pub(crate) async fn add_subscriptions(client: &WsClient, channels: Vec<String>) {
+        let mut client_guard = client.lock().await;
+        for channel in channels {
+            client_guard.last_message.insert(channel.clone(), Instant::now());
+            if !client_guard.subscriptions.contains(&channel) {
+                client_guard.subscriptions.push(channel);
+            }
+        }
+    }
+
+    /// Stops replaying the channels after a reconnect, see `WsMux`
+    pub(crate) async fn remove_subscriptions(client: &WsClient, channels: &[String]) {
+        let mut client_guard = client.lock().await;
+        client_guard.subscriptions.retain(|channel| !channels.contains(channel));
+        for channel in channels {
+            client_guard.last_message.remove(channel);
+            client_guard.stale_thresholds.remove(channel);
+            client_guard.resubscribed.remove(channel);
+        }
+    }
+
+    /// Only one task reconnects at a time, others see the bumped generation and return early.
+    async fn reconnect_with_backoff(client: &WsClient, generation: u64) -> Result<()> {
+        let reconnect_lock = client.lock().await.reconnect_lock.clone();
+        let reconnect_guard = reconnect_lock.lock().await;
+        if client.lock().await.generation != generation {
+            return Ok(());
+        }
+        let mut backoff = 1;
+        loop {
+            match WsClientState::reconnect_and_replay(client).await {
+                Ok(()) => break,
+                Err(e) => {
+                    error!("Reconnect failed with {:?}, retrying in {} sec", e, backoff);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(backoff)).await;
+                    backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF_SEC);
+                }
+            }
+        }
+        let callback = {
+            let mut client_guard = client.lock().await;
+            client_guard.generation += 1;
+            client_guard.on_reconnect.clone()
+        };
+        info!("Reconnected, generation {}", generation + 1);
+        // release before the callback since it may send rpcs that trigger another reconnect
+        drop(reconnect_guard);
+        if let Some(callback) = callback {
+            if let Err(e) = callback().await {
+                error!("Reconnect callback failed with {:?}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Connects to the preferred websocket endpoint, failing over to the next ones
+    async fn connect_to_endpoint() -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, String)> {
+        Endpoints::ws()
+            .try_each(|url| async move {
+                let (socket, _) = connect_async(&url).await?;
+                Ok((socket, url))
+            })
+            .await
+    }
+
+    async fn reconnect_and_replay(client: &WsClient) -> Result<()> {
+        // the connection to the current endpoint was lost, prefer the others for a while
+        let previous_url = client.lock().await.url.clone();
+        Endpoints::ws().report_failure(&previous_url);
+        let (socket, url) = WsClientState::connect_to_endpoint().await?;
+        info!("Reconnected to {}", &url);
+        let (signer, owner, channels, cancel_on_disconnect) = {
+            let mut client_guard = client.lock().await;
+            client_guard.socket = socket;
+            client_guard.url = url;
+            client_guard.messages.clear();
+            client_guard.in_flight.clear();
+            client_guard.notifications.clear();
+            client_guard.resubscribed.clear();
+            let now = Instant::now();
+            client_guard.last_message.values_mut().for_each(|last_message| *last_message = now);
+            let owner = client_guard.owner.clone();
+            let channels = client_guard.subscriptions.clone();
+            (client_guard.relogin_signer(), owner, channels, client_guard.cancel_on_disconnect)
+        };
+        if let Some(wallet) = signer {
+            WsClientState::set_signer(client, wallet.clone()).await;
+            let login_params = sign_auth_msg(&wallet, &owner).await?;
+            WsClientState::raw_rpc(client, "public/login", login_params).await?;
+            WsClientState::session_started(client).await;
+            if let Some(enabled) = cancel_on_disconnect {
+                info!("Re-applying cancel on disconnect: {}", enabled);
+                let params = PrivateSetCancelOnDisconnectParamsSchema { enabled, wallet: owner };
+                WsClientState::raw_rpc(client, "private/set_cancel_on_disconnect", params).await?;
+            }
+        }
+        if !channels.is_empty() {
+            info!("Replaying subscriptions: {:?}", channels);
+            let sub_params = SubscribeParamsSchema { channels };
+            WsClientState::raw_rpc(client, "subscribe", sub_params).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends an rpc without any reconnect handling, used during the reconnect itself.
+    async fn raw_rpc<P>(client: &WsClient, method: &str, params: P) -> Result<Value>
+    where
+        P: Serialize,
+    {
+        let (rate_limiter, timeout) = {
+            let client_guard = client.lock().await;
+            (client_guard.rate_limiter.clone(), client_guard.request_timeout)
+        };
+        rate_limiter.acquire(method).await?;
+        let this_id = WsClientState::send_to_socket(client, method, params).await?;
+        let val = tokio::select! {
+            val = WsClientState::wait_for(client.clone(), this_id, timeout) => val?,
+            res = WsClientState::listen(client.clone()) => {
+                client.lock().await.in_flight.remove(&this_id);
+                return Err(LyraError::transport(format!("listen() exited during {method}: {res:?}")));
+            }
+        };
+        if let Some(e) = val.get("error") {
+            error!("RPC error during {method}: {e}");
+            return Err(serde_json::from_value::<RPCError>(e.clone())?.into());
+        }
+        Ok(val)
+    }
+
+    async fn session_started(client: &WsClient) {
+        let mut client_guard = client.lock().await;
+        client_guard.session_started = Some(Instant::now());
+        client_guard.session_generation += 1;
+    }
+
+    /// The signer to log in again with, the rotated session key once the owner's key was rotated
+    fn relogin_signer(&self) -> Option<LyraSigner> {
+        self.signer.as_ref()?;
+        rotated_session_key(&self.config).or(self.signer.clone())
+    }
+
+    /// When the session should be refreshed, None if not logged in
+    fn session_refresh_at(&self) -> Option<Instant> {
+        let margin = Duration::from_secs(SESSION_REFRESH_MARGIN_SEC);
+        self.session_started.map(|started| started + self.session_lifetime.saturating_sub(margin))
+    }
+
+    async fn refresh_session_if_expiring(client: &WsClient) -> Result<()> {
+        let (refresh_at, generation) = {
+            let client_guard = client.lock().await;
+            (client_guard.session_refresh_at(), client_guard.session_generation)
+        };
+        match refresh_at {
+            Some(refresh_at) if Instant::now() >= refresh_at => {
+                WsClientState::relogin_if_current(client, generation).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Re-logs in with the stored signer unless another task already did since `generation`
+    async fn relogin_if_current(client: &WsClient, generation: u64) -> Result<()> {
+        let session_lock = client.lock().await.session_lock.clone();
+        let _session_guard = session_lock.lock().await;
+        let (signer, owner) = {
+            let client_guard = client.lock().await;
+            if client_guard.session_generation != generation {
+                return Ok(());
+            }
+            (client_guard.relogin_signer(), client_guard.owner.clone())
+        };
+        let wallet = signer.ok_or(LyraError::signing("Not logged in or signer not set"))?;
+        WsClientState::set_signer(client, wallet.clone()).await;
+        info!("Refreshing session of {}", owner);
+        let login_params = sign_auth_msg(&wallet, &owner).await?;
+        WsClientState::raw_rpc(client, "public/login", login_params).await?;
+        WsClientState::session_started(client).await;
+        Ok(())
+    }
+
+    /// Keeps the session of a long-running subscription alive, checking every second while
+    /// not logged in
+    async fn refresh_session_forever(client: WsClient) -> Result<()> {
+        loop {
+            let (refresh_at, generation) = {
+                let client_guard = client.lock().await;
+                (client_guard.session_refresh_at(), client_guard.session_generation)
+            };
+            let Some(refresh_at) = refresh_at else {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            };
+            tokio::time::sleep_until(refresh_at).await;
+            WsClientState::relogin_if_current(&client, generation).await?;
+        }
+    }
+
+    async fn set_signer(client: &WsClient, signer: LyraSigner) {
         let mut client_guard = client.lock().await;
         client_guard.signer = Some(signer);
     }
 
+    /// The signer and config of the session, cloned so that no lock is held while signing
+    async fn signer_and_config(client: &WsClient) -> Result<(LyraSigner, Arc<LyraConfig>)> {
+        let client_guard = client.lock().await;
+        let signer = client_guard.signer.clone();
+        let signer = signer.ok_or(LyraError::signing("Not logged in or signer not set"))?;
+        Ok((signer, client_guard.config.clone()))
+    }
+
     async fn set_owner(client: &WsClient, owner: String) {
         let mut client_guard = client.lock().await;
         client_guard.owner = owner;
@@ -407,12 +1177,9 @@ impl WsClientState {
         asset_name: String,
         margin_type: MarginType,
     ) -> Result<DepositParams> {
-        let client_guard = client.lock().await;
-        if let Some(signer) = &client_guard.signer {
-            Ok(new_deposit_params(signer, subaccount_id, amount, asset_name, margin_type)?)
-        } else {
-            Err(Error::msg("Not logged in or signer not set"))
-        }
+        let asset = CollateralAsset::get(&asset_name).await?;
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_deposit_params(&signer, &config, subaccount_id, amount, &asset, margin_type).await
     }
 
     async fn new_signed_withdraw(
@@ -421,12 +1188,54 @@ impl WsClientState {
         amount: BigDecimal,
         asset_name: String,
     ) -> Result<WithdrawParams> {
-        let client_guard = client.lock().await;
-        if let Some(signer) = &client_guard.signer {
-            Ok(new_withdraw_params(signer, subaccount_id, amount, asset_name)?)
-        } else {
-            Err(Error::msg("Not logged in or signer not set"))
-        }
+        let asset = CollateralAsset::get(&asset_name).await?;
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_withdraw_params(&signer, &config, subaccount_id, amount, &asset).await
+    }
+
+    async fn new_signed_create_subaccount(
+        client: &WsClient,
+        amount: BigDecimal,
+        asset_name: String,
+        margin_type: MarginType,
+    ) -> Result<CreateSubaccountParams> {
+        let asset = CollateralAsset::get(&asset_name).await?;
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_create_subaccount_params(&signer, &config, amount, &asset, margin_type).await
+    }
+
+    async fn new_signed_transfer(
+        client: &WsClient,
+        subaccount_id: i64,
+        recipient_subaccount_id: i64,
+        amount: BigDecimal,
+        asset_name: String,
+    ) -> Result<TransferParams> {
+        let asset = CollateralAsset::get(&asset_name).await?;
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        let recipient_id = recipient_subaccount_id;
+        new_transfer_params(&signer, &config, subaccount_id, recipient_id, amount, &asset).await
+    }
+
+    async fn new_signed_transfer_position(
+        client: &WsClient,
+        ticker: &InstrumentTicker,
+        subaccount_id: i64,
+        recipient_subaccount_id: i64,
+        amount: BigDecimal,
+        limit_price: BigDecimal,
+    ) -> Result<TransferPositionParams> {
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_transfer_position_params(
+            &signer,
+            &config,
+            ticker,
+            subaccount_id,
+            recipient_subaccount_id,
+            amount,
+            limit_price,
+        )
+        .await
     }
 
     async fn new_signed_order(
@@ -435,12 +1244,8 @@ impl WsClientState {
         subaccount_id: i64,
         args: OrderArgs,
     ) -> Result<OrderParams> {
-        let client_guard = client.lock().await;
-        if let Some(signer) = &client_guard.signer {
-            Ok(new_order_params(signer, ticker, subaccount_id, args)?)
-        } else {
-            Err(Error::msg("Not logged in or signer not set"))
-        }
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_order_params(&signer, &config, ticker, subaccount_id, args).await
     }
 
     async fn new_signed_replace(
@@ -450,12 +1255,8 @@ impl WsClientState {
         to_cancel: Uuid,
         args: OrderArgs,
     ) -> Result<ReplaceParams> {
-        let client_guard = client.lock().await;
-        if let Some(signer) = &client_guard.signer {
-            Ok(new_replace_params(signer, ticker, subaccount_id, to_cancel, args)?)
-        } else {
-            Err(Error::msg("Not logged in or signer not set"))
-        }
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_replace_params(&signer, &config, ticker, subaccount_id, to_cancel, args).await
     }
 
     async fn new_signed_quote(
@@ -464,12 +1265,8 @@ impl WsClientState {
         subaccount_id: i64,
         args: QuoteArgs,
     ) -> Result<QuoteParams> {
-        let client_guard = client.lock().await;
-        if let Some(signer) = &client_guard.signer {
-            Ok(new_quote_params(signer, tickers, subaccount_id, args)?)
-        } else {
-            Err(Error::msg("Not logged in or signer not set"))
-        }
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_quote_params(&signer, &config, tickers, subaccount_id, args).await
     }
     async fn new_signed_execute(
         client: &WsClient,
@@ -477,12 +1274,8 @@ impl WsClientState {
         subaccount_id: i64,
         quote: &QuoteResultPublic,
     ) -> Result<ExecuteQuoteParams> {
-        let client_guard = client.lock().await;
-        if let Some(signer) = &client_guard.signer {
-            Ok(new_execute_params(signer, tickers, subaccount_id, &quote)?)
-        } else {
-            Err(Error::msg("Not logged in or signer not set"))
-        }
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_execute_params(&signer, &config, tickers, subaccount_id, &quote).await
     }
 
     async fn new_signed_liquidate(
@@ -492,12 +1285,9 @@ impl WsClientState {
         percent_bid: BigDecimal,
         details: &AuctionDetailsSchema,
     ) -> Result<LiquidationParams> {
-        let client_guard = client.lock().await;
-        if let Some(signer) = &client_guard.signer {
-            Ok(new_liquidate_params(signer, subaccount_id, liquidated_id, percent_bid, details)?)
-        } else {
-            Err(Error::msg("Not logged in or signer not set"))
-        }
+        let (signer, config) = WsClientState::signer_and_config(client).await?;
+        new_liquidate_params(&signer, &config, subaccount_id, liquidated_id, percent_bid, details)
+            .await
     }
 
     async fn send_to_socket<P>(client: &WsClient, method: &str, params: P) -> Result<Uuid>
@@ -512,7 +1302,13 @@ impl WsClientState {
         });
         let item = Message::Text(payload.to_string());
         let mut client_guard = client.lock().await;
-        client_guard.socket.send(item).await?;
+        let request =
+            InFlightRequest { id: this_id, method: method.to_string(), sent_at: Instant::now() };
+        client_guard.in_flight.insert(this_id, request);
+        if let Err(e) = client_guard.socket.send(item).await {
+            client_guard.in_flight.remove(&this_id);
+            return Err(e.into());
+        }
         Ok(this_id)
     }
 
@@ -522,41 +1318,77 @@ impl WsClientState {
         Ok(())
     }
 
-    async fn listen_and_wait_for<R>(client: &WsClient, id: Uuid) -> Result<Response<R>>
+    async fn listen_and_wait_for<R>(
+        client: &WsClient,
+        id: Uuid,
+        timeout: Duration,
+    ) -> Result<Response<R>>
     where
         R: for<'de> Deserialize<'de>,
     {
-        let wait_handle = WsClientState::wait_for(client.clone(), id);
+        let (generation, auto_reconnect) = {
+            let client_guard = client.lock().await;
+            (client_guard.generation, client_guard.auto_reconnect)
+        };
+        let wait_handle = WsClientState::wait_for(client.clone(), id, timeout);
         let listen_handle = WsClientState::listen(client.clone());
-        tokio::select! {
+        let listen_res = tokio::select! {
             val = wait_handle => {
                 let val = val?;
                 info!("Received: {}", serde_json::to_string_pretty(&val).unwrap_or("could not serialize".into()));
                 let response: Result<Response<R>, _> = serde_path_to_error::deserialize(val);
-                Ok(response?)
-            }
-            _ = listen_handle => {
-                Err(Error::msg("LyraWsClient::listen() exited before receiving reply"))
+                return Ok(response?);
             }
+            listen_res = listen_handle => listen_res
+        };
+        error!("LyraWsClient::listen() exited before receiving reply: {:?}", listen_res);
+        client.lock().await.in_flight.remove(&id);
+        if auto_reconnect {
+            WsClientState::reconnect_with_backoff(client, generation).await?;
         }
+        Err(LyraError::transport(format!(
+            "Connection lost before receiving reply for msg id: {id}"
+        )))
     }
 
-    async fn listen(client: WsClient) {
+    /// Reads messages into the client state until the socket disconnects.
+    async fn listen(client: WsClient) -> Result<()> {
         loop {
             let mut client_guard = client.lock().await;
             let msg = client_guard.socket.next().now_or_never();
-            if let Some(Some(msg)) = msg {
-                let result = WsClientState::decode_and_insert(msg, &mut client_guard);
-                if let Err(e) = result {
-                    warn!("decode_and_insert error: {:?}", e);
+            match msg {
+                Some(Some(msg)) => {
+                    if WsClientState::is_disconnect(&msg) {
+                        return Err(LyraError::transport(format!(
+                            "Socket disconnected: {:?}",
+                            msg
+                        )));
+                    }
+                    let result = WsClientState::decode_and_insert(msg, &mut client_guard);
+                    if let Err(e) = result {
+                        warn!("decode_and_insert error: {:?}", e);
+                    }
+                }
+                Some(None) => return Err(LyraError::transport("Socket stream ended")),
+                None => {
+                    drop(client_guard);
+                    tokio::time::sleep(tokio::time::Duration::from_micros(1000)).await;
                 }
-            } else {
-                drop(client_guard);
-                tokio::time::sleep(tokio::time::Duration::from_micros(1000)).await;
             }
         }
     }
 
+    fn is_disconnect(msg: &Result<Message, SocketError>) -> bool {
+        match msg {
+            Ok(Message::Close(_)) => true,
+            Err(SocketError::ConnectionClosed)
+            | Err(SocketError::AlreadyClosed)
+            | Err(SocketError::Io(_))
+            | Err(SocketError::Protocol(_)) => true,
+            _ => false,
+        }
+    }
+
     async fn ping_interval(client: WsClient, interval_sec: u64) -> Result<()> {
         loop {
             tokio::time::sleep(tokio::time::Duration::from_secs(interval_sec)).await;
@@ -564,38 +1396,114 @@ impl WsClientState {
         }
     }
 
-    async fn wait_for(client: WsClient, id: Uuid) -> Result<Value> {
+    /// Waits for the reply of an in-flight request, a reply after the timeout is dropped
+    async fn wait_for(client: WsClient, id: Uuid, timeout: Duration) -> Result<Value> {
         let start = Instant::now();
         loop {
             let mut client_guard = client.lock().await;
             if let Some(json) = client_guard.messages.remove(&id) {
+                client_guard.in_flight.remove(&id);
                 return Ok(json);
-            } else {
-                drop(client_guard);
-                tokio::time::sleep(tokio::time::Duration::from_micros(100)).await;
             }
-            // TODO timeout env var or param
-            if start.elapsed().as_secs() > 5 {
-                return Err(Error::msg(format!("Timeout waiting for msg id: {id}")));
+            if start.elapsed() > timeout {
+                let request = client_guard.in_flight.remove(&id);
+                let method = request.map(|r| r.method).unwrap_or_default();
+                let timeout_ms = timeout.as_millis() as u64;
+                warn!(%method, %id, timeout_ms, "No reply within the timeout");
+                return Err(LyraError::Timeout { method, timeout });
             }
+            drop(client_guard);
+            tokio::time::sleep(tokio::time::Duration::from_micros(100)).await;
         }
     }
 
-    async fn listen_forever<Fut, Data>(
+    pub(crate) async fn listen_forever<Fut, Data>(
         client: &WsClient,
-        handler: impl FnMut(Data) -> Fut,
+        mut handler: impl FnMut(Data) -> Fut,
     ) -> Result<()>
     where
-        Fut: Future<Output = Result<()>>,
+        Fut: Future<Output = anyhow::Result<()>>,
         Data: for<'de> Deserialize<'de> + Debug,
     {
-        let listen_handle = WsClientState::listen(client.clone());
-        let notification_handle = WsClientState::handle_notifications(client.clone(), handler);
-        let ping_handle = WsClientState::ping_interval(client.clone(), 15);
-        tokio::select! {
-            _ = listen_handle => { Err(Error::msg("listen() exited")) }
-            _ = notification_handle => { Err(Error::msg("handle_notifications() exited")) }
-            _ = ping_handle => { Err(Error::msg("ping_interval() exited")) }
+        loop {
+            let (generation, auto_reconnect) = {
+                let client_guard = client.lock().await;
+                (client_guard.generation, client_guard.auto_reconnect)
+            };
+            let listen_handle = WsClientState::listen(client.clone());
+            let notification_handle =
+                WsClientState::handle_notifications(client.clone(), &mut handler);
+            let ping_handle = WsClientState::ping_interval(client.clone(), 15);
+            let session_handle = WsClientState::refresh_session_forever(client.clone());
+            let heartbeat_handle = WsClientState::watch_heartbeats(client.clone());
+            let res = tokio::select! {
+                r = listen_handle => { Err(LyraError::transport(format!("listen() exited with {:?}", r))) }
+                r = notification_handle => {
+                    return Err(LyraError::Other(format_err!("handle_notifications() exited with {:?}", r)));
+                }
+                r = ping_handle => { Err(LyraError::transport(format!("ping_interval() exited with {:?}", r))) }
+                r = session_handle => { Err(LyraError::transport(format!("refresh_session_forever() exited with {:?}", r))) }
+                r = heartbeat_handle => { Err(LyraError::transport(format!("watch_heartbeats() exited with {:?}", r))) }
+            };
+            if !auto_reconnect {
+                return res;
+            }
+            warn!("Subscription connection lost with {:?}, reconnecting", res);
+            WsClientState::reconnect_with_backoff(client, generation).await?;
+        }
+    }
+
+    /// Channels with a stale threshold and no message within it
+    fn stale_channels(&self) -> Vec<String> {
+        let stale = self.subscriptions.iter().filter(|channel| {
+            match (self.stale_thresholds.get(*channel), self.last_message.get(*channel)) {
+                (Some(threshold), Some(last_message)) => last_message.elapsed() > *threshold,
+                _ => false,
+            }
+        });
+        stale.cloned().collect()
+    }
+
+    /// Resubscribes channels that went quiet beyond their stale threshold, and fails (so that
+    /// `listen_forever` reconnects) once a resubscribed channel goes quiet again
+    async fn watch_heartbeats(client: WsClient) -> Result<()> {
+        loop {
+            tokio::time::sleep(Duration::from_secs(HEARTBEAT_CHECK_SEC)).await;
+            let (stale, quiet_after_resubscribe, on_stale) = {
+                let client_guard = client.lock().await;
+                let stale = client_guard.stale_channels();
+                let quiet_after_resubscribe = stale
+                    .iter()
+                    .filter(|channel| client_guard.resubscribed.contains(*channel))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                (stale, quiet_after_resubscribe, client_guard.on_stale.clone())
+            };
+            if stale.is_empty() {
+                continue;
+            }
+            warn!("No messages on {:?} within their stale threshold", stale);
+            if let Some(callback) = on_stale {
+                if let Err(e) = callback(stale.clone()).await {
+                    error!("Stale callback failed with {:?}", e);
+                }
+            }
+            if !quiet_after_resubscribe.is_empty() {
+                return Err(LyraError::transport(format!(
+                    "Still no messages on {:?} after resubscribing",
+                    quiet_after_resubscribe
+                )));
+            }
+            info!("Resubscribing to {:?}", stale);
+            let params = UnsubscribeParamsSchema { channels: Some(stale.clone()) };
+            WsClientState::raw_rpc(&client, "unsubscribe", params).await?;
+            let params = SubscribeParamsSchema { channels: stale.clone() };
+            WsClientState::raw_rpc(&client, "subscribe", params).await?;
+            let mut client_guard = client.lock().await;
+            for channel in stale {
+                client_guard.last_message.insert(channel.clone(), Instant::now());
+                client_guard.resubscribed.insert(channel);
+            }
         }
     }
 
@@ -604,7 +1512,7 @@ impl WsClientState {
         mut handler: impl FnMut(Data) -> Fut,
     ) -> Result<()>
     where
-        Fut: Future<Output = Result<()>>,
+        Fut: Future<Output = anyhow::Result<()>>,
         Data: for<'de> Deserialize<'de> + Debug,
     {
         loop {
@@ -654,12 +1562,18 @@ impl WsClientState {
         // TODO max size for # of messages and notifications
         if let Some(id_value) = id_value {
             let id = Uuid::deserialize(id_value)?;
+            if !state.in_flight.contains_key(&id) {
+                warn!("Dropping reply to msg id {} that is no longer in flight", id);
+                return Ok(());
+            }
             state.messages.insert(id, json);
         } else {
             let channel = &json["params"]["channel"];
             let channel = channel.as_str();
             if let Some(channel) = channel {
                 debug!("Received: {}", channel);
+                state.last_message.insert(channel.to_string(), Instant::now());
+                state.resubscribed.remove(channel);
                 state.notifications.push(json);
             } else {
                 warn!("Received unrecognized message format: {:?}", json);
@@ -673,12 +1587,64 @@ impl WsClientState {
         let json = serde_json::from_str::<Value>(msg_text);
         match json {
             Ok(json) => Ok(json),
-            Err(e) => Err(Error::msg(format!("Error in serde_json::from_str::<Value>: {:?}", e))),
+            Err(e) => Err(LyraError::deserialization(format!(
+                "Error in serde_json::from_str::<Value>: {:?}",
+                e
+            ))),
         }
     }
 }
 
+/// The order with the nonce of the params and its trades, from the open orders or else the order
+/// history of its instrument and label. None if the order was never accepted.
+async fn find_order_by_nonce(
+    client: &WsClient,
+    params: &OrderParams,
+) -> Result<Option<SendOrderResult>> {
+    let subaccount_id = params.subaccount_id;
+    let open_params = PrivateGetOpenOrdersParamsSchema { subaccount_id };
+    let res = client.send_rpc::<_, Value>("private/get_open_orders", open_params).await?;
+    let mut orders: Vec<OrderResponse> =
+        serde_json::from_value(res.into_result()?["result"]["orders"].clone())?;
+    if !orders.iter().any(|order| order.nonce == params.nonce) {
+        let history_params = PrivateGetOrdersParamsSchema {
+            instrument_name: Some(params.instrument_name.clone()),
+            label: Some(params.label.clone()),
+            page: 1,
+            page_size: 100,
+            status: None,
+            subaccount_id,
+        };
+        let res = client.send_rpc::<_, Value>("private/get_orders", history_params).await?;
+        orders = serde_json::from_value(res.into_result()?["result"]["orders"].clone())?;
+    }
+    let Some(order) = orders.into_iter().find(|order| order.nonce == params.nonce) else {
+        return Ok(None);
+    };
+    let mut trades: Vec<TradeResponse> = vec![];
+    if !order.filled_amount.is_zero() {
+        let trade_params = PrivateGetTradeHistoryParamsSchema {
+            from_timestamp: order.creation_timestamp,
+            instrument_name: Some(order.instrument_name.clone()),
+            order_id: Some(order.order_id.clone()),
+            page: 1,
+            page_size: 1000,
+            subaccount_id,
+            to_timestamp: i64::MAX,
+        };
+        let res = client.send_rpc::<_, Value>("private/get_trade_history", trade_params).await?;
+        trades = serde_json::from_value(res.into_result()?["result"]["trades"].clone())?;
+    }
+    Ok(Some(SendOrderResult { order, trades }))
+}
+
+/// Methods that only read, so resending them to another endpoint can not act twice
+fn is_idempotent(method: &str) -> bool {
+    method.starts_with("public/get_") || method.starts_with("private/get_")
+}
+
 // TODO a bit ugly to pass two types here, can use one trait but the stub generator needs to be updated
+/// Shares the process wide `RateLimiter::http()`, retries rate limited requests like `send_rpc`
 pub async fn http_rpc<P, R>(
     method: &str,
     params: P,
@@ -689,14 +1655,63 @@ where
     R: for<'de> Deserialize<'de>,
 {
     let headers = headers.unwrap_or_default();
-    let root = std::env::var("HTTP_ADDRESS").expect("HTTP_ADDRESS must be set");
-    let url = format!("{root}/{method}");
     let client = Client::new();
-    info!("HTTP Request: {} with {:?} and headers {:?}", url, params, headers);
-    let response = client.post(url).json(&params).headers(headers).send().await?;
-    let response_text = response.text().await?;
-    debug!("HTTP Response: {response_text}");
-    let jd = &mut serde_json::Deserializer::from_str(&response_text);
-    let parsed_response: Result<Response<R>, _> = serde_path_to_error::deserialize(jd);
-    Ok(parsed_response?)
+    let rate_limiter = RateLimiter::http();
+    // a request that reached the gateway may have been processed, so only reads are resent to
+    // the next endpoint after a gateway error or a dropped response
+    let idempotent = is_idempotent(method);
+    let fail_over = |e: &reqwest::Error| idempotent || e.is_connect();
+    let mut retry = 0;
+    loop {
+        rate_limiter.acquire(method).await?;
+        info!(method, ?params, ?headers, "HTTP request");
+        let response = Endpoints::http()
+            .try_each_if(
+                |root| {
+                    let request = client.post(format!("{root}/{method}")).json(&params);
+                    let request = request.headers(headers.clone());
+                    async move {
+                        let response = request.send().await?;
+                        if response.status().is_server_error() {
+                            return Err(response.error_for_status().unwrap_err());
+                        }
+                        Ok(response)
+                    }
+                },
+                fail_over,
+            )
+            .await?;
+        let retry_after = match response.status() {
+            StatusCode::TOO_MANY_REQUESTS => Some(
+                response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|h| h.to_str().ok()?.parse::<u64>().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(rate_limiter.limits().backoff(retry)),
+            ),
+            _ => None,
+        };
+        let response_text = response.text().await?;
+        debug!("HTTP Response: {response_text}");
+        let jd = &mut serde_json::Deserializer::from_str(&response_text);
+        let parsed_response: Result<Response<R>, _> = serde_path_to_error::deserialize(jd);
+        let retry_after = match &parsed_response {
+            Ok(Response::Error(e)) if e.error.code == RATE_LIMIT_ERROR_CODE => {
+                Some(retry_after.unwrap_or(rate_limiter.limits().backoff(retry)))
+            }
+            _ => retry_after,
+        };
+        let Some(retry_after) = retry_after else {
+            return Ok(parsed_response?);
+        };
+        if retry >= rate_limiter.limits().max_retries {
+            error!(method, retry, "Still rate limited after the retries");
+            return Err(LyraError::RateLimited { method: method.to_string(), retry_after });
+        }
+        let retry_after_ms = retry_after.as_millis() as u64;
+        warn!(method, retry_after_ms, "Rate limited, retrying");
+        rate_limiter.penalize(retry_after).await;
+        retry += 1;
+    }
 }
@@ -0,0 +1,84 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use tokio::time::Duration;
+
+/// Latencies of the process by name, e.g. `rpc.private/order` or `order_ack`
+static LATENCIES: OnceLock<Mutex<HashMap<String, LatencyWindow>>> = OnceLock::new();
+
+/// Samples kept per name for the percentiles, override with LATENCY_WINDOW
+const DEFAULT_LATENCY_WINDOW: usize = 1000;
+
+/// Name of the socket round trip of an RPC method
+pub fn rpc_name(method: &str) -> String {
+    format!("rpc.{}", method)
+}
+
+/// Name of the time from sending a signed order until the server acknowledged (or rejected) it
+pub const ORDER_ACK: &str = "order_ack";
+
+/// Recent samples of one latency, plus totals since the start of the process
+#[derive(Debug, Default)]
+struct LatencyWindow {
+    samples: VecDeque<Duration>,
+    count: u64,
+    max: Duration,
+}
+
+/// Percentiles of the recent samples of a latency, in milliseconds
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySummary {
+    pub count: u64, // since the start of the process
+    pub window: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64, // since the start of the process
+}
+
+fn latency_window() -> usize {
+    let window = std::env::var("LATENCY_WINDOW").ok().and_then(|s| s.parse().ok());
+    window.unwrap_or(DEFAULT_LATENCY_WINDOW).max(1)
+}
+
+/// Adds a sample of the latency
+pub fn record(name: &str, elapsed: Duration) {
+    let mut latencies = LATENCIES.get_or_init(Default::default).lock().unwrap();
+    let window = latencies.entry(name.to_string()).or_default();
+    if window.samples.len() >= latency_window() {
+        window.samples.pop_front();
+    }
+    window.samples.push_back(elapsed);
+    window.count += 1;
+    window.max = window.max.max(elapsed);
+}
+
+/// Percentiles of all latencies recorded so far, by name
+pub fn snapshot() -> BTreeMap<String, LatencySummary> {
+    let latencies = LATENCIES.get_or_init(Default::default).lock().unwrap();
+    latencies.iter().map(|(name, window)| (name.clone(), window.summary())).collect()
+}
+
+impl LatencyWindow {
+    fn summary(&self) -> LatencySummary {
+        let mut sorted = self.samples.iter().copied().collect::<Vec<_>>();
+        sorted.sort();
+        // nearest rank of the percentile
+        let percentile = |p: f64| {
+            let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+            as_ms(sorted[rank - 1])
+        };
+        LatencySummary {
+            count: self.count,
+            window: sorted.len(),
+            p50_ms: percentile(0.50),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: as_ms(self.max),
+        }
+    }
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
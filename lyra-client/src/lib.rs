@@ -1,7 +1,30 @@
 pub mod actions;
 pub mod auth;
 pub mod aws;
+mod bootstrap;
+pub mod channels;
 mod cli;
+pub mod config;
+mod decode;
+pub mod endpoints;
+pub mod error;
+mod history;
 pub mod json_rpc;
+pub mod latency;
+pub mod liquidations;
+pub mod logging;
+pub mod market_history;
+pub mod notifications;
+pub mod orders;
+mod output;
+mod presets;
+pub mod rate_limit;
+mod repl;
+pub mod rfq_maker;
+pub mod session;
 pub mod setup;
+pub mod signer;
+pub mod subaccounts;
+pub mod tickers;
 pub mod utils;
+pub mod ws_mux;
@@ -0,0 +1,108 @@
+use crate::actions::max_percent_bid;
+use crate::error::{LyraError, Result};
+use crate::json_rpc::{Notification, WsClient, WsClientExt};
+use crate::utils::await_tx_settlement;
+use anyhow::format_err;
+use bigdecimal::{BigDecimal, Zero};
+use orderbook_types::generated::public_get_transaction::Status;
+use orderbook_types::types::liquidations::{
+    AuctionDetailsSchema, AuctionState, AuctionsWatchData, AuctionsWatchResultSchema,
+    SendLiquidateResult,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::info;
+
+type Auctions = Arc<RwLock<HashMap<i64, AuctionsWatchResultSchema>>>;
+
+/// Tracks the ongoing liquidation auctions from the `auctions.watch` channel
+#[derive(Clone, Default)]
+pub struct AuctionWatcher {
+    auctions: Auctions,
+}
+
+impl AuctionWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to auction updates forever with its own client.
+    /// Use with `select!` if you'd like to be able to cancel it.
+    pub async fn run(&self) -> Result<()> {
+        let client = WsClient::new_client().await?;
+        let channels = vec!["auctions.watch".to_string()];
+        client
+            .subscribe(channels, |msg: Notification<AuctionsWatchData>| async {
+                let mut writer = self.auctions.write().await;
+                for auction in msg.params.data {
+                    match auction.state {
+                        AuctionState::Ended => writer.remove(&auction.subaccount_id),
+                        AuctionState::Ongoing => writer.insert(auction.subaccount_id, auction),
+                    };
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    /// Ongoing auctions with details, the most profitable (estimated discount PnL) first
+    pub async fn liquidatable(&self) -> Vec<AuctionsWatchResultSchema> {
+        let reader = self.auctions.read().await;
+        let mut auctions =
+            reader.values().filter(|a| a.details.is_some()).cloned().collect::<Vec<_>>();
+        auctions.sort_by(|a, b| {
+            let pnl = |a: &AuctionsWatchResultSchema| {
+                a.details.as_ref().map(|d| d.estimated_discount_pnl.clone()).unwrap_or_default()
+            };
+            pnl(b).cmp(&pnl(a))
+        });
+        auctions
+    }
+
+    pub async fn get(&self, subaccount_id: i64) -> Option<AuctionsWatchResultSchema> {
+        self.auctions.read().await.get(&subaccount_id).cloned()
+    }
+}
+
+/// Subaccounts under liquidation seen on `auctions.watch` within `wait`, see `AuctionWatcher`
+pub async fn get_liquidatable_subaccounts(
+    wait: Duration,
+) -> Result<Vec<AuctionsWatchResultSchema>> {
+    let watcher = AuctionWatcher::new();
+    if let Ok(res) = tokio::time::timeout(wait, watcher.run()).await {
+        res?;
+    }
+    Ok(watcher.liquidatable().await)
+}
+
+/// Bids for the auction of `liquidated_id` from `subaccount_id` (sized to `max_cash`, see
+/// `max_percent_bid`) and waits for the liquidation tx. Expects the client to be logged in.
+pub async fn bid_and_wait(
+    client: &WsClient,
+    subaccount_id: i64,
+    liquidated_id: i64,
+    details: &AuctionDetailsSchema,
+    max_cash: Option<BigDecimal>,
+) -> Result<SendLiquidateResult> {
+    let percent_bid = max_percent_bid(details, max_cash.as_ref());
+    if percent_bid.is_zero() {
+        return Err(LyraError::Other(format_err!("Bid for {} rounds to zero", liquidated_id)));
+    }
+    info!("Bidding {} of subaccount {} from {}", percent_bid, liquidated_id, subaccount_id);
+    let res = client
+        .send_liquidate(subaccount_id, liquidated_id, percent_bid, details)
+        .await?
+        .into_result()?;
+    let transaction_id = res.result.transaction_id;
+    let tx = await_tx_settlement(transaction_id).await?;
+    match tx.status {
+        Status::Settled => Ok(res.result),
+        _ => Err(LyraError::Other(format_err!(
+            "Liquidation tx {} not settled: {:?}",
+            transaction_id,
+            tx.status
+        ))),
+    }
+}
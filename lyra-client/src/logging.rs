@@ -0,0 +1,21 @@
+use tracing_subscriber::EnvFilter;
+
+/// Filter while RUST_LOG is unset
+const DEFAULT_LOG_FILTER: &str = "info";
+
+/// Installs the global subscriber, which also receives the `log` records of dependencies.
+/// Levels are set per module with RUST_LOG, e.g. `info,lyra_vaults::shared::auction=debug`.
+/// With LOG_FORMAT=json every event is one JSON line carrying the fields of its spans, e.g. the
+/// vault, epoch and stage of an executor stage or the label and id of an auction order.
+pub fn init_logging() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let res = match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().flatten_event(true).with_span_list(true).try_init(),
+        _ => builder.try_init(),
+    };
+    if let Err(e) = res {
+        eprintln!("Logging not initialized again: {}", e);
+    }
+}
@@ -1,22 +1,52 @@
 pub mod actions;
 pub mod auth;
 pub mod aws;
+mod bootstrap;
+pub mod channels;
 mod cli;
+pub mod config;
+mod decode;
+pub mod endpoints;
+pub mod error;
+mod history;
 pub mod json_rpc;
+pub mod latency;
+pub mod liquidations;
+pub mod logging;
+pub mod market_history;
+pub mod notifications;
+pub mod orders;
+mod output;
+mod presets;
+pub mod rate_limit;
+mod repl;
+pub mod rfq_maker;
+pub mod session;
 pub mod setup;
+pub mod signer;
+pub mod subaccounts;
+pub mod tickers;
 pub mod utils;
+pub mod ws_mux;
 
-use crate::cli::CliRpc;
+use crate::cli::{Cli, CliRpc, Command};
 use clap::Parser;
-use log::{error, info};
+use lyra_client::config::LyraConfig;
 use lyra_client::json_rpc::{http_rpc, WsClient, WsClientExt};
-use lyra_client::setup::{ensure_owner, ensure_session_key, setup_env};
+use lyra_client::setup::{env_config, setup_env};
+use tracing::{error, info};
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 2)]
 async fn main() -> anyhow::Result<()> {
-    setup_env().await;
-    ensure_session_key().await;
-    ensure_owner().await;
-    let _ = CliRpc::execute().await;
+    let env = setup_env().await;
+    let cli = Cli::parse();
+    // bootstrap-testnet registers the first session key of the OWNER signer
+    if !matches!(cli.command, Command::BootstrapTestnet(_)) {
+        LyraConfig::init(env_config(&env).await?)?;
+    }
+    if let Err(e) = CliRpc::execute(cli).await {
+        error!("{:#}", e);
+        std::process::exit(1);
+    }
     Ok(())
 }
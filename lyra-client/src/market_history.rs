@@ -0,0 +1,177 @@
+use crate::history::parse_timestamp_ms;
+use crate::json_rpc::http_rpc;
+use crate::output::{print_result, OutputFormat};
+use anyhow::{format_err, Result};
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use clap::{Args, Subcommand};
+use orderbook_types::generated::public_get_option_settlement_history::{
+    OptionSettlementResponseSchema, PublicGetOptionSettlementHistoryParamsSchema,
+    PublicGetOptionSettlementHistoryResponseSchema,
+};
+use orderbook_types::types::funding::{
+    FundingRate, FundingRateHistoryParams, FundingRateHistoryResponse,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashSet;
+use tracing::info;
+
+/// Max page size of public/get_option_settlement_history
+const SETTLEMENT_PAGE_SIZE: i64 = 1000;
+
+/// Longest range fetched per public/get_funding_rate_history call
+const FUNDING_WINDOW_MS: i64 = 30 * 24 * 3600 * 1000;
+
+/// Settlement price of an expired option
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementPrice {
+    pub instrument_name: String,
+    pub expiry: i64, // sec
+    pub settlement_price: BigDecimal,
+}
+
+/// All option settlements of the subaccount, fetched page by page and sorted by expiry
+pub async fn get_option_settlements(
+    subaccount_id: i64,
+) -> Result<Vec<OptionSettlementResponseSchema>> {
+    let mut settlements = vec![];
+    let mut num_pages = 1;
+    let mut page = 1;
+    while page <= num_pages {
+        let params = PublicGetOptionSettlementHistoryParamsSchema {
+            page,
+            page_size: SETTLEMENT_PAGE_SIZE,
+            subaccount_id: Some(subaccount_id),
+        };
+        let result = http_rpc::<_, PublicGetOptionSettlementHistoryResponseSchema>(
+            "public/get_option_settlement_history",
+            params,
+            None,
+        )
+        .await?
+        .into_result()?
+        .result;
+        num_pages = result.pagination.num_pages;
+        info!("Fetched settlement page {} of {} for subaccount {}", page, num_pages, subaccount_id);
+        settlements.extend(result.settlements);
+        page += 1;
+    }
+    settlements.sort_by_key(|s| s.expiry);
+    Ok(settlements)
+}
+
+/// Settlement prices of the options the subaccount held to expiry whose name starts with
+/// `prefix` (e.g. ETH or ETH-20240628), one per option sorted by expiry
+pub async fn get_settlement_prices(
+    subaccount_id: i64,
+    prefix: &str,
+) -> Result<Vec<SettlementPrice>> {
+    let mut seen = HashSet::new();
+    let prices = get_option_settlements(subaccount_id)
+        .await?
+        .into_iter()
+        .filter(|s| s.instrument_name.starts_with(prefix))
+        .filter(|s| seen.insert(s.instrument_name.clone()))
+        .map(|s| SettlementPrice {
+            instrument_name: s.instrument_name,
+            expiry: s.expiry,
+            settlement_price: s.settlement_price,
+        });
+    Ok(prices.collect())
+}
+
+/// Funding rates of the perp between the timestamps (ms) sampled every `period_sec`, fetched in
+/// windows of 30 days and sorted by timestamp
+pub async fn get_funding_rate_history(
+    instrument_name: &str,
+    start_ms: i64,
+    end_ms: i64,
+    period_sec: i64,
+) -> Result<Vec<FundingRate>> {
+    let mut rates = vec![];
+    let mut window_start = start_ms;
+    while window_start < end_ms {
+        let window_end = (window_start + FUNDING_WINDOW_MS).min(end_ms);
+        let params = FundingRateHistoryParams {
+            instrument_name: instrument_name.to_string(),
+            start_timestamp: window_start,
+            end_timestamp: window_end,
+            period: period_sec,
+        };
+        let result = http_rpc::<_, FundingRateHistoryResponse>(
+            "public/get_funding_rate_history",
+            params,
+            None,
+        )
+        .await?
+        .into_result()?
+        .result;
+        info!("Fetched {} funding rates until {}", result.funding_rate_history.len(), window_end);
+        rates.extend(result.funding_rate_history);
+        window_start = window_end;
+    }
+    // the windows share their boundaries
+    let mut seen = HashSet::new();
+    rates.retain(|r| seen.insert(r.timestamp));
+    rates.sort_by_key(|r| r.timestamp);
+    Ok(rates)
+}
+
+/// Prints the funding rate or settlement price history of an instrument
+#[derive(Args, Debug)]
+pub struct CliMarketHistory {
+    #[command(subcommand)]
+    pub command: MarketHistoryCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MarketHistoryCommand {
+    /// Funding rates of a perp
+    Funding {
+        #[arg(short, long)]
+        instrument: String,
+        /// Start of the range, as a date (2024-05-01), an RFC 3339 time or a timestamp in ms
+        #[arg(long)]
+        from: String,
+        /// End of the range (same formats as --from), defaults to now
+        #[arg(long)]
+        to: Option<String>,
+        /// Sampling period in seconds
+        #[arg(long, default_value_t = 3600)]
+        period: i64,
+    },
+    /// Settlement prices of the expired options a subaccount held, of a currency or expiry,
+    /// e.g. ETH-20240628
+    Settlements {
+        #[arg(short, long)]
+        subaccount: i64,
+        #[arg(short, long, default_value = "")]
+        prefix: String,
+    },
+}
+
+impl CliMarketHistory {
+    pub async fn execute(self, output: OutputFormat) -> Result<()> {
+        let res = match self.command {
+            MarketHistoryCommand::Funding { instrument, from, to, period } => {
+                let from_timestamp = parse_timestamp_ms(&from)?;
+                let to_timestamp = match to {
+                    Some(to) => parse_timestamp_ms(&to)?,
+                    None => Utc::now().timestamp_millis(),
+                };
+                if from_timestamp >= to_timestamp {
+                    return Err(format_err!("--from must be before --to"));
+                }
+                let rates =
+                    get_funding_rate_history(&instrument, from_timestamp, to_timestamp, period)
+                        .await?;
+                json!({ "instrument_name": instrument, "funding_rate_history": rates })
+            }
+            MarketHistoryCommand::Settlements { subaccount, prefix } => {
+                json!({ "settlements": get_settlement_prices(subaccount, &prefix).await? })
+            }
+        };
+        print_result("market-history", &res, output)
+    }
+}
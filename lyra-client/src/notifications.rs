@@ -0,0 +1,85 @@
+use crate::channels::Channel;
+use crate::error::Result;
+use crate::json_rpc::{Notification, NotificationParams};
+use orderbook_types::generated::channel_orderbook_instrument_name_group_depth::OrderbookInstrumentNameGroupDepthPublisherDataSchema;
+use orderbook_types::generated::channel_spot_feed_currency::SpotFeedCurrencyPublisherDataSchema;
+use orderbook_types::generated::channel_subaccount_id_balances::BalanceUpdateSchema;
+use orderbook_types::generated::channel_subaccount_id_quotes::QuoteResultSchema;
+use orderbook_types::generated::channel_trades_instrument_name::TradePublicResponseSchema;
+use orderbook_types::types::orders::{OrderNotificationData, TradeNotificationData};
+use orderbook_types::types::rfqs::RFQResultPrivate;
+use orderbook_types::types::tickers::result::TickerNotificationData;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Notification of any subscription channel, typed by its channel name (see `Channel`), so one
+/// handler can take several kinds of channels and match on them:
+/// `client.subscribe(channels, |msg: LyraNotification| ...)`. Channels without a typed
+/// variant (e.g. the auctions channel) are kept as `Other`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LyraNotification {
+    Ticker(Notification<TickerNotificationData>),
+    Orderbook(Notification<OrderbookInstrumentNameGroupDepthPublisherDataSchema>),
+    Trades(Notification<Vec<TradePublicResponseSchema>>), // public trades of an instrument
+    SpotFeed(Notification<SpotFeedCurrencyPublisherDataSchema>),
+    Orders(Notification<OrderNotificationData>),
+    Balances(Notification<Vec<BalanceUpdateSchema>>),
+    SubaccountTrades(Notification<TradeNotificationData>),
+    Quotes(Notification<Vec<QuoteResultSchema>>),
+    Rfqs(Notification<Vec<RFQResultPrivate>>),
+    Other(Notification<Value>),
+}
+
+impl LyraNotification {
+    /// Decodes the data by the channel, an error if it does not match the channel's type
+    pub fn from_notification(notification: Notification<Value>) -> Result<Self> {
+        let Ok(channel) = Channel::from_str(&notification.params.channel) else {
+            return Ok(LyraNotification::Other(notification));
+        };
+        let typed = match channel {
+            Channel::Ticker { .. } => LyraNotification::Ticker(typed(notification)?),
+            Channel::Orderbook { .. } => LyraNotification::Orderbook(typed(notification)?),
+            Channel::Trades { .. } => LyraNotification::Trades(typed(notification)?),
+            Channel::SpotFeed { .. } => LyraNotification::SpotFeed(typed(notification)?),
+            Channel::Orders { .. } => LyraNotification::Orders(typed(notification)?),
+            Channel::Balances { .. } => LyraNotification::Balances(typed(notification)?),
+            Channel::SubaccountTrades { .. } => {
+                LyraNotification::SubaccountTrades(typed(notification)?)
+            }
+            Channel::Quotes { .. } => LyraNotification::Quotes(typed(notification)?),
+            Channel::Rfqs { .. } => LyraNotification::Rfqs(typed(notification)?),
+        };
+        Ok(typed)
+    }
+
+    pub fn channel(&self) -> &str {
+        match self {
+            LyraNotification::Ticker(n) => &n.params.channel,
+            LyraNotification::Orderbook(n) => &n.params.channel,
+            LyraNotification::Trades(n) => &n.params.channel,
+            LyraNotification::SpotFeed(n) => &n.params.channel,
+            LyraNotification::Orders(n) => &n.params.channel,
+            LyraNotification::Balances(n) => &n.params.channel,
+            LyraNotification::SubaccountTrades(n) => &n.params.channel,
+            LyraNotification::Quotes(n) => &n.params.channel,
+            LyraNotification::Rfqs(n) => &n.params.channel,
+            LyraNotification::Other(n) => &n.params.channel,
+        }
+    }
+}
+
+fn typed<D: DeserializeOwned>(notification: Notification<Value>) -> Result<Notification<D>> {
+    let Notification { method, params } = notification;
+    let data = serde_json::from_value(params.data)?;
+    Ok(Notification { method, params: NotificationParams { channel: params.channel, data } })
+}
+
+impl<'de> Deserialize<'de> for LyraNotification {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let notification = Notification::<Value>::deserialize(deserializer)?;
+        LyraNotification::from_notification(notification).map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,202 @@
+use crate::actions::{OrderArgs, OrderResponse, OrderStatus};
+use crate::auth::get_auth_headers;
+use crate::channels::Channel;
+use crate::error::Result;
+use crate::json_rpc::{http_rpc, Notification, WsClient, WsClientExt};
+use anyhow::Error;
+use bigdecimal::BigDecimal;
+use futures::FutureExt;
+use orderbook_types::generated::private_get_open_orders::PrivateGetOpenOrdersParamsSchema;
+use orderbook_types::types::orders::{OrderNotificationData, ReplaceResponse};
+use orderbook_types::types::tickers::InstrumentTicker;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+type OpenOrders = Arc<RwLock<HashMap<String, OrderResponse>>>;
+
+#[derive(Deserialize, Debug)]
+struct OpenOrdersResult {
+    orders: Vec<OrderResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenOrdersResponse {
+    result: OpenOrdersResult,
+}
+
+/// Tracks open orders of a subaccount from the `{subaccount_id}.orders` channel.
+/// The passed client is used for cancels / amends, `run` opens its own subscription client.
+#[derive(Clone)]
+pub struct OrderManager {
+    client: WsClient,
+    subaccount_id: i64,
+    orders: OpenOrders,
+}
+
+impl OrderManager {
+    /// Expects the client to be logged in
+    pub fn new(client: WsClient, subaccount_id: i64) -> Self {
+        Self { client, subaccount_id, orders: Arc::new(RwLock::new(HashMap::new())) }
+    }
+
+    /// Subscribes to order updates forever, reconciling with the exchange on (re)connect.
+    /// Use with `select!` if you'd like to be able to cancel it.
+    pub async fn run(&self) -> Result<()> {
+        reconcile_open_orders(self.orders.clone(), self.subaccount_id).await?;
+        let sub_client = WsClient::new_client().await?;
+        sub_client.login().await?.into_result()?;
+        let (orders, subaccount_id) = (self.orders.clone(), self.subaccount_id);
+        sub_client
+            .set_on_reconnect(Arc::new(move || {
+                let orders = orders.clone();
+                async move { Ok(reconcile_open_orders(orders, subaccount_id).await?) }.boxed()
+            }))
+            .await;
+        let channel = Channel::orders(self.subaccount_id).to_string();
+        sub_client
+            .subscribe(vec![channel], |msg: Notification<OrderNotificationData>| async {
+                let mut writer = self.orders.write().await;
+                for order in msg.params.data {
+                    insert_order(&mut writer, order);
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    pub async fn reconcile(&self) -> Result<()> {
+        reconcile_open_orders(self.orders.clone(), self.subaccount_id).await
+    }
+
+    pub async fn open_orders(&self) -> Vec<OrderResponse> {
+        self.orders.read().await.values().cloned().collect()
+    }
+
+    pub async fn get_order(&self, order_id: &str) -> Option<OrderResponse> {
+        self.orders.read().await.get(order_id).cloned()
+    }
+
+    /// Sends a new order and tracks it right away, before its channel update arrives
+    pub async fn place(&self, ticker: &InstrumentTicker, args: OrderArgs) -> Result<OrderResponse> {
+        let res = self.client.send_order(ticker, self.subaccount_id, args).await?.into_result()?;
+        let order = res.result.order;
+        let mut writer = self.orders.write().await;
+        insert_order(&mut writer, order.clone());
+        Ok(order)
+    }
+
+    /// Same as `place` but post-only, re-priced away from the book on a post-only reject
+    /// (see `WsClientExt::send_order_post_only_retry`)
+    pub async fn place_post_only(
+        &self,
+        ticker: &InstrumentTicker,
+        args: OrderArgs,
+        max_retries: u32,
+    ) -> Result<OrderResponse> {
+        let res = self
+            .client
+            .send_order_post_only_retry(ticker, self.subaccount_id, args, max_retries)
+            .await?
+            .into_result()?;
+        let order = res.result.order;
+        let mut writer = self.orders.write().await;
+        insert_order(&mut writer, order.clone());
+        Ok(order)
+    }
+
+    pub async fn cancel(&self, order_id: &str) -> Result<()> {
+        let order = self
+            .get_order(order_id)
+            .await
+            .ok_or(Error::msg(format!("Order {} is not open", order_id)))?;
+        let to_cancel = Uuid::parse_str(order_id)?;
+        self.client
+            .cancel(self.subaccount_id, order.instrument_name, to_cancel)
+            .await?
+            .into_result()?;
+        self.orders.write().await.remove(order_id);
+        Ok(())
+    }
+
+    pub async fn cancel_all(&self) -> Result<()> {
+        self.client.cancel_all(self.subaccount_id).await?.into_result()?;
+        self.orders.write().await.clear();
+        Ok(())
+    }
+
+    /// Replaces the order with the same args at a new price, keeping the unfilled amount.
+    /// The replace is atomic on the exchange, so the old order can never fill twice.
+    pub async fn amend(
+        &self,
+        ticker: &InstrumentTicker,
+        order_id: &str,
+        new_price: BigDecimal,
+    ) -> Result<ReplaceResponse> {
+        let order = self
+            .get_order(order_id)
+            .await
+            .ok_or(Error::msg(format!("Order {} is not open", order_id)))?;
+        let args = OrderArgs {
+            amount: &order.amount - &order.filled_amount,
+            limit_price: new_price,
+            direction: order.direction,
+            time_in_force: order.time_in_force,
+            order_type: order.order_type,
+            label: order.label.clone(),
+            mmp: order.mmp,
+            max_slippage_bps: None,
+        };
+        let to_cancel = Uuid::parse_str(order_id)?;
+        let res = self
+            .client
+            .send_replace(ticker, self.subaccount_id, to_cancel, args)
+            .await?
+            .into_result()?;
+        let mut writer = self.orders.write().await;
+        writer.remove(order_id);
+        if let Some(new_order) = &res.result.order {
+            insert_order(&mut writer, new_order.clone());
+        }
+        if let Some(e) = &res.result.create_order_error {
+            warn!("OrderManager amend cancelled {} but failed to create: {:?}", order_id, e);
+        }
+        Ok(res)
+    }
+}
+
+/// Keeps only open orders, ignoring updates older than the stored one
+fn insert_order(orders: &mut HashMap<String, OrderResponse>, order: OrderResponse) {
+    if let Some(existing) = orders.get(&order.order_id) {
+        if existing.last_update_timestamp > order.last_update_timestamp {
+            return;
+        }
+    }
+    match order.order_status {
+        OrderStatus::Open => orders.insert(order.order_id.clone(), order),
+        _ => orders.remove(&order.order_id),
+    };
+}
+
+async fn reconcile_open_orders(orders: OpenOrders, subaccount_id: i64) -> Result<()> {
+    let headers = get_auth_headers().await?;
+    let res = http_rpc::<_, Value>(
+        "private/get_open_orders",
+        PrivateGetOpenOrdersParamsSchema { subaccount_id },
+        Some(headers),
+    )
+    .await?
+    .into_result()?;
+    let res: OpenOrdersResponse = serde_json::from_value(res)?;
+    let mut writer = orders.write().await;
+    writer.clear();
+    for order in res.result.orders {
+        writer.insert(order.order_id.clone(), order);
+    }
+    info!("OrderManager reconciled {} open orders for {}", writer.len(), subaccount_id);
+    Ok(())
+}
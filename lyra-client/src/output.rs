@@ -0,0 +1,117 @@
+use crate::error::LyraError;
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use comfy_table::Table;
+use serde_json::{json, Value};
+
+/// How CLI results are written to stdout
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Single line JSON, one result per line, for piping into scripts
+    Json,
+    /// Indented JSON
+    #[default]
+    Pretty,
+    /// Tables for positions, open orders and tickers, pretty JSON for anything else
+    Table,
+}
+
+const POSITION_COLUMNS: [&str; 6] =
+    ["instrument_name", "amount", "average_price", "mark_price", "unrealized_pnl", "delta"];
+const ORDER_COLUMNS: [&str; 8] = [
+    "order_id",
+    "instrument_name",
+    "direction",
+    "amount",
+    "filled_amount",
+    "limit_price",
+    "order_status",
+    "label",
+];
+const COLLATERAL_COLUMNS: [&str; 3] = ["asset_name", "amount", "mark_price"];
+const TICKER_FIELDS: [&str; 8] = [
+    "instrument_name",
+    "best_bid_amount",
+    "best_bid_price",
+    "best_ask_price",
+    "best_ask_amount",
+    "mark_price",
+    "index_price",
+    "timestamp",
+];
+
+pub fn print_result(method: &str, res: &Value, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(res)?),
+        OutputFormat::Pretty => println!("{}", serde_json::to_string_pretty(res)?),
+        OutputFormat::Table => print_table(method, res)?,
+    }
+    Ok(())
+}
+
+/// Prints the RPC error (or any other error) in the requested format
+pub fn print_error(e: &Error, format: OutputFormat) -> Result<()> {
+    let rpc_error = e.downcast_ref::<LyraError>().and_then(|e| e.rpc_error());
+    let value = match rpc_error {
+        Some(rpc_error) => json!({ "error": rpc_error }),
+        None => json!({ "error": format!("{:#}", e) }),
+    };
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&value)?),
+        _ => println!("{}", serde_json::to_string_pretty(&value)?),
+    }
+    Ok(())
+}
+
+fn print_table(method: &str, res: &Value) -> Result<()> {
+    let result = &res["result"];
+    match method {
+        "private/get_positions" => {
+            println!("{}", rows_table(&result["positions"], &POSITION_COLUMNS))
+        }
+        "private/get_open_orders" => println!("{}", rows_table(&result["orders"], &ORDER_COLUMNS)),
+        "private/get_subaccount" => {
+            println!("{}", rows_table(&result["collaterals"], &COLLATERAL_COLUMNS));
+            println!("{}", rows_table(&result["positions"], &POSITION_COLUMNS));
+            println!("{}", rows_table(&result["open_orders"], &ORDER_COLUMNS));
+        }
+        "public/get_ticker" => println!("{}", fields_table(result, &TICKER_FIELDS)),
+        "public/get_tickers" => {
+            let tickers = match result["tickers"].as_object() {
+                Some(tickers) => Value::Array(tickers.values().cloned().collect()),
+                None => result["tickers"].clone(),
+            };
+            println!("{}", rows_table(&tickers, &TICKER_FIELDS))
+        }
+        _ => println!("{}", serde_json::to_string_pretty(res)?),
+    }
+    Ok(())
+}
+
+/// One row per object in the array, one column per key
+fn rows_table(rows: &Value, columns: &[&str]) -> Table {
+    let mut table = Table::new();
+    table.set_header(columns.to_vec());
+    for row in rows.as_array().into_iter().flatten() {
+        table.add_row(columns.iter().map(|c| cell(&row[*c])).collect::<Vec<_>>());
+    }
+    table
+}
+
+/// One row per field of a single object
+fn fields_table(object: &Value, fields: &[&str]) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Field", "Value"]);
+    for field in fields.iter() {
+        table.add_row(vec![field.to_string(), cell(&object[*field])]);
+    }
+    table
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
@@ -0,0 +1,131 @@
+use crate::output::{print_result, OutputFormat};
+use anyhow::{format_err, Result};
+use clap::{Args, Subcommand};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Saved params of an RPC, optionally with the method they are for (an order template)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    pub params: Value,
+}
+
+impl Preset {
+    /// Presets are kept as `{name}.json` in PRESETS_DIR, ~/.lyra/presets by default
+    pub fn dir() -> PathBuf {
+        match std::env::var("PRESETS_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => {
+                let home = std::env::var("HOME").unwrap_or(".".to_string());
+                PathBuf::from(home).join(".lyra").join("presets")
+            }
+        }
+    }
+
+    fn path(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(format_err!("Invalid preset name {}", name));
+        }
+        Ok(Preset::dir().join(format!("{name}.json")))
+    }
+
+    /// Loads the preset, a file without `params` is taken as the params themselves
+    pub async fn load(name: &str) -> Result<Self> {
+        let path = Preset::path(name)?;
+        let contents = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| format_err!("Preset {} not found at {:?}: {}", name, path, e))?;
+        let value = serde_json::from_str::<Value>(&contents)?;
+        match value.get("params") {
+            Some(_) => Ok(serde_json::from_value(value)?),
+            None => Ok(Preset { method: None, params: value }),
+        }
+    }
+
+    pub async fn save(&self, name: &str) -> Result<PathBuf> {
+        let path = Preset::path(name)?;
+        tokio::fs::create_dir_all(Preset::dir()).await?;
+        tokio::fs::write(&path, serde_json::to_string_pretty(self)?).await?;
+        Ok(path)
+    }
+}
+
+/// Merges the overrides into the params, objects key by key and anything else replaced
+pub fn merge_params(params: &mut Value, overrides: Value) {
+    match (params, overrides) {
+        (Value::Object(params), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge_params(params.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (params, overrides) => *params = overrides,
+    }
+}
+
+/// Manages the presets `rpc --preset` reads
+#[derive(Args, Debug)]
+pub struct CliPreset {
+    #[command(subcommand)]
+    pub command: PresetCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PresetCommand {
+    /// Lists the saved presets with their methods
+    List,
+    /// Prints a preset
+    Show { name: String },
+    /// Saves the params (and method) as a preset, replacing one of the same name
+    Save {
+        name: String,
+        /// The method `rpc` calls with the preset unless given one
+        #[arg(short, long)]
+        method: Option<String>,
+        /// Inline json params, e.g. '{"subaccount_id": 1}'
+        #[arg(short, long, required_unless_present = "file")]
+        inline: Option<String>,
+        /// File to read the params from
+        #[arg(short, long, conflicts_with = "inline")]
+        file: Option<PathBuf>,
+    },
+}
+
+impl CliPreset {
+    pub async fn execute(self, output: OutputFormat) -> Result<()> {
+        let res = match self.command {
+            PresetCommand::List => {
+                let mut presets = vec![];
+                let mut entries = match tokio::fs::read_dir(Preset::dir()).await {
+                    Ok(entries) => entries,
+                    Err(_) => return print_result("preset", &json!([]), output),
+                };
+                while let Some(entry) = entries.next_entry().await? {
+                    let path = entry.path();
+                    if path.extension().map_or(true, |ext| ext != "json") {
+                        continue;
+                    }
+                    let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                    let method = Preset::load(&name).await.ok().and_then(|p| p.method);
+                    presets.push(json!({ "name": name, "method": method }));
+                }
+                presets.sort_by_key(|p| p["name"].as_str().unwrap_or_default().to_string());
+                Value::from(presets)
+            }
+            PresetCommand::Show { name } => serde_json::to_value(Preset::load(&name).await?)?,
+            PresetCommand::Save { name, method, inline, file } => {
+                let params = match (inline, file) {
+                    (Some(inline), _) => inline,
+                    (None, Some(file)) => tokio::fs::read_to_string(file).await?,
+                    (None, None) => return Err(format_err!("Pass the params with -i or -f")),
+                };
+                let preset = Preset { method, params: serde_json::from_str(&params)? };
+                let path = preset.save(&name).await?;
+                json!({ "name": name, "path": path })
+            }
+        };
+        print_result("preset", &res, output)
+    }
+}
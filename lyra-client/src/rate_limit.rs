@@ -0,0 +1,231 @@
+use crate::error::LyraError;
+use anyhow::{Error, Result};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use tracing::warn;
+
+/// RPC error code returned when the request rate limit is exceeded
+pub const RATE_LIMIT_ERROR_CODE: i64 = -32000;
+/// First retry delay after the server rate limited a request, doubled on every retry
+const RATE_LIMIT_BACKOFF_MS: u64 = 500;
+
+static HTTP_LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+    pub requests_per_sec: f64, // Token refill rate, a weight 1 request costs one token
+    pub burst: f64,            // Bucket capacity, i.e. the max weight sent at once
+    pub max_queue_ms: u64, // Fail with LyraError::RateLimited instead of queueing for longer than this
+    pub max_retries: u32,  // Retries after the server rejected a request as rate limited
+    // Cost of a request per method, 1 if not listed
+    pub method_weights: HashMap<String, f64>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        let method_weights = [
+            ("public/get_instruments", 5.0),
+            ("private/get_trade_history", 5.0),
+            ("private/get_subaccount", 2.0),
+            ("private/cancel_all", 2.0),
+        ];
+        RateLimits {
+            requests_per_sec: 10.0,
+            burst: 20.0,
+            max_queue_ms: 30_000,
+            max_retries: 5,
+            method_weights: method_weights.into_iter().map(|(m, w)| (m.to_string(), w)).collect(),
+        }
+    }
+}
+
+impl RateLimits {
+    /// Defaults overridden by RATE_LIMIT_PER_SEC, RATE_LIMIT_BURST, RATE_LIMIT_MAX_QUEUE_MS,
+    /// RATE_LIMIT_MAX_RETRIES and RATE_LIMIT_WEIGHTS (e.g. "public/get_ticker=2,private/order=1")
+    pub fn from_env() -> Result<Self> {
+        let mut limits = RateLimits::default();
+        if let Ok(value) = std::env::var("RATE_LIMIT_PER_SEC") {
+            limits.requests_per_sec = value.parse()?;
+        }
+        if let Ok(value) = std::env::var("RATE_LIMIT_BURST") {
+            limits.burst = value.parse()?;
+        }
+        if let Ok(value) = std::env::var("RATE_LIMIT_MAX_QUEUE_MS") {
+            limits.max_queue_ms = value.parse()?;
+        }
+        if let Ok(value) = std::env::var("RATE_LIMIT_MAX_RETRIES") {
+            limits.max_retries = value.parse()?;
+        }
+        if let Ok(value) = std::env::var("RATE_LIMIT_WEIGHTS") {
+            for entry in value.split(',').filter(|e| !e.is_empty()) {
+                let (method, weight) = entry
+                    .split_once('=')
+                    .ok_or(Error::msg(format!("Invalid RATE_LIMIT_WEIGHTS entry: {}", entry)))?;
+                limits.method_weights.insert(method.trim().to_string(), weight.trim().parse()?);
+            }
+        }
+        if limits.requests_per_sec <= 0.0 || limits.burst <= 0.0 {
+            return Err(Error::msg("RATE_LIMIT_PER_SEC and RATE_LIMIT_BURST must be positive"));
+        }
+        Ok(limits)
+    }
+
+    pub fn weight(&self, method: &str) -> f64 {
+        self.method_weights.get(method).copied().unwrap_or(1.0).min(self.burst)
+    }
+
+    /// Delay before the given retry of a request the server rejected as rate limited
+    pub fn backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(RATE_LIMIT_BACKOFF_MS << retry.min(10))
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket shared by all requests of a client. Requests wait for their turn in FIFO order
+/// (the bucket lock is held while waiting), so a burst is spread out instead of rejected.
+pub struct RateLimiter {
+    limits: RateLimits,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        let bucket = Bucket { tokens: limits.burst, last_refill: Instant::now() };
+        RateLimiter { limits, bucket: Mutex::new(bucket) }
+    }
+
+    /// Process wide limiter used by `http_rpc`, configured from env on first use
+    pub fn http() -> &'static RateLimiter {
+        HTTP_LIMITER.get_or_init(|| {
+            let limits = RateLimits::from_env().unwrap_or_else(|e| {
+                warn!("Invalid rate limits in env ({:#}), using defaults", e);
+                RateLimits::default()
+            });
+            RateLimiter::new(limits)
+        })
+    }
+
+    pub fn limits(&self) -> &RateLimits {
+        &self.limits
+    }
+
+    /// Waits until the method's weight is available and takes it from the bucket
+    pub async fn acquire(&self, method: &str) -> Result<(), LyraError> {
+        let weight = self.limits.weight(method);
+        let mut bucket = self.bucket.lock().await;
+        bucket.refill(&self.limits);
+        if bucket.tokens < weight {
+            let wait =
+                Duration::from_secs_f64((weight - bucket.tokens) / self.limits.requests_per_sec);
+            if wait > Duration::from_millis(self.limits.max_queue_ms) {
+                let method = method.to_string();
+                return Err(LyraError::RateLimited { method, retry_after: wait });
+            }
+            tokio::time::sleep(wait).await;
+            bucket.refill(&self.limits);
+        }
+        bucket.tokens -= weight;
+        Ok(())
+    }
+
+    /// Empties the bucket so that no request is sent for `retry_after`, called when the server
+    /// rate limited a request since the local limits were evidently too generous
+    pub async fn penalize(&self, retry_after: Duration) {
+        let mut bucket = self.bucket.lock().await;
+        bucket.refill(&self.limits);
+        let debt = retry_after.as_secs_f64() * self.limits.requests_per_sec;
+        bucket.tokens = bucket.tokens.min(0.0) - debt;
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, limits: &RateLimits) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limits.requests_per_sec).min(limits.burst);
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(requests_per_sec: f64, burst: f64, max_queue_ms: u64) -> RateLimiter {
+        let method_weights =
+            HashMap::from([("heavy".to_string(), 5.0), ("huge".to_string(), 50.0)]);
+        let limits =
+            RateLimits { requests_per_sec, burst, max_queue_ms, max_retries: 0, method_weights };
+        RateLimiter::new(limits)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_then_refill_rate() {
+        let limiter = limiter(10.0, 3.0, 1_000);
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire("light").await.unwrap();
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limiter.acquire("light").await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+        limiter.acquire("light").await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_method_weights() {
+        let limiter = limiter(10.0, 10.0, 1_000);
+        let start = Instant::now();
+        limiter.acquire("heavy").await.unwrap();
+        limiter.acquire("heavy").await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limiter.acquire("heavy").await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+        // weights above the burst are capped, or the request could never be sent
+        assert_eq!(limiter.limits().weight("huge"), 10.0);
+        assert_eq!(limiter.limits().weight("light"), 1.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limited_instead_of_long_queue() {
+        let limiter = limiter(1.0, 5.0, 2_000);
+        limiter.acquire("heavy").await.unwrap();
+        match limiter.acquire("heavy").await {
+            Err(LyraError::RateLimited { method, retry_after }) => {
+                assert_eq!(method, "heavy");
+                assert_eq!(retry_after, Duration::from_secs(5));
+            }
+            res => panic!("expected RateLimited, got {:?}", res),
+        }
+        // the rejected request took no tokens
+        tokio::time::advance(Duration::from_secs(3)).await;
+        let start = Instant::now();
+        limiter.acquire("light").await.unwrap();
+        limiter.acquire("light").await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_penalize_blocks_for_retry_after() {
+        let limiter = limiter(10.0, 10.0, 10_000);
+        limiter.penalize(Duration::from_secs(2)).await;
+        let start = Instant::now();
+        limiter.acquire("light").await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(2_100));
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let limits = RateLimits::default();
+        assert_eq!(limits.backoff(0), Duration::from_millis(500));
+        assert_eq!(limits.backoff(3), Duration::from_millis(4_000));
+        assert_eq!(limits.backoff(20), limits.backoff(10));
+    }
+}
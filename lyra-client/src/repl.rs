@@ -0,0 +1,399 @@
+use crate::actions::OrderArgs;
+use crate::channels::Channel;
+use crate::cli::{confirm_cancel_all, OrderbookData};
+use crate::config::LyraConfig;
+use crate::json_rpc::{Notification, WsClient, WsClientExt};
+use crate::output::{print_error, print_result, OutputFormat};
+use crate::tickers::TickerCache;
+use anyhow::{format_err, Error, Result};
+use bigdecimal::BigDecimal;
+use clap::Args;
+use orderbook_types::types::orders::{Direction, OrderType, TimeInForce};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const BOOK_TIMEOUT_SEC: u64 = 5;
+
+const SHORTHANDS: &[&str] =
+    &["help", "ticker", "book", "positions", "order", "cancel", "subaccount", "exit", "quit"];
+
+/// RPC methods completed with tab, any other method can still be called
+const RPC_METHODS: &[&str] = &[
+    "private/cancel",
+    "private/cancel_all",
+    "private/cancel_batch_quotes",
+    "private/cancel_batch_rfqs",
+    "private/cancel_by_instrument",
+    "private/cancel_by_label",
+    "private/cancel_by_nonce",
+    "private/cancel_quote",
+    "private/cancel_rfq",
+    "private/change_session_key_label",
+    "private/change_subaccount_label",
+    "private/create_subaccount",
+    "private/deposit",
+    "private/execute_quote",
+    "private/expired_and_cancelled_history",
+    "private/get_account",
+    "private/get_collaterals",
+    "private/get_deposit_history",
+    "private/get_erc20_transfer_history",
+    "private/get_funding_history",
+    "private/get_interest_history",
+    "private/get_liquidation_history",
+    "private/get_margin",
+    "private/get_mmp_config",
+    "private/get_notifications",
+    "private/get_open_orders",
+    "private/get_option_settlement_history",
+    "private/get_order",
+    "private/get_order_history",
+    "private/get_orders",
+    "private/get_positions",
+    "private/get_quotes",
+    "private/get_rfqs",
+    "private/get_subaccount",
+    "private/get_subaccount_value_history",
+    "private/get_subaccounts",
+    "private/get_trade_history",
+    "private/get_withdrawal_history",
+    "private/order",
+    "private/order_debug",
+    "private/order_quote",
+    "private/poll_quotes",
+    "private/poll_rfqs",
+    "private/replace",
+    "private/reset_mmp",
+    "private/rfq_get_best_quote",
+    "private/send_quote",
+    "private/send_rfq",
+    "private/session_keys",
+    "private/set_cancel_on_disconnect",
+    "private/set_mmp_config",
+    "private/transfer_erc20",
+    "private/transfer_position",
+    "private/update_notifications",
+    "private/withdraw",
+    "public/build_register_session_key_tx",
+    "public/change_compliance_status",
+    "public/create_account",
+    "public/create_subaccount_debug",
+    "public/deregister_session_key",
+    "public/get_all_currencies",
+    "public/get_asset",
+    "public/get_assets",
+    "public/get_instrument",
+    "public/get_instruments",
+    "public/get_latest_signed_feeds",
+    "public/get_margin",
+    "public/get_option_settlement_history",
+    "public/get_perp_impact_twap",
+    "public/get_spot_feed_history",
+    "public/get_ticker",
+    "public/get_time",
+    "public/get_trade_history",
+    "public/get_transaction",
+    "public/login",
+    "public/margin_watch",
+    "public/register_session_key",
+    "public/set_feed_data",
+    "public/statistics",
+];
+
+const HELP: &str = "\
+ticker <instrument>                                  ticker of the instrument
+book <instrument> [depth]                            orderbook snapshot, 10 levels by default
+positions                                            positions of the subaccount
+order <buy|sell> <instrument> <amount> <price> [tif] limit order, gtc by default
+cancel <instrument> <order id> | cancel all          cancels an order or all orders
+subaccount [id]                                      prints or switches the subaccount
+<method> [json params]                               any RPC, e.g. public/get_time {}
+exit                                                 ends the session";
+
+/// Interactive shell on one logged in connection, with command history and tab completion
+/// of the commands and RPC methods
+#[derive(Args, Debug)]
+pub struct CliRepl {
+    /// Subaccount of the shorthand commands, SUBACCOUNT_ID by default
+    #[arg(short, long)]
+    pub subaccount: Option<i64>,
+
+    /// File the command history is kept in, ~/.lyra_client_history by default
+    #[arg(long)]
+    pub history_file: Option<PathBuf>,
+}
+
+struct ReplHelper {
+    instruments: Arc<Mutex<Vec<String>>>, // completed after the commands taking one
+}
+
+impl Helper for ReplHelper {}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+        let candidates = match line[..start].split_whitespace().collect::<Vec<_>>()[..] {
+            [] => SHORTHANDS.iter().chain(RPC_METHODS.iter()).map(|s| s.to_string()).collect(),
+            ["ticker"] | ["book"] | ["cancel"] | ["order", _] => {
+                self.instruments.lock().unwrap().clone()
+            }
+            ["order"] => vec!["buy".to_string(), "sell".to_string()],
+            _ => vec![],
+        };
+        let pairs = candidates
+            .into_iter()
+            .filter(|c| c.starts_with(word))
+            .map(|c| Pair { display: c.clone(), replacement: c })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl CliRepl {
+    pub async fn run(&self, output: OutputFormat) -> Result<()> {
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        let ping_client = client.clone();
+        tokio::spawn(async move { ping_client.ping_interval(15).await });
+
+        let instruments = Arc::new(Mutex::new(vec![]));
+        let (prompts, mut lines, reader) = spawn_reader(self.history_file(), instruments.clone());
+        let mut subaccount_id = self.subaccount.or(LyraConfig::current()?.subaccount_id().ok());
+        println!("Logged in, type help for the commands");
+        loop {
+            let prompt = match subaccount_id {
+                Some(subaccount_id) => format!("lyra:{}> ", subaccount_id),
+                None => "lyra> ".to_string(),
+            };
+            prompts.send(prompt)?;
+            let line = match lines.recv().await {
+                Some(Ok(line)) => line,
+                Some(Err(ReadlineError::Interrupted)) => continue,
+                Some(Err(ReadlineError::Eof)) | None => break,
+                Some(Err(e)) => return Err(e.into()),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let args = line.split_whitespace().collect::<Vec<_>>();
+            let res = match args[..] {
+                ["exit"] | ["quit"] => break,
+                ["help"] => {
+                    println!("{}", HELP);
+                    Ok(())
+                }
+                ["subaccount"] => {
+                    println!("{:?}", subaccount_id);
+                    Ok(())
+                }
+                ["subaccount", id] => match id.parse() {
+                    Ok(id) => {
+                        subaccount_id = Some(id);
+                        Ok(())
+                    }
+                    Err(e) => Err(Error::from(e)),
+                },
+                _ => {
+                    let res = execute(&client, subaccount_id, &args, line, output).await;
+                    let instrument_index = if args[0] == "order" { 2 } else { 1 };
+                    if res.is_ok() && matches!(args[0], "ticker" | "book" | "order") {
+                        remember_instrument(&instruments, args.get(instrument_index));
+                    }
+                    res
+                }
+            };
+            if let Err(e) = res {
+                print_error(&e, output)?;
+            }
+        }
+        // the reader saves the history once the prompts end
+        drop(prompts);
+        if tokio::task::spawn_blocking(move || reader.join()).await?.is_err() {
+            warn!("Line reader panicked, history not saved");
+        }
+        client.close().await?;
+        Ok(())
+    }
+
+    fn history_file(&self) -> PathBuf {
+        match &self.history_file {
+            Some(path) => path.clone(),
+            None => {
+                let home = std::env::var("HOME").unwrap_or(".".to_string());
+                PathBuf::from(home).join(".lyra_client_history")
+            }
+        }
+    }
+}
+
+/// Reads a line for every prompt sent on a thread of its own, since rustyline blocks, and saves
+/// the history once the prompt sender is dropped
+fn spawn_reader(
+    history_file: PathBuf,
+    instruments: Arc<Mutex<Vec<String>>>,
+) -> (
+    std::sync::mpsc::Sender<String>,
+    mpsc::UnboundedReceiver<rustyline::Result<String>>,
+    JoinHandle<()>,
+) {
+    let (prompt_sender, prompts) = std::sync::mpsc::channel::<String>();
+    let (line_sender, lines) = mpsc::unbounded_channel();
+    let reader = std::thread::spawn(move || {
+        let mut editor = match Editor::<ReplHelper, DefaultHistory>::new() {
+            Ok(editor) => editor,
+            Err(e) => {
+                let _ = line_sender.send(Err(e));
+                return;
+            }
+        };
+        editor.set_helper(Some(ReplHelper { instruments }));
+        if let Err(e) = editor.load_history(&history_file) {
+            info!("No history loaded from {:?}: {}", history_file, e);
+        }
+        while let Ok(prompt) = prompts.recv() {
+            let line = editor.readline(&prompt);
+            if let Ok(line) = &line {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.trim());
+                }
+            }
+            if line_sender.send(line).is_err() {
+                break;
+            }
+        }
+        if let Err(e) = editor.save_history(&history_file) {
+            warn!("History not saved to {:?}: {}", history_file, e);
+        }
+    });
+    (prompt_sender, lines, reader)
+}
+
+/// Instruments of the session, offered by the tab completion
+fn remember_instrument(instruments: &Mutex<Vec<String>>, name: Option<&&str>) {
+    let Some(name) = name.filter(|n| n.contains('-')) else {
+        return;
+    };
+    let mut instruments = instruments.lock().unwrap();
+    if !instruments.iter().any(|i| i == name) {
+        instruments.push(name.to_string());
+    }
+}
+
+async fn execute(
+    client: &WsClient,
+    subaccount_id: Option<i64>,
+    args: &[&str],
+    line: &str,
+    output: OutputFormat,
+) -> Result<()> {
+    let subaccount = || subaccount_id.ok_or(format_err!("No subaccount, set one with subaccount"));
+    let (method, res) = match args {
+        ["ticker", instrument_name] => {
+            let params = json!({ "instrument_name": instrument_name });
+            let res = client.send_rpc::<Value, Value>("public/get_ticker", params).await?;
+            ("public/get_ticker", res.into_result()?)
+        }
+        ["book", instrument_name] => ("book", get_book(instrument_name, 10).await?),
+        ["book", instrument_name, depth] => {
+            ("book", get_book(instrument_name, depth.parse()?).await?)
+        }
+        ["positions"] => {
+            let params = json!({ "subaccount_id": subaccount()? });
+            let res = client.send_rpc::<Value, Value>("private/get_positions", params).await?;
+            ("private/get_positions", res.into_result()?)
+        }
+        ["order", direction, instrument_name, amount, price, rest @ ..] if rest.len() <= 1 => {
+            let time_in_force = match rest.first() {
+                Some(tif) => serde_json::from_value::<TimeInForce>(json!(tif))?,
+                None => TimeInForce::Gtc,
+            };
+            let order_args = OrderArgs {
+                amount: amount.parse::<BigDecimal>()?,
+                limit_price: price.parse::<BigDecimal>()?,
+                direction: serde_json::from_value::<Direction>(json!(direction))?,
+                time_in_force,
+                order_type: OrderType::Limit,
+                label: "repl".to_string(),
+                mmp: false,
+                max_slippage_bps: None,
+            };
+            let ticker = TickerCache::global().get(instrument_name).await?;
+            let res = client.send_order(&ticker, subaccount()?, order_args).await?;
+            ("private/order", serde_json::to_value(res.into_result()?)?)
+        }
+        ["cancel", "all"] => {
+            let subaccount_id = subaccount()?;
+            if !confirm_cancel_all(subaccount_id)? {
+                println!("Cancel all aborted");
+                return Ok(());
+            }
+            let res = client.cancel_all(subaccount_id).await?;
+            ("private/cancel_all", serde_json::to_value(res.into_result()?)?)
+        }
+        ["cancel", instrument_name, order_id] => {
+            let order_id = Uuid::parse_str(order_id)
+                .map_err(|_| format_err!("Invalid order id {}, expected a UUID", order_id))?;
+            let res = client.cancel(subaccount()?, instrument_name.to_string(), order_id).await?;
+            ("private/cancel", serde_json::to_value(res.into_result()?)?)
+        }
+        [method, ..] if method.contains('/') => {
+            let params = line[method.len()..].trim();
+            let params = match params {
+                "" => json!({}),
+                params => serde_json::from_str::<Value>(params)?,
+            };
+            let res = client.send_rpc::<Value, Value>(method, params).await?;
+            (*method, res.into_result()?)
+        }
+        _ => return Err(format_err!("Unknown command {}, see help", line)),
+    };
+    print_result(method, &res, output)
+}
+
+/// First orderbook message of the instrument, from a connection of its own
+async fn get_book(instrument_name: &str, depth: u32) -> Result<Value> {
+    let channels = vec![Channel::orderbook(instrument_name, depth).to_string()];
+    let client = WsClient::new_client().await?;
+    let book = Arc::new(Mutex::new(None));
+    let subscription = client.subscribe(channels, |d: Notification<OrderbookData>| {
+        let book = book.clone();
+        async move {
+            *book.lock().unwrap() = Some(serde_json::to_value(d.params.data)?);
+            Err(format_err!("Snapshot received"))
+        }
+    });
+    let _ = tokio::time::timeout(Duration::from_secs(BOOK_TIMEOUT_SEC), subscription).await;
+    client.close().await?;
+    let book = book.lock().unwrap().take();
+    book.ok_or(format_err!("No orderbook of {} within {}s", instrument_name, BOOK_TIMEOUT_SEC))
+}
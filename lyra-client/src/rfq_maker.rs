@@ -0,0 +1,292 @@
+use crate::actions::{round_to_tick, Direction, LegPriced, LegUnpriced, OrderStatus, QuoteArgs};
+use crate::channels::Channel;
+use crate::error::Result;
+use crate::json_rpc::{Notification, WsClient, WsClientExt};
+use crate::subaccounts::get_subaccount;
+use crate::tickers::TickerCache;
+use crate::ws_mux::WsMux;
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use ethers::utils::hex;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use orderbook_types::types::rfqs::RFQResultPrivate;
+use orderbook_types::types::tickers::InstrumentTicker;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Tickers of the instruments by name, used to price the legs of an RFQ.
+/// See `cached_tickers`, or e.g. a vault's market state.
+pub type QuoteTickers = Arc<
+    dyn Fn(Vec<String>) -> BoxFuture<'static, anyhow::Result<HashMap<String, InstrumentTicker>>>
+        + Send
+        + Sync,
+>;
+
+/// Tickers from the process wide `TickerCache`
+pub fn cached_tickers() -> QuoteTickers {
+    Arc::new(|names: Vec<String>| {
+        async move {
+            let names = names.iter().map(String::as_str);
+            Ok(TickerCache::global().get_many(names).await?)
+        }
+        .boxed()
+    })
+}
+
+/// Spread model and limits of the RFQ maker. Each leg is quoted at its mark price moved away by
+/// `spread_bps` plus `size_spread_bps` per unit of the leg amount, at least `min_spread`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RfqMakerParams {
+    #[serde(default = "default_spread_bps")]
+    pub spread_bps: BigDecimal,
+    #[serde(default)]
+    pub size_spread_bps: BigDecimal,
+    #[serde(default)]
+    pub min_spread: BigDecimal, // per unit, in the quote currency
+    // RFQs are quoted at most this long after they were sent, later ones are skipped
+    #[serde(default = "default_quote_ttl_ms")]
+    pub quote_ttl_ms: u64,
+    pub max_leg_amount: Option<BigDecimal>, // RFQs with larger legs are skipped
+    // Max absolute position per instrument once the quote fills, counting the resting quotes
+    pub max_position: Option<BigDecimal>,
+    // Max notional of a quote, the legs' amounts times their mark prices
+    pub max_quote_notional: Option<BigDecimal>,
+    #[serde(default = "default_sides")]
+    pub sides: Vec<Direction>, // quoted directions, sell quotes sell the legs as requested
+}
+
+fn default_spread_bps() -> BigDecimal {
+    BigDecimal::from(50)
+}
+
+fn default_quote_ttl_ms() -> u64 {
+    2000
+}
+
+fn default_sides() -> Vec<Direction> {
+    vec![Direction::Buy, Direction::Sell]
+}
+
+impl RfqMakerParams {
+    /// Legs priced for a quote in the direction, None if a leg can not be priced
+    pub fn price_legs(
+        &self,
+        legs: &[LegUnpriced],
+        direction: Direction,
+        tickers: &HashMap<String, InstrumentTicker>,
+    ) -> Option<Vec<LegPriced>> {
+        legs.iter()
+            .map(|leg| {
+                let ticker = tickers.get(&leg.instrument_name)?;
+                // the maker trades each leg in its direction for buy quotes, opposite for sells
+                let side =
+                    if leg.direction == direction { Direction::Buy } else { Direction::Sell };
+                Some(LegPriced {
+                    amount: leg.amount.clone(),
+                    direction: leg.direction,
+                    instrument_name: leg.instrument_name.clone(),
+                    price: self.leg_price(ticker, side, &leg.amount)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Amounts the maker trades per leg when the quote in the direction fills, negative sells
+    fn maker_amounts(legs: &[LegUnpriced], direction: Direction) -> Vec<(String, BigDecimal)> {
+        let amounts = legs.iter().map(|leg| {
+            let amount = match leg.direction == direction {
+                true => leg.amount.clone(),
+                false => -leg.amount.clone(),
+            };
+            (leg.instrument_name.clone(), amount)
+        });
+        amounts.collect()
+    }
+
+    /// Mark price moved away from the maker's side by the spread, on the tick
+    fn leg_price(
+        &self,
+        ticker: &InstrumentTicker,
+        side: Direction,
+        amount: &BigDecimal,
+    ) -> Option<BigDecimal> {
+        let mark = &ticker.mark_price;
+        let bps = &self.spread_bps + &self.size_spread_bps * amount;
+        let spread = (mark * bps / BigDecimal::from(10_000)).max(self.min_spread.clone());
+        let price = match side {
+            Direction::Buy => {
+                round_to_tick(&(mark - spread), &ticker.tick_size, RoundingMode::Down)
+            }
+            Direction::Sell => round_to_tick(&(mark + spread), &ticker.tick_size, RoundingMode::Up),
+        };
+        let price = price.max(ticker.min_price.clone()).min(ticker.max_price.clone());
+        (price > BigDecimal::zero()).then_some(price)
+    }
+}
+
+/// A quoted RFQ, until it is no longer valid
+#[derive(Debug, Default)]
+struct QuotedRfq {
+    valid_until: i64,                    // in ms
+    exposure: Vec<(String, BigDecimal)>, // maker amounts of the quoted sides, see `maker_amounts`
+}
+
+/// Quotes the RFQs sent to the wallet of the config (the `{wallet}.rfqs` channel) from the
+/// subaccount, priced by the spread model around the mark prices of `tickers`. Each open RFQ is
+/// quoted once per side within `quote_ttl_ms` of its creation, the quotes rest until the RFQ
+/// expires or is filled. Sides that would take a position past `max_position` (with all resting
+/// quotes filled) or a quote above `max_quote_notional` are not quoted.
+pub struct RfqMaker {
+    subaccount_id: i64,
+    params: RfqMakerParams,
+    tickers: QuoteTickers,
+    dry_run: bool, // only log the quotes
+    quoted: Mutex<HashMap<Uuid, QuotedRfq>>,
+}
+
+impl RfqMaker {
+    pub fn new(
+        subaccount_id: i64,
+        params: RfqMakerParams,
+        tickers: QuoteTickers,
+        dry_run: bool,
+    ) -> Self {
+        Self { subaccount_id, params, tickers, dry_run, quoted: Mutex::new(HashMap::new()) }
+    }
+
+    /// Quotes incoming RFQs on the shared connection until it fails
+    pub async fn run(&self) -> Result<()> {
+        let mux = WsMux::shared().await?;
+        let client = mux.client();
+        let owner = client.get_config().await.owner()?;
+        let channel = Channel::rfqs(&hex::encode_prefixed(owner)).to_string();
+        info!("RFQ maker of {} quoting {}", self.subaccount_id, channel);
+        mux.subscribe(vec![channel], |msg: Notification<Vec<RFQResultPrivate>>| {
+            let client = client.clone();
+            async move {
+                for rfq in msg.params.data {
+                    self.handle(&client, rfq).await;
+                }
+                Ok(())
+            }
+        })
+        .await
+    }
+
+    async fn handle(&self, client: &WsClient, rfq: RFQResultPrivate) {
+        let now = chrono::Utc::now().timestamp_millis();
+        let deadline = rfq.creation_timestamp + self.params.quote_ttl_ms as i64;
+        if rfq.status != OrderStatus::Open || rfq.subaccount_id == self.subaccount_id {
+            return;
+        }
+        if now >= deadline.min(rfq.valid_until) {
+            info!("Skipping RFQ {} sent {} ms ago", rfq.rfq_id, now - rfq.creation_timestamp);
+            return;
+        }
+        {
+            let mut quoted = self.quoted.lock().unwrap();
+            quoted.retain(|_, quote| quote.valid_until > now);
+            let quote = QuotedRfq { valid_until: rfq.valid_until, exposure: vec![] };
+            if quoted.insert(rfq.rfq_id, quote).is_some() {
+                return;
+            }
+        }
+        let timeout = Duration::from_millis((deadline - now) as u64);
+        match tokio::time::timeout(timeout, self.quote(client, &rfq)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Quoting RFQ {} failed with {}", rfq.rfq_id, e),
+            Err(_) => warn!("Quoting RFQ {} took longer than the TTL", rfq.rfq_id),
+        }
+    }
+
+    async fn quote(&self, client: &WsClient, rfq: &RFQResultPrivate) -> Result<()> {
+        if let Some(max_amount) = &self.params.max_leg_amount {
+            if rfq.legs.iter().any(|leg| &leg.amount > max_amount) {
+                info!("Skipping RFQ {} with legs above {}", rfq.rfq_id, max_amount);
+                return Ok(());
+            }
+        }
+        let names = rfq.legs.iter().map(|leg| leg.instrument_name.clone()).collect();
+        let tickers = (self.tickers)(names).await?;
+        if let Some(max_notional) = &self.params.max_quote_notional {
+            let notional = quote_notional(&rfq.legs, &tickers);
+            if &notional > max_notional {
+                info!(
+                    "Skipping RFQ {} with notional {} above {}",
+                    rfq.rfq_id, notional, max_notional
+                );
+                return Ok(());
+            }
+        }
+        let positions = self.positions(client).await?;
+        for direction in self.params.sides.iter() {
+            let amounts = RfqMakerParams::maker_amounts(&rfq.legs, *direction);
+            if let Some(name) = self.exceeds_max_position(&amounts, &positions) {
+                info!(
+                    "Not quoting RFQ {} ({:?}), {} above max position",
+                    rfq.rfq_id, direction, name
+                );
+                continue;
+            }
+            let Some(legs) = self.params.price_legs(&rfq.legs, *direction, &tickers) else {
+                warn!("Could not price RFQ {} for a {:?} quote", rfq.rfq_id, direction);
+                continue;
+            };
+            let args = QuoteArgs { rfq_id: rfq.rfq_id, direction: *direction, legs };
+            if self.dry_run {
+                info!("RFQ maker (dry run) would quote {:?}", args);
+                continue;
+            }
+            let res = client.send_quote(&tickers, self.subaccount_id, args).await?.into_result()?;
+            info!("Quoted RFQ {} ({:?}): {}", rfq.rfq_id, direction, res["result"]["quote_id"]);
+            if let Some(quote) = self.quoted.lock().unwrap().get_mut(&rfq.rfq_id) {
+                quote.exposure.extend(amounts);
+            }
+        }
+        Ok(())
+    }
+
+    /// Positions of the subaccount by instrument, only fetched with a `max_position`
+    async fn positions(&self, client: &WsClient) -> Result<HashMap<String, BigDecimal>> {
+        if self.params.max_position.is_none() {
+            return Ok(HashMap::new());
+        }
+        let positions = get_subaccount(client, self.subaccount_id).await?.positions;
+        Ok(positions.into_iter().map(|p| (p.instrument_name, p.amount)).collect())
+    }
+
+    /// The first instrument that would end up past `max_position` if the quote and all resting
+    /// quotes on the same side of it filled
+    fn exceeds_max_position(
+        &self,
+        amounts: &[(String, BigDecimal)],
+        positions: &HashMap<String, BigDecimal>,
+    ) -> Option<String> {
+        let max_position = self.params.max_position.as_ref()?;
+        let quoted = self.quoted.lock().unwrap();
+        let resting = quoted.values().flat_map(|quote| quote.exposure.iter());
+        amounts.iter().find_map(|(name, amount)| {
+            let is_long = amount > &BigDecimal::zero();
+            let same_side = resting
+                .clone()
+                .filter(|(n, a)| n == name && (a > &BigDecimal::zero()) == is_long)
+                .fold(BigDecimal::zero(), |sum, (_, a)| sum + a);
+            let position = positions.get(name).cloned().unwrap_or_default();
+            let worst = position + same_side + amount;
+            (&worst.abs() > max_position).then(|| name.clone())
+        })
+    }
+}
+
+/// Sum of the legs' amounts times their mark prices, legs without a ticker count as zero
+fn quote_notional(legs: &[LegUnpriced], tickers: &HashMap<String, InstrumentTicker>) -> BigDecimal {
+    let notionals = legs.iter().filter_map(|leg| {
+        let ticker = tickers.get(&leg.instrument_name)?;
+        Some(&leg.amount * &ticker.mark_price)
+    });
+    notionals.fold(BigDecimal::zero(), |sum, notional| sum + notional)
+}
@@ -0,0 +1,292 @@
+use crate::auth::sign_auth_header;
+use crate::aws::put_secret;
+use crate::config::LyraConfig;
+use crate::error::{LyraError, Result};
+use crate::json_rpc::http_rpc;
+use crate::signer::LyraSigner;
+use crate::utils::await_tx_settlement;
+use ethers::abi::Detokenize;
+use ethers::contract::abigen;
+use ethers::core::rand::thread_rng;
+use ethers::prelude::{Address, Http, LocalWallet, Middleware, Provider, Signer};
+use ethers::utils::hex;
+use orderbook_types::generated::private_session_keys::{
+    PrivateSessionKeysParamsSchema, PrivateSessionKeysResponseSchema, SessionKeyResponseSchema,
+};
+use orderbook_types::generated::public_deregister_session_key::{
+    PublicDeregisterSessionKeyParamsSchema, PublicDeregisterSessionKeyResponseSchema,
+};
+use orderbook_types::generated::public_get_transaction::Status;
+use orderbook_types::generated::public_register_session_key::{
+    PublicRegisterSessionKeyParamsSchema, PublicRegisterSessionKeyResponseSchema,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+abigen!(
+    MatchingSessionKeys,
+    r#"[
+        function registerSessionKey(address sessionKey, uint256 expiry)
+        function deregisterSessionKey(address sessionKey)
+    ]"#
+);
+
+const ACTIVATION_POLL_SEC: u64 = 5;
+
+/// Session keys that replaced the configured one after a rotation, by owner
+static ROTATED_SESSION_KEYS: OnceLock<RwLock<HashMap<Address, LyraSigner>>> = OnceLock::new();
+
+/// The key the session of `config` was rotated to, if any. Takes precedence over
+/// `config.session_key` and the `SESSION` signer.
+pub fn rotated_session_key(config: &LyraConfig) -> Option<LyraSigner> {
+    let owner = config.owner().ok()?;
+    let rotated = ROTATED_SESSION_KEYS.get_or_init(Default::default).read().unwrap();
+    rotated.get(&owner).cloned()
+}
+
+fn set_rotated_session_key(owner: Address, session_key: LyraSigner) {
+    let mut rotated = ROTATED_SESSION_KEYS.get_or_init(Default::default).write().unwrap();
+    rotated.insert(owner, session_key);
+}
+
+/// Registered session keys of the current owner, authorized with `session_key`
+pub async fn get_session_keys(session_key: &LyraSigner) -> Result<Vec<SessionKeyResponseSchema>> {
+    let wallet = hex::encode_prefixed(LyraConfig::current()?.owner()?);
+    let headers = sign_auth_header(session_key).await?;
+    let res = http_rpc::<_, PrivateSessionKeysResponseSchema>(
+        "private/session_keys",
+        PrivateSessionKeysParamsSchema { wallet },
+        Some(headers),
+    )
+    .await?
+    .into_result()?;
+    Ok(res.result.public_session_keys)
+}
+
+/// Expiry of `session_key` as registered with the exchange, None if it is not listed
+pub async fn get_session_key_expiry(session_key: &LyraSigner) -> Result<Option<i64>> {
+    let address = session_key.address();
+    let keys = get_session_keys(session_key).await?;
+    let key =
+        keys.into_iter().find(|k| k.public_session_key.parse::<Address>().ok() == Some(address));
+    Ok(key.map(|k| k.expiry_sec))
+}
+
+/// Registers and revokes the session keys of an owner
+pub trait SessionKeyRegistrar: Sync {
+    fn register(
+        &self,
+        session_key: Address,
+        expiry_sec: i64,
+        label: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// True once the new key is accepted for the owner, by default when it can authorize
+    /// private requests
+    fn is_active(&self, session_key: &LyraSigner) -> impl Future<Output = Result<bool>> + Send {
+        async move {
+            match get_session_keys(session_key).await {
+                Ok(_) => Ok(true),
+                Err(LyraError::Unauthorized(_)) => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    fn revoke(&self, session_key: Address) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Registers session keys with the exchange through the API, the Matching contract txs are
+/// signed by the owner wallet (an EOA) and relayed by the API
+pub struct ApiRegistrar {
+    pub owner_signer: LyraSigner,
+    pub config: Arc<LyraConfig>,
+    provider: Provider<Http>,
+}
+
+impl ApiRegistrar {
+    /// Txs are filled (nonce, gas and fees) with the WEB3_PROVIDER node
+    pub fn new(owner_signer: LyraSigner, config: Arc<LyraConfig>) -> Result<Self> {
+        let provider_url = std::env::var("WEB3_PROVIDER").expect("WEB3_PROVIDER must be set");
+        let provider = Provider::<Http>::try_from(provider_url).map_err(anyhow::Error::from)?;
+        Ok(Self { owner_signer, config, provider })
+    }
+
+    fn matching(&self) -> MatchingSessionKeys<Provider<Http>> {
+        MatchingSessionKeys::new(self.config.matching_address, Arc::new(self.provider.clone()))
+    }
+
+    fn wallet(&self) -> String {
+        hex::encode_prefixed(self.owner_signer.address())
+    }
+
+    async fn sign_raw_tx<D: Detokenize>(
+        &self,
+        call: ethers::contract::ContractCall<Provider<Http>, D>,
+    ) -> Result<String> {
+        let mut tx = call.tx;
+        tx.set_from(self.owner_signer.address());
+        tx.set_chain_id(self.config.chain_id);
+        self.provider.fill_transaction(&mut tx, None).await.map_err(LyraError::transport)?;
+        let signature = self.owner_signer.sign_transaction(&tx).await?;
+        Ok(hex::encode_prefixed(tx.rlp_signed(&signature)))
+    }
+
+    async fn await_settled(&self, transaction_id: uuid::Uuid) -> Result<()> {
+        let tx = await_tx_settlement(transaction_id).await?;
+        match tx.status {
+            Status::Settled => Ok(()),
+            _ => Err(LyraError::Other(anyhow::format_err!(
+                "Session key tx {} not settled: {:?}",
+                transaction_id,
+                tx.status
+            ))),
+        }
+    }
+}
+
+impl SessionKeyRegistrar for ApiRegistrar {
+    async fn register(&self, session_key: Address, expiry_sec: i64, label: &str) -> Result<()> {
+        let call = self.matching().register_session_key(session_key, expiry_sec.into());
+        let params = PublicRegisterSessionKeyParamsSchema {
+            expiry_sec,
+            label: label.to_string(),
+            public_session_key: hex::encode_prefixed(session_key),
+            signed_raw_tx: self.sign_raw_tx(call).await?,
+            wallet: self.wallet(),
+        };
+        let res = http_rpc::<_, PublicRegisterSessionKeyResponseSchema>(
+            "public/register_session_key",
+            params,
+            None,
+        )
+        .await?
+        .into_result()?;
+        self.await_settled(res.result.transaction_id).await
+    }
+
+    async fn revoke(&self, session_key: Address) -> Result<()> {
+        let call = self.matching().deregister_session_key(session_key);
+        let params = PublicDeregisterSessionKeyParamsSchema {
+            public_session_key: hex::encode_prefixed(session_key),
+            signed_raw_tx: self.sign_raw_tx(call).await?,
+            wallet: self.wallet(),
+        };
+        let res = http_rpc::<_, PublicDeregisterSessionKeyResponseSchema>(
+            "public/deregister_session_key",
+            params,
+            None,
+        )
+        .await?
+        .into_result()?;
+        self.await_settled(res.result.transaction_id).await
+    }
+}
+
+/// Where the new session key is kept so that restarts pick it up
+#[derive(Debug, Clone)]
+pub enum SessionKeyStore {
+    /// AWS parameter `/session_keys/{env}/{name}`, as loaded by `ensure_session_key`
+    Aws(String),
+    /// File with the hex private key, readable by the owner only
+    File(PathBuf),
+}
+
+impl SessionKeyStore {
+    async fn store(&self, session_key: &LocalWallet) -> Result<()> {
+        let private_key = hex::encode_prefixed(session_key.signer().to_bytes());
+        match self {
+            SessionKeyStore::Aws(name) => {
+                let env = LyraConfig::current()?.env.clone();
+                put_secret(&format!("/session_keys/{env}/{name}"), &private_key, None).await?;
+            }
+            SessionKeyStore::File(path) => {
+                let mut file = std::fs::OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .mode(0o600)
+                    .open(path)
+                    .map_err(anyhow::Error::from)?;
+                file.write_all(private_key.as_bytes()).map_err(anyhow::Error::from)?;
+                info!("Stored session key in {:?}", path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces a session key: generates a new key, registers it until `lifetime_sec` from now,
+/// waits until it is active, stores it, switches signing to it and revokes the old key.
+/// The old key stays valid until the new one is stored, so a failed rotation is retryable.
+#[derive(Debug, Clone)]
+pub struct SessionKeyRotation {
+    pub lifetime_sec: i64,
+    pub label: String,
+    pub activation_timeout: Duration,
+    pub store: SessionKeyStore,
+}
+
+impl SessionKeyRotation {
+    /// Generates a new key for the current `LyraConfig` owner, registers it and stores it once
+    /// it is active, without switching signing to it (e.g. the first key of a new owner)
+    pub async fn register_new(&self, registrar: &impl SessionKeyRegistrar) -> Result<LyraSigner> {
+        let config = LyraConfig::current()?;
+        let wallet = LocalWallet::new(&mut thread_rng()).with_chain_id(config.chain_id);
+        let new_key = LyraSigner::from(wallet.clone());
+        let expiry_sec = chrono::Utc::now().timestamp() + self.lifetime_sec;
+        info!(
+            "Registering session key {:?} of {:?}, expiring at {}",
+            new_key.address(),
+            config.owner()?,
+            expiry_sec
+        );
+        registrar.register(new_key.address(), expiry_sec, &self.label).await?;
+        self.await_active(registrar, &new_key).await?;
+        self.store.store(&wallet).await?;
+        Ok(new_key)
+    }
+
+    /// Rotates the session key of the current `LyraConfig` owner, returns the new key
+    pub async fn rotate(
+        &self,
+        registrar: &impl SessionKeyRegistrar,
+        old_key: &LyraSigner,
+    ) -> Result<LyraSigner> {
+        let owner = LyraConfig::current()?.owner()?;
+        info!("Rotating session key of {:?} from {:?}", owner, old_key.address());
+        let new_key = self.register_new(registrar).await?;
+        set_rotated_session_key(owner, new_key.clone());
+        info!("Switched session key of {:?} to {:?}", owner, new_key.address());
+        // the new key is in use already, a failed revoke only leaves the old key to expire
+        if let Err(e) = registrar.revoke(old_key.address()).await {
+            warn!("Failed to revoke session key {:?}: {}", old_key.address(), e);
+        }
+        Ok(new_key)
+    }
+
+    async fn await_active(
+        &self,
+        registrar: &impl SessionKeyRegistrar,
+        new_key: &LyraSigner,
+    ) -> Result<()> {
+        let deadline = Instant::now() + self.activation_timeout;
+        while !registrar.is_active(new_key).await? {
+            if Instant::now() >= deadline {
+                return Err(LyraError::signing(format!(
+                    "Session key {:?} not active after {:?}",
+                    new_key.address(),
+                    self.activation_timeout
+                )));
+            }
+            tokio::time::sleep(Duration::from_secs(ACTIVATION_POLL_SEC)).await;
+        }
+        Ok(())
+    }
+}
@@ -1,47 +1,94 @@
 use crate::aws::get_secret;
-use dotenv::dotenv;
-use env_logger;
-use log::{info, warn};
+use crate::config::LyraConfig;
+use crate::logging::init_logging;
+use crate::signer::{LyraSigner, SignerProvider};
+use anyhow::{Error, Result};
+use ethers::prelude::{Address, LocalWallet};
+use tracing::info;
 
-pub async fn ensure_env() {
-    let env_name = std::env::var("ENV").expect("ENV must be set");
-    match env_name.as_str() {
+pub async fn ensure_env(env_name: &str) {
+    match env_name {
         "staging" | "prod" => (),
         _ => panic!("Invalid env name"),
     }
 }
 
-/// Validates that the session private key is set in the environment or loads it from AWS if not.
-/// Will panic if neither is set.
-pub async fn ensure_session_key() {
-    let mut pk_str = std::env::var("SESSION_PRIVATE_KEY");
-    if pk_str.is_err() {
-        info!("No signer in env, loading signer from AWS");
-        let env = std::env::var("ENV").expect("ENV must be set");
-        let name = std::env::var("SESSION_KEY_NAME").expect("SESSION_KEY_NAME must be set");
-        let aws_param_name = format!("/session_keys/{env}/{name}");
-        std::env::set_var("SESSION_PRIVATE_KEY", get_secret(&aws_param_name, None).await);
+/// The session key loaded from the `/session_keys/{env}/{name}` AWS parameter, None if
+/// SESSION_PRIVATE_KEY is set (read by `LyraConfig::from_env`) or `SESSION_SIGNER` selects a KMS
+/// or ledger signer. Fails if the key has to be loaded from AWS and no name is given.
+pub async fn ensure_session_key(env: &str, name: Option<&str>) -> Result<Option<LyraSigner>> {
+    let signer_type = std::env::var("SESSION_SIGNER").unwrap_or("local".to_string());
+    if std::env::var("SESSION_PRIVATE_KEY").is_ok() || signer_type != "local" {
+        return Ok(None);
+    }
+    info!("No signer in env, loading signer from AWS");
+    let name = name.ok_or(Error::msg("SESSION_KEY_NAME must be set"))?;
+    let pk_str = get_secret(&format!("/session_keys/{env}/{name}"), None).await;
+    Ok(Some(pk_str.parse::<LocalWallet>()?.into()))
+}
+
+/// The owner loaded from the `/owners/{env}/{name}` AWS parameter, None if OWNER_PUBLIC_KEY is
+/// set (read by `LyraConfig::from_env`). Fails if it has to be loaded and no name is given.
+pub async fn ensure_owner(env: &str, name: Option<&str>) -> Result<Option<Address>> {
+    if std::env::var("OWNER_PUBLIC_KEY").is_ok() {
+        return Ok(None);
+    }
+    info!("No owner in env, loading owner from AWS");
+    let name = name.ok_or(Error::msg("OWNER_KEY_NAME must be set"))?;
+    let owner = get_secret(&format!("/owners/{env}/{name}"), None).await;
+    Ok(Some(owner.parse()?))
+}
+
+/// The config of `env` with the session key and owner of SESSION_KEY_NAME and OWNER_KEY_NAME
+/// loaded from AWS unless set in the env files, see `ensure_session_key` and `ensure_owner`
+pub async fn env_config(env: &str) -> Result<LyraConfig> {
+    let mut config = LyraConfig::from_env()?.with_env(env);
+    let key_name = std::env::var("SESSION_KEY_NAME").ok();
+    if let Some(session_key) = ensure_session_key(env, key_name.as_deref()).await? {
+        config = config.with_session_key(session_key);
+    }
+    let owner_name = std::env::var("OWNER_KEY_NAME").ok();
+    if let Some(owner) = ensure_owner(env, owner_name.as_deref()).await? {
+        config = config.with_owner(owner);
     }
+    Ok(config)
 }
 
-/// Validates that the owner public key is set in the environment or loads it from AWS if not.
-/// Will panic if neither is set.
-pub async fn ensure_owner() {
-    let mut pk_str = std::env::var("OWNER_PUBLIC_KEY");
-    if pk_str.is_err() {
-        info!("No owner in env, loading owner from AWS");
-        let env = std::env::var("ENV").expect("ENV must be set");
-        let name = std::env::var("OWNER_KEY_NAME").expect("OWNER_KEY_NAME must be set");
-        let aws_param_name = format!("/owners/{env}/{name}");
-        std::env::set_var("OWNER_PUBLIC_KEY", get_secret(&aws_param_name, None).await);
+/// Loads a named session key from `{NAME}_SESSION_PRIVATE_KEY` or AWS without setting any env
+/// vars, for processes that run several vaults with different session keys. With
+/// `{NAME}_SESSION_SIGNER` set to "aws" or "ledger" the key is loaded as that signer instead.
+pub async fn load_session_key(env: &str, name: &str) -> Result<LyraSigner> {
+    let signer_name = format!("{}_SESSION", name.to_uppercase());
+    if std::env::var(format!("{signer_name}_SIGNER")).is_ok_and(|s| s != "local") {
+        let chain_id = std::env::var("CHAIN_ID")?.parse()?;
+        return Ok(LyraSigner::load(&signer_name, chain_id).await?);
     }
+    let env_key = format!("{signer_name}_PRIVATE_KEY");
+    let pk_str = match std::env::var(&env_key) {
+        Ok(pk_str) => pk_str,
+        Err(_) => {
+            info!("No {} in env, loading session key from AWS", env_key);
+            get_secret(&format!("/session_keys/{env}/{name}"), None).await
+        }
+    };
+    Ok(pk_str.parse::<LocalWallet>()?.into())
+}
+
+/// Loads `.env` and the constants (and keys, if present) of the env it sets in ENV, which is
+/// returned
+pub async fn setup_env() -> String {
+    dotenv::from_filename(".env").expect("Failed to load .env file");
+    let env_name = std::env::var("ENV").expect("ENV must be set");
+    setup_env_for(&env_name).await;
+    env_name
 }
 
-pub async fn setup_env() {
+/// Loads `.env` and the constants (and keys, if present) of `env_name`, e.g. the env of the
+/// vault params, whatever ENV is set to
+pub async fn setup_env_for(env_name: &str) {
     dotenv::from_filename(".env").expect("Failed to load .env file");
-    info!("{}", std::env::var("ENV").unwrap());
-    ensure_env().await;
-    let env_name = std::env::var("ENV").unwrap();
+    info!("{}", env_name);
+    ensure_env(env_name).await;
     let env_consts = format!(".env.constants.{env_name}");
     let env_keys = format!(".env.keys.{env_name}");
     dotenv::from_filename(env_consts).expect("Failed to load .env.constants.{} file");
@@ -49,5 +96,5 @@ pub async fn setup_env() {
     if key_loaded.is_err() {
         println!("No keys file found for env, expecting them to be in AWS");
     }
-    env_logger::builder().format_timestamp_millis().init();
+    init_logging();
 }
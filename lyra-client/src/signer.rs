@@ -0,0 +1,226 @@
+use crate::actions::ActionData;
+use crate::config::LyraConfig;
+use crate::error::{LyraError, Result};
+use async_trait::async_trait;
+use ethers::prelude::{Address, LocalWallet, Signature, Signer};
+#[cfg(feature = "aws-kms")]
+use ethers::signers::AwsSigner;
+#[cfg(feature = "ledger")]
+use ethers::signers::{HDPath, Ledger};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip712::{EIP712Domain, Eip712, Eip712Error};
+use std::collections::HashMap;
+use std::future::Future;
+#[cfg(feature = "ledger")]
+use std::sync::Arc;
+use std::sync::OnceLock;
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Signers that are expensive to load (a KMS key lookup, the ledger device) are loaded once
+static LOADED_SIGNERS: OnceLock<Mutex<HashMap<String, LyraSigner>>> = OnceLock::new();
+
+/// A key that signs Lyra actions, auth headers and txs, wherever it is kept. Actions are signed
+/// as EIP-712 typed data (domain separator + action hash) so that devices which refuse to sign
+/// raw hashes, i.e. ledgers, can sign them too.
+pub trait SignerProvider: Signer + Sized + 'static {
+    /// Loads the signer named e.g. "SESSION" or "KEEPER" from its `{NAME}_*` env vars
+    fn load(name: &str, chain_id: u64) -> impl Future<Output = Result<Self>> + Send;
+
+    /// Signs the typed data hash of the action, i.e. `ActionData::hash(config)`
+    fn sign_action(
+        &self,
+        action: &ActionData,
+        config: &LyraConfig,
+    ) -> impl Future<Output = Result<Signature>> + Send {
+        let typed_action =
+            TypedAction { action: action.clone(), domain_separator: config.domain_separator };
+        async move { self.sign_typed_data(&typed_action).await.map_err(LyraError::signing) }
+    }
+
+    /// Signs a login / auth header timestamp as a personal message
+    fn sign_auth(&self, timestamp: &str) -> impl Future<Output = Result<Signature>> + Send {
+        let timestamp = timestamp.to_string();
+        async move { self.sign_message(timestamp).await.map_err(LyraError::signing) }
+    }
+}
+
+/// Action with the domain separator of the config it is signed for, which is either the
+/// EIP-712 one or the env provided one with the `legacy-signing` feature
+#[derive(Debug, Clone)]
+struct TypedAction {
+    action: ActionData,
+    domain_separator: [u8; 32],
+}
+
+impl Eip712 for TypedAction {
+    type Error = Eip712Error;
+
+    fn domain(&self) -> Result<EIP712Domain, Self::Error> {
+        self.action.domain()
+    }
+
+    fn type_hash() -> Result<[u8; 32], Self::Error> {
+        ActionData::type_hash()
+    }
+
+    fn domain_separator(&self) -> Result<[u8; 32], Self::Error> {
+        Ok(self.domain_separator)
+    }
+
+    fn struct_hash(&self) -> Result<[u8; 32], Self::Error> {
+        self.action.struct_hash()
+    }
+}
+
+/// Raw private key in `{NAME}_PRIVATE_KEY`
+impl SignerProvider for LocalWallet {
+    async fn load(name: &str, chain_id: u64) -> Result<Self> {
+        let env_name = format!("{name}_PRIVATE_KEY");
+        info!("Loading signer from env {}", &env_name);
+        let pk_str = std::env::var(&env_name)
+            .map_err(|_| LyraError::signing(format!("{env_name} not set")))?;
+        Ok(pk_str.parse::<LocalWallet>()?.with_chain_id(chain_id))
+    }
+}
+
+/// AWS KMS key `{NAME}_KMS_KEY_ID` (id, ARN or alias) in the region of the AWS env config
+#[cfg(feature = "aws-kms")]
+impl SignerProvider for AwsSigner {
+    async fn load(name: &str, chain_id: u64) -> Result<Self> {
+        let env_name = format!("{name}_KMS_KEY_ID");
+        let key_id = std::env::var(&env_name)
+            .map_err(|_| LyraError::signing(format!("{env_name} not set")))?;
+        info!("Loading {} signer from KMS key {}", name, key_id);
+        let kms = rusoto_kms::KmsClient::new(rusoto_core::Region::default());
+        AwsSigner::new(kms, key_id, chain_id).await.map_err(LyraError::signing)
+    }
+}
+
+/// Ledger Live account `{NAME}_LEDGER_INDEX` (default 0) of the connected device, the ledger
+/// Ethereum app needs blind signing enabled for actions
+#[cfg(feature = "ledger")]
+impl SignerProvider for Ledger {
+    async fn load(name: &str, chain_id: u64) -> Result<Self> {
+        let index = match std::env::var(format!("{name}_LEDGER_INDEX")) {
+            Ok(index) => index.parse()?,
+            Err(_) => 0,
+        };
+        info!("Loading {} signer from ledger account {}", name, index);
+        Ledger::new(HDPath::LedgerLive(index), chain_id).await.map_err(LyraError::signing)
+    }
+}
+
+/// The signer selected by `{NAME}_SIGNER`: "local" (default), "aws" or "ledger"
+#[derive(Debug, Clone)]
+pub enum LyraSigner {
+    Local(LocalWallet),
+    #[cfg(feature = "aws-kms")]
+    Aws(AwsSigner),
+    #[cfg(feature = "ledger")]
+    Ledger(Arc<Ledger>),
+}
+
+impl From<LocalWallet> for LyraSigner {
+    fn from(wallet: LocalWallet) -> Self {
+        LyraSigner::Local(wallet)
+    }
+}
+
+impl LyraSigner {
+    async fn load_uncached(name: &str, chain_id: u64) -> Result<Self> {
+        let signer_type = std::env::var(format!("{name}_SIGNER")).unwrap_or("local".to_string());
+        match signer_type.as_str() {
+            "local" => Ok(LyraSigner::Local(LocalWallet::load(name, chain_id).await?)),
+            #[cfg(feature = "aws-kms")]
+            "aws" => Ok(LyraSigner::Aws(AwsSigner::load(name, chain_id).await?)),
+            #[cfg(feature = "ledger")]
+            "ledger" => Ok(LyraSigner::Ledger(Arc::new(Ledger::load(name, chain_id).await?))),
+            _ => Err(LyraError::signing(format!(
+                "Unsupported {name}_SIGNER {signer_type}, check the enabled signer features"
+            ))),
+        }
+    }
+}
+
+impl SignerProvider for LyraSigner {
+    /// Remote and hardware signers are cached per name, local keys are re-read from env
+    async fn load(name: &str, chain_id: u64) -> Result<Self> {
+        let mut loaded = LOADED_SIGNERS.get_or_init(Default::default).lock().await;
+        let key = format!("{name}:{chain_id}");
+        if let Some(signer) = loaded.get(&key) {
+            return Ok(signer.clone());
+        }
+        let signer = LyraSigner::load_uncached(name, chain_id).await?;
+        if !matches!(signer, LyraSigner::Local(_)) {
+            loaded.insert(key, signer.clone());
+        }
+        Ok(signer)
+    }
+}
+
+#[async_trait]
+impl Signer for LyraSigner {
+    type Error = LyraError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(&self, message: S) -> Result<Signature> {
+        match self {
+            LyraSigner::Local(s) => s.sign_message(message).await.map_err(LyraError::signing),
+            #[cfg(feature = "aws-kms")]
+            LyraSigner::Aws(s) => s.sign_message(message).await.map_err(LyraError::signing),
+            #[cfg(feature = "ledger")]
+            LyraSigner::Ledger(s) => s.sign_message(message).await.map_err(LyraError::signing),
+        }
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature> {
+        match self {
+            LyraSigner::Local(s) => s.sign_transaction(tx).await.map_err(LyraError::signing),
+            #[cfg(feature = "aws-kms")]
+            LyraSigner::Aws(s) => s.sign_transaction(tx).await.map_err(LyraError::signing),
+            #[cfg(feature = "ledger")]
+            LyraSigner::Ledger(s) => s.sign_transaction(tx).await.map_err(LyraError::signing),
+        }
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(&self, payload: &T) -> Result<Signature> {
+        match self {
+            LyraSigner::Local(s) => s.sign_typed_data(payload).await.map_err(LyraError::signing),
+            #[cfg(feature = "aws-kms")]
+            LyraSigner::Aws(s) => s.sign_typed_data(payload).await.map_err(LyraError::signing),
+            #[cfg(feature = "ledger")]
+            LyraSigner::Ledger(s) => s.sign_typed_data(payload).await.map_err(LyraError::signing),
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            LyraSigner::Local(s) => s.address(),
+            #[cfg(feature = "aws-kms")]
+            LyraSigner::Aws(s) => s.address(),
+            #[cfg(feature = "ledger")]
+            LyraSigner::Ledger(s) => s.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            LyraSigner::Local(s) => s.chain_id(),
+            #[cfg(feature = "aws-kms")]
+            LyraSigner::Aws(s) => s.chain_id(),
+            #[cfg(feature = "ledger")]
+            LyraSigner::Ledger(s) => s.chain_id(),
+        }
+    }
+
+    /// The ledger is shared by all clones and keeps the chain id it was loaded with
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            LyraSigner::Local(s) => LyraSigner::Local(s.with_chain_id(chain_id)),
+            #[cfg(feature = "aws-kms")]
+            LyraSigner::Aws(s) => LyraSigner::Aws(s.with_chain_id(chain_id)),
+            #[cfg(feature = "ledger")]
+            LyraSigner::Ledger(s) => LyraSigner::Ledger(s),
+        }
+    }
+}
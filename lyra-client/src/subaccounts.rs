@@ -0,0 +1,187 @@
+use crate::actions::round_to_tick;
+use crate::error::{LyraError, Result};
+use crate::json_rpc::{http_rpc, WsClient, WsClientExt};
+use crate::utils::await_tx_settlement;
+use anyhow::format_err;
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use orderbook_types::generated::private_get_subaccount::{
+    MarginType, PrivateGetSubaccountParamsSchema, PrivateGetSubaccountResponseSchema,
+    PrivateGetSubaccountResultSchema,
+};
+use orderbook_types::generated::private_get_subaccounts::{
+    PrivateGetSubaccountsParamsSchema, PrivateGetSubaccountsResponseSchema,
+};
+use orderbook_types::generated::private_transfer_position::PrivateTransferPositionResultSchema;
+use orderbook_types::generated::public_get_transaction::Status;
+use orderbook_types::types::tickers::TickerResponse;
+use serde_json::json;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+async fn await_settled(transaction_id: Uuid) -> Result<()> {
+    let tx = await_tx_settlement(transaction_id).await?;
+    match tx.status {
+        Status::Settled => Ok(()),
+        _ => {
+            Err(LyraError::Other(format_err!("Tx {} not settled: {:?}", transaction_id, tx.status)))
+        }
+    }
+}
+
+/// Subaccount ids of the logged in owner
+pub async fn get_subaccount_ids(client: &WsClient) -> Result<Vec<i64>> {
+    let wallet = client.get_owner().await;
+    let res = client
+        .send_rpc::<_, PrivateGetSubaccountsResponseSchema>(
+            "private/get_subaccounts",
+            PrivateGetSubaccountsParamsSchema { wallet },
+        )
+        .await?
+        .into_result()?;
+    Ok(res.result.subaccount_ids)
+}
+
+pub async fn get_subaccount(
+    client: &WsClient,
+    subaccount_id: i64,
+) -> Result<PrivateGetSubaccountResultSchema> {
+    let res = client
+        .send_rpc::<_, PrivateGetSubaccountResponseSchema>(
+            "private/get_subaccount",
+            PrivateGetSubaccountParamsSchema { subaccount_id },
+        )
+        .await?
+        .into_result()?;
+    Ok(res.result)
+}
+
+/// Creates the subaccount and waits for its tx, returns the new subaccount id
+pub async fn create_subaccount_and_wait(
+    client: &WsClient,
+    amount: BigDecimal,
+    asset_name: String,
+    margin_type: MarginType,
+) -> Result<i64> {
+    let existing = get_subaccount_ids(client).await?;
+    let res = client.create_subaccount(amount, asset_name, margin_type).await?.into_result()?;
+    await_settled(res.result.transaction_id).await?;
+    let created = get_subaccount_ids(client).await?.into_iter().filter(|id| !existing.contains(id));
+    let subaccount_id = created.max().ok_or(LyraError::Other(format_err!(
+        "Create tx {} settled but no new subaccount found",
+        res.result.transaction_id
+    )))?;
+    info!("Created {:?} subaccount {}", margin_type, subaccount_id);
+    Ok(subaccount_id)
+}
+
+/// Transfers the collateral and waits for its tx
+pub async fn transfer_and_wait(
+    client: &WsClient,
+    subaccount_id: i64,
+    recipient_subaccount_id: i64,
+    amount: BigDecimal,
+    asset_name: String,
+) -> Result<()> {
+    let res = client
+        .transfer_between_subaccounts(subaccount_id, recipient_subaccount_id, amount, asset_name)
+        .await?
+        .into_result()?;
+    await_settled(res.result.transaction_id).await
+}
+
+/// Subaccounts can't switch their margin manager in place, so this creates a subaccount of
+/// `margin_type` (funded with `amount` of `asset_name` from the wallet) and moves all collateral
+/// of `subaccount_id` over. Positions and open orders have to be closed first.
+/// Returns the new subaccount id.
+pub async fn change_margin_type(
+    client: &WsClient,
+    subaccount_id: i64,
+    margin_type: MarginType,
+    amount: BigDecimal,
+    asset_name: String,
+) -> Result<i64> {
+    let subaccount = get_subaccount(client, subaccount_id).await?;
+    if subaccount.margin_type == margin_type {
+        return Err(LyraError::Other(format_err!(
+            "Subaccount {} is {:?} already",
+            subaccount_id,
+            margin_type
+        )));
+    }
+    if !subaccount.positions.is_empty() || !subaccount.open_orders.is_empty() {
+        return Err(LyraError::Other(format_err!(
+            "Subaccount {} has {} positions and {} open orders, close them first",
+            subaccount_id,
+            subaccount.positions.len(),
+            subaccount.open_orders.len()
+        )));
+    }
+    if let Some(c) = subaccount.collaterals.iter().find(|c| c.amount < BigDecimal::zero()) {
+        return Err(LyraError::Other(format_err!(
+            "Subaccount {} borrows {} {}, repay it first",
+            subaccount_id,
+            c.amount,
+            c.asset_name
+        )));
+    }
+    let new_id = create_subaccount_and_wait(client, amount, asset_name, margin_type).await?;
+    for collateral in subaccount.collaterals.into_iter().filter(|c| !c.amount.is_zero()) {
+        info!(
+            "Moving {} {} from subaccount {} to {}",
+            collateral.amount, collateral.asset_name, subaccount_id, new_id
+        );
+        transfer_and_wait(client, subaccount_id, new_id, collateral.amount, collateral.asset_name)
+            .await?;
+    }
+    let remaining = get_subaccount(client, subaccount_id).await?.collaterals;
+    if remaining.iter().any(|c| !c.amount.is_zero()) {
+        // e.g. interest accrued on cash while transferring
+        warn!("Collateral left in subaccount {}: {:?}", subaccount_id, remaining);
+    }
+    Ok(new_id)
+}
+
+/// Moves a position to another subaccount of the owner, all of it unless `amount` (negative for
+/// shorts) is given, at the mark price unless `limit_price` is given
+pub async fn transfer_position(
+    client: &WsClient,
+    subaccount_id: i64,
+    recipient_subaccount_id: i64,
+    instrument_name: &str,
+    amount: Option<BigDecimal>,
+    limit_price: Option<BigDecimal>,
+) -> Result<PrivateTransferPositionResultSchema> {
+    let amount = match amount {
+        Some(amount) => amount,
+        None => {
+            let positions = get_subaccount(client, subaccount_id).await?.positions;
+            let position = positions.into_iter().find(|p| p.instrument_name == instrument_name);
+            position.map(|p| p.amount).ok_or(LyraError::Other(format_err!(
+                "No {} position in subaccount {}",
+                instrument_name,
+                subaccount_id
+            )))?
+        }
+    };
+    let ticker = http_rpc::<_, TickerResponse>(
+        "public/get_ticker",
+        json!({ "instrument_name": instrument_name }),
+        None,
+    )
+    .await?
+    .into_result()?
+    .result;
+    // the mark price is not a multiple of the tick size
+    let limit_price = limit_price.unwrap_or_else(|| {
+        round_to_tick(&ticker.mark_price, &ticker.tick_size, RoundingMode::HalfEven)
+    });
+    info!(
+        "Transferring {} {} from subaccount {} to {} at {}",
+        amount, instrument_name, subaccount_id, recipient_subaccount_id, limit_price
+    );
+    let res = client
+        .transfer_position(&ticker, subaccount_id, recipient_subaccount_id, amount, limit_price)
+        .await?
+        .into_result()?;
+    Ok(res.result)
+}
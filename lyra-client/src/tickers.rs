@@ -0,0 +1,104 @@
+use crate::error::{LyraError, Result};
+use crate::json_rpc::http_rpc;
+use anyhow::format_err;
+use futures::{StreamExt, TryStreamExt};
+use orderbook_types::types::tickers::{InstrumentTicker, TickerResponse};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::RwLock;
+use tokio::time::{Duration, Instant};
+use tracing::debug;
+
+const DEFAULT_TICKER_TTL_MS: u64 = 1000;
+const DEFAULT_TICKER_CONCURRENCY: usize = 10; // public/get_ticker requests in flight
+
+static GLOBAL_TICKER_CACHE: OnceLock<TickerCache> = OnceLock::new();
+
+/// Tickers fetched with `public/get_ticker`, reused for `ttl`. Tickers that are missing or
+/// expired are fetched in parallel, at most `concurrency` requests at a time.
+#[derive(Debug, Clone)]
+pub struct TickerCache {
+    ttl: Duration,
+    concurrency: usize,
+    tickers: Arc<RwLock<HashMap<String, (Instant, InstrumentTicker)>>>,
+}
+
+impl TickerCache {
+    pub fn new(ttl: Duration, concurrency: usize) -> Self {
+        Self {
+            ttl,
+            concurrency: concurrency.max(1),
+            tickers: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// The process wide cache, with the TTL from TICKER_CACHE_TTL_MS (default 1000)
+    pub fn global() -> &'static TickerCache {
+        GLOBAL_TICKER_CACHE.get_or_init(|| {
+            let ttl_ms = std::env::var("TICKER_CACHE_TTL_MS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_TICKER_TTL_MS);
+            TickerCache::new(Duration::from_millis(ttl_ms), DEFAULT_TICKER_CONCURRENCY)
+        })
+    }
+
+    pub async fn get(&self, instrument_name: &str) -> Result<InstrumentTicker> {
+        let mut tickers = self.get_many([instrument_name]).await?;
+        let ticker = tickers.remove(instrument_name);
+        ticker.ok_or(LyraError::Other(format_err!("No ticker for {}", instrument_name)))
+    }
+
+    /// Tickers of all instruments by name, only fetching the ones not cached within the TTL
+    pub async fn get_many<'a>(
+        &self,
+        instrument_names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<HashMap<String, InstrumentTicker>> {
+        let mut names = instrument_names.into_iter().map(String::from).collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        let mut result = HashMap::new();
+        let mut missing = vec![];
+        let reader = self.tickers.read().await;
+        for name in names {
+            match reader.get(&name) {
+                Some((fetched_at, ticker)) if fetched_at.elapsed() < self.ttl => {
+                    result.insert(name, ticker.clone());
+                }
+                _ => missing.push(name),
+            }
+        }
+        drop(reader);
+        debug!("Ticker cache hits: {}, fetching: {:?}", result.len(), missing);
+
+        let fetched = futures::stream::iter(missing)
+            .map(|name| async move { fetch_ticker(&name).await.map(|ticker| (name, ticker)) })
+            .buffer_unordered(self.concurrency)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let now = Instant::now();
+        let mut writer = self.tickers.write().await;
+        for (name, ticker) in fetched {
+            writer.insert(name.clone(), (now, ticker.clone()));
+            result.insert(name, ticker);
+        }
+        Ok(result)
+    }
+
+    /// Drops the cached ticker so that the next `get` fetches it, e.g. after a post-only reject
+    pub async fn invalidate(&self, instrument_name: &str) {
+        self.tickers.write().await.remove(instrument_name);
+    }
+}
+
+async fn fetch_ticker(instrument_name: &str) -> Result<InstrumentTicker> {
+    let ticker = http_rpc::<_, TickerResponse>(
+        "public/get_ticker",
+        json!({ "instrument_name": instrument_name }),
+        None,
+    )
+    .await?
+    .into_result()?;
+    Ok(ticker.result)
+}
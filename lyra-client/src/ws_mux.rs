@@ -0,0 +1,300 @@
+use crate::config::LyraConfig;
+use crate::error::{LyraError, Result};
+use crate::json_rpc::{ReconnectCallback, Response, WsClient, WsClientExt, WsClientState};
+use anyhow::format_err;
+use ethers::prelude::{Address, Signer};
+use futures::FutureExt;
+use orderbook_types::generated::subscribe::{SubscribeParamsSchema, SubscribeResponseSchema};
+use orderbook_types::generated::unsubscribe::UnsubscribeParamsSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use tokio::select;
+use tokio::sync::{mpsc, watch, Mutex};
+use tracing::{error, info, warn};
+
+/// Shared connections by owner and session key
+static WS_MUX_POOL: OnceLock<Mutex<HashMap<MuxKey, Arc<WsMux>>>> = OnceLock::new();
+
+type MuxKey = (Option<Address>, Option<Address>);
+
+#[derive(Debug, Default)]
+struct Routes {
+    next_id: u64,
+    senders: HashMap<String, Vec<(u64, mpsc::UnboundedSender<Value>)>>, // by channel
+}
+
+type SharedRoutes = Arc<std::sync::Mutex<Routes>>;
+
+/// One authenticated connection shared by the subscribers of a process, e.g. the market task,
+/// the ticker manager and the auctions of a vault executor. Notifications are routed to the
+/// subscribers of their channel, a channel is subscribed on the socket by its first subscriber
+/// and unsubscribed once its last subscriber is gone. Reconnects, re-logins and subscription
+/// replays are handled by the underlying `WsClient`.
+///
+/// Subscribers joining a channel that is already subscribed only get the notifications from
+/// then on, e.g. no orderbook snapshot, so fetch one separately if needed.
+pub struct WsMux {
+    client: WsClient,
+    routes: SharedRoutes,
+    reconnects: Arc<watch::Sender<u64>>, // bumped after every reconnect of the connection
+    closed: Arc<AtomicBool>,
+    // held while (un)subscribing the socket, so an unsubscribe never overtakes a resubscribe
+    subscription_lock: Arc<Mutex<()>>,
+}
+
+impl WsMux {
+    /// The shared connection of `LyraConfig::current()`, connected (and logged in if the config
+    /// has an owner) on first use
+    pub async fn shared() -> Result<Arc<WsMux>> {
+        WsMux::shared_with_config(LyraConfig::current()?).await
+    }
+
+    /// Same as `shared` but for the given config
+    pub async fn shared_with_config(config: Arc<LyraConfig>) -> Result<Arc<WsMux>> {
+        let key = (config.owner, config.session_key.as_ref().map(|k| k.address()));
+        // held while connecting so that concurrent callers do not open a connection each
+        let mut pool = WS_MUX_POOL.get_or_init(Default::default).lock().await;
+        if let Some(mux) = pool.get(&key) {
+            if !mux.closed.load(Ordering::SeqCst) {
+                return Ok(mux.clone());
+            }
+        }
+        let mux = Arc::new(WsMux::connect(key, config).await?);
+        pool.insert(key, mux.clone());
+        Ok(mux)
+    }
+
+    async fn connect(key: MuxKey, config: Arc<LyraConfig>) -> Result<Self> {
+        let client = WsClient::new_client_with_config(config.clone()).await?;
+        if config.owner.is_some() {
+            client.login().await?.into_result()?;
+        }
+        let reconnects = Arc::new(watch::channel(0).0);
+        let reconnect_sender = reconnects.clone();
+        client
+            .set_on_reconnect(Arc::new(move || {
+                reconnect_sender.send_modify(|generation| *generation += 1);
+                async { Ok(()) }.boxed()
+            }))
+            .await;
+        let mux = WsMux {
+            client,
+            routes: SharedRoutes::default(),
+            reconnects,
+            closed: Arc::new(AtomicBool::new(false)),
+            subscription_lock: Arc::new(Mutex::new(())),
+        };
+        tokio::spawn(WsMux::route_forever(
+            key,
+            mux.client.clone(),
+            mux.routes.clone(),
+            mux.closed.clone(),
+        ));
+        info!("Opened shared connection of {:?}", key);
+        Ok(mux)
+    }
+
+    /// The shared client, e.g. for requests that should not open a connection of their own
+    pub fn client(&self) -> WsClient {
+        self.client.clone()
+    }
+
+    /// Subscribes to the channels on the shared connection and calls the handler with their
+    /// notifications until it fails, like `WsClientExt::subscribe`
+    pub async fn subscribe<Fut, Data>(
+        &self,
+        channels: Vec<String>,
+        handler: impl FnMut(Data) -> Fut,
+    ) -> Result<()>
+    where
+        Fut: Future<Output = anyhow::Result<()>>,
+        Data: for<'de> Deserialize<'de> + Debug,
+    {
+        self.subscribe_with_resync(channels, None, handler).await
+    }
+
+    /// Same as `subscribe`, also calling `on_reconnect` after the shared connection reconnected
+    /// to resync state that may have missed notifications. It runs in the subscriber's task,
+    /// so it sees the same task locals as the handler.
+    pub async fn subscribe_with_resync<Fut, Data>(
+        &self,
+        channels: Vec<String>,
+        on_reconnect: Option<ReconnectCallback>,
+        mut handler: impl FnMut(Data) -> Fut,
+    ) -> Result<()>
+    where
+        Fut: Future<Output = anyhow::Result<()>>,
+        Data: for<'de> Deserialize<'de> + Debug,
+    {
+        let mut reconnects = self.reconnects.subscribe();
+        let mut subscriber = self.add_subscriber(channels).await?;
+        loop {
+            select! {
+                msg = subscriber.receiver.recv() => {
+                    let Some(msg) = msg else {
+                        return Err(LyraError::transport("Shared connection closed"));
+                    };
+                    match serde_path_to_error::deserialize::<_, Data>(msg) {
+                        Ok(notification) => handler(notification).await?,
+                        Err(e) => error!("Error decoding notification of shared connection: {}", e),
+                    }
+                }
+                changed = reconnects.changed() => {
+                    if changed.is_err() {
+                        return Err(LyraError::transport("Shared connection closed"));
+                    }
+                    let Some(callback) = &on_reconnect else {
+                        continue;
+                    };
+                    if let Err(e) = callback().await {
+                        error!("Reconnect callback failed with {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Routes the channels to a new subscriber, subscribing the socket to channels without one
+    async fn add_subscriber(&self, mut channels: Vec<String>) -> Result<Subscriber> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(LyraError::transport("Shared connection closed"));
+        }
+        channels.sort();
+        channels.dedup();
+        let _subscription_guard = self.subscription_lock.lock().await;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (id, new_channels) = {
+            let mut routes = self.routes.lock().unwrap();
+            routes.next_id += 1;
+            let id = routes.next_id;
+            let mut new_channels = vec![];
+            for channel in channels.iter() {
+                let senders = routes.senders.entry(channel.clone()).or_default();
+                if senders.is_empty() {
+                    new_channels.push(channel.clone());
+                }
+                senders.push((id, sender.clone()));
+            }
+            (id, new_channels)
+        };
+        // dropping the subscriber on errors removes its routes again
+        let subscriber = Subscriber {
+            id,
+            channels,
+            receiver,
+            client: self.client.clone(),
+            routes: self.routes.clone(),
+            closed: self.closed.clone(),
+            subscription_lock: self.subscription_lock.clone(),
+        };
+        if new_channels.is_empty() {
+            return Ok(subscriber);
+        }
+        info!("Subscribing shared connection to {:?}", new_channels);
+        let params = SubscribeParamsSchema { channels: new_channels.clone() };
+        match self.client.send_rpc::<_, SubscribeResponseSchema>("subscribe", params).await? {
+            Response::Success(success) => {
+                for (channel, status) in success.result.status.iter() {
+                    if status != "ok" {
+                        return Err(LyraError::Other(format_err!("Subscription error: {channel}")));
+                    }
+                }
+            }
+            Response::Error(e) => {
+                error!("RPC error while subscribing: {:?}", e);
+                return Err(LyraError::from(e));
+            }
+        }
+        WsClientState::add_subscriptions(&self.client, new_channels).await;
+        Ok(subscriber)
+    }
+
+    /// Reads the socket and routes the notifications until the connection is lost for good,
+    /// then closes the receivers of all subscribers and leaves the pool
+    async fn route_forever(
+        key: MuxKey,
+        client: WsClient,
+        routes: SharedRoutes,
+        closed: Arc<AtomicBool>,
+    ) {
+        let res = WsClientState::listen_forever(&client, |msg: Value| {
+            route(&routes, msg);
+            async { Ok(()) }
+        })
+        .await;
+        error!("Shared connection of {:?} exited with {:?}", key, res);
+        closed.store(true, Ordering::SeqCst);
+        routes.lock().unwrap().senders.clear();
+        let mut pool = WS_MUX_POOL.get_or_init(Default::default).lock().await;
+        if pool.get(&key).is_some_and(|mux| Arc::ptr_eq(&mux.client, &client)) {
+            pool.remove(&key);
+        }
+    }
+}
+
+fn route(routes: &std::sync::Mutex<Routes>, msg: Value) {
+    let Some(channel) = msg["params"]["channel"].as_str() else {
+        warn!("Received unrecognized message format: {:?}", msg);
+        return;
+    };
+    let mut routes = routes.lock().unwrap();
+    if let Some(senders) = routes.senders.get_mut(channel) {
+        senders.retain(|(_, sender)| sender.send(msg.clone()).is_ok());
+    }
+}
+
+/// Routes of a subscriber, removed on drop along with the socket subscriptions of the channels
+/// that have no other subscriber
+struct Subscriber {
+    id: u64,
+    channels: Vec<String>,
+    receiver: mpsc::UnboundedReceiver<Value>,
+    client: WsClient,
+    routes: SharedRoutes,
+    closed: Arc<AtomicBool>,
+    subscription_lock: Arc<Mutex<()>>,
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let mut unused = vec![];
+        {
+            let mut routes = self.routes.lock().unwrap();
+            for channel in self.channels.iter() {
+                let Some(senders) = routes.senders.get_mut(channel) else {
+                    continue;
+                };
+                senders.retain(|(id, _)| *id != self.id);
+                if senders.is_empty() {
+                    routes.senders.remove(channel);
+                    unused.push(channel.clone());
+                }
+            }
+        }
+        if unused.is_empty() || self.closed.load(Ordering::SeqCst) {
+            return;
+        }
+        let client = self.client.clone();
+        let routes = self.routes.clone();
+        let subscription_lock = self.subscription_lock.clone();
+        tokio::spawn(async move {
+            let _subscription_guard = subscription_lock.lock().await;
+            // channels a new subscriber joined in the meantime stay subscribed
+            unused.retain(|channel| !routes.lock().unwrap().senders.contains_key(channel));
+            if unused.is_empty() {
+                return;
+            }
+            WsClientState::remove_subscriptions(&client, &unused).await;
+            info!("Unsubscribing shared connection from {:?}", unused);
+            let params = UnsubscribeParamsSchema { channels: Some(unused) };
+            if let Err(e) = client.send_rpc::<_, Value>("unsubscribe", params).await {
+                warn!("Unsubscribe failed with {:?}", e);
+            }
+        });
+    }
+}
@@ -135,6 +135,7 @@ impl GammaDDHAlgo {
             order_type: OrderType::Limit,
             label,
             mmp: false,
+            max_slippage_bps: None,
         };
         // todo really need a way to re-sync the orders if something happens to ws sub
         match open_ids.len() {
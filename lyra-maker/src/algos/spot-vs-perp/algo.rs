@@ -157,6 +157,7 @@ impl MakerAlgo {
             order_type: OrderType::Limit,
             label: "spot-maker".to_string(),
             mmp: false,
+            max_slippage_bps: None,
         };
         let open_ids = self.get_open_ids(state.clone(), direction).await;
         let data = state.read().await;
@@ -225,6 +226,7 @@ impl MakerAlgo {
             order_type: OrderType::Limit,
             label: "perp-hedger".to_string(),
             mmp: false,
+            max_slippage_bps: None,
         };
         info!("Hedger action: {} {} {}", direction, price, amount);
         let order_res = client.send_order(&ticker, self.subaccount_id, order_args).await?;
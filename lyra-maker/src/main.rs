@@ -60,8 +60,8 @@ use orderbook_types::generated::public_login::{
     PublicLoginParamsSchema, PublicLoginResponseSchema,
 };
 
-use crate::market::tasks::public::MarketSubscriberData;
-use lyra_client::setup::{ensure_owner, ensure_session_key, setup_env};
+use lyra_client::config::LyraConfig;
+use lyra_client::setup::{env_config, setup_env};
 
 pub async fn setup_ip_whitelist() -> Result<()> {
     let client = WsClient::new_client().await?;
@@ -82,13 +82,7 @@ pub async fn setup_ip_whitelist() -> Result<()> {
 }
 
 async fn run_ddh() -> Result<()> {
-    // read subaccount id from os args provided to the run script
-    let subaccount_id: i64 = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| std::env::var("SUBACCOUNT_ID").unwrap())
-        .parse()?;
-    // set SUBACCOUNT_ID env variable
-    std::env::set_var("SUBACCOUNT_ID", subaccount_id.to_string());
+    let subaccount_id = LyraConfig::current()?.subaccount_id()?;
 
     let currency = "ETH".to_string();
     let perps = vec![format!("{currency}-PERP")];
@@ -161,9 +155,13 @@ async fn run_ddh() -> Result<()> {
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() -> Result<()> {
-    setup_env().await;
-    ensure_session_key().await;
-    ensure_owner().await;
+    let env = setup_env().await;
+    // read subaccount id from os args provided to the run script
+    let subaccount_id: i64 = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| std::env::var("SUBACCOUNT_ID").unwrap())
+        .parse()?;
+    LyraConfig::init(env_config(&env).await?.with_subaccount_id(subaccount_id))?;
     loop {
         let res = run_ddh().await;
         error!("Unexpected exit in run_ddh(): {:?}", res);
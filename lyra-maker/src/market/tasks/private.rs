@@ -1,26 +1,19 @@
-use serde::{Deserialize, Serialize};
 use anyhow::{Result, Error};
 
-use lyra_client::json_rpc::{Notification, Response, WsClient, WsClientExt};
+use lyra_client::channels::Channel;
+use lyra_client::json_rpc::{Response, WsClient, WsClientExt};
+use lyra_client::notifications::LyraNotification;
 use orderbook_types::generated::private_get_subaccount::{PrivateGetSubaccountParamsSchema, PrivateGetSubaccountResponseSchema};
-use orderbook_types::generated::channel_subaccount_id_balances::BalanceUpdateSchema;
-use orderbook_types::types::orders::{OrderResponse, OrderNotificationData};
+use orderbook_types::types::orders::OrderResponse;
 use chrono::Utc;
 use log::{info, warn, error, debug};
 
 use crate::market::core::{MarketState, Balance};
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(untagged)]
-pub enum SubaccountSubscriberData {
-    BalancesMsg(Notification<Vec<BalanceUpdateSchema>>),
-    OrdersMsg(Notification<OrderNotificationData>),
-}
-
 pub async fn start_subaccount(state: MarketState, subaccount_id: i64) -> Result<()> {
     let channels: Vec<String> = vec![
-        format!("{}.balances", subaccount_id),
-        format!("{}.orders", subaccount_id),
+        Channel::balances(subaccount_id).to_string(),
+        Channel::orders(subaccount_id).to_string(),
     ];
     let client = WsClient::new_client().await?;
     let login = client.login().await?.into_result()?;
@@ -56,9 +49,9 @@ pub async fn start_subaccount(state: MarketState, subaccount_id: i64) -> Result<
     }
 
     // TODO can regularly re-check the balances and orders via RPC as a validation
-    client.subscribe(channels, |d: SubaccountSubscriberData| async {
+    client.subscribe(channels, |d: LyraNotification| async {
         match d {
-            SubaccountSubscriberData::BalancesMsg(msg) => {
+            LyraNotification::Balances(msg) => {
                 let mut writer = state.write().await;
                 for balance in msg.params.data {
                     writer.insert_position(Balance {
@@ -68,12 +61,13 @@ pub async fn start_subaccount(state: MarketState, subaccount_id: i64) -> Result<
                     });
                 }
             }
-            SubaccountSubscriberData::OrdersMsg(msg) => {
+            LyraNotification::Orders(msg) => {
                 let mut writer = state.write().await;
                 for order in msg.params.data {
                     writer.insert_order(order);
                 }
             }
+            other => warn!("Unexpected subaccount notification on {}", other.channel()),
         }
         Ok(())
     }).await?;
@@ -1,27 +1,21 @@
 use anyhow::{Error, Result};
-use serde::{Deserialize, Serialize};
 
-use crate::market::core::{MarketState, OrderbookData};
+use crate::market::core::MarketState;
 use log::{debug, error, info, warn};
 use lyra_client::auth::{load_signer, sign_auth_header};
-use lyra_client::json_rpc::{http_rpc, Notification, Response, WsClient, WsClientExt};
+use lyra_client::channels::Channel;
+use lyra_client::json_rpc::{http_rpc, Response, WsClient, WsClientExt};
+use lyra_client::notifications::LyraNotification;
 use orderbook_types::generated::public_get_instruments::{
     InstrumentType, PublicGetInstrumentsParamsSchema, PublicGetInstrumentsResponseSchema,
 };
-use orderbook_types::types::tickers::result::{InstrumentTicker, TickerNotificationData};
+use orderbook_types::types::tickers::result::InstrumentTicker;
 use serde_json::{json, Value};
 use tokio::select;
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(untagged)]
-pub enum MarketSubscriberData {
-    OrderbookMsg(Notification<OrderbookData>),
-    TickerMsg(Notification<TickerNotificationData>),
-}
-
 pub async fn fetch_live_options(currency: String) -> Result<Vec<String>> {
     let subaccount_id: i64 = std::env::var("SUBACCOUNT_ID")?.parse()?;
-    let headers = sign_auth_header(&load_signer().await).await;
+    let headers = sign_auth_header(&load_signer().await?).await?;
     let pos_resp = http_rpc::<Value, Value>(
         "private/get_positions",
         json!({"subaccount_id": subaccount_id}),
@@ -92,16 +86,13 @@ pub async fn start_option_tickers(state: MarketState, currency: String) -> Resul
 pub async fn subscribe_tickers(state: MarketState, instrument_names: Vec<String>) -> Result<()> {
     let channels: Vec<String> = instrument_names
         .iter()
-        .map(|instrument_name| format!("ticker.{}.100", instrument_name))
+        .map(|instrument_name| Channel::ticker(instrument_name, 100).to_string())
         .collect();
     let client = WsClient::new_client().await?;
     client
-        .subscribe(channels, |d: MarketSubscriberData| async {
-            match d {
-                MarketSubscriberData::TickerMsg(msg) => {
-                    state.write().await.insert_ticker(msg.params.data.instrument_ticker);
-                }
-                _ => {}
+        .subscribe(channels, |d: LyraNotification| async {
+            if let LyraNotification::Ticker(msg) = d {
+                state.write().await.insert_ticker(msg.params.data.instrument_ticker);
             }
             Ok(())
         })
@@ -115,22 +106,23 @@ pub async fn start_market(state: MarketState, instrument_names: Vec<String>) ->
         .iter()
         .flat_map(|instrument_name| {
             vec![
-                format!("orderbook.{}.1.10", instrument_name),
-                format!("ticker.{}.100", instrument_name),
+                Channel::orderbook(instrument_name, 10).to_string(),
+                Channel::ticker(instrument_name, 100).to_string(),
             ]
         })
         .collect();
 
     let client = WsClient::new_client().await?;
     client
-        .subscribe(channels, |d: MarketSubscriberData| async {
+        .subscribe(channels, |d: LyraNotification| async {
             match d {
-                MarketSubscriberData::OrderbookMsg(msg) => {
+                LyraNotification::Orderbook(msg) => {
                     state.write().await.insert_orderbook(msg.params.data);
                 }
-                MarketSubscriberData::TickerMsg(msg) => {
+                LyraNotification::Ticker(msg) => {
                     state.write().await.insert_ticker(msg.params.data.instrument_ticker);
                 }
+                other => warn!("Unexpected market notification on {}", other.channel()),
             }
             Ok(())
         })
@@ -3,7 +3,8 @@ use statrs::function::erf::erf;
 
 const FRAC_1_SQRT_PI: f64 = 0.564189583547756286948079451560772586_f64;
 const FRAC_1_SQRT_2_PI: f64 = FRAC_1_SQRT_PI * std::f64::consts::FRAC_1_SQRT_2;
-const SEC_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+pub const SEC_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+const MAX_IMPLIED_VOL: f64 = 10.0;
 
 pub fn normcdf(x: f64) -> f64 {
     0.5 * (1.0 + erf(x * std::f64::consts::FRAC_1_SQRT_2))
@@ -53,6 +54,23 @@ impl OptionContract {
         price
     }
 
+    /// Vol at which the contract is worth `price`, by bisection. None if no vol in
+    /// (0, MAX_IMPLIED_VOL] matches, e.g. for a price below intrinsic or an expired contract.
+    pub fn implied_vol(&self, fwd: f64, price: f64) -> Option<f64> {
+        let (mut lo, mut hi) = (1e-6, MAX_IMPLIED_VOL);
+        if self.expiry_sec <= 0.0 || price < self.price(fwd, lo) || price > self.price(fwd, hi) {
+            return None;
+        }
+        for _ in 0..100 {
+            let mid = 0.5 * (lo + hi);
+            match self.price(fwd, mid) < price {
+                true => lo = mid,
+                false => hi = mid,
+            }
+        }
+        Some(0.5 * (lo + hi))
+    }
+
     pub fn delta(&self, fwd: f64, vol: f64) -> f64 {
         let tau = self.expiry_sec / SEC_PER_YEAR;
         if tau <= 0.0 {
@@ -1 +1,2 @@
 pub mod black76;
+pub mod pricing;
@@ -0,0 +1,70 @@
+use crate::black76::OptionContract;
+use anyhow::{Error, Result};
+
+/// Source of the implied vol an option contract is priced at with Black-76
+pub trait PricingModel {
+    fn vol(&self, contract: &OptionContract, fwd: f64) -> Result<f64>;
+
+    fn price(&self, contract: &OptionContract, fwd: f64) -> Result<f64> {
+        Ok(contract.price(fwd, self.vol(contract, fwd)?))
+    }
+}
+
+/// Black-76 at a single implied vol, e.g. the mark IV of the contract
+#[derive(Debug, Clone)]
+pub struct Black76 {
+    pub iv: f64,
+}
+
+impl PricingModel for Black76 {
+    fn vol(&self, _contract: &OptionContract, _fwd: f64) -> Result<f64> {
+        Ok(self.iv)
+    }
+}
+
+/// Implied vol quoted for a strike of the smile's expiry
+#[derive(Debug, Clone)]
+pub struct SmileQuote {
+    pub strike: f64,
+    pub iv: f64,
+}
+
+/// Smile of a single expiry, interpolated linearly in variance over log-moneyness between the
+/// nearest quoted strikes and flat beyond the outermost ones
+#[derive(Debug, Clone)]
+pub struct InterpolatedSmile {
+    quotes: Vec<SmileQuote>, // sorted by strike
+}
+
+impl InterpolatedSmile {
+    /// Ignores quotes without a positive strike and IV, fails if none are left
+    pub fn new(mut quotes: Vec<SmileQuote>) -> Result<Self> {
+        quotes.retain(|q| q.strike > 0.0 && q.iv.is_finite() && q.iv > 0.0);
+        if quotes.is_empty() {
+            return Err(Error::msg("No valid quotes for the smile"));
+        }
+        quotes.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+        Ok(Self { quotes })
+    }
+
+    pub fn quotes(&self) -> &[SmileQuote] {
+        &self.quotes
+    }
+}
+
+impl PricingModel for InterpolatedSmile {
+    fn vol(&self, contract: &OptionContract, fwd: f64) -> Result<f64> {
+        let (first, last) = (&self.quotes[0], &self.quotes[self.quotes.len() - 1]);
+        let upper = match self.quotes.iter().position(|q| q.strike >= contract.strike) {
+            None => return Ok(last.iv),
+            Some(0) => return Ok(first.iv),
+            Some(upper) => upper,
+        };
+        let (lo, hi) = (&self.quotes[upper - 1], &self.quotes[upper]);
+        let moneyness = |strike: f64| (strike / fwd).ln();
+        let weight = (moneyness(contract.strike) - moneyness(lo.strike))
+            / (moneyness(hi.strike) - moneyness(lo.strike));
+        let variance = lo.iv.powi(2) + weight * (hi.iv.powi(2) - lo.iv.powi(2));
+        Ok(variance.sqrt())
+    }
+}
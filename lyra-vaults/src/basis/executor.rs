@@ -0,0 +1,130 @@
+use crate::basis::funding::{get_short_target, FundingWait};
+use crate::basis::params::BasisParams;
+use crate::basis::stages::BasisExecutorStage;
+use crate::basis::stages::BasisExecutorStage::{
+    Harvest, ShortAuction, SpotAuction, SpotOnly, UnwindAuction,
+};
+use crate::dn::hedge_auction::HedgeStrategy;
+use crate::helpers::sync_vault_subaccounts;
+use crate::market::new_market_state;
+use crate::shared::auction::{LimitOrderAuction, LimitOrderAuctionExecutor};
+use crate::shared::reload::ParamsSection;
+use crate::shared::twap::TwapExecutor;
+use crate::shared::vault::VaultStrategy;
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use tracing::info;
+
+/// Funding basis strategy, run with `VaultExecutor::new(BasisExecutor::new(params))`
+pub struct BasisExecutor {
+    params: BasisParams,
+}
+
+impl BasisExecutor {
+    pub fn new(params: BasisParams) -> Self {
+        Self { params }
+    }
+
+    /// Infers the stage from the positions
+    /// - Harvest has a non-zero perp position (it rebalances right away if off target)
+    /// - Spot Auction has no perp and USDC outside of threshold
+    /// - Spot Only has no perp and USDC within threshold
+    async fn infer_stage(params: BasisParams) -> Result<BasisExecutorStage> {
+        let market = new_market_state();
+        sync_vault_subaccounts(market.clone(), vec![]).await?;
+
+        let reader = market.read().await;
+        let cash_bal = reader.get_amount(&params.spot_auction_params.cash_name);
+        let perp_bal = reader.get_amount(&params.hedge_auction_params.perp_name);
+        drop(reader);
+
+        if !perp_bal.is_zero() {
+            info!("Starting in Harvest stage");
+            return Ok(Harvest(FundingWait::new(params, true).await?));
+        }
+        if params.spot_auction_params.is_cash_within_threshold(&cash_bal) {
+            info!("Starting in Spot Only stage");
+            Ok(SpotOnly(FundingWait::new(params, false).await?))
+        } else {
+            info!("Starting in Spot Auction stage");
+            BasisExecutor::new_spot_auction_stage(params).await
+        }
+    }
+
+    /// Shorts the perp against the collateral, or unwinds the short if `is_unwind`
+    pub async fn new_perp_stage(
+        params: BasisParams,
+        is_unwind: bool,
+    ) -> Result<BasisExecutorStage> {
+        let hedge_params = &params.hedge_auction_params;
+        let target_amount = match is_unwind {
+            true => BigDecimal::zero(),
+            false => get_short_target(&params).await?,
+        };
+        let mut auction = LimitOrderAuction::new(
+            hedge_params.perp_name.clone(),
+            chrono::Utc::now().timestamp(),
+            hedge_params.auction_sec,
+            hedge_params.price_change_tolerance.clone(),
+        )
+        .await?;
+        auction.depth_limit = hedge_params.depth_limit.clone();
+        auction.max_slippage_bps = hedge_params.max_slippage_bps;
+        auction.cancel_on_disconnect = hedge_params.cancel_on_disconnect;
+        let executor = LimitOrderAuctionExecutor {
+            auction,
+            strategy: HedgeStrategy { params: hedge_params.clone(), target_amount },
+        };
+        match is_unwind {
+            true => Ok(UnwindAuction(executor)),
+            false => Ok(ShortAuction(executor)),
+        }
+    }
+
+    pub async fn new_spot_auction_stage(params: BasisParams) -> Result<BasisExecutorStage> {
+        let spot_params = &params.spot_auction_params;
+        let mut auction = LimitOrderAuction::new(
+            params.spot_instrument_name(),
+            chrono::Utc::now().timestamp(),
+            spot_params.auction_sec,
+            spot_params.price_change_tolerance.clone(),
+        )
+        .await?;
+        auction.depth_limit = spot_params.depth_limit.clone();
+        auction.orderbook_levels = spot_params.pricing_orderbook_levels();
+        auction.reload_section = Some(ParamsSection::SpotAuction);
+        auction.candle_interval_sec = spot_params.candle_interval_sec;
+        auction.max_slippage_bps = spot_params.max_slippage_bps;
+        auction.on_timeout = spot_params.on_timeout.clone();
+        auction.cancel_on_disconnect = spot_params.cancel_on_disconnect;
+        auction.price_check = spot_params.price_check.clone();
+        Ok(SpotAuction(TwapExecutor::new(auction, spot_params.clone())))
+    }
+}
+
+impl VaultStrategy for BasisExecutor {
+    type Stage = BasisExecutorStage;
+
+    async fn initial_stage(&self) -> Result<BasisExecutorStage> {
+        BasisExecutor::infer_stage(self.params.clone()).await
+    }
+
+    async fn next_stage(&self, completed: &BasisExecutorStage) -> Result<BasisExecutorStage> {
+        let params = self.params.clone();
+        let stage = match completed {
+            SpotOnly(_) => BasisExecutor::new_perp_stage(params, false).await?,
+            ShortAuction(_) => Harvest(FundingWait::new(params, true).await?),
+            Harvest(s) if s.rebalance_requested() => {
+                BasisExecutor::new_perp_stage(params, false).await?
+            }
+            Harvest(_) => BasisExecutor::new_perp_stage(params, true).await?,
+            UnwindAuction(_) => BasisExecutor::new_spot_auction_stage(params).await?,
+            SpotAuction(_) => SpotOnly(FundingWait::new(params, false).await?),
+        };
+        Ok(stage)
+    }
+
+    fn is_epoch_start(&self, stage: &BasisExecutorStage) -> bool {
+        matches!(stage, ShortAuction(_))
+    }
+}
@@ -0,0 +1,127 @@
+use crate::basis::params::BasisParams;
+use crate::helpers::{fetch_ticker, sync_vault_subaccounts};
+use crate::market::new_market_state;
+use crate::shared::stages::{ExecutorStage, TSACollateralOnly};
+use anyhow::{Error, Result};
+use bigdecimal::BigDecimal;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Returns the perp position that offsets the delta of the collateral held by the vault
+pub async fn get_short_target(params: &BasisParams) -> Result<BigDecimal> {
+    let market = new_market_state();
+    sync_vault_subaccounts(market.clone(), vec![]).await?;
+    let spot_amount = market.read().await.get_amount(&params.spot_name);
+    let target = short_target(params, &spot_amount);
+    info!("Short target for spot {}: {}", spot_amount, target);
+    Ok(target)
+}
+
+fn short_target(params: &BasisParams, spot_amount: &BigDecimal) -> BigDecimal {
+    let hedge_params = &params.hedge_auction_params;
+    -(spot_amount * &hedge_params.spot_delta * &hedge_params.hedge_ratio)
+}
+
+/// Current hourly funding rate of the perp
+pub async fn get_funding_rate(perp_name: &str) -> Result<BigDecimal> {
+    let market = new_market_state();
+    fetch_ticker(market.clone(), perp_name).await?;
+    let reader = market.read().await;
+    let ticker = reader.get_ticker(perp_name).ok_or(Error::msg("Perp ticker not found"))?;
+    let details = ticker.perp_details.as_ref().ok_or(Error::msg("Not a perp"))?;
+    Ok(details.funding_rate.clone())
+}
+
+/// - This stage processes deposits and withdrawals while sampling the perp funding rate.
+/// - Without a short (`is_short` false) it completes once the average funding over the window
+/// is above `entry_funding_rate`, with a short once it is below `exit_funding_rate`.
+/// - With a short it also completes early once the perp drifted more than `rebalance_threshold`
+/// from the target (e.g. after deposits or withdrawals), see `rebalance_requested`.
+#[derive(Debug)]
+pub struct FundingWait {
+    pub params: BasisParams,
+    pub is_short: bool,
+    pub collateral: TSACollateralOnly,
+    /// Funding rate samples (timestamp sec, hourly rate) within the window, kept on reconnects
+    samples: Mutex<VecDeque<(i64, BigDecimal)>>,
+    rebalance_requested: AtomicBool,
+}
+
+impl FundingWait {
+    pub async fn new(params: BasisParams, is_short: bool) -> Result<Self> {
+        info!("Starting FundingWait Stage, short: {}", is_short);
+        Ok(Self {
+            params,
+            is_short,
+            collateral: TSACollateralOnly::new().await?,
+            samples: Mutex::new(VecDeque::new()),
+            rebalance_requested: AtomicBool::new(false),
+        })
+    }
+
+    /// True if the stage completed to resize the short rather than to unwind it
+    pub fn rebalance_requested(&self) -> bool {
+        self.rebalance_requested.load(Ordering::Relaxed)
+    }
+
+    /// Adds a sample and returns the average funding rate, None until the window is covered
+    async fn sample_funding(&self) -> Result<Option<BigDecimal>> {
+        let rate = get_funding_rate(&self.params.hedge_auction_params.perp_name).await?;
+        let now = chrono::Utc::now().timestamp();
+        let window_start = now - self.params.funding_window_sec();
+        let mut samples = self.samples.lock().await;
+        samples.push_back((now, rate));
+        let is_covered = samples.front().is_some_and(|(ts, _)| *ts <= window_start);
+        while samples.len() > 1 && samples[1].0 <= window_start {
+            samples.pop_front();
+        }
+        let sum: BigDecimal = samples.iter().map(|(_, rate)| rate).sum();
+        let avg = sum / BigDecimal::from(samples.len() as u64);
+        info!("Funding rate avg {} over {} samples", avg, samples.len());
+        Ok(is_covered.then_some(avg))
+    }
+
+    async fn is_off_target(&self) -> Result<bool> {
+        let market = new_market_state();
+        sync_vault_subaccounts(market.clone(), vec![]).await?;
+        let reader = market.read().await;
+        let target = short_target(&self.params, &reader.get_amount(&self.params.spot_name));
+        let perp_amount = reader.get_amount(&self.params.hedge_auction_params.perp_name);
+        Ok((&target - &perp_amount).abs() > self.params.rebalance_threshold)
+    }
+}
+
+impl ExecutorStage for FundingWait {
+    fn name(&self) -> String {
+        "FundingWait".to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        let interval = tokio::time::Duration::from_secs(self.params.check_interval_sec);
+        loop {
+            self.collateral.run().await?;
+            let avg = self.sample_funding().await?;
+            match (avg, self.is_short) {
+                (Some(avg), false) if avg > self.params.entry_funding_rate => {
+                    info!("Funding {} above entry rate, shorting", avg);
+                    return Ok(());
+                }
+                (Some(avg), true) if avg < self.params.exit_funding_rate => {
+                    info!("Funding {} below exit rate, unwinding", avg);
+                    return Ok(());
+                }
+                (_, true) if self.is_off_target().await? => {
+                    info!("Short off target, rebalancing");
+                    self.rebalance_requested.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
+                _ => {}
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+    async fn reconnect(&mut self) -> Result<()> {
+        self.collateral.reconnect().await
+    }
+}
@@ -0,0 +1,4 @@
+pub mod executor;
+pub mod funding;
+pub mod params;
+pub mod stages;
@@ -0,0 +1,108 @@
+use crate::dn::params::HedgeAuctionParams;
+use crate::shared::fees::FeeBudgetParams;
+use crate::shared::margin::{MarginLimitParams, MarginMonitorParams};
+use crate::shared::params::SpotAuctionParams;
+use crate::shared::reconcile::ReconcileParams;
+use crate::shared::reload::ParamsReloadParams;
+use crate::shared::risk::RiskLimits;
+use crate::shared::subaccounts::SubaccountParams;
+use crate::shared::validation::{ParamsCheck, ValidateParams};
+use bigdecimal::{BigDecimal, Zero};
+use lyra_client::actions::HttpFallbackParams;
+use lyra_client::rfq_maker::RfqMakerParams;
+use serde::Deserialize;
+
+/// Funding basis vault: holds the LRT collateral and shorts the perp while its funding is
+/// positive, unwinding the short once funding flips.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BasisParams {
+    pub env: String,        // Environment name (e.g. staging, prod)
+    pub vault_name: String, // used as prefix for env vars, e.g. {RSWETH}_TSA_ADDRESS
+    pub spot_name: String,  // LRT collateral held by the vault (e.g. RSWETH)
+
+    pub entry_funding_rate: BigDecimal, // Shorts the perp once the avg hourly funding is above it
+    pub exit_funding_rate: BigDecimal,  // Unwinds the short once the avg hourly funding is below it
+    #[serde(default = "default_funding_window_min")]
+    pub funding_window_min: i64, // Funding rate samples are averaged over this window
+    #[serde(default = "default_check_interval_sec")]
+    pub check_interval_sec: u64, // Funding is sampled (and deposits processed) at this interval
+    // Re-runs the short auction once the perp is this far off target, e.g. after deposits
+    pub rebalance_threshold: BigDecimal,
+
+    // Entries / exits of the short: perp_name, spot_delta and hedge_ratio set the target
+    pub hedge_auction_params: HedgeAuctionParams,
+    // Buys the LRT with the funding earned (or sells it for cash owed) once the short is unwound
+    pub spot_auction_params: SpotAuctionParams,
+
+    // Halts the vault and cancels its orders while any of the limits is breached
+    pub risk_limits: Option<RiskLimits>,
+
+    // Alerts on the maintenance margin utilization and runs the emergency action above it
+    pub margin_monitor: Option<MarginMonitorParams>,
+
+    // Resizes or skips auction orders that would take the initial margin above a utilization
+    pub margin_limit: Option<MarginLimitParams>,
+
+    // Throttles and then halts the auction orders past a per epoch budget of trading fees
+    pub fee_budget: Option<FeeBudgetParams>,
+
+    // Resends the auction orders over HTTP while the websocket fails with transport errors
+    pub http_fallback: Option<HttpFallbackParams>,
+
+    // Compares the API balances with the on-chain subaccount, blocking auctions on a drift
+    pub reconcile: Option<ReconcileParams>,
+
+    // Reloads the spreads and tolerances of the running auctions when the params file changes
+    pub params_reload: Option<ParamsReloadParams>,
+
+    // Quotes incoming RFQs around the mark prices to earn the spread
+    pub rfq_maker: Option<RfqMakerParams>,
+
+    // Option and spot subaccounts the orders are routed to, all on the TSA subaccount if unset
+    #[serde(default)]
+    pub subaccounts: SubaccountParams,
+}
+
+fn default_funding_window_min() -> i64 {
+    60
+}
+
+fn default_check_interval_sec() -> u64 {
+    60
+}
+
+impl BasisParams {
+    pub fn funding_window_sec(&self) -> i64 {
+        self.funding_window_min * 60
+    }
+
+    pub fn spot_instrument_name(&self) -> String {
+        format!("{}-{}", self.spot_name, self.spot_auction_params.cash_name)
+    }
+}
+
+impl ValidateParams for BasisParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        // the gap between the rates keeps the short from flapping around a single threshold
+        check.not_above(
+            "exit_funding_rate",
+            &self.exit_funding_rate,
+            "entry_funding_rate",
+            &self.entry_funding_rate,
+        );
+        check.at_least("funding_window_min", &self.funding_window_min, &1);
+        check.at_least("check_interval_sec", &self.check_interval_sec, &1);
+        check.at_least("rebalance_threshold", &self.rebalance_threshold, &BigDecimal::zero());
+        check.nested("hedge_auction_params", &self.hedge_auction_params);
+        check.nested("spot_auction_params", &self.spot_auction_params);
+        if let Some(margin_limit) = &self.margin_limit {
+            check.nested("margin_limit", margin_limit);
+        }
+        if let Some(fee_budget) = &self.fee_budget {
+            check.nested("fee_budget", fee_budget);
+        }
+        if let Some(http_fallback) = &self.http_fallback {
+            check.nested("http_fallback", http_fallback);
+        }
+    }
+}
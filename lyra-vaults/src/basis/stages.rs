@@ -0,0 +1,47 @@
+use crate::basis::funding::FundingWait;
+use crate::dn::hedge_auction::HedgeStrategy;
+use crate::shared::auction::LimitOrderAuctionExecutor;
+use crate::shared::stages::ExecutorStage;
+use crate::shared::twap::TwapExecutor;
+use anyhow::Result;
+use std::fmt::Debug;
+
+#[derive(Debug)]
+pub enum BasisExecutorStage {
+    SpotOnly(FundingWait),
+    ShortAuction(LimitOrderAuctionExecutor<HedgeStrategy>),
+    Harvest(FundingWait),
+    UnwindAuction(LimitOrderAuctionExecutor<HedgeStrategy>),
+    SpotAuction(TwapExecutor),
+}
+
+impl ExecutorStage for BasisExecutorStage {
+    fn name(&self) -> String {
+        let name = match self {
+            BasisExecutorStage::SpotOnly(_) => "SpotOnly",
+            BasisExecutorStage::ShortAuction(_) => "ShortAuction",
+            BasisExecutorStage::Harvest(_) => "Harvest",
+            BasisExecutorStage::UnwindAuction(_) => "UnwindAuction",
+            BasisExecutorStage::SpotAuction(_) => "SpotAuction",
+        };
+        name.to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        match self {
+            BasisExecutorStage::SpotOnly(s) => s.run().await,
+            BasisExecutorStage::ShortAuction(s) => s.run().await,
+            BasisExecutorStage::Harvest(s) => s.run().await,
+            BasisExecutorStage::UnwindAuction(s) => s.run().await,
+            BasisExecutorStage::SpotAuction(s) => s.run().await,
+        }
+    }
+    async fn reconnect(&mut self) -> Result<()> {
+        match self {
+            BasisExecutorStage::SpotOnly(s) => s.reconnect().await,
+            BasisExecutorStage::ShortAuction(s) => s.reconnect().await,
+            BasisExecutorStage::Harvest(s) => s.reconnect().await,
+            BasisExecutorStage::UnwindAuction(s) => s.reconnect().await,
+            BasisExecutorStage::SpotAuction(s) => s.reconnect().await,
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::dn::hedge_auction::{get_hedge_target, HedgeStrategy};
+use crate::dn::params::DNParams;
+use crate::dn::stages::DNExecutorStage;
+use crate::dn::stages::DNExecutorStage::{
+    AwaitSettlement, HedgeAuction, OptionAuction, SpotAuction, SpotOnly, UnwindAuction,
+};
+use crate::helpers::{fetch_ticker, get_option_expiry, sync_vault_subaccounts};
+use crate::lrtc::selector::{maybe_select_from_positions, select_new_option};
+use crate::market::new_market_state;
+use crate::shared::auction::{LimitOrderAuction, LimitOrderAuctionExecutor};
+use crate::shared::cash_yield::swap_idle_cash;
+use crate::shared::reload::ParamsSection;
+use crate::shared::schedule::StageSchedule;
+use crate::shared::stages::{TSACollateralOnly, TSAWaitForSettlement};
+use crate::shared::twap::TwapExecutor;
+use crate::shared::vault::VaultStrategy;
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use orderbook_types::types::orders::Direction;
+use tracing::info;
+
+/// Delta-neutral covered call strategy, run with `VaultExecutor::new(DNExecutor::new(params))`
+pub struct DNExecutor {
+    params: DNParams,
+}
+
+impl DNExecutor {
+    pub fn new(params: DNParams) -> Self {
+        Self { params }
+    }
+
+    /// Infers the stage from the positions / market
+    /// Cues for the state (same as LRTC, plus the perp hedge):
+    /// - Spot Only MUST have no options, no perp and USDC within threshold
+    /// - Unwind Auction has no options and a non-zero perp position
+    /// - Option Auction has # of options > 0 and the option auction is likely still ongoing
+    /// - Hedge Auction has # of options > 0 and the option auction is over
+    /// - Spot Auction has no options, no perp and USDC outside of threshold
+    /// Hedge Auction is a no-op if the perp position is already within one amount step of target,
+    /// so restarting in it is always safe and it will then proceed to Await Settlement.
+    async fn infer_stage(params: DNParams) -> Result<DNExecutorStage> {
+        let market = new_market_state();
+        sync_vault_subaccounts(market.clone(), vec![]).await?;
+
+        let option_name = maybe_select_from_positions(&market).await?;
+        info!("Current option position: {:?}", option_name);
+
+        let reader = market.read().await;
+        let cash_bal = reader.get_amount(&params.covered_call.spot_auction_params.cash_name);
+        let perp_bal = reader.get_amount(&params.hedge_auction_params.perp_name);
+        drop(reader);
+
+        let is_cash_within_threshold =
+            params.covered_call.spot_auction_params.is_cash_within_threshold(&cash_bal);
+
+        if option_name.is_none() && !perp_bal.is_zero() {
+            info!("Starting in Unwind Auction stage");
+            return DNExecutor::new_hedge_stage(params.clone(), None).await;
+        } else if option_name.is_none() && is_cash_within_threshold {
+            info!("Starting in Spot Only stage");
+            return Ok(SpotOnly(TSACollateralOnly::new().await?));
+        } else if option_name.is_none() && !is_cash_within_threshold {
+            info!("Starting in Spot Auction stage");
+            return DNExecutor::new_spot_auction_stage(params.clone()).await;
+        }
+        let option_name = option_name.unwrap();
+
+        fetch_ticker(market.clone(), &option_name).await?;
+        let reader = market.read().await;
+        let option_expiry =
+            reader.get_ticker(&option_name).unwrap().option_details.as_ref().unwrap().expiry;
+
+        let now = chrono::Utc::now().timestamp();
+        let cc_params = &params.covered_call;
+        let approx_auction_start = cc_params.option_auction_start(option_expiry);
+        let is_still_ongoing =
+            now < approx_auction_start + cc_params.option_auction_params.auction_sec;
+        let is_expiry_still_valid = option_expiry > now + cc_params.min_expiry_sec();
+
+        return if is_still_ongoing && is_expiry_still_valid {
+            info!("Starting in Option Auction stage");
+            DNExecutor::new_option_stage(params.clone(), option_name).await
+        } else {
+            info!("Starting in Hedge Auction stage");
+            DNExecutor::new_hedge_stage(params.clone(), Some(option_name)).await
+        };
+    }
+
+    pub async fn new_settlement_stage(
+        params: DNParams,
+        option_name: String,
+    ) -> Result<DNExecutorStage> {
+        let mut stage = TSAWaitForSettlement::new(
+            params.covered_call.spot_auction_delay_min,
+            vec![option_name],
+        )
+        .await?;
+        stage.schedule = params.covered_call.schedule.clone();
+        stage.settlement_check = params.covered_call.settlement_check.clone();
+        stage.cash_yield = params.covered_call.cash_yield.clone();
+        Ok(AwaitSettlement(stage))
+    }
+
+    pub async fn new_option_stage(
+        params: DNParams,
+        option_name: String,
+    ) -> Result<DNExecutorStage> {
+        let cc_params = &params.covered_call;
+        let option_expiry = get_option_expiry(&option_name).await?;
+        let mut auction = LimitOrderAuction::new(
+            option_name,
+            cc_params.option_auction_start(option_expiry),
+            cc_params.option_auction_params.auction_sec,
+            cc_params.option_auction_params.price_change_tolerance.clone(),
+        )
+        .await?;
+        auction.display_fraction = cc_params.option_auction_params.display_fraction.clone();
+        auction.mmp = cc_params.option_auction_params.mmp.clone();
+        auction.depth_limit = cc_params.option_auction_params.depth_limit.clone();
+        auction.reload_section = Some(ParamsSection::OptionAuction);
+        auction.candle_interval_sec = cc_params.option_auction_params.candle_interval_sec;
+        auction.max_slippage_bps = cc_params.option_auction_params.max_slippage_bps;
+        auction.on_timeout = cc_params.option_auction_params.on_timeout.clone();
+        auction.cancel_on_disconnect = cc_params.option_auction_params.cancel_on_disconnect;
+        auction.reference_instruments =
+            cc_params.option_auction_params.reference_instruments(&auction.instrument_name).await?;
+        let strategy =
+            cc_params.option_auction_params.clone().with_realized_vol_spread(&auction).await;
+        let stage = OptionAuction(LimitOrderAuctionExecutor { auction, strategy });
+        Ok(stage)
+    }
+
+    /// Hedges the delta of the given option + collateral, or unwinds the hedge if None.
+    pub async fn new_hedge_stage(
+        params: DNParams,
+        option_name: Option<String>,
+    ) -> Result<DNExecutorStage> {
+        let hedge_params = &params.hedge_auction_params;
+        let is_unwind = option_name.is_none();
+        let spot_name = &params.covered_call.option_auction_params.spot_name;
+        let target_amount = get_hedge_target(hedge_params, spot_name, option_name).await?;
+        let start_sec = match is_unwind {
+            true => chrono::Utc::now().timestamp(),
+            false => chrono::Utc::now().timestamp() + params.hedge_auction_delay_sec(),
+        };
+        let mut auction = LimitOrderAuction::new(
+            hedge_params.perp_name.clone(),
+            start_sec,
+            hedge_params.auction_sec,
+            hedge_params.price_change_tolerance.clone(),
+        )
+        .await?;
+        auction.depth_limit = hedge_params.depth_limit.clone();
+        auction.max_slippage_bps = hedge_params.max_slippage_bps;
+        auction.cancel_on_disconnect = hedge_params.cancel_on_disconnect;
+        let executor = LimitOrderAuctionExecutor {
+            auction,
+            strategy: HedgeStrategy { params: hedge_params.clone(), target_amount },
+        };
+        match is_unwind {
+            true => Ok(UnwindAuction(executor)),
+            false => Ok(HedgeAuction(executor)),
+        }
+    }
+
+    pub async fn new_spot_auction_stage(params: DNParams) -> Result<DNExecutorStage> {
+        let cc_params = &params.covered_call;
+        let mut auction = LimitOrderAuction::new(
+            cc_params.spot_instrument_name(),
+            chrono::Utc::now().timestamp(),
+            cc_params.spot_auction_params.auction_sec,
+            cc_params.spot_auction_params.price_change_tolerance.clone(),
+        )
+        .await?;
+        auction.depth_limit = cc_params.spot_auction_params.depth_limit.clone();
+        auction.orderbook_levels = cc_params.spot_auction_params.pricing_orderbook_levels();
+        auction.reload_section = Some(ParamsSection::SpotAuction);
+        auction.candle_interval_sec = cc_params.spot_auction_params.candle_interval_sec;
+        auction.max_slippage_bps = cc_params.spot_auction_params.max_slippage_bps;
+        auction.on_timeout = cc_params.spot_auction_params.on_timeout.clone();
+        auction.cancel_on_disconnect = cc_params.spot_auction_params.cancel_on_disconnect;
+        auction.price_check = cc_params.spot_auction_params.price_check.clone();
+        let stage = SpotAuction(TwapExecutor::new(auction, cc_params.spot_auction_params.clone()));
+        Ok(stage)
+    }
+
+    pub async fn select_new_option_until_success(&self) -> String {
+        loop {
+            match select_new_option(&self.params.covered_call).await {
+                Ok(option_name) => return option_name,
+                Err(e) => {
+                    info!("select_new_option failed with {:#}, waiting for 60s", e);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+                }
+            }
+        }
+    }
+
+    async fn await_option_auction_start(&self) -> Result<()> {
+        let option_name = self.select_new_option_until_success().await;
+        let option_expiry = get_option_expiry(&option_name).await?;
+        let start_sec = self.params.covered_call.option_auction_start(option_expiry);
+        let sleep_sec = start_sec - chrono::Utc::now().timestamp();
+        if sleep_sec > 0 {
+            info!("Executor await_option_auction_start sleep for {} sec", sleep_sec);
+            tokio::time::sleep(tokio::time::Duration::from_secs(sleep_sec as u64)).await;
+        }
+        Ok(())
+    }
+}
+
+impl VaultStrategy for DNExecutor {
+    type Stage = DNExecutorStage;
+
+    /// Unwinds a cash yield wrapper left by a restart unless it is still in the settlement
+    /// wait, same as LRTC
+    async fn initial_stage(&self) -> Result<DNExecutorStage> {
+        let stage = DNExecutor::infer_stage(self.params.clone()).await?;
+        let cc_params = &self.params.covered_call;
+        let Some(cash_yield) = &cc_params.cash_yield else {
+            return Ok(stage);
+        };
+        if cash_yield.wrapper_name.is_none() || matches!(stage, AwaitSettlement(_)) {
+            return Ok(stage);
+        }
+        let cash_name = &cc_params.spot_auction_params.cash_name;
+        swap_idle_cash(cash_yield, cash_name, Direction::Sell, None).await?;
+        DNExecutor::infer_stage(self.params.clone()).await
+    }
+
+    async fn next_stage(&self, completed: &DNExecutorStage) -> Result<DNExecutorStage> {
+        let stage = match completed {
+            SpotOnly(_) => {
+                let option_name = select_new_option(&self.params.covered_call).await;
+                match option_name {
+                    Ok(_) => {
+                        self.await_option_auction_start().await?;
+                        let option_name = self.select_new_option_until_success().await;
+                        DNExecutor::new_option_stage(self.params.clone(), option_name).await?
+                    }
+                    Err(e) => {
+                        info!("select_new_option failed with {:#}, re-entering spot only stage", e);
+                        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+                        SpotOnly(TSACollateralOnly::new().await?)
+                    }
+                }
+            }
+            OptionAuction(ref s) => {
+                let option_name = s.auction.instrument_name.clone();
+                DNExecutor::new_hedge_stage(self.params.clone(), Some(option_name)).await?
+            }
+            HedgeAuction(_) => {
+                let market = new_market_state();
+                sync_vault_subaccounts(market.clone(), vec![]).await?;
+                match maybe_select_from_positions(&market).await? {
+                    Some(option_name) => {
+                        DNExecutor::new_settlement_stage(self.params.clone(), option_name).await?
+                    }
+                    None => DNExecutor::new_hedge_stage(self.params.clone(), None).await?,
+                }
+            }
+            AwaitSettlement(_) => DNExecutor::new_hedge_stage(self.params.clone(), None).await?,
+            UnwindAuction(_) => DNExecutor::new_spot_auction_stage(self.params.clone()).await?,
+            SpotAuction(_) => SpotOnly(TSACollateralOnly::new().await?),
+        };
+        Ok(stage)
+    }
+
+    fn is_epoch_start(&self, stage: &DNExecutorStage) -> bool {
+        matches!(stage, OptionAuction(_))
+    }
+
+    fn schedule(&self) -> Option<&StageSchedule> {
+        Some(&self.params.covered_call.schedule)
+    }
+
+    fn yield_instruments(&self) -> Vec<String> {
+        self.params.covered_call.yield_instruments()
+    }
+}
@@ -0,0 +1,117 @@
+use crate::dn::params::HedgeAuctionParams;
+use crate::helpers::{fetch_ticker, sync_vault_subaccounts};
+use crate::market::new_market_state;
+use crate::shared::auction::{LimitOrderAuction, OrderStrategy};
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, ToPrimitive, Zero};
+use lyra_client::actions::Direction;
+use std::cmp::Ordering;
+use tracing::{debug, info};
+
+/// Trades the perp towards a fixed target position.
+/// The target is computed once when the stage is created (see `get_hedge_target`).
+#[derive(Debug, Clone)]
+pub struct HedgeStrategy {
+    pub params: HedgeAuctionParams,
+    pub target_amount: BigDecimal,
+}
+
+/// Returns the perp position that offsets the collateral and option deltas.
+/// Pass `None` as option_name to unwind the hedge (target of zero).
+pub async fn get_hedge_target(
+    params: &HedgeAuctionParams,
+    spot_name: &str,
+    option_name: Option<String>,
+) -> Result<BigDecimal> {
+    let option_name = match option_name {
+        Some(option_name) => option_name,
+        None => return Ok(BigDecimal::zero()),
+    };
+    let market = new_market_state();
+    sync_vault_subaccounts(market.clone(), vec![]).await?;
+    fetch_ticker(market.clone(), &option_name).await?;
+
+    let reader = market.read().await;
+    let ticker = reader.get_ticker(&option_name).ok_or(Error::msg("Ticker not found"))?;
+    let option_delta = &ticker.option_pricing.as_ref().unwrap().delta;
+    let spot_amount = reader.get_amount(spot_name);
+    let option_amount = reader.get_amount(&option_name);
+
+    let net_delta = &spot_amount * &params.spot_delta + &option_amount * option_delta;
+    let target = -(net_delta * &params.hedge_ratio);
+    info!(
+        "Hedge target for spot {}, option {} with delta {}: {}",
+        spot_amount, option_amount, option_delta, target
+    );
+    Ok(target)
+}
+
+impl HedgeStrategy {
+    fn get_remaining_amount(
+        &self,
+        auction: &LimitOrderAuction,
+        perp_pos: &BigDecimal,
+    ) -> BigDecimal {
+        if auction.remain_sec() <= 0 {
+            return BigDecimal::zero();
+        }
+        &self.target_amount - perp_pos
+    }
+}
+
+impl OrderStrategy for HedgeStrategy {
+    async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal> {
+        let market = &auction.market;
+        let reader = market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let perp_pos = reader.get_amount(&auction.instrument_name);
+        let remaining = self.get_remaining_amount(auction, &perp_pos);
+
+        let spread = self.params.get_spread(auction.start_timestamp_sec);
+        let mark = ticker.mark_price.to_f64().ok_or(Error::msg("mark cast to f64 failed"))?;
+
+        debug!("HedgeAuction mark, spread, remaining: {}, {}, {}", mark, spread, remaining);
+
+        let price = match remaining.cmp(&BigDecimal::zero()) {
+            Ordering::Greater => mark * (1.0 + spread),
+            Ordering::Less => mark * (1.0 - spread),
+            Ordering::Equal => return Ok(BigDecimal::zero()),
+        };
+
+        let price = BigDecimal::from_f64(price)
+            .unwrap()
+            .round(ticker.tick_size.fractional_digit_count())
+            .max(ticker.min_price.clone());
+
+        Ok(price)
+    }
+    async fn get_desired_amount(
+        &self,
+        auction: &LimitOrderAuction,
+        price: &BigDecimal,
+    ) -> Result<(Direction, BigDecimal)> {
+        let market = &auction.market;
+        let reader = market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let zero = BigDecimal::zero();
+        if price == &zero {
+            return Ok((Direction::Sell, zero));
+        }
+        let perp_pos = reader.get_amount(&auction.instrument_name);
+        let remaining = self.get_remaining_amount(auction, &perp_pos);
+        let (direction, amount) = match remaining.cmp(&zero) {
+            Ordering::Less | Ordering::Equal => (Direction::Sell, -remaining),
+            Ordering::Greater => (Direction::Buy, remaining),
+        };
+
+        let amount = amount
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down);
+        if amount < ticker.minimum_amount.clone() {
+            return Ok((Direction::Sell, zero));
+        }
+        drop(reader);
+        Ok((direction, auction.cap_to_depth(&direction, amount).await?))
+    }
+}
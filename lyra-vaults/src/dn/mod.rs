@@ -0,0 +1,4 @@
+pub mod executor;
+pub mod hedge_auction;
+pub mod params;
+pub mod stages;
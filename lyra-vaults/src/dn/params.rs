@@ -0,0 +1,79 @@
+use crate::lrtc::params::LRTCParams;
+use crate::shared::params::{default_cancel_on_disconnect, DepthLimitParams};
+use crate::shared::validation::{ParamsCheck, ValidateParams};
+use bigdecimal::{BigDecimal, Zero};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HedgeAuctionParams {
+    pub max_spread: f64,
+    pub init_spread: f64,
+    pub spread_per_min: f64,
+    pub auction_sec: i64,
+    pub price_change_tolerance: BigDecimal,
+
+    pub perp_name: String,       // Perp used for hedging (e.g. ETH-PERP)
+    pub spot_delta: BigDecimal,  // Delta of one unit of collateral in perp terms (e.g. 1 for LRTs)
+    pub hedge_ratio: BigDecimal, // Fraction of the net delta to hedge, 1 is fully delta neutral
+    // Caps the perp orders at a fraction of the visible orderbook depth
+    #[serde(default)]
+    pub depth_limit: Option<DepthLimitParams>,
+    // Caps perp orders crossing the book at this many bps from mark (skipped beyond that)
+    #[serde(default)]
+    pub max_slippage_bps: Option<u32>,
+    // Cancels the hedge orders when the connection drops, enabled by default
+    #[serde(default = "default_cancel_on_disconnect")]
+    pub cancel_on_disconnect: bool,
+}
+
+/// Covered call vault with the net delta (collateral + short calls) hedged in the perp.
+/// Shares all option / spot auction params with the LRTC vault.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DNParams {
+    #[serde(flatten)]
+    pub covered_call: LRTCParams,
+    pub hedge_auction_delay_min: i64, // Min delay after the option auction before hedging
+    pub hedge_auction_params: HedgeAuctionParams,
+}
+
+impl DNParams {
+    pub fn hedge_auction_delay_sec(&self) -> i64 {
+        self.hedge_auction_delay_min * 60
+    }
+}
+
+impl HedgeAuctionParams {
+    /// Returns an auction perp spread, starting from its init value and increasing per minute.
+    pub fn get_spread(&self, start_timestamp_sec: i64) -> f64 {
+        let sec_since_start = chrono::Utc::now().timestamp() - start_timestamp_sec;
+        let min_since_start = sec_since_start as f64 / 60.0;
+        let spread = self.init_spread + min_since_start * self.spread_per_min;
+        spread.min(self.max_spread)
+    }
+}
+
+impl ValidateParams for DNParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        // flattened, so the fields of the covered call are at the top level
+        self.covered_call.validate(check);
+        check.at_least("hedge_auction_delay_min", &self.hedge_auction_delay_min, &0);
+        check.nested("hedge_auction_params", &self.hedge_auction_params);
+    }
+}
+
+impl ValidateParams for HedgeAuctionParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        // a negative initial spread starts the auction through the mark
+        check.at_least("init_spread", &self.init_spread, &-self.max_spread);
+        check.fraction("max_spread", self.max_spread);
+        check.not_above("init_spread", &self.init_spread, "max_spread", &self.max_spread);
+        check.at_least("spread_per_min", &self.spread_per_min, &0.0);
+        check.at_least("auction_sec", &self.auction_sec, &1);
+        check.at_least("price_change_tolerance", &self.price_change_tolerance, &BigDecimal::zero());
+        check.above("spot_delta", &self.spot_delta, &BigDecimal::zero());
+        check.at_least("hedge_ratio", &self.hedge_ratio, &BigDecimal::zero());
+        if let Some(depth_limit) = &self.depth_limit {
+            check.nested("depth_limit", depth_limit);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::dn::hedge_auction::HedgeStrategy;
+use crate::lrtc::params::OptionAuctionParams;
+use crate::shared::auction::LimitOrderAuctionExecutor;
+use crate::shared::stages::{ExecutorStage, TSACollateralOnly, TSAWaitForSettlement};
+use crate::shared::twap::TwapExecutor;
+use anyhow::Result;
+use std::fmt::Debug;
+
+#[derive(Debug)]
+pub enum DNExecutorStage {
+    SpotOnly(TSACollateralOnly),
+    OptionAuction(LimitOrderAuctionExecutor<OptionAuctionParams>),
+    HedgeAuction(LimitOrderAuctionExecutor<HedgeStrategy>),
+    AwaitSettlement(TSAWaitForSettlement),
+    UnwindAuction(LimitOrderAuctionExecutor<HedgeStrategy>),
+    SpotAuction(TwapExecutor),
+}
+
+impl ExecutorStage for DNExecutorStage {
+    fn name(&self) -> String {
+        let name = match self {
+            DNExecutorStage::SpotOnly(_) => "SpotOnly",
+            DNExecutorStage::OptionAuction(_) => "OptionAuction",
+            DNExecutorStage::HedgeAuction(_) => "HedgeAuction",
+            DNExecutorStage::AwaitSettlement(_) => "AwaitSettlement",
+            DNExecutorStage::UnwindAuction(_) => "UnwindAuction",
+            DNExecutorStage::SpotAuction(_) => "SpotAuction",
+        };
+        name.to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        match self {
+            DNExecutorStage::SpotOnly(s) => s.run().await,
+            DNExecutorStage::OptionAuction(s) => s.run().await,
+            DNExecutorStage::HedgeAuction(s) => s.run().await,
+            DNExecutorStage::AwaitSettlement(s) => s.run().await,
+            DNExecutorStage::UnwindAuction(s) => s.run().await,
+            DNExecutorStage::SpotAuction(s) => s.run().await,
+        }
+    }
+    async fn reconnect(&mut self) -> Result<()> {
+        match self {
+            DNExecutorStage::SpotOnly(s) => s.reconnect().await,
+            DNExecutorStage::OptionAuction(s) => s.reconnect().await,
+            DNExecutorStage::HedgeAuction(s) => s.reconnect().await,
+            DNExecutorStage::AwaitSettlement(s) => s.reconnect().await,
+            DNExecutorStage::UnwindAuction(s) => s.reconnect().await,
+            DNExecutorStage::SpotAuction(s) => s.reconnect().await,
+        }
+    }
+}
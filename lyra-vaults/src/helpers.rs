@@ -1,9 +1,15 @@
-use crate::market::{new_market_state, Balance, MarketState};
+use crate::market::{
+    new_market_state, Balance, MarketState, OrderbookData, PortfolioGreeks, PublicTrade,
+};
+use crate::shared::context::VaultContext;
 use lyra_client::auth::get_auth_headers;
-use lyra_client::json_rpc::{http_rpc, Notification, Response, WsClient, WsClientExt};
+use lyra_client::channels::Channel;
+use lyra_client::json_rpc::{http_rpc, Notification, ReconnectCallback, Response, WsClientExt};
+use lyra_client::notifications::LyraNotification;
+use lyra_client::tickers::TickerCache;
+use lyra_client::ws_mux::WsMux;
 use std::str::FromStr;
 
-use orderbook_types::generated::channel_subaccount_id_balances::BalanceUpdateSchema;
 use orderbook_types::generated::private_get_subaccount::{
     PrivateGetSubaccountParamsSchema, PrivateGetSubaccountResponseSchema,
 };
@@ -18,18 +24,22 @@ use orderbook_types::types::tickers::result::{
 use anyhow::{Error, Result};
 use bigdecimal::{BigDecimal, Zero};
 use chrono::Utc;
-use log::{debug, error, info, warn};
-use orderbook_types::types::orders::{
-    GetTradesParams, GetTradesResponse, OrderNotificationData, OrderResponse,
-    TradeNotificationData, TxStatus,
-};
-use serde::{Deserialize, Serialize};
+use futures::FutureExt;
+use orderbook_types::types::orders::{GetTradesParams, GetTradesResponse, OrderResponse, TxStatus};
 use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::select;
+use tracing::{debug, error, info, warn};
 
 const SPOT_QUERY_BUFFER_SEC: i64 = 60 * 60; // 1 hour
 
 type TickerMsg = Notification<TickerNotificationData>;
+type OrderbookMsg = Notification<OrderbookData>;
+type PublicTradesMsg = Notification<Vec<PublicTrade>>;
+
+/// Default time without a ticker or orderbook message before its channel is resubscribed
+const DEFAULT_STALE_CHANNEL_SEC: u64 = 30;
 
 #[derive(Copy, Clone)]
 pub enum TickerInterval {
@@ -37,6 +47,12 @@ pub enum TickerInterval {
     _100Ms = 100,
 }
 
+/// Stale threshold of the periodic market data channels, override with WS_STALE_CHANNEL_SEC
+pub fn stale_channel_threshold() -> Duration {
+    let stale_sec = std::env::var("WS_STALE_CHANNEL_SEC").ok().and_then(|s| s.parse().ok());
+    Duration::from_secs(stale_sec.unwrap_or(DEFAULT_STALE_CHANNEL_SEC))
+}
+
 pub async fn subscribe_tickers(
     market: MarketState,
     instrument_names: Vec<String>,
@@ -44,16 +60,71 @@ pub async fn subscribe_tickers(
 ) -> Result<()> {
     let channels: Vec<String> = instrument_names
         .iter()
-        .map(|instrument_name| format!("ticker.{}.{}", instrument_name, interval as u32))
+        .map(|instrument_name| Channel::ticker(instrument_name, interval as u32).to_string())
         .collect();
-    let client = WsClient::new_client().await?;
+    let mux = WsMux::shared().await?;
+    mux.client().set_stale_threshold(channels.clone(), stale_channel_threshold()).await;
+    let health = VaultContext::current().health.clone();
     info!("Subscribing to tickers: {:?}", channels);
-    client
-        .subscribe(channels, |msg: TickerMsg| async {
-            market.write().await.insert_ticker(msg.params.data.instrument_ticker);
+    let subscription = mux.subscribe(channels, |msg: TickerMsg| async {
+        health.market_data_received();
+        market.write().await.insert_ticker(msg.params.data.instrument_ticker);
+        Ok(())
+    });
+    health.track_subscription(|h| &mut h.ticker_subscriptions, subscription).await?;
+    Ok(())
+}
+
+/// Subscribes to the best `depth` levels (1, 10, 20 or 100) of the orderbooks
+pub async fn subscribe_orderbooks(
+    market: MarketState,
+    instrument_names: Vec<String>,
+    depth: u32,
+) -> Result<()> {
+    let channels: Vec<String> = instrument_names
+        .iter()
+        .map(|instrument_name| Channel::orderbook(instrument_name, depth).to_string())
+        .collect();
+    let mux = WsMux::shared().await?;
+    mux.client().set_stale_threshold(channels.clone(), stale_channel_threshold()).await;
+    let health = VaultContext::current().health.clone();
+    info!("Subscribing to orderbooks: {:?}", channels);
+    let subscription = mux.subscribe(channels, |msg: OrderbookMsg| async {
+        health.market_data_received();
+        market.write().await.insert_orderbook(msg.params.data);
+        Ok(())
+    });
+    health.track_subscription(|h| &mut h.orderbook_subscriptions, subscription).await?;
+    Ok(())
+}
+
+/// Subscribes to the public trades of the instruments, aggregated into candles of every
+/// interval (see `MarketData::get_candles`)
+pub async fn subscribe_trades(
+    market: MarketState,
+    instrument_names: Vec<String>,
+    intervals_sec: Vec<i64>,
+) -> Result<()> {
+    let channels: Vec<String> = instrument_names
+        .iter()
+        .map(|instrument_name| Channel::trades(instrument_name).to_string())
+        .collect();
+    let mux = WsMux::shared().await?;
+    let health = VaultContext::current().health.clone();
+    info!("Subscribing to trades: {:?}", channels);
+    let subscription = mux.subscribe(channels, |msg: PublicTradesMsg| {
+        let (health, market, intervals_sec) =
+            (health.clone(), market.clone(), intervals_sec.clone());
+        async move {
+            health.market_data_received();
+            let mut writer = market.write().await;
+            for trade in msg.params.data.iter() {
+                writer.insert_public_trade(trade, &intervals_sec);
+            }
             Ok(())
-        })
-        .await?;
+        }
+    });
+    health.track_subscription(|h| &mut h.trade_subscriptions, subscription).await?;
     Ok(())
 }
 
@@ -62,7 +133,7 @@ pub async fn sync_subaccount(
     subaccount_id: i64,
     instrument_names: Vec<String>,
 ) -> Result<()> {
-    let headers = get_auth_headers().await;
+    let headers = get_auth_headers().await?;
     let subacc = http_rpc::<_, PrivateGetSubaccountResponseSchema>(
         "private/get_subaccount",
         PrivateGetSubaccountParamsSchema { subaccount_id },
@@ -79,18 +150,24 @@ pub async fn sync_subaccount(
         Response::Success(subacc) => {
             let now = Utc::now().timestamp_millis();
             for position in subacc.result.positions {
-                writer.insert_position(Balance {
-                    instrument_name: position.instrument_name,
-                    amount: position.amount,
-                    timestamp: now,
-                });
+                writer.insert_subaccount_position(
+                    subaccount_id,
+                    Balance {
+                        instrument_name: position.instrument_name,
+                        amount: position.amount,
+                        timestamp: now,
+                    },
+                );
             }
             for collateral in subacc.result.collaterals {
-                writer.insert_position(Balance {
-                    instrument_name: collateral.asset_name,
-                    amount: collateral.amount,
-                    timestamp: now,
-                });
+                writer.insert_subaccount_position(
+                    subaccount_id,
+                    Balance {
+                        instrument_name: collateral.asset_name,
+                        amount: collateral.amount,
+                        timestamp: now,
+                    },
+                );
             }
             for order in subacc.result.open_orders {
                 // TODO horribly inefficient to do casting this way but don't want to rewrite schema
@@ -139,6 +216,26 @@ pub async fn sync_subaccount(
     Ok(())
 }
 
+/// Syncs all subaccounts of the vault into the market, positions are summed over them
+pub async fn sync_vault_subaccounts(
+    market: MarketState,
+    instrument_names: Vec<String>,
+) -> Result<()> {
+    let subaccount_ids = VaultContext::current().subaccount_ids()?;
+    sync_subaccounts(market, &subaccount_ids, instrument_names).await
+}
+
+pub async fn sync_subaccounts(
+    market: MarketState,
+    subaccount_ids: &[i64],
+    instrument_names: Vec<String>,
+) -> Result<()> {
+    for subaccount_id in subaccount_ids {
+        sync_subaccount(market.clone(), *subaccount_id, instrument_names.clone()).await?;
+    }
+    Ok(())
+}
+
 pub async fn fetch_ticker(market: MarketState, instrument_name: &str) -> Result<()> {
     let ticker = http_rpc::<_, TickerResponse>(
         "public/get_ticker",
@@ -151,62 +248,105 @@ pub async fn fetch_ticker(market: MarketState, instrument_name: &str) -> Result<
     Ok(())
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(untagged)]
-pub enum SubaccountSubscriberData {
-    BalancesMsg(Notification<Vec<BalanceUpdateSchema>>),
-    OrdersMsg(Notification<OrderNotificationData>),
-    TradesMsg(Notification<TradeNotificationData>),
+/// Fetches the positions and their tickers, logs the portfolio greeks and reports them to the
+/// vault's health status (e.g. for delta alerts)
+pub async fn log_portfolio_greeks(subaccount_ids: &[i64]) -> Result<PortfolioGreeks> {
+    let market = new_market_state();
+    sync_subaccounts(market.clone(), subaccount_ids, vec![]).await?;
+    let instrument_names = market
+        .read()
+        .await
+        .iter_positions()
+        .filter(|p| !p.amount.is_zero() && p.instrument_name.contains('-'))
+        .map(|p| p.instrument_name.clone())
+        .collect::<Vec<_>>();
+    for instrument_name in instrument_names {
+        fetch_ticker(market.clone(), &instrument_name).await?;
+    }
+    let ctx = VaultContext::current();
+    let greeks = market.read().await.get_portfolio_greeks(&ctx.collateral_names());
+    info!(
+        "Portfolio greeks: net delta {}, gamma {}, vega {}, theta {}",
+        greeks.net_delta(),
+        greeks.gamma,
+        greeks.vega,
+        greeks.theta
+    );
+    if !greeks.missing.is_empty() {
+        warn!("Greeks exclude positions without pricing: {:?}", greeks.missing);
+    }
+    ctx.health.update(|h| h.greeks = Some(greeks.clone()));
+    Ok(greeks)
 }
 
 pub async fn subscribe_subaccount(state: MarketState, subaccount_id: i64) -> Result<()> {
-    let channels: Vec<String> = vec![
-        format!("{}.balances", subaccount_id),
-        format!("{}.orders", subaccount_id),
-        format!("{}.trades.settled", subaccount_id),
-        format!("{}.trades.reverted", subaccount_id),
-        format!("{}.trades", subaccount_id),
-    ];
+    let channels = Channel::names(&[
+        Channel::balances(subaccount_id),
+        Channel::orders(subaccount_id),
+        Channel::subaccount_trades(subaccount_id, Some("settled")),
+        Channel::subaccount_trades(subaccount_id, Some("reverted")),
+        Channel::subaccount_trades(subaccount_id, None),
+    ]);
 
-    let client = WsClient::new_client().await?;
-    let login = client.login().await?.into_result()?;
-    info!("Login: {:?}", login);
+    let mux = WsMux::shared().await?;
+    // balances and orders may have changed while disconnected, so resync them on reconnect
+    let resync_state = state.clone();
+    let on_reconnect: ReconnectCallback =
+        Arc::new(move || sync_subaccount(resync_state.clone(), subaccount_id, vec![]).boxed());
     info!("Subscribing to subaccount: {:?}", channels);
-    client
-        .subscribe(channels, |d: SubaccountSubscriberData| async {
+    let subscription =
+        mux.subscribe_with_resync(channels, Some(on_reconnect), |d: LyraNotification| async {
             match d {
-                SubaccountSubscriberData::BalancesMsg(msg) => {
+                LyraNotification::Balances(msg) => {
                     let mut writer = state.write().await;
                     for balance in msg.params.data {
-                        writer.insert_position(Balance {
-                            instrument_name: balance.name.clone(),
-                            amount: balance.new_balance.clone(),
-                            timestamp: Utc::now().timestamp_millis(),
-                        });
+                        writer.insert_subaccount_position(
+                            subaccount_id,
+                            Balance {
+                                instrument_name: balance.name.clone(),
+                                amount: balance.new_balance.clone(),
+                                timestamp: Utc::now().timestamp_millis(),
+                            },
+                        );
                     }
                 }
-                SubaccountSubscriberData::OrdersMsg(msg) => {
+                LyraNotification::Orders(msg) => {
                     let mut writer = state.write().await;
                     for order in msg.params.data {
                         writer.insert_order(order);
                     }
                 }
-                SubaccountSubscriberData::TradesMsg(msg) => {
+                LyraNotification::SubaccountTrades(msg) => {
                     let mut writer = state.write().await;
                     for trade in msg.params.data {
                         writer.insert_trade(trade);
                     }
                 }
+                other => warn!("Unexpected subaccount notification on {}", other.channel()),
             }
             Ok(())
-        })
+        });
+    VaultContext::current()
+        .health
+        .track_subscription(|h| &mut h.subaccount_subscriptions, subscription)
         .await?;
     Ok(())
 }
 
+/// Subscribes to several subaccounts of the same market, exits once any subscription does
+pub async fn subscribe_subaccounts(state: MarketState, subaccount_ids: Vec<i64>) -> Result<()> {
+    if subaccount_ids.is_empty() {
+        return Err(Error::msg("No subaccounts to subscribe to"));
+    }
+    let subscriptions = subaccount_ids
+        .into_iter()
+        .map(|subaccount_id| subscribe_subaccount(state.clone(), subaccount_id).boxed());
+    futures::future::select_all(subscriptions).await.0
+}
+
 /// Fetches the balance of a subaccount for a given asset.
 pub async fn get_single_balance(subaccount_id: i64, asset_name: &str) -> Result<BigDecimal> {
-    let headers = get_auth_headers().await;
+    let headers = get_auth_headers().await?;
     let subaccount = http_rpc::<_, PrivateGetSubaccountResponseSchema>(
         "private/get_subaccount",
         PrivateGetSubaccountParamsSchema { subaccount_id },
@@ -226,14 +366,9 @@ pub async fn get_single_balance(subaccount_id: i64, asset_name: &str) -> Result<
 }
 
 pub async fn get_option_expiry(instrument_name: &str) -> Result<i64> {
-    let ticker = http_rpc::<_, TickerResponse>(
-        "public/get_ticker",
-        json!({ "instrument_name": instrument_name }),
-        None,
-    )
-    .await?
-    .into_result()?;
-    Ok(ticker.result.option_details.unwrap().expiry)
+    let ticker = TickerCache::global().get(instrument_name).await?;
+    let details = ticker.option_details.ok_or(Error::msg("Not an option"))?;
+    Ok(details.expiry)
 }
 
 pub async fn get_expiry_options(
@@ -278,6 +413,62 @@ pub async fn get_expiry_options(
     Ok(expiry_options)
 }
 
+/// Active options of the currency with exactly the given expiry (timestamp in sec) and type
+pub async fn get_options_at_expiry(
+    currency: &str,
+    expiry: i64,
+    is_call: bool,
+) -> Result<Vec<String>> {
+    let options = http_rpc::<_, InstrumentsResponse>(
+        "public/get_instruments",
+        json!({"currency": currency, "instrument_type": "option", "expired": false}),
+        None,
+    )
+    .await?
+    .into_result()?
+    .result;
+    let expiry_options = options
+        .into_iter()
+        .filter(|r| r.is_active)
+        .filter(|r| {
+            r.option_details
+                .as_ref()
+                .is_some_and(|d| d.expiry == expiry && d.option_type.is_call() == is_call)
+        })
+        .map(|r| r.instrument_name)
+        .collect();
+    Ok(expiry_options)
+}
+
+/// Up to `count` options of the same expiry and type on each side of the option's strike,
+/// e.g. to interpolate its IV from the smile
+pub async fn get_adjacent_strikes(option_name: &str, count: usize) -> Result<Vec<String>> {
+    let currency = option_name.split('-').next().unwrap_or_default();
+    let options = http_rpc::<_, InstrumentsResponse>(
+        "public/get_instruments",
+        json!({"currency": currency, "instrument_type": "option", "expired": false}),
+        None,
+    )
+    .await?
+    .into_result()?
+    .result;
+    let option = options
+        .iter()
+        .find(|r| r.instrument_name == option_name)
+        .and_then(|r| r.option_details.clone())
+        .ok_or(Error::msg(format!("Option {} not found", option_name)))?;
+    let mut same_expiry = options
+        .iter()
+        .filter(|r| r.is_active && r.instrument_name != option_name)
+        .filter_map(|r| r.option_details.as_ref().map(|d| (r.instrument_name.clone(), d)))
+        .filter(|(_, d)| d.expiry == option.expiry && d.option_type == option.option_type)
+        .collect::<Vec<_>>();
+    same_expiry.sort_by(|a, b| a.1.strike.cmp(&b.1.strike));
+    let below = same_expiry.iter().filter(|(_, d)| d.strike < option.strike).rev().take(count);
+    let above = same_expiry.iter().filter(|(_, d)| d.strike > option.strike).take(count);
+    Ok(below.chain(above).map(|(name, _)| name.clone()).collect())
+}
+
 pub async fn sleep_till(start_timestamp: i64) {
     let sleep_sec = start_timestamp - Utc::now().timestamp();
     if sleep_sec > 0 {
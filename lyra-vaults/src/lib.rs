@@ -0,0 +1,9 @@
+pub mod basis;
+pub mod dn;
+pub mod helpers;
+pub mod longpp;
+pub mod lrtc;
+pub mod market;
+pub mod runner;
+pub mod shared;
+pub mod web3;
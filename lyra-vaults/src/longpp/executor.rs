@@ -1,4 +1,4 @@
-use crate::helpers::{fetch_ticker, get_option_expiry, sleep_till, sync_subaccount};
+use crate::helpers::{fetch_ticker, get_option_expiry, sleep_till, sync_vault_subaccounts};
 use crate::longpp::params::LongPPParams;
 use crate::longpp::selector::{maybe_select_from_positions, select_new_spread};
 use crate::longpp::stages::LongPPExecutorStage;
@@ -6,21 +6,29 @@ use crate::longpp::stages::LongPPExecutorStage::{
     AwaitSettlement, OptionAuction, SpotAuction, SpotOnly,
 };
 use crate::market::new_market_state;
-use crate::shared::auction::{LimitOrderAuction, LimitOrderAuctionExecutor};
+use crate::shared::auction::LimitOrderAuction;
+use crate::shared::reload::ParamsSection;
 use crate::shared::rfq::{RFQAuction, RFQAuctionExecutor};
-use crate::shared::stages::{ExecutorStage, TSACollateralOnly, TSAWaitForSettlement};
+use crate::shared::schedule::StageSchedule;
+use crate::shared::stages::{TSACollateralOnly, TSAWaitForSettlement};
+use crate::shared::twap::TwapExecutor;
+use crate::shared::vault::VaultStrategy;
 use anyhow::Result;
 use bigdecimal::{BigDecimal, Zero};
-use log::info;
 use orderbook_types::types::rfqs::LegUnpriced;
+use tracing::info;
 
+/// Long principal protected strategy, run with `VaultExecutor::new(LongPPExecutor::new(params))`
 pub struct LongPPExecutor {
     params: LongPPParams,
-    stage: LongPPExecutorStage,
 }
 
 impl LongPPExecutor {
-    /// Create a new LongPPExecutor inferring the state from the positions / market
+    pub fn new(params: LongPPParams) -> Self {
+        Self { params }
+    }
+
+    /// Infers the stage from the positions / market
     /// Cues for the state:
     /// - Spot Only MUST have no options and USDC < threshold and USDC >= -threshold
     /// - Option Auction has USDC >= 0 and # of options > 0 and expiry >= auction len
@@ -28,10 +36,9 @@ impl LongPPExecutor {
     /// - Spot Auction has no options and USDC < 0 or USDC > threshold
     /// Usually the executor will start in the Spot Only state, the other states are meant for
     /// recovery from hard crashes during e.g. spot or option auction
-    pub async fn new(params: LongPPParams) -> Result<Self> {
+    async fn infer_stage(params: LongPPParams) -> Result<LongPPExecutorStage> {
         let market = new_market_state();
-        let subaccount_id: i64 = std::env::var("SUBACCOUNT_ID").unwrap().parse()?;
-        sync_subaccount(market.clone(), subaccount_id, vec![]).await?;
+        sync_vault_subaccounts(market.clone(), vec![]).await?;
 
         let open_legs = maybe_select_from_positions(&market).await?;
         info!("Current option positions: {:?}", open_legs);
@@ -45,11 +52,10 @@ impl LongPPExecutor {
 
         if open_legs.is_none() && is_cash_within_threshold {
             info!("Starting in Spot Only stage");
-            return Ok(Self { params, stage: SpotOnly(TSACollateralOnly::new().await?) });
+            return Ok(SpotOnly(TSACollateralOnly::new().await?));
         } else if open_legs.is_none() && !is_cash_within_threshold {
             info!("Starting in Spot Auction stage");
-            let stage = LongPPExecutor::new_spot_auction_stage(params.clone()).await?;
-            return Ok(Self { params, stage });
+            return LongPPExecutor::new_spot_auction_stage(params.clone()).await;
         }
         let open_legs = open_legs.unwrap();
         let option_expiry = get_option_expiry(&open_legs[0].instrument_name).await?;
@@ -64,12 +70,10 @@ impl LongPPExecutor {
 
         return if is_still_ongoing && is_expiry_still_valid {
             info!("Starting in Option Auction stage");
-            let stage = LongPPExecutor::new_option_stage(params.clone(), open_legs).await?;
-            Ok(Self { params, stage })
+            LongPPExecutor::new_option_stage(params.clone(), open_legs).await
         } else {
             info!("Starting in Await Settlement stage");
-            let stage = LongPPExecutor::new_settlement_stage(params.clone(), open_legs).await?;
-            Ok(Self { params, stage })
+            LongPPExecutor::new_settlement_stage(params.clone(), open_legs).await
         };
     }
 
@@ -78,9 +82,11 @@ impl LongPPExecutor {
         legs: Vec<LegUnpriced>,
     ) -> Result<LongPPExecutorStage> {
         let option_names = legs.into_iter().map(|l| l.instrument_name).collect();
-        Ok(AwaitSettlement(
-            TSAWaitForSettlement::new(params.spot_auction_delay_min, option_names).await?,
-        ))
+        let mut stage =
+            TSAWaitForSettlement::new(params.spot_auction_delay_min, option_names).await?;
+        stage.schedule = params.schedule.clone();
+        stage.settlement_check = params.settlement_check.clone();
+        Ok(AwaitSettlement(stage))
     }
 
     pub async fn new_option_stage(
@@ -106,17 +112,22 @@ impl LongPPExecutor {
     pub async fn new_spot_auction_stage(params: LongPPParams) -> Result<LongPPExecutorStage> {
         // pass current time as start_sec to avoid querying the option expiry (which is not known yet)
         // spot auction always start after AwaitSettlement and it will ensure to wait for spot_auction_delay
-        let auction = LimitOrderAuction::new(
+        let mut auction = LimitOrderAuction::new(
             params.spot_instrument_name(),
             chrono::Utc::now().timestamp(),
             params.spot_auction_params.auction_sec,
             params.spot_auction_params.price_change_tolerance.clone(),
         )
         .await?;
-        let stage = SpotAuction(LimitOrderAuctionExecutor {
-            auction,
-            strategy: params.spot_auction_params.clone(),
-        });
+        auction.depth_limit = params.spot_auction_params.depth_limit.clone();
+        auction.orderbook_levels = params.spot_auction_params.pricing_orderbook_levels();
+        auction.reload_section = Some(ParamsSection::SpotAuction);
+        auction.candle_interval_sec = params.spot_auction_params.candle_interval_sec;
+        auction.max_slippage_bps = params.spot_auction_params.max_slippage_bps;
+        auction.on_timeout = params.spot_auction_params.on_timeout.clone();
+        auction.cancel_on_disconnect = params.spot_auction_params.cancel_on_disconnect;
+        auction.price_check = params.spot_auction_params.price_check.clone();
+        let stage = SpotAuction(TwapExecutor::new(auction, params.spot_auction_params.clone()));
         Ok(stage)
     }
 
@@ -140,9 +151,17 @@ impl LongPPExecutor {
         sleep_till(start_sec).await;
         Ok(())
     }
+}
+
+impl VaultStrategy for LongPPExecutor {
+    type Stage = LongPPExecutorStage;
+
+    async fn initial_stage(&self) -> Result<LongPPExecutorStage> {
+        LongPPExecutor::infer_stage(self.params.clone()).await
+    }
 
-    pub async fn next(&mut self) -> Result<()> {
-        self.stage = match &self.stage {
+    async fn next_stage(&self, completed: &LongPPExecutorStage) -> Result<LongPPExecutorStage> {
+        let stage = match completed {
             SpotOnly(_) => {
                 let legs = select_new_spread(&self.params).await;
                 match legs {
@@ -167,20 +186,14 @@ impl LongPPExecutor {
             }
             SpotAuction(_) => SpotOnly(TSACollateralOnly::new().await?),
         };
-        Ok(())
+        Ok(stage)
     }
 
-    pub async fn run(&mut self) -> Result<()> {
-        loop {
-            info!("Stage {:?} entered", self.stage);
-            match self.stage {
-                SpotOnly(ref mut stage) => stage.run_with_reconnect().await?,
-                OptionAuction(ref mut stage) => stage.run_with_reconnect().await?,
-                AwaitSettlement(ref mut stage) => stage.run_with_reconnect().await?,
-                SpotAuction(ref mut stage) => stage.run_with_reconnect().await?,
-            }
-            info!("Stage {:?} completed", self.stage);
-            self.next().await?;
-        }
+    fn is_epoch_start(&self, stage: &LongPPExecutorStage) -> bool {
+        matches!(stage, OptionAuction(_))
+    }
+
+    fn schedule(&self) -> Option<&StageSchedule> {
+        Some(&self.params.schedule)
     }
 }
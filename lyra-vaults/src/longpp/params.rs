@@ -1,15 +1,27 @@
+use crate::shared::fees::FeeBudgetParams;
+use crate::shared::instruments::InstrumentWatchParams;
+use crate::shared::margin::{MarginLimitParams, MarginMonitorParams};
 use crate::shared::params::{OptionRFQParams, SpotAuctionParams};
+use crate::shared::reconcile::ReconcileParams;
+use crate::shared::reload::ParamsReloadParams;
 use crate::shared::rfq::{RFQAuction, RFQLot, RFQStrategy};
+use crate::shared::risk::RiskLimits;
+use crate::shared::schedule::StageSchedule;
+use crate::shared::settlement::SettlementCheckParams;
+use crate::shared::subaccounts::SubaccountParams;
+use crate::shared::validation::{ParamsCheck, ValidateParams};
 use crate::web3::yields::{get_growth_between, get_price_at_timestamp};
 use anyhow::Result;
 use bigdecimal::num_traits::real::Real;
 use bigdecimal::RoundingMode::{Down, HalfEven};
 use bigdecimal::{BigDecimal, One, Zero};
-use log::info;
+use lyra_client::actions::HttpFallbackParams;
+use lyra_client::rfq_maker::RfqMakerParams;
 use rust_decimal::prelude::FromPrimitive;
 use serde::Deserialize;
 use std::env;
 use std::str::FromStr;
+use tracing::info;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct LongPPParams {
@@ -27,9 +39,47 @@ pub struct LongPPParams {
 
     pub spot_auction_delay_min: i64, // Min delay after expiry before starting spot auctions
     pub option_auction_delay_min: i64, // Min Delay after expiry before starting option auctions
+    // Start times of the auctions instead of the delays, e.g. {"option_auction": "fri 08:05 UTC"}
+    #[serde(default)]
+    pub schedule: StageSchedule,
 
     pub option_auction_params: OptionRFQParams,
     pub spot_auction_params: SpotAuctionParams,
+
+    // Halts the vault and cancels its orders while any of the limits is breached
+    pub risk_limits: Option<RiskLimits>,
+
+    // Alerts on the maintenance margin utilization and runs the emergency action above it
+    pub margin_monitor: Option<MarginMonitorParams>,
+
+    // Resizes or skips auction orders that would take the initial margin above a utilization
+    pub margin_limit: Option<MarginLimitParams>,
+
+    // Throttles and then halts the auction orders past a per epoch budget of trading fees
+    pub fee_budget: Option<FeeBudgetParams>,
+
+    // Resends the auction orders over HTTP while the websocket fails with transport errors
+    pub http_fallback: Option<HttpFallbackParams>,
+
+    // Compares the API balances with the on-chain subaccount, blocking auctions on a drift
+    pub reconcile: Option<ReconcileParams>,
+
+    // Reloads the spreads and tolerances of the running auctions when the params file changes
+    pub params_reload: Option<ParamsReloadParams>,
+
+    // Quotes incoming RFQs around the mark prices to earn the spread
+    pub rfq_maker: Option<RfqMakerParams>,
+
+    // Refreshes the instrument lists and ends the settlement wait once the options are delisted
+    pub instrument_watch: Option<InstrumentWatchParams>,
+
+    // Tolerances of the settlement cross-check with the on-chain settlement feed
+    #[serde(default)]
+    pub settlement_check: SettlementCheckParams,
+
+    // Option and spot subaccounts the orders are routed to, all on the TSA subaccount if unset
+    #[serde(default)]
+    pub subaccounts: SubaccountParams,
 }
 
 impl LongPPParams {
@@ -46,7 +96,8 @@ impl LongPPParams {
     }
 
     pub fn option_auction_start(&self, option_expiry: i64) -> i64 {
-        option_expiry - self.expiry_sec() + self.option_auction_delay_sec()
+        let previous_expiry = option_expiry - self.expiry_sec();
+        self.schedule.option_auction_start(previous_expiry, self.option_auction_delay_sec())
     }
 
     pub fn spot_instrument_name(&self) -> String {
@@ -89,3 +140,40 @@ impl RFQStrategy for OptionRFQParams {
         }
     }
 }
+
+impl ValidateParams for LongPPParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        check.at_least("expiry_days", &self.expiry_days, &1);
+        check.require(
+            self.min_expiry_hours < self.expiry_days * 24,
+            "min_expiry_hours",
+            &format!("= {} is not below expiry_days * 24", self.min_expiry_hours),
+        );
+        check.at_least("strike_diff", &self.strike_diff, &BigDecimal::zero());
+        check.not_above(
+            "min_premium_to_strike_ratio",
+            &self.min_premium_to_strike_ratio,
+            "target_premium_to_strike_ratio",
+            &self.target_premium_to_strike_ratio,
+        );
+        check.not_above(
+            "target_premium_to_strike_ratio",
+            &self.target_premium_to_strike_ratio,
+            "max_premium_to_strike_ratio",
+            &self.max_premium_to_strike_ratio,
+        );
+        check.at_least("spot_auction_delay_min", &self.spot_auction_delay_min, &0);
+        check.at_least("option_auction_delay_min", &self.option_auction_delay_min, &0);
+        check.nested("option_auction_params", &self.option_auction_params);
+        check.nested("spot_auction_params", &self.spot_auction_params);
+        if let Some(margin_limit) = &self.margin_limit {
+            check.nested("margin_limit", margin_limit);
+        }
+        if let Some(fee_budget) = &self.fee_budget {
+            check.nested("fee_budget", fee_budget);
+        }
+        if let Some(http_fallback) = &self.http_fallback {
+            check.nested("http_fallback", http_fallback);
+        }
+    }
+}
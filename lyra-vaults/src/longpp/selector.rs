@@ -6,7 +6,6 @@ use bigdecimal::{BigDecimal, Zero};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use log::{debug, error, info, warn};
 use lyra_client::auth::{load_signer, sign_auth_header};
 use lyra_client::json_rpc::{http_rpc, Notification, Response, WsClient, WsClientExt};
 use orderbook_types::types::orders::Direction;
@@ -16,16 +15,16 @@ use orderbook_types::types::tickers::result::{
 };
 use rust_decimal::prelude::One;
 use serde_json::{json, Value};
-use tokio::select;
+use tracing::{debug, error, info, warn};
 
-use crate::helpers::{get_expiry_options, subscribe_tickers, sync_subaccount, TickerInterval};
+use crate::helpers::{get_expiry_options, sync_subaccount, TickerInterval};
 use crate::longpp::params::LongPPParams;
 use crate::shared::rfq::get_legs_mark_unit_cost;
+use crate::shared::tickers::TickerManager;
 
 /// Returns the option name that satisfies the LRT-C params (target expiry and delta)
 pub async fn select_new_spread(params: &LongPPParams) -> Result<Vec<LegUnpriced>> {
     let market = new_market_state();
-    let client = WsClient::new_client().await?;
     let now = chrono::Utc::now().timestamp();
     let err = Error::msg("No options found within the LongPP params");
     let is_long = params.is_long;
@@ -38,11 +37,9 @@ pub async fn select_new_spread(params: &LongPPParams) -> Result<Vec<LegUnpriced>
     )
     .await?;
 
-    let sub = subscribe_tickers(market.clone(), expiry_options, TickerInterval::_1000Ms);
-    let _ = select! {
-        _ = sub => {},
-        _ = tokio::time::sleep(tokio::time::Duration::from_secs(3)) => {},
-    };
+    // a snapshot is enough to select from, no need to run the subscription
+    let tickers = TickerManager::new(market.clone(), TickerInterval::_1000Ms);
+    tickers.add(expiry_options).await?;
 
     let reader = market.read().await;
     let tickers = reader.get_tickers();
@@ -1,8 +1,8 @@
-use crate::shared::auction::LimitOrderAuctionExecutor;
 use crate::shared::params::OptionRFQParams;
-use crate::shared::params::SpotAuctionParams;
 use crate::shared::rfq::RFQAuctionExecutor;
-use crate::shared::stages::{TSACollateralOnly, TSAWaitForSettlement};
+use crate::shared::stages::{ExecutorStage, TSACollateralOnly, TSAWaitForSettlement};
+use crate::shared::twap::TwapExecutor;
+use anyhow::Result;
 use std::fmt::Debug;
 
 #[derive(Debug)]
@@ -10,5 +10,33 @@ pub enum LongPPExecutorStage {
     SpotOnly(TSACollateralOnly),
     OptionAuction(RFQAuctionExecutor<OptionRFQParams>),
     AwaitSettlement(TSAWaitForSettlement),
-    SpotAuction(LimitOrderAuctionExecutor<SpotAuctionParams>),
+    SpotAuction(TwapExecutor),
+}
+
+impl ExecutorStage for LongPPExecutorStage {
+    fn name(&self) -> String {
+        let name = match self {
+            LongPPExecutorStage::SpotOnly(_) => "SpotOnly",
+            LongPPExecutorStage::OptionAuction(_) => "OptionAuction",
+            LongPPExecutorStage::AwaitSettlement(_) => "AwaitSettlement",
+            LongPPExecutorStage::SpotAuction(_) => "SpotAuction",
+        };
+        name.to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        match self {
+            LongPPExecutorStage::SpotOnly(s) => s.run().await,
+            LongPPExecutorStage::OptionAuction(s) => s.run().await,
+            LongPPExecutorStage::AwaitSettlement(s) => s.run().await,
+            LongPPExecutorStage::SpotAuction(s) => s.run().await,
+        }
+    }
+    async fn reconnect(&mut self) -> Result<()> {
+        match self {
+            LongPPExecutorStage::SpotOnly(s) => s.reconnect().await,
+            LongPPExecutorStage::OptionAuction(s) => s.reconnect().await,
+            LongPPExecutorStage::AwaitSettlement(s) => s.reconnect().await,
+            LongPPExecutorStage::SpotAuction(s) => s.reconnect().await,
+        }
+    }
 }
@@ -1,53 +1,131 @@
-use crate::helpers::{fetch_ticker, get_option_expiry, sync_subaccount};
-use crate::lrtc::params::LRTCParams;
-use crate::lrtc::selector::{maybe_select_from_positions, select_new_option};
+use crate::helpers::{fetch_ticker, get_option_expiry, sleep_till, sync_vault_subaccounts};
+use crate::lrtc::option_auction::LadderRungStrategy;
+use crate::lrtc::params::{ExpiryRung, LRTCParams, OptionAuctionParams};
+use crate::lrtc::selector::{
+    maybe_select_from_positions, maybe_select_spread_from_positions, select_all_from_positions,
+    select_call_spread, select_new_ladder, select_new_option,
+};
 use crate::lrtc::stages::LRTCExecutorStage;
 use crate::lrtc::stages::LRTCExecutorStage::{
-    AwaitSettlement, OptionAuction, SpotAuction, SpotOnly,
+    AwaitSettlement, CollateralAuctions, ITMRoll, OptionAuction, OptionLadder, OptionRFQ,
+    SpotAuction, SpotOnly, TopupAuction, Withdrawals,
 };
 use crate::market::new_market_state;
-use crate::shared::auction::{LimitOrderAuction, LimitOrderAuctionExecutor};
-use crate::shared::stages::{ExecutorStage, TSACollateralOnly, TSAWaitForSettlement};
-use anyhow::Result;
+use crate::shared::alert::send_alert;
+use crate::shared::auction::{LimitOrderAuction, LimitOrderAuctionExecutor, MultiAuctionExecutor};
+use crate::shared::cash_yield::swap_idle_cash;
+use crate::shared::dry_run::DryRunRecorder;
+use crate::shared::reload::ParamsSection;
+use crate::shared::rfq::{RFQAuction, RFQAuctionExecutor};
+use crate::shared::schedule::StageSchedule;
+use crate::shared::spot_auction::CollateralSpotStrategy;
+use crate::shared::stages::{
+    ExecutorStage, TSACollateralOnly, TSAWaitForSettlement, TSAWithdrawals,
+};
+use crate::shared::state_store::{ExecutorSnapshot, StateStore};
+use crate::shared::twap::TwapExecutor;
+use crate::shared::vault::VaultStrategy;
+use anyhow::{Error, Result};
 use bigdecimal::{BigDecimal, Zero};
-use log::info;
+use orderbook_types::types::orders::Direction;
+use orderbook_types::types::rfqs::LegUnpriced;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{info, warn};
 
+/// LRTC vault strategy, run with `VaultExecutor::new(LRTCExecutor::new(params))`
 pub struct LRTCExecutor {
     params: LRTCParams,
-    stage: LRTCExecutorStage,
+    store: StateStore,
+    dry_run: Option<DryRunRecorder>,
+    resumed: AtomicBool, // the stage was resumed from the snapshot, its epoch already started
 }
 
 impl LRTCExecutor {
-    /// Create a new LRTCExecutor inferring the state from the positions / market
+    pub fn new(params: LRTCParams) -> Self {
+        // dry runs keep their own state so they never resume from (or clobber) the live one
+        let (store, dry_run) = match params.dry_run {
+            true => {
+                let store = StateStore::new(&format!("{}_dry_run", params.vault_name));
+                (store, Some(DryRunRecorder::new(params.dry_run_file.clone())))
+            }
+            false => (StateStore::new(&params.vault_name), None),
+        };
+        Self { params, store, dry_run, resumed: AtomicBool::new(false) }
+    }
+
+    /// Infers the stage from the positions / market
     /// Cues for the state:
     /// - Spot Only MUST have no options and USDC < threshold and USDC >= -threshold
     /// - Option Auction has USDC >= 0 and # of options > 0 and expiry >= auction len
     /// - Await Settlement has USDC >= 0 and # of options > 0 and expiry < auction len
-    /// - Spot Auction has no options and USDC < 0 or USDC > threshold
+    /// - Spot Auction has no options and USDC < 0 or USDC > threshold (calls only)
     /// Usually the executor will start in the Spot Only state, the other states are meant for
     /// recovery from hard crashes during e.g. spot or option auction
-    pub async fn new(params: LRTCParams) -> Result<Self> {
+    /// If a stored snapshot is consistent with the positions, the executor resumes from it instead.
+    /// Ladder (and strike split) vaults with open options always restart in Await Settlement,
+    /// cutting any interrupted ladder auctions short, and so do vaults interrupted mid ITM roll.
+    /// Spread vaults with open legs continue the RFQ auction of the same legs if it is likely to
+    /// still be ongoing.
+    async fn infer_stage(
+        params: LRTCParams,
+        store: &StateStore,
+        resumed: &AtomicBool,
+    ) -> Result<LRTCExecutorStage> {
+        resumed.store(false, Ordering::Relaxed);
         let market = new_market_state();
-        let subaccount_id: i64 = std::env::var("SUBACCOUNT_ID").unwrap().parse()?;
-        sync_subaccount(market.clone(), subaccount_id, vec![]).await?;
+        sync_vault_subaccounts(market.clone(), vec![]).await?;
+
+        let option_names = select_all_from_positions(&market).await;
+        if params.is_multi_option() && !option_names.is_empty() {
+            info!(options = ?option_names, "Starting in Await Settlement stage for the ladder");
+            return LRTCExecutor::new_ladder_settlement_stage(params.clone(), option_names).await;
+        }
+        // a roll interrupted mid-way leaves both calls open
+        if params.itm_roll.is_some() && !params.is_spread() && option_names.len() > 1 {
+            info!(options = ?option_names, "Starting in Await Settlement stage for the roll");
+            return LRTCExecutor::new_ladder_settlement_stage(params.clone(), option_names).await;
+        }
+
+        if params.is_spread() {
+            if let Some((short_name, long_name)) =
+                maybe_select_spread_from_positions(&market).await?
+            {
+                info!(short = %short_name, long = %long_name, "Current spread position");
+                return LRTCExecutor::infer_spread_stage(params, short_name, long_name).await;
+            }
+        }
 
         let option_name = maybe_select_from_positions(&market).await?;
-        info!("Current option position: {:?}", option_name);
+        info!(option = ?option_name, "Current option position");
+
+        store.cancel_stored_orders().await?;
+        if let Some(snapshot) = store.load().await? {
+            info!(?snapshot, "Stored executor state");
+            match LRTCExecutor::resume_stage(params.clone(), &snapshot, &option_name).await {
+                Ok(Some(stage)) => {
+                    info!(stage = %snapshot.stage, "Resuming from the stored stage");
+                    resumed.store(true, Ordering::Relaxed);
+                    return Ok(stage);
+                }
+                Ok(None) => info!("Stored state is stale, inferring stage from positions"),
+                Err(e) => warn!(error = format!("{:#}", e), "Failed to resume from stored state"),
+            }
+        }
 
         let reader = market.read().await;
         let cash_bal = reader.get_amount(&params.spot_auction_params.cash_name);
         drop(reader);
 
+        // cash-secured put vaults hold cash as collateral and never need a spot auction
         let is_cash_within_threshold =
-            params.spot_auction_params.is_cash_within_threshold(&cash_bal);
+            !params.is_call() || params.spot_auction_params.is_cash_within_threshold(&cash_bal);
 
         if option_name.is_none() && is_cash_within_threshold {
             info!("Starting in Spot Only stage");
-            return Ok(Self { params, stage: SpotOnly(TSACollateralOnly::new().await?) });
+            return Ok(SpotOnly(TSACollateralOnly::new().await?));
         } else if option_name.is_none() && !is_cash_within_threshold {
             info!("Starting in Spot Auction stage");
-            let stage = LRTCExecutor::new_spot_auction_stage(params.clone()).await?;
-            return Ok(Self { params, stage });
+            return LRTCExecutor::new_spot_auction_stage(params.clone()).await;
         }
         let option_name = option_name.unwrap();
 
@@ -60,128 +138,743 @@ impl LRTCExecutor {
         // if it is likely to still be ongoing
         let now = chrono::Utc::now().timestamp();
         let approx_auction_start = params.option_auction_start(option_expiry);
-        let is_still_ongoing =
-            now < approx_auction_start + params.option_auction_params.auction_sec;
+        let is_still_ongoing = now < approx_auction_start + params.option_auction_sec();
         let is_expiry_still_valid = option_expiry > now + params.min_expiry_sec();
 
         return if is_still_ongoing && is_expiry_still_valid {
             info!("Starting in Option Auction stage");
-            let stage = LRTCExecutor::new_option_stage(params.clone(), option_name).await?;
-            Ok(Self { params, stage })
+            LRTCExecutor::new_option_stage(params.clone(), option_name).await
         } else {
             info!("Starting in Await Settlement stage");
-            let stage = LRTCExecutor::new_settlement_stage(params.clone(), option_name).await?;
-            Ok(Self { params, stage })
+            LRTCExecutor::new_settlement_stage(params.clone(), option_name).await
         };
     }
 
+    /// RFQ auction of the open spread if it is likely to still be ongoing, else Await Settlement
+    async fn infer_spread_stage(
+        params: LRTCParams,
+        short_name: String,
+        long_name: String,
+    ) -> Result<LRTCExecutorStage> {
+        let option_expiry = get_option_expiry(&short_name).await?;
+        let now = chrono::Utc::now().timestamp();
+        let approx_auction_start = params.option_auction_start(option_expiry);
+        let is_still_ongoing = now < approx_auction_start + params.option_auction_sec();
+        let is_expiry_still_valid = option_expiry > now + params.min_expiry_sec();
+        if is_still_ongoing && is_expiry_still_valid {
+            info!("Starting in Option Auction stage for the spread");
+            let unit_legs = vec![
+                LegUnpriced {
+                    instrument_name: short_name,
+                    amount: BigDecimal::from(1),
+                    direction: Direction::Sell,
+                },
+                LegUnpriced {
+                    instrument_name: long_name,
+                    amount: BigDecimal::from(1),
+                    direction: Direction::Buy,
+                },
+            ];
+            return LRTCExecutor::new_rfq_stage(params, unit_legs).await;
+        }
+        info!("Starting in Await Settlement stage for the spread");
+        LRTCExecutor::new_ladder_settlement_stage(params, vec![short_name, long_name]).await
+    }
+
+    /// Rebuilds the stored stage if it is still consistent with the current option position.
+    /// Auctions keep their original start time so the spreads continue from where they were.
+    async fn resume_stage(
+        params: LRTCParams,
+        snapshot: &ExecutorSnapshot,
+        option_name: &Option<String>,
+    ) -> Result<Option<LRTCExecutorStage>> {
+        let now = chrono::Utc::now().timestamp();
+        let stage = match (snapshot.stage.as_str(), &snapshot.instrument_name) {
+            ("OptionAuction", Some(name)) => {
+                let start_sec = snapshot.auction_start_sec.unwrap_or(now);
+                let is_same_option = option_name.as_ref().map_or(true, |o| o == name);
+                let is_still_ongoing = now < start_sec + params.option_auction_sec();
+                if !is_same_option || !is_still_ongoing {
+                    return Ok(None);
+                }
+                let mut stage = LRTCExecutor::new_option_stage(params, name.clone()).await?;
+                match stage {
+                    OptionAuction(ref mut s) => {
+                        s.auction.start_timestamp_sec = start_sec;
+                        s.strategy.mini_auction = snapshot.mini_auction.or(s.strategy.mini_auction);
+                    }
+                    OptionRFQ(ref mut s) => s.auction.start_timestamp_sec = start_sec,
+                    _ => {}
+                }
+                stage
+            }
+            ("AwaitSettlement", Some(name)) if option_name.as_ref() == Some(name) => {
+                LRTCExecutor::new_settlement_stage(params, name.clone()).await?
+            }
+            ("SpotAuction", _) if option_name.is_none() => {
+                let mut stage = LRTCExecutor::new_spot_auction_stage(params).await?;
+                match (&mut stage, snapshot.auction_start_sec) {
+                    (SpotAuction(s), Some(start_sec)) => {
+                        s.executor.auction.start_timestamp_sec = start_sec
+                    }
+                    (CollateralAuctions(s), Some(start_sec)) => s
+                        .executors
+                        .iter_mut()
+                        .for_each(|e| e.auction.start_timestamp_sec = start_sec),
+                    _ => {}
+                }
+                stage
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(stage))
+    }
+
+    /// Writes the stage to the state store and lets auctions record their open orders
+    async fn persist_stage(&self, stage: &mut LRTCExecutorStage) -> Result<()> {
+        let mut snapshot = stage.snapshot();
+        // orders of the previous stage stay recorded until they are cancelled
+        if let Some(previous) = self.store.load().await? {
+            snapshot.open_orders = previous.open_orders;
+        }
+        self.store.save(&snapshot).await?;
+        match stage {
+            OptionAuction(s) | TopupAuction(s) => s.auction.state_store = Some(self.store.clone()),
+            SpotAuction(s) => s.executor.auction.state_store = Some(self.store.clone()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Moves the auctions of the stage to its subaccount if `subaccounts` routes the stage
+    fn route_stage(stage: &mut LRTCExecutorStage) -> Result<()> {
+        let name = stage.name();
+        match stage {
+            OptionAuction(s) | TopupAuction(s) => s.auction.route_to_stage(&name),
+            OptionLadder(s) => {
+                s.executors.iter_mut().try_for_each(|e| e.auction.route_to_stage(&name))
+            }
+            OptionRFQ(s) => s.auction.route_to_stage(&name),
+            ITMRoll(s) => s.auction.route_to_stage(&name),
+            SpotAuction(s) => s.executor.auction.route_to_stage(&name),
+            CollateralAuctions(s) => {
+                s.executors.iter_mut().try_for_each(|e| e.auction.route_to_stage(&name))
+            }
+            SpotOnly(_) | AwaitSettlement(_) | Withdrawals(_) => Ok(()),
+        }
+    }
+
+    /// Makes the stage log its orders / on-chain calls instead of sending them
+    fn attach_dry_run(&self, stage: &mut LRTCExecutorStage) {
+        let dry_run = self.dry_run.clone();
+        match stage {
+            SpotOnly(s) => s.dry_run = dry_run,
+            OptionAuction(s) => s.auction.dry_run = dry_run,
+            OptionRFQ(s) => s.auction.dry_run = dry_run,
+            OptionLadder(s) => {
+                s.executors.iter_mut().for_each(|e| e.auction.dry_run = dry_run.clone())
+            }
+            AwaitSettlement(s) => s.dry_run = dry_run,
+            TopupAuction(s) => s.auction.dry_run = dry_run,
+            ITMRoll(s) => s.auction.dry_run = dry_run,
+            SpotAuction(s) => s.executor.auction.dry_run = dry_run,
+            CollateralAuctions(s) => {
+                s.executors.iter_mut().for_each(|e| e.auction.dry_run = dry_run.clone())
+            }
+            Withdrawals(s) => s.dry_run = dry_run,
+        }
+    }
+
+    /// Returns funds to depositors first if withdrawals are queued, otherwise goes to Spot Only
+    pub async fn new_collateral_stage() -> Result<LRTCExecutorStage> {
+        match TSAWithdrawals::is_needed().await {
+            Ok(true) => return Ok(Withdrawals(TSAWithdrawals::new().await?)),
+            Ok(false) => {}
+            Err(e) => warn!(error = format!("{:#}", e), "Failed to check pending withdrawals"),
+        }
+        Ok(SpotOnly(TSACollateralOnly::new().await?))
+    }
+
+    /// Services the withdrawals requested while waiting for the auction start before the
+    /// collateral is sold against, None if none are pending. With `roll_option` the stage is
+    /// followed by the ITM roll of that call instead of the option auction.
+    pub async fn new_pre_auction_withdrawals_stage(
+        roll_option: Option<String>,
+    ) -> Result<Option<LRTCExecutorStage>> {
+        match TSAWithdrawals::is_needed().await {
+            Ok(true) => {
+                let mut stage = TSAWithdrawals::new().await?;
+                stage.before_auction = true;
+                stage.before_roll = roll_option;
+                Ok(Some(Withdrawals(stage)))
+            }
+            Ok(false) => Ok(None),
+            Err(e) => {
+                warn!(error = format!("{:#}", e), "Failed to check pending withdrawals");
+                Ok(None)
+            }
+        }
+    }
+
     pub async fn new_settlement_stage(
         params: LRTCParams,
         option_name: String,
     ) -> Result<LRTCExecutorStage> {
-        Ok(AwaitSettlement(
-            TSAWaitForSettlement::new(params.spot_auction_delay_min, vec![option_name]).await?,
-        ))
+        let mut stage =
+            TSAWaitForSettlement::new(params.spot_auction_delay_min, vec![option_name]).await?;
+        stage.schedule = params.schedule.clone();
+        stage.hedger = params.hedger_params.clone();
+        stage.settlement_check = params.settlement_check.clone();
+        stage.cash_yield = params.cash_yield.clone();
+        if params.is_call() {
+            stage.itm_roll = params.itm_roll.clone();
+        }
+        if !params.is_rfq() {
+            stage.min_topup_size = params.min_topup_size.clone();
+            stage.topup_until_sec = stage.option_expiry - params.min_expiry_sec();
+        }
+        Ok(AwaitSettlement(stage))
+    }
+
+    /// Sells the same option against the collateral deposited mid-epoch, starting the spreads
+    /// from scratch. The strategy only sells the collateral not yet covered by the position.
+    pub async fn new_topup_stage(
+        params: LRTCParams,
+        option_name: String,
+    ) -> Result<LRTCExecutorStage> {
+        let stage = LRTCExecutor::new_option_stage(params, option_name).await?;
+        let OptionAuction(mut s) = stage else {
+            return Err(Error::msg("Top-ups only support orderbook execution"));
+        };
+        s.auction.start_timestamp_sec = chrono::Utc::now().timestamp();
+        // top-ups may sell up to the whole epoch cap of the sizing
+        s.strategy.mini_auction = None;
+        Ok(TopupAuction(s))
+    }
+
+    /// Awaits the settlement of several options, i.e. the rungs of a ladder or the legs of a spread
+    pub async fn new_ladder_settlement_stage(
+        params: LRTCParams,
+        option_names: Vec<String>,
+    ) -> Result<LRTCExecutorStage> {
+        let mut stage =
+            TSAWaitForSettlement::new(params.spot_auction_delay_min, option_names).await?;
+        stage.schedule = params.schedule.clone();
+        stage.hedger = params.hedger_params.clone();
+        stage.settlement_check = params.settlement_check.clone();
+        stage.cash_yield = params.cash_yield.clone();
+        Ok(AwaitSettlement(stage))
     }
 
     pub async fn new_option_stage(
         params: LRTCParams,
         option_name: String,
     ) -> Result<LRTCExecutorStage> {
+        if params.is_rfq() {
+            return LRTCExecutor::new_option_rfq_stage(params, option_name).await;
+        }
+        if params.is_spread() {
+            return Err(Error::msg("Call spreads only support rfq execution"));
+        }
+        let option_expiry = get_option_expiry(&option_name).await?;
+        let start_sec = params.option_auction_start(option_expiry);
+        LRTCExecutor::new_option_auction_stage(params, option_name, start_sec, 0).await
+    }
+
+    /// Runs the next mini auction of the epoch's sizing after the gap, selling the next share of
+    /// the cap plus the remainder of the previous auctions. Awaits settlement instead if the
+    /// auction would end within `min_expiry_hours` of the expiry.
+    pub async fn new_next_mini_auction_stage(
+        params: LRTCParams,
+        completed: &OptionAuctionParams,
+        option_name: String,
+    ) -> Result<LRTCExecutorStage> {
+        let mini_auction = completed.mini_auction.unwrap_or_default() + 1;
+        let gap_sec = completed.sizing.as_ref().map_or(0, |s| s.mini_auction_gap_sec());
+        let start_sec = chrono::Utc::now().timestamp() + gap_sec;
         let option_expiry = get_option_expiry(&option_name).await?;
-        let auction = LimitOrderAuction::new(
+        let end_sec = start_sec + params.option_auction_params.auction_sec;
+        if end_sec > option_expiry - params.min_expiry_sec() {
+            info!(mini_auction, option = %option_name, "No time left for the mini auction");
+            return LRTCExecutor::new_settlement_stage(params, option_name).await;
+        }
+        info!(mini_auction, option = %option_name, start_sec, "Starting mini auction");
+        LRTCExecutor::new_option_auction_stage(params, option_name, start_sec, mini_auction).await
+    }
+
+    /// Orderbook auction of the option from `start_sec`, the `mini_auction`th of the epoch
+    async fn new_option_auction_stage(
+        params: LRTCParams,
+        option_name: String,
+        start_sec: i64,
+        mini_auction: u32,
+    ) -> Result<LRTCExecutorStage> {
+        let mut auction = LimitOrderAuction::new(
             option_name,
-            params.option_auction_start(option_expiry),
+            start_sec,
             params.option_auction_params.auction_sec,
             params.option_auction_params.price_change_tolerance.clone(),
         )
         .await?;
-        let stage = OptionAuction(LimitOrderAuctionExecutor {
-            auction,
-            strategy: params.option_auction_params.clone(),
-        });
+        auction.display_fraction = params.option_auction_params.display_fraction.clone();
+        auction.mmp = params.option_auction_params.mmp.clone();
+        auction.depth_limit = params.option_auction_params.depth_limit.clone();
+        auction.reload_section = Some(ParamsSection::OptionAuction);
+        auction.candle_interval_sec = params.option_auction_params.candle_interval_sec;
+        auction.max_slippage_bps = params.option_auction_params.max_slippage_bps;
+        auction.on_timeout = params.option_auction_params.on_timeout.clone();
+        auction.cancel_on_disconnect = params.option_auction_params.cancel_on_disconnect;
+        auction.reference_instruments =
+            params.option_auction_params.reference_instruments(&auction.instrument_name).await?;
+        let mut strategy =
+            params.option_auction_params.clone().with_realized_vol_spread(&auction).await;
+        strategy.mini_auction = Some(mini_auction);
+        let stage = OptionAuction(LimitOrderAuctionExecutor { auction, strategy });
         Ok(stage)
     }
 
+    /// Runs one option auction per ladder rung (or strike of a strike split) concurrently, each
+    /// selling its rung's weight of the collateral the others leave uncovered
+    pub async fn new_ladder_stage(
+        params: LRTCParams,
+        ladder: Vec<(String, ExpiryRung)>,
+    ) -> Result<LRTCExecutorStage> {
+        if params.is_rfq() {
+            return Err(Error::msg(
+                "Expiry ladders and strike splits only support orderbook execution",
+            ));
+        }
+        let auction_params = &params.option_auction_params;
+        let option_names = ladder.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        let mut executors = vec![];
+        for (option_name, rung) in ladder {
+            let siblings = option_names
+                .iter()
+                .filter(|name| **name != option_name)
+                .cloned()
+                .collect::<Vec<_>>();
+            let option_expiry = get_option_expiry(&option_name).await?;
+            let mut auction = LimitOrderAuction::new(
+                option_name,
+                params.rung_auction_start(option_expiry, rung.expiry_sec()),
+                auction_params.auction_sec,
+                auction_params.price_change_tolerance.clone(),
+            )
+            .await?;
+            auction.display_fraction = auction_params.display_fraction.clone();
+            auction.mmp = auction_params.mmp.clone();
+            auction.depth_limit = auction_params.depth_limit.clone();
+            auction.reload_section = Some(ParamsSection::OptionAuction);
+            auction.candle_interval_sec = auction_params.candle_interval_sec;
+            auction.max_slippage_bps = auction_params.max_slippage_bps;
+            auction.on_timeout = auction_params.on_timeout.clone();
+            auction.cancel_on_disconnect = auction_params.cancel_on_disconnect;
+            auction.reference_instruments =
+                auction_params.reference_instruments(&auction.instrument_name).await?;
+            // the siblings' strikes size the cash left to puts
+            for sibling in siblings.iter() {
+                if !auction.reference_instruments.contains(sibling) {
+                    auction.reference_instruments.push(sibling.clone());
+                }
+            }
+            let rung_params = auction_params.clone().with_realized_vol_spread(&auction).await;
+            let strategy =
+                LadderRungStrategy { params: rung_params, weight: rung.weight, siblings };
+            executors.push(LimitOrderAuctionExecutor { auction, strategy });
+        }
+        Ok(OptionLadder(MultiAuctionExecutor { executors }))
+    }
+
+    /// Sells the option via RFQs in lots instead of resting limit orders on the orderbook.
+    /// Quotes are accepted once the premium is within the current premium spread to mark.
+    /// Spread vaults sell the call spread of the option as a package instead.
+    pub async fn new_option_rfq_stage(
+        params: LRTCParams,
+        option_name: String,
+    ) -> Result<LRTCExecutorStage> {
+        let unit_legs = match params.is_spread() {
+            true => select_call_spread(&params, &option_name).await?,
+            false => vec![LegUnpriced {
+                instrument_name: option_name,
+                amount: BigDecimal::from(1),
+                direction: Direction::Sell,
+            }],
+        };
+        LRTCExecutor::new_rfq_stage(params, unit_legs).await
+    }
+
+    /// RFQ auction of the unit legs, the first being the short option the lots are sized by
+    async fn new_rfq_stage(
+        params: LRTCParams,
+        unit_legs: Vec<LegUnpriced>,
+    ) -> Result<LRTCExecutorStage> {
+        let rfq_params = params
+            .option_rfq_params
+            .clone()
+            .ok_or(Error::msg("option_rfq_params are required for rfq execution"))?;
+        if !params.is_call() || rfq_params.sizing_type != "covered" {
+            return Err(Error::msg("RFQ execution only supports covered calls"));
+        }
+        let option_expiry = get_option_expiry(&unit_legs[0].instrument_name).await?;
+        let auction = RFQAuction::new(
+            unit_legs,
+            params.option_auction_start(option_expiry),
+            rfq_params.lot_init_sleep_sec,
+            rfq_params.auction_sec,
+        )
+        .await?;
+        Ok(OptionRFQ(RFQAuctionExecutor { auction, strategy: rfq_params }))
+    }
+
+    /// RFQ package buying back the deep in-the-money short call and selling the option of the
+    /// next epoch, starting right away
+    pub async fn new_itm_roll_stage(
+        params: LRTCParams,
+        option_name: String,
+    ) -> Result<LRTCExecutorStage> {
+        let roll_params =
+            params.itm_roll.clone().ok_or(Error::msg("itm_roll params are required"))?;
+        let new_name = select_new_option(&params).await?;
+        if get_option_expiry(&new_name).await? <= get_option_expiry(&option_name).await? {
+            return Err(Error::msg(format!("No later expiry to roll {} into", option_name)));
+        }
+        info!(option = %option_name, new_option = %new_name, "Rolling option");
+        let unit_legs = vec![
+            LegUnpriced {
+                instrument_name: option_name,
+                amount: BigDecimal::from(1),
+                direction: Direction::Buy,
+            },
+            LegUnpriced {
+                instrument_name: new_name,
+                amount: BigDecimal::from(1),
+                direction: Direction::Sell,
+            },
+        ];
+        let auction = RFQAuction::new(
+            unit_legs,
+            chrono::Utc::now().timestamp(),
+            roll_params.lot_init_sleep_sec,
+            roll_params.auction_sec,
+        )
+        .await?;
+        Ok(ITMRoll(RFQAuctionExecutor { auction, strategy: roll_params }))
+    }
+
+    /// Awaits the settlement of the new call once the roll completed, or of both calls if
+    /// some of the short call is left unrolled
+    async fn new_rolled_settlement_stage(
+        params: LRTCParams,
+        option_names: Vec<String>,
+    ) -> Result<LRTCExecutorStage> {
+        let market = new_market_state();
+        sync_vault_subaccounts(market.clone(), vec![]).await?;
+        let open_names = select_all_from_positions(&market).await;
+        if open_names.contains(&option_names[0]) {
+            send_alert(&format!(
+                "ITM roll of {} incomplete, open: {:?}",
+                option_names[0], open_names
+            ))
+            .await;
+            return LRTCExecutor::new_ladder_settlement_stage(params, open_names).await;
+        }
+        LRTCExecutor::new_settlement_stage(params, option_names[1].clone()).await
+    }
+
     pub async fn new_spot_auction_stage(params: LRTCParams) -> Result<LRTCExecutorStage> {
+        if !params.option_auction_params.extra_collaterals.is_empty() {
+            return LRTCExecutor::new_collateral_auctions_stage(params).await;
+        }
         // pass current time as start_sec to avoid querying the option expiry (which is not known yet)
         // spot auction always start after AwaitSettlement and it will ensure to wait for spot_auction_delay
-        let auction = LimitOrderAuction::new(
+        let mut auction = LimitOrderAuction::new(
             params.spot_instrument_name(),
             chrono::Utc::now().timestamp(),
             params.spot_auction_params.auction_sec,
             params.spot_auction_params.price_change_tolerance.clone(),
         )
         .await?;
-        let stage = SpotAuction(LimitOrderAuctionExecutor {
-            auction,
-            strategy: params.spot_auction_params.clone(),
-        });
+        auction.depth_limit = params.spot_auction_params.depth_limit.clone();
+        auction.orderbook_levels = params.spot_auction_params.pricing_orderbook_levels();
+        auction.reload_section = Some(ParamsSection::SpotAuction);
+        auction.candle_interval_sec = params.spot_auction_params.candle_interval_sec;
+        auction.max_slippage_bps = params.spot_auction_params.max_slippage_bps;
+        auction.on_timeout = params.spot_auction_params.on_timeout.clone();
+        auction.cancel_on_disconnect = params.spot_auction_params.cancel_on_disconnect;
+        auction.price_check = params.spot_auction_params.price_check.clone();
+        let stage = SpotAuction(TwapExecutor::new(auction, params.spot_auction_params.clone()));
         Ok(stage)
     }
 
+    /// Runs one spot auction per collateral concurrently, see `CollateralSpotStrategy`
+    pub async fn new_collateral_auctions_stage(params: LRTCParams) -> Result<LRTCExecutorStage> {
+        let spot_params = &params.spot_auction_params;
+        let collateral_names = params.option_auction_params.collateral_names();
+        let mut executors = vec![];
+        for collateral_name in collateral_names.iter() {
+            let mut auction = LimitOrderAuction::new(
+                params.collateral_instrument_name(collateral_name),
+                chrono::Utc::now().timestamp(),
+                spot_params.auction_sec,
+                spot_params.price_change_tolerance.clone(),
+            )
+            .await?;
+            auction.depth_limit = spot_params.depth_limit.clone();
+            auction.orderbook_levels = spot_params.pricing_orderbook_levels();
+            auction.reload_section = Some(ParamsSection::SpotAuction);
+            auction.candle_interval_sec = spot_params.candle_interval_sec;
+            auction.max_slippage_bps = spot_params.max_slippage_bps;
+            auction.on_timeout = spot_params.on_timeout.clone();
+            auction.cancel_on_disconnect = spot_params.cancel_on_disconnect;
+            // the sources of the price check quote spot_name, the extra collaterals are unchecked
+            if collateral_name == &params.option_auction_params.spot_name {
+                auction.price_check = spot_params.price_check.clone();
+            }
+            let strategy = CollateralSpotStrategy {
+                params: spot_params.clone(),
+                collateral_name: collateral_name.clone(),
+                collateral_names: collateral_names.clone(),
+            };
+            executors.push(LimitOrderAuctionExecutor { auction, strategy });
+        }
+        Ok(CollateralAuctions(MultiAuctionExecutor { executors }))
+    }
+
     pub async fn select_new_option_until_success(&self) -> String {
         loop {
             match select_new_option(&self.params).await {
                 Ok(option_name) => return option_name,
                 Err(e) => {
-                    info!("select_new_option failed with {:#}, waiting for 60s", e);
+                    info!(error = format!("{:#}", e), "select_new_option failed, waiting for 60s");
+                    tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+                }
+            }
+        }
+    }
+
+    pub async fn select_new_ladder_until_success(&self) -> Vec<(String, ExpiryRung)> {
+        loop {
+            match select_new_ladder(&self.params).await {
+                Ok(ladder) => return ladder,
+                Err(e) => {
+                    info!(error = format!("{:#}", e), "select_new_ladder failed, waiting for 60s");
                     tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
                 }
             }
         }
     }
 
+    /// Option (or ladder) auction of the epoch, once its start time has come
+    async fn new_auction_stage(&self) -> Result<LRTCExecutorStage> {
+        if self.params.is_multi_option() {
+            let ladder = self.select_new_ladder_until_success().await;
+            return LRTCExecutor::new_ladder_stage(self.params.clone(), ladder).await;
+        }
+        let option_name = self.select_new_option_until_success().await;
+        LRTCExecutor::new_option_stage(self.params.clone(), option_name).await
+    }
+
+    /// The ITM roll of the short call, or its settlement without rolling if the roll fails
+    /// to start
+    async fn new_roll_stage(&self, option_name: String) -> Result<LRTCExecutorStage> {
+        match LRTCExecutor::new_itm_roll_stage(self.params.clone(), option_name.clone()).await {
+            Ok(stage) => Ok(stage),
+            Err(e) => {
+                let message = format!("ITM roll of {} failed to start: {:#}", option_name, e);
+                send_alert(&message).await;
+                // the settlement is awaited without rolling again
+                let mut params = self.params.clone();
+                params.itm_roll = None;
+                LRTCExecutor::new_settlement_stage(params, option_name).await
+            }
+        }
+    }
+
+    /// Sleeps until the earliest rung auction start
+    async fn await_ladder_auction_start(&self) -> Result<()> {
+        let ladder = self.select_new_ladder_until_success().await;
+        let mut start_sec = i64::MAX;
+        for (option_name, rung) in ladder.iter() {
+            let option_expiry = get_option_expiry(option_name).await?;
+            start_sec =
+                start_sec.min(self.params.rung_auction_start(option_expiry, rung.expiry_sec()));
+        }
+        sleep_till(start_sec).await;
+        Ok(())
+    }
+
     async fn await_option_auction_start(&self) -> Result<()> {
         let option_name = self.select_new_option_until_success().await;
         let option_expiry = get_option_expiry(&option_name).await?;
         let start_sec = self.params.option_auction_start(option_expiry);
         let sleep_sec = start_sec - chrono::Utc::now().timestamp();
         if sleep_sec > 0 {
-            info!("Executor await_option_auction_start sleep for {} sec", sleep_sec);
+            info!(sleep_sec, "Executor await_option_auction_start sleeping");
             tokio::time::sleep(tokio::time::Duration::from_secs(sleep_sec as u64)).await;
         }
         Ok(())
     }
+}
+
+impl VaultStrategy for LRTCExecutor {
+    type Stage = LRTCExecutorStage;
+
+    /// Unwinds a cash yield wrapper left by a restart, which would otherwise be stuck outside
+    /// of the settlement wait that unwinds it. A restart into the settlement wait keeps it, so
+    /// that the spread is not paid twice, and the stage is inferred again after an unwind.
+    async fn initial_stage(&self) -> Result<LRTCExecutorStage> {
+        let stage = LRTCExecutor::infer_stage(self.params.clone(), &self.store, &self.resumed);
+        let stage = stage.await?;
+        let Some(cash_yield) = &self.params.cash_yield else {
+            return Ok(stage);
+        };
+        if cash_yield.wrapper_name.is_none() || matches!(stage, AwaitSettlement(_)) {
+            return Ok(stage);
+        }
+        let cash_name = &self.params.spot_auction_params.cash_name;
+        swap_idle_cash(cash_yield, cash_name, Direction::Sell, self.dry_run.clone()).await?;
+        LRTCExecutor::infer_stage(self.params.clone(), &self.store, &self.resumed).await
+    }
 
-    pub async fn next(&mut self) -> Result<()> {
-        self.stage = match &self.stage {
+    async fn next_stage(&self, completed: &LRTCExecutorStage) -> Result<LRTCExecutorStage> {
+        self.resumed.store(false, Ordering::Relaxed);
+        let stage = match completed {
+            SpotOnly(_) if self.params.is_multi_option() => {
+                match select_new_ladder(&self.params).await {
+                    Ok(_) => {
+                        self.await_ladder_auction_start().await?;
+                        match LRTCExecutor::new_pre_auction_withdrawals_stage(None).await? {
+                            Some(stage) => stage,
+                            None => self.new_auction_stage().await?,
+                        }
+                    }
+                    Err(e) => {
+                        info!(
+                            error = format!("{:#}", e),
+                            "select_new_ladder failed, re-entering spot only stage"
+                        );
+                        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+                        SpotOnly(TSACollateralOnly::new().await?)
+                    }
+                }
+            }
             SpotOnly(_) => {
                 let option_name = select_new_option(&self.params).await;
                 match option_name {
                     Ok(_) => {
                         self.await_option_auction_start().await?;
-                        let option_name = self.select_new_option_until_success().await;
-                        LRTCExecutor::new_option_stage(self.params.clone(), option_name).await?
+                        match LRTCExecutor::new_pre_auction_withdrawals_stage(None).await? {
+                            Some(stage) => stage,
+                            None => self.new_auction_stage().await?,
+                        }
                     }
                     Err(e) => {
-                        info!("select_new_option failed with {:#}, re-entering spot only stage", e);
+                        info!(
+                            error = format!("{:#}", e),
+                            "select_new_option failed, re-entering spot only stage"
+                        );
                         tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
                         SpotOnly(TSACollateralOnly::new().await?)
                     }
                 }
             }
+            OptionAuction(ref s) if s.strategy.has_next_mini_auction() => {
+                let option_name = s.auction.instrument_name.clone();
+                let params = self.params.clone();
+                LRTCExecutor::new_next_mini_auction_stage(params, &s.strategy, option_name).await?
+            }
             OptionAuction(ref s) => {
                 let option_name = s.auction.instrument_name.clone();
                 LRTCExecutor::new_settlement_stage(self.params.clone(), option_name).await?
             }
+            OptionRFQ(ref s) if s.auction.unit_legs.len() > 1 => {
+                let option_names = s.auction.instrument_names();
+                LRTCExecutor::new_ladder_settlement_stage(self.params.clone(), option_names).await?
+            }
+            OptionRFQ(ref s) => {
+                let option_name = s.auction.unit_legs[0].instrument_name.clone();
+                LRTCExecutor::new_settlement_stage(self.params.clone(), option_name).await?
+            }
+            OptionLadder(ref s) => {
+                let option_names = s.instrument_names();
+                LRTCExecutor::new_ladder_settlement_stage(self.params.clone(), option_names).await?
+            }
+            AwaitSettlement(s) if s.topup_requested() => {
+                let option_name = s.option_names[0].clone();
+                LRTCExecutor::new_topup_stage(self.params.clone(), option_name).await?
+            }
+            TopupAuction(ref s) => {
+                let option_name = s.auction.instrument_name.clone();
+                LRTCExecutor::new_settlement_stage(self.params.clone(), option_name).await?
+            }
+            AwaitSettlement(s) if s.roll_requested() => {
+                // the roll sells the option of the next epoch, so withdrawals go first
+                let option_name = s.option_names[0].clone();
+                let roll_option = Some(option_name.clone());
+                match LRTCExecutor::new_pre_auction_withdrawals_stage(roll_option).await? {
+                    Some(stage) => stage,
+                    None => self.new_roll_stage(option_name).await?,
+                }
+            }
+            ITMRoll(ref s) => {
+                let option_names = s.auction.instrument_names();
+                LRTCExecutor::new_rolled_settlement_stage(self.params.clone(), option_names).await?
+            }
+            AwaitSettlement(_) if !self.params.is_call() => {
+                LRTCExecutor::new_collateral_stage().await?
+            }
             AwaitSettlement(_) => LRTCExecutor::new_spot_auction_stage(self.params.clone()).await?,
-            SpotAuction(_) => SpotOnly(TSACollateralOnly::new().await?),
+            SpotAuction(_) | CollateralAuctions(_) => LRTCExecutor::new_collateral_stage().await?,
+            Withdrawals(TSAWithdrawals { before_roll: Some(option_name), .. }) => {
+                self.new_roll_stage(option_name.clone()).await?
+            }
+            Withdrawals(s) if s.before_auction => self.new_auction_stage().await?,
+            Withdrawals(_) => SpotOnly(TSACollateralOnly::new().await?),
         };
+        Ok(stage)
+    }
+
+    async fn on_stage_entered(&self, stage: &mut LRTCExecutorStage) -> Result<()> {
+        LRTCExecutor::route_stage(stage)?;
+        self.persist_stage(stage).await?;
+        self.attach_dry_run(stage);
         Ok(())
     }
 
-    pub async fn run(&mut self) -> Result<()> {
-        loop {
-            info!("Stage {:?} entered", self.stage);
-            match self.stage {
-                SpotOnly(ref mut stage) => stage.run_with_reconnect().await?,
-                OptionAuction(ref mut stage) => stage.run_with_reconnect().await?,
-                AwaitSettlement(ref mut stage) => stage.run_with_reconnect().await?,
-                SpotAuction(ref mut stage) => stage.run_with_reconnect().await?,
-            }
-            info!("Stage {:?} completed", self.stage);
-            self.next().await?;
+    fn is_epoch_start(&self, stage: &LRTCExecutorStage) -> bool {
+        // a restart continues the epoch of the resumed stage
+        if self.resumed.load(Ordering::Relaxed) {
+            return false;
         }
+        match stage {
+            // later mini auctions continue the epoch of the first
+            OptionAuction(s) => s.strategy.mini_auction.unwrap_or_default() == 0,
+            // the rolled call starts the next epoch early
+            OptionRFQ(_) | OptionLadder(_) | ITMRoll(_) => true,
+            _ => false,
+        }
+    }
+
+    fn schedule(&self) -> Option<&StageSchedule> {
+        Some(&self.params.schedule)
+    }
+
+    fn yield_instruments(&self) -> Vec<String> {
+        self.params.yield_instruments()
+    }
+
+    /// The collateral stages, which need no option of the epoch
+    async fn named_stage(&self, name: &str) -> Result<Option<LRTCExecutorStage>> {
+        self.resumed.store(false, Ordering::Relaxed);
+        let stage = match name {
+            "SpotOnly" => SpotOnly(TSACollateralOnly::new().await?),
+            "Withdrawals" => Withdrawals(TSAWithdrawals::new().await?),
+            "SpotAuction" => LRTCExecutor::new_spot_auction_stage(self.params.clone()).await?,
+            "CollateralAuctions" => {
+                LRTCExecutor::new_collateral_auctions_stage(self.params.clone()).await?
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(stage))
     }
 }
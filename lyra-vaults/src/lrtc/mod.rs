@@ -1,5 +1,7 @@
 pub mod executor;
 pub mod option_auction;
 pub mod params;
+pub mod plan;
+pub mod roll;
 pub mod selector;
 pub mod stages;
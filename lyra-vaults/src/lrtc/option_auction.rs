@@ -1,23 +1,156 @@
-use crate::lrtc::params::OptionAuctionParams;
+use crate::helpers::{fetch_ticker, get_adjacent_strikes};
+use crate::lrtc::params::{AuctionSizing, OptionAuctionParams, PricingModelType};
 use crate::shared::auction::{LimitOrderAuction, OrderStrategy};
+use crate::shared::context::VaultContext;
+use crate::shared::stats::VolStats;
 use anyhow::{Error, Result};
-use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, ToPrimitive, Zero};
-use log::{debug, info};
+use bigdecimal::{BigDecimal, FromPrimitive, One, RoundingMode, ToPrimitive, Zero};
 use lyra_client::actions::Direction;
 use lyra_utils::black76::OptionContract;
-use orderbook_types::types::tickers::OptionType;
+use lyra_utils::pricing::{Black76, InterpolatedSmile, PricingModel, SmileQuote};
+use orderbook_types::types::tickers::{InstrumentTicker, OptionType};
+use tracing::{debug, info, warn};
 
-impl OrderStrategy for OptionAuctionParams {
-    async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal> {
-        let market = &auction.market;
-        let reader = market.read().await;
+/// Sells a fraction of the covered amount, one per expiry of an option ladder (or per strike of
+/// a strike split)
+#[derive(Debug, Clone)]
+pub struct LadderRungStrategy {
+    pub params: OptionAuctionParams,
+    pub weight: BigDecimal,
+    pub siblings: Vec<String>, // options of the other auctions, sold against the same collateral
+}
+
+impl LadderRungStrategy {
+    /// Caps the amount at the collateral the options of all auctions of the stage leave
+    /// uncovered, so that fills across the auctions never add up to more than it covers. Puts
+    /// convert the siblings' cash at their strikes, and sell nothing until their tickers arrive.
+    async fn cap_to_siblings(
+        &self,
+        auction: &LimitOrderAuction,
+        amount: BigDecimal,
+    ) -> Result<BigDecimal> {
+        if self.siblings.is_empty() || amount.is_zero() {
+            return Ok(amount);
+        }
+        let (_, uncovered) = self.params.get_remaining_amount(auction, &BigDecimal::one()).await?;
+        let reader = auction.market.read().await;
         let ticker =
             reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
         let details = ticker.option_details.as_ref().unwrap();
+        let mut sold = BigDecimal::zero();
+        for name in self.siblings.iter() {
+            let amount = -reader.get_amount(name);
+            sold += match details.option_type {
+                OptionType::C => amount,
+                OptionType::P => {
+                    let sibling = reader.get_ticker(name).and_then(|t| t.option_details.as_ref());
+                    let Some(sibling) = sibling else {
+                        warn!("OptionAuction waiting for the ticker of {}", name);
+                        return Ok(BigDecimal::zero());
+                    };
+                    amount * &sibling.strike / &details.strike
+                }
+            };
+        }
+        let left = (uncovered - sold)
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down);
+        if left < amount {
+            info!("OptionAuction caps {} at {} left by {:?}", amount, left, self.siblings);
+        }
+        if left < ticker.minimum_amount {
+            return Ok(BigDecimal::zero());
+        }
+        Ok(amount.min(left))
+    }
+}
+
+impl OptionAuctionParams {
+    /// Tickers the pricing model needs next to the option's, see `LimitOrderAuction`
+    pub async fn reference_instruments(&self, option_name: &str) -> Result<Vec<String>> {
+        match self.pricing_model {
+            PricingModelType::Black76 => Ok(vec![]),
+            PricingModelType::Smile => get_adjacent_strikes(option_name, self.smile_strikes).await,
+        }
+    }
+
+    /// Sets init_iv_spread to `iv_rv_fraction` of the IV-RV spread of the auction's option,
+    /// within 0 and max_iv_spread. Keeps the configured init_iv_spread if the stats fail.
+    pub async fn with_realized_vol_spread(mut self, auction: &LimitOrderAuction) -> Self {
+        let Some(rv_params) = self.realized_vol.clone() else {
+            return self;
+        };
+        match self.get_iv_rv_spread(auction, rv_params.lookback_sec(), rv_params.period_sec).await {
+            Ok(iv_rv_spread) => {
+                let spread =
+                    (rv_params.iv_rv_fraction * iv_rv_spread).clamp(0.0, self.max_iv_spread);
+                info!("OptionAuction init IV spread {} from IV-RV spread {}", spread, iv_rv_spread);
+                self.init_iv_spread = spread;
+            }
+            Err(e) => warn!("OptionAuction keeps init IV spread {}: {:#}", self.init_iv_spread, e),
+        }
+        self
+    }
+
+    async fn get_iv_rv_spread(
+        &self,
+        auction: &LimitOrderAuction,
+        lookback_sec: i64,
+        period_sec: i64,
+    ) -> Result<f64> {
+        fetch_ticker(auction.market.clone(), &auction.instrument_name).await?;
+        let reader = auction.market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let mark_iv = ticker.option_pricing.as_ref().and_then(|p| p.iv.to_f64());
+        drop(reader);
+        let currency = auction.instrument_name.split('-').next().unwrap_or_default();
+        let stats = VolStats::fetch(currency, lookback_sec, period_sec, mark_iv).await?;
+        stats.iv_rv_spread.ok_or(Error::msg("No mark IV for the IV-RV spread"))
+    }
+
+    /// Pricing model for the option, the smile falls back to the mark IV without fresh quotes
+    /// on both sides of the strike
+    fn pricing_model(
+        &self,
+        ticker: &InstrumentTicker,
+        references: &[&InstrumentTicker],
+        mark_iv: f64,
+    ) -> Box<dyn PricingModel> {
+        if self.pricing_model == PricingModelType::Black76 {
+            return Box::new(Black76 { iv: mark_iv });
+        }
+        let details = ticker.option_details.as_ref().unwrap();
+        let quotes = references
+            .iter()
+            .filter(|t| t.option_details.as_ref().is_some_and(|d| d.expiry == details.expiry))
+            .filter_map(|t| {
+                Some((&t.option_details.as_ref()?.strike, &t.option_pricing.as_ref()?.iv))
+            })
+            .filter_map(|(k, iv)| Some(SmileQuote { strike: k.to_f64()?, iv: iv.to_f64()? }))
+            .collect::<Vec<_>>();
+        let strike = details.strike.to_f64().unwrap_or_default();
+        let has_below = quotes.iter().any(|q| q.strike < strike);
+        let has_above = quotes.iter().any(|q| q.strike > strike);
+        match InterpolatedSmile::new(quotes) {
+            Ok(smile) if has_below && has_above => Box::new(smile),
+            _ => {
+                debug!("OptionAuction smile has no fresh strikes around {}, using mark IV", strike);
+                Box::new(Black76 { iv: mark_iv })
+            }
+        }
+    }
+
+    /// Black-76 price at the pricing model's IV minus the current auction IV spread.
+    /// `references` are the tickers of `reference_instruments`.
+    pub fn get_option_price(
+        &self,
+        ticker: &InstrumentTicker,
+        references: &[&InstrumentTicker],
+        start_timestamp_sec: i64,
+    ) -> Result<BigDecimal> {
+        let details = ticker.option_details.as_ref().unwrap();
         let pricing = ticker.option_pricing.as_ref().unwrap();
         let mark_iv: f64 = pricing.iv.to_f64().ok_or(Error::msg("IV cast to f64 failed"))?;
-        let spread = self.get_iv_spread(auction.start_timestamp_sec);
-        let iv = mark_iv * (1.0 - spread);
 
         let contract = OptionContract {
             strike: details.strike.to_f64().unwrap(),
@@ -26,8 +159,14 @@ impl OrderStrategy for OptionAuctionParams {
         };
 
         let fwd = pricing.forward_price.to_f64().ok_or(Error::msg("fwd cast to f64 failed"))?;
+        let model_iv = self.pricing_model(ticker, references, mark_iv).vol(&contract, fwd)?;
+        let spread = self.get_iv_spread(start_timestamp_sec);
+        let iv = model_iv * (1.0 - spread);
 
-        debug!("OptionAuction mark_iv, spread, iv, fwd: {}, {}, {}, {}", mark_iv, spread, iv, fwd);
+        debug!(
+            "OptionAuction mark_iv, model_iv, spread, iv, fwd: {}, {}, {}, {}, {}",
+            mark_iv, model_iv, spread, iv, fwd
+        );
 
         let price = contract.price(fwd, iv);
         let price = BigDecimal::from_f64(price)
@@ -37,10 +176,12 @@ impl OrderStrategy for OptionAuctionParams {
 
         Ok(price)
     }
-    async fn get_desired_amount(
+
+    /// Returns the remaining amount to sell to cover `weight` of the collateral
+    pub async fn get_remaining_amount(
         &self,
         auction: &LimitOrderAuction,
-        price: &BigDecimal,
+        weight: &BigDecimal,
     ) -> Result<(Direction, BigDecimal)> {
         if auction.remain_sec() <= 0 {
             return Ok((Direction::Sell, BigDecimal::zero()));
@@ -50,14 +191,23 @@ impl OrderStrategy for OptionAuctionParams {
         let reader = market.read().await;
         let ticker =
             reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
-        let lrt_pos = reader.get_position(&self.spot_name);
+        let details = ticker.option_details.as_ref().unwrap();
+        let collateral_names = self.collateral_names();
+        if collateral_names.iter().all(|name| reader.get_position(name).is_none()) {
+            return Err(Error::msg("Zero LRT position during option auction"));
+        }
         let option_pos = reader.get_position(&auction.instrument_name);
-        let amount = match (lrt_pos, option_pos) {
-            (Some(lrt_pos), Some(option_pos)) => lrt_pos.amount.clone() + option_pos.amount.clone(),
-            (Some(lrt_pos), None) => lrt_pos.amount.clone(),
-            _ => {
-                return Err(Error::msg("Zero LRT position during option auction"));
-            }
+        // collateral owed to pending withdrawals is left unsold, see `WithdrawalReserve`
+        let reserve = VaultContext::current().withdrawal_reserve.get();
+        // calls are covered 1:1 by all collaterals, puts are cash-secured at the strike
+        let covered_amount = match details.option_type {
+            OptionType::C => reader.get_total_amount(&collateral_names) - reserve,
+            OptionType::P => (reader.get_amount(&self.spot_name) - reserve) / &details.strike,
+        };
+        let covered_amount = covered_amount * weight;
+        let amount = match option_pos {
+            Some(option_pos) => covered_amount + option_pos.amount.clone(),
+            None => covered_amount,
         };
         let amount = amount
             .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down);
@@ -67,3 +217,120 @@ impl OrderStrategy for OptionAuctionParams {
         Ok((Direction::Sell, amount))
     }
 }
+
+impl AuctionSizing {
+    /// Options the epoch may sell in total, None if neither cap applies (e.g. zero vega)
+    pub fn epoch_cap(&self, ticker: &InstrumentTicker) -> Result<Option<BigDecimal>> {
+        let mut caps = vec![];
+        if let Some(max_vega) = &self.max_short_vega {
+            let pricing = ticker.option_pricing.as_ref().ok_or(Error::msg("No option pricing"))?;
+            if !pricing.vega.is_zero() {
+                caps.push(max_vega / pricing.vega.abs());
+            }
+        }
+        if let Some(max_notional) = &self.max_notional {
+            if !ticker.index_price.is_zero() {
+                caps.push(max_notional / &ticker.index_price);
+            }
+        }
+        Ok(caps.into_iter().reduce(|a, b| a.min(b)))
+    }
+
+    /// Options left to sell by the end of the mini auction given the option position (negative
+    /// when short), i.e. its share of the epoch cap plus the unsold shares of the previous ones
+    pub fn remaining_amount(
+        &self,
+        ticker: &InstrumentTicker,
+        option_amount: &BigDecimal,
+        mini_auction: Option<u32>,
+    ) -> Result<Option<BigDecimal>> {
+        let Some(cap) = self.epoch_cap(ticker)? else {
+            return Ok(None);
+        };
+        let auctions = self.mini_auctions.max(1);
+        let shares = mini_auction.map_or(auctions, |i| (i + 1).min(auctions));
+        let target = cap * BigDecimal::from(shares) / BigDecimal::from(auctions);
+        Ok(Some((target + option_amount).max(BigDecimal::zero())))
+    }
+
+    pub fn mini_auction_gap_sec(&self) -> i64 {
+        self.mini_auction_gap_min * 60
+    }
+}
+
+impl OptionAuctionParams {
+    /// Whether the executor should run another mini auction of the epoch after this one
+    pub fn has_next_mini_auction(&self) -> bool {
+        match (&self.sizing, self.mini_auction) {
+            (Some(sizing), Some(i)) => i + 1 < sizing.mini_auctions,
+            _ => false,
+        }
+    }
+
+    /// Caps the amount at what the sizing leaves to sell in this mini auction
+    async fn cap_to_sizing(
+        &self,
+        auction: &LimitOrderAuction,
+        amount: BigDecimal,
+    ) -> Result<BigDecimal> {
+        let Some(sizing) = &self.sizing else {
+            return Ok(amount);
+        };
+        let reader = auction.market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let option_amount = reader.get_amount(&auction.instrument_name);
+        let Some(remaining) = sizing.remaining_amount(ticker, &option_amount, self.mini_auction)?
+        else {
+            return Ok(amount);
+        };
+        let remaining = remaining
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down);
+        if remaining < amount {
+            info!("OptionAuction sizing caps {} at {}", amount, remaining);
+        }
+        if remaining < ticker.minimum_amount {
+            return Ok(BigDecimal::zero());
+        }
+        Ok(amount.min(remaining))
+    }
+}
+
+impl OrderStrategy for OptionAuctionParams {
+    async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal> {
+        let market = &auction.market;
+        let reader = market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let references = auction
+            .reference_instruments
+            .iter()
+            .filter_map(|name| reader.get_ticker(name))
+            .collect::<Vec<_>>();
+        self.get_option_price(ticker, &references, auction.start_timestamp_sec)
+    }
+    async fn get_desired_amount(
+        &self,
+        auction: &LimitOrderAuction,
+        price: &BigDecimal,
+    ) -> Result<(Direction, BigDecimal)> {
+        let (direction, amount) = self.get_remaining_amount(auction, &BigDecimal::one()).await?;
+        let amount = self.cap_to_sizing(auction, amount).await?;
+        Ok((direction, auction.cap_to_depth(&direction, amount).await?))
+    }
+}
+
+impl OrderStrategy for LadderRungStrategy {
+    async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal> {
+        self.params.get_desired_price(auction).await
+    }
+    async fn get_desired_amount(
+        &self,
+        auction: &LimitOrderAuction,
+        price: &BigDecimal,
+    ) -> Result<(Direction, BigDecimal)> {
+        let (direction, amount) = self.params.get_remaining_amount(auction, &self.weight).await?;
+        let amount = self.cap_to_siblings(auction, amount).await?;
+        Ok((direction, auction.cap_to_depth(&direction, amount).await?))
+    }
+}
@@ -1,17 +1,158 @@
-use crate::shared::params::SpotAuctionParams;
-use bigdecimal::BigDecimal;
+use crate::lrtc::roll::ITMRollParams;
+use crate::shared::cash_yield::CashYieldParams;
+use crate::shared::context::VaultContext;
+use crate::shared::fees::FeeBudgetParams;
+use crate::shared::hedger::HedgerParams;
+use crate::shared::instruments::InstrumentWatchParams;
+use crate::shared::margin::{MarginLimitParams, MarginMonitorParams};
+use crate::shared::params::{
+    default_cancel_on_disconnect, DepthLimitParams, ExecutionType, MmpParams, OptionRFQParams,
+    SpotAuctionParams, TimeoutPolicy,
+};
+use crate::shared::reconcile::ReconcileParams;
+use crate::shared::reload::{ParamsReloadParams, ParamsSection};
+use crate::shared::risk::RiskLimits;
+use crate::shared::schedule::StageSchedule;
+use crate::shared::settlement::SettlementCheckParams;
+use crate::shared::stats::RealizedVolParams;
+use crate::shared::subaccounts::SubaccountParams;
+use crate::shared::validation::{ParamsCheck, ValidateParams};
+use bigdecimal::{BigDecimal, One, Zero};
+use lyra_client::actions::HttpFallbackParams;
+use lyra_client::rfq_maker::RfqMakerParams;
+use orderbook_types::types::tickers::OptionType;
 use serde::Deserialize;
 use std::str::FromStr;
 
+/// How the option is picked among the options of the expiry, all modes stay below max_delta
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionMode {
+    /// Delta closest to target_delta
+    #[default]
+    Delta,
+    /// Strike closest to target_moneyness times the forward, e.g. 1.1 for calls 10% OTM
+    Moneyness,
+    /// Furthest OTM option whose annualized premium yield is at least min_premium_yield,
+    /// for when inflated IVs (e.g. around events) push the delta target too far out
+    Yield,
+}
+
+/// Fair value anchor of the option auction, the IV spread is taken off its vol
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PricingModelType {
+    /// Black-76 at the mark IV of the option
+    #[default]
+    Black76,
+    /// Black-76 at the IV interpolated from the mark IVs of adjacent strikes of the same expiry,
+    /// for when the option's own mark IV is stale
+    Smile,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct OptionAuctionParams {
     pub max_iv_spread: f64,
-    pub init_iv_spread: f64,
+    pub init_iv_spread: f64, // Also the fallback when the realized vol stats fail
+    // Starts at a fraction of the option's IV-RV spread instead of init_iv_spread if set
+    #[serde(default)]
+    pub realized_vol: Option<RealizedVolParams>,
     pub iv_spread_per_min: f64,
     pub auction_sec: i64,
     pub price_change_tolerance: BigDecimal,
+    #[serde(default)]
+    pub pricing_model: PricingModelType,
+    // Adjacent strikes subscribed on each side of the option for the smile model
+    #[serde(default = "default_smile_strikes")]
+    pub smile_strikes: usize,
+    // Iceberg: fraction of the option amount shown at a time (e.g. 0.2), replenished as it fills
+    #[serde(default)]
+    pub display_fraction: Option<BigDecimal>,
+    // Arms MMP before quoting and sends the auction orders with the mmp flag
+    #[serde(default)]
+    pub mmp: Option<MmpParams>,
+    // Caps the option orders at a fraction of the visible orderbook depth
+    #[serde(default)]
+    pub depth_limit: Option<DepthLimitParams>,
+    // Caps option orders crossing the book at this many bps from mark (skipped beyond that)
+    #[serde(default)]
+    pub max_slippage_bps: Option<u32>,
+    // Cancels the resting option orders when the connection drops, enabled by default
+    #[serde(default = "default_cancel_on_disconnect")]
+    pub cancel_on_disconnect: bool,
+    // Candles of the option's public trades in the execution report, e.g. 60 for 1m candles
+    #[serde(default)]
+    pub candle_interval_sec: Option<i64>,
+    // What the auction does once auction_sec passed, see `TimeoutPolicy`
+    #[serde(default)]
+    pub on_timeout: Option<TimeoutPolicy>,
+    // Caps the options sold per epoch by their short vega or notional next to the collateral
+    #[serde(default)]
+    pub sizing: Option<AuctionSizing>,
+    // Mini auction of the epoch the strategy runs in, None sells up to the whole epoch cap.
+    // Not part of the params, restored from the executor snapshot on a restart.
+    #[serde(skip)]
+    pub mini_auction: Option<u32>,
 
     pub spot_name: String,
+    // Other ERC-20 collaterals of the subaccount covering the calls (e.g. RSWETH next to
+    // WEETH), each sold in its own spot auction. They must share the spot_name underlying.
+    #[serde(default)]
+    pub extra_collaterals: Vec<String>,
+}
+
+/// Per-epoch cap of the options sold, the lower of `max_short_vega` / vega and
+/// `max_notional` / index price. The cap is split evenly over `mini_auctions` auctions
+/// `mini_auction_gap_min` apart, each selling its share plus what the previous ones left unsold.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuctionSizing {
+    pub max_short_vega: Option<BigDecimal>,
+    pub max_notional: Option<BigDecimal>, // in the quote currency
+    #[serde(default = "default_mini_auctions")]
+    pub mini_auctions: u32,
+    #[serde(default = "default_mini_auction_gap_min")]
+    pub mini_auction_gap_min: i64,
+}
+
+fn default_mini_auctions() -> u32 {
+    1
+}
+
+fn default_mini_auction_gap_min() -> i64 {
+    60
+}
+
+/// Long leg of the call spreads sold instead of naked calls. The long call is picked in the
+/// expiry of the short call, above its strike, with the delta closest to `long_delta`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CallSpreadParams {
+    pub long_delta: BigDecimal,
+}
+
+/// Sells the two strikes of the selected option's expiry whose deltas bracket target_delta
+/// instead of the closest one, weighted so that their combined delta is target_delta. A weight
+/// below `min_weight` (e.g. a target close to a listed strike) sells the closest option only.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StrikeSplitParams {
+    #[serde(default = "default_min_split_weight")]
+    pub min_weight: BigDecimal,
+}
+
+fn default_min_split_weight() -> BigDecimal {
+    BigDecimal::new(1.into(), 1)
+}
+
+/// One expiry of an option ladder, selling `weight` of the collateral at `expiry_days`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExpiryRung {
+    pub expiry_days: u64,
+    pub weight: BigDecimal,
+}
+
+impl ExpiryRung {
+    pub fn expiry_sec(&self) -> i64 {
+        self.expiry_days as i64 * 86400
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -19,18 +160,137 @@ pub struct LRTCParams {
     pub env: String,             // Environment name (e.g. staging, prod)
     pub vault_name: String,      // used as prefix for env vars, e.g. {vault_name}_TSA_ADDRESS
     pub option_currency: String, // Currency of the options (e.g. ETH)
+    #[serde(default = "default_option_type")]
+    pub option_type: OptionType, // C for covered calls, P for cash-secured puts (spot_name is cash)
     pub expiry_days: u64,
+    // Optional split of the collateral across expiries, e.g. 50% 7d + 50% 14d.
+    // The cycle settles once the longest expiry settles, expiry_days is ignored if set.
+    #[serde(default)]
+    pub expiry_ladder: Vec<ExpiryRung>,
     pub min_expiry_hours: u64, // Minimum expiry for options in hours, will remain in spot only stage until an option is available
     pub target_delta: BigDecimal,
     pub max_delta: BigDecimal,
+    #[serde(default)]
+    pub selection_mode: SelectionMode, // "delta" (default), "moneyness" or "yield"
+    pub target_moneyness: Option<BigDecimal>, // Strike / forward, required by "moneyness"
+    pub min_premium_yield: Option<BigDecimal>, // Mark / collateral, annualized, required by "yield"
     pub spot_auction_delay_min: i64, // Min delay after expiry before starting spot auctions
     pub option_auction_delay_min: i64, // Min Delay after expiry before starting option auctions
+    // Start times of the auctions instead of the delays, e.g. {"option_auction": "fri 08:05 UTC"}
+    #[serde(default)]
+    pub schedule: StageSchedule,
 
     pub option_auction_params: OptionAuctionParams,
     pub spot_auction_params: SpotAuctionParams,
+
+    #[serde(default)]
+    pub option_execution: ExecutionType, // "orderbook" (default) or "rfq"
+    pub option_rfq_params: Option<OptionRFQParams>, // required when option_execution is "rfq"
+    // Sell call spreads instead of naked calls, priced as a package via RFQ (rfq execution only)
+    pub call_spread: Option<CallSpreadParams>,
+    // Split the option across the two strikes around target_delta, delta selection and
+    // orderbook execution only
+    pub strike_split: Option<StrikeSplitParams>,
+
+    // Hedge the portfolio delta in the perp while awaiting settlement, the perp position is
+    // kept across cycles and re-adjusted in the next settlement wait
+    pub hedger_params: Option<HedgerParams>,
+
+    // Halts the vault and cancels its orders while any of the limits is breached
+    pub risk_limits: Option<RiskLimits>,
+
+    // Alerts on the maintenance margin utilization and runs the emergency action above it
+    pub margin_monitor: Option<MarginMonitorParams>,
+
+    // Resizes or skips auction orders that would take the initial margin above a utilization
+    pub margin_limit: Option<MarginLimitParams>,
+
+    // Throttles and then halts the auction orders past a per epoch budget of trading fees
+    pub fee_budget: Option<FeeBudgetParams>,
+
+    // Resends the auction orders over HTTP while the websocket fails with transport errors
+    pub http_fallback: Option<HttpFallbackParams>,
+
+    // Compares the API balances with the on-chain subaccount, blocking auctions on a drift
+    pub reconcile: Option<ReconcileParams>,
+
+    // Reloads the spreads and tolerances of the running auctions when the params file changes
+    pub params_reload: Option<ParamsReloadParams>,
+
+    // Quotes incoming RFQs around the mark prices to earn the spread
+    pub rfq_maker: Option<RfqMakerParams>,
+
+    // Refreshes the instrument lists and ends the settlement wait once the options are delisted
+    pub instrument_watch: Option<InstrumentWatchParams>,
+
+    // Tolerances of the settlement cross-check with the on-chain settlement feed
+    #[serde(default)]
+    pub settlement_check: SettlementCheckParams,
+
+    // Swaps the idle cash into a yield-bearing wrapper between settlement and the spot auction
+    pub cash_yield: Option<CashYieldParams>,
+
+    // Rolls a deep in-the-money short call into the next epoch's option via an RFQ package
+    // shortly before expiry instead of letting it settle (single calls only)
+    pub itm_roll: Option<ITMRollParams>,
+
+    // Option and spot subaccounts the orders are routed to, all on the TSA subaccount if unset
+    #[serde(default)]
+    pub subaccounts: SubaccountParams,
+
+    // Sells options against deposits of at least this size mid-epoch instead of leaving them
+    // idle until the next epoch, orderbook execution only
+    #[serde(default)]
+    pub min_topup_size: Option<BigDecimal>,
+
+    // Log orders, RFQs and on-chain calls instead of sending them, market data still runs live
+    #[serde(default)]
+    pub dry_run: bool,
+    pub dry_run_file: Option<String>, // optional JSON lines file to record dry run actions to
+}
+
+fn default_smile_strikes() -> usize {
+    2
+}
+
+fn default_option_type() -> OptionType {
+    OptionType::C
 }
 
 impl LRTCParams {
+    pub fn is_call(&self) -> bool {
+        self.option_type.is_call()
+    }
+
+    pub fn is_rfq(&self) -> bool {
+        self.option_execution == ExecutionType::Rfq
+    }
+
+    pub fn is_spread(&self) -> bool {
+        self.call_spread.is_some()
+    }
+
+    pub fn is_ladder(&self) -> bool {
+        !self.expiry_ladder.is_empty()
+    }
+
+    pub fn is_strike_split(&self) -> bool {
+        self.strike_split.is_some()
+    }
+
+    /// Ladders and strike splits sell several options per epoch, one auction each
+    pub fn is_multi_option(&self) -> bool {
+        self.is_ladder() || self.is_strike_split()
+    }
+
+    /// Auction length of the configured option execution venue
+    pub fn option_auction_sec(&self) -> i64 {
+        match (&self.option_execution, &self.option_rfq_params) {
+            (ExecutionType::Rfq, Some(rfq_params)) => rfq_params.auction_sec,
+            _ => self.option_auction_params.auction_sec,
+        }
+    }
+
     pub fn expiry_sec(&self) -> i64 {
         self.expiry_days as i64 * 86400
     }
@@ -48,27 +308,161 @@ impl LRTCParams {
     }
 
     pub fn spot_auction_start(&self, option_expiry: i64) -> i64 {
-        option_expiry - self.expiry_sec() + self.spot_auction_delay_sec()
+        let previous_expiry = option_expiry - self.expiry_sec();
+        self.schedule.spot_auction_start(previous_expiry, self.spot_auction_delay_sec())
     }
 
     pub fn option_auction_start(&self, option_expiry: i64) -> i64 {
-        option_expiry - self.expiry_sec() + self.option_auction_delay_sec()
+        self.rung_auction_start(option_expiry, self.expiry_sec())
+    }
+
+    pub fn rung_auction_start(&self, option_expiry: i64, expiry_sec: i64) -> i64 {
+        let previous_expiry = option_expiry - expiry_sec;
+        self.schedule.option_auction_start(previous_expiry, self.option_auction_delay_sec())
     }
 
     pub fn spot_instrument_name(&self) -> String {
-        let spot_name = &self.option_auction_params.spot_name;
+        self.collateral_instrument_name(&self.option_auction_params.spot_name)
+    }
+
+    pub fn collateral_instrument_name(&self, collateral_name: &str) -> String {
+        let cash_name = &self.spot_auction_params.cash_name;
+        format!("{}-{}", collateral_name, cash_name)
+    }
+
+    /// The wrapper the idle cash is swapped into, if any
+    pub fn yield_instruments(&self) -> Vec<String> {
         let cash_name = &self.spot_auction_params.cash_name;
-        format!("{}-{}", spot_name, cash_name)
+        let cash_yield = self.cash_yield.as_ref();
+        cash_yield.and_then(|c| c.wrapper_instrument_name(cash_name)).into_iter().collect()
     }
 }
 
 impl OptionAuctionParams {
     /// Returns an auction IV spread, starting from its init value and increasing per minute.
     /// Option selling auctions would subtract a spread, buying auctions would add a spread.
+    /// Values changed by a params reload take precedence.
     pub fn get_iv_spread(&self, start_timestamp_sec: i64) -> f64 {
+        let live = VaultContext::current().live_params.clone();
+        let field =
+            |name: &str, value: f64| live.f64(ParamsSection::OptionAuction, name).unwrap_or(value);
         let sec_since_start = chrono::Utc::now().timestamp() - start_timestamp_sec;
         let min_since_start = sec_since_start as f64 / 60.0;
-        let spread = self.init_iv_spread + min_since_start * self.iv_spread_per_min;
-        spread.min(self.max_iv_spread)
+        let spread = field("init_iv_spread", self.init_iv_spread)
+            + min_since_start * field("iv_spread_per_min", self.iv_spread_per_min);
+        spread.min(field("max_iv_spread", self.max_iv_spread))
+    }
+
+    /// spot_name first, followed by the extra collaterals
+    pub fn collateral_names(&self) -> Vec<String> {
+        let mut names = vec![self.spot_name.clone()];
+        names.extend(self.extra_collaterals.iter().cloned());
+        names
+    }
+}
+
+impl ValidateParams for LRTCParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        let (zero, one) = (BigDecimal::zero(), BigDecimal::one());
+        if self.is_ladder() {
+            let mut total_weight = BigDecimal::zero();
+            for (i, rung) in self.expiry_ladder.iter().enumerate() {
+                check.at_least(&format!("expiry_ladder[{}].expiry_days", i), &rung.expiry_days, &1);
+                check.above(&format!("expiry_ladder[{}].weight", i), &rung.weight, &zero);
+                total_weight += &rung.weight;
+            }
+            check.require(total_weight <= one, "expiry_ladder", "weights add up to more than 1");
+        } else {
+            check.at_least("expiry_days", &self.expiry_days, &1);
+            check.require(
+                self.min_expiry_hours < self.expiry_days * 24,
+                "min_expiry_hours",
+                &format!("= {} is not below expiry_days * 24", self.min_expiry_hours),
+            );
+        }
+        check.above("max_delta", &self.max_delta, &zero);
+        check.at_most("max_delta", &self.max_delta, &one);
+        check.at_least("target_delta", &self.target_delta, &zero);
+        check.not_above("target_delta", &self.target_delta, "max_delta", &self.max_delta);
+        match self.selection_mode {
+            SelectionMode::Delta => {}
+            SelectionMode::Moneyness => check.require(
+                self.target_moneyness.as_ref().is_some_and(|m| m > &zero),
+                "target_moneyness",
+                "must be set above 0 by the moneyness selection mode",
+            ),
+            SelectionMode::Yield => check.require(
+                self.min_premium_yield.is_some(),
+                "min_premium_yield",
+                "must be set by the yield selection mode",
+            ),
+        }
+        check.at_least("spot_auction_delay_min", &self.spot_auction_delay_min, &0);
+        check.at_least("option_auction_delay_min", &self.option_auction_delay_min, &0);
+
+        check.nested("option_auction_params", &self.option_auction_params);
+        check.nested("spot_auction_params", &self.spot_auction_params);
+        match &self.option_rfq_params {
+            Some(rfq_params) => check.nested("option_rfq_params", rfq_params),
+            None => check.require(
+                !self.is_rfq(),
+                "option_rfq_params",
+                "must be set by the rfq option_execution",
+            ),
+        }
+        check.require(
+            !self.is_spread() || self.is_rfq(),
+            "call_spread",
+            "needs the rfq option_execution",
+        );
+        if let Some(split) = &self.strike_split {
+            check.require(
+                self.selection_mode == SelectionMode::Delta && !self.is_rfq() && !self.is_ladder(),
+                "strike_split",
+                "needs the delta selection mode and orderbook execution, without expiry_ladder",
+            );
+            check.at_least("strike_split.min_weight", &split.min_weight, &zero);
+            let half = BigDecimal::new(5.into(), 1);
+            check.at_most("strike_split.min_weight", &split.min_weight, &half);
+        }
+        if let Some(margin_limit) = &self.margin_limit {
+            check.nested("margin_limit", margin_limit);
+        }
+        if let Some(fee_budget) = &self.fee_budget {
+            check.nested("fee_budget", fee_budget);
+        }
+        if let Some(http_fallback) = &self.http_fallback {
+            check.nested("http_fallback", http_fallback);
+        }
+    }
+}
+
+impl ValidateParams for OptionAuctionParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        // a negative initial spread starts the auction through the mark
+        check.at_least("init_iv_spread", &self.init_iv_spread, &-self.max_iv_spread);
+        check.fraction("max_iv_spread", self.max_iv_spread);
+        check.not_above(
+            "init_iv_spread",
+            &self.init_iv_spread,
+            "max_iv_spread",
+            &self.max_iv_spread,
+        );
+        check.at_least("iv_spread_per_min", &self.iv_spread_per_min, &0.0);
+        check.at_least("auction_sec", &self.auction_sec, &1);
+        check.at_least("price_change_tolerance", &self.price_change_tolerance, &BigDecimal::zero());
+        if let Some(fraction) = &self.display_fraction {
+            check.above("display_fraction", fraction, &BigDecimal::zero());
+            check.at_most("display_fraction", fraction, &BigDecimal::one());
+        }
+        if let Some(realized_vol) = &self.realized_vol {
+            check.fraction("realized_vol.iv_rv_fraction", realized_vol.iv_rv_fraction);
+        }
+        if let Some(sizing) = &self.sizing {
+            check.at_least("sizing.mini_auctions", &sizing.mini_auctions, &1);
+        }
+        if let Some(depth_limit) = &self.depth_limit {
+            check.nested("depth_limit", depth_limit);
+        }
     }
 }
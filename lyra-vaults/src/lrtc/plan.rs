@@ -0,0 +1,108 @@
+use crate::helpers::sync_subaccounts;
+use crate::lrtc::params::LRTCParams;
+use crate::lrtc::selector::select_new_option;
+use crate::market::new_market_state;
+use crate::shared::context::VaultContext;
+use crate::shared::margin::{preview_order_margin, MarginPreview};
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use lyra_client::tickers::TickerCache;
+use orderbook_types::types::orders::Direction;
+use orderbook_types::types::tickers::OptionType;
+use serde::Serialize;
+use tracing::warn;
+
+/// Next epoch of an LRTC (or DN) vault as the executor would run it at the current market,
+/// for a check of the params before the roll. Assumes the previous epoch settled, i.e. the
+/// options sold are covered by all of the current collateral.
+#[derive(Debug, Serialize)]
+pub struct EpochPlan {
+    pub vault_name: String,
+    pub option_name: String,
+    pub option_expiry_sec: i64,
+    pub covered_amount: BigDecimal, // collateral, or cash over the strike for puts
+    pub epoch_cap: Option<BigDecimal>, // of the sizing, None without one
+    pub target_amount: BigDecimal,  // options sold by the epoch's auctions
+    pub mark_price: BigDecimal,
+    pub mark_iv: BigDecimal,
+    pub init_price: BigDecimal, // auction price at init_iv_spread below the model IV
+    pub floor_price: BigDecimal, // auction price once the spread reached max_iv_spread
+    pub expected_premium: BigDecimal, // target amount at the init price
+    pub min_premium: BigDecimal, // target amount at the floor price
+    pub option_auction_start_sec: i64,
+    pub option_auction_end_sec: i64, // of the first auction if the sizing has mini auctions
+    pub mini_auctions: u32,
+    pub spot_auction_start_sec: i64,
+    pub margin: Option<MarginPreview>, // of selling the target amount, None if it failed
+}
+
+/// Selects the option of the next epoch and projects its size, premium, schedule and margin
+pub async fn project_epoch(params: &LRTCParams) -> Result<EpochPlan> {
+    if params.is_multi_option() || params.is_spread() {
+        return Err(Error::msg("Epoch plans only cover single option epochs"));
+    }
+    let option_name = select_new_option(params).await?;
+    let ticker = TickerCache::global().get(&option_name).await?;
+    let details = ticker.option_details.clone().ok_or(Error::msg("Not an option"))?;
+    let pricing = ticker.option_pricing.clone().ok_or(Error::msg("No option pricing"))?;
+
+    // sized against the collateral of the subaccount the option is sold from
+    let stage = if params.is_rfq() { "OptionRFQ" } else { "OptionAuction" };
+    let (subaccount_id, subaccount_ids) =
+        VaultContext::current().stage_subaccount_ids(stage, &option_name)?;
+    let market = new_market_state();
+    sync_subaccounts(market.clone(), &subaccount_ids, vec![]).await?;
+    let auction_params = &params.option_auction_params;
+    let reader = market.read().await;
+    let covered_amount = match details.option_type {
+        OptionType::C => reader.get_total_amount(&auction_params.collateral_names()),
+        OptionType::P => reader.get_amount(&auction_params.spot_name) / &details.strike,
+    };
+    drop(reader);
+
+    let epoch_cap = match &auction_params.sizing {
+        Some(sizing) => sizing.epoch_cap(&ticker)?,
+        None => None,
+    };
+    let target_amount = epoch_cap
+        .clone()
+        .map_or(covered_amount.clone(), |cap| cap.min(covered_amount.clone()))
+        .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down)
+        .max(BigDecimal::zero());
+
+    let now = chrono::Utc::now().timestamp();
+    let init_price = auction_params.get_option_price(&ticker, &[], now)?;
+    let mut floor_params = auction_params.clone();
+    floor_params.init_iv_spread = floor_params.max_iv_spread;
+    let floor_price = floor_params.get_option_price(&ticker, &[], now)?;
+
+    let option_auction_start_sec = params.option_auction_start(details.expiry);
+    let margin = match target_amount.is_zero() {
+        true => None,
+        false => {
+            let preview =
+                preview_order_margin(subaccount_id, &option_name, Direction::Sell, &target_amount)
+                    .await;
+            preview.map_err(|e| warn!("Margin preview of the plan failed with {:#}", e)).ok()
+        }
+    };
+    Ok(EpochPlan {
+        vault_name: params.vault_name.clone(),
+        option_name,
+        option_expiry_sec: details.expiry,
+        covered_amount,
+        epoch_cap,
+        expected_premium: &init_price * &target_amount,
+        min_premium: &floor_price * &target_amount,
+        target_amount,
+        mark_price: ticker.mark_price.clone(),
+        mark_iv: pricing.iv,
+        init_price,
+        floor_price,
+        option_auction_start_sec,
+        option_auction_end_sec: option_auction_start_sec + auction_params.auction_sec,
+        mini_auctions: auction_params.sizing.as_ref().map_or(1, |s| s.mini_auctions.max(1)),
+        spot_auction_start_sec: params.spot_auction_start(details.expiry),
+        margin,
+    })
+}
@@ -0,0 +1,80 @@
+use crate::shared::rfq::{RFQAuction, RFQStrategy};
+use anyhow::Result;
+use bigdecimal::RoundingMode::{Down, HalfEven};
+use bigdecimal::{BigDecimal, FromPrimitive, One, Zero};
+use serde::Deserialize;
+use tracing::info;
+
+/// Roll of a deep in-the-money short call into the option of the next epoch before expiry,
+/// instead of letting it settle. Sent as an RFQ package buying back the short call and selling
+/// the new one, so the package usually costs a net debit.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ITMRollParams {
+    pub trigger_moneyness: BigDecimal, // Rolls once strike / forward is at or below, e.g. 0.95
+    pub hours_before_expiry: u64,      // Moneyness is only checked this close to expiry
+    pub init_premium_spread: f64,
+    pub premium_spread_per_min: f64,
+    pub max_premium_spread: f64,
+    pub max_unit_cost: BigDecimal, // Max net debit per rolled call, in the quote currency
+    pub lot_size: BigDecimal,
+    pub lot_rounding: BigDecimal,
+    #[serde(default = "default_lot_init_sleep_sec")]
+    pub lot_init_sleep_sec: u64,
+    pub auction_sec: i64,
+}
+
+fn default_lot_init_sleep_sec() -> u64 {
+    5
+}
+
+impl ITMRollParams {
+    /// The roll window before expiry, closing once the roll auction would no longer finish
+    pub fn is_in_window(&self, option_expiry: i64, now: i64) -> bool {
+        let window_start = option_expiry - self.hours_before_expiry as i64 * 3600;
+        now >= window_start && now < option_expiry - self.auction_sec
+    }
+
+    pub fn is_triggered(&self, moneyness: &BigDecimal) -> bool {
+        moneyness <= &self.trigger_moneyness
+    }
+
+    fn get_premium_spread(&self, start_timestamp_sec: i64) -> f64 {
+        let min_since_start = (chrono::Utc::now().timestamp() - start_timestamp_sec) as f64 / 60.0;
+        let spread = self.init_premium_spread + min_since_start * self.premium_spread_per_min;
+        spread.min(self.max_premium_spread)
+    }
+}
+
+/// Unit legs are the short call bought back first and the new call sold second. Lots are
+/// sized by what is left of the short call, so the auction stops once it is fully rolled.
+impl RFQStrategy for ITMRollParams {
+    async fn get_desired_unit_cost(
+        &self,
+        auction: &RFQAuction,
+        start_sec: i64,
+    ) -> Result<BigDecimal> {
+        let spread = self.get_premium_spread(start_sec);
+        let mark = auction.get_mark_unit_cost().await?;
+        let factor = BigDecimal::from_f64(1.0 + spread).unwrap();
+        let factor = if mark < BigDecimal::zero() { BigDecimal::one() / factor } else { factor };
+        let cost = (mark * factor).min(self.max_unit_cost.clone());
+        Ok(cost.with_scale_round(6, HalfEven))
+    }
+
+    async fn get_desired_lot_size(
+        &self,
+        auction: &RFQAuction,
+        _unit_cost: &BigDecimal,
+    ) -> Result<BigDecimal> {
+        let reader = auction.market.read().await;
+        let short_name = &auction.unit_legs[0].instrument_name;
+        let remaining = match reader.get_position(short_name) {
+            Some(pos) if pos.amount < BigDecimal::zero() => pos.amount.abs(),
+            _ => BigDecimal::zero(),
+        };
+        let num_rounded = (&remaining / &self.lot_rounding).with_scale_round(0, Down);
+        let lot_size = (num_rounded * &self.lot_rounding).min(self.lot_size.clone());
+        info!("ITM roll remaining: {}, lot_size: {}", remaining, lot_size);
+        Ok(lot_size)
+    }
+}
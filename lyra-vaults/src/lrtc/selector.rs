@@ -1,76 +1,272 @@
-use crate::lrtc::params::LRTCParams;
+use crate::lrtc::params::{ExpiryRung, LRTCParams, SelectionMode};
 use crate::market::{new_market_state, MarketState};
 use anyhow::{Error, Result};
-use bigdecimal::{BigDecimal, Zero};
+use bigdecimal::{BigDecimal, One, Zero};
 use serde::{Deserialize, Serialize};
 
-use log::{debug, error, info, warn};
 use lyra_client::auth::{load_signer, sign_auth_header};
 use lyra_client::json_rpc::{http_rpc, Notification, Response, WsClient, WsClientExt};
+use lyra_utils::black76::SEC_PER_YEAR;
+use orderbook_types::types::orders::Direction;
+use orderbook_types::types::rfqs::LegUnpriced;
 use orderbook_types::types::tickers::result::{
     InstrumentTicker, InstrumentsResponse, OptionType, TickerNotificationData,
 };
 use serde_json::{json, Value};
-use tokio::select;
+use tracing::{debug, error, info, warn};
 
-use crate::helpers::{get_expiry_options, subscribe_tickers, sync_subaccount, TickerInterval};
+use crate::helpers::{
+    get_expiry_options, get_option_expiry, get_options_at_expiry, sync_subaccount, TickerInterval,
+};
+use crate::shared::tickers::TickerManager;
 
-/// Returns the option name that satisfies the LRT-C params (target expiry and delta)
+/// Returns the option name that satisfies the LRT-C params (target expiry and selection mode)
+/// Deltas are compared in absolute terms so that the same params work for puts
 pub async fn select_new_option(params: &LRTCParams) -> Result<String> {
+    select_option_for_expiry(params, params.expiry_sec()).await
+}
+
+/// Returns one option per rung of the expiry ladder together with its rung.
+/// Rungs that resolve to the same option are merged by adding up their weights.
+/// Strike splits return their strikes as rungs of the epoch's expiry, see `select_strike_split`.
+pub async fn select_new_ladder(params: &LRTCParams) -> Result<Vec<(String, ExpiryRung)>> {
+    if params.is_strike_split() {
+        return select_strike_split(params).await;
+    }
+    let mut selected: Vec<(String, ExpiryRung)> = vec![];
+    for rung in params.expiry_ladder.iter() {
+        let option_name = select_option_for_expiry(params, rung.expiry_sec()).await?;
+        match selected.iter_mut().find(|(name, _)| name == &option_name) {
+            Some((_, existing)) => {
+                warn!("Ladder rungs merged into a single option {}", option_name);
+                existing.weight += rung.weight.clone();
+            }
+            None => selected.push((option_name, rung.clone())),
+        }
+    }
+    info!("Selected option ladder: {:?}", selected);
+    Ok(selected)
+}
+
+async fn select_option_for_expiry(params: &LRTCParams, max_expiry_sec: i64) -> Result<String> {
     let market = new_market_state();
-    let client = WsClient::new_client().await?;
     let now = chrono::Utc::now().timestamp();
     let err = Error::msg("No options found within the LRTC params");
 
     let expiry_options = get_expiry_options(
         &params.option_currency,
-        params.expiry_sec(),
+        max_expiry_sec,
         params.min_expiry_sec(),
-        true,
+        params.is_call(),
     )
     .await?;
 
-    let sub = subscribe_tickers(market.clone(), expiry_options, TickerInterval::_1000Ms);
-    let _ = select! {
-        _ = sub => {},
-        _ = tokio::time::sleep(tokio::time::Duration::from_secs(3)) => {},
+    // a snapshot is enough to select from, no need to run the subscription
+    let tickers = TickerManager::new(market.clone(), TickerInterval::_1000Ms);
+    tickers.add(expiry_options).await?;
+
+    let reader = market.read().await;
+    let candidates = reader.iter_tickers().filter(|&ticker| {
+        if let Some(ref pricing) = ticker.option_pricing {
+            &pricing.delta.abs() < &params.max_delta
+        } else {
+            false
+        }
+    });
+    let abs_delta = |ticker: &InstrumentTicker| ticker.option_pricing.as_ref().unwrap().delta.abs();
+    let selected_option = match params.selection_mode {
+        SelectionMode::Delta => {
+            let desired_delta = &params.target_delta;
+            candidates.min_by_key(|&ticker| (abs_delta(ticker) - desired_delta).abs())
+        }
+        SelectionMode::Moneyness => {
+            let target = params.target_moneyness.as_ref().ok_or(Error::msg(
+                "target_moneyness is required by the moneyness selection mode",
+            ))?;
+            candidates
+                .filter_map(|ticker| Some((ticker, get_moneyness(ticker)?)))
+                .min_by_key(|(_, moneyness)| (moneyness - target).abs())
+                .map(|(ticker, _)| ticker)
+        }
+        SelectionMode::Yield => {
+            let min_yield = params
+                .min_premium_yield
+                .as_ref()
+                .ok_or(Error::msg("min_premium_yield is required by the yield selection mode"))?;
+            candidates
+                .filter(|&ticker| get_premium_yield(ticker, now).is_some_and(|y| &y >= min_yield))
+                .min_by_key(|&ticker| abs_delta(ticker))
+        }
+    };
+    if let Some(option) = selected_option {
+        info!(
+            "Selected {} by {:?}: moneyness {:?}, premium yield {:?}",
+            option.instrument_name,
+            params.selection_mode,
+            get_moneyness(option),
+            get_premium_yield(option, now)
+        );
+    }
+    match selected_option {
+        Some(option) => Ok(option.instrument_name.clone()),
+        None => Err(err),
+    }
+}
+
+/// Returns the two options of the selected option's expiry whose deltas bracket target_delta,
+/// weighted so that their weighted delta is the target, or the selected option alone if no
+/// strike brackets it or a weight falls below `strike_split.min_weight`
+async fn select_strike_split(params: &LRTCParams) -> Result<Vec<(String, ExpiryRung)>> {
+    let split =
+        params.strike_split.as_ref().ok_or(Error::msg("strike_split params are required"))?;
+    if params.selection_mode != SelectionMode::Delta {
+        return Err(Error::msg("Strike splits only support the delta selection mode"));
+    }
+    let option_name = select_new_option(params).await?;
+    let market = new_market_state();
+    let expiry = get_option_expiry(&option_name).await?;
+    let expiry_options =
+        get_options_at_expiry(&params.option_currency, expiry, params.is_call()).await?;
+    let tickers = TickerManager::new(market.clone(), TickerInterval::_1000Ms);
+    tickers.add(expiry_options).await?;
+
+    let reader = market.read().await;
+    let deltas = reader
+        .iter_tickers()
+        .filter_map(|t| Some((&t.instrument_name, t.option_pricing.as_ref()?.delta.abs())))
+        .filter(|(_, delta)| delta < &params.max_delta)
+        .collect::<Vec<_>>();
+    let target = &params.target_delta;
+    let below = deltas.iter().filter(|(_, delta)| delta <= target).max_by_key(|(_, d)| d.clone());
+    let above = deltas.iter().filter(|(_, delta)| delta > target).min_by_key(|(_, d)| d.clone());
+    let rung = |weight| ExpiryRung { expiry_days: params.expiry_days, weight };
+    let (Some((low_name, low_delta)), Some((high_name, high_delta))) = (below, above) else {
+        info!("No strikes around delta {}, selling {} only", target, option_name);
+        return Ok(vec![(option_name, rung(BigDecimal::one()))]);
     };
+    let high_weight = (target - low_delta) / (high_delta - low_delta);
+    let low_weight = BigDecimal::one() - &high_weight;
+    if low_weight < split.min_weight || high_weight < split.min_weight {
+        info!(
+            "Strike split weights {} / {} too small, selling {} only",
+            low_weight, high_weight, option_name
+        );
+        return Ok(vec![(option_name, rung(BigDecimal::one()))]);
+    }
+    info!(
+        "Selected strike split {} ({} at delta {}) / {} ({} at delta {})",
+        low_name, low_weight, low_delta, high_name, high_weight, high_delta
+    );
+    Ok(vec![(low_name.to_string(), rung(low_weight)), (high_name.to_string(), rung(high_weight))])
+}
+
+/// Returns the unit legs of the call spread sold instead of the naked short call: the short
+/// call and the long call of the same expiry above its strike with the delta closest to
+/// `call_spread.long_delta`
+pub async fn select_call_spread(params: &LRTCParams, short_name: &str) -> Result<Vec<LegUnpriced>> {
+    let spread =
+        params.call_spread.as_ref().ok_or(Error::msg("call_spread params are required"))?;
+    if !params.is_call() {
+        return Err(Error::msg("Spreads are only supported for calls"));
+    }
+    let market = new_market_state();
+    let expiry = get_option_expiry(short_name).await?;
+    let expiry_options = get_options_at_expiry(&params.option_currency, expiry, true).await?;
+    let tickers = TickerManager::new(market.clone(), TickerInterval::_1000Ms);
+    tickers.add(expiry_options).await?;
 
-    let desired_delta = &params.target_delta;
     let reader = market.read().await;
-    let selected_option = reader
+    let short_strike = reader
+        .get_ticker(short_name)
+        .and_then(|ticker| ticker.option_details.as_ref())
+        .map(|details| details.strike.clone())
+        .ok_or(Error::msg(format!("Ticker of {} not found", short_name)))?;
+    let long_option = reader
         .iter_tickers()
         .filter(|&ticker| {
-            if let Some(ref pricing) = ticker.option_pricing {
-                &pricing.delta < &params.max_delta
-            } else {
-                false
-            }
+            ticker.option_details.as_ref().is_some_and(|d| d.strike > short_strike)
+                && ticker.option_pricing.is_some()
         })
         .min_by_key(|&ticker| {
-            (ticker.option_pricing.as_ref().unwrap().delta.clone() - desired_delta).abs()
-        });
-    match selected_option {
-        Some(option) => Ok(option.instrument_name.clone()),
-        None => Err(err),
+            let delta = &ticker.option_pricing.as_ref().unwrap().delta;
+            (delta - &spread.long_delta).abs()
+        })
+        .ok_or(Error::msg(format!("No long leg found above {}", short_name)))?;
+    info!("Selected call spread {} / {}", short_name, long_option.instrument_name);
+    Ok(vec![
+        LegUnpriced {
+            instrument_name: short_name.to_string(),
+            amount: BigDecimal::from(1),
+            direction: Direction::Sell,
+        },
+        LegUnpriced {
+            instrument_name: long_option.instrument_name.clone(),
+            amount: BigDecimal::from(1),
+            direction: Direction::Buy,
+        },
+    ])
+}
+
+/// Strike over the forward price of the option
+pub fn get_moneyness(ticker: &InstrumentTicker) -> Option<BigDecimal> {
+    let (details, pricing) = (ticker.option_details.as_ref()?, ticker.option_pricing.as_ref()?);
+    (!pricing.forward_price.is_zero()).then(|| &details.strike / &pricing.forward_price)
+}
+
+/// Mark price over the collateral covering one option (spot for calls, the strike for
+/// cash-secured puts), annualized over the time to expiry
+fn get_premium_yield(ticker: &InstrumentTicker, now_sec: i64) -> Option<BigDecimal> {
+    let details = ticker.option_details.as_ref()?;
+    let collateral = match details.option_type {
+        OptionType::C => &ticker.index_price,
+        OptionType::P => &details.strike,
+    };
+    let expiry_sec = details.expiry - now_sec;
+    if collateral.is_zero() || expiry_sec <= 0 {
+        return None;
     }
+    let annualize = BigDecimal::from(SEC_PER_YEAR as i64) / BigDecimal::from(expiry_sec);
+    Some(&ticker.mark_price / collateral * annualize)
 }
 
 /// Returns the option name from an existing position
 /// Expects the market state to be synced to the subaccount
 pub async fn maybe_select_from_positions(market: &MarketState) -> Result<Option<String>> {
+    let position_names = select_all_from_positions(market).await;
+    match position_names.len() {
+        0 => Ok(None),
+        1 => Ok(Some(position_names[0].clone())),
+        _ => Err(Error::msg("Unexpected multiple open options positions")),
+    }
+}
+
+/// Returns the short and long leg of an open call spread
+/// Expects the market state to be synced to the subaccount
+pub async fn maybe_select_spread_from_positions(
+    market: &MarketState,
+) -> Result<Option<(String, String)>> {
+    let position_names = select_all_from_positions(market).await;
+    let reader = market.read().await;
+    let is_short =
+        |name: &String| reader.get_position(name).is_some_and(|p| p.amount < BigDecimal::zero());
+    match position_names.as_slice() {
+        [] => Ok(None),
+        [a, b] if is_short(a) && !is_short(b) => Ok(Some((a.clone(), b.clone()))),
+        [a, b] if is_short(b) && !is_short(a) => Ok(Some((b.clone(), a.clone()))),
+        _ => Err(Error::msg(format!("Unexpected spread positions {:?}", position_names))),
+    }
+}
+
+/// Returns the names of all open option positions (e.g. every expiry of a ladder)
+/// Expects the market state to be synced to the subaccount
+pub async fn select_all_from_positions(market: &MarketState) -> Vec<String> {
     let reader = market.read().await;
-    let position_names: Vec<String> = reader
+    reader
         .iter_positions()
         .filter(|&p| {
             p.amount != BigDecimal::zero()
                 && (p.instrument_name.ends_with("-C") || p.instrument_name.ends_with("-P"))
         })
         .map(|p| p.instrument_name.clone())
-        .collect();
-    match position_names.len() {
-        0 => Ok(None),
-        1 => Ok(Some(position_names[0].clone())),
-        _ => Err(Error::msg("Unexpected multiple open options positions")),
-    }
+        .collect()
 }
@@ -1,13 +1,128 @@
+use crate::lrtc::option_auction::LadderRungStrategy;
 use crate::lrtc::params::OptionAuctionParams;
-use crate::shared::auction::LimitOrderAuctionExecutor;
-use crate::shared::params::SpotAuctionParams;
-use crate::shared::stages::{TSACollateralOnly, TSAWaitForSettlement};
+use crate::lrtc::roll::ITMRollParams;
+use crate::shared::auction::{LimitOrderAuctionExecutor, MultiAuctionExecutor};
+use crate::shared::params::OptionRFQParams;
+use crate::shared::rfq::RFQAuctionExecutor;
+use crate::shared::spot_auction::CollateralSpotStrategy;
+use crate::shared::stages::{
+    ExecutorStage, TSACollateralOnly, TSAWaitForSettlement, TSAWithdrawals,
+};
+use crate::shared::state_store::ExecutorSnapshot;
+use crate::shared::twap::TwapExecutor;
+use anyhow::Result;
 use std::fmt::Debug;
 
 #[derive(Debug)]
 pub enum LRTCExecutorStage {
     SpotOnly(TSACollateralOnly),
     OptionAuction(LimitOrderAuctionExecutor<OptionAuctionParams>),
+    OptionRFQ(RFQAuctionExecutor<OptionRFQParams>),
+    OptionLadder(MultiAuctionExecutor<LadderRungStrategy>),
     AwaitSettlement(TSAWaitForSettlement),
-    SpotAuction(LimitOrderAuctionExecutor<SpotAuctionParams>),
+    TopupAuction(LimitOrderAuctionExecutor<OptionAuctionParams>),
+    ITMRoll(RFQAuctionExecutor<ITMRollParams>),
+    SpotAuction(TwapExecutor),
+    CollateralAuctions(MultiAuctionExecutor<CollateralSpotStrategy>),
+    Withdrawals(TSAWithdrawals),
+}
+
+impl LRTCExecutorStage {
+    pub fn snapshot(&self) -> ExecutorSnapshot {
+        match self {
+            LRTCExecutorStage::SpotOnly(_) => ExecutorSnapshot::new("SpotOnly", None, None),
+            LRTCExecutorStage::OptionAuction(s) => ExecutorSnapshot {
+                mini_auction: s.strategy.mini_auction,
+                ..ExecutorSnapshot::new(
+                    "OptionAuction",
+                    Some(s.auction.instrument_name.clone()),
+                    Some(s.auction.start_timestamp_sec),
+                )
+            },
+            // stored as an option auction so that resuming re-reads the execution type from params
+            LRTCExecutorStage::OptionRFQ(s) => ExecutorSnapshot::new(
+                "OptionAuction",
+                s.auction.instrument_names().first().cloned(),
+                Some(s.auction.start_timestamp_sec),
+            ),
+            LRTCExecutorStage::OptionLadder(s) => ExecutorSnapshot::new(
+                "OptionLadder",
+                s.instrument_names().first().cloned(),
+                s.executors.first().map(|e| e.auction.start_timestamp_sec),
+            ),
+            LRTCExecutorStage::AwaitSettlement(s) => {
+                ExecutorSnapshot::new("AwaitSettlement", s.option_names.first().cloned(), None)
+            }
+            // resuming waits for settlement, the deposits are topped up at the next heartbeat
+            LRTCExecutorStage::TopupAuction(s) => ExecutorSnapshot::new(
+                "AwaitSettlement",
+                Some(s.auction.instrument_name.clone()),
+                None,
+            ),
+            // not resumed, a restart mid-roll finds both calls open and awaits their settlement
+            LRTCExecutorStage::ITMRoll(s) => ExecutorSnapshot::new(
+                "ITMRoll",
+                s.auction.instrument_names().first().cloned(),
+                Some(s.auction.start_timestamp_sec),
+            ),
+            LRTCExecutorStage::SpotAuction(s) => ExecutorSnapshot::new(
+                "SpotAuction",
+                Some(s.executor.auction.instrument_name.clone()),
+                Some(s.executor.auction.start_timestamp_sec),
+            ),
+            // stored as a spot auction, resuming rebuilds one auction per collateral from params
+            LRTCExecutorStage::CollateralAuctions(s) => ExecutorSnapshot::new(
+                "SpotAuction",
+                s.instrument_names().first().cloned(),
+                s.executors.first().map(|e| e.auction.start_timestamp_sec),
+            ),
+            LRTCExecutorStage::Withdrawals(_) => ExecutorSnapshot::new("Withdrawals", None, None),
+        }
+    }
+}
+
+impl ExecutorStage for LRTCExecutorStage {
+    fn name(&self) -> String {
+        let name = match self {
+            LRTCExecutorStage::SpotOnly(_) => "SpotOnly",
+            LRTCExecutorStage::OptionAuction(_) => "OptionAuction",
+            LRTCExecutorStage::OptionRFQ(_) => "OptionRFQ",
+            LRTCExecutorStage::OptionLadder(_) => "OptionLadder",
+            LRTCExecutorStage::AwaitSettlement(_) => "AwaitSettlement",
+            LRTCExecutorStage::TopupAuction(_) => "TopupAuction",
+            LRTCExecutorStage::ITMRoll(_) => "ITMRoll",
+            LRTCExecutorStage::SpotAuction(_) => "SpotAuction",
+            LRTCExecutorStage::CollateralAuctions(_) => "CollateralAuctions",
+            LRTCExecutorStage::Withdrawals(_) => "Withdrawals",
+        };
+        name.to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        match self {
+            LRTCExecutorStage::SpotOnly(s) => s.run().await,
+            LRTCExecutorStage::OptionAuction(s) => s.run().await,
+            LRTCExecutorStage::OptionRFQ(s) => s.run().await,
+            LRTCExecutorStage::OptionLadder(s) => s.run().await,
+            LRTCExecutorStage::AwaitSettlement(s) => s.run().await,
+            LRTCExecutorStage::TopupAuction(s) => s.run().await,
+            LRTCExecutorStage::ITMRoll(s) => s.run().await,
+            LRTCExecutorStage::SpotAuction(s) => s.run().await,
+            LRTCExecutorStage::CollateralAuctions(s) => s.run().await,
+            LRTCExecutorStage::Withdrawals(s) => s.run().await,
+        }
+    }
+    async fn reconnect(&mut self) -> Result<()> {
+        match self {
+            LRTCExecutorStage::SpotOnly(s) => s.reconnect().await,
+            LRTCExecutorStage::OptionAuction(s) => s.reconnect().await,
+            LRTCExecutorStage::OptionRFQ(s) => s.reconnect().await,
+            LRTCExecutorStage::OptionLadder(s) => s.reconnect().await,
+            LRTCExecutorStage::AwaitSettlement(s) => s.reconnect().await,
+            LRTCExecutorStage::TopupAuction(s) => s.reconnect().await,
+            LRTCExecutorStage::ITMRoll(s) => s.reconnect().await,
+            LRTCExecutorStage::SpotAuction(s) => s.reconnect().await,
+            LRTCExecutorStage::CollateralAuctions(s) => s.reconnect().await,
+            LRTCExecutorStage::Withdrawals(s) => s.reconnect().await,
+        }
+    }
 }
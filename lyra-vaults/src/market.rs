@@ -3,20 +3,25 @@ Defines core shared state of the market.
 Public and private modules define logic for ws subscriptions that update the shared state.
 */
 use bigdecimal::{BigDecimal, Zero};
-use log::info;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::Hash;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tracing::info;
 use uuid::Uuid;
 
 use lyra_client::actions::{Direction, OrderResponse, OrderStatus};
 use orderbook_types::generated::channel_orderbook_instrument_name_group_depth::OrderbookInstrumentNameGroupDepthPublisherDataSchema;
-use orderbook_types::types::orders::{TradeResponse, TxStatus};
-use orderbook_types::types::tickers::result::InstrumentTicker;
+use orderbook_types::generated::channel_trades_instrument_name::TradePublicResponseSchema;
+use orderbook_types::types::orders::{CancelReason, TradeResponse, TxStatus};
+use orderbook_types::types::tickers::result::{InstrumentData, InstrumentTicker};
+use orderbook_types::types::tickers::InstrumentType;
 
 pub type OrderbookData = OrderbookInstrumentNameGroupDepthPublisherDataSchema;
+pub type PublicTrade = TradePublicResponseSchema;
+
+const MAX_CANDLES: usize = 1_000; // per instrument and interval, the oldest are dropped
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Balance {
@@ -27,12 +32,78 @@ pub struct Balance {
 
 pub type MarketState = Arc<RwLock<MarketData>>;
 
+/// Greeks of all positions, weighted by position size.
+/// Deltas are in units of the underlying, the LRT collateral counts 1:1 with it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PortfolioGreeks {
+    pub option_delta: BigDecimal,
+    pub perp_delta: BigDecimal,
+    pub collateral_delta: BigDecimal,
+    pub gamma: BigDecimal,
+    pub vega: BigDecimal,
+    pub theta: BigDecimal,
+    /// Positions without a ticker (or option pricing), excluded from the greeks
+    pub missing: Vec<String>,
+}
+
+impl PortfolioGreeks {
+    /// Spot-equivalent delta of the options, perps and collateral
+    pub fn net_delta(&self) -> BigDecimal {
+        &self.option_delta + &self.perp_delta + &self.collateral_delta
+    }
+}
+
+/// OHLCV of the public trades of an instrument within `[start_ms, start_ms + interval)`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Candle {
+    pub start_ms: i64,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    pub volume: BigDecimal,   // traded amount
+    pub notional: BigDecimal, // traded amount times price, e.g. for the VWAP
+    pub num_trades: usize,
+}
+
+impl Candle {
+    fn new(start_ms: i64, trade: &PublicTrade) -> Self {
+        Self {
+            start_ms,
+            open: trade.trade_price.clone(),
+            high: trade.trade_price.clone(),
+            low: trade.trade_price.clone(),
+            close: trade.trade_price.clone(),
+            volume: BigDecimal::zero(),
+            notional: BigDecimal::zero(),
+            num_trades: 0,
+        }
+    }
+
+    fn add(&mut self, trade: &PublicTrade) {
+        if trade.trade_price > self.high {
+            self.high = trade.trade_price.clone();
+        }
+        if trade.trade_price < self.low {
+            self.low = trade.trade_price.clone();
+        }
+        self.close = trade.trade_price.clone();
+        self.volume += &trade.trade_amount;
+        self.notional += &trade.trade_amount * &trade.trade_price;
+        self.num_trades += 1;
+    }
+}
+
 pub struct MarketData {
     tickers: HashMap<String, InstrumentTicker>,
+    instruments: HashMap<String, InstrumentData>,
     orderbooks: HashMap<String, OrderbookData>,
-    positions: HashMap<String, Balance>,
+    positions: HashMap<String, Balance>, // summed over the subaccounts
+    subaccount_positions: HashMap<i64, HashMap<String, Balance>>,
     orders: HashMap<String, HashMap<String, OrderResponse>>,
     trades: HashMap<String, HashMap<String, TradeResponse>>,
+    candles: HashMap<(String, i64), BTreeMap<i64, Candle>>, // by instrument and interval sec
+    mmp_triggered: HashSet<String>, // instruments with an order cancelled by an MMP trigger
 }
 
 const STALENESS_MS: i64 = 2_000; // todo ideally want to log the staleness
@@ -41,10 +112,14 @@ impl MarketData {
     pub fn new() -> Self {
         MarketData {
             tickers: HashMap::new(),
+            instruments: HashMap::new(),
             orderbooks: HashMap::new(),
             positions: HashMap::new(),
+            subaccount_positions: HashMap::new(),
             orders: HashMap::new(),
             trades: HashMap::new(),
+            candles: HashMap::new(),
+            mmp_triggered: HashSet::new(),
         }
     }
     pub fn get_orderbook(&self, instrument_name: &str) -> Option<&OrderbookData> {
@@ -77,18 +152,74 @@ impl MarketData {
     pub fn insert_ticker(&mut self, ticker: InstrumentTicker) {
         self.tickers.insert(ticker.instrument_name.clone(), ticker);
     }
+    pub fn remove_ticker(&mut self, instrument_name: &str) -> Option<InstrumentTicker> {
+        self.tickers.remove(instrument_name)
+    }
     pub fn iter_tickers(&self) -> impl Iterator<Item = &InstrumentTicker> {
         self.tickers.values()
     }
+    pub fn get_instrument(&self, instrument_name: &str) -> Option<&InstrumentData> {
+        self.instruments.get(instrument_name)
+    }
+    pub fn iter_instruments(&self) -> impl Iterator<Item = &InstrumentData> {
+        self.instruments.values()
+    }
+    /// Replaces the instruments of the currency with the active ones of a fresh instrument list
+    /// and returns the names of the (newly) listed and delisted ones
+    pub fn refresh_instruments(
+        &mut self,
+        currency: &str,
+        instruments: Vec<InstrumentData>,
+    ) -> (Vec<String>, Vec<String>) {
+        let active = instruments
+            .into_iter()
+            .filter(|i| i.is_active)
+            .map(|i| (i.instrument_name.clone(), i))
+            .collect::<HashMap<_, _>>();
+        let delisted = self
+            .instruments
+            .values()
+            .filter(|i| i.base_currency == currency && !active.contains_key(&i.instrument_name))
+            .map(|i| i.instrument_name.clone())
+            .collect::<Vec<_>>();
+        let listed = active
+            .keys()
+            .filter(|name| !self.instruments.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        delisted.iter().for_each(|name| {
+            self.instruments.remove(name);
+        });
+        self.instruments.extend(active);
+        (listed, delisted)
+    }
     pub fn get_position(&self, instrument_name: &str) -> Option<&Balance> {
         self.positions.get(instrument_name)
     }
     pub fn get_amount(&self, instrument_name: &str) -> BigDecimal {
         self.positions.get(instrument_name).map_or(BigDecimal::zero(), |p| p.amount.clone())
     }
+    /// Sum of the positions, e.g. of several collaterals sharing an underlying
+    pub fn get_total_amount(&self, instrument_names: &[String]) -> BigDecimal {
+        instrument_names.iter().map(|name| self.get_amount(name)).sum()
+    }
     pub fn insert_position(&mut self, position: Balance) {
         self.positions.insert(position.instrument_name.clone(), position);
     }
+    /// Sets the position of one of the tracked subaccounts (e.g. the option book and the spot
+    /// inventory of a vault), the getters above return the sum over all of them
+    pub fn insert_subaccount_position(&mut self, subaccount_id: i64, position: Balance) {
+        let name = position.instrument_name.clone();
+        self.subaccount_positions.entry(subaccount_id).or_default().insert(name.clone(), position);
+        let (amount, timestamp) = self
+            .subaccount_positions
+            .values()
+            .filter_map(|positions| positions.get(&name))
+            .fold((BigDecimal::zero(), 0), |(amount, timestamp), p| {
+                (amount + &p.amount, timestamp.max(p.timestamp))
+            });
+        self.insert_position(Balance { instrument_name: name, amount, timestamp });
+    }
     pub fn iter_positions(&self) -> impl Iterator<Item = &Balance> {
         self.positions.values()
     }
@@ -96,6 +227,9 @@ impl MarketData {
         self.orders.get(instrument_name)
     }
     pub fn insert_order(&mut self, order: OrderResponse) {
+        if order.cancel_reason == CancelReason::MmpTrigger {
+            self.mmp_triggered.insert(order.instrument_name.clone());
+        }
         let orders = self.orders.entry(order.instrument_name.clone()).or_default();
         let order_id = order.order_id.clone();
         let existing = orders.remove(&order_id);
@@ -113,6 +247,10 @@ impl MarketData {
             orders.insert(order_id, order);
         }
     }
+    /// True once an order of the instrument was cancelled by an MMP trigger, taking the trigger
+    pub fn take_mmp_trigger(&mut self, instrument_name: &str) -> bool {
+        self.mmp_triggered.remove(instrument_name)
+    }
     pub fn iter_orders(&self) -> impl Iterator<Item = &HashMap<String, OrderResponse>> {
         self.orders.values()
     }
@@ -144,6 +282,21 @@ impl MarketData {
         }
         Some(ob)
     }
+    /// Amount in the best `levels` levels an order in `direction` trades against (bids for a
+    /// sell), excluding the own orders. None without a fresh orderbook.
+    pub fn get_visible_depth(
+        &self,
+        instrument_name: &str,
+        direction: &Direction,
+        levels: usize,
+    ) -> Option<BigDecimal> {
+        let ob = self.get_orderbook_exclude_my_orders(instrument_name)?;
+        let side = match direction {
+            Direction::Buy => ob.asks,
+            Direction::Sell => ob.bids,
+        };
+        Some(side.iter().take(levels).map(|level| level[1].clone()).sum())
+    }
     pub fn get_trades(&self, instrument_name: &str) -> Option<&HashMap<String, TradeResponse>> {
         self.trades.get(instrument_name)
     }
@@ -151,6 +304,29 @@ impl MarketData {
         let trades = self.trades.entry(trade.instrument_name.clone()).or_default();
         trades.insert(trade.trade_id.clone(), trade);
     }
+    pub fn iter_trades(&self) -> impl Iterator<Item = &TradeResponse> {
+        self.trades.values().flat_map(|trades| trades.values())
+    }
+    /// Adds a public trade to the candles of the instrument for every interval
+    pub fn insert_public_trade(&mut self, trade: &PublicTrade, intervals_sec: &[i64]) {
+        for interval_sec in intervals_sec.iter().filter(|i| **i > 0) {
+            let key = (trade.instrument_name.clone(), *interval_sec);
+            let candles = self.candles.entry(key).or_default();
+            let interval_ms = interval_sec * 1000;
+            let start_ms = trade.timestamp - trade.timestamp.rem_euclid(interval_ms);
+            let candle = candles.entry(start_ms).or_insert_with(|| Candle::new(start_ms, trade));
+            candle.add(trade);
+            while candles.len() > MAX_CANDLES {
+                candles.pop_first();
+            }
+        }
+    }
+    /// Candles of the instrument's public trades, oldest first. Intervals without trades are
+    /// skipped, empty for instruments or intervals not subscribed.
+    pub fn get_candles(&self, instrument_name: &str, interval_sec: i64) -> Vec<Candle> {
+        let key = (instrument_name.to_string(), interval_sec);
+        self.candles.get(&key).map(|c| c.values().cloned().collect()).unwrap_or_default()
+    }
     pub fn all_trades_confirmed(&self, instrument_name: &str) -> bool {
         let trades = self.get_trades(instrument_name);
         match trades {
@@ -177,6 +353,36 @@ impl MarketData {
             _ => false,
         }
     }
+    /// Aggregates the greeks of all option and perp positions from the (possibly stale) tickers.
+    /// `collateral_name` is the spot collateral (e.g. the LRT) counted towards delta,
+    /// other collaterals such as USDC carry no delta.
+    pub fn get_portfolio_greeks(&self, collateral_names: &[String]) -> PortfolioGreeks {
+        let mut greeks = PortfolioGreeks::default();
+        for position in self.iter_positions().filter(|p| !p.amount.is_zero()) {
+            let amount = &position.amount;
+            if collateral_names.contains(&position.instrument_name) {
+                greeks.collateral_delta += amount;
+                continue;
+            }
+            let ticker = self.tickers.get(&position.instrument_name);
+            match ticker.map(|t| (&t.instrument_type, &t.option_pricing)) {
+                Some((InstrumentType::Option, Some(pricing))) => {
+                    greeks.option_delta += amount * &pricing.delta;
+                    greeks.gamma += amount * &pricing.gamma;
+                    greeks.vega += amount * &pricing.vega;
+                    greeks.theta += amount * &pricing.theta;
+                }
+                Some((InstrumentType::Perp, _)) => greeks.perp_delta += amount,
+                Some((InstrumentType::Erc20, _)) => {}
+                // collaterals (e.g. USDC) have no ticker, instruments are named like ETH-PERP
+                _ if position.instrument_name.contains('-') => {
+                    greeks.missing.push(position.instrument_name.clone())
+                }
+                _ => {}
+            }
+        }
+        greeks
+    }
     pub fn log_state(&self) {
         info!("Market state:");
         info!("Tickers:");
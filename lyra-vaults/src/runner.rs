@@ -0,0 +1,693 @@
+use crate::basis::executor::BasisExecutor;
+use crate::basis::params::BasisParams;
+use crate::dn::executor::DNExecutor;
+use crate::dn::params::DNParams;
+use crate::longpp::executor::LongPPExecutor;
+use crate::longpp::params::LongPPParams;
+use crate::lrtc::executor::LRTCExecutor;
+use crate::lrtc::params::LRTCParams;
+use crate::lrtc::plan::project_epoch;
+use crate::shared::accounting::Ledger;
+use crate::shared::admin::{run_admin_commands, serve_admin, AdminControl, AdminRegistry};
+use crate::shared::backtest::{run_backtest, BacktestParams};
+use crate::shared::context::VaultContext;
+use crate::shared::fees::{FeeBudgetParams, FeeTracker};
+use crate::shared::health::{serve_health, HealthHandle, HealthRegistry};
+use crate::shared::instruments::{instrument_watcher, InstrumentWatchParams};
+use crate::shared::labels::OrderLabels;
+use crate::shared::margin::{margin_monitor, MarginLimitParams, MarginMonitorParams};
+use crate::shared::reconcile::{reconciler, ReconcileParams};
+use crate::shared::recorder::{run_recorder, RecorderParams};
+use crate::shared::reload::{params_reloader, LiveParams, ParamsReloadParams};
+use crate::shared::rfq_maker::rfq_maker;
+use crate::shared::risk::{risk_guard, RiskLimits};
+use crate::shared::subaccounts::SubaccountParams;
+use crate::shared::supervisor::supervise;
+use crate::shared::validation::{parse_params, ParamsCheck, ValidateParams};
+use crate::shared::vault::VaultExecutor;
+use crate::shared::withdrawals::WithdrawalReserve;
+use crate::web3::{actions, events, get_subaccount_id};
+use anyhow::{Context, Error, Result};
+use bigdecimal::BigDecimal;
+use ethers::abi::Address;
+use lyra_client::actions::{HttpFallback, HttpFallbackParams};
+use lyra_client::config::LyraConfig;
+use lyra_client::logging::init_logging;
+use lyra_client::rfq_maker::RfqMakerParams;
+use lyra_client::setup::{
+    ensure_session_key, env_config, load_session_key, setup_env, setup_env_for,
+};
+use lyra_client::signer::LyraSigner;
+use std::future::Future;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{error, info};
+
+#[derive(Debug, Clone)]
+pub enum VaultParams {
+    // DN must come before LRTC since DN json is a superset of LRTC json
+    DN(DNParams),
+    LRTC(LRTCParams),
+    LongPP(LongPPParams),
+    Basis(BasisParams),
+    // Add more vaults here
+}
+
+impl VaultParams {
+    /// Dispatches on a `type` field ("lrtc", "dn", "longpp" or "basis", `strategy_type` is an
+    /// alias), falling back to matching the params shape when it is not set. Errors name the
+    /// vault and the offending fields, see `ParamsCheck`.
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let vault_name = value.get("vault_name").and_then(|v| v.as_str()).unwrap_or("vault");
+        let context = format!("Params of {}", vault_name);
+        let kind = value.get("type").or(value.get("strategy_type"));
+        let kind = kind.map(|v| v.as_str().map(str::to_string));
+        let params = match kind {
+            Some(Some(kind)) => Self::parse_kind(&kind, value).context(context.clone())?,
+            Some(None) => return Err(Error::msg("type is not a string").context(context)),
+            None => Self::parse_shape(value).context(context.clone())?,
+        };
+        params.validate().context(context)?;
+        Ok(params)
+    }
+
+    fn parse_kind(kind: &str, value: serde_json::Value) -> Result<Self> {
+        let params = match kind {
+            "lrtc" => VaultParams::LRTC(parse_params(value)?),
+            "dn" => VaultParams::DN(parse_params(value)?),
+            "longpp" => VaultParams::LongPP(parse_params(value)?),
+            "basis" => VaultParams::Basis(parse_params(value)?),
+            other => {
+                let message = format!("Unknown type {}, expected lrtc, dn, longpp or basis", other);
+                return Err(Error::msg(message));
+            }
+        };
+        Ok(params)
+    }
+
+    /// The first vault type the params parse as, DN before LRTC since DN json is a superset
+    /// of LRTC json. Lists why each type failed otherwise.
+    fn parse_shape(value: serde_json::Value) -> Result<Self> {
+        let mut errors = vec![];
+        for kind in ["dn", "lrtc", "longpp", "basis"] {
+            match Self::parse_kind(kind, value.clone()) {
+                Ok(params) => return Ok(params),
+                Err(e) => errors.push(format!("  as {}: {:#}", kind, e)),
+            }
+        }
+        let message =
+            format!("Params match no vault type, set \"type\" to pick one:\n{}", errors.join("\n"));
+        Err(Error::msg(message))
+    }
+
+    /// Range checks and cross-field invariants, see `ValidateParams`
+    pub fn validate(&self) -> Result<()> {
+        let mut check = ParamsCheck::default();
+        match self {
+            VaultParams::DN(params) => params.validate(&mut check),
+            VaultParams::LRTC(params) => params.validate(&mut check),
+            VaultParams::LongPP(params) => params.validate(&mut check),
+            VaultParams::Basis(params) => params.validate(&mut check),
+        }
+        check.finish(self.kind())
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            VaultParams::DN(_) => "DN",
+            VaultParams::LRTC(_) => "LRTC",
+            VaultParams::LongPP(_) => "LongPP",
+            VaultParams::Basis(_) => "Basis",
+        }
+    }
+
+    fn env(&self) -> String {
+        match self {
+            VaultParams::DN(params) => params.covered_call.env.clone(),
+            VaultParams::LRTC(params) => params.env.clone(),
+            VaultParams::LongPP(params) => params.env.clone(),
+            VaultParams::Basis(params) => params.env.clone(),
+        }
+    }
+
+    fn risk_limits(&self) -> Option<RiskLimits> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.risk_limits.clone(),
+            VaultParams::LRTC(params) => params.risk_limits.clone(),
+            VaultParams::LongPP(params) => params.risk_limits.clone(),
+            VaultParams::Basis(params) => params.risk_limits.clone(),
+        }
+    }
+
+    fn margin_monitor(&self) -> Option<MarginMonitorParams> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.margin_monitor.clone(),
+            VaultParams::LRTC(params) => params.margin_monitor.clone(),
+            VaultParams::LongPP(params) => params.margin_monitor.clone(),
+            VaultParams::Basis(params) => params.margin_monitor.clone(),
+        }
+    }
+
+    fn yield_instruments(&self) -> Vec<String> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.yield_instruments(),
+            VaultParams::LRTC(params) => params.yield_instruments(),
+            VaultParams::LongPP(_) | VaultParams::Basis(_) => vec![],
+        }
+    }
+
+    fn subaccounts(&self) -> SubaccountParams {
+        match self {
+            VaultParams::DN(params) => params.covered_call.subaccounts.clone(),
+            VaultParams::LRTC(params) => params.subaccounts.clone(),
+            VaultParams::LongPP(params) => params.subaccounts.clone(),
+            VaultParams::Basis(params) => params.subaccounts.clone(),
+        }
+    }
+
+    fn margin_limit(&self) -> Option<MarginLimitParams> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.margin_limit.clone(),
+            VaultParams::LRTC(params) => params.margin_limit.clone(),
+            VaultParams::LongPP(params) => params.margin_limit.clone(),
+            VaultParams::Basis(params) => params.margin_limit.clone(),
+        }
+    }
+
+    fn fee_budget(&self) -> Option<FeeBudgetParams> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.fee_budget.clone(),
+            VaultParams::LRTC(params) => params.fee_budget.clone(),
+            VaultParams::LongPP(params) => params.fee_budget.clone(),
+            VaultParams::Basis(params) => params.fee_budget.clone(),
+        }
+    }
+
+    fn http_fallback(&self) -> Option<HttpFallbackParams> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.http_fallback.clone(),
+            VaultParams::LRTC(params) => params.http_fallback.clone(),
+            VaultParams::LongPP(params) => params.http_fallback.clone(),
+            VaultParams::Basis(params) => params.http_fallback.clone(),
+        }
+    }
+
+    fn reconcile(&self) -> Option<ReconcileParams> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.reconcile.clone(),
+            VaultParams::LRTC(params) => params.reconcile.clone(),
+            VaultParams::LongPP(params) => params.reconcile.clone(),
+            VaultParams::Basis(params) => params.reconcile.clone(),
+        }
+    }
+
+    fn params_reload(&self) -> Option<ParamsReloadParams> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.params_reload.clone(),
+            VaultParams::LRTC(params) => params.params_reload.clone(),
+            VaultParams::LongPP(params) => params.params_reload.clone(),
+            VaultParams::Basis(params) => params.params_reload.clone(),
+        }
+    }
+
+    fn rfq_maker(&self) -> Option<RfqMakerParams> {
+        match self {
+            VaultParams::DN(params) => params.covered_call.rfq_maker.clone(),
+            VaultParams::LRTC(params) => params.rfq_maker.clone(),
+            VaultParams::LongPP(params) => params.rfq_maker.clone(),
+            VaultParams::Basis(params) => params.rfq_maker.clone(),
+        }
+    }
+
+    /// Instrument watch params and the currency of the options
+    fn instrument_watch(&self) -> Option<(InstrumentWatchParams, String)> {
+        match self {
+            VaultParams::DN(params) => params
+                .covered_call
+                .instrument_watch
+                .clone()
+                .map(|p| (p, params.covered_call.option_currency.clone())),
+            VaultParams::LRTC(params) => {
+                params.instrument_watch.clone().map(|p| (p, params.option_currency.clone()))
+            }
+            VaultParams::LongPP(params) => {
+                params.instrument_watch.clone().map(|p| (p, params.option_currency.clone()))
+            }
+            VaultParams::Basis(_) => None,
+        }
+    }
+
+    fn dry_run(&self) -> bool {
+        match self {
+            VaultParams::DN(params) => params.covered_call.dry_run,
+            VaultParams::LRTC(params) => params.dry_run,
+            VaultParams::LongPP(_) | VaultParams::Basis(_) => false,
+        }
+    }
+
+    fn context(&self) -> VaultContext {
+        match self {
+            VaultParams::DN(params) => VaultContext {
+                vault_name: params.covered_call.vault_name.clone(),
+                spot_name: params.covered_call.option_auction_params.spot_name.clone(),
+                extra_collaterals: params
+                    .covered_call
+                    .option_auction_params
+                    .extra_collaterals
+                    .clone(),
+                cash_name: params.covered_call.spot_auction_params.cash_name.clone(),
+                health: HealthHandle::default(),
+                admin: Arc::new(AdminControl::default()),
+                tsa_events: None,
+                risk_halt: None,
+                reconcile_gate: None,
+                margin_limit: None,
+                fee_budget: None,
+                http_fallback: None,
+                instrument_events: None,
+                withdrawal_reserve: Arc::new(WithdrawalReserve::default()),
+                order_labels: Arc::new(OrderLabels::new(&params.covered_call.vault_name)),
+                live_params: Arc::new(LiveParams::default()),
+                subaccounts: self.subaccounts(),
+            },
+            VaultParams::LRTC(params) => VaultContext {
+                vault_name: params.vault_name.clone(),
+                spot_name: params.option_auction_params.spot_name.clone(),
+                extra_collaterals: params.option_auction_params.extra_collaterals.clone(),
+                cash_name: params.spot_auction_params.cash_name.clone(),
+                health: HealthHandle::default(),
+                admin: Arc::new(AdminControl::default()),
+                tsa_events: None,
+                risk_halt: None,
+                reconcile_gate: None,
+                margin_limit: None,
+                fee_budget: None,
+                http_fallback: None,
+                instrument_events: None,
+                withdrawal_reserve: Arc::new(WithdrawalReserve::default()),
+                order_labels: Arc::new(OrderLabels::new(&params.vault_name)),
+                live_params: Arc::new(LiveParams::default()),
+                subaccounts: self.subaccounts(),
+            },
+            VaultParams::LongPP(params) => VaultContext {
+                vault_name: params.vault_name.clone(),
+                spot_name: params.option_auction_params.collat_name.clone(),
+                extra_collaterals: vec![],
+                cash_name: params.spot_auction_params.cash_name.clone(),
+                health: HealthHandle::default(),
+                admin: Arc::new(AdminControl::default()),
+                tsa_events: None,
+                risk_halt: None,
+                reconcile_gate: None,
+                margin_limit: None,
+                fee_budget: None,
+                http_fallback: None,
+                instrument_events: None,
+                withdrawal_reserve: Arc::new(WithdrawalReserve::default()),
+                order_labels: Arc::new(OrderLabels::new(&params.vault_name)),
+                live_params: Arc::new(LiveParams::default()),
+                subaccounts: self.subaccounts(),
+            },
+            VaultParams::Basis(params) => VaultContext {
+                vault_name: params.vault_name.clone(),
+                spot_name: params.spot_name.clone(),
+                extra_collaterals: vec![],
+                cash_name: params.spot_auction_params.cash_name.clone(),
+                health: HealthHandle::default(),
+                admin: Arc::new(AdminControl::default()),
+                tsa_events: None,
+                risk_halt: None,
+                reconcile_gate: None,
+                margin_limit: None,
+                fee_budget: None,
+                http_fallback: None,
+                instrument_events: None,
+                withdrawal_reserve: Arc::new(WithdrawalReserve::default()),
+                order_labels: Arc::new(OrderLabels::new(&params.vault_name)),
+                live_params: Arc::new(LiveParams::default()),
+                subaccounts: self.subaccounts(),
+            },
+        }
+    }
+}
+
+/// Loads the protocol config of `env` with the vault TSA as owner and the TSA subaccount
+async fn vault_config(
+    ctx: &VaultContext,
+    env: &str,
+    session_key: Option<LyraSigner>,
+) -> Result<Arc<LyraConfig>> {
+    let vault_name = &ctx.vault_name;
+    let mut config = LyraConfig::from_env()?.with_env(env);
+    if let Some(session_key) = session_key {
+        config = config.with_session_key(session_key);
+    }
+    // the TSA contract is loaded with the vault's session key
+    let subacc_id =
+        LyraConfig::scope(Arc::new(config.clone()), get_subaccount_id(vault_name)).await?;
+    info!("{} subaccount ID: {}", vault_name, subacc_id);
+    let tsa_address: Address = std::env::var(format!("{vault_name}_TSA_ADDRESS"))?.parse()?;
+    Ok(Arc::new(config.with_owner(tsa_address).with_subaccount_id(subacc_id)))
+}
+
+pub async fn run_executor(params: VaultParams) -> Result<()> {
+    match params {
+        VaultParams::DN(params) => {
+            if params.covered_call.dry_run {
+                return Err(Error::msg("dry_run is not supported for DN vaults yet"));
+            }
+            VaultExecutor::new(DNExecutor::new(params)).await?.run().await
+        }
+        VaultParams::LRTC(params) => {
+            VaultExecutor::new(LRTCExecutor::new(params)).await?.run().await
+        }
+        VaultParams::LongPP(params) => {
+            VaultExecutor::new(LongPPExecutor::new(params)).await?.run().await
+        }
+        VaultParams::Basis(params) => {
+            VaultExecutor::new(BasisExecutor::new(params)).await?.run().await
+        }
+    }
+}
+
+/// Runs the executor with the vault's own config and names, so that nothing is read from
+/// (or shared through) env vars between vaults in the same process
+async fn run_scoped(
+    params: VaultParams,
+    ctx: Arc<VaultContext>,
+    config: Arc<LyraConfig>,
+    params_path: PathBuf,
+) -> Result<()> {
+    let health = ctx.health.clone();
+    let mut ctx = (*ctx).clone();
+    let listener = events::tsa_event_listener(&ctx.vault_name).map(|(feed, listener)| {
+        ctx.tsa_events = Some(feed);
+        listener
+    });
+    let guard = risk_guard(params.risk_limits(), params.dry_run()).map(|(halt, guard)| {
+        ctx.risk_halt = Some(halt);
+        guard
+    });
+    let monitor = margin_monitor(params.margin_monitor(), params.dry_run());
+    ctx.margin_limit = params.margin_limit();
+    ctx.fee_budget = params.fee_budget().map(|params| Arc::new(FeeTracker::new(params)));
+    ctx.http_fallback = params.http_fallback().map(|params| Arc::new(HttpFallback::new(params)));
+    let reconciler = reconciler(params.reconcile()).map(|(gate, reconciler)| {
+        ctx.reconcile_gate = Some(gate);
+        reconciler
+    });
+    let maker = rfq_maker(params.rfq_maker(), params.dry_run());
+    let reloader = params_reloader(params.params_reload(), params_path, ctx.live_params.clone());
+    let admin_commands = run_admin_commands(ctx.admin.clone(), params.dry_run());
+    let (watch_params, currency) = params.instrument_watch().unzip();
+    let watcher =
+        instrument_watcher(watch_params, currency.unwrap_or_default()).map(|(feed, watcher)| {
+            ctx.instrument_events = Some(feed);
+            watcher
+        });
+    let run = async {
+        tokio::select! {
+            res = run_executor(params) => res,
+            _ = run_optional(listener) => Err(Error::msg("TSA event listener exited")),
+            res = run_optional(guard) => res.and(Err(Error::msg("Risk guard exited"))),
+            res = run_optional(monitor) => res.and(Err(Error::msg("Margin monitor exited"))),
+            res = run_optional(reconciler) => res.and(Err(Error::msg("Reconciler exited"))),
+            res = run_optional(maker) => res.and(Err(Error::msg("RFQ maker exited"))),
+            res = run_optional(reloader) => res.and(Err(Error::msg("Params reloader exited"))),
+            res = admin_commands => res.and(Err(Error::msg("Admin commands exited"))),
+            res = run_optional(watcher) => res.and(Err(Error::msg("Instrument watcher exited"))),
+        }
+    };
+    let res = LyraConfig::scope(config, VaultContext::scope(Arc::new(ctx), run)).await;
+    if let Err(e) = &res {
+        health.executor_failed(e);
+    }
+    res
+}
+
+/// Runs the task if there is one, otherwise never resolves
+async fn run_optional<T>(task: Option<impl Future<Output = T>>) -> T {
+    match task {
+        Some(task) => task.await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Starts the admin API if ADMIN_PORT is set, see `serve_admin`
+fn spawn_admin_server(admins: &AdminRegistry, registry: &HealthRegistry) {
+    let port = match std::env::var("ADMIN_PORT") {
+        Ok(port) => port,
+        Err(_) => return,
+    };
+    let (admins, registry) = (admins.clone(), registry.clone());
+    tokio::spawn(async move {
+        if let Err(e) = serve_admin(port, admins, registry).await {
+            error!("Admin server failed: {:?}", e);
+        }
+    });
+}
+
+/// Starts the `/healthz` and `/status` server if HEALTH_PORT is set
+fn spawn_health_server(registry: &HealthRegistry) {
+    let port = match std::env::var("HEALTH_PORT") {
+        Ok(port) => port,
+        Err(_) => return,
+    };
+    let registry = registry.clone();
+    tokio::spawn(async move {
+        if let Err(e) = serve_health(format!("0.0.0.0:{port}"), registry).await {
+            error!("Health server failed: {:?}", e);
+        }
+    });
+}
+
+/// Runs one vault, `params_path` is the file its params were read from
+pub async fn run_single(params: VaultParams, params_path: PathBuf) -> Result<()> {
+    let registry = HealthRegistry::default();
+    let admins = AdminRegistry::default();
+    let mut ctx = params.context();
+    ctx.health = registry.register(&ctx.vault_name);
+    ctx.admin = admins.register(&ctx.vault_name);
+    let ctx = Arc::new(ctx);
+    let env = params.env();
+    println!("Setting up {} env for {} executor", env, params.kind());
+    setup_env_for(&env).await;
+    let session_key = ensure_session_key(&env, Some(&ctx.vault_name.to_lowercase())).await?;
+    info!("{} executor params: {:?}", params.kind(), params);
+
+    let config = vault_config(&ctx, &env, session_key).await?;
+    spawn_health_server(&registry);
+    spawn_admin_server(&admins, &registry);
+    info!("Starting {} executor", params.kind());
+    let task_handle = tokio::spawn(run_scoped(params, ctx, config, params_path));
+    let res = task_handle.await?;
+    if let Err(e) = res {
+        error!("Executor failed: {:?}", e);
+    }
+    Ok(())
+}
+
+/// Multi vault mode: one supervised executor task per vault, each with its own session key,
+/// owner and subaccount. Private WS connections stay per vault since a login binds the
+/// connection to a single owner.
+pub async fn run_multi(params: Vec<VaultParams>, params_path: PathBuf) -> Result<()> {
+    let env = params.first().ok_or(Error::msg("No vaults in params"))?.env();
+    if params.iter().any(|p| p.env() != env) {
+        return Err(Error::msg("All vaults must use the same env in multi mode"));
+    }
+    let mut vault_names = params.iter().map(|p| p.context().vault_name).collect::<Vec<_>>();
+    vault_names.sort();
+    vault_names.dedup();
+    if vault_names.len() != params.len() {
+        return Err(Error::msg("Vault names must be unique in multi mode"));
+    }
+    println!("Setting up {} env for {} vaults", env, params.len());
+    setup_env_for(&env).await;
+
+    let registry = HealthRegistry::default();
+    let admins = AdminRegistry::default();
+    spawn_health_server(&registry);
+    spawn_admin_server(&admins, &registry);
+    let mut handles = vec![];
+    for vault_params in params {
+        let mut ctx = vault_params.context();
+        ctx.health = registry.register(&ctx.vault_name);
+        ctx.admin = admins.register(&ctx.vault_name);
+        let ctx = Arc::new(ctx);
+        let session_key = load_session_key(&env, &ctx.vault_name.to_lowercase()).await?;
+        let config = vault_config(&ctx, &env, Some(session_key)).await?;
+        info!("Starting {} executor with params: {:?}", ctx.vault_name, vault_params);
+        let name = ctx.vault_name.clone();
+        let params_path = params_path.clone();
+        handles.push(tokio::spawn(supervise(name, move || {
+            run_scoped(vault_params.clone(), ctx.clone(), config.clone(), params_path.clone())
+        })));
+    }
+    futures::future::try_join_all(handles).await?;
+    Ok(())
+}
+
+pub fn params_path(json_name: &str) -> String {
+    format!("./params/{json_name}.json")
+}
+
+pub async fn read_params(json_name: &str) -> Result<serde_json::Value> {
+    let params = tokio::fs::read_to_string(params_path(json_name)).await?;
+    Ok(serde_json::from_str(&params)?)
+}
+
+/// Replays a market data recording against the option or spot auction of a vault offline,
+/// printing one JSON report per simulated `spread_per_min`
+pub async fn run_backtest_mode(json_name: &str) -> Result<()> {
+    init_logging();
+    let params: BacktestParams = serde_json::from_value(read_params(json_name).await?)?;
+    let vault_params = match VaultParams::from_value(read_params(&params.vault_params).await?)? {
+        VaultParams::DN(params) => params.covered_call,
+        VaultParams::LRTC(params) => params,
+        VaultParams::LongPP(_) | VaultParams::Basis(_) => {
+            return Err(Error::msg("Backtests only support orderbook auctions (LRTC or DN)"));
+        }
+    };
+    let reports = run_backtest(&params, &vault_params).await?;
+    for report in reports {
+        println!("{}", serde_json::to_string(&report)?);
+    }
+    Ok(())
+}
+
+/// Records the tickers and orderbooks of the configured instruments until stopped,
+/// restarting the subscription (into a new file) whenever it fails
+pub async fn run_recorder_mode(json_name: &str) -> Result<()> {
+    let params: RecorderParams = serde_json::from_value(read_params(json_name).await?)?;
+    setup_env_for(&params.env).await;
+    LyraConfig::init(LyraConfig::from_env()?.with_env(&params.env))?;
+    supervise("recorder".to_string(), || run_recorder(&params)).await;
+    Ok(())
+}
+
+/// Parses and validates the params of every vault in the file, printing the type of each
+pub async fn run_check_mode(json_name: &str) -> Result<()> {
+    let vaults = match read_params(json_name).await? {
+        serde_json::Value::Array(vaults) => vaults,
+        params => vec![params],
+    };
+    for value in vaults {
+        let params = VaultParams::from_value(value)?;
+        println!("{} params of {} are valid", params.kind(), params.context().vault_name);
+    }
+    Ok(())
+}
+
+/// Prints the projected next epoch of the LRTC or DN vault (option, size, premium, schedule and
+/// margin at the current market) without trading, see `project_epoch`
+pub async fn run_plan_mode(json_name: &str) -> Result<()> {
+    let params = VaultParams::from_value(read_params(json_name).await?)?;
+    let ctx = params.context();
+    let lrtc_params = match params {
+        VaultParams::DN(params) => params.covered_call,
+        VaultParams::LRTC(params) => params,
+        VaultParams::LongPP(_) | VaultParams::Basis(_) => {
+            return Err(Error::msg("Epoch plans only support LRTC or DN vaults"));
+        }
+    };
+    let env = &lrtc_params.env;
+    setup_env_for(env).await;
+    let session_key = ensure_session_key(env, Some(&ctx.vault_name.to_lowercase())).await?;
+    let config = vault_config(&ctx, env, session_key).await?;
+    let plan =
+        LyraConfig::scope(config, VaultContext::scope(Arc::new(ctx), project_epoch(&lrtc_params)))
+            .await?;
+    println!("{}", serde_json::to_string_pretty(&plan)?);
+    Ok(())
+}
+
+/// Syncs the ledger of the vault (in LEDGER_DIR, default ./ledger) and exports its entries and
+/// per-epoch PnL as CSV to the output directory (default ./reports)
+pub async fn run_ledger_mode(json_name: &str, out_dir: Option<&String>) -> Result<()> {
+    let params = VaultParams::from_value(read_params(json_name).await?)?;
+    let ctx = params.context();
+    let env = params.env();
+    setup_env_for(&env).await;
+    let session_key = ensure_session_key(&env, Some(&ctx.vault_name.to_lowercase())).await?;
+    let config = vault_config(&ctx, &env, session_key).await?;
+    let ledger_dir = std::env::var("LEDGER_DIR").unwrap_or("./ledger".to_string());
+    let ledger = Ledger::open_in(&PathBuf::from(ledger_dir), &ctx.vault_name)?
+        .with_yield_instruments(params.yield_instruments());
+    let subaccount_ids = ctx.subaccounts.all_ids(config.subaccount_id()?);
+    LyraConfig::scope(config, ledger.sync(&subaccount_ids)).await?;
+    let out_dir = out_dir.cloned().unwrap_or("./reports".to_string());
+    ledger.export_csv(&PathBuf::from(out_dir), &ctx.vault_name.to_lowercase())
+}
+
+/// Deposits from the DEPOSIT_SIGNER wallet (OWNER by default) into the subaccount, SUBACCOUNT_ID
+/// unless given, see `deposit_from_wallet`
+pub async fn run_deposit_mode(
+    asset_name: &str,
+    amount: &str,
+    subaccount_id: Option<&String>,
+) -> Result<()> {
+    let env = setup_env().await;
+    LyraConfig::init(env_config(&env).await?)?;
+    let amount = BigDecimal::from_str(amount)?;
+    let subaccount_id = match subaccount_id {
+        Some(subaccount_id) => subaccount_id.parse()?,
+        None => LyraConfig::current()?.subaccount_id()?,
+    };
+    let signer_name = std::env::var("DEPOSIT_SIGNER").unwrap_or("OWNER".to_string());
+    let balance =
+        actions::deposit_from_wallet(&signer_name, asset_name, &amount, subaccount_id).await?;
+    info!("Deposited {} {} into {}, balance {}", amount, asset_name, subaccount_id, balance);
+    Ok(())
+}
+
+/// Entry point of the binary: `<json name>` runs the vault(s) in `./params/<json name>.json`,
+/// `backtest <json name>` or `record <json name>` a backtest or the market data recorder,
+/// `ledger <json name> [out dir]` exports the accounting ledger of the vault,
+/// `plan <json name>` prints the projected next epoch of the vault,
+/// `check <json name>` validates the params of the vault(s) without running them,
+/// `deposit <asset> <amount> [subaccount id]` deposits from a wallet
+pub async fn run_from_args(args: Vec<String>) -> Result<()> {
+    println!("Reading params from json file");
+    let json_name = args.get(1).ok_or(Error::msg("No json name provided"))?;
+    match json_name.as_str() {
+        "backtest" => {
+            let backtest_name = args.get(2).ok_or(Error::msg("No backtest json name provided"))?;
+            return run_backtest_mode(backtest_name).await;
+        }
+        "record" => {
+            let recorder_name = args.get(2).ok_or(Error::msg("No recorder json name provided"))?;
+            return run_recorder_mode(recorder_name).await;
+        }
+        "plan" => {
+            let vault_name = args.get(2).ok_or(Error::msg("No vault json name provided"))?;
+            return run_plan_mode(vault_name).await;
+        }
+        "ledger" => {
+            let vault_name = args.get(2).ok_or(Error::msg("No vault json name provided"))?;
+            return run_ledger_mode(vault_name, args.get(3)).await;
+        }
+        "check" => {
+            let vault_name = args.get(2).ok_or(Error::msg("No vault json name provided"))?;
+            return run_check_mode(vault_name).await;
+        }
+        "deposit" => {
+            let asset_name = args.get(2).ok_or(Error::msg("No asset provided"))?;
+            let amount = args.get(3).ok_or(Error::msg("No amount provided"))?;
+            return run_deposit_mode(asset_name, amount, args.get(4)).await;
+        }
+        _ => {}
+    }
+    let params = read_params(json_name).await?;
+    // watched by the params reloader of the vaults
+    let path = PathBuf::from(params_path(json_name));
+    match params {
+        serde_json::Value::Array(vaults) => {
+            let vaults = vaults.into_iter().map(VaultParams::from_value).collect::<Result<_>>()?;
+            run_multi(vaults, path).await?
+        }
+        params => run_single(VaultParams::from_value(params)?, path).await?,
+    }
+
+    Ok(())
+}
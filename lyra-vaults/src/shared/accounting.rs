@@ -0,0 +1,590 @@
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use lyra_client::auth::get_auth_headers;
+use lyra_client::json_rpc::http_rpc;
+use lyra_client::market_history::get_option_settlements;
+use orderbook_types::generated::private_get_deposit_history::{
+    PrivateGetDepositHistoryParamsSchema, PrivateGetDepositHistoryResponseSchema,
+    TxStatus as DepositStatus,
+};
+use orderbook_types::generated::private_get_funding_history::{
+    PrivateGetFundingHistoryParamsSchema, PrivateGetFundingHistoryResponseSchema,
+};
+use orderbook_types::generated::private_get_interest_history::{
+    PrivateGetInterestHistoryParamsSchema, PrivateGetInterestHistoryResponseSchema,
+};
+use orderbook_types::generated::private_get_withdrawal_history::{
+    PrivateGetWithdrawalHistoryParamsSchema, PrivateGetWithdrawalHistoryResponseSchema,
+};
+use orderbook_types::types::orders::{
+    Direction, GetTradesParams, GetTradesResponse, TradeResponse, TxStatus,
+};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+const TRADES_PAGE_SIZE: i64 = 1000;
+const SYNC_OVERLAP_MS: i64 = 3600 * 1000; // re-fetched on every sync, ids are only inserted once
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Fill,
+    Fee,
+    Deposit,
+    Withdrawal,
+    Settlement,
+    Funding,
+    Interest,
+}
+
+impl EntryKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::Fill => "fill",
+            EntryKind::Fee => "fee",
+            EntryKind::Deposit => "deposit",
+            EntryKind::Withdrawal => "withdrawal",
+            EntryKind::Settlement => "settlement",
+            EntryKind::Funding => "funding",
+            EntryKind::Interest => "interest",
+        }
+    }
+
+    fn from_str(kind: &str) -> Option<Self> {
+        match kind {
+            "fill" => Some(EntryKind::Fill),
+            "fee" => Some(EntryKind::Fee),
+            "deposit" => Some(EntryKind::Deposit),
+            "withdrawal" => Some(EntryKind::Withdrawal),
+            "settlement" => Some(EntryKind::Settlement),
+            "funding" => Some(EntryKind::Funding),
+            "interest" => Some(EntryKind::Interest),
+            _ => None,
+        }
+    }
+}
+
+/// One ledger row. Amounts are signed from the vault's side: fills are positive when bought,
+/// `cash_flow` is the cash received (negative when paid), e.g. the premium of a sold option.
+#[derive(Debug, Clone, Serialize)]
+pub struct LedgerEntry {
+    pub id: String, // trade id, deposit transaction id, withdrawal tx hash, ...
+    pub timestamp_ms: i64,
+    pub kind: EntryKind,
+    pub instrument_name: String, // or the asset of deposits and withdrawals
+    pub amount: BigDecimal,
+    pub price: BigDecimal,      // fills and settlements only
+    pub mark_price: BigDecimal, // fills only, for the slippage vs mark
+    pub cash_flow: BigDecimal,
+    pub realized_pnl: BigDecimal, // perp fills only
+}
+
+/// PnL of one epoch split by source, from the first epoch start up to the next one
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EpochPnl {
+    pub epoch: i64,
+    pub start_ms: i64,
+    pub end_ms: Option<i64>,        // None for the current epoch
+    pub option_premium: BigDecimal, // cash received for options sold net of options bought
+    pub option_settlement: BigDecimal,
+    pub spot_slippage: BigDecimal, // spot fills vs mark, negative when filled worse than mark
+    pub perp_pnl: BigDecimal,
+    pub fees: BigDecimal, // negative when paid
+    pub funding: BigDecimal,
+    pub interest: BigDecimal, // paid by the protocol on cash, negative when borrowing
+    pub cash_yield: BigDecimal, // net cash of the yield wrapper fills, i.e. its accrual
+    pub total: BigDecimal,
+    pub deposits: BigDecimal, // flows in and out of the vault, not part of the PnL
+    pub withdrawals: BigDecimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InstrumentKind {
+    Option,
+    Perp,
+    Spot,
+}
+
+/// Options are named like ETH-20240628-3000-C, perps like ETH-PERP and spot like WEETH-USDC
+fn instrument_kind(instrument_name: &str) -> InstrumentKind {
+    match instrument_name.split('-').collect::<Vec<_>>().as_slice() {
+        [_, _, _, "C" | "P"] => InstrumentKind::Option,
+        [_, "PERP"] => InstrumentKind::Perp,
+        _ => InstrumentKind::Spot,
+    }
+}
+
+/// Local sqlite ledger of a vault's fills, fees, deposits, withdrawals, settlements, funding
+/// and interest payments, kept under LEDGER_DIR. Entries are synced from the subaccount's
+/// history and attributed to the epoch (see `VaultStrategy::is_epoch_start`) they happened in.
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    conn: Arc<Mutex<Connection>>,
+    yield_instruments: Vec<String>, // see `VaultStrategy::yield_instruments`
+}
+
+impl Ledger {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS entries (
+                id TEXT PRIMARY KEY,
+                timestamp_ms INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                instrument_name TEXT NOT NULL,
+                amount TEXT NOT NULL,
+                price TEXT NOT NULL,
+                mark_price TEXT NOT NULL,
+                cash_flow TEXT NOT NULL,
+                realized_pnl TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS entries_timestamp ON entries (timestamp_ms);
+            CREATE TABLE IF NOT EXISTS epochs (
+                epoch INTEGER PRIMARY KEY,
+                start_ms INTEGER NOT NULL
+            );",
+        )?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)), yield_instruments: vec![] })
+    }
+
+    /// Books the fills of these instruments as cash yield instead of spot trades
+    pub fn with_yield_instruments(mut self, instrument_names: Vec<String>) -> Self {
+        self.yield_instruments = instrument_names;
+        self
+    }
+
+    /// The ledger of the vault in the directory, `{vault}_ledger.sqlite`
+    pub fn open_in(dir: &Path, vault_name: &str) -> Result<Self> {
+        Ledger::open(&dir.join(format!("{}_ledger.sqlite", vault_name.to_lowercase())))
+    }
+
+    /// The vault's ledger if LEDGER_DIR is set, None keeps no ledger
+    pub fn from_env(vault_name: &str) -> Result<Option<Self>> {
+        let Ok(dir) = std::env::var("LEDGER_DIR") else {
+            return Ok(None);
+        };
+        Ok(Some(Ledger::open_in(&PathBuf::from(dir), vault_name)?))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Inserts the entries not yet recorded and returns how many were new
+    pub fn record(&self, entries: &[LedgerEntry]) -> Result<usize> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let mut inserted = 0;
+        for e in entries {
+            inserted += tx.execute(
+                "INSERT OR IGNORE INTO entries VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    e.id,
+                    e.timestamp_ms,
+                    e.kind.as_str(),
+                    e.instrument_name,
+                    e.amount.to_string(),
+                    e.price.to_string(),
+                    e.mark_price.to_string(),
+                    e.cash_flow.to_string(),
+                    e.realized_pnl.to_string(),
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// Starts the epoch numbered by the vault's epoch counter (see `OrderLabels`), entries from
+    /// `start_ms` on are attributed to it. An epoch started before keeps its start.
+    pub fn start_epoch(&self, epoch: u64, start_ms: i64) -> Result<()> {
+        let conn = self.lock();
+        let query = "INSERT OR IGNORE INTO epochs VALUES (?1, ?2)";
+        if conn.execute(query, params![epoch as i64, start_ms])? > 0 {
+            info!("Ledger epoch {} started at {}", epoch, start_ms);
+        }
+        Ok(())
+    }
+
+    fn last_timestamp_ms(&self) -> Result<i64> {
+        let conn = self.lock();
+        let query = "SELECT COALESCE(MAX(timestamp_ms), 0) FROM entries WHERE kind != 'settlement'";
+        Ok(conn.query_row(query, [], |r| r.get(0))?)
+    }
+
+    pub fn entries(&self) -> Result<Vec<LedgerEntry>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare("SELECT * FROM entries ORDER BY timestamp_ms, id")?;
+        let rows = stmt.query_map([], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, i64>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, String>(3)?,
+                [r.get::<_, String>(4)?, r.get(5)?, r.get(6)?, r.get(7)?, r.get(8)?],
+            ))
+        })?;
+        let mut entries = vec![];
+        for row in rows {
+            let (id, timestamp_ms, kind, instrument_name, decimals) = row?;
+            let Some(kind) = EntryKind::from_str(&kind) else {
+                continue;
+            };
+            let [amount, price, mark_price, cash_flow, realized_pnl] =
+                decimals.map(|d| BigDecimal::from_str(&d).unwrap_or_default());
+            entries.push(LedgerEntry {
+                id,
+                timestamp_ms,
+                kind,
+                instrument_name,
+                amount,
+                price,
+                mark_price,
+                cash_flow,
+                realized_pnl,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn epochs(&self) -> Result<Vec<(i64, i64)>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare("SELECT epoch, start_ms FROM epochs ORDER BY epoch")?;
+        let epochs = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        Ok(epochs.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// PnL attribution of every epoch, entries before the first epoch are left out
+    pub fn epoch_pnl(&self) -> Result<Vec<EpochPnl>> {
+        let epochs = self.epochs()?;
+        let entries = self.entries()?;
+        let mut pnls = vec![];
+        for (i, (epoch, start_ms)) in epochs.iter().enumerate() {
+            let end_ms = epochs.get(i + 1).map(|(_, start_ms)| *start_ms);
+            let mut pnl =
+                EpochPnl { epoch: *epoch, start_ms: *start_ms, end_ms, ..Default::default() };
+            let in_epoch = entries.iter().filter(|e| {
+                e.timestamp_ms >= *start_ms && end_ms.map_or(true, |end| e.timestamp_ms < end)
+            });
+            for e in in_epoch {
+                let is_yield = self.yield_instruments.contains(&e.instrument_name);
+                match (e.kind, instrument_kind(&e.instrument_name)) {
+                    (EntryKind::Fill, _) if is_yield => pnl.cash_yield += &e.cash_flow,
+                    (EntryKind::Fill, InstrumentKind::Option) => pnl.option_premium += &e.cash_flow,
+                    (EntryKind::Fill, InstrumentKind::Perp) => pnl.perp_pnl += &e.realized_pnl,
+                    // bought above or sold below mark costs the difference
+                    (EntryKind::Fill, InstrumentKind::Spot) => {
+                        pnl.spot_slippage += (&e.mark_price - &e.price) * &e.amount
+                    }
+                    (EntryKind::Fee, _) => pnl.fees += &e.cash_flow,
+                    (EntryKind::Settlement, _) => pnl.option_settlement += &e.cash_flow,
+                    (EntryKind::Funding, _) => pnl.funding += &e.cash_flow,
+                    (EntryKind::Interest, _) => pnl.interest += &e.cash_flow,
+                    (EntryKind::Deposit, _) => pnl.deposits += &e.amount,
+                    (EntryKind::Withdrawal, _) => pnl.withdrawals += &e.amount,
+                }
+            }
+            pnl.total = &pnl.option_premium
+                + &pnl.option_settlement
+                + &pnl.spot_slippage
+                + &pnl.perp_pnl
+                + &pnl.fees
+                + &pnl.funding
+                + &pnl.interest
+                + &pnl.cash_yield;
+            pnls.push(pnl);
+        }
+        Ok(pnls)
+    }
+
+    /// Writes `{prefix}_entries.csv` and `{prefix}_epochs.csv` to the directory
+    pub fn export_csv(&self, dir: &Path, prefix: &str) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let mut entries_csv = String::from(
+            "id,timestamp_ms,kind,instrument_name,amount,price,mark_price,cash_flow,realized_pnl\n",
+        );
+        for e in self.entries()? {
+            entries_csv += &format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                e.id,
+                e.timestamp_ms,
+                e.kind.as_str(),
+                e.instrument_name,
+                e.amount,
+                e.price,
+                e.mark_price,
+                e.cash_flow,
+                e.realized_pnl
+            );
+        }
+        let mut epochs_csv = String::from(
+            "epoch,start_ms,end_ms,option_premium,option_settlement,spot_slippage,perp_pnl,fees,\
+            funding,interest,cash_yield,total,deposits,withdrawals\n",
+        );
+        for p in self.epoch_pnl()? {
+            epochs_csv += &format!(
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                p.epoch,
+                p.start_ms,
+                p.end_ms.map(|end| end.to_string()).unwrap_or_default(),
+                p.option_premium,
+                p.option_settlement,
+                p.spot_slippage,
+                p.perp_pnl,
+                p.fees,
+                p.funding,
+                p.interest,
+                p.cash_yield,
+                p.total,
+                p.deposits,
+                p.withdrawals
+            );
+        }
+        std::fs::write(dir.join(format!("{}_entries.csv", prefix)), entries_csv)?;
+        std::fs::write(dir.join(format!("{}_epochs.csv", prefix)), epochs_csv)?;
+        info!("Ledger exported to {:?}", dir);
+        Ok(())
+    }
+
+    /// Records the history of the vault's subaccounts since the last sync (with some overlap)
+    pub async fn sync(&self, subaccount_ids: &[i64]) -> Result<usize> {
+        let start_ms = (self.last_timestamp_ms()? - SYNC_OVERLAP_MS).max(0);
+        let end_ms = chrono::Utc::now().timestamp_millis();
+        let mut entries = vec![];
+        for &subaccount_id in subaccount_ids {
+            entries.extend(fetch_fills(subaccount_id, start_ms, end_ms).await?);
+            entries.extend(fetch_transfers(subaccount_id, start_ms, end_ms).await?);
+            entries.extend(fetch_funding(subaccount_id, start_ms, end_ms).await?);
+            entries.extend(fetch_interest(subaccount_id, start_ms, end_ms).await?);
+            entries.extend(fetch_settlements(subaccount_id).await?);
+        }
+        let inserted = self.record(&entries)?;
+        info!("Ledger synced, {} new entries", inserted);
+        Ok(inserted)
+    }
+}
+
+/// All trades of the subaccount in the time range, paging through the trade history
+pub async fn fetch_trades(
+    subaccount_id: i64,
+    start_ms: i64,
+    end_ms: i64,
+) -> Result<Vec<TradeResponse>> {
+    let mut all_trades = vec![];
+    for page in 1.. {
+        let params = GetTradesParams {
+            subaccount_id,
+            instrument_name: None,
+            order_id: None,
+            quote_id: None,
+            from_timestamp: start_ms,
+            to_timestamp: end_ms,
+            page,
+            page_size: TRADES_PAGE_SIZE,
+        };
+        let headers = get_auth_headers().await?;
+        let trades =
+            http_rpc::<_, GetTradesResponse>("private/get_trade_history", params, Some(headers))
+                .await?
+                .into_result()?
+                .result
+                .trades;
+        let num_trades = trades.len() as i64;
+        all_trades.extend(trades);
+        if num_trades < TRADES_PAGE_SIZE {
+            break;
+        }
+    }
+    Ok(all_trades)
+}
+
+/// Settled fills and their fees, reverted and pending trades are left for a later sync
+async fn fetch_fills(subaccount_id: i64, start_ms: i64, end_ms: i64) -> Result<Vec<LedgerEntry>> {
+    let trades = fetch_trades(subaccount_id, start_ms, end_ms).await?;
+    let mut entries = vec![];
+    for trade in trades.into_iter().filter(|t| t.tx_status == TxStatus::Settled) {
+        let amount = match trade.direction {
+            Direction::Buy => trade.trade_amount.clone(),
+            Direction::Sell => -trade.trade_amount.clone(),
+        };
+        entries.push(LedgerEntry {
+            id: format!("{}-fee", trade.trade_id),
+            timestamp_ms: trade.timestamp,
+            kind: EntryKind::Fee,
+            instrument_name: trade.instrument_name.clone(),
+            amount: BigDecimal::zero(),
+            price: BigDecimal::zero(),
+            mark_price: BigDecimal::zero(),
+            cash_flow: -trade.trade_fee.clone(),
+            realized_pnl: BigDecimal::zero(),
+        });
+        entries.push(LedgerEntry {
+            id: trade.trade_id,
+            timestamp_ms: trade.timestamp,
+            kind: EntryKind::Fill,
+            instrument_name: trade.instrument_name,
+            cash_flow: -(&amount * &trade.trade_price),
+            amount,
+            price: trade.trade_price,
+            mark_price: trade.mark_price,
+            realized_pnl: trade.realized_pnl,
+        });
+    }
+    Ok(entries)
+}
+
+async fn fetch_transfers(
+    subaccount_id: i64,
+    start_ms: i64,
+    end_ms: i64,
+) -> Result<Vec<LedgerEntry>> {
+    let headers = get_auth_headers().await?;
+    let deposits = http_rpc::<_, PrivateGetDepositHistoryResponseSchema>(
+        "private/get_deposit_history",
+        PrivateGetDepositHistoryParamsSchema {
+            subaccount_id,
+            start_timestamp: start_ms,
+            end_timestamp: end_ms,
+        },
+        Some(headers.clone()),
+    )
+    .await?
+    .into_result()?
+    .result
+    .events;
+    let withdrawals = http_rpc::<_, PrivateGetWithdrawalHistoryResponseSchema>(
+        "private/get_withdrawal_history",
+        PrivateGetWithdrawalHistoryParamsSchema {
+            subaccount_id,
+            start_timestamp: start_ms,
+            end_timestamp: end_ms,
+        },
+        Some(headers),
+    )
+    .await?
+    .into_result()?
+    .result
+    .events;
+    let deposits =
+        deposits.into_iter().filter(|d| d.tx_status == DepositStatus::Settled).map(|d| {
+            transfer_entry(
+                d.transaction_id.to_string(),
+                d.timestamp,
+                EntryKind::Deposit,
+                d.asset,
+                d.amount,
+            )
+        });
+    let withdrawals = withdrawals
+        .into_iter()
+        .map(|w| transfer_entry(w.tx_hash, w.timestamp, EntryKind::Withdrawal, w.asset, -w.amount));
+    Ok(deposits.chain(withdrawals).collect())
+}
+
+fn transfer_entry(
+    id: String,
+    timestamp_ms: i64,
+    kind: EntryKind,
+    asset: String,
+    amount: BigDecimal,
+) -> LedgerEntry {
+    LedgerEntry {
+        id,
+        timestamp_ms,
+        kind,
+        instrument_name: asset,
+        amount,
+        price: BigDecimal::zero(),
+        mark_price: BigDecimal::zero(),
+        cash_flow: BigDecimal::zero(),
+        realized_pnl: BigDecimal::zero(),
+    }
+}
+
+async fn fetch_funding(subaccount_id: i64, start_ms: i64, end_ms: i64) -> Result<Vec<LedgerEntry>> {
+    let headers = get_auth_headers().await?;
+    let events = http_rpc::<_, PrivateGetFundingHistoryResponseSchema>(
+        "private/get_funding_history",
+        PrivateGetFundingHistoryParamsSchema {
+            subaccount_id,
+            instrument_name: None,
+            start_timestamp: start_ms,
+            end_timestamp: end_ms,
+        },
+        Some(headers),
+    )
+    .await?
+    .into_result()?
+    .result
+    .events;
+    let entries = events.into_iter().map(|e| LedgerEntry {
+        id: format!("funding-{}-{}", e.instrument_name, e.timestamp),
+        timestamp_ms: e.timestamp,
+        kind: EntryKind::Funding,
+        instrument_name: e.instrument_name,
+        amount: BigDecimal::zero(),
+        price: BigDecimal::zero(),
+        mark_price: BigDecimal::zero(),
+        cash_flow: e.funding,
+        realized_pnl: BigDecimal::zero(),
+    });
+    Ok(entries.collect())
+}
+
+/// Interest received on positive cash balances (or paid on borrowed cash)
+async fn fetch_interest(
+    subaccount_id: i64,
+    start_ms: i64,
+    end_ms: i64,
+) -> Result<Vec<LedgerEntry>> {
+    let headers = get_auth_headers().await?;
+    let events = http_rpc::<_, PrivateGetInterestHistoryResponseSchema>(
+        "private/get_interest_history",
+        PrivateGetInterestHistoryParamsSchema {
+            subaccount_id,
+            start_timestamp: start_ms,
+            end_timestamp: end_ms,
+        },
+        Some(headers),
+    )
+    .await?
+    .into_result()?
+    .result
+    .events;
+    let entries = events.into_iter().map(|e| LedgerEntry {
+        id: format!("interest-{}", e.timestamp),
+        timestamp_ms: e.timestamp,
+        kind: EntryKind::Interest,
+        instrument_name: "USDC".to_string(),
+        amount: BigDecimal::zero(),
+        price: BigDecimal::zero(),
+        mark_price: BigDecimal::zero(),
+        cash_flow: e.interest,
+        realized_pnl: BigDecimal::zero(),
+    });
+    Ok(entries.collect())
+}
+
+/// All option settlements of the subaccount (fetched page by page), timestamped at expiry
+async fn fetch_settlements(subaccount_id: i64) -> Result<Vec<LedgerEntry>> {
+    let settlements = get_option_settlements(subaccount_id).await?;
+    let entries = settlements.into_iter().map(|s| LedgerEntry {
+        id: format!("settlement-{}", s.instrument_name),
+        timestamp_ms: s.expiry * 1000,
+        kind: EntryKind::Settlement,
+        instrument_name: s.instrument_name,
+        amount: s.amount,
+        price: s.settlement_price,
+        mark_price: BigDecimal::zero(),
+        cash_flow: s.option_settlement_pnl,
+        realized_pnl: BigDecimal::zero(),
+    });
+    Ok(entries.collect())
+}
@@ -0,0 +1,250 @@
+use crate::shared::context::VaultContext;
+use crate::shared::health::HealthRegistry;
+use anyhow::{Error, Result};
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use lyra_client::json_rpc::{WsClient, WsClientExt};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::select;
+use tokio::sync::{watch, Notify};
+use tracing::{info, warn};
+
+/// Commands of the admin API to one vault, executed by its executor and `run_admin_commands`
+#[derive(Debug)]
+pub struct AdminControl {
+    paused: watch::Sender<bool>,
+    forced_stage: watch::Sender<Option<String>>,
+    cancel: Notify,
+    cancels_done: watch::Sender<u64>, // cancels run by `run_admin_commands`, failed ones included
+    stage: Mutex<Option<String>>,     // the stage as last entered, for the state dump
+}
+
+impl Default for AdminControl {
+    fn default() -> Self {
+        Self {
+            paused: watch::channel(false).0,
+            forced_stage: watch::channel(None).0,
+            cancel: Notify::new(),
+            cancels_done: watch::channel(0).0,
+            stage: Mutex::new(None),
+        }
+    }
+}
+
+impl AdminControl {
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Resolves once trading is paused, right away if it is
+    pub async fn paused(&self) {
+        let mut receiver = self.paused.subscribe();
+        let _ = receiver.wait_for(|paused| *paused).await;
+    }
+
+    /// Resolves once trading is resumed, right away if it is not paused
+    pub async fn resumed(&self) {
+        let mut receiver = self.paused.subscribe();
+        let _ = receiver.wait_for(|paused| !*paused).await;
+    }
+
+    /// Cancels all open orders of the vault once, the running stage may place new ones
+    pub fn cancel_all(&self) {
+        self.cancel.notify_one();
+    }
+
+    /// Cancels all open orders of the vault like `cancel_all`, resolving once the cancel ran
+    pub async fn cancel_all_and_wait(&self) {
+        let mut done = self.cancels_done.subscribe();
+        self.cancel_all();
+        let _ = done.changed().await;
+    }
+
+    /// Stops the running stage and enters the named one, see `VaultExecutor::forced_stage`
+    pub fn force_stage(&self, name: &str) {
+        self.forced_stage.send_replace(Some(name.to_string()));
+    }
+
+    /// Resolves with the name of the next forced stage, taking the request
+    pub async fn stage_forced(&self) -> String {
+        let mut receiver = self.forced_stage.subscribe();
+        let _ = receiver.wait_for(|name| name.is_some()).await;
+        self.forced_stage.send_replace(None).unwrap_or_default()
+    }
+
+    pub fn stage_entered(&self, stage: String) {
+        *self.stage.lock().unwrap() = Some(stage);
+    }
+
+    fn state(&self) -> Value {
+        json!({
+            "paused": self.is_paused(),
+            "stage": self.stage.lock().unwrap().clone(),
+            "forced_stage": self.forced_stage.borrow().clone(),
+        })
+    }
+}
+
+/// Admin controls of all vaults running in this process, keyed by vault name
+#[derive(Debug, Clone, Default)]
+pub struct AdminRegistry(Arc<RwLock<BTreeMap<String, Arc<AdminControl>>>>);
+
+impl AdminRegistry {
+    pub fn register(&self, vault_name: &str) -> Arc<AdminControl> {
+        let control = Arc::new(AdminControl::default());
+        if let Ok(mut controls) = self.0.write() {
+            controls.insert(vault_name.to_string(), control.clone());
+        }
+        control
+    }
+
+    fn get(&self, vault_name: &str) -> Option<Arc<AdminControl>> {
+        self.0.read().ok()?.get(vault_name).cloned()
+    }
+}
+
+/// Cancels the open orders of the vault (in all of its subaccounts) whenever trading is paused
+/// or a cancel is requested. Runs next to the executor, so cancels go through while a stage
+/// is busy.
+pub async fn run_admin_commands(admin: Arc<AdminControl>, dry_run: bool) -> Result<()> {
+    let subaccount_ids = VaultContext::current().subaccount_ids()?;
+    let mut paused = admin.paused.subscribe();
+    loop {
+        select! {
+            _ = admin.cancel.notified() => info!("Admin cancelling all orders"),
+            res = paused.changed() => {
+                res?;
+                if !*paused.borrow() {
+                    info!("Admin resumed trading");
+                    continue;
+                }
+                warn!("Admin paused trading, cancelling all orders");
+            }
+        }
+        if dry_run {
+            info!("Admin (dry run) would cancel all orders of {:?}", subaccount_ids);
+            admin.cancels_done.send_modify(|done| *done += 1);
+            continue;
+        }
+        let res = async {
+            let client = WsClient::new_client().await?;
+            client.login().await?.into_result()?;
+            for subaccount_id in subaccount_ids.iter() {
+                let res = client.cancel_all(*subaccount_id).await?.into_result()?;
+                info!("Admin cancelled all orders of {}: {:?}", subaccount_id, res.result);
+            }
+            Ok::<_, Error>(())
+        };
+        if let Err(e) = res.await {
+            warn!("Admin cancel all failed with {:#}", e);
+        }
+        admin.cancels_done.send_modify(|done| *done += 1);
+    }
+}
+
+#[derive(Clone)]
+struct AdminState {
+    token: String,
+    admins: AdminRegistry,
+    health: HealthRegistry,
+}
+
+/// Serves the admin API on localhost, every request needs `Authorization: Bearer <ADMIN_TOKEN>`:
+/// `POST /vaults/{vault}/pause`, `/resume`, `/cancel_all`, `/stage/{name}` and
+/// `GET /vaults/{vault}/state`
+pub async fn serve_admin(
+    port: String,
+    admins: AdminRegistry,
+    health: HealthRegistry,
+) -> Result<()> {
+    let token = std::env::var("ADMIN_TOKEN").map_err(|_| Error::msg("ADMIN_TOKEN is not set"))?;
+    let app = Router::new()
+        .route("/vaults/:vault/state", get(state))
+        .route("/vaults/:vault/stage/:name", post(force_stage))
+        .route("/vaults/:vault/:command", post(command))
+        .with_state(AdminState { token, admins, health });
+    let addr = format!("127.0.0.1:{port}");
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    info!("Admin server listening on {}", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+type AdminResponse = (StatusCode, Json<Value>);
+
+fn error(status: StatusCode, message: &str) -> AdminResponse {
+    (status, Json(json!({ "error": message })))
+}
+
+/// The control of the vault if the request carries the admin token
+fn authorize(
+    state: &AdminState,
+    headers: &HeaderMap,
+    vault: &str,
+) -> Result<Arc<AdminControl>, AdminResponse> {
+    let bearer = headers.get("authorization").and_then(|v| v.to_str().ok());
+    if bearer != Some(format!("Bearer {}", state.token).as_str()) {
+        return Err(error(StatusCode::UNAUTHORIZED, "invalid admin token"));
+    }
+    state.admins.get(vault).ok_or(error(StatusCode::NOT_FOUND, "unknown vault"))
+}
+
+async fn command(
+    State(state): State<AdminState>,
+    Path((vault, command)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> AdminResponse {
+    let admin = match authorize(&state, &headers, &vault) {
+        Ok(admin) => admin,
+        Err(res) => return res,
+    };
+    match command.as_str() {
+        "pause" => admin.pause(),
+        "resume" => admin.resume(),
+        "cancel_all" => admin.cancel_all(),
+        _ => return error(StatusCode::NOT_FOUND, "unknown command"),
+    }
+    warn!("Admin {} of {}", command, vault);
+    (StatusCode::ACCEPTED, Json(admin.state()))
+}
+
+async fn force_stage(
+    State(state): State<AdminState>,
+    Path((vault, name)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> AdminResponse {
+    let admin = match authorize(&state, &headers, &vault) {
+        Ok(admin) => admin,
+        Err(res) => return res,
+    };
+    warn!("Admin forcing stage {} of {}", name, vault);
+    admin.force_stage(&name);
+    (StatusCode::ACCEPTED, Json(admin.state()))
+}
+
+async fn state(
+    State(state): State<AdminState>,
+    Path(vault): Path<String>,
+    headers: HeaderMap,
+) -> AdminResponse {
+    let admin = match authorize(&state, &headers, &vault) {
+        Ok(admin) => admin,
+        Err(res) => return res,
+    };
+    let health = state.health.snapshot().remove(&vault);
+    let mut dump = admin.state();
+    dump["health"] = json!(health);
+    (StatusCode::OK, Json(dump))
+}
@@ -0,0 +1,18 @@
+use crate::shared::context::VaultContext;
+use serde_json::json;
+use tracing::{error, warn};
+
+/// Logs the alert and posts it to ALERT_WEBHOOK_URL if set (e.g. a Slack incoming webhook).
+/// Failing to deliver an alert is logged but never fails the caller.
+pub async fn send_alert(message: &str) {
+    let message = format!("[{}] {}", VaultContext::current().vault_name, message);
+    error!("ALERT {}", message);
+    let Ok(url) = std::env::var("ALERT_WEBHOOK_URL") else {
+        return;
+    };
+    let res = reqwest::Client::new().post(url).json(&json!({ "text": message })).send().await;
+    match res.and_then(|res| res.error_for_status()) {
+        Ok(_) => {}
+        Err(e) => warn!("Failed to send alert to webhook: {:?}", e),
+    }
+}
@@ -1,22 +1,47 @@
 use crate::helpers::{
-    sleep_till, subscribe_subaccount, subscribe_tickers, sync_subaccount, TickerInterval,
+    sleep_till, subscribe_orderbooks, subscribe_subaccounts, subscribe_tickers, subscribe_trades,
+    sync_subaccounts, TickerInterval,
 };
 use crate::market::{new_market_state, MarketState};
+use crate::shared::alert::send_alert;
+use crate::shared::context::VaultContext;
+use crate::shared::dry_run::DryRunRecorder;
+use crate::shared::fees::{FeeLevel, FeeTracker};
+use crate::shared::margin::{preview_order_margin, MarginLimitParams, MarginPreview};
+use crate::shared::oracle::PriceCheckParams;
+use crate::shared::params::{DepthLimitParams, MmpParams, TimeoutPolicy};
+use crate::shared::reload::ParamsSection;
+use crate::shared::report::{AuctionStats, ExecutionReport};
 use crate::shared::stages::ExecutorStage;
+use crate::shared::state_store::StateStore;
 use crate::web3::actions::{get_tsa_contract, sign_order, ProviderWithSigner, TSA};
 use anyhow::{Error, Result};
-use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive, Zero};
+use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, ToPrimitive, Zero};
 use core::fmt;
 use ethers::prelude::Middleware;
-use log::{info, warn};
-use lyra_client::actions::{Direction, OrderArgs, OrderType, TimeInForce};
+use lyra_client::actions::{
+    signature_expiry_sec, Direction, OrderArgs, OrderResponse, OrderType, TimeInForce,
+};
+use lyra_client::error::LyraError;
 use lyra_client::json_rpc::{WsClient, WsClientExt};
 use lyra_utils::black76::OptionContract;
-use orderbook_types::types::tickers::OptionType;
-use serde_json::Value;
+use orderbook_types::generated::private_set_mmp_config::PrivateSetMmpConfigParamsSchema;
+use orderbook_types::types::orders::ReplaceResponse;
+use orderbook_types::types::tickers::{InstrumentTicker, OptionType};
+use serde_json::{json, Value};
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
 use tokio::select;
+use tracing::{debug, field, info, info_span, warn, Instrument, Span};
+use uuid::Uuid;
+
+/// Min time between refreshes of expiring orders, each one costs an on-chain `sign_action`
+const MIN_REFRESH_INTERVAL_SEC: i64 = 60;
+
+/// Subaccounts and currencies whose MMP trigger was reset by `reset_on_start` in this process
+static MMP_RESET: OnceLock<Mutex<HashSet<(i64, String)>>> = OnceLock::new();
 
 pub trait OrderStrategy {
     async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal>;
@@ -32,16 +57,51 @@ pub trait OrderStrategy {
 /// State struct for a limit order auction.
 pub struct LimitOrderAuction {
     // State
-    pub subaccount_id: i64,
+    pub subaccount_id: i64, // the orders are sent from, see `SubaccountParams`
+    pub synced_subaccount_ids: Vec<i64>, // tracked in the market, subaccount_id first
     pub market: MarketState,
-    pub client: WsClient,
-    pub tsa: TSA<ProviderWithSigner>,
+    pub client: Option<WsClient>, // None for offline auctions, e.g. in a backtest
+    pub tsa: Option<TSA<ProviderWithSigner>>,
     pub start_timestamp_sec: i64,
+    pub state_store: Option<StateStore>,
+    pub dry_run: Option<DryRunRecorder>,
 
     // Params
     pub instrument_name: String,
     pub auction_sec: i64,
     pub price_change_tolerance: BigDecimal,
+    // Iceberg: only this fraction of the desired amount is shown at a time, None shows it all
+    pub display_fraction: Option<BigDecimal>,
+    // Market maker protection armed before quoting, None sends orders without the mmp flag
+    pub mmp: Option<MmpParams>,
+    // Tickers subscribed next to the instrument's, e.g. adjacent strikes for the smile
+    pub reference_instruments: Vec<String>,
+    // Orderbook subscribed and orders capped at a fraction of its depth, None does not cap them
+    pub depth_limit: Option<DepthLimitParams>,
+    // Orderbook levels subscribed for the strategy's pricing, e.g. inventory-aware spot spreads
+    pub orderbook_levels: Option<u32>,
+    // Price protection of orders crossing the book, see `OrderArgs::with_price_protection`
+    pub max_slippage_bps: Option<u32>,
+    // Set for the wallet before quoting (and by the client after reconnects), true by default
+    pub cancel_on_disconnect: bool,
+    // Mark checked against external prices before quoting, None does not check it
+    pub price_check: Option<PriceCheckParams>,
+    // Public trades aggregated into candles of this interval for the execution report, None
+    // does not subscribe to them
+    pub candle_interval_sec: Option<i64>,
+    // Open orders whose signature expires within this many seconds are re-signed and replaced,
+    // None lets them expire. See `lyra_client::actions::signature_expiry_sec`.
+    pub refresh_before_expiry_sec: Option<i64>,
+    refreshed_sec: Mutex<Option<i64>>, // when an expiring order was last refreshed
+    margin_preview: Mutex<Option<CachedPreview>>, // of the last order checked by margin_limit
+    // Params section whose reloaded price_change_tolerance applies, None ignores reloads
+    pub reload_section: Option<ParamsSection>,
+    // Applied once auction_sec passed, None leaves the end of the auction to the strategy
+    pub on_timeout: Option<TimeoutPolicy>,
+    timed_out_sec: Mutex<Option<i64>>, // when on_timeout was applied
+
+    // Observed while quoting, for the execution report once the auction completes
+    pub stats: Mutex<AuctionStats>,
 }
 
 impl LimitOrderAuction {
@@ -51,30 +111,164 @@ impl LimitOrderAuction {
         auction_sec: i64,
         price_change_tolerance: BigDecimal,
     ) -> Result<Self> {
-        info!("LimitOrderAuction selected option: {}", instrument_name);
-        let vault_name = std::env::var("VAULT_NAME").unwrap();
-        let subaccount_id = std::env::var("SUBACCOUNT_ID").unwrap().parse().unwrap();
+        info!(instrument = %instrument_name, "LimitOrderAuction selected option");
+        let vault_name = VaultContext::current().vault_name.clone();
+        let subaccount_id = VaultContext::current().order_subaccount_id(&instrument_name)?;
+        let synced_subaccount_ids =
+            VaultContext::current().synced_subaccount_ids(&instrument_name)?;
         sleep_till(start_sec).await;
+        VaultContext::current().await_reconciled().await;
 
         let start_timestamp_sec = chrono::Utc::now().timestamp();
         let market = new_market_state();
         let client = WsClient::new_client().await?;
         client.login().await?;
-        client.enable_cancel_on_disconnect().await?;
         let tsa = get_tsa_contract(&vault_name, "SESSION").await?;
+        let refresh_before_expiry_sec = std::env::var("ORDER_REFRESH_BEFORE_EXPIRY_SEC")
+            .ok()
+            .map(|s| s.parse::<i64>())
+            .transpose()?;
+        // a margin beyond the signature lifetime would refresh every order right after placing it
+        let expiry_sec = signature_expiry_sec();
+        if let Some(margin) = refresh_before_expiry_sec {
+            if margin <= 0 || margin >= expiry_sec {
+                return Err(Error::msg(format!(
+                    "ORDER_REFRESH_BEFORE_EXPIRY_SEC {} must be within (0, {})",
+                    margin, expiry_sec
+                )));
+            }
+        }
         Ok(LimitOrderAuction {
             subaccount_id,
+            synced_subaccount_ids,
             market,
-            client,
-            tsa,
+            client: Some(client),
+            tsa: Some(tsa),
             start_timestamp_sec,
+            state_store: None,
+            dry_run: None,
             instrument_name,
             auction_sec,
             price_change_tolerance,
+            display_fraction: None,
+            mmp: None,
+            reference_instruments: vec![],
+            depth_limit: None,
+            orderbook_levels: None,
+            max_slippage_bps: None,
+            cancel_on_disconnect: true,
+            price_check: None,
+            candle_interval_sec: None,
+            refresh_before_expiry_sec,
+            refreshed_sec: Mutex::new(None),
+            margin_preview: Mutex::new(None),
+            reload_section: None,
+            on_timeout: None,
+            timed_out_sec: Mutex::new(None),
+            stats: Mutex::new(AuctionStats::default()),
         })
     }
+    /// Auction without a connection, only usable to query strategies (e.g. in a backtest)
+    pub fn new_offline(
+        instrument_name: String,
+        market: MarketState,
+        start_timestamp_sec: i64,
+        auction_sec: i64,
+        price_change_tolerance: BigDecimal,
+    ) -> Self {
+        LimitOrderAuction {
+            subaccount_id: 0,
+            synced_subaccount_ids: vec![],
+            market,
+            client: None,
+            tsa: None,
+            start_timestamp_sec,
+            state_store: None,
+            dry_run: None,
+            instrument_name,
+            auction_sec,
+            price_change_tolerance,
+            display_fraction: None,
+            mmp: None,
+            reference_instruments: vec![],
+            depth_limit: None,
+            orderbook_levels: None,
+            max_slippage_bps: None,
+            cancel_on_disconnect: false,
+            price_check: None,
+            candle_interval_sec: None,
+            refresh_before_expiry_sec: None,
+            refreshed_sec: Mutex::new(None),
+            margin_preview: Mutex::new(None),
+            reload_section: None,
+            on_timeout: None,
+            timed_out_sec: Mutex::new(None),
+            stats: Mutex::new(AuctionStats::default()),
+        }
+    }
+    /// Sends the orders from the subaccount `subaccounts` routes the stage to, see
+    /// `SubaccountParams::stage_subaccount_ids`
+    pub fn route_to_stage(&mut self, stage: &str) -> Result<()> {
+        let ctx = VaultContext::current();
+        (self.subaccount_id, self.synced_subaccount_ids) =
+            ctx.stage_subaccount_ids(stage, &self.instrument_name)?;
+        Ok(())
+    }
+    /// The tolerance of the params reloaded last, or the one the auction was created with
+    pub fn price_change_tolerance(&self) -> BigDecimal {
+        let live = VaultContext::current().live_params.clone();
+        self.reload_section
+            .and_then(|section| live.decimal(section, "price_change_tolerance"))
+            .unwrap_or(self.price_change_tolerance.clone())
+    }
+    /// Time left, including the extension of `on_timeout` once applied
     pub fn remain_sec(&self) -> i64 {
-        self.auction_sec - (chrono::Utc::now().timestamp() - self.start_timestamp_sec)
+        let extension_sec = match (&self.on_timeout, self.timed_out_sec()) {
+            (Some(policy), Some(_)) => policy.extension_sec(),
+            _ => 0,
+        };
+        self.auction_sec + extension_sec
+            - (chrono::Utc::now().timestamp() - self.start_timestamp_sec)
+    }
+    fn timed_out_sec(&self) -> Option<i64> {
+        *self.timed_out_sec.lock().unwrap()
+    }
+    pub fn client(&self) -> Result<&WsClient> {
+        self.client.as_ref().ok_or(Error::msg("LimitOrderAuction is offline"))
+    }
+    pub fn tsa(&self) -> Result<&TSA<ProviderWithSigner>> {
+        self.tsa.as_ref().ok_or(Error::msg("LimitOrderAuction is offline"))
+    }
+    /// Caps the amount at `depth_limit.max_fraction` of the visible depth the order trades
+    /// against. Without a visible book the amount is left as is, so the auction never stops on it.
+    pub async fn cap_to_depth(
+        &self,
+        direction: &Direction,
+        amount: BigDecimal,
+    ) -> Result<BigDecimal> {
+        let Some(limit) = &self.depth_limit else {
+            return Ok(amount);
+        };
+        if amount.is_zero() {
+            return Ok(amount);
+        }
+        let reader = self.market.read().await;
+        let ticker =
+            reader.get_ticker(&self.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let depth =
+            reader.get_visible_depth(&self.instrument_name, direction, limit.levels as usize);
+        let Some(depth) = depth.filter(|depth| !depth.is_zero()) else {
+            debug!(instrument = %self.instrument_name, "LimitOrderAuction no visible depth");
+            return Ok(amount);
+        };
+        let capped = (&depth * &limit.max_fraction)
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down)
+            .max(ticker.minimum_amount.clone())
+            .min(amount.clone());
+        if capped < amount {
+            info!(%amount, %capped, %depth, "LimitOrderAuction capped the amount to the depth");
+        }
+        Ok(capped)
     }
 }
 
@@ -87,39 +281,104 @@ impl Debug for LimitOrderAuction {
             .field("start_timestamp_sec", &self.start_timestamp_sec)
             .field("auction_sec", &self.auction_sec)
             .field("price_change_tolerance", &self.price_change_tolerance)
+            .field("display_fraction", &self.display_fraction)
+            .field("mmp", &self.mmp)
+            .field("reference_instruments", &self.reference_instruments)
+            .field("depth_limit", &self.depth_limit)
+            .field("orderbook_levels", &self.orderbook_levels)
+            .field("max_slippage_bps", &self.max_slippage_bps)
+            .field("cancel_on_disconnect", &self.cancel_on_disconnect)
+            .field("price_check", &self.price_check)
+            .field("candle_interval_sec", &self.candle_interval_sec)
+            .field("refresh_before_expiry_sec", &self.refresh_before_expiry_sec)
+            .field("refreshed_sec", &self.refreshed_sec)
+            .field("reload_section", &self.reload_section)
+            .field("on_timeout", &self.on_timeout)
+            .field("timed_out_sec", &self.timed_out_sec())
+            .field("stats", &self.stats)
+            .field("state_store", &self.state_store)
+            .field("dry_run", &self.dry_run.is_some())
             .finish()
     }
 }
 
+/// Margin preview of an order, reused for the same order until the ticker updates
+#[derive(Debug, Clone)]
+struct CachedPreview {
+    ticker_timestamp: i64,
+    direction: Direction,
+    amount: BigDecimal,
+    preview: MarginPreview,
+}
+
 #[derive(Debug)]
 pub struct LimitOrderAuctionExecutor<S: OrderStrategy + Debug> {
     pub auction: LimitOrderAuction,
     pub strategy: S,
 }
 
+/// Runs several limit order auctions concurrently, e.g. one per expiry of an option ladder.
+/// Each auction trades its own instrument, so they never cancel each other's orders.
+#[derive(Debug)]
+pub struct MultiAuctionExecutor<S: OrderStrategy + Debug> {
+    pub executors: Vec<LimitOrderAuctionExecutor<S>>,
+}
+
+impl<S: OrderStrategy + Debug> MultiAuctionExecutor<S> {
+    pub fn instrument_names(&self) -> Vec<String> {
+        self.executors.iter().map(|e| e.auction.instrument_name.clone()).collect()
+    }
+}
+
 impl<S: OrderStrategy + Debug> LimitOrderAuctionExecutor<S> {
     pub async fn run_market(&self) -> Result<()> {
         let market = &self.auction.market;
         let sync_instruments = vec![self.auction.instrument_name.clone()];
-        sync_subaccount(market.clone(), self.auction.subaccount_id, sync_instruments).await?;
+        let subaccount_ids = self.auction.synced_subaccount_ids.clone();
+        sync_subaccounts(market.clone(), &subaccount_ids, sync_instruments).await?;
 
-        let subacc_sub = subscribe_subaccount(market.clone(), self.auction.subaccount_id);
-        let ticker_sub = subscribe_tickers(
-            market.clone(),
-            vec![self.auction.instrument_name.clone()],
-            TickerInterval::_100Ms,
-        );
+        let subacc_sub = subscribe_subaccounts(market.clone(), subaccount_ids);
+        let mut instrument_names = vec![self.auction.instrument_name.clone()];
+        instrument_names.extend(self.auction.reference_instruments.iter().cloned());
+        let ticker_sub =
+            subscribe_tickers(market.clone(), instrument_names, TickerInterval::_100Ms);
+        let orderbook_levels = self
+            .auction
+            .depth_limit
+            .as_ref()
+            .map(|limit| limit.levels)
+            .max(self.auction.orderbook_levels);
+        let orderbook_sub = async {
+            match orderbook_levels {
+                Some(levels) => {
+                    let instrument_names = vec![self.auction.instrument_name.clone()];
+                    subscribe_orderbooks(market.clone(), instrument_names, levels).await
+                }
+                None => std::future::pending().await,
+            }
+        };
+        let trades_sub = async {
+            match self.auction.candle_interval_sec {
+                Some(interval_sec) => {
+                    let instrument_names = vec![self.auction.instrument_name.clone()];
+                    subscribe_trades(market.clone(), instrument_names, vec![interval_sec]).await
+                }
+                None => std::future::pending().await,
+            }
+        };
 
         let res = select! {
             _ = ticker_sub => {Err(Error::msg("Market subscription exited early"))},
             _ = subacc_sub => {Err(Error::msg("Subaccount subscription exited early"))},
+            _ = orderbook_sub => {Err(Error::msg("Orderbook subscription exited early"))},
+            _ = trades_sub => {Err(Error::msg("Trades subscription exited early"))},
         };
 
-        warn!("LimitOrderAuction run_market finished with {:?}", res);
+        warn!(result = ?res, "LimitOrderAuction run_market finished");
         res
     }
 
-    async fn wait_for_ticker(&self) {
+    pub async fn wait_for_ticker(&self) {
         let market = &self.auction.market;
         loop {
             let reader = market.read().await;
@@ -134,8 +393,21 @@ impl<S: OrderStrategy + Debug> LimitOrderAuctionExecutor<S> {
     /// Executes an option auction. Assumes market is already running and has correct state.
     pub async fn run_auction(&self) -> Result<()> {
         self.wait_for_ticker().await;
+        self.record_start_ticker().await;
+        self.check_price().await?;
+        self.arm_mmp().await?;
+        let client = self.auction.client()?;
+        let enabled = self.auction.cancel_on_disconnect;
+        client.set_cancel_on_disconnect(enabled).await?.into_result()?;
         loop {
+            if self.auction.market.write().await.take_mmp_trigger(&self.auction.instrument_name) {
+                return self.on_mmp_trigger().await;
+            }
+            if self.auction.remain_sec() <= 0 && self.on_timeout().await? {
+                return Ok(());
+            }
             let desired_price = self.strategy.get_desired_price(&self.auction).await?;
+            let desired_price = self.timeout_price(desired_price).await?;
             if self.needs_update(&desired_price).await? {
                 let amount = self.update_order(&desired_price).await?;
                 if amount.is_zero() {
@@ -146,7 +418,164 @@ impl<S: OrderStrategy + Debug> LimitOrderAuctionExecutor<S> {
         }
     }
 
-    async fn sync(&self) {
+    /// Applies `on_timeout` the first time the auction runs out of time, true if that stops it.
+    /// Once an extension ran out as well, the strategy decides as without a policy.
+    async fn on_timeout(&self) -> Result<bool> {
+        let Some(policy) = &self.auction.on_timeout else {
+            return Ok(false);
+        };
+        if self.auction.timed_out_sec().is_some() {
+            return Ok(matches!(policy, TimeoutPolicy::Abort));
+        }
+        let now = chrono::Utc::now().timestamp();
+        *self.auction.timed_out_sec.lock().unwrap() = Some(now);
+        let instrument_name = &self.auction.instrument_name;
+        let TimeoutPolicy::Abort = policy else {
+            info!(instrument = %instrument_name, ?policy, "LimitOrderAuction timed out");
+            return Ok(false);
+        };
+        let left = self.get_open_order().await.map(|o| &o.amount - &o.filled_amount);
+        self.cancel_all().await?;
+        let message = format!(
+            "{} auction aborted after {}s with {} left on the open order",
+            instrument_name,
+            self.auction.auction_sec,
+            left.map_or("nothing".to_string(), |left| left.to_string())
+        );
+        send_alert(&message).await;
+        Ok(true)
+    }
+
+    /// The strategy's price moved by the extend or cross policy while it applies, the price is
+    /// left as is otherwise (and if zero, i.e. nothing to trade)
+    async fn timeout_price(&self, price: BigDecimal) -> Result<BigDecimal> {
+        let (Some(policy), Some(timed_out_sec)) =
+            (&self.auction.on_timeout, self.auction.timed_out_sec())
+        else {
+            return Ok(price);
+        };
+        if price.is_zero() || self.auction.remain_sec() <= 0 {
+            return Ok(price);
+        }
+        let (direction, _) = self.strategy.get_desired_amount(&self.auction, &price).await?;
+        let sign = match direction {
+            Direction::Buy => 1.0,
+            Direction::Sell => -1.0,
+        };
+        let reader = self.auction.market.read().await;
+        let ticker = reader
+            .get_ticker(&self.auction.instrument_name)
+            .ok_or(Error::msg("Ticker not found"))?;
+        let moved = match policy {
+            TimeoutPolicy::Extend { extend_sec, extra_spread } => {
+                let elapsed_sec = chrono::Utc::now().timestamp() - timed_out_sec;
+                let progress = (elapsed_sec as f64 / (*extend_sec).max(1) as f64).clamp(0.0, 1.0);
+                let factor = BigDecimal::from_f64(1.0 + sign * extra_spread * progress).unwrap();
+                price * factor
+            }
+            TimeoutPolicy::Cross { max_slippage_bps, .. } => {
+                let slippage = sign * *max_slippage_bps as f64 / 10000.0;
+                &ticker.mark_price * BigDecimal::from_f64(1.0 + slippage).unwrap()
+            }
+            TimeoutPolicy::Abort => return Ok(price),
+        };
+        Ok(moved.round(ticker.tick_size.fractional_digit_count()).max(ticker.min_price.clone()))
+    }
+
+    /// Keeps the first ticker the auction quotes against, a restarted auction keeps the original
+    pub async fn record_start_ticker(&self) {
+        let reader = self.auction.market.read().await;
+        let ticker = reader.get_ticker(&self.auction.instrument_name).cloned();
+        let mut stats = self.auction.stats.lock().unwrap();
+        if stats.start_ticker.is_none() {
+            stats.start_ticker = ticker;
+        }
+    }
+
+    /// Writes the execution report of the completed auction, only once per auction since
+    /// completed auctions return right away when rerun
+    pub async fn report_execution(&self) {
+        if std::mem::replace(&mut self.auction.stats.lock().unwrap().reported, true) {
+            return;
+        }
+        let report = ExecutionReport::new(&self.auction).await;
+        if let Err(e) = report.write().await {
+            warn!(error = format!("{:#}", e), "Failed to write the execution report");
+        }
+    }
+
+    /// Alerts if the mark deviates from the external prices of `price_check` and fails the
+    /// auction (to be retried by the stage) unless `alert_only`, to not trade on a broken feed
+    async fn check_price(&self) -> Result<()> {
+        let Some(price_check) = &self.auction.price_check else {
+            return Ok(());
+        };
+        let reader = self.auction.market.read().await;
+        let ticker = reader
+            .get_ticker(&self.auction.instrument_name)
+            .ok_or(Error::msg("Ticker not found"))?;
+        let mark_price = ticker.mark_price.clone();
+        drop(reader);
+        let deviations = price_check.check(&self.auction.instrument_name, &mark_price).await?;
+        if deviations.is_empty() {
+            return Ok(());
+        }
+        let message = format!("Spot price check failed: {}", deviations.join("; "));
+        send_alert(&message).await;
+        match price_check.alert_only {
+            true => Ok(()),
+            false => Err(Error::msg(message)),
+        }
+    }
+
+    /// Alerts and pauses the vault once MMP froze the auction's orders. The admin pause cancels
+    /// the orders and holds the stage until the operator resets MMP and resumes trading.
+    async fn on_mmp_trigger(&self) -> Result<()> {
+        let message = format!(
+            "{} MMP triggered on subaccount {}, trading paused until MMP is reset and resumed",
+            self.auction.instrument_name, self.auction.subaccount_id
+        );
+        send_alert(&message).await;
+        let ctx = VaultContext::current();
+        ctx.admin.pause();
+        // dropped by the pause in `run_stage`
+        std::future::pending().await
+    }
+
+    /// Sets the MMP config of the instrument's currency. A previous trigger is left to the
+    /// operator, unless `reset_on_start` resets it the first time this process arms the currency,
+    /// so neither a rerun nor a reconnect unfreezes the auction.
+    async fn arm_mmp(&self) -> Result<()> {
+        let Some(mmp) = &self.auction.mmp else {
+            return Ok(());
+        };
+        let currency = self.auction.instrument_name.split('-').next().unwrap_or_default();
+        if let Some(dry_run) = &self.auction.dry_run {
+            let payload = json!({ "currency": currency, "mmp": format!("{:?}", mmp) });
+            return dry_run.record("arm_mmp", payload).await;
+        }
+        let params = PrivateSetMmpConfigParamsSchema {
+            currency: currency.to_string(),
+            mmp_amount_limit: mmp.amount_limit.clone(),
+            mmp_delta_limit: mmp.delta_limit.clone(),
+            mmp_frozen_time: mmp.frozen_time_ms,
+            mmp_interval: mmp.interval_ms,
+            subaccount_id: self.auction.subaccount_id,
+        };
+        let client = self.auction.client()?;
+        let res = client.set_mmp_config(params).await?.into_result()?;
+        info!(mmp = ?res.result, "LimitOrderAuction armed MMP");
+        let key = (self.auction.subaccount_id, currency.to_string());
+        let reset = MMP_RESET.get_or_init(Default::default);
+        if mmp.reset_on_start && reset.lock().unwrap().insert(key) {
+            let subaccount_id = self.auction.subaccount_id;
+            client.reset_mmp(subaccount_id, Some(currency.to_string())).await?.into_result()?;
+            info!(%currency, "LimitOrderAuction reset MMP on start");
+        }
+        Ok(())
+    }
+
+    pub async fn sync(&self) {
         loop {
             if self.is_synced().await {
                 break;
@@ -163,6 +592,9 @@ impl<S: OrderStrategy + Debug> LimitOrderAuctionExecutor<S> {
     }
 
     async fn get_open_order_price(&self) -> Result<Option<BigDecimal>> {
+        if let Some(dry_run) = &self.auction.dry_run {
+            return Ok(dry_run.open_price(&self.auction.instrument_name).await);
+        }
         let market = &self.auction.market;
         let reader = market.read().await;
         let orders = reader.get_orders(&self.auction.instrument_name);
@@ -182,15 +614,30 @@ impl<S: OrderStrategy + Debug> LimitOrderAuctionExecutor<S> {
         }
     }
 
-    async fn cancel_all(&self) -> Result<()> {
+    pub async fn cancel_all(&self) -> Result<()> {
+        if let Some(dry_run) = &self.auction.dry_run {
+            dry_run.set_open_price(&self.auction.instrument_name, None).await;
+            return Ok(());
+        }
         // Note: API migration,
         // this used to call private/cancel_all but it no longer returns # of cancelled orders
-        let res = self
-            .auction
-            .client
-            .cancel_by_instrument(self.auction.subaccount_id, self.auction.instrument_name.clone())
-            .await?
-            .into_result()?;
+        let (client, subaccount_id) = (self.auction.client()?, self.auction.subaccount_id);
+        let instrument_name = self.auction.instrument_name.clone();
+        let res = match VaultContext::current().http_fallback.clone() {
+            Some(fallback) => {
+                let tsa = self.auction.tsa()?;
+                let tsa_client = tsa.client();
+                let signer = tsa_client.inner().signer();
+                fallback
+                    .cancel_by_instrument(client, signer, subaccount_id, instrument_name)
+                    .await?
+            }
+            None => client.cancel_by_instrument(subaccount_id, instrument_name).await?,
+        };
+        let res = res.into_result()?;
+        if let Some(store) = &self.auction.state_store {
+            store.set_open_orders(&self.auction.instrument_name, vec![]).await?;
+        }
         if res.result.cancelled_orders == 0 {
             warn!("LimitOrderAuction cancel_all failed to cancel any orders, likely mid fill");
             tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
@@ -199,51 +646,390 @@ impl<S: OrderStrategy + Debug> LimitOrderAuctionExecutor<S> {
     }
 
     async fn needs_update(&self, desired_price: &BigDecimal) -> Result<bool> {
+        if self.expires_soon().await {
+            return Ok(true);
+        }
         let open_price = self.get_open_order_price().await?;
         match open_price {
             None => Ok(true),
             Some(open_price) => {
-                Ok((&open_price - desired_price).abs() > self.auction.price_change_tolerance)
+                Ok((&open_price - desired_price).abs() > self.auction.price_change_tolerance())
             }
         }
     }
 
+    /// Whether the open order's signature expires within `refresh_before_expiry_sec`, so the
+    /// update replaces it with a freshly signed one at the desired price. Orders of a vault that
+    /// stopped updating them still expire on the exchange, which is the dead man's switch.
+    /// Refreshes at most once per `MIN_REFRESH_INTERVAL_SEC`.
+    async fn expires_soon(&self) -> bool {
+        let Some(margin) = self.auction.refresh_before_expiry_sec else {
+            return false;
+        };
+        let Some(order) = self.get_open_order().await else {
+            return false;
+        };
+        let now = chrono::Utc::now().timestamp();
+        let remain_sec = order.signature_expiry_sec - now;
+        if remain_sec > margin {
+            return false;
+        }
+        let mut refreshed_sec = self.auction.refreshed_sec.lock().unwrap();
+        if refreshed_sec.is_some_and(|sec| now - sec < MIN_REFRESH_INTERVAL_SEC) {
+            return false;
+        }
+        *refreshed_sec = Some(now);
+        drop(refreshed_sec);
+        let order_id = &order.order_id;
+        info!(%order_id, remain_sec, "LimitOrderAuction refreshing expiring order");
+        true
+    }
+
+    /// Part of the desired amount to show in the order book. Once an iceberg order is fully
+    /// filled there is no open order left, so the next update shows the next part.
+    async fn display_amount(&self, amount: &BigDecimal) -> Result<BigDecimal> {
+        let Some(fraction) = &self.auction.display_fraction else {
+            return Ok(amount.clone());
+        };
+        let reader = self.auction.market.read().await;
+        let ticker = reader
+            .get_ticker(&self.auction.instrument_name)
+            .ok_or(Error::msg("Ticker not found"))?;
+        let shown = (amount * fraction)
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down)
+            .max(ticker.minimum_amount.clone())
+            .min(amount.clone());
+        info!(%shown, %amount, "LimitOrderAuction showing part of the amount");
+        Ok(shown)
+    }
+
+    /// The single open order of the auction, to be amended with an atomic replace. None in a
+    /// dry run or with several open orders, which are cancelled instead.
+    async fn get_open_order(&self) -> Option<OrderResponse> {
+        if self.auction.dry_run.is_some() {
+            return None;
+        }
+        let reader = self.auction.market.read().await;
+        let orders = reader.get_orders(&self.auction.instrument_name)?;
+        match orders.len() {
+            1 => orders.values().next().cloned(),
+            _ => None,
+        }
+    }
+
+    /// Amends the open order by cancelling it only if it didn't fill since the last sync. If it
+    /// did the new order is not created, and the next update re-syncs and resizes it from scratch.
     async fn update_order(&self, desired_price: &BigDecimal) -> Result<BigDecimal> {
-        self.cancel_all().await?;
+        let open_order = self.get_open_order().await;
+        if open_order.is_none() {
+            self.cancel_all().await?;
+        }
         self.sync().await;
         let (direction, amount) =
             self.strategy.get_desired_amount(&self.auction, desired_price).await?;
-        info!("LimitOrderAuction desired price: {}", desired_price);
-        info!("LimitOrderAuction {} desired amount: {}", direction.to_string(), amount);
+        info!(price = %desired_price, %direction, %amount, "LimitOrderAuction desired order");
         if amount.is_zero() {
+            if open_order.is_some() {
+                self.cancel_all().await?;
+            }
             return Ok(amount);
         }
 
+        let market = &self.auction.market;
+        let reader = market.read().await;
+        let ticker = reader
+            .get_ticker(&self.auction.instrument_name)
+            .ok_or(Error::msg("Ticker not found"))?
+            .clone();
+        drop(reader);
+
         let order_args = OrderArgs {
-            amount: amount.clone(),
+            amount: self.display_amount(&amount).await?,
             limit_price: desired_price.clone(),
             direction,
             time_in_force: TimeInForce::Gtc,
             order_type: OrderType::Limit,
-            mmp: false,
-            label: "".to_string(),
+            mmp: self.auction.mmp.is_some(),
+            label: VaultContext::current().order_labels.next_label(),
+            max_slippage_bps: self.auction.max_slippage_bps,
+        };
+        let order_args = match VaultContext::current().fee_budget.clone() {
+            Some(tracker) => match self.limit_fees(&tracker, &ticker, order_args).await {
+                Some(order_args) => order_args,
+                None => {
+                    if open_order.is_some() {
+                        self.cancel_all().await?;
+                    }
+                    let retry_sec = tracker.params.retry_sec;
+                    tokio::time::sleep(tokio::time::Duration::from_secs(retry_sec)).await;
+                    return Ok(amount);
+                }
+            },
+            None => order_args,
+        };
+        // a dislocated book skips this update, the next one retries with a fresh ticker
+        let order_args = match order_args.with_price_protection(&ticker) {
+            Ok(order_args) => order_args,
+            Err(LyraError::PriceProtection(e)) => {
+                warn!(error = %e, "LimitOrderAuction order not sent");
+                if open_order.is_some() {
+                    self.cancel_all().await?;
+                }
+                return Ok(amount);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let order_args = match VaultContext::current().margin_limit.clone() {
+            Some(limit) => match self.limit_margin(&limit, &ticker, order_args).await? {
+                Some(order_args) => order_args,
+                None => {
+                    if open_order.is_some() {
+                        self.cancel_all().await?;
+                    }
+                    tokio::time::sleep(tokio::time::Duration::from_secs(limit.retry_sec)).await;
+                    return Ok(amount);
+                }
+            },
+            None => order_args,
         };
+        self.auction.stats.lock().unwrap().num_orders += 1;
 
-        info!("LimitOrderAuction run_auction sending order: {:?}", order_args);
-        let market = &self.auction.market;
-        let reader = market.read().await;
-        let ticker = reader
-            .get_ticker(&self.auction.instrument_name)
-            .ok_or(Error::msg("Ticker not found"))?
-            .clone();
+        if let Some(dry_run) = &self.auction.dry_run {
+            let payload = json!({
+                "instrument_name": self.auction.instrument_name,
+                "order": format!("{:?}", order_args),
+            });
+            dry_run.record("order", payload).await?;
+            dry_run
+                .set_open_price(&self.auction.instrument_name, Some(desired_price.clone()))
+                .await;
+            return Ok(amount);
+        }
+
+        match open_order {
+            Some(open_order) => {
+                let span = info_span!(
+                    "order",
+                    label = %order_args.label,
+                    replaced_order_id = %open_order.order_id,
+                    order_id = field::Empty
+                );
+                self.replace_order(&open_order, &ticker, order_args).instrument(span).await?;
+            }
+            None => {
+                let span = info_span!("order", label = %order_args.label, order_id = field::Empty);
+                self.send_order(&ticker, order_args).instrument(span).await?;
+            }
+        }
+        Ok(amount)
+    }
+
+    /// Records the auction's fills in the fee budget and makes the order post-only at a less
+    /// aggressive price once the budget is throttled, None once it is exhausted
+    async fn limit_fees(
+        &self,
+        tracker: &FeeTracker,
+        ticker: &InstrumentTicker,
+        mut order_args: OrderArgs,
+    ) -> Option<OrderArgs> {
+        tracker.sync_epoch().await;
+        let reader = self.auction.market.read().await;
+        tracker.record(reader.iter_trades());
         drop(reader);
+        let level = tracker.level().await;
+        match level {
+            FeeLevel::Normal => Some(order_args),
+            FeeLevel::Exhausted => {
+                let amount = &order_args.amount;
+                warn!(%amount, "LimitOrderAuction skipping order, fee budget spent");
+                None
+            }
+            FeeLevel::Throttled => {
+                let spread =
+                    BigDecimal::from_f64(tracker.params.throttle_spread).unwrap_or_default();
+                let (factor, mode) = match order_args.direction {
+                    Direction::Buy => (BigDecimal::from(1) - spread, RoundingMode::Down),
+                    Direction::Sell => (BigDecimal::from(1) + spread, RoundingMode::Up),
+                };
+                let price = (&order_args.limit_price * factor)
+                    .with_scale_round(ticker.tick_size.fractional_digit_count(), mode);
+                info!(
+                    %price,
+                    unthrottled_price = %order_args.limit_price,
+                    "LimitOrderAuction throttled by the fee budget, post-only"
+                );
+                order_args.limit_price = price;
+                order_args.time_in_force = TimeInForce::PostOnly;
+                Some(order_args)
+            }
+        }
+    }
+
+    /// Resizes the order so that its fill keeps the initial margin utilization within the limit,
+    /// None if not even the minimum amount fits (or resizing is off and the whole order does not)
+    async fn limit_margin(
+        &self,
+        limit: &MarginLimitParams,
+        ticker: &InstrumentTicker,
+        mut order_args: OrderArgs,
+    ) -> Result<Option<OrderArgs>> {
+        let preview = self.margin_preview(ticker, &order_args).await?;
+        let fraction = preview.max_fraction(limit.max_initial_utilization);
+        if preview.is_valid_trade && fraction >= 1.0 {
+            return Ok(Some(order_args));
+        }
+        let amount = match limit.resize && preview.is_valid_trade {
+            true => (&order_args.amount * BigDecimal::from_f64(fraction).unwrap_or_default())
+                .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down),
+            false => BigDecimal::zero(),
+        };
+        if amount < ticker.minimum_amount {
+            warn!(
+                amount = %order_args.amount,
+                pre_utilization = preview.pre_utilization(),
+                post_utilization = preview.post_utilization(),
+                "LimitOrderAuction skipping order over the margin limit"
+            );
+            return Ok(None);
+        }
+        info!(
+            from = %order_args.amount,
+            to = %amount,
+            max_initial_utilization = limit.max_initial_utilization,
+            "LimitOrderAuction resized order for the margin limit"
+        );
+        order_args.amount = amount;
+        Ok(Some(order_args))
+    }
+
+    /// Previews the order with `preview_order_margin`, reusing the preview of the same order
+    /// until the ticker updates instead of fetching the margin twice per update
+    async fn margin_preview(
+        &self,
+        ticker: &InstrumentTicker,
+        order_args: &OrderArgs,
+    ) -> Result<MarginPreview> {
+        let cached = self.auction.margin_preview.lock().unwrap().clone();
+        if let Some(cached) = cached.filter(|c| {
+            c.ticker_timestamp == ticker.timestamp
+                && c.direction == order_args.direction
+                && c.amount == order_args.amount
+        }) {
+            return Ok(cached.preview);
+        }
+        let preview = preview_order_margin(
+            self.auction.subaccount_id,
+            &self.auction.instrument_name,
+            order_args.direction,
+            &order_args.amount,
+        )
+        .await?;
+        *self.auction.margin_preview.lock().unwrap() = Some(CachedPreview {
+            ticker_timestamp: ticker.timestamp,
+            direction: order_args.direction,
+            amount: order_args.amount.clone(),
+            preview: preview.clone(),
+        });
+        Ok(preview)
+    }
 
-        let provider = self.auction.tsa.client();
+    async fn send_order(&self, ticker: &InstrumentTicker, order_args: OrderArgs) -> Result<()> {
+        info!(order = ?order_args, "LimitOrderAuction sending order");
+        let (client, tsa) = (self.auction.client()?, self.auction.tsa()?);
+        let provider = tsa.client();
         let signer = provider.inner().signer();
-        let action_data = sign_order(&self.auction.tsa, &ticker, &order_args).await?;
-        let order_params = action_data.to_order_params(&signer, &ticker, order_args)?;
-        let res = self.auction.client.send_rpc::<_, Value>("private/order", order_params).await?;
-        res.into_result()?;
-        Ok(amount)
+        let action_data = sign_order(tsa, self.auction.subaccount_id, ticker, &order_args).await?;
+        let config = client.get_config().await;
+        let order_params = action_data.to_order_params(signer, &config, ticker, order_args).await?;
+        let res = match VaultContext::current().http_fallback.clone() {
+            Some(fallback) => fallback.send_order::<Value>(client, signer, order_params).await?,
+            None => client.send_rpc::<_, Value>("private/order", order_params).await?,
+        };
+        let res = res.into_result()?;
+        let order_id = res["result"]["order"]["order_id"].as_str().unwrap_or_default();
+        self.store_open_order(order_id, None).await
+    }
+
+    /// Sends `private/replace` for the open order with `expected_filled_amount` set to its filled
+    /// amount as synced, so that a fill racing the replace never leaves both orders to fill
+    async fn replace_order(
+        &self,
+        open_order: &OrderResponse,
+        ticker: &InstrumentTicker,
+        order_args: OrderArgs,
+    ) -> Result<()> {
+        info!(
+            replaced_order_id = %open_order.order_id,
+            order = ?order_args,
+            "LimitOrderAuction replacing order"
+        );
+        let (client, tsa) = (self.auction.client()?, self.auction.tsa()?);
+        let order_id_to_cancel = Uuid::from_str(&open_order.order_id)?;
+        let provider = tsa.client();
+        let signer = provider.inner().signer();
+        let action_data = sign_order(tsa, self.auction.subaccount_id, ticker, &order_args).await?;
+        let config = client.get_config().await;
+        let mut replace_params = action_data
+            .to_replace_params(signer, &config, ticker, order_id_to_cancel, order_args)
+            .await?;
+        replace_params.expected_filled_amount = Some(open_order.filled_amount.clone());
+        let res = match VaultContext::current().http_fallback.clone() {
+            Some(fallback) => fallback.send_replace(client, signer, replace_params).await?,
+            None => {
+                client.send_rpc::<_, ReplaceResponse>("private/replace", replace_params).await?
+            }
+        };
+        let res = match res.into_result() {
+            Ok(res) => res.result,
+            Err(LyraError::OrderNotOpen(e)) => {
+                // filled or cancelled meanwhile, the next update sends a new order if needed
+                warn!(error = ?e, "LimitOrderAuction order to replace no longer open");
+                return Ok(());
+            }
+            Err(e) => {
+                warn!(error = %e, "LimitOrderAuction replace failed, cancelling instead");
+                return self.cancel_all().await;
+            }
+        };
+        match (res.order, res.create_order_error) {
+            (Some(order), _) => {
+                self.store_open_order(&order.order_id, Some(&open_order.order_id)).await
+            }
+            (None, error) => {
+                warn!(
+                    cancelled_order_id = %open_order.order_id,
+                    filled = %res.cancelled_order.filled_amount,
+                    ?error,
+                    "LimitOrderAuction cancelled without a new order"
+                );
+                Ok(())
+            }
+        }
+    }
+
+    async fn store_open_order(
+        &self,
+        order_id: &str,
+        replaced_order_id: Option<&str>,
+    ) -> Result<()> {
+        // fills the order id of the order span, see `update_order`
+        Span::current().record("order_id", order_id);
+        info!(order_id, "LimitOrderAuction order open");
+        if let Some(store) = &self.auction.state_store {
+            // orders not synced yet (or that failed to cancel) are recorded next to the new one
+            let reader = self.auction.market.read().await;
+            let orders = reader.get_orders(&self.auction.instrument_name);
+            let mut order_ids: Vec<String> =
+                orders.map(|orders| orders.keys().cloned().collect()).unwrap_or_default();
+            drop(reader);
+            if let Some(replaced_order_id) = replaced_order_id {
+                order_ids.retain(|id| id != replaced_order_id);
+            }
+            if !order_ids.iter().any(|id| id == order_id) {
+                order_ids.push(order_id.to_string());
+            }
+            store.set_open_orders(&self.auction.instrument_name, order_ids).await?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,483 @@
+use crate::lrtc::params::LRTCParams;
+use crate::market::{new_market_state, Balance, MarketState, OrderbookData};
+use crate::shared::auction::{LimitOrderAuction, OrderStrategy};
+use crate::shared::settlement::settled_value;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use lyra_client::actions::Direction;
+use lyra_client::channels::Channel;
+use lyra_client::market_history::get_settlement_prices;
+use orderbook_types::types::tickers::result::{InstrumentTicker, TickerNotificationData};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use tracing::{debug, info, warn};
+
+/// One market data message as received over the websocket, as written by the recorder.
+/// Ticker channels hold the ticker notification data, orderbook channels the orderbook.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedMessage {
+    pub timestamp: i64, // ms, when the message was received
+    pub channel: String,
+    pub data: Value,
+}
+
+/// Reads a JSON lines recording, or all `.jsonl` files of a (rotated) recording directory,
+/// sorted by receive time
+pub async fn load_recording(path: &str) -> Result<Vec<RecordedMessage>> {
+    let mut files = vec![];
+    if tokio::fs::metadata(path).await?.is_dir() {
+        let mut entries = tokio::fs::read_dir(path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.path().extension().is_some_and(|e| e == "jsonl") {
+                files.push(entry.path());
+            }
+        }
+    } else {
+        files.push(path.into());
+    }
+    let mut messages = vec![];
+    for file in files {
+        let content = tokio::fs::read_to_string(file).await?;
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            messages.push(serde_json::from_str::<RecordedMessage>(line)?);
+        }
+    }
+    messages.sort_by_key(|m| m.timestamp);
+    Ok(messages)
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BacktestAuction {
+    Option,
+    Spot,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BacktestParams {
+    pub vault_params: String, // Name of the LRTC (or DN) params json in ./params
+    pub recording: String,    // JSON lines file of recorded ticker and orderbook messages
+    pub auction: BacktestAuction, // "option" or "spot"
+    pub instrument_name: String, // Option or spot instrument the auction trades
+    pub positions: HashMap<String, BigDecimal>, // Starting positions, e.g. collateral and cash
+    // Values of iv_spread_per_min (option) or spot_spread_per_min (spot) to compare,
+    // the value from the vault params is used if empty
+    #[serde(default)]
+    pub spread_per_min: Vec<f64>,
+    #[serde(default = "default_order_interval_ms")]
+    pub order_interval_ms: i64, // Time between strategy updates
+    #[serde(default)]
+    pub latency_ms: i64, // Time until a placed order rests on the book and can be filled
+    // Vault subaccount that held the option to expiry, once settled its settlement value
+    // marks the PnL instead of the last recorded mark
+    #[serde(default)]
+    pub settlement_subaccount_id: Option<i64>,
+}
+
+fn default_order_interval_ms() -> i64 {
+    1000
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SimFill {
+    pub timestamp: i64,
+    pub direction: Direction,
+    pub amount: BigDecimal,
+    pub price: BigDecimal,
+    pub mark_price: BigDecimal,
+    pub order_latency_ms: i64, // time between placing the order and the fill
+}
+
+/// Results of one backtest run, spreads are relative to the mark at the time of each fill
+/// (positive when the fill was better than mark) and PnL is marked at the last recorded mark,
+/// or at the settlement value of an expired option. Fees are not included.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct BacktestReport {
+    pub spread_per_min: f64,
+    pub num_orders: usize,
+    pub num_fills: usize,
+    pub filled_amount: BigDecimal,
+    pub avg_realized_spread_bps: f64,
+    pub avg_fill_sec: f64, // time from the auction start to each fill
+    pub avg_order_latency_ms: f64,
+    pub pnl: BigDecimal,
+    pub completed: bool, // whether the strategy stopped before the recording or auction ended
+    pub final_positions: HashMap<String, BigDecimal>,
+}
+
+/// Outcome of replaying the recording against one strategy
+struct SimResult {
+    fills: Vec<SimFill>,
+    num_orders: usize,
+    completed: bool,
+    positions: HashMap<String, BigDecimal>,
+}
+
+#[derive(Debug, Clone)]
+struct SimOrder {
+    direction: Direction,
+    amount: BigDecimal,
+    price: BigDecimal,
+    placed_ms: i64,
+}
+
+/// Replays the recording into an offline auction and simulates fills of its orders.
+///
+/// The strategies read the wall clock (auction start, option expiries and ticker staleness),
+/// so at every step all recorded timestamps are shifted by the offset between the wall clock
+/// and the simulated time. This keeps every time difference the strategies see equal to the
+/// recorded one without any clock in the strategies themselves.
+struct Simulator<'a> {
+    params: &'a BacktestParams,
+    messages: &'a [RecordedMessage],
+    base_name: String, // position changed by a fill, the option itself or the spot asset
+    cash_name: String,
+    start_ms: i64,
+    end_ms: i64,
+}
+
+impl Simulator<'_> {
+    fn shift_ticker(ticker: &InstrumentTicker, offset_ms: i64) -> InstrumentTicker {
+        let mut ticker = ticker.clone();
+        ticker.timestamp += offset_ms;
+        if let Some(details) = ticker.option_details.as_mut() {
+            details.expiry += offset_ms / 1000;
+        }
+        ticker
+    }
+
+    fn shift_orderbook(orderbook: &OrderbookData, offset_ms: i64) -> OrderbookData {
+        let mut orderbook = orderbook.clone();
+        orderbook.timestamp += offset_ms;
+        orderbook
+    }
+
+    /// Amount a resting order would have traded against the recorded book (or top of book)
+    fn crossed_amount(
+        order: &SimOrder,
+        orderbook: Option<&OrderbookData>,
+        ticker: &InstrumentTicker,
+    ) -> BigDecimal {
+        let levels = match (orderbook, &order.direction) {
+            (Some(orderbook), Direction::Sell) => orderbook.bids.clone(),
+            (Some(orderbook), Direction::Buy) => orderbook.asks.clone(),
+            (None, Direction::Sell) => {
+                vec![vec![ticker.best_bid_price.clone(), ticker.best_bid_amount.clone()]]
+            }
+            (None, Direction::Buy) => {
+                vec![vec![ticker.best_ask_price.clone(), ticker.best_ask_amount.clone()]]
+            }
+        };
+        levels
+            .iter()
+            .filter(|level| level.len() == 2 && !level[1].is_zero())
+            .filter(|level| match order.direction {
+                Direction::Sell => level[0] >= order.price,
+                Direction::Buy => level[0] <= order.price,
+            })
+            .map(|level| level[1].clone())
+            .sum()
+    }
+
+    async fn set_positions(market: &MarketState, positions: &HashMap<String, BigDecimal>) {
+        let mut writer = market.write().await;
+        for (name, amount) in positions.iter() {
+            writer.insert_position(Balance {
+                instrument_name: name.clone(),
+                amount: amount.clone(),
+                timestamp: chrono::Utc::now().timestamp_millis(),
+            });
+        }
+    }
+
+    async fn run<S: OrderStrategy + Debug>(
+        &self,
+        strategy: &S,
+        auction_sec: i64,
+        price_change_tolerance: BigDecimal,
+    ) -> Result<SimResult> {
+        let instrument_name = &self.params.instrument_name;
+        let market = new_market_state();
+        let mut auction = LimitOrderAuction::new_offline(
+            instrument_name.clone(),
+            market.clone(),
+            self.start_ms / 1000,
+            auction_sec,
+            price_change_tolerance.clone(),
+        );
+        // e.g. the other strikes of the expiry for the smile pricing model
+        auction.reference_instruments = recorded_instruments(&self.messages, instrument_name);
+        let mut positions = self.params.positions.clone();
+        let mut tickers: HashMap<String, InstrumentTicker> = HashMap::new();
+        let mut orderbooks: HashMap<String, OrderbookData> = HashMap::new();
+        let mut order: Option<SimOrder> = None;
+        let mut fills = vec![];
+        let mut num_orders = 0;
+        let mut next_message = 0;
+
+        let mut now_ms = self.start_ms;
+        while now_ms <= self.end_ms {
+            let mut book_updated = false;
+            while next_message < self.messages.len()
+                && self.messages[next_message].timestamp <= now_ms
+            {
+                let message = &self.messages[next_message];
+                next_message += 1;
+                match message.channel.parse::<Channel>() {
+                    Ok(Channel::Ticker { .. }) => {
+                        let data: TickerNotificationData =
+                            serde_json::from_value(message.data.clone())?;
+                        let ticker = data.instrument_ticker;
+                        book_updated |= &ticker.instrument_name == instrument_name;
+                        tickers.insert(ticker.instrument_name.clone(), ticker);
+                    }
+                    Ok(Channel::Orderbook { .. }) => {
+                        let orderbook: OrderbookData =
+                            serde_json::from_value(message.data.clone())?;
+                        book_updated |= &orderbook.instrument_name == instrument_name;
+                        orderbooks.insert(orderbook.instrument_name.clone(), orderbook);
+                    }
+                    _ => {}
+                }
+            }
+
+            let offset_ms = chrono::Utc::now().timestamp_millis() - now_ms;
+            {
+                let mut writer = market.write().await;
+                for ticker in tickers.values() {
+                    writer.insert_ticker(Self::shift_ticker(ticker, offset_ms));
+                }
+                for orderbook in orderbooks.values() {
+                    writer.insert_orderbook(Self::shift_orderbook(orderbook, offset_ms));
+                }
+            }
+            auction.start_timestamp_sec = (self.start_ms + offset_ms) / 1000;
+
+            // fills only against book updates, so the same liquidity is never traded twice
+            let resting = order.as_mut().filter(|o| now_ms - o.placed_ms >= self.params.latency_ms);
+            if let (Some(resting), Some(ticker), true) =
+                (resting, tickers.get(instrument_name), book_updated)
+            {
+                let crossed =
+                    Self::crossed_amount(resting, orderbooks.get(instrument_name), ticker);
+                let amount = crossed.min(resting.amount.clone());
+                if amount > BigDecimal::zero() {
+                    let signed = match resting.direction {
+                        Direction::Buy => amount.clone(),
+                        Direction::Sell => -amount.clone(),
+                    };
+                    *positions.entry(self.base_name.clone()).or_default() += &signed;
+                    *positions.entry(self.cash_name.clone()).or_default() -=
+                        &signed * &resting.price;
+                    resting.amount -= &amount;
+                    debug!("Backtest fill of {} at {}", amount, resting.price);
+                    fills.push(SimFill {
+                        timestamp: now_ms,
+                        direction: resting.direction,
+                        amount,
+                        price: resting.price.clone(),
+                        mark_price: ticker.mark_price.clone(),
+                        order_latency_ms: now_ms - resting.placed_ms,
+                    });
+                }
+            }
+            Self::set_positions(&market, &positions).await;
+
+            // same update rule as LimitOrderAuctionExecutor::run_auction
+            let desired_price = match strategy.get_desired_price(&auction).await {
+                Ok(price) => price,
+                Err(e) => {
+                    warn!("Backtest skipping step at {} with {:#}", now_ms, e);
+                    now_ms += self.params.order_interval_ms;
+                    continue;
+                }
+            };
+            let needs_update = match &order {
+                None => true,
+                Some(o) => {
+                    o.amount.is_zero() || (&o.price - &desired_price).abs() > price_change_tolerance
+                }
+            };
+            if needs_update {
+                order = None;
+                let (direction, amount) =
+                    match strategy.get_desired_amount(&auction, &desired_price).await {
+                        Ok(desired) => desired,
+                        Err(e) => {
+                            warn!("Backtest skipping step at {} with {:#}", now_ms, e);
+                            now_ms += self.params.order_interval_ms;
+                            continue;
+                        }
+                    };
+                if amount.is_zero() {
+                    return Ok(SimResult { fills, num_orders, completed: true, positions });
+                }
+                num_orders += 1;
+                order =
+                    Some(SimOrder { direction, amount, price: desired_price, placed_ms: now_ms });
+            }
+            now_ms += self.params.order_interval_ms;
+        }
+        Ok(SimResult { fills, num_orders, completed: false, positions })
+    }
+
+    fn report(
+        &self,
+        spread_per_min: f64,
+        res: SimResult,
+        final_mark: &BigDecimal,
+    ) -> BacktestReport {
+        let fills = &res.fills;
+        let mut report = BacktestReport {
+            spread_per_min,
+            num_orders: res.num_orders,
+            num_fills: fills.len(),
+            completed: res.completed,
+            final_positions: res.positions.clone(),
+            ..Default::default()
+        };
+        if fills.is_empty() {
+            return report;
+        }
+        let mut spread_bps = 0.0;
+        let mut fill_sec = 0.0;
+        let mut order_latency_ms = 0.0;
+        for fill in fills {
+            let side = match fill.direction {
+                Direction::Buy => -1.0,
+                Direction::Sell => 1.0,
+            };
+            let mark = fill.mark_price.to_f64().unwrap_or_default();
+            let price = fill.price.to_f64().unwrap_or_default();
+            if mark > 0.0 {
+                spread_bps += side * (price - mark) / mark * 10_000.0;
+            }
+            fill_sec += (fill.timestamp - self.start_ms) as f64 / 1000.0;
+            order_latency_ms += fill.order_latency_ms as f64;
+            report.filled_amount += &fill.amount;
+            report.pnl += match fill.direction {
+                Direction::Buy => (final_mark - &fill.price) * &fill.amount,
+                Direction::Sell => (&fill.price - final_mark) * &fill.amount,
+            };
+        }
+        let num_fills = fills.len() as f64;
+        report.avg_realized_spread_bps = spread_bps / num_fills;
+        report.avg_fill_sec = fill_sec / num_fills;
+        report.avg_order_latency_ms = order_latency_ms / num_fills;
+        report
+    }
+}
+
+/// Instruments with recorded tickers other than `instrument_name`
+fn recorded_instruments(messages: &[RecordedMessage], instrument_name: &str) -> Vec<String> {
+    let mut names = messages
+        .iter()
+        .filter_map(|m| match m.channel.parse() {
+            Ok(Channel::Ticker { instrument, .. }) => Some(instrument),
+            _ => None,
+        })
+        .filter(|name| name != instrument_name)
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Last recorded mark price of the instrument, used to mark the PnL of the fills
+fn final_mark(messages: &[RecordedMessage], instrument_name: &str) -> Result<BigDecimal> {
+    messages
+        .iter()
+        .rev()
+        .filter(|m| matches!(m.channel.parse(), Ok(Channel::Ticker { .. })))
+        .filter_map(|m| serde_json::from_value::<TickerNotificationData>(m.data.clone()).ok())
+        .find(|d| d.instrument_ticker.instrument_name == instrument_name)
+        .map(|d| d.instrument_ticker.mark_price)
+        .ok_or(Error::msg(format!("No ticker for {} in the recording", instrument_name)))
+}
+
+/// Settlement value of the option if `subaccount_id` held it to expiry and it has settled
+async fn settled_mark(subaccount_id: i64, instrument_name: &str) -> Result<Option<BigDecimal>> {
+    let prices = get_settlement_prices(subaccount_id, instrument_name).await?;
+    match prices.into_iter().find(|p| p.instrument_name == instrument_name) {
+        Some(price) => Ok(Some(settled_value(instrument_name, &price.settlement_price)?)),
+        None => Ok(None),
+    }
+}
+
+/// Runs the option or spot auction of the vault params against a recording, once per value
+/// of `spread_per_min`, and returns one report per run
+pub async fn run_backtest(
+    params: &BacktestParams,
+    vault_params: &LRTCParams,
+) -> Result<Vec<BacktestReport>> {
+    let messages = load_recording(&params.recording).await?;
+    let start_ms = messages.first().ok_or(Error::msg("Empty recording"))?.timestamp;
+    let recording_end_ms = messages.last().unwrap().timestamp;
+    let settled = match (&params.auction, params.settlement_subaccount_id) {
+        (BacktestAuction::Option, Some(id)) => settled_mark(id, &params.instrument_name).await?,
+        _ => None,
+    };
+    let final_mark = match settled {
+        Some(value) => {
+            info!("Backtest marking {} at its settlement {}", params.instrument_name, value);
+            value
+        }
+        None => final_mark(&messages, &params.instrument_name)?,
+    };
+    info!(
+        "Backtest replaying {} messages over {} sec",
+        messages.len(),
+        (recording_end_ms - start_ms) / 1000
+    );
+
+    let option_params = &vault_params.option_auction_params;
+    let spot_params = &vault_params.spot_auction_params;
+    let (base_name, auction_sec, default_spread) = match params.auction {
+        BacktestAuction::Option => (
+            params.instrument_name.clone(),
+            option_params.auction_sec,
+            option_params.iv_spread_per_min,
+        ),
+        BacktestAuction::Spot => (
+            option_params.spot_name.clone(),
+            spot_params.auction_sec,
+            spot_params.spot_spread_per_min,
+        ),
+    };
+    let sim = Simulator {
+        params,
+        messages: &messages,
+        base_name,
+        cash_name: spot_params.cash_name.clone(),
+        start_ms,
+        end_ms: recording_end_ms.min(start_ms + auction_sec * 1000),
+    };
+    let spreads = match params.spread_per_min.is_empty() {
+        true => vec![default_spread],
+        false => params.spread_per_min.clone(),
+    };
+
+    let mut reports = vec![];
+    for spread_per_min in spreads {
+        let res = match params.auction {
+            BacktestAuction::Option => {
+                let mut strategy = option_params.clone();
+                strategy.iv_spread_per_min = spread_per_min;
+                let tolerance = strategy.price_change_tolerance.clone();
+                sim.run(&strategy, auction_sec, tolerance).await?
+            }
+            BacktestAuction::Spot => {
+                let mut strategy = spot_params.clone();
+                strategy.spot_spread_per_min = spread_per_min;
+                let tolerance = strategy.price_change_tolerance.clone();
+                sim.run(&strategy, auction_sec, tolerance).await?
+            }
+        };
+        let report = sim.report(spread_per_min, res, &final_mark);
+        info!("Backtest {:?}", report);
+        reports.push(report);
+    }
+    Ok(reports)
+}
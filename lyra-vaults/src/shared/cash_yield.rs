@@ -0,0 +1,130 @@
+use crate::shared::auction::{LimitOrderAuction, LimitOrderAuctionExecutor, OrderStrategy};
+use crate::shared::dry_run::DryRunRecorder;
+use crate::shared::stages::ExecutorStage;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, Zero};
+use lyra_client::actions::Direction;
+use serde::Deserialize;
+use tracing::info;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CashYieldParams {
+    // Yield-bearing spot asset the idle cash is swapped into, e.g. SUSDE (traded as SUSDE-USDC).
+    // None keeps the cash, which earns the protocol's interest on positive balances.
+    pub wrapper_name: Option<String>,
+    pub min_idle_cash: BigDecimal, // Cash below this is not worth the round trip and stays idle
+    #[serde(default)]
+    pub reserve_cash: BigDecimal, // Kept as cash, e.g. for fees
+    #[serde(default = "default_auction_sec")]
+    pub auction_sec: i64, // Of the deposit and the unwind, the unwind starts this early
+    #[serde(default = "default_max_spread")]
+    pub max_spread: f64, // Over the wrapper's mark, widened linearly over the auction
+    pub price_change_tolerance: BigDecimal,
+}
+
+fn default_auction_sec() -> i64 {
+    600
+}
+
+fn default_max_spread() -> f64 {
+    0.001
+}
+
+impl CashYieldParams {
+    pub fn wrapper_instrument_name(&self, cash_name: &str) -> Option<String> {
+        let wrapper_name = self.wrapper_name.as_ref()?;
+        Some(format!("{}-{}", wrapper_name, cash_name))
+    }
+
+    /// Lead of the unwind before the spot auction, zero if the cash is never swapped
+    pub fn unwind_lead_sec(&self) -> i64 {
+        match self.wrapper_name {
+            Some(_) => self.auction_sec,
+            None => 0,
+        }
+    }
+}
+
+/// Deposits the idle cash above `reserve_cash` into the wrapper (buy) or unwinds the whole
+/// wrapper balance back into cash (sell). Deposits give up once the auction ends, unwinds keep
+/// going at the max spread until nothing is left.
+#[derive(Debug, Clone)]
+pub struct CashYieldStrategy {
+    pub params: CashYieldParams,
+    pub cash_name: String,
+    pub direction: Direction,
+}
+
+impl CashYieldStrategy {
+    fn get_spread(&self, auction: &LimitOrderAuction) -> f64 {
+        let elapsed_sec = chrono::Utc::now().timestamp() - auction.start_timestamp_sec;
+        let progress = elapsed_sec as f64 / auction.auction_sec.max(1) as f64;
+        self.params.max_spread * progress.clamp(0.0, 1.0)
+    }
+}
+
+impl OrderStrategy for CashYieldStrategy {
+    async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal> {
+        let reader = auction.market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let spread = match self.direction {
+            Direction::Buy => 1.0 + self.get_spread(auction),
+            Direction::Sell => 1.0 - self.get_spread(auction),
+        };
+        let price = &ticker.mark_price * BigDecimal::from_f64(spread).unwrap();
+        Ok(price.round(ticker.tick_size.fractional_digit_count()).max(ticker.min_price.clone()))
+    }
+    async fn get_desired_amount(
+        &self,
+        auction: &LimitOrderAuction,
+        price: &BigDecimal,
+    ) -> Result<(Direction, BigDecimal)> {
+        let reader = auction.market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let wrapper_name = auction.instrument_name.split('-').next().unwrap_or_default();
+        let zero = (self.direction, BigDecimal::zero());
+        let amount = match self.direction {
+            Direction::Buy => {
+                let idle_cash = reader.get_amount(&self.cash_name) - &self.params.reserve_cash;
+                if auction.remain_sec() <= 0 || idle_cash < self.params.min_idle_cash {
+                    return Ok(zero);
+                }
+                match price.is_zero() {
+                    true => return Ok(zero),
+                    false => idle_cash / price,
+                }
+            }
+            Direction::Sell => reader.get_amount(wrapper_name),
+        };
+        let amount = amount
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down);
+        if amount < ticker.minimum_amount {
+            return Ok(zero);
+        }
+        Ok((self.direction, amount))
+    }
+}
+
+/// Runs the deposit (buy) or unwind (sell) auction of the wrapper to completion, nothing
+/// without a wrapper
+pub async fn swap_idle_cash(
+    params: &CashYieldParams,
+    cash_name: &str,
+    direction: Direction,
+    dry_run: Option<DryRunRecorder>,
+) -> Result<()> {
+    let Some(instrument_name) = params.wrapper_instrument_name(cash_name) else {
+        return Ok(());
+    };
+    info!("Cash yield {:?} of {}", direction, instrument_name);
+    let now = chrono::Utc::now().timestamp();
+    let tolerance = params.price_change_tolerance.clone();
+    let mut auction =
+        LimitOrderAuction::new(instrument_name, now, params.auction_sec, tolerance).await?;
+    auction.dry_run = dry_run;
+    let strategy =
+        CashYieldStrategy { params: params.clone(), cash_name: cash_name.to_string(), direction };
+    LimitOrderAuctionExecutor { auction, strategy }.run_with_reconnect().await
+}
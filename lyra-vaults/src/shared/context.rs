@@ -0,0 +1,142 @@
+use crate::shared::admin::AdminControl;
+use crate::shared::fees::FeeTracker;
+use crate::shared::health::HealthHandle;
+use crate::shared::instruments::InstrumentFeed;
+use crate::shared::labels::OrderLabels;
+use crate::shared::margin::MarginLimitParams;
+use crate::shared::reconcile::ReconcileGate;
+use crate::shared::reload::LiveParams;
+use crate::shared::risk::RiskHalt;
+use crate::shared::subaccounts::SubaccountParams;
+use crate::shared::withdrawals::WithdrawalReserve;
+use crate::web3::events::TsaEventFeed;
+use anyhow::Result;
+use lyra_client::actions::HttpFallback;
+use lyra_client::config::LyraConfig;
+use std::future::Future;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+tokio::task_local! {
+    static VAULT_CONTEXT: Arc<VaultContext>;
+}
+
+/// Per vault names (and health handle) the stages need, scoped to the vault's task so that
+/// several vaults can run in one process (previously the VAULT_NAME, SPOT_NAME and CASH_NAME
+/// env vars).
+#[derive(Debug, Clone)]
+pub struct VaultContext {
+    pub vault_name: String,
+    pub spot_name: String,
+    pub extra_collaterals: Vec<String>, // collaterals held next to spot_name, e.g. RSWETH
+    pub cash_name: String,
+    pub health: HealthHandle,
+    pub admin: Arc<AdminControl>, // commands of the admin API, see `serve_admin`
+    // None without WEB3_WS_PROVIDER, see `tsa_event_listener`
+    pub tsa_events: Option<Arc<TsaEventFeed>>,
+    pub risk_halt: Option<Arc<RiskHalt>>, // None without risk_limits, see `risk_guard`
+    pub reconcile_gate: Option<Arc<ReconcileGate>>, // None without reconcile, see `reconciler`
+    pub margin_limit: Option<MarginLimitParams>, // checked before each auction order
+    pub fee_budget: Option<Arc<FeeTracker>>, // None without fee_budget, see `FeeTracker`
+    pub http_fallback: Option<Arc<HttpFallback>>, // None without http_fallback, see `HttpFallback`
+    // None without instrument_watch, see `instrument_watcher`
+    pub instrument_events: Option<Arc<InstrumentFeed>>,
+    pub withdrawal_reserve: Arc<WithdrawalReserve>,
+    pub order_labels: Arc<OrderLabels>, // epoch and stage tags of the auction orders
+    pub live_params: Arc<LiveParams>,   // tunable fields changed by params reloads
+    pub subaccounts: SubaccountParams,  // routing of the orders to the vault's subaccounts
+}
+
+impl VaultContext {
+    pub fn from_env() -> Self {
+        let vault_name = std::env::var("VAULT_NAME").expect("VAULT_NAME is not set");
+        Self {
+            order_labels: Arc::new(OrderLabels::new(&vault_name)),
+            vault_name,
+            spot_name: std::env::var("SPOT_NAME").unwrap_or_default(),
+            extra_collaterals: vec![],
+            cash_name: std::env::var("CASH_NAME").unwrap_or_default(),
+            health: HealthHandle::default(),
+            admin: Arc::new(AdminControl::default()),
+            tsa_events: None,
+            risk_halt: None,
+            reconcile_gate: None,
+            margin_limit: None,
+            fee_budget: None,
+            http_fallback: None,
+            instrument_events: None,
+            withdrawal_reserve: Arc::new(WithdrawalReserve::default()),
+            live_params: Arc::new(LiveParams::default()),
+            subaccounts: SubaccountParams::default(),
+        }
+    }
+
+    /// spot_name first, followed by the extra collaterals
+    pub fn collateral_names(&self) -> Vec<String> {
+        let mut names = vec![self.spot_name.clone()];
+        names.extend(self.extra_collaterals.iter().cloned());
+        names
+    }
+
+    /// All subaccounts of the vault, the TSA subaccount first
+    pub fn subaccount_ids(&self) -> Result<Vec<i64>> {
+        Ok(self.subaccounts.all_ids(LyraConfig::current()?.subaccount_id()?))
+    }
+
+    /// Subaccount of the option book, which also quotes RFQs and hedges in the perp
+    pub fn option_subaccount_id(&self) -> Result<i64> {
+        Ok(self.subaccounts.option_id(LyraConfig::current()?.subaccount_id()?))
+    }
+
+    /// Subaccount the orders of the instrument are routed to, see `SubaccountParams`
+    pub fn order_subaccount_id(&self, instrument_name: &str) -> Result<i64> {
+        let tsa_subaccount_id = LyraConfig::current()?.subaccount_id()?;
+        Ok(self.subaccounts.order_id(tsa_subaccount_id, instrument_name))
+    }
+
+    /// Subaccounts an auction of the instrument tracks, its order subaccount first
+    pub fn synced_subaccount_ids(&self, instrument_name: &str) -> Result<Vec<i64>> {
+        let tsa_subaccount_id = LyraConfig::current()?.subaccount_id()?;
+        Ok(self.subaccounts.synced_ids(tsa_subaccount_id, instrument_name))
+    }
+
+    /// Subaccount the stage routes the orders of the instrument to, and the subaccounts its
+    /// auction tracks with that one first
+    pub fn stage_subaccount_ids(
+        &self,
+        stage: &str,
+        instrument_name: &str,
+    ) -> Result<(i64, Vec<i64>)> {
+        let tsa_subaccount_id = LyraConfig::current()?.subaccount_id()?;
+        let order_id = match self.subaccounts.stage_id(stage) {
+            Some(order_id) => order_id,
+            None => self.subaccounts.order_id(tsa_subaccount_id, instrument_name),
+        };
+        let synced_ids =
+            self.subaccounts.synced_ids_from(tsa_subaccount_id, order_id, instrument_name);
+        Ok((order_id, synced_ids))
+    }
+
+    /// Waits while the reconciler reports a drift between the API and on-chain balances, so
+    /// that no new auction starts on balances that may be wrong
+    pub async fn await_reconciled(&self) {
+        let Some(gate) = &self.reconcile_gate else {
+            return;
+        };
+        if let Some(drift) = gate.drift() {
+            warn!("Auction blocked until the balances reconcile: {}", drift);
+            gate.reconciled().await;
+            info!("Balances reconciled, starting the auction");
+        }
+    }
+
+    /// The context of the enclosing `VaultContext::scope`, or the env vars outside of one
+    /// (e.g. in the web3 scripts)
+    pub fn current() -> Arc<VaultContext> {
+        VAULT_CONTEXT.try_with(|ctx| ctx.clone()).unwrap_or_else(|_| Arc::new(Self::from_env()))
+    }
+
+    pub async fn scope<F: Future>(ctx: Arc<VaultContext>, f: F) -> F::Output {
+        VAULT_CONTEXT.scope(ctx, f).await
+    }
+}
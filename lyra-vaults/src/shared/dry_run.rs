@@ -0,0 +1,52 @@
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Logs (and optionally appends to a JSON lines file) actions that would have been sent.
+/// Also remembers the last simulated order price per instrument so that auctions only
+/// re-"send" orders when the price moves beyond their tolerance, same as with real orders.
+#[derive(Debug, Clone)]
+pub struct DryRunRecorder {
+    path: Option<PathBuf>,
+    open_prices: Arc<Mutex<HashMap<String, BigDecimal>>>,
+}
+
+impl DryRunRecorder {
+    pub fn new(path: Option<String>) -> Self {
+        Self { path: path.map(PathBuf::from), open_prices: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    pub async fn record(&self, action: &str, payload: Value) -> Result<()> {
+        info!("[dry run] {}: {}", action, payload);
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let line = json!({
+            "timestamp": chrono::Utc::now().timestamp_millis(),
+            "action": action,
+            "payload": payload,
+        });
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+        file.write_all(format!("{}\n", line).as_bytes()).await?;
+        Ok(())
+    }
+
+    pub async fn open_price(&self, instrument_name: &str) -> Option<BigDecimal> {
+        self.open_prices.lock().await.get(instrument_name).cloned()
+    }
+
+    pub async fn set_open_price(&self, instrument_name: &str, price: Option<BigDecimal>) {
+        let mut open_prices = self.open_prices.lock().await;
+        match price {
+            Some(price) => open_prices.insert(instrument_name.to_string(), price),
+            None => open_prices.remove(instrument_name),
+        };
+    }
+}
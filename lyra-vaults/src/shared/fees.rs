@@ -0,0 +1,176 @@
+use crate::shared::alert::send_alert;
+use crate::shared::context::VaultContext;
+use crate::shared::validation::{ParamsCheck, ValidateParams};
+use bigdecimal::{BigDecimal, FromPrimitive, Zero};
+use orderbook_types::types::orders::{LiquidityRole, TradeResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// Budget of the trading fees (in USDC) the vault's auction orders may pay per epoch. Past
+/// `throttle_fraction` of either budget the orders are only posted (post-only) and priced
+/// `throttle_spread` less aggressively, once either is spent no more orders are sent until the
+/// next epoch.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeeBudgetParams {
+    pub max_epoch_fees: BigDecimal,         // maker and taker fees
+    pub max_taker_fees: Option<BigDecimal>, // of fills crossing the book, None only caps the total
+    #[serde(default = "default_throttle_fraction")]
+    pub throttle_fraction: f64,
+    #[serde(default = "default_throttle_spread")]
+    pub throttle_spread: f64, // fraction of the price the orders are moved away from the book
+    #[serde(default = "default_retry_sec")]
+    pub retry_sec: u64, // Wait before the next order once the budget is spent
+}
+
+fn default_throttle_fraction() -> f64 {
+    0.8
+}
+
+fn default_throttle_spread() -> f64 {
+    0.01
+}
+
+fn default_retry_sec() -> u64 {
+    60
+}
+
+impl ValidateParams for FeeBudgetParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        check.above("max_epoch_fees", &self.max_epoch_fees, &BigDecimal::zero());
+        if let Some(max_taker_fees) = &self.max_taker_fees {
+            check.above("max_taker_fees", max_taker_fees, &BigDecimal::zero());
+        }
+        check.fraction("throttle_fraction", self.throttle_fraction);
+        check.fraction("throttle_spread", self.throttle_spread);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FeeLevel {
+    Normal,
+    Throttled, // post-only orders at a wider spread
+    Exhausted, // no orders until the next epoch
+}
+
+#[derive(Debug)]
+struct FeeState {
+    epoch: Option<u64>, // None until the epoch's fees were loaded
+    fees: HashMap<String, (LiquidityRole, BigDecimal)>, // by trade id
+    level: FeeLevel,
+}
+
+/// Fees paid by the fills of the vault's orders in the current epoch, told apart by the epoch of
+/// their order labels. Auctions record the fills of their market state before each order, the
+/// fills of earlier stages are loaded from the trade history when the epoch changes (or after a
+/// restart).
+#[derive(Debug)]
+pub struct FeeTracker {
+    pub params: FeeBudgetParams,
+    state: Mutex<FeeState>,
+}
+
+impl FeeTracker {
+    pub fn new(params: FeeBudgetParams) -> Self {
+        let state = FeeState { epoch: None, fees: HashMap::new(), level: FeeLevel::Normal };
+        Self { params, state: Mutex::new(state) }
+    }
+
+    /// Loads the fills of the current epoch from the trade history of the vault's subaccounts,
+    /// if the epoch changed since the last load. The epoch is only marked loaded once the
+    /// history of every subaccount was fetched, otherwise the next order retries.
+    pub async fn sync_epoch(&self) {
+        let ctx = VaultContext::current();
+        let epoch = ctx.order_labels.epoch();
+        if self.state.lock().unwrap().epoch == Some(epoch) {
+            return;
+        }
+        let mut fees = HashMap::new();
+        let mut loaded = true;
+        let from_ms = ctx.order_labels.epoch_start_sec() * 1000;
+        let to_ms = chrono::Utc::now().timestamp_millis();
+        let subaccount_ids = ctx.subaccount_ids().unwrap_or_default();
+        for subaccount_id in subaccount_ids {
+            match ctx.order_labels.fetch_trades_by_epoch(subaccount_id, from_ms, to_ms).await {
+                Ok(mut trades_by_epoch) => {
+                    for trade in trades_by_epoch.remove(&epoch).unwrap_or_default() {
+                        fees.insert(trade.trade_id, (trade.liquidity_role, trade.trade_fee));
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to load the fees of epoch {} with {:#}", epoch, e);
+                    loaded = false;
+                }
+            }
+        }
+        info!("Fee budget loaded {} fills of epoch {}", fees.len(), epoch);
+        let mut state = self.state.lock().unwrap();
+        // a failed load keeps the level so that retries do not alert again
+        let level = if loaded { FeeLevel::Normal } else { state.level };
+        *state = FeeState { epoch: loaded.then_some(epoch), fees, level };
+    }
+
+    /// Adds the fills of the vault's orders in the current epoch, e.g. those of an auction
+    pub fn record<'a>(&self, trades: impl Iterator<Item = &'a TradeResponse>) {
+        let ctx = VaultContext::current();
+        let format = ctx.order_labels.format();
+        let mut state = self.state.lock().unwrap();
+        let Some(epoch) = state.epoch else {
+            return;
+        };
+        for trade in trades {
+            match format.parse(&trade.label) {
+                Some(label) if label.vault_name == ctx.vault_name && label.epoch == epoch => {
+                    let fee = (trade.liquidity_role, trade.trade_fee.clone());
+                    state.fees.insert(trade.trade_id.clone(), fee);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Total and taker fees paid in the epoch
+    pub fn fees_paid(&self) -> (BigDecimal, BigDecimal) {
+        let state = self.state.lock().unwrap();
+        let mut total = BigDecimal::zero();
+        let mut taker = BigDecimal::zero();
+        for (role, fee) in state.fees.values() {
+            total += fee;
+            if *role == LiquidityRole::Taker {
+                taker += fee;
+            }
+        }
+        (total, taker)
+    }
+
+    /// Level of the epoch's fees against the budget, logged (and alerted once spent) on change
+    pub async fn level(&self) -> FeeLevel {
+        let (total, taker) = self.fees_paid();
+        let params = &self.params;
+        let fraction = BigDecimal::from_f64(params.throttle_fraction).unwrap_or_default();
+        let mut budgets = vec![(total.clone(), params.max_epoch_fees.clone())];
+        if let Some(max_taker_fees) = &params.max_taker_fees {
+            budgets.push((taker.clone(), max_taker_fees.clone()));
+        }
+        let level = if budgets.iter().any(|(fees, max)| fees >= max) {
+            FeeLevel::Exhausted
+        } else if budgets.iter().any(|(fees, max)| *fees >= max * &fraction) {
+            FeeLevel::Throttled
+        } else {
+            FeeLevel::Normal
+        };
+        let previous = std::mem::replace(&mut self.state.lock().unwrap().level, level);
+        if previous != level {
+            let message = format!(
+                "Fee budget {:?} -> {:?}, paid {} fees ({} taker) of {}",
+                previous, level, total, taker, params.max_epoch_fees
+            );
+            match level {
+                FeeLevel::Exhausted => send_alert(&message).await,
+                _ => warn!("{}", message),
+            }
+        }
+        level
+    }
+}
@@ -0,0 +1,166 @@
+use crate::market::PortfolioGreeks;
+use crate::shared::margin::MarginStatus;
+use crate::shared::nav::VaultNav;
+use crate::shared::schedule::StageSchedule;
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use lyra_client::endpoints::Endpoints;
+use lyra_client::latency;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+/// While a ticker subscription is live, older market data marks the vault as unhealthy
+const MARKET_DATA_STALE_SEC: i64 = 120;
+
+/// What the executor last reported, served on `/status`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VaultHealth {
+    pub stage: Option<String>,
+    pub stage_entered_sec: Option<i64>,
+    pub ticker_subscriptions: usize,
+    pub orderbook_subscriptions: usize,
+    pub trade_subscriptions: usize,
+    pub subaccount_subscriptions: usize,
+    pub last_market_data_ms: Option<i64>,
+    pub pending_deposits: Option<usize>,
+    pub pending_withdrawals: Option<usize>,
+    pub greeks: Option<PortfolioGreeks>,
+    pub nav: Option<VaultNav>,
+    pub risk_breach: Option<String>, // set while the `RiskGuard` halts the vault
+    pub margin: Option<MarginStatus>, // of the most utilized subaccount, see `MarginMonitor`
+    pub reconcile_drift: Option<String>, // set while the `Reconciler` blocks new auctions
+    pub failures: u64,
+    pub last_error: Option<String>,
+    #[serde(skip)]
+    pub schedule: Option<StageSchedule>,
+    pub upcoming_runs: BTreeMap<String, i64>, // next run of each scheduled stage
+}
+
+impl VaultHealth {
+    /// Unhealthy if the executor failed and has not entered a stage since (i.e. restarting),
+    /// or if its ticker subscriptions are up but stopped delivering data
+    pub fn is_healthy(&self, now_ms: i64) -> bool {
+        if self.stage.is_none() && self.last_error.is_some() {
+            return false;
+        }
+        match self.last_market_data_ms {
+            Some(ts) if self.ticker_subscriptions > 0 => now_ms - ts < MARKET_DATA_STALE_SEC * 1000,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HealthHandle(Arc<RwLock<VaultHealth>>);
+
+impl HealthHandle {
+    pub fn update(&self, f: impl FnOnce(&mut VaultHealth)) {
+        if let Ok(mut health) = self.0.write() {
+            f(&mut health);
+        }
+    }
+
+    pub fn snapshot(&self) -> VaultHealth {
+        let mut health = self.0.read().map(|health| health.clone()).unwrap_or_default();
+        if let Some(schedule) = &health.schedule {
+            health.upcoming_runs = schedule.upcoming(chrono::Utc::now());
+        }
+        health
+    }
+
+    pub fn stage_entered(&self, stage: String) {
+        self.update(|h| {
+            h.stage = Some(stage);
+            h.stage_entered_sec = Some(chrono::Utc::now().timestamp());
+        });
+    }
+
+    pub fn market_data_received(&self) {
+        self.update(|h| h.last_market_data_ms = Some(chrono::Utc::now().timestamp_millis()));
+    }
+
+    /// Tracks a live subscription in one of the counters while the subscribe future runs
+    pub async fn track_subscription<T, E>(
+        &self,
+        counter: fn(&mut VaultHealth) -> &mut usize,
+        subscribe: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        self.update(|h| *counter(h) += 1);
+        let res = subscribe.await;
+        self.update(|h| *counter(h) = counter(h).saturating_sub(1));
+        res
+    }
+
+    pub fn executor_failed(&self, error: &anyhow::Error) {
+        self.update(|h| {
+            h.stage = None;
+            h.failures += 1;
+            h.last_error = Some(format!("{:#}", error));
+        });
+    }
+}
+
+/// Health handles of all vaults running in this process, keyed by vault name
+#[derive(Debug, Clone, Default)]
+pub struct HealthRegistry(Arc<RwLock<BTreeMap<String, HealthHandle>>>);
+
+impl HealthRegistry {
+    pub fn register(&self, vault_name: &str) -> HealthHandle {
+        let handle = HealthHandle::default();
+        if let Ok(mut handles) = self.0.write() {
+            handles.insert(vault_name.to_string(), handle.clone());
+        }
+        handle
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, VaultHealth> {
+        let handles = match self.0.read() {
+            Ok(handles) => handles.clone(),
+            Err(_) => return BTreeMap::new(),
+        };
+        handles.into_iter().map(|(name, handle)| (name, handle.snapshot())).collect()
+    }
+}
+
+/// Serves `/healthz` (200 or 503, for liveness probes), `/status` (per vault JSON) and
+/// `/metrics` (RPC and order ack latency percentiles and endpoint health of the process)
+pub async fn serve_health(addr: String, registry: HealthRegistry) -> Result<()> {
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/status", get(status))
+        .route("/metrics", get(metrics))
+        .with_state(registry);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    info!("Health server listening on {}", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn healthz(State(registry): State<HealthRegistry>) -> (StatusCode, Json<Value>) {
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let unhealthy = registry
+        .snapshot()
+        .into_iter()
+        .filter(|(_, health)| !health.is_healthy(now_ms))
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    let status =
+        if unhealthy.is_empty() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(json!({ "healthy": unhealthy.is_empty(), "unhealthy": unhealthy })))
+}
+
+async fn status(State(registry): State<HealthRegistry>) -> Json<BTreeMap<String, VaultHealth>> {
+    Json(registry.snapshot())
+}
+
+async fn metrics() -> Json<Value> {
+    let endpoints = json!({ "ws": Endpoints::ws().status(), "http": Endpoints::http().status() });
+    Json(json!({ "latency": latency::snapshot(), "endpoints": endpoints }))
+}
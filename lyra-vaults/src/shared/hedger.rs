@@ -0,0 +1,165 @@
+use crate::helpers::{fetch_ticker, log_portfolio_greeks};
+use crate::market::{new_market_state, PortfolioGreeks};
+use crate::shared::context::VaultContext;
+use crate::shared::dry_run::DryRunRecorder;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, Zero};
+use lyra_client::actions::{Direction, OrderArgs, OrderResponse};
+use lyra_client::json_rpc::{WsClient, WsClientExt};
+use lyra_client::orders::OrderManager;
+use orderbook_types::types::orders::{OrderType, TimeInForce};
+use orderbook_types::types::tickers::InstrumentTicker;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::select;
+use tracing::{info, warn};
+
+const HEDGE_LABEL: &str = "delta-hedge";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HedgerParams {
+    pub perp_name: String,                  // Perp used for hedging (e.g. ETH-PERP)
+    pub hedge_ratio: BigDecimal, // Fraction of the net delta to hedge, 1 is fully delta neutral
+    pub delta_band: BigDecimal,  // Re-hedge only once the unhedged delta exceeds the band
+    pub spread: f64,             // Limit price offset from the perp mark, e.g. 0.001 for 10bps
+    pub price_change_tolerance: BigDecimal, // Amend the open hedge once the price moved this much
+    pub interval_sec: u64,       // Time between re-hedge checks
+    // Sets cancel on disconnect before resting the hedge, e.g. false to keep it through
+    // reconnects. None leaves the setting of the wallet (the last auction's) as is.
+    #[serde(default)]
+    pub cancel_on_disconnect: Option<bool>,
+}
+
+impl HedgerParams {
+    /// Perp amount to trade (positive to buy), None while the delta is within the band
+    pub fn get_hedge_amount(&self, greeks: &PortfolioGreeks) -> Option<BigDecimal> {
+        let amount = -(greeks.net_delta() * &self.hedge_ratio);
+        match amount.abs() > self.delta_band {
+            true => Some(amount),
+            false => None,
+        }
+    }
+}
+
+/// Keeps the net portfolio delta within a band by resting a single limit order in the perp.
+/// The order is placed, amended and cancelled via the `OrderManager`, labelled so that it
+/// never touches the orders of other auctions on the same subaccount.
+pub struct DeltaHedger {
+    pub params: HedgerParams,
+    pub subaccount_id: i64,
+    pub orders: OrderManager,
+    pub dry_run: Option<DryRunRecorder>,
+}
+
+impl DeltaHedger {
+    pub async fn new(params: HedgerParams, dry_run: Option<DryRunRecorder>) -> Result<Self> {
+        // the perp is hedged from the option book, against the delta of the whole vault
+        let subaccount_id = VaultContext::current().order_subaccount_id(&params.perp_name)?;
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        if let Some(enabled) = params.cancel_on_disconnect {
+            client.set_cancel_on_disconnect(enabled).await?.into_result()?;
+        }
+        let orders = OrderManager::new(client, subaccount_id);
+        Ok(Self { params, subaccount_id, orders, dry_run })
+    }
+
+    /// Re-hedges every `interval_sec` until cancelled (e.g. with `select!`)
+    pub async fn run(&self) -> Result<()> {
+        let rehedge_task = async {
+            loop {
+                if let Err(e) = self.rehedge_once().await {
+                    warn!(error = format!("{:#}", e), "DeltaHedger rehedge failed");
+                }
+                let interval = tokio::time::Duration::from_secs(self.params.interval_sec);
+                tokio::time::sleep(interval).await;
+            }
+        };
+        select! {
+            o = self.orders.run() => {
+                warn!(result = ?o, "DeltaHedger order updates exited");
+                Err(Error::msg("DeltaHedger order updates exited early"))
+            },
+            r = rehedge_task => r,
+        }
+    }
+
+    async fn hedge_orders(&self) -> Vec<OrderResponse> {
+        let orders = self.orders.open_orders().await;
+        orders.into_iter().filter(|o| o.label == HEDGE_LABEL).collect()
+    }
+
+    /// Cancels the resting hedge order, e.g. when the stage running the hedger completes
+    pub async fn cancel_hedge(&self) -> Result<()> {
+        if let Some(dry_run) = &self.dry_run {
+            return dry_run.record("cancel_hedge", json!({ "perp": self.params.perp_name })).await;
+        }
+        for order in self.hedge_orders().await {
+            self.orders.cancel(&order.order_id).await?;
+        }
+        Ok(())
+    }
+
+    fn get_hedge_price(&self, ticker: &InstrumentTicker, amount: &BigDecimal) -> BigDecimal {
+        let side = if amount > &BigDecimal::zero() { 1.0 } else { -1.0 };
+        let offset = BigDecimal::from_f64(1.0 + side * self.params.spread).unwrap();
+        let price = (&ticker.mark_price * offset).round(ticker.tick_size.fractional_digit_count());
+        price.max(ticker.min_price.clone()).min(ticker.max_price.clone())
+    }
+
+    pub async fn rehedge_once(&self) -> Result<()> {
+        let subaccount_ids = VaultContext::current().subaccount_ids()?;
+        let greeks = log_portfolio_greeks(&subaccount_ids).await?;
+        let market = new_market_state();
+        fetch_ticker(market.clone(), &self.params.perp_name).await?;
+        let reader = market.read().await;
+        let ticker = reader
+            .get_tickers()
+            .get(&self.params.perp_name)
+            .ok_or(Error::msg("Perp ticker not found"))?;
+
+        let amount = match self.params.get_hedge_amount(&greeks) {
+            Some(amount) => amount
+                .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down),
+            None => BigDecimal::zero(),
+        };
+        if amount.abs() < ticker.minimum_amount {
+            info!(net_delta = %greeks.net_delta(), "DeltaHedger net delta within band");
+            return self.cancel_hedge().await;
+        }
+        let direction = if amount > BigDecimal::zero() { Direction::Buy } else { Direction::Sell };
+        let price = self.get_hedge_price(ticker, &amount);
+        info!(net_delta = %greeks.net_delta(), %amount, %price, "DeltaHedger hedging");
+        if let Some(dry_run) = &self.dry_run {
+            let payload =
+                json!({ "perp": self.params.perp_name, "amount": amount, "price": price });
+            return dry_run.record("hedge", payload).await;
+        }
+
+        let open = self.hedge_orders().await;
+        if let [order] = open.as_slice() {
+            let remaining = &order.amount - &order.filled_amount;
+            let is_same_hedge = order.direction == direction && remaining == amount.abs();
+            if is_same_hedge {
+                if (&order.limit_price - &price).abs() > self.params.price_change_tolerance {
+                    self.orders.amend(ticker, &order.order_id, price).await?;
+                }
+                return Ok(());
+            }
+        }
+        self.cancel_hedge().await?;
+        let args = OrderArgs {
+            amount: amount.abs(),
+            limit_price: price,
+            direction,
+            time_in_force: TimeInForce::Gtc,
+            order_type: OrderType::Limit,
+            mmp: false,
+            label: HEDGE_LABEL.to_string(),
+            max_slippage_bps: None,
+        };
+        let order = self.orders.place(ticker, args).await?;
+        info!(order_id = %order.order_id, "DeltaHedger placed order");
+        Ok(())
+    }
+}
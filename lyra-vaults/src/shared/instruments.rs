@@ -0,0 +1,190 @@
+use crate::helpers::sync_vault_subaccounts;
+use crate::market::{new_market_state, MarketState};
+use anyhow::Result;
+use bigdecimal::Zero;
+use lyra_client::json_rpc::http_rpc;
+use orderbook_types::types::tickers::{InstrumentType, InstrumentsResponse};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+const EVENT_CAPACITY: usize = 64;
+
+/// Refresh of the instrument lists by the `InstrumentWatcher`
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstrumentWatchParams {
+    #[serde(default = "default_refresh_interval_sec")]
+    pub refresh_interval_sec: u64,
+    pub expiry_warning_hours: u64, // Emits `ExpiringSoon` once a held option is this close to expiry
+}
+
+fn default_refresh_interval_sec() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstrumentEvent {
+    /// Newly listed option or perp, e.g. a new expiry or strike
+    Listed(String),
+    /// No longer listed or active, e.g. expired or deactivated
+    Delisted(String),
+    /// A held option is within `expiry_warning_hours` of its expiry, sent once per option
+    ExpiringSoon { instrument_name: String, expiry_sec: i64 },
+}
+
+impl InstrumentEvent {
+    pub fn instrument_name(&self) -> &str {
+        match self {
+            InstrumentEvent::Listed(name) | InstrumentEvent::Delisted(name) => name,
+            InstrumentEvent::ExpiringSoon { instrument_name, .. } => instrument_name,
+        }
+    }
+}
+
+/// Receiving end of the instrument watcher, read by the stages through the `VaultContext`
+#[derive(Debug)]
+pub struct InstrumentFeed(broadcast::Sender<InstrumentEvent>);
+
+impl InstrumentFeed {
+    fn new() -> Self {
+        Self(broadcast::channel(EVENT_CAPACITY).0)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<InstrumentEvent> {
+        self.0.subscribe()
+    }
+
+    fn send(&self, event: InstrumentEvent) {
+        info!("Instrument event: {:?}", event);
+        // no receivers while no stage is listening
+        let _ = self.0.send(event);
+    }
+}
+
+/// Waits for the next event of one of the instruments, never resolves without a feed
+pub async fn wait_for_event(
+    feed: Option<&InstrumentFeed>,
+    instrument_names: &[String],
+) -> InstrumentEvent {
+    let Some(feed) = feed else {
+        return std::future::pending().await;
+    };
+    let mut receiver = feed.subscribe();
+    loop {
+        match receiver.recv().await {
+            Ok(event) if instrument_names.iter().any(|n| n == event.instrument_name()) => {
+                return event
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(n)) => warn!("Missed {} instrument events", n),
+            Err(broadcast::error::RecvError::Closed) => return std::future::pending().await,
+        }
+    }
+}
+
+/// Refreshes the options and perps of the currency in its `MarketState` every
+/// `refresh_interval_sec` and sends an event for every listing, delisting and held option
+/// coming close to expiry. The first refresh only loads the instruments.
+pub struct InstrumentWatcher {
+    params: InstrumentWatchParams,
+    currency: String,
+    market: MarketState,
+    feed: Arc<InstrumentFeed>,
+}
+
+impl InstrumentWatcher {
+    pub fn new(
+        params: InstrumentWatchParams,
+        currency: String,
+        feed: Arc<InstrumentFeed>,
+    ) -> Result<Self> {
+        Ok(Self { params, currency, market: new_market_state(), feed })
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let interval = tokio::time::Duration::from_secs(self.params.refresh_interval_sec);
+        let mut is_loaded = false;
+        let mut warned = HashSet::new();
+        loop {
+            match self.refresh(is_loaded).await {
+                Ok(()) => is_loaded = true,
+                Err(e) => warn!("Instrument refresh failed with {:#}", e),
+            }
+            if let Err(e) = self.check_expiries(&mut warned).await {
+                warn!("Instrument expiry check failed with {:#}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn refresh(&self, send_events: bool) -> Result<()> {
+        let mut instruments = vec![];
+        for instrument_type in [InstrumentType::Option, InstrumentType::Perp] {
+            let res = http_rpc::<_, InstrumentsResponse>(
+                "public/get_instruments",
+                json!({
+                    "currency": self.currency,
+                    "instrument_type": instrument_type,
+                    "expired": false,
+                }),
+                None,
+            )
+            .await?
+            .into_result()?;
+            instruments.extend(res.result);
+        }
+        let mut writer = self.market.write().await;
+        let (listed, delisted) = writer.refresh_instruments(&self.currency, instruments);
+        drop(writer);
+        info!("Instruments refreshed, {} listed, {} delisted", listed.len(), delisted.len());
+        if !send_events {
+            return Ok(());
+        }
+        listed.into_iter().for_each(|name| self.feed.send(InstrumentEvent::Listed(name)));
+        delisted.into_iter().for_each(|name| self.feed.send(InstrumentEvent::Delisted(name)));
+        Ok(())
+    }
+
+    async fn check_expiries(&self, warned: &mut HashSet<String>) -> Result<()> {
+        // fresh positions, closed ones are not returned and would stay in a reused state
+        let positions = new_market_state();
+        sync_vault_subaccounts(positions.clone(), vec![]).await?;
+        let warning_sec = self.params.expiry_warning_hours as i64 * 3600;
+        let now = chrono::Utc::now().timestamp();
+        let positions = positions.read().await;
+        let reader = self.market.read().await;
+        for position in positions.iter_positions().filter(|p| !p.amount.is_zero()) {
+            let instrument = reader.get_instrument(&position.instrument_name);
+            let Some(details) = instrument.and_then(|i| i.option_details.as_ref()) else {
+                continue;
+            };
+            if details.expiry - now > warning_sec || warned.contains(&position.instrument_name) {
+                continue;
+            }
+            warned.insert(position.instrument_name.clone());
+            self.feed.send(InstrumentEvent::ExpiringSoon {
+                instrument_name: position.instrument_name.clone(),
+                expiry_sec: details.expiry,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The instrument watcher task of the vault (and the feed the stages read its events from) if
+/// `instrument_watch` is set in its params
+pub fn instrument_watcher(
+    params: Option<InstrumentWatchParams>,
+    currency: String,
+) -> Option<(Arc<InstrumentFeed>, impl Future<Output = Result<()>>)> {
+    let params = params?;
+    let feed = Arc::new(InstrumentFeed::new());
+    let watcher_feed = feed.clone();
+    let watcher =
+        async move { InstrumentWatcher::new(params, currency, watcher_feed)?.run().await };
+    Some((feed, watcher))
+}
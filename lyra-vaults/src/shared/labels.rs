@@ -0,0 +1,319 @@
+use crate::shared::accounting::fetch_trades;
+use anyhow::{Error, Result};
+use orderbook_types::types::orders::TradeResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+const DEFAULT_LABEL_FORMAT: &str = "{vault}:{epoch}:{stage}:{attempt}";
+
+/// Label of an order sent by the auction executor, e.g. `WEETHC:12:OptionAuction:3` for the
+/// 3rd order of the option auction in the 12th epoch of the vault
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OrderLabel {
+    pub vault_name: String,
+    pub epoch: u64,
+    pub stage: String,
+    pub attempt: u64, // orders sent (or replaced) since the stage was entered, from 1
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Vault,
+    Epoch,
+    Stage,
+    Attempt,
+}
+
+/// Template of the order labels with the `{vault}`, `{epoch}`, `{stage}` and `{attempt}`
+/// placeholders, each exactly once and separated by a literal so that labels parse back
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelFormat(Vec<Segment>);
+
+impl FromStr for LabelFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        let mut segments = vec![];
+        let mut rest = format;
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                segments.push(Segment::Literal(rest.to_string()));
+                break;
+            };
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest.find('}').ok_or(Error::msg("Unclosed placeholder in label format"))?;
+            segments.push(match &rest[start + 1..end] {
+                "vault" => Segment::Vault,
+                "epoch" => Segment::Epoch,
+                "stage" => Segment::Stage,
+                "attempt" => Segment::Attempt,
+                other => return Err(Error::msg(format!("Unknown label placeholder {}", other))),
+            });
+            rest = &rest[end + 1..];
+        }
+        for placeholder in [Segment::Vault, Segment::Epoch, Segment::Stage, Segment::Attempt] {
+            if segments.iter().filter(|s| **s == placeholder).count() != 1 {
+                return Err(Error::msg(format!(
+                    "Label format needs {:?} exactly once",
+                    placeholder
+                )));
+            }
+        }
+        let is_adjacent = segments
+            .windows(2)
+            .any(|w| !matches!(w[0], Segment::Literal(_)) && !matches!(w[1], Segment::Literal(_)));
+        if is_adjacent {
+            return Err(Error::msg("Label placeholders must be separated by a literal"));
+        }
+        Ok(Self(segments))
+    }
+}
+
+impl Default for LabelFormat {
+    fn default() -> Self {
+        LabelFormat::from_str(DEFAULT_LABEL_FORMAT).unwrap()
+    }
+}
+
+impl LabelFormat {
+    /// The format from ORDER_LABEL_FORMAT, the default one if unset or invalid
+    pub fn from_env() -> Self {
+        let Ok(format) = std::env::var("ORDER_LABEL_FORMAT") else {
+            return Self::default();
+        };
+        LabelFormat::from_str(&format).unwrap_or_else(|e| {
+            warn!("Invalid ORDER_LABEL_FORMAT {}: {:#}, using the default", format, e);
+            Self::default()
+        })
+    }
+
+    pub fn format(&self, label: &OrderLabel) -> String {
+        let segments = self.0.iter().map(|segment| match segment {
+            Segment::Literal(literal) => literal.clone(),
+            Segment::Vault => label.vault_name.clone(),
+            Segment::Epoch => label.epoch.to_string(),
+            Segment::Stage => label.stage.clone(),
+            Segment::Attempt => label.attempt.to_string(),
+        });
+        segments.collect()
+    }
+
+    /// The label back from an order or trade label, None for labels of another format (e.g.
+    /// hedge orders or orders sent by hand)
+    pub fn parse(&self, label: &str) -> Option<OrderLabel> {
+        let (mut vault_name, mut epoch, mut stage, mut attempt) = (None, None, None, None);
+        let mut rest = label;
+        for (i, segment) in self.0.iter().enumerate() {
+            if let Segment::Literal(literal) = segment {
+                rest = rest.strip_prefix(literal.as_str())?;
+                continue;
+            }
+            // placeholders run up to the next literal, or to the end
+            let end = match self.0.get(i + 1) {
+                Some(Segment::Literal(literal)) => rest.find(literal.as_str())?,
+                _ => rest.len(),
+            };
+            let value = &rest[..end];
+            rest = &rest[end..];
+            match segment {
+                Segment::Vault => vault_name = Some(value.to_string()),
+                Segment::Epoch => epoch = Some(value.parse().ok()?),
+                Segment::Stage => stage = Some(value.to_string()),
+                Segment::Attempt => attempt = Some(value.parse().ok()?),
+                Segment::Literal(_) => {}
+            }
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+        Some(OrderLabel {
+            vault_name: vault_name?,
+            epoch: epoch?,
+            stage: stage?,
+            attempt: attempt?,
+        })
+    }
+}
+
+/// Epoch number of a vault, kept in STATE_DIR next to the executor state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct EpochCounter {
+    epoch: u64,
+    start_sec: i64,
+    #[serde(default)]
+    stage: String, // that started the epoch
+    #[serde(default)]
+    stage_done: bool, // whether a later stage was entered
+}
+
+impl EpochCounter {
+    fn path(vault_name: &str) -> PathBuf {
+        let dir = std::env::var("STATE_DIR").unwrap_or("./state".to_string());
+        PathBuf::from(dir).join(format!("{}_epoch.json", vault_name.to_lowercase()))
+    }
+
+    async fn load(vault_name: &str) -> Result<Self> {
+        let path = Self::path(vault_name);
+        if !tokio::fs::try_exists(&path).await? {
+            return Ok(Self::default());
+        }
+        let contents = tokio::fs::read_to_string(&path).await?;
+        match serde_json::from_str::<EpochCounter>(&contents) {
+            Ok(counter) => Ok(counter),
+            Err(e) => {
+                warn!("Ignoring corrupt epoch counter at {:?}: {:?}", path, e);
+                Ok(Self::default())
+            }
+        }
+    }
+
+    async fn save(&self, vault_name: &str) -> Result<()> {
+        let path = Self::path(vault_name);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&path, serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct LabelState {
+    format: LabelFormat,
+    epoch: u64,
+    start_sec: i64, // of the epoch, 0 if never started
+    stage: String,
+    attempt: u64,
+    epoch_stage: String, // that started the epoch
+    epoch_stage_done: bool,
+    restored: bool, // until the first stage entry after a restart
+}
+
+impl LabelState {
+    fn counter(&self) -> EpochCounter {
+        EpochCounter {
+            epoch: self.epoch,
+            start_sec: self.start_sec,
+            stage: self.epoch_stage.clone(),
+            stage_done: self.epoch_stage_done,
+        }
+    }
+}
+
+/// Labels of the vault's auction orders, tagged with the epoch and stage they were sent in.
+/// The executor moves it along on every stage entry, see `VaultStrategy::is_epoch_start`.
+#[derive(Debug)]
+pub struct OrderLabels {
+    vault_name: String,
+    state: Mutex<LabelState>,
+}
+
+impl OrderLabels {
+    pub fn new(vault_name: &str) -> Self {
+        Self { vault_name: vault_name.to_string(), state: Mutex::new(LabelState::default()) }
+    }
+
+    pub fn format(&self) -> LabelFormat {
+        self.state.lock().unwrap().format.clone()
+    }
+
+    /// Restores the epoch number of the vault (e.g. after a restart) and reads the label format
+    /// from ORDER_LABEL_FORMAT
+    pub async fn restore(&self) -> Result<()> {
+        let counter = EpochCounter::load(&self.vault_name).await?;
+        info!("{} order labels at epoch {}", self.vault_name, counter.epoch);
+        let mut state = self.state.lock().unwrap();
+        state.format = LabelFormat::from_env();
+        state.epoch = counter.epoch;
+        state.start_sec = counter.start_sec;
+        state.epoch_stage = counter.stage;
+        state.epoch_stage_done = counter.stage_done;
+        state.restored = true;
+        Ok(())
+    }
+
+    /// Resets the attempt counter for the stage, and starts the next epoch on epoch starts. A
+    /// restart back into the stage that started the epoch, before any later stage was entered,
+    /// continues the epoch (e.g. an interrupted option auction).
+    pub async fn stage_entered(&self, stage: String, is_epoch_start: bool) -> Result<()> {
+        let counter = {
+            let mut state = self.state.lock().unwrap();
+            let restored = std::mem::take(&mut state.restored);
+            let resumed = restored && !state.epoch_stage_done && state.epoch_stage == stage;
+            state.stage = stage.clone();
+            state.attempt = 0;
+            if is_epoch_start && !resumed {
+                state.epoch += 1;
+                state.start_sec = chrono::Utc::now().timestamp();
+                state.epoch_stage = stage;
+                state.epoch_stage_done = false;
+            } else if resumed || state.epoch_stage_done {
+                return Ok(());
+            } else {
+                state.epoch_stage_done = true;
+            }
+            state.counter()
+        };
+        counter.save(&self.vault_name).await
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.state.lock().unwrap().epoch
+    }
+
+    pub fn epoch_start_sec(&self) -> i64 {
+        self.state.lock().unwrap().start_sec
+    }
+
+    /// Label of the next order of the current stage
+    pub fn next_label(&self) -> String {
+        let mut state = self.state.lock().unwrap();
+        state.attempt += 1;
+        let label = OrderLabel {
+            vault_name: self.vault_name.clone(),
+            epoch: state.epoch,
+            stage: state.stage.clone(),
+            attempt: state.attempt,
+        };
+        state.format.format(&label)
+    }
+
+    /// True if the label is one of the vault's orders from another epoch or stage, e.g. a late
+    /// fill of the previous auction. Unlabeled orders are not told apart.
+    pub fn is_from_other_stage(&self, label: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        let Some(label) = state.format.parse(label) else {
+            return false;
+        };
+        label.vault_name == self.vault_name
+            && (label.epoch != state.epoch || label.stage != state.stage)
+    }
+
+    /// The vault's trades in the time range grouped by the epoch of their order label, trades
+    /// of orders without a parseable label of the vault are left out
+    pub async fn fetch_trades_by_epoch(
+        &self,
+        subaccount_id: i64,
+        from_ms: i64,
+        to_ms: i64,
+    ) -> Result<BTreeMap<u64, Vec<TradeResponse>>> {
+        let format = self.format();
+        let mut trades_by_epoch = BTreeMap::<u64, Vec<TradeResponse>>::new();
+        for trade in fetch_trades(subaccount_id, from_ms, to_ms).await? {
+            match format.parse(&trade.label) {
+                Some(label) if label.vault_name == self.vault_name => {
+                    trades_by_epoch.entry(label.epoch).or_default().push(trade)
+                }
+                _ => {}
+            }
+        }
+        Ok(trades_by_epoch)
+    }
+}
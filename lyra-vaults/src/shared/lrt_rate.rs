@@ -0,0 +1,102 @@
+use crate::shared::oracle::get_index_price;
+use crate::web3::lrt::{get_lrt_rate, LrtRateMethod};
+use crate::web3::providers::MAINNET_CHAIN_ID;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, Zero};
+use ethers::abi::Address;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const RATE_TTL: Duration = Duration::from_secs(300);
+
+// LRT rates only move on rebases, so calls within the TTL are answered from here
+static RATE_CACHE: OnceLock<Mutex<HashMap<String, (Instant, BigDecimal)>>> = OnceLock::new();
+
+/// On-chain exchange rate of an LRT (e.g. weETH) into its underlying (e.g. ETH), configured by
+/// `{LRT}_RATE_ADDRESS`, `{LRT}_RATE_CHAIN_ID` (mainnet by default), `{LRT}_RATE_METHOD`
+/// (get_rate by default) and `{LRT}_RATE_UNDERLYING` (ETH by default)
+#[derive(Debug, Clone)]
+pub struct LrtRateSource {
+    pub lrt_name: String,
+    pub chain_id: u64,
+    pub address: Address,
+    pub method: LrtRateMethod,
+    pub underlying: String,
+}
+
+impl LrtRateSource {
+    /// The rate source of the LRT, None if `{LRT}_RATE_ADDRESS` is not set
+    pub fn from_env(lrt_name: &str) -> Result<Option<Self>> {
+        let prefix = lrt_name.to_uppercase();
+        let Ok(address) = std::env::var(format!("{prefix}_RATE_ADDRESS")) else {
+            return Ok(None);
+        };
+        let chain_id = match std::env::var(format!("{prefix}_RATE_CHAIN_ID")) {
+            Ok(chain_id) => chain_id.parse()?,
+            Err(_) => MAINNET_CHAIN_ID,
+        };
+        let method = match std::env::var(format!("{prefix}_RATE_METHOD")) {
+            Ok(method) => LrtRateMethod::from_str(&method)?,
+            Err(_) => LrtRateMethod::default(),
+        };
+        Ok(Some(Self {
+            lrt_name: lrt_name.to_string(),
+            chain_id,
+            address: Address::from_str(&address)?,
+            method,
+            underlying: std::env::var(format!("{prefix}_RATE_UNDERLYING"))
+                .unwrap_or("ETH".to_string()),
+        }))
+    }
+
+    /// Underlying per LRT, cached for a few minutes
+    pub async fn get_rate(&self) -> Result<BigDecimal> {
+        let cache = RATE_CACHE.get_or_init(Default::default);
+        if let Some((fetched_at, rate)) = cache.lock().unwrap().get(&self.lrt_name) {
+            if fetched_at.elapsed() < RATE_TTL {
+                return Ok(rate.clone());
+            }
+        }
+        let rate = get_lrt_rate(self.chain_id, self.address, self.method).await?;
+        if rate <= BigDecimal::zero() {
+            return Err(Error::msg(format!("Non-positive {} rate {}", self.lrt_name, rate)));
+        }
+        info!("{} rate {} {}", self.lrt_name, rate, self.underlying);
+        cache.lock().unwrap().insert(self.lrt_name.clone(), (Instant::now(), rate.clone()));
+        Ok(rate)
+    }
+
+    /// Price of the LRT in the Lyra quote (e.g. USDC): the rate times the index price of the
+    /// underlying's perp
+    pub async fn get_fair_price(&self) -> Result<BigDecimal> {
+        let rate = self.get_rate().await?;
+        let index_price = get_index_price(&format!("{}-PERP", self.underlying)).await?;
+        Ok(rate * index_price)
+    }
+}
+
+/// Fair prices of the collaterals with a rate source, failing sources are logged and left out
+/// so that their collateral falls back to the spot mark
+pub async fn get_fair_prices(collateral_names: &[String]) -> HashMap<String, BigDecimal> {
+    let mut fair_prices = HashMap::new();
+    for collateral_name in collateral_names {
+        let source = match LrtRateSource::from_env(collateral_name) {
+            Ok(Some(source)) => source,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Invalid rate source of {}: {:#}", collateral_name, e);
+                continue;
+            }
+        };
+        match source.get_fair_price().await {
+            Ok(price) => {
+                fair_prices.insert(collateral_name.clone(), price);
+            }
+            Err(e) => warn!("Fair price of {} failed with {:#}", collateral_name, e),
+        }
+    }
+    fair_prices
+}
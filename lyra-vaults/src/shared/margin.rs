@@ -0,0 +1,408 @@
+use crate::shared::alert::send_alert;
+use crate::shared::context::VaultContext;
+use crate::shared::validation::{ParamsCheck, ValidateParams};
+use crate::web3::{get_tsa_contract, sign_order};
+use anyhow::Result;
+use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, ToPrimitive, Zero};
+use ethers::providers::Middleware;
+use futures::future::try_join_all;
+use lyra_client::actions::OrderArgs;
+use lyra_client::auth::get_auth_headers;
+use lyra_client::json_rpc::{http_rpc, WsClient, WsClientExt};
+use orderbook_types::generated::private_get_margin::{
+    PrivateGetMarginParamsSchema, PrivateGetMarginResponseSchema, SimulatedPositionSchema,
+};
+use orderbook_types::generated::private_get_subaccount::{
+    InstrumentType, PrivateGetSubaccountParamsSchema, PrivateGetSubaccountResponseSchema,
+    PrivateGetSubaccountResultSchema,
+};
+use orderbook_types::types::orders::{Direction, OrderType, TimeInForce};
+use orderbook_types::types::tickers::TickerResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::future::Future;
+use tracing::{info, warn};
+
+const EMERGENCY_LABEL: &str = "margin-emergency";
+
+/// Thresholds of the `MarginMonitor` on the maintenance margin utilization of the subaccount,
+/// i.e. the maintenance margin required by the positions over the credit of the collaterals.
+/// The subaccount is liquidated at a utilization of 1.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarginMonitorParams {
+    pub alert_utilization: f64,     // Alerts once the utilization is above it
+    pub emergency_utilization: f64, // Runs the emergency action once the utilization is above it
+    #[serde(default)]
+    pub emergency_action: EmergencyAction,
+    #[serde(default = "default_check_interval_sec")]
+    pub check_interval_sec: u64,
+}
+
+fn default_check_interval_sec() -> u64 {
+    10
+}
+
+/// What the `MarginMonitor` does when the utilization crosses `emergency_utilization`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmergencyAction {
+    /// Only send the emergency alert
+    #[default]
+    Alert,
+    /// Cancel all open orders, releasing the margin they hold
+    CancelOrders,
+    /// Cancel all open orders and buy back `fraction` of every short option with IOC orders
+    /// priced at most `max_slippage` above mark
+    ReduceOptions { fraction: f64, max_slippage: f64 },
+}
+
+/// Limit of the auction orders on the initial margin utilization of the subaccount, checked with
+/// `preview_order_margin` before each order is sent
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarginLimitParams {
+    pub max_initial_utilization: f64, // Orders are resized (or skipped) to stay below it
+    #[serde(default = "default_resize")]
+    pub resize: bool, // false skips orders above the limit instead of resizing them
+    #[serde(default = "default_retry_sec")]
+    pub retry_sec: u64, // Wait before the next order after one was skipped
+}
+
+fn default_resize() -> bool {
+    true
+}
+
+fn default_retry_sec() -> u64 {
+    10
+}
+
+impl ValidateParams for MarginLimitParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        check.fraction("max_initial_utilization", self.max_initial_utilization);
+    }
+}
+
+/// Initial margin of the subaccount before and after a prospective order fills
+#[derive(Debug, Clone, Serialize)]
+pub struct MarginPreview {
+    pub pre_initial_margin: BigDecimal, // Surplus over the requirement, as in get_subaccount
+    pub post_initial_margin: BigDecimal,
+    pub collaterals_initial_margin: BigDecimal,
+    pub is_valid_trade: bool,
+}
+
+impl MarginPreview {
+    pub fn pre_utilization(&self) -> f64 {
+        utilization(&self.pre_initial_margin, &self.collaterals_initial_margin)
+    }
+
+    pub fn post_utilization(&self) -> f64 {
+        utilization(&self.post_initial_margin, &self.collaterals_initial_margin)
+    }
+
+    /// Largest fraction of the order that keeps the utilization at most `max_utilization`,
+    /// assuming the margin it takes is linear in its amount. 1 if the whole order fits.
+    pub fn max_fraction(&self, max_utilization: f64) -> f64 {
+        let credit = self.collaterals_initial_margin.to_f64().unwrap_or_default();
+        let pre = self.pre_initial_margin.to_f64().unwrap_or_default();
+        let post = self.post_initial_margin.to_f64().unwrap_or_default();
+        let used = pre - post;
+        if used <= 0.0 || self.post_utilization() <= max_utilization {
+            return 1.0;
+        }
+        let available = pre - (1.0 - max_utilization) * credit;
+        (available / used).clamp(0.0, 1.0)
+    }
+}
+
+/// Share of the collateral credit taken by the margin requirement, 1 - surplus / credit
+fn utilization(surplus: &BigDecimal, credit: &BigDecimal) -> f64 {
+    let credit = credit.to_f64().unwrap_or_default();
+    let surplus = surplus.to_f64().unwrap_or_default();
+    match credit > 0.0 {
+        true => 1.0 - surplus / credit,
+        false if surplus < 0.0 => f64::INFINITY,
+        false => 0.0,
+    }
+}
+
+/// Simulates the order filling in full with `private/get_margin`. The collateral credit is
+/// taken from the current subaccount, so premiums the order would receive are not counted.
+pub async fn preview_order_margin(
+    subaccount_id: i64,
+    instrument_name: &str,
+    direction: Direction,
+    amount: &BigDecimal,
+) -> Result<MarginPreview> {
+    let headers = get_auth_headers().await?;
+    let subacc = http_rpc::<_, PrivateGetSubaccountResponseSchema>(
+        "private/get_subaccount",
+        PrivateGetSubaccountParamsSchema { subaccount_id },
+        Some(headers.clone()),
+    )
+    .await?
+    .into_result()?
+    .result;
+    let amount = match direction {
+        Direction::Buy => amount.clone(),
+        Direction::Sell => -amount,
+    };
+    let change = SimulatedPositionSchema {
+        amount,
+        entry_price: None,
+        instrument_name: instrument_name.to_string(),
+    };
+    let margin = http_rpc::<_, PrivateGetMarginResponseSchema>(
+        "private/get_margin",
+        PrivateGetMarginParamsSchema {
+            simulated_position_changes: Some(vec![change]),
+            subaccount_id,
+        },
+        Some(headers),
+    )
+    .await?
+    .into_result()?
+    .result;
+    Ok(MarginPreview {
+        pre_initial_margin: margin.pre_initial_margin,
+        post_initial_margin: margin.post_initial_margin,
+        collaterals_initial_margin: subacc.collaterals_initial_margin,
+        is_valid_trade: margin.is_valid_trade,
+    })
+}
+
+/// Margin of the subaccount the monitor last fetched, served on `/status`
+#[derive(Debug, Clone, Serialize)]
+pub struct MarginStatus {
+    pub subaccount_id: i64,
+    pub maintenance_margin: BigDecimal, // Surplus over the requirement, liquidated below zero
+    pub collaterals_maintenance_margin: BigDecimal,
+    pub positions_maintenance_margin: BigDecimal,
+    pub subaccount_value: BigDecimal,
+    pub utilization: f64,
+    pub is_under_liquidation: bool,
+    pub timestamp_sec: i64,
+}
+
+impl MarginStatus {
+    pub fn from_subaccount(subacc: &PrivateGetSubaccountResultSchema) -> Self {
+        let utilization =
+            utilization(&subacc.maintenance_margin, &subacc.collaterals_maintenance_margin);
+        Self {
+            subaccount_id: subacc.subaccount_id,
+            maintenance_margin: subacc.maintenance_margin.clone(),
+            collaterals_maintenance_margin: subacc.collaterals_maintenance_margin.clone(),
+            positions_maintenance_margin: subacc.positions_maintenance_margin.clone(),
+            subaccount_value: subacc.subaccount_value.clone(),
+            utilization,
+            is_under_liquidation: subacc.is_under_liquidation,
+            timestamp_sec: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum MarginLevel {
+    Normal,
+    Alert,
+    Emergency,
+}
+
+/// Fetches the subaccount margin every `check_interval_sec`, reports it in the vault health and
+/// alerts whenever the utilization crosses a threshold (either way). The emergency action runs
+/// once per crossing of `emergency_utilization`, and again only after it dropped below.
+pub struct MarginMonitor {
+    params: MarginMonitorParams,
+    subaccount_id: i64,
+    dry_run: bool, // only alert, leave the orders to the dry run recorder
+}
+
+impl MarginMonitor {
+    pub fn new(params: MarginMonitorParams, subaccount_id: i64, dry_run: bool) -> Self {
+        Self { params, subaccount_id, dry_run }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let interval = tokio::time::Duration::from_secs(self.params.check_interval_sec);
+        let mut level = MarginLevel::Normal;
+        loop {
+            match self.check(level).await {
+                Ok(new_level) => level = new_level,
+                Err(e) => warn!("Margin monitor check failed with {:#}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn check(&self, previous: MarginLevel) -> Result<MarginLevel> {
+        let subacc = self.get_subaccount().await?;
+        let status = MarginStatus::from_subaccount(&subacc);
+        info!(
+            "Margin utilization of {} {:.4}: {:?}",
+            self.subaccount_id, status.utilization, status
+        );
+        // the most utilized subaccount is reported, replaced by the same one once it recovered
+        VaultContext::current().health.update(|h| {
+            let worse = h.margin.as_ref().filter(|m| m.subaccount_id != status.subaccount_id);
+            if worse.map_or(true, |m| m.utilization <= status.utilization) {
+                h.margin = Some(status.clone());
+            }
+        });
+
+        let level = match status.utilization {
+            u if u >= self.params.emergency_utilization || status.is_under_liquidation => {
+                MarginLevel::Emergency
+            }
+            u if u >= self.params.alert_utilization => MarginLevel::Alert,
+            _ => MarginLevel::Normal,
+        };
+        if level == previous {
+            return Ok(level);
+        }
+        let summary = format!(
+            "subaccount {} margin utilization {:.4} (surplus {}, under liquidation: {})",
+            self.subaccount_id,
+            status.utilization,
+            status.maintenance_margin,
+            status.is_under_liquidation
+        );
+        match level {
+            MarginLevel::Emergency => {
+                send_alert(&format!(
+                    "Margin emergency, running {:?}: {}",
+                    self.params.emergency_action, summary
+                ))
+                .await;
+                if let Err(e) = self.run_emergency_action(&subacc).await {
+                    send_alert(&format!("Margin emergency action failed with {:#}", e)).await;
+                }
+            }
+            MarginLevel::Alert if level > previous => {
+                send_alert(&format!("Margin utilization above alert level: {}", summary)).await;
+            }
+            _ => send_alert(&format!("Margin utilization back down: {}", summary)).await,
+        }
+        Ok(level)
+    }
+
+    async fn get_subaccount(&self) -> Result<PrivateGetSubaccountResultSchema> {
+        let headers = get_auth_headers().await?;
+        let subacc = http_rpc::<_, PrivateGetSubaccountResponseSchema>(
+            "private/get_subaccount",
+            PrivateGetSubaccountParamsSchema { subaccount_id: self.subaccount_id },
+            Some(headers),
+        )
+        .await?
+        .into_result()?;
+        Ok(subacc.result)
+    }
+
+    async fn run_emergency_action(&self, subacc: &PrivateGetSubaccountResultSchema) -> Result<()> {
+        let (fraction, max_slippage) = match &self.params.emergency_action {
+            EmergencyAction::Alert => return Ok(()),
+            EmergencyAction::CancelOrders => return self.cancel_open_orders(subacc).await,
+            EmergencyAction::ReduceOptions { fraction, max_slippage } => (*fraction, *max_slippage),
+        };
+        self.cancel_open_orders(subacc).await?;
+        let short_options = subacc
+            .positions
+            .iter()
+            .filter(|p| p.instrument_type == InstrumentType::Option)
+            .filter(|p| p.amount < BigDecimal::zero());
+        for position in short_options {
+            let amount = -&position.amount * BigDecimal::from_f64(fraction).unwrap_or_default();
+            if let Err(e) = self.buy_back(&position.instrument_name, amount, max_slippage).await {
+                warn!("Margin monitor failed to buy back {}: {:#}", position.instrument_name, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn cancel_open_orders(&self, subacc: &PrivateGetSubaccountResultSchema) -> Result<()> {
+        if subacc.open_orders.is_empty() {
+            return Ok(());
+        }
+        if self.dry_run {
+            info!("Margin monitor (dry run) would cancel all orders of {}", self.subaccount_id);
+            return Ok(());
+        }
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        let res = client.cancel_all(self.subaccount_id).await?.into_result()?;
+        info!("Margin monitor cancelled all orders: {:?}", res.result);
+        Ok(())
+    }
+
+    /// Sends an IOC buy of the option signed by the vault TSA, like the option auctions do
+    async fn buy_back(
+        &self,
+        instrument_name: &str,
+        amount: BigDecimal,
+        max_slippage: f64,
+    ) -> Result<()> {
+        let ticker = http_rpc::<_, TickerResponse>(
+            "public/get_ticker",
+            json!({ "instrument_name": instrument_name }),
+            None,
+        )
+        .await?
+        .into_result()?
+        .result;
+        let amount = amount
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down);
+        if amount < ticker.minimum_amount {
+            info!("Margin monitor buy back of {} {} is below minimum", amount, instrument_name);
+            return Ok(());
+        }
+        let offset = BigDecimal::from_f64(1.0 + max_slippage).unwrap_or_default();
+        let limit_price = (&ticker.mark_price * offset)
+            .with_scale_round(ticker.tick_size.fractional_digit_count(), RoundingMode::Up)
+            .min(ticker.max_price.clone());
+        let order_args = OrderArgs {
+            amount,
+            limit_price,
+            direction: Direction::Buy,
+            time_in_force: TimeInForce::Ioc,
+            order_type: OrderType::Limit,
+            mmp: false,
+            label: EMERGENCY_LABEL.to_string(),
+            max_slippage_bps: None,
+        };
+        if self.dry_run {
+            info!("Margin monitor (dry run) would buy back {}: {:?}", instrument_name, order_args);
+            return Ok(());
+        }
+        warn!("Margin monitor buying back {}: {:?}", instrument_name, order_args);
+        let vault_name = VaultContext::current().vault_name.clone();
+        let tsa = get_tsa_contract(&vault_name, "SESSION").await?;
+        let action_data = sign_order(&tsa, self.subaccount_id, &ticker, &order_args).await?;
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        let provider = tsa.client();
+        let signer = provider.inner().signer();
+        let config = client.get_config().await;
+        let order_params =
+            action_data.to_order_params(signer, &config, &ticker, order_args).await?;
+        let res = client.send_rpc::<_, Value>("private/order", order_params).await?;
+        let res = res.into_result()?;
+        info!("Margin monitor buy back of {}: {:?}", instrument_name, res);
+        Ok(())
+    }
+}
+
+/// The margin monitor task of the vault if `margin_monitor` is set in its params, with one
+/// monitor per subaccount of the vault
+pub fn margin_monitor(
+    params: Option<MarginMonitorParams>,
+    dry_run: bool,
+) -> Option<impl Future<Output = Result<()>>> {
+    let params = params?;
+    Some(async move {
+        let subaccount_ids = VaultContext::current().subaccount_ids()?;
+        let monitors = subaccount_ids
+            .into_iter()
+            .map(|id| MarginMonitor::new(params.clone(), id, dry_run))
+            .collect::<Vec<_>>();
+        try_join_all(monitors.iter().map(|m| m.run())).await?;
+        Ok(())
+    })
+}
@@ -1,5 +1,39 @@
+pub mod accounting;
+pub mod admin;
+pub mod alert;
 pub mod auction;
+pub mod backtest;
+pub mod cash_yield;
+pub mod context;
+pub mod dry_run;
+pub mod fees;
+pub mod health;
+pub mod hedger;
+pub mod instruments;
+pub mod labels;
+pub mod lrt_rate;
+pub mod margin;
+pub mod nav;
+pub mod oracle;
 pub mod params;
+pub mod reconcile;
+pub mod recorder;
+pub mod reload;
+pub mod report;
 pub mod rfq;
+pub mod rfq_maker;
+pub mod risk;
+pub mod schedule;
+pub mod session;
+pub mod settlement;
 pub mod spot_auction;
 pub mod stages;
+pub mod state_store;
+pub mod stats;
+pub mod subaccounts;
+pub mod supervisor;
+pub mod tickers;
+pub mod twap;
+pub mod validation;
+pub mod vault;
+pub mod withdrawals;
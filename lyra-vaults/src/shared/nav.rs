@@ -0,0 +1,136 @@
+use crate::helpers::{fetch_ticker, sync_subaccounts};
+use crate::market::{new_market_state, MarketData};
+use crate::shared::context::VaultContext;
+use crate::shared::lrt_rate::get_fair_prices;
+use crate::web3::contracts::get_tsa_total_supply;
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use orderbook_types::types::tickers::InstrumentType;
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// Net asset value of the vault subaccount in units of the cash asset (e.g. USDC).
+/// Perps are left out since their PnL is periodically settled into cash.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VaultNav {
+    pub timestamp_sec: i64,
+    pub collateral_value: BigDecimal,
+    pub option_value: BigDecimal, // negative for sold options
+    pub cash: BigDecimal,
+    pub nav: BigDecimal,
+    pub total_supply: BigDecimal,
+    pub share_price: Option<BigDecimal>, // None while no shares are minted
+    /// Positions without a mark price, excluded from the NAV
+    pub missing: Vec<String>,
+}
+
+impl VaultNav {
+    /// Values the positions at their (possibly stale) ticker marks, collaterals are valued at
+    /// their fair price from the LRT exchange rate if given, else at their `{collateral}-{cash}`
+    /// spot ticker
+    pub fn from_market(
+        market: &MarketData,
+        collateral_names: &[String],
+        fair_prices: &HashMap<String, BigDecimal>,
+        cash_name: &str,
+        total_supply: BigDecimal,
+    ) -> Self {
+        let mut nav = VaultNav {
+            timestamp_sec: chrono::Utc::now().timestamp(),
+            total_supply,
+            ..Default::default()
+        };
+        let tickers = market.get_tickers();
+        for position in market.iter_positions().filter(|p| !p.amount.is_zero()) {
+            let amount = &position.amount;
+            let name = &position.instrument_name;
+            if name == cash_name {
+                nav.cash += amount;
+                continue;
+            }
+            if collateral_names.contains(name) {
+                if let Some(fair_price) = fair_prices.get(name) {
+                    nav.collateral_value += amount * fair_price;
+                    continue;
+                }
+                match tickers.get(&format!("{}-{}", name, cash_name)) {
+                    Some(ticker) => nav.collateral_value += amount * &ticker.mark_price,
+                    None => nav.missing.push(name.clone()),
+                }
+                continue;
+            }
+            match tickers.get(name).map(|t| (&t.instrument_type, &t.mark_price)) {
+                Some((InstrumentType::Option, mark)) => nav.option_value += amount * mark,
+                Some(_) => {}
+                None if name.contains('-') => nav.missing.push(name.clone()),
+                None => {}
+            }
+        }
+        nav.nav = &nav.collateral_value + &nav.option_value + &nav.cash;
+        if nav.total_supply > BigDecimal::zero() {
+            nav.share_price = Some(&nav.nav / &nav.total_supply);
+        }
+        nav
+    }
+
+    /// Logs the change of NAV and share price since `previous`, e.g. over an epoch
+    pub fn log_change(&self, previous: &VaultNav) {
+        let nav_change = &self.nav - &previous.nav;
+        let share_price_change = match (&self.share_price, &previous.share_price) {
+            (Some(current), Some(previous)) if !previous.is_zero() => {
+                Some((current - previous) / previous)
+            }
+            _ => None,
+        };
+        info!(
+            "Epoch NAV change over {} sec: {} -> {} ({}), share price change: {:?}",
+            self.timestamp_sec - previous.timestamp_sec,
+            previous.nav,
+            self.nav,
+            nav_change,
+            share_price_change
+        );
+    }
+}
+
+/// Fetches the positions of the subaccounts, their tickers and the TSA share supply, logs the
+/// NAV and reports it to the vault's health status
+pub async fn log_vault_nav(subaccount_ids: &[i64]) -> Result<VaultNav> {
+    let ctx = VaultContext::current();
+    let collateral_names = ctx.collateral_names();
+    let market = new_market_state();
+    sync_subaccounts(market.clone(), subaccount_ids, vec![]).await?;
+    let mut instrument_names = market
+        .read()
+        .await
+        .iter_positions()
+        .filter(|p| !p.amount.is_zero() && p.instrument_name.contains('-'))
+        .map(|p| p.instrument_name.clone())
+        .collect::<Vec<_>>();
+    for collateral_name in collateral_names.iter().filter(|c| **c != ctx.cash_name) {
+        instrument_names.push(format!("{}-{}", collateral_name, ctx.cash_name));
+    }
+    for instrument_name in instrument_names {
+        fetch_ticker(market.clone(), &instrument_name).await?;
+    }
+    let fair_prices = get_fair_prices(&collateral_names).await;
+    let total_supply = get_tsa_total_supply(&ctx.vault_name).await?;
+    let reader = market.read().await;
+    let nav = VaultNav::from_market(
+        &reader,
+        &collateral_names,
+        &fair_prices,
+        &ctx.cash_name,
+        total_supply,
+    );
+    info!(
+        "Vault NAV: {} (collateral {}, options {}, cash {}), share price {:?}",
+        nav.nav, nav.collateral_value, nav.option_value, nav.cash, nav.share_price
+    );
+    if !nav.missing.is_empty() {
+        warn!("NAV excludes positions without a mark: {:?}", nav.missing);
+    }
+    ctx.health.update(|h| h.nav = Some(nav.clone()));
+    Ok(nav)
+}
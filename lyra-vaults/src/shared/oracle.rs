@@ -0,0 +1,137 @@
+use crate::web3::oracle::get_chainlink_price;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, FromPrimitive, One, Zero};
+use ethers::abi::Address;
+use lyra_client::json_rpc::http_rpc;
+use orderbook_types::types::tickers::TickerResponse;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+/// External prices the Lyra mark of a spot auction is checked against before quoting
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceCheckParams {
+    pub sources: Vec<PriceSource>, // One or two venues, each compared on its own
+    pub max_deviation: f64,        // Relative deviation of the Lyra price from a source
+    // Lyra instrument the sources are quoted in, e.g. ETH-PERP for LRT/ETH feeds: the auction's
+    // mark is divided by its index price. None compares in the auction's quote (e.g. USDC).
+    #[serde(default)]
+    pub quote_instrument: Option<String>,
+    // Only alert on a deviation instead of aborting the auction
+    #[serde(default)]
+    pub alert_only: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PriceSource {
+    /// Chainlink aggregator on the `chain_id` provider or read via the RPC URL in `provider_env`
+    Chainlink {
+        address: Address,
+        chain_id: Option<u64>,
+        #[serde(default = "default_provider_env")]
+        provider_env: String,
+        #[serde(default = "default_max_age_sec")]
+        max_age_sec: i64,
+    },
+    /// JSON REST endpoint (e.g. a CEX ticker), `path` is the dot separated path to the price
+    Rest { url: String, path: String },
+}
+
+fn default_provider_env() -> String {
+    "MAINNET_PROVIDER".to_string()
+}
+
+fn default_max_age_sec() -> i64 {
+    86400
+}
+
+impl PriceSource {
+    pub async fn get_price(&self) -> Result<BigDecimal> {
+        match self {
+            PriceSource::Chainlink { address, chain_id, provider_env, max_age_sec } => {
+                get_chainlink_price(*chain_id, provider_env, *address, *max_age_sec).await
+            }
+            PriceSource::Rest { url, path } => get_rest_price(url, path).await,
+        }
+    }
+}
+
+impl PriceCheckParams {
+    /// Compares the mark of the instrument with every source and returns the deviations above
+    /// `max_deviation`, empty if the prices agree. Sources that fail are logged and skipped, but
+    /// with none of them available the check fails as on a deviation.
+    pub async fn check(
+        &self,
+        instrument_name: &str,
+        mark_price: &BigDecimal,
+    ) -> Result<Vec<String>> {
+        let quote_price = match &self.quote_instrument {
+            Some(quote_instrument) => get_index_price(quote_instrument).await?,
+            None => BigDecimal::one(),
+        };
+        if quote_price.is_zero() {
+            return Err(Error::msg("Zero index price of the quote instrument"));
+        }
+        let lyra_price = mark_price / &quote_price;
+        let max_deviation = BigDecimal::from_f64(self.max_deviation).unwrap_or_default();
+        let mut deviations = vec![];
+        let mut num_prices = 0;
+        for source in self.sources.iter() {
+            let price = match source.get_price().await {
+                Ok(price) if !price.is_zero() => price,
+                Ok(_) => {
+                    warn!("Price source {:?} returned zero", source);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Price source {:?} failed with {:#}", source, e);
+                    continue;
+                }
+            };
+            num_prices += 1;
+            let deviation = ((&lyra_price - &price) / &price).abs();
+            info!("{} price {} vs {} from {:?}", instrument_name, lyra_price, price, source);
+            if deviation > max_deviation {
+                deviations.push(format!(
+                    "{} price {} deviates {} from {} of {:?}",
+                    instrument_name,
+                    lyra_price,
+                    deviation.round(4),
+                    price,
+                    source
+                ));
+            }
+        }
+        if num_prices == 0 {
+            deviations.push(format!("No price source available to check {}", instrument_name));
+        }
+        Ok(deviations)
+    }
+}
+
+pub async fn get_index_price(instrument_name: &str) -> Result<BigDecimal> {
+    let ticker = http_rpc::<_, TickerResponse>(
+        "public/get_ticker",
+        json!({ "instrument_name": instrument_name }),
+        None,
+    )
+    .await?
+    .into_result()?
+    .result;
+    Ok(ticker.index_price)
+}
+
+async fn get_rest_price(url: &str, path: &str) -> Result<BigDecimal> {
+    let response = reqwest::get(url).await?.error_for_status()?.json::<Value>().await?;
+    let value = path.split('.').fold(&response, |value, key| match key.parse::<usize>() {
+        Ok(index) if value.is_array() => &value[index],
+        _ => &value[key],
+    });
+    match value {
+        Value::String(price) => Ok(BigDecimal::from_str(price)?),
+        Value::Number(price) => Ok(BigDecimal::from_str(&price.to_string())?),
+        _ => Err(Error::msg(format!("No price at {} in the response of {}", path, url))),
+    }
+}
@@ -1,9 +1,23 @@
+use crate::shared::context::VaultContext;
+use crate::shared::oracle::PriceCheckParams;
+use crate::shared::reload::ParamsSection;
 use crate::shared::rfq::RFQAuction;
+use crate::shared::validation::{ParamsCheck, ValidateParams};
 use crate::web3::yields::get_growth_between;
 use bigdecimal::RoundingMode::Down;
-use bigdecimal::{BigDecimal, Zero};
-use log::info;
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use lyra_client::actions::HttpFallbackParams;
 use serde::Deserialize;
+use tracing::info;
+
+/// Venue used to execute an auction, either limit orders on the orderbook or RFQs to makers
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionType {
+    #[default]
+    Orderbook,
+    Rfq,
+}
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct SpotAuctionParams {
@@ -17,16 +31,65 @@ pub struct SpotAuctionParams {
     pub cash_name: String,
     pub max_cash: BigDecimal,
     // todo add max_cash_pct_tvl
+
+    // Splits the auction into time-sliced child orders, None trades the whole amount at once
+    #[serde(default)]
+    pub twap: Option<TwapParams>,
+    // Caps the orders at a fraction of the visible orderbook depth, None does not cap them
+    #[serde(default)]
+    pub depth_limit: Option<DepthLimitParams>,
+    // Caps orders crossing the book at this many bps from mark, None does not protect them
+    #[serde(default)]
+    pub max_slippage_bps: Option<u32>,
+    // Orders are cancelled if the connection drops mid auction (default), false leaves them
+    #[serde(default = "default_cancel_on_disconnect")]
+    pub cancel_on_disconnect: bool,
+    // Aborts (or alerts) before quoting if the mark deviates from external prices
+    #[serde(default)]
+    pub price_check: Option<PriceCheckParams>,
+    // Candles of the public trades in the execution report, e.g. 60 for 1m candles
+    #[serde(default)]
+    pub candle_interval_sec: Option<i64>,
+    // Keeps prices within this fraction of the LRT's fair price from its on-chain exchange
+    // rate (see `LrtRateSource`), None prices off the Lyra spot mark only
+    #[serde(default)]
+    pub lrt_rate_band: Option<f64>,
+    // What the auction does once auction_sec passed, see `TimeoutPolicy`
+    #[serde(default)]
+    pub on_timeout: Option<TimeoutPolicy>,
+    // Widens the spread while a lot is left to trade against the book, None prices by time only
+    #[serde(default)]
+    pub inventory_pricing: Option<InventoryPricingParams>,
+}
+
+/// Time slicing of a spot auction, see `TwapExecutor`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwapParams {
+    pub num_slices: i64, // Child orders the amount is split into, the last one trades the rest
+    pub slice_sec: i64,  // Duration of a slice, unfilled amounts roll over into the next slice
+    #[serde(default)]
+    pub size_jitter: f64, // Child sizes are randomized by up to +/- this fraction
+    pub max_slice_move: f64, // Child prices stay within this fraction of the mark at slice start
 }
 
 impl SpotAuctionParams {
     /// Returns an auction spot spread, starting from its init value and increasing per minute.
     /// Spot selling auctions would subtract a spread, buying auctions would add a spread.
+    /// Values changed by a params reload take precedence.
     pub fn get_spot_spread(&self, start_timestamp_sec: i64) -> f64 {
+        let live = VaultContext::current().live_params.clone();
+        let field =
+            |name: &str, value: f64| live.f64(ParamsSection::SpotAuction, name).unwrap_or(value);
         let sec_since_start = chrono::Utc::now().timestamp() - start_timestamp_sec;
         let min_since_start = sec_since_start as f64 / 60.0;
-        let spread = self.init_spot_spread + min_since_start * self.spot_spread_per_min;
-        spread.min(self.max_spot_spread)
+        let spread = field("init_spot_spread", self.init_spot_spread)
+            + min_since_start * field("spot_spread_per_min", self.spot_spread_per_min);
+        spread.min(field("max_spot_spread", self.max_spot_spread))
+    }
+
+    /// Orderbook levels the pricing needs subscribed, None if it does not read the book
+    pub fn pricing_orderbook_levels(&self) -> Option<u32> {
+        self.inventory_pricing.as_ref().map(|pricing| pricing.levels)
     }
 
     pub fn is_cash_within_threshold(&self, cash_bal: &BigDecimal) -> bool {
@@ -35,6 +98,92 @@ impl SpotAuctionParams {
     }
 }
 
+/// Market maker protection armed on the auction subaccount before quoting, so a burst of fills
+/// freezes the `mmp` orders of the currency instead of filling the whole auction at a stale price
+#[derive(Debug, Clone, Deserialize)]
+pub struct MmpParams {
+    pub interval_ms: i64,    // Window in which fills count towards the limits
+    pub frozen_time_ms: i64, // How long orders stay frozen after a trigger, 0 needs a manual reset
+    #[serde(default)]
+    pub amount_limit: BigDecimal, // Max amount filled within the interval, 0 for no limit
+    #[serde(default)]
+    pub delta_limit: BigDecimal, // Max absolute delta filled within the interval, 0 for no limit
+    // Resets a previous trigger once when the process starts quoting, else left to the operator
+    #[serde(default)]
+    pub reset_on_start: bool,
+}
+
+/// What an auction does once `auction_sec` passed without completing, e.g.
+/// `{"action": "extend", "extend_sec": 600, "extra_spread": 0.005}`. Without one it is up to
+/// the strategy: option auctions stop, spot auctions covering negative cash go on at max spread.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum TimeoutPolicy {
+    /// Runs `extend_sec` longer, moving the price up to `extra_spread` (a fraction of the
+    /// price) beyond the strategy's max spread over the extension
+    Extend { extend_sec: i64, extra_spread: f64 },
+    /// Runs `cross_sec` longer at `max_slippage_bps` from mark, taking the book up to there
+    Cross { cross_sec: i64, max_slippage_bps: u32 },
+    /// Cancels the open order and alerts
+    Abort,
+}
+
+impl TimeoutPolicy {
+    /// Time the auction runs past auction_sec
+    pub fn extension_sec(&self) -> i64 {
+        match self {
+            TimeoutPolicy::Extend { extend_sec, .. } => *extend_sec,
+            TimeoutPolicy::Cross { cross_sec, .. } => *cross_sec,
+            TimeoutPolicy::Abort => 0,
+        }
+    }
+}
+
+/// Extra spot spread for the amount left to trade, on top of the time-based spread: the amount
+/// left over the visible depth it trades against (asks for a buy) times `spread_per_depth`,
+/// capped at `max_inventory_spread` and tightened linearly to zero over the auction
+#[derive(Debug, Clone, Deserialize)]
+pub struct InventoryPricingParams {
+    pub spread_per_depth: f64, // Extra spread per multiple of the visible depth left to trade
+    pub max_inventory_spread: f64,
+    #[serde(default = "default_depth_levels")]
+    pub levels: u32, // Best levels subscribed and counted, 1, 10, 20 or 100
+}
+
+impl InventoryPricingParams {
+    /// Zero without a visible book, `progress` is the elapsed fraction of the auction
+    pub fn get_spread(
+        &self,
+        remaining: &BigDecimal,
+        depth: Option<BigDecimal>,
+        progress: f64,
+    ) -> f64 {
+        let Some(depth) = depth.filter(|depth| !depth.is_zero()) else {
+            return 0.0;
+        };
+        let depth_ratio = (remaining / depth).to_f64().unwrap_or_default();
+        let spread = (depth_ratio * self.spread_per_depth).min(self.max_inventory_spread);
+        spread * (1.0 - progress.clamp(0.0, 1.0))
+    }
+}
+
+/// Caps auction orders at a fraction of the liquidity they trade against (bids for a sell),
+/// so a single order does not sweep a thin book
+#[derive(Debug, Clone, Deserialize)]
+pub struct DepthLimitParams {
+    pub max_fraction: BigDecimal, // Max order amount as a fraction of the visible depth
+    #[serde(default = "default_depth_levels")]
+    pub levels: u32, // Best levels subscribed and counted, 1, 10, 20 or 100
+}
+
+fn default_depth_levels() -> u32 {
+    10
+}
+
+pub fn default_cancel_on_disconnect() -> bool {
+    true
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct OptionRFQParams {
     pub max_cost: BigDecimal,
@@ -123,3 +272,90 @@ impl OptionRFQParams {
         Ok(lot_size)
     }
 }
+
+impl ValidateParams for SpotAuctionParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        // a negative initial spread starts the auction through the mark
+        check.at_least("init_spot_spread", &self.init_spot_spread, &-self.max_spot_spread);
+        check.fraction("max_spot_spread", self.max_spot_spread);
+        check.not_above(
+            "init_spot_spread",
+            &self.init_spot_spread,
+            "max_spot_spread",
+            &self.max_spot_spread,
+        );
+        check.at_least("spot_spread_per_min", &self.spot_spread_per_min, &0.0);
+        check.at_least("auction_sec", &self.auction_sec, &1);
+        check.at_least("price_change_tolerance", &self.price_change_tolerance, &BigDecimal::zero());
+        check.at_least("max_cash", &self.max_cash, &BigDecimal::zero());
+        if let Some(band) = self.lrt_rate_band {
+            check.fraction("lrt_rate_band", band);
+        }
+        if let Some(twap) = &self.twap {
+            check.nested("twap", twap);
+        }
+        if let Some(depth_limit) = &self.depth_limit {
+            check.nested("depth_limit", depth_limit);
+        }
+        if let Some(inventory_pricing) = &self.inventory_pricing {
+            check.nested("inventory_pricing", inventory_pricing);
+        }
+    }
+}
+
+impl ValidateParams for TwapParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        check.at_least("num_slices", &self.num_slices, &1);
+        check.at_least("slice_sec", &self.slice_sec, &1);
+        check.fraction("size_jitter", self.size_jitter);
+        check.fraction("max_slice_move", self.max_slice_move);
+    }
+}
+
+impl ValidateParams for InventoryPricingParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        check.at_least("spread_per_depth", &self.spread_per_depth, &0.0);
+        check.fraction("max_inventory_spread", self.max_inventory_spread);
+    }
+}
+
+impl ValidateParams for DepthLimitParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        check.above("max_fraction", &self.max_fraction, &BigDecimal::zero());
+        check.require(
+            [1, 10, 20, 100].contains(&self.levels),
+            "levels",
+            &format!("= {} is not one of 1, 10, 20 or 100", self.levels),
+        );
+    }
+}
+
+impl ValidateParams for HttpFallbackParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        check.at_least("max_ws_failures", &self.max_ws_failures, &1);
+        check.at_least("ws_retry_sec", &self.ws_retry_sec, &1);
+    }
+}
+
+impl ValidateParams for OptionRFQParams {
+    fn validate(&self, check: &mut ParamsCheck) {
+        // a negative initial spread starts the auction through the mark
+        check.at_least("init_premium_spread", &self.init_premium_spread, &-self.max_premium_spread);
+        check.fraction("max_premium_spread", self.max_premium_spread);
+        check.not_above(
+            "init_premium_spread",
+            &self.init_premium_spread,
+            "max_premium_spread",
+            &self.max_premium_spread,
+        );
+        check.at_least("premium_spread_per_min", &self.premium_spread_per_min, &0.0);
+        check.not_above("min_cost", &self.min_cost, "max_cost", &self.max_cost);
+        check.require(
+            ["pp", "covered"].contains(&self.sizing_type.as_str()),
+            "sizing_type",
+            &format!("= {} is not one of pp or covered", self.sizing_type),
+        );
+        check.above("lot_size", &self.lot_size, &BigDecimal::zero());
+        check.at_least("auction_sec", &self.auction_sec, &1);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::shared::alert::send_alert;
+use crate::shared::context::VaultContext;
+use crate::web3::providers::get_lyra_provider;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, Zero};
+use ethers::abi::Address;
+use ethers::contract::abigen;
+use ethers::types::U256;
+use lyra_client::actions::CollateralAsset;
+use lyra_client::auth::get_auth_headers;
+use lyra_client::config::LyraConfig;
+use lyra_client::json_rpc::http_rpc;
+use lyra_client::utils::i256_to_decimal;
+use orderbook_types::generated::private_get_subaccount::{
+    PrivateGetSubaccountParamsSchema, PrivateGetSubaccountResponseSchema,
+};
+use orderbook_types::types::tickers::TickerResponse;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+abigen!(
+    SubAccounts,
+    r#"[
+        struct AssetBalance { address asset; uint96 subId; int256 balance; }
+        function getAccountBalances(uint256 accountId) external view returns (AssetBalance[])
+    ]"#,
+);
+
+/// Asset and sub id of a balance in the on-chain subaccount storage
+type AssetKey = (Address, u128);
+
+/// Tolerances of the reconciliation of the API balances with the on-chain subaccount
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReconcileParams {
+    #[serde(default = "default_max_collateral_drift")]
+    pub max_collateral_drift: BigDecimal, // Max absolute difference of a collateral balance
+    #[serde(default = "default_max_position_drift")]
+    pub max_position_drift: BigDecimal, // Max absolute difference of an option or perp position
+    // Consecutive checks a drift has to show up in before it blocks, as the API trails the chain
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u32,
+    #[serde(default = "default_check_interval_sec")]
+    pub check_interval_sec: u64,
+}
+
+fn default_max_collateral_drift() -> BigDecimal {
+    BigDecimal::from_str("0.01").unwrap()
+}
+
+fn default_max_position_drift() -> BigDecimal {
+    BigDecimal::from_str("0.001").unwrap()
+}
+
+fn default_confirmations() -> u32 {
+    3
+}
+
+fn default_check_interval_sec() -> u64 {
+    60
+}
+
+/// Current drift between the API and on-chain balances, shared between the reconciler and the
+/// auctions. None while they agree.
+#[derive(Debug)]
+pub struct ReconcileGate(watch::Sender<Option<String>>);
+
+impl ReconcileGate {
+    fn new() -> Self {
+        Self(watch::channel(None).0)
+    }
+
+    pub fn drift(&self) -> Option<String> {
+        self.0.borrow().clone()
+    }
+
+    /// Resolves once the balances agree again, right away if they do
+    pub async fn reconciled(&self) {
+        let mut receiver = self.0.subscribe();
+        let _ = receiver.wait_for(|drift| drift.is_none()).await;
+    }
+}
+
+/// Compares the subaccount balances of `private/get_subaccount` with the on-chain subaccount
+/// storage (SUBACCOUNTS_ADDRESS) every `check_interval_sec`. A drift in a collateral or position
+/// that persists for `confirmations` checks is alerted and blocks new auctions until the
+/// balances agree again, running auctions are left to finish.
+pub struct Reconciler {
+    params: ReconcileParams,
+    subaccount_id: i64,
+    gate: Arc<ReconcileGate>,
+    assets: Mutex<HashMap<String, AssetKey>>, // on-chain asset of each API name
+}
+
+impl Reconciler {
+    pub fn new(params: ReconcileParams, gate: Arc<ReconcileGate>) -> Result<Self> {
+        let subaccount_id = LyraConfig::current()?.subaccount_id()?;
+        Ok(Self { params, subaccount_id, gate, assets: Mutex::new(HashMap::new()) })
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let interval = tokio::time::Duration::from_secs(self.params.check_interval_sec);
+        let mut checks_drifted = 0;
+        loop {
+            match self.drifts().await {
+                Ok(drifts) => checks_drifted = self.update(drifts, checks_drifted).await,
+                Err(e) => warn!("Reconciliation failed with {:#}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Blocks or unblocks the auctions, returns the number of consecutive checks with a drift
+    async fn update(&self, drifts: Vec<String>, checks_drifted: u32) -> u32 {
+        let ctx = VaultContext::current();
+        if drifts.is_empty() {
+            if let Some(previous) = self.gate.drift() {
+                send_alert(&format!(
+                    "Balances reconciled, unblocking auctions after: {}",
+                    previous
+                ))
+                .await;
+                self.gate.0.send_replace(None);
+                ctx.health.update(|h| h.reconcile_drift = None);
+            }
+            return 0;
+        }
+        let drift = drifts.join(", ");
+        let checks_drifted = checks_drifted + 1;
+        warn!("Reconciliation drift ({}/{}): {}", checks_drifted, self.params.confirmations, drift);
+        if checks_drifted >= self.params.confirmations && self.gate.drift().is_none() {
+            send_alert(&format!("API and on-chain balances drifted, blocking auctions: {}", drift))
+                .await;
+            self.gate.0.send_replace(Some(drift.clone()));
+            ctx.health.update(|h| h.reconcile_drift = Some(drift));
+        }
+        checks_drifted
+    }
+
+    /// Descriptions of the balances that differ beyond the tolerances, empty if all agree
+    async fn drifts(&self) -> Result<Vec<String>> {
+        let headers = get_auth_headers().await?;
+        let subacc = http_rpc::<_, PrivateGetSubaccountResponseSchema>(
+            "private/get_subaccount",
+            PrivateGetSubaccountParamsSchema { subaccount_id: self.subaccount_id },
+            Some(headers),
+        )
+        .await?
+        .into_result()?
+        .result;
+        let onchain = self.onchain_balances().await?;
+
+        let mut api_balances = vec![];
+        for collateral in subacc.collaterals {
+            let key = self.collateral_key(&collateral.asset_name).await?;
+            let tolerance = &self.params.max_collateral_drift;
+            api_balances.push((collateral.asset_name, collateral.amount, key, tolerance));
+        }
+        for position in subacc.positions {
+            let key = self.position_key(&position.instrument_name).await?;
+            let tolerance = &self.params.max_position_drift;
+            api_balances.push((position.instrument_name, position.amount, key, tolerance));
+        }
+
+        let mut drifts = vec![];
+        let mut seen = HashSet::new();
+        for (name, amount, key, tolerance) in api_balances {
+            seen.insert(key);
+            let onchain_amount = onchain.get(&key).cloned().unwrap_or_default();
+            if (&amount - &onchain_amount).abs() > *tolerance {
+                drifts.push(format!(
+                    "{} is {} in the API, {} on-chain",
+                    name, amount, onchain_amount
+                ));
+            }
+        }
+        for (key, amount) in onchain.iter() {
+            if !seen.contains(key) && !amount.is_zero() {
+                drifts.push(format!("{:?} sub id {} is {} on-chain only", key.0, key.1, amount));
+            }
+        }
+        info!(
+            "Reconciled {} balances of {}, {} drifted",
+            seen.len(),
+            self.subaccount_id,
+            drifts.len()
+        );
+        Ok(drifts)
+    }
+
+    async fn onchain_balances(&self) -> Result<HashMap<AssetKey, BigDecimal>> {
+        let address = std::env::var("SUBACCOUNTS_ADDRESS")
+            .map_err(|_| Error::msg("SUBACCOUNTS_ADDRESS is not set"))?
+            .parse::<Address>()?;
+        let subaccounts = SubAccounts::new(address, get_lyra_provider().await?);
+        let balances =
+            subaccounts.get_account_balances(U256::from(self.subaccount_id)).call().await?;
+        // all balances are stored with 18 decimals
+        balances
+            .into_iter()
+            .map(|(asset, sub_id, balance)| Ok(((asset, sub_id), i256_to_decimal(balance)?)))
+            .collect()
+    }
+
+    /// Collaterals are stored under sub id 0 of their asset
+    async fn collateral_key(&self, asset_name: &str) -> Result<AssetKey> {
+        if let Some(key) = self.assets.lock().unwrap().get(asset_name) {
+            return Ok(*key);
+        }
+        let key = (CollateralAsset::get(asset_name).await?.address, 0);
+        self.assets.lock().unwrap().insert(asset_name.to_string(), key);
+        Ok(key)
+    }
+
+    /// Options and perps are stored under the base asset and sub id of their ticker
+    async fn position_key(&self, instrument_name: &str) -> Result<AssetKey> {
+        if let Some(key) = self.assets.lock().unwrap().get(instrument_name) {
+            return Ok(*key);
+        }
+        let ticker = http_rpc::<_, TickerResponse>(
+            "public/get_ticker",
+            json!({ "instrument_name": instrument_name }),
+            None,
+        )
+        .await?
+        .into_result()?
+        .result;
+        let key = (ticker.base_asset_address.parse()?, ticker.base_asset_sub_id.parse()?);
+        self.assets.lock().unwrap().insert(instrument_name.to_string(), key);
+        Ok(key)
+    }
+}
+
+/// The gate and reconciler task of the vault if `reconcile` is set in its params
+pub fn reconciler(
+    params: Option<ReconcileParams>,
+) -> Option<(Arc<ReconcileGate>, impl Future<Output = Result<()>>)> {
+    let params = params?;
+    let gate = Arc::new(ReconcileGate::new());
+    let reconciler_gate = gate.clone();
+    let reconciler = async move { Reconciler::new(params, reconciler_gate)?.run().await };
+    Some((gate, reconciler))
+}
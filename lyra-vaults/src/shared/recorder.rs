@@ -0,0 +1,267 @@
+use crate::shared::backtest::RecordedMessage;
+use anyhow::{Error, Result};
+use chrono::Utc;
+use lyra_client::channels::Channel;
+use lyra_client::json_rpc::{Notification, WsClient, WsClientExt};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::Write;
+use tokio::select;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+/// Max messages buffered between the subscription and the file writer
+const RECORDER_CHANNEL_SIZE: usize = 10_000;
+/// Rows per parquet row group, also how often buffered messages are written out
+const PARQUET_BATCH_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordFormat {
+    #[default]
+    Jsonl,
+    Parquet, // requires the `parquet` feature
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecorderParams {
+    pub env: String,              // Environment name (e.g. staging, prod)
+    pub instruments: Vec<String>, // Instruments to record tickers and orderbooks of
+    pub dir: String,              // Directory for the recording files
+    #[serde(default)]
+    pub format: RecordFormat, // "jsonl" (default, read by the backtester) or "parquet"
+    #[serde(default = "default_ticker_interval_ms")]
+    pub ticker_interval_ms: u32, // 100 or 1000
+    #[serde(default = "default_orderbook_depth")]
+    pub orderbook_depth: u32, // 1, 10, 20 or 100 levels, 0 to only record tickers
+    #[serde(default = "default_rotate_min")]
+    pub rotate_min: i64, // A new file is started every rotate_min minutes
+}
+
+fn default_ticker_interval_ms() -> u32 {
+    100
+}
+
+fn default_orderbook_depth() -> u32 {
+    10
+}
+
+fn default_rotate_min() -> i64 {
+    60
+}
+
+impl RecorderParams {
+    pub fn channels(&self) -> Vec<String> {
+        let mut channels = vec![];
+        for instrument_name in self.instruments.iter() {
+            channels.push(Channel::ticker(instrument_name, self.ticker_interval_ms).to_string());
+            if self.orderbook_depth > 0 {
+                channels
+                    .push(Channel::orderbook(instrument_name, self.orderbook_depth).to_string());
+            }
+        }
+        channels
+    }
+}
+
+/// Writes messages to the current file, closing it and starting a new one on rotation
+enum RecordFile {
+    Jsonl(std::io::BufWriter<std::fs::File>),
+    Parquet { file: std::fs::File, rows: Vec<RecordedMessage>, writer: Option<ParquetWriter> },
+}
+
+#[cfg(feature = "parquet")]
+type ParquetWriter = parquet::arrow::ArrowWriter<std::fs::File>;
+#[cfg(not(feature = "parquet"))]
+type ParquetWriter = std::convert::Infallible;
+
+impl RecordFile {
+    fn create(path: &str, format: RecordFormat) -> Result<Self> {
+        let file = std::fs::File::create(path)?;
+        match format {
+            RecordFormat::Jsonl => Ok(RecordFile::Jsonl(std::io::BufWriter::new(file))),
+            RecordFormat::Parquet => Ok(RecordFile::Parquet { file, rows: vec![], writer: None }),
+        }
+    }
+
+    fn write(&mut self, message: RecordedMessage) -> Result<()> {
+        match self {
+            RecordFile::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, &message)?;
+                writer.write_all(b"\n")?;
+            }
+            RecordFile::Parquet { rows, .. } => {
+                rows.push(message);
+                if rows.len() >= PARQUET_BATCH_SIZE {
+                    self.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            RecordFile::Jsonl(writer) => writer.flush()?,
+            RecordFile::Parquet { file, rows, writer } => {
+                if !rows.is_empty() {
+                    write_parquet_batch(file, writer, rows)?;
+                    rows.clear();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<()> {
+        self.flush()?;
+        if let RecordFile::Parquet { writer: Some(writer), .. } = self {
+            close_parquet(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Columns are the receive timestamp (Int64 ms), the channel and the message data as JSON
+#[cfg(feature = "parquet")]
+fn write_parquet_batch(
+    file: &std::fs::File,
+    writer: &mut Option<ParquetWriter>,
+    rows: &[RecordedMessage],
+) -> Result<()> {
+    use arrow::array::{ArrayRef, Int64Array, StringArray};
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    let timestamps: ArrayRef =
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.timestamp)));
+    let channels: ArrayRef =
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.channel.as_str())));
+    let data: ArrayRef =
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.data.to_string())));
+    let batch = RecordBatch::try_from_iter(vec![
+        ("timestamp", timestamps),
+        ("channel", channels),
+        ("data", data),
+    ])?;
+    if writer.is_none() {
+        *writer = Some(ParquetWriter::try_new(file.try_clone()?, batch.schema(), None)?);
+    }
+    writer.as_mut().unwrap().write(&batch)?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+fn close_parquet(writer: ParquetWriter) -> Result<()> {
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet_batch(
+    _file: &std::fs::File,
+    _writer: &mut Option<ParquetWriter>,
+    _rows: &[RecordedMessage],
+) -> Result<()> {
+    Err(Error::msg("Parquet recordings require building with --features parquet"))
+}
+
+#[cfg(not(feature = "parquet"))]
+fn close_parquet(_writer: ParquetWriter) -> Result<()> {
+    Ok(())
+}
+
+/// Subscribes to the ticker and orderbook channels of the configured instruments and writes
+/// every message with its receive timestamp to disk, starting a new file every `rotate_min`.
+/// The JSON lines files can be replayed with the backtester.
+pub async fn run_recorder(params: &RecorderParams) -> Result<()> {
+    if params.format == RecordFormat::Parquet && cfg!(not(feature = "parquet")) {
+        return Err(Error::msg("Parquet recordings require building with --features parquet"));
+    }
+    std::fs::create_dir_all(&params.dir)?;
+    let (sender, receiver) = mpsc::channel::<RecordedMessage>(RECORDER_CHANNEL_SIZE);
+    let channels = params.channels();
+    info!("Recording channels: {:?}", channels);
+
+    let client = WsClient::new_client().await?;
+    // owns the only sender, so the channel closes once the subscription exits
+    let subscription = async move {
+        let callback = move |msg: Notification<Value>| {
+            let sender = sender.clone();
+            async move {
+                let message = RecordedMessage {
+                    timestamp: Utc::now().timestamp_millis(),
+                    channel: msg.params.channel,
+                    data: msg.params.data,
+                };
+                sender.send(message).await.map_err(|_| Error::msg("Recorder writer exited"))
+            }
+        };
+        client.subscribe(channels, callback).await
+    };
+    let writer = write_recording(params, receiver);
+    tokio::pin!(writer);
+    select! {
+        res = subscription => {
+            error!("Recorder subscription exited with {:?}", res);
+            // the writer drains the closed channel and closes the current file
+            writer.await?;
+            Ok(res?)
+        },
+        res = &mut writer => res,
+    }
+}
+
+/// Writes until the channel closes. The current file is closed on every exit, since a parquet
+/// file without its footer can not be read.
+async fn write_recording(
+    params: &RecorderParams,
+    mut receiver: mpsc::Receiver<RecordedMessage>,
+) -> Result<()> {
+    let mut current: Option<(RecordFile, i64)> = None;
+    let res = record_messages(params, &mut receiver, &mut current).await;
+    if let Some((file, _)) = current.take() {
+        file.close()?;
+    }
+    res
+}
+
+async fn record_messages(
+    params: &RecorderParams,
+    receiver: &mut mpsc::Receiver<RecordedMessage>,
+    current: &mut Option<(RecordFile, i64)>,
+) -> Result<()> {
+    let rotate_ms = params.rotate_min * 60 * 1000;
+    let extension = match params.format {
+        RecordFormat::Jsonl => "jsonl",
+        RecordFormat::Parquet => "parquet",
+    };
+    let mut flush_interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+    loop {
+        let message = select! {
+            message = receiver.recv() => match message {
+                Some(message) => message,
+                None => return Ok(()),
+            },
+            _ = flush_interval.tick() => {
+                // parquet buffers until a full batch to keep row groups large
+                if let Some((RecordFile::Jsonl(writer), _)) = current.as_mut() {
+                    writer.flush()?;
+                }
+                continue;
+            },
+        };
+        let is_expired = current.as_ref().map_or(true, |(_, end_ms)| message.timestamp >= *end_ms);
+        if is_expired {
+            if let Some((file, _)) = current.take() {
+                file.close()?;
+            }
+            let start = Utc::now();
+            let path = format!("{}/{}.{}", params.dir, start.format("%Y%m%d_%H%M%S"), extension);
+            info!("Recording to {}", path);
+            let file = RecordFile::create(&path, params.format)?;
+            *current = Some((file, start.timestamp_millis() + rotate_ms));
+        }
+        current.as_mut().unwrap().0.write(message)?;
+    }
+}
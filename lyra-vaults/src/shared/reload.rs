@@ -0,0 +1,219 @@
+use crate::runner::VaultParams;
+use crate::shared::alert::send_alert;
+use crate::shared::context::VaultContext;
+use anyhow::{Error, Result};
+use bigdecimal::BigDecimal;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{info, warn};
+
+/// Fields a reload may change while the executor runs, by section. Changes of any other field
+/// are structural (instruments, schedules, tasks, ...) and only take effect after a restart.
+const TUNABLE_FIELDS: &[(ParamsSection, &str)] = &[
+    (ParamsSection::OptionAuction, "init_iv_spread"),
+    (ParamsSection::OptionAuction, "iv_spread_per_min"),
+    (ParamsSection::OptionAuction, "max_iv_spread"),
+    (ParamsSection::OptionAuction, "price_change_tolerance"),
+    (ParamsSection::SpotAuction, "init_spot_spread"),
+    (ParamsSection::SpotAuction, "spot_spread_per_min"),
+    (ParamsSection::SpotAuction, "max_spot_spread"),
+    (ParamsSection::SpotAuction, "price_change_tolerance"),
+];
+
+/// Section of the vault params an auction takes its tunable fields from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParamsSection {
+    OptionAuction,
+    SpotAuction,
+}
+
+impl ParamsSection {
+    fn key(&self) -> &'static str {
+        match self {
+            ParamsSection::OptionAuction => "option_auction_params",
+            ParamsSection::SpotAuction => "spot_auction_params",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParamsReloadParams {
+    #[serde(default = "default_check_interval_sec")]
+    pub check_interval_sec: u64, // How often the params file is checked for changes
+}
+
+fn default_check_interval_sec() -> u64 {
+    5
+}
+
+/// Tunable fields changed by the reloads so far, which take precedence over the params the
+/// executor was started with. Kept in the vault context, so they survive executor restarts.
+#[derive(Debug, Default)]
+pub struct LiveParams {
+    overrides: Mutex<HashMap<(ParamsSection, String), Value>>,
+    baseline: Mutex<Option<Value>>, // the params the last reload compared against
+}
+
+impl LiveParams {
+    pub fn f64(&self, section: ParamsSection, field: &str) -> Option<f64> {
+        self.get(section, field)?.as_f64()
+    }
+
+    pub fn decimal(&self, section: ParamsSection, field: &str) -> Option<BigDecimal> {
+        match self.get(section, field)? {
+            Value::String(s) => BigDecimal::from_str(&s).ok(),
+            Value::Number(n) => BigDecimal::from_str(&n.to_string()).ok(),
+            _ => None,
+        }
+    }
+
+    fn get(&self, section: ParamsSection, field: &str) -> Option<Value> {
+        self.overrides.lock().unwrap().get(&(section, field.to_string())).cloned()
+    }
+}
+
+/// Watches the params file the vault was started from for changes, and reloads it
+/// on SIGHUP as well. The vault's entry is compared with the previous one: changes of tunable
+/// fields are applied to the running auctions, other changes are alerted and ignored.
+pub struct ParamsReloader {
+    params: ParamsReloadParams,
+    path: PathBuf,
+    live: Arc<LiveParams>,
+}
+
+impl ParamsReloader {
+    pub fn new(params: ParamsReloadParams, path: PathBuf, live: Arc<LiveParams>) -> Self {
+        Self { params, path, live }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let mut hangup = signal(SignalKind::hangup())?;
+        let interval = tokio::time::Duration::from_secs(self.params.check_interval_sec);
+        if self.live.baseline.lock().unwrap().is_none() {
+            let baseline = self.read_vault_params().await?;
+            *self.live.baseline.lock().unwrap() = Some(baseline);
+        }
+        let mut modified = self.modified().await;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {
+                    let now_modified = self.modified().await;
+                    if now_modified == modified {
+                        continue;
+                    }
+                    modified = now_modified;
+                }
+                _ = hangup.recv() => info!("Reloading params on SIGHUP"),
+            }
+            if let Err(e) = self.reload().await {
+                send_alert(&format!("Params reload failed, keeping the running params: {:#}", e))
+                    .await;
+            }
+        }
+    }
+
+    async fn modified(&self) -> Option<SystemTime> {
+        tokio::fs::metadata(&self.path).await.and_then(|m| m.modified()).ok()
+    }
+
+    /// The vault's entry of the params file, which holds one vault or a list of them
+    async fn read_vault_params(&self) -> Result<Value> {
+        let vault_name = VaultContext::current().vault_name.clone();
+        let content = tokio::fs::read_to_string(&self.path).await?;
+        let vaults = match serde_json::from_str(&content)? {
+            Value::Array(vaults) => vaults,
+            params => vec![params],
+        };
+        vaults
+            .into_iter()
+            .find(|v| v.get("vault_name").and_then(Value::as_str) == Some(vault_name.as_str()))
+            .ok_or(Error::msg(format!("{} not found in {:?}", vault_name, self.path)))
+    }
+
+    async fn reload(&self) -> Result<()> {
+        let new = self.read_vault_params().await?;
+        VaultParams::from_value(new.clone())?;
+        let old = self.live.baseline.lock().unwrap().replace(new.clone()).unwrap_or_default();
+
+        let mut changed = vec![];
+        changed_paths(&old, &new, "", &mut changed);
+        if changed.is_empty() {
+            return Ok(());
+        }
+        let mut applied = vec![];
+        let mut rejected = vec![];
+        for path in changed {
+            match tunable_field(&path, &old) {
+                Some((section, field)) => {
+                    let value = new[section.key()][field].clone();
+                    applied.push(format!("{} = {}", path, value));
+                    self.live.overrides.lock().unwrap().insert((section, field.to_string()), value);
+                }
+                None => rejected.push(path),
+            }
+        }
+        if !applied.is_empty() {
+            send_alert(&format!("Params reloaded: {}", applied.join(", "))).await;
+        }
+        if !rejected.is_empty() {
+            warn!("Params reload rejected changes of {:?}", rejected);
+            send_alert(&format!(
+                "Params reload ignored structural changes until the next restart: {}",
+                rejected.join(", ")
+            ))
+            .await;
+        }
+        Ok(())
+    }
+}
+
+/// The tunable field at the path, if the previous params had it set (fields a vault does not
+/// use can not be tuned by adding them)
+fn tunable_field(path: &str, old: &Value) -> Option<(ParamsSection, &'static str)> {
+    let (section_key, field) = path.split_once('.')?;
+    let (section, field) = TUNABLE_FIELDS
+        .iter()
+        .find(|(section, name)| section.key() == section_key && *name == field)?;
+    old.get(section_key)?.get(field)?;
+    Some((*section, *field))
+}
+
+/// Dot separated paths of the values that differ, recursing into objects
+fn changed_paths(old: &Value, new: &Value, prefix: &str, out: &mut Vec<String>) {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        if old != new {
+            out.push(prefix.to_string());
+        }
+        return;
+    };
+    let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let path = match prefix {
+            "" => key.clone(),
+            _ => format!("{}.{}", prefix, key),
+        };
+        match (old.get(key), new.get(key)) {
+            (Some(old), Some(new)) => changed_paths(old, new, &path, out),
+            _ => out.push(path),
+        }
+    }
+}
+
+/// The params reloader task of the vault if `params_reload` is set in its params
+pub fn params_reloader(
+    params: Option<ParamsReloadParams>,
+    path: PathBuf,
+    live: Arc<LiveParams>,
+) -> Option<impl Future<Output = Result<()>>> {
+    let params = params?;
+    Some(async move { ParamsReloader::new(params, path, live).run().await })
+}
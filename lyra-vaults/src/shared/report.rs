@@ -0,0 +1,178 @@
+use crate::shared::auction::LimitOrderAuction;
+use crate::shared::context::VaultContext;
+use crate::shared::stats::candle_realized_vol;
+use anyhow::Result;
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use lyra_utils::black76::OptionContract;
+use orderbook_types::types::orders::Direction;
+use orderbook_types::types::tickers::{InstrumentTicker, OptionType};
+use serde::Serialize;
+use std::path::PathBuf;
+use tracing::info;
+
+/// What a limit order auction observed while quoting, see `ExecutionReport`
+#[derive(Debug, Clone, Default)]
+pub struct AuctionStats {
+    pub start_ticker: Option<InstrumentTicker>, // ticker once the auction started quoting
+    pub num_orders: u64,                        // orders sent, all but the first replace one
+    pub reported: bool,
+}
+
+/// Execution quality of a completed auction, to compare epochs and tune the auction params.
+/// Prices vs mark are relative and positive when the fills beat the mark at the auction start.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutionReport {
+    pub vault_name: String,
+    pub instrument_name: String,
+    pub start_sec: i64,
+    pub end_sec: i64,
+    pub duration_sec: i64,
+    pub direction: Option<Direction>,
+    pub filled_amount: BigDecimal,
+    pub num_fills: usize,
+    pub num_replaces: u64,
+    pub total_fees: BigDecimal,
+    pub start_mark_price: Option<BigDecimal>,
+    pub avg_fill_price: Option<BigDecimal>,
+    pub fill_vs_mark: Option<f64>,
+    pub start_mark_iv: Option<f64>,      // options only
+    pub fill_iv: Option<f64>,            // IV of the average fill price, at the start forward
+    pub realized_iv_spread: Option<f64>, // relative to the start mark IV, like the auction spread
+    // Public trades during the auction, from the candles of `candle_interval_sec` if set
+    pub market_volume: Option<BigDecimal>, // including the auction's own fills
+    pub market_vwap: Option<BigDecimal>,
+    pub fill_vs_vwap: Option<f64>, // like fill_vs_mark, against the market VWAP
+    pub realized_vol: Option<f64>, // annualized, of the candle closes
+}
+
+impl ExecutionReport {
+    /// Summarizes the fills of the auction instrument since the auction start, leaving out the
+    /// fills of orders labeled in another epoch or stage
+    pub async fn new(auction: &LimitOrderAuction) -> Self {
+        let stats = auction.stats.lock().unwrap().clone();
+        let start_ms = auction.start_timestamp_sec * 1000;
+        let labels = VaultContext::current().order_labels.clone();
+        let reader = auction.market.read().await;
+        let trades = reader
+            .get_trades(&auction.instrument_name)
+            .map(|trades| {
+                let trades = trades.values().filter(|t| t.timestamp >= start_ms);
+                trades.filter(|t| !labels.is_from_other_stage(&t.label)).collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let filled_amount: BigDecimal = trades.iter().map(|t| &t.trade_amount).sum();
+        let notional: BigDecimal = trades.iter().map(|t| &t.trade_amount * &t.trade_price).sum();
+        let total_fees: BigDecimal = trades.iter().map(|t| &t.trade_fee).sum();
+        let direction = trades.first().map(|t| t.direction);
+        let end_sec = trades.iter().map(|t| t.timestamp / 1000).max();
+        let end_sec = end_sec.unwrap_or(chrono::Utc::now().timestamp());
+        let avg_fill_price = (!filled_amount.is_zero()).then(|| &notional / &filled_amount);
+        let avg_fill_sec = (!filled_amount.is_zero()).then(|| {
+            let weighted: BigDecimal =
+                trades.iter().map(|t| &t.trade_amount * BigDecimal::from(t.timestamp)).sum();
+            (weighted / &filled_amount).to_i64().unwrap_or(start_ms) / 1000
+        });
+        let num_fills = trades.len();
+        let candles = auction
+            .candle_interval_sec
+            .map(|interval_sec| {
+                // owned copies, the reader is released below
+                let candles = reader.get_candles(&auction.instrument_name, interval_sec);
+                let candles = candles.into_iter().filter(|c| c.start_ms >= start_ms);
+                (interval_sec, candles.collect::<Vec<_>>())
+            })
+            .filter(|(_, candles)| !candles.is_empty());
+        drop(reader);
+
+        let start_ticker = stats.start_ticker.as_ref();
+        let start_mark_price = start_ticker.map(|t| t.mark_price.clone());
+        let fill_vs_mark = match (&avg_fill_price, &start_mark_price, direction) {
+            (Some(fill), Some(mark), Some(direction)) if !mark.is_zero() => {
+                let diff = ((fill - mark) / mark).to_f64();
+                match direction {
+                    Direction::Buy => diff.map(|diff| -diff),
+                    Direction::Sell => diff,
+                }
+            }
+            _ => None,
+        };
+        let start_mark_iv =
+            start_ticker.and_then(|t| t.option_pricing.as_ref()).and_then(|p| p.iv.to_f64());
+        let fill_iv = match (start_ticker, &avg_fill_price, avg_fill_sec) {
+            (Some(ticker), Some(price), Some(fill_sec)) => fill_iv(ticker, price, fill_sec),
+            _ => None,
+        };
+        let realized_iv_spread = match (start_mark_iv, fill_iv, direction) {
+            (Some(mark_iv), Some(fill_iv), Some(Direction::Sell)) => Some(1.0 - fill_iv / mark_iv),
+            (Some(mark_iv), Some(fill_iv), Some(Direction::Buy)) => Some(fill_iv / mark_iv - 1.0),
+            _ => None,
+        };
+
+        let market_volume: Option<BigDecimal> =
+            candles.as_ref().map(|(_, c)| c.iter().map(|c| &c.volume).sum());
+        let market_notional: Option<BigDecimal> =
+            candles.as_ref().map(|(_, c)| c.iter().map(|c| &c.notional).sum());
+        let market_vwap = match (&market_notional, &market_volume) {
+            (Some(notional), Some(volume)) if !volume.is_zero() => Some(notional / volume),
+            _ => None,
+        };
+        let fill_vs_vwap = match (&avg_fill_price, &market_vwap, direction) {
+            (Some(fill), Some(vwap), Some(direction)) => {
+                let diff = ((fill - vwap) / vwap).to_f64();
+                match direction {
+                    Direction::Buy => diff.map(|diff| -diff),
+                    Direction::Sell => diff,
+                }
+            }
+            _ => None,
+        };
+        let realized_vol = candles
+            .as_ref()
+            .and_then(|(interval_sec, candles)| candle_realized_vol(candles, *interval_sec));
+
+        Self {
+            vault_name: VaultContext::current().vault_name.clone(),
+            instrument_name: auction.instrument_name.clone(),
+            start_sec: auction.start_timestamp_sec,
+            end_sec,
+            duration_sec: end_sec - auction.start_timestamp_sec,
+            direction,
+            filled_amount,
+            num_fills,
+            num_replaces: stats.num_orders.saturating_sub(1),
+            total_fees,
+            start_mark_price,
+            avg_fill_price,
+            fill_vs_mark,
+            start_mark_iv,
+            fill_iv,
+            realized_iv_spread,
+            market_volume,
+            market_vwap,
+            fill_vs_vwap,
+            realized_vol,
+        }
+    }
+
+    /// Logs the report and writes it to REPORT_DIR (defaults to ./reports), one file per auction
+    pub async fn write(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        info!("Execution report: {}", json);
+        let dir = PathBuf::from(std::env::var("REPORT_DIR").unwrap_or("./reports".to_string()));
+        let vault_name = self.vault_name.to_lowercase();
+        let file_name = format!("{}_{}_{}.json", vault_name, self.instrument_name, self.start_sec);
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(dir.join(file_name), json).await?;
+        Ok(())
+    }
+}
+
+fn fill_iv(ticker: &InstrumentTicker, price: &BigDecimal, fill_sec: i64) -> Option<f64> {
+    let (details, pricing) = (ticker.option_details.as_ref()?, ticker.option_pricing.as_ref()?);
+    let contract = OptionContract {
+        strike: details.strike.to_f64()?,
+        expiry_sec: (details.expiry - fill_sec) as f64,
+        is_call: details.option_type == OptionType::C,
+    };
+    contract.implied_vol(pricing.forward_price.to_f64()?, price.to_f64()?)
+}
@@ -1,19 +1,22 @@
 use crate::helpers::{
-    sleep_till, subscribe_subaccount, subscribe_tickers, sync_subaccount, TickerInterval,
+    sleep_till, subscribe_subaccounts, subscribe_tickers, sync_subaccounts, TickerInterval,
 };
 use crate::market::{new_market_state, MarketState};
+use crate::shared::context::VaultContext;
+use crate::shared::dry_run::DryRunRecorder;
 use crate::web3::{get_tsa_contract, sign_execute_quote, sign_order, ProviderWithSigner, TSA};
 use anyhow::{Error, Result};
 use bigdecimal::{BigDecimal, Zero};
 use core::fmt;
 use ethers::prelude::Middleware;
-use log::{error, info, warn};
 use lyra_client::actions::rfq::{LegUnpriced, QuoteResultPublic};
+use lyra_client::error::LyraError;
 use lyra_client::json_rpc::{Response, WsClient, WsClientExt};
 use orderbook_types::types::rfqs::{
     Direction, GetRFQsResponse, OrderStatus, PollQuotesResponse, PollQuotesResult,
     RFQResponsePrivate, RFQResultPrivate,
 };
+use tracing::{error, info, warn};
 
 use orderbook_types::types::tickers::InstrumentTicker;
 use serde_json::{json, Value};
@@ -101,19 +104,21 @@ impl RFQLot {
         let (unfreeze, count) = self.timeouts.entry(wallet.clone()).or_insert((0, 0));
         *unfreeze = now + BASE_FREEZE_SEC * 2_i64.pow(*count);
         *count += 1;
-        warn!("RFQLot timeout wallet: {} for: {} count: {}", wallet, *unfreeze - now, count);
+        warn!(%wallet, timeout_sec = *unfreeze - now, count = *count, "RFQLot wallet timed out");
     }
 }
 
 /// State struct for an RFQ auction
 pub struct RFQAuction {
     // State
-    pub subaccount_id: i64,
+    pub subaccount_id: i64, // the RFQs are sent from, routed by the first leg
+    pub synced_subaccount_ids: Vec<i64>, // tracked in the market, subaccount_id first
     pub market: MarketState,
     pub client: WsClient,
     pub tsa: TSA<ProviderWithSigner>,
     pub start_timestamp_sec: i64,
     pub lots: Arc<Mutex<Vec<RFQLot>>>,
+    pub dry_run: Option<DryRunRecorder>,
 
     // Params
     pub unit_legs: Vec<LegUnpriced>,
@@ -128,9 +133,13 @@ impl RFQAuction {
         lot_init_sleep_sec: u64,
         auction_sec: i64,
     ) -> Result<Self> {
-        let vault_name = std::env::var("VAULT_NAME").unwrap();
-        let subaccount_id = std::env::var("SUBACCOUNT_ID").unwrap().parse().unwrap();
+        let ctx = VaultContext::current();
+        let vault_name = ctx.vault_name.clone();
+        let first_leg = unit_legs.first().ok_or(Error::msg("RFQ auction without legs"))?;
+        let subaccount_id = ctx.order_subaccount_id(&first_leg.instrument_name)?;
+        let synced_subaccount_ids = ctx.synced_subaccount_ids(&first_leg.instrument_name)?;
         sleep_till(start_sec).await;
+        ctx.await_reconciled().await;
         let start_timestamp_sec = chrono::Utc::now().timestamp();
 
         let market = new_market_state();
@@ -140,11 +149,13 @@ impl RFQAuction {
         let tsa = get_tsa_contract(&vault_name, "SESSION").await?;
         Ok(Self {
             subaccount_id,
+            synced_subaccount_ids,
             market,
             client,
             tsa,
             start_timestamp_sec,
             lots: Arc::new(Mutex::new(vec![])),
+            dry_run: None,
             unit_legs,
             auction_sec,
             lot_init_sleep_sec,
@@ -156,6 +167,15 @@ impl RFQAuction {
     pub fn instrument_names(&self) -> Vec<String> {
         self.unit_legs.iter().map(|leg| leg.instrument_name.clone()).collect()
     }
+    /// Sends the RFQs from the subaccount `subaccounts` routes the stage to, like
+    /// `LimitOrderAuction::route_to_stage`
+    pub fn route_to_stage(&mut self, stage: &str) -> Result<()> {
+        let first_leg = &self.unit_legs[0].instrument_name;
+        let ctx = VaultContext::current();
+        (self.subaccount_id, self.synced_subaccount_ids) =
+            ctx.stage_subaccount_ids(stage, first_leg)?;
+        Ok(())
+    }
     pub fn scaled_legs(&self, size: BigDecimal) -> Vec<LegUnpriced> {
         self.unit_legs
             .iter()
@@ -191,9 +211,10 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
     pub async fn run_market(&self) -> Result<()> {
         let market = &self.auction.market;
         let instruments = self.auction.instrument_names();
-        sync_subaccount(market.clone(), self.auction.subaccount_id, instruments.clone()).await?;
+        let subaccount_ids = self.auction.synced_subaccount_ids.clone();
+        sync_subaccounts(market.clone(), &subaccount_ids, instruments.clone()).await?;
 
-        let subacc_sub = subscribe_subaccount(market.clone(), self.auction.subaccount_id);
+        let subacc_sub = subscribe_subaccounts(market.clone(), subaccount_ids);
         let ticker_sub = subscribe_tickers(market.clone(), instruments, TickerInterval::_100Ms);
 
         let res = select! {
@@ -201,7 +222,7 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
             _ = subacc_sub => {Err(Error::msg("Subaccount subscription exited early"))},
         };
 
-        warn!("RFQAuctionExecutor run_market finished with {:?}", res);
+        warn!(result = ?res, "RFQAuctionExecutor run_market finished");
         res
     }
     async fn wait_for_tickers(&self) {
@@ -235,7 +256,7 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
             .await?
             .into_result()?;
         let lot = RFQLot::new(rfq.result, size);
-        info!("RFQAuctionExecutor new lot created: {:#?}", lot);
+        info!(?lot, "RFQAuctionExecutor new lot created");
         let mut lots = self.auction.lots.lock().await;
         lots.push(lot);
         Ok(())
@@ -296,7 +317,7 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
         let unit_cost =
             self.strategy.get_desired_unit_cost(&self.auction, current_lot.start_sec()).await?;
         let desired_cost = &unit_cost * &current_lot.size;
-        info!("RFQAuctionExecutor unit and desired costs: {}, {}", unit_cost, desired_cost);
+        info!(%unit_cost, %desired_cost, "RFQAuctionExecutor lot costs");
         let best_quote = current_lot.best_quote();
         if best_quote.is_none() {
             info!("RFQAuctionExecutor no quotes found for current lot");
@@ -304,17 +325,21 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
         }
         let best_quote = best_quote.unwrap();
         let best_cost = -best_quote.total_cost();
-        info!("RFQAuctionExecutor best quote cost: {} vs. desired {}", best_cost, desired_cost);
+        info!(%best_cost, %desired_cost, "RFQAuctionExecutor best quote");
         if best_cost > desired_cost {
-            info!("RFQ best quote cost too high. Cost: {}", best_cost);
+            info!(%best_cost, "RFQAuctionExecutor best quote cost too high");
             Ok(None)
         } else {
             let provider = self.auction.tsa.client();
             let signer = provider.inner().signer();
             let reader = self.auction.market.read().await;
             let tickers = reader.get_tickers();
-            let action_data = sign_execute_quote(&self.auction.tsa, &tickers, &best_quote).await?;
-            let execute_params = action_data.to_execute_params(&signer, tickers, best_quote)?;
+            let subaccount_id = self.auction.subaccount_id;
+            let action_data =
+                sign_execute_quote(&self.auction.tsa, subaccount_id, &tickers, &best_quote).await?;
+            let config = self.auction.client.get_config().await;
+            let execute_params =
+                action_data.to_execute_params(signer, &config, tickers, best_quote).await?;
             let send_resp = self
                 .auction
                 .client
@@ -323,14 +348,14 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
             return match send_resp {
                 Response::Success(v) => Ok(Some(v)),
                 Response::Error(e) => {
-                    error!("RFQAuctionExecutor send_execute failed with {:#}", e);
+                    error!(error = format!("{:#}", e), "RFQAuctionExecutor send_execute failed");
                     match e.error.code {
                         11104 | 8501 | 8500 => {
                             let wallet = best_quote.wallet.clone();
                             current_lot.timeout(&wallet);
                             Ok(None)
                         }
-                        _ => Err(Error::new(e)),
+                        _ => Err(LyraError::from(e).into()),
                     }
                 }
             };
@@ -340,6 +365,9 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
     pub async fn run_auction(&self) -> Result<()> {
         self.wait_for_tickers().await;
         let mut unit_cost = self.auction.get_mark_unit_cost().await?;
+        if let Some(dry_run) = &self.auction.dry_run {
+            return self.run_dry_auction(dry_run, &unit_cost).await;
+        }
 
         loop {
             self.sync().await;
@@ -355,9 +383,9 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
 
             loop {
                 let status = self.update_current_lot().await?;
-                info!("RFQAuctionExecutor current lot status: {:?}", status);
+                info!(?status, "RFQAuctionExecutor current lot status");
                 if status != OrderStatus::Open {
-                    warn!("RFQAuctionExecutor current lot status no longer open: {:?}", status);
+                    warn!(?status, "RFQAuctionExecutor current lot no longer open");
                     break;
                 }
                 if let Some(_) = self.maybe_execute_lot().await? {
@@ -374,12 +402,34 @@ impl<S: RFQStrategy + Debug> RFQAuctionExecutor<S> {
                 let worst_price = self.strategy.get_desired_unit_cost(&self.auction, start).await?;
                 let quote_price = -quote.total_cost() / &lot_size;
                 unit_cost = best_price.max(quote_price).min(worst_price);
-                info!("RFQAuctionExecutor new est_unit_cost: {}", unit_cost);
+                info!(est_unit_cost = %unit_cost, "RFQAuctionExecutor new unit cost estimate");
             }
         }
 
         Ok(())
     }
+    /// Records the RFQ that would have been sent without sending it (makers would see it),
+    /// then idles until the auction ends.
+    async fn run_dry_auction(
+        &self,
+        dry_run: &DryRunRecorder,
+        unit_cost: &BigDecimal,
+    ) -> Result<()> {
+        let lot_size = self.strategy.get_desired_lot_size(&self.auction, unit_cost).await?;
+        let desired_unit_cost = self
+            .strategy
+            .get_desired_unit_cost(&self.auction, self.auction.start_timestamp_sec)
+            .await?;
+        let payload = json!({
+            "legs": self.auction.scaled_legs(lot_size),
+            "mark_unit_cost": unit_cost.to_string(),
+            "desired_unit_cost": desired_unit_cost.to_string(),
+        });
+        dry_run.record("rfq", payload).await?;
+        let remain_sec = self.auction.remain_sec().max(0) as u64;
+        tokio::time::sleep(tokio::time::Duration::from_secs(remain_sec)).await;
+        Ok(())
+    }
     async fn sync(&self) {
         loop {
             if self.is_synced().await {
@@ -416,6 +466,7 @@ impl Debug for RFQAuction {
             .field("client", &"WsClient")
             .field("start_timestamp_sec", &self.start_timestamp_sec)
             .field("duration_sec", &self.auction_sec)
+            .field("dry_run", &self.dry_run.is_some())
             .finish()
     }
 }
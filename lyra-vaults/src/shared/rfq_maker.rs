@@ -0,0 +1,45 @@
+use crate::helpers::TickerInterval;
+use crate::market::new_market_state;
+use crate::shared::context::VaultContext;
+use crate::shared::tickers::TickerManager;
+use anyhow::{Error, Result};
+use futures::FutureExt;
+use lyra_client::rfq_maker::{QuoteTickers, RfqMaker, RfqMakerParams};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::select;
+
+/// The RFQ maker task of the vault if `rfq_maker` is set in its params, quoting from the vault's
+/// option book subaccount. Legs are priced with the tickers of a `TickerManager`, so the instruments of an
+/// RFQ stay subscribed and later RFQs on them are priced without a fetch.
+pub fn rfq_maker(
+    params: Option<RfqMakerParams>,
+    dry_run: bool,
+) -> Option<impl Future<Output = Result<()>>> {
+    let params = params?;
+    Some(async move {
+        let subaccount_id = VaultContext::current().option_subaccount_id()?;
+        let market = new_market_state();
+        let manager = Arc::new(TickerManager::new(market.clone(), TickerInterval::_1000Ms));
+        let lookup_manager = manager.clone();
+        let tickers: QuoteTickers = Arc::new(move |instrument_names: Vec<String>| {
+            let manager = lookup_manager.clone();
+            let market = market.clone();
+            async move {
+                manager.add(instrument_names.clone()).await?;
+                let reader = market.read().await;
+                let tickers = instrument_names
+                    .into_iter()
+                    .filter_map(|name| Some((name.clone(), reader.get_ticker(&name)?.clone())))
+                    .collect();
+                Ok(tickers)
+            }
+            .boxed()
+        });
+        let maker = RfqMaker::new(subaccount_id, params, tickers, dry_run);
+        select! {
+            res = maker.run() => Err(Error::msg(format!("RFQ maker exited with {:?}", res))),
+            res = manager.run() => res,
+        }
+    })
+}
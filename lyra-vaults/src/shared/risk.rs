@@ -0,0 +1,236 @@
+use crate::helpers::{fetch_ticker, subscribe_subaccounts, sync_subaccounts};
+use crate::market::{new_market_state, MarketState};
+use crate::shared::alert::send_alert;
+use crate::shared::context::VaultContext;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, Zero};
+use lyra_client::auth::get_auth_headers;
+use lyra_client::json_rpc::{http_rpc, WsClient, WsClientExt};
+use orderbook_types::types::orders::{GetTradesParams, GetTradesResponse};
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::select;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+const DAY_MS: i64 = 24 * 3600 * 1000;
+
+/// Invariants of the vault checked by the `RiskGuard`, limits that are not set are not checked
+#[derive(Debug, Clone, Deserialize)]
+pub struct RiskLimits {
+    pub max_option_delta: Option<BigDecimal>, // Max absolute delta of the option positions
+    pub max_short_vega: Option<BigDecimal>,   // Max vega sold, i.e. vega >= -max_short_vega
+    pub min_cash: Option<BigDecimal>,         // Min balance of cash_name, negative allows a borrow
+    pub max_daily_notional: Option<BigDecimal>, // Max amount * index price traded in the last 24h
+    #[serde(default = "default_check_interval_sec")]
+    pub check_interval_sec: u64,
+}
+
+fn default_check_interval_sec() -> u64 {
+    10
+}
+
+impl RiskLimits {
+    /// Descriptions of the breached limits, empty if all of them hold
+    async fn breaches(&self, market: &MarketState, cash_name: &str) -> Vec<String> {
+        let reader = market.read().await;
+        let collateral_names = VaultContext::current().collateral_names();
+        let greeks = reader.get_portfolio_greeks(&collateral_names);
+        let cash = reader.get_amount(cash_name);
+        let day_start = chrono::Utc::now().timestamp_millis() - DAY_MS;
+        let notional: BigDecimal = reader
+            .iter_trades()
+            .filter(|t| t.timestamp > day_start)
+            .map(|t| &t.trade_amount * &t.index_price)
+            .sum();
+        drop(reader);
+
+        let mut breaches = vec![];
+        if let Some(max_delta) = &self.max_option_delta {
+            if greeks.option_delta.abs() > *max_delta {
+                breaches
+                    .push(format!("option delta {} exceeds {}", greeks.option_delta, max_delta));
+            }
+        }
+        if let Some(max_short_vega) = &self.max_short_vega {
+            if -&greeks.vega > *max_short_vega {
+                breaches.push(format!("vega {} below -{}", greeks.vega, max_short_vega));
+            }
+        }
+        if let Some(min_cash) = &self.min_cash {
+            if cash < *min_cash {
+                breaches.push(format!("{} balance {} below {}", cash_name, cash, min_cash));
+            }
+        }
+        if let Some(max_notional) = &self.max_daily_notional {
+            if notional > *max_notional {
+                breaches
+                    .push(format!("notional traded in 24h {} exceeds {}", notional, max_notional));
+            }
+        }
+        if !greeks.missing.is_empty() {
+            warn!("Risk checks exclude positions without pricing: {:?}", greeks.missing);
+        }
+        breaches
+    }
+}
+
+/// Current breach of the vault's risk limits, shared between the guard and the executor.
+/// None while all limits hold.
+#[derive(Debug)]
+pub struct RiskHalt(watch::Sender<Option<String>>);
+
+impl RiskHalt {
+    fn new() -> Self {
+        Self(watch::channel(None).0)
+    }
+
+    pub fn breach(&self) -> Option<String> {
+        self.0.borrow().clone()
+    }
+
+    /// Resolves with the breach once a limit is breached, right away if one is already
+    pub async fn breached(&self) -> String {
+        let mut receiver = self.0.subscribe();
+        let breach = receiver.wait_for(|breach| breach.is_some()).await;
+        // the sender lives as long as self, so waiting can't fail
+        breach.ok().and_then(|breach| breach.clone()).unwrap_or_default()
+    }
+
+    /// Resolves once all limits hold again
+    pub async fn cleared(&self) {
+        let mut receiver = self.0.subscribe();
+        let _ = receiver.wait_for(|breach| breach.is_none()).await;
+    }
+}
+
+/// Checks the `RiskLimits` against the positions summed over the vault's subaccounts every
+/// `check_interval_sec`. On a breach
+/// it halts the vault (see `VaultExecutor`), cancels the open orders and sends an alert.
+/// Open orders placed while halted are cancelled on every check until the limits hold again.
+pub struct RiskGuard {
+    limits: RiskLimits,
+    subaccount_ids: Vec<i64>,
+    market: MarketState,
+    halt: Arc<RiskHalt>,
+    dry_run: bool, // only alert, leave the orders to the dry run recorder
+}
+
+impl RiskGuard {
+    pub fn new(limits: RiskLimits, halt: Arc<RiskHalt>, dry_run: bool) -> Result<Self> {
+        let subaccount_ids = VaultContext::current().subaccount_ids()?;
+        Ok(Self { limits, subaccount_ids, market: new_market_state(), halt, dry_run })
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        sync_subaccounts(self.market.clone(), &self.subaccount_ids, vec![]).await?;
+        for subaccount_id in self.subaccount_ids.iter() {
+            self.sync_daily_trades(*subaccount_id).await?;
+        }
+        let subacc_sub = subscribe_subaccounts(self.market.clone(), self.subaccount_ids.clone());
+        select! {
+            _ = subacc_sub => Err(Error::msg("Risk guard subaccount subscription exited early")),
+            _ = self.check_forever() => Err(Error::msg("Risk guard checks exited early")),
+        }
+    }
+
+    /// Trades of the last 24h for the notional limit, the subscription adds new ones
+    async fn sync_daily_trades(&self, subaccount_id: i64) -> Result<()> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let params = GetTradesParams {
+            subaccount_id,
+            instrument_name: None,
+            order_id: None,
+            quote_id: None,
+            from_timestamp: now - DAY_MS,
+            to_timestamp: now,
+            page: 1,
+            page_size: 1000, // NOTE: assumes there's <= 1000 trades per day
+        };
+        let headers = get_auth_headers().await?;
+        let trades =
+            http_rpc::<_, GetTradesResponse>("private/get_trade_history", params, Some(headers))
+                .await?
+                .into_result()?;
+        let mut writer = self.market.write().await;
+        for trade in trades.result.trades {
+            writer.insert_trade(trade);
+        }
+        Ok(())
+    }
+
+    async fn check_forever(&self) {
+        let interval = tokio::time::Duration::from_secs(self.limits.check_interval_sec);
+        loop {
+            if let Err(e) = self.check().await {
+                warn!("Risk guard check failed with {:#}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Refreshes the option and perp tickers, then halts or resumes the vault
+    async fn check(&self) -> Result<()> {
+        let instrument_names = self
+            .market
+            .read()
+            .await
+            .iter_positions()
+            .filter(|p| !p.amount.is_zero() && p.instrument_name.contains('-'))
+            .map(|p| p.instrument_name.clone())
+            .collect::<Vec<_>>();
+        for instrument_name in instrument_names {
+            fetch_ticker(self.market.clone(), &instrument_name).await?;
+        }
+        let ctx = VaultContext::current();
+        let breaches = self.limits.breaches(&self.market, &ctx.cash_name).await;
+        let breach = (!breaches.is_empty()).then(|| breaches.join(", "));
+        match (breach, self.halt.breach()) {
+            (Some(breach), None) => {
+                send_alert(&format!("Risk limits breached, halting: {}", breach)).await;
+                self.halt.0.send_replace(Some(breach.clone()));
+                ctx.health.update(|h| h.risk_breach = Some(breach));
+                self.cancel_open_orders().await?;
+            }
+            (Some(_), Some(_)) => self.cancel_open_orders().await?,
+            (None, Some(previous)) => {
+                send_alert(&format!("Risk limits hold again, resuming after: {}", previous)).await;
+                self.halt.0.send_replace(None);
+                ctx.health.update(|h| h.risk_breach = None);
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+    async fn cancel_open_orders(&self) -> Result<()> {
+        let has_orders = self.market.read().await.iter_orders().any(|orders| !orders.is_empty());
+        if !has_orders {
+            return Ok(());
+        }
+        if self.dry_run {
+            info!("Risk guard (dry run) would cancel all orders of {:?}", self.subaccount_ids);
+            return Ok(());
+        }
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        for subaccount_id in self.subaccount_ids.iter() {
+            let res = client.cancel_all(*subaccount_id).await?.into_result()?;
+            info!("Risk guard cancelled all orders of {}: {:?}", subaccount_id, res.result);
+        }
+        Ok(())
+    }
+}
+
+/// The halt and guard task of the vault if `risk_limits` are set in its params
+pub fn risk_guard(
+    limits: Option<RiskLimits>,
+    dry_run: bool,
+) -> Option<(Arc<RiskHalt>, impl Future<Output = Result<()>>)> {
+    let limits = limits?;
+    let halt = Arc::new(RiskHalt::new());
+    let guard_halt = halt.clone();
+    let guard = async move { RiskGuard::new(limits, guard_halt, dry_run)?.run().await };
+    Some((halt, guard))
+}
@@ -0,0 +1,344 @@
+use anyhow::{Error, Result};
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Days searched for the next run, e.g. for runs on Feb 29 only
+const MAX_SEARCH_DAYS: u32 = 5 * 366;
+
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const MONTHS: [&str; 12] =
+    ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+/// Recurring run time in a timezone (UTC by default), either as a cron expression with the
+/// minute, hour, day of month, month and day of week fields and an optional timezone, e.g.
+/// `5 8 * * FRI Europe/London`, or as `<days> <HH:MM> [timezone]` with the days `daily`,
+/// `weekdays` or a comma separated list of week days, e.g. `fri 08:05 UTC`.
+/// Times are local to the timezone, so a run at 08:05 London stays at 08:05 across DST changes.
+/// Runs skipped by a DST change happen once the clocks moved on, repeated times run once.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days_of_month: Option<Vec<u32>>, // None for `*`
+    months: Vec<u32>,
+    days_of_week: Option<Vec<u32>>, // 0 is Sunday, None for `*`
+    tz: Tz,
+}
+
+impl CronSchedule {
+    /// First run strictly after the time
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut date = after.with_timezone(&self.tz).date_naive();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_date(date) {
+                for hour in self.hours.iter() {
+                    for minute in self.minutes.iter() {
+                        let Some(run) = self.resolve(date.and_hms_opt(*hour, *minute, 0)?) else {
+                            continue;
+                        };
+                        if run > after {
+                            return Some(run);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    /// First run at or after the timestamp (in seconds)
+    pub fn next_from_sec(&self, from_sec: i64) -> Option<i64> {
+        let after = DateTime::from_timestamp(from_sec - 1, 0)?;
+        self.next_after(after).map(|run| run.timestamp())
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !self.months.contains(&date.month()) {
+            return false;
+        }
+        let day_of_month = self.days_of_month.as_ref().map(|days| days.contains(&date.day()));
+        let weekday = date.weekday().num_days_from_sunday();
+        let day_of_week = self.days_of_week.as_ref().map(|days| days.contains(&weekday));
+        // like cron, a day matches either of the two fields if both are restricted
+        match (day_of_month, day_of_week) {
+            (Some(dom), Some(dow)) => dom || dow,
+            (Some(matches), None) | (None, Some(matches)) => matches,
+            (None, None) => true,
+        }
+    }
+
+    fn resolve(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let run = match self.tz.from_local_datetime(&local) {
+            LocalResult::Single(run) | LocalResult::Ambiguous(run, _) => run,
+            // skipped by the clocks moving forward
+            LocalResult::None => {
+                self.tz.from_local_datetime(&(local + Duration::hours(1))).earliest()?
+            }
+        };
+        Some(run.with_timezone(&Utc))
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let invalid = || Error::msg(format!("Invalid schedule {:?}", expression));
+        let (minutes, hours, days_of_month, months, days_of_week, tz) = match fields.as_slice() {
+            [days, time, rest @ ..] if time.contains(':') && rest.len() <= 1 => {
+                let (hour, minute) = time.split_once(':').ok_or_else(invalid)?;
+                let days_of_week = match days.to_lowercase().as_str() {
+                    "daily" => None,
+                    "weekdays" => Some((1..=5).collect()),
+                    days => Some(days.split(',').map(parse_weekday).collect::<Result<_>>()?),
+                };
+                let hour = parse_value(hour, 0, 23, &[])?;
+                let minute = parse_value(minute, 0, 59, &[])?;
+                (vec![minute], vec![hour], None, (1..=12).collect(), days_of_week, rest.first())
+            }
+            [minute, hour, day_of_month, month, day_of_week, rest @ ..] if rest.len() <= 1 => {
+                let days_of_week = match *day_of_week {
+                    "*" => None,
+                    // 7 is Sunday as well
+                    days => Some(
+                        parse_field(days, 0, 7, &WEEKDAYS)?.into_iter().map(|d| d % 7).collect(),
+                    ),
+                };
+                (
+                    parse_field(minute, 0, 59, &[])?,
+                    parse_field(hour, 0, 23, &[])?,
+                    match *day_of_month {
+                        "*" => None,
+                        days => Some(parse_field(days, 1, 31, &[])?),
+                    },
+                    parse_field(month, 1, 12, &MONTHS)?,
+                    days_of_week,
+                    rest.first(),
+                )
+            }
+            _ => return Err(invalid()),
+        };
+        let tz = match tz {
+            Some(tz) => Tz::from_str(tz).map_err(|e| Error::msg(format!("{}: {}", tz, e)))?,
+            None => Tz::UTC,
+        };
+        let sorted = |mut values: Vec<u32>| {
+            values.sort();
+            values.dedup();
+            values
+        };
+        Ok(Self {
+            expression: expression.to_string(),
+            minutes: sorted(minutes),
+            hours: sorted(hours),
+            days_of_month: days_of_month.map(sorted),
+            months,
+            days_of_week: days_of_week.map(sorted),
+            tz,
+        })
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = Error;
+
+    fn try_from(expression: String) -> Result<Self> {
+        CronSchedule::from_str(&expression)
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.expression
+    }
+}
+
+/// Week day by its name or a prefix of at least 3 letters, e.g. `fri`, `Friday` or `fridays`
+fn parse_weekday(day: &str) -> Result<u32> {
+    let day = day.to_lowercase();
+    let index = WEEKDAYS.iter().position(|name| day.len() >= 3 && day.starts_with(name));
+    index.map(|i| i as u32).ok_or(Error::msg(format!("Invalid week day {:?}", day)))
+}
+
+/// A number in the range or one of the names, whose first has the value `min`
+fn parse_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32> {
+    let lower = value.to_lowercase();
+    let parsed = match names.iter().position(|name| *name == lower) {
+        Some(i) => i as u32 + min,
+        None => value.parse().map_err(|_| Error::msg(format!("Invalid value {:?}", value)))?,
+    };
+    if parsed < min || parsed > max {
+        return Err(Error::msg(format!("{} is not within {}-{}", parsed, min, max)));
+    }
+    Ok(parsed)
+}
+
+/// A cron field: comma separated values, ranges `a-b` and steps `*/n` or `a-b/n`
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<Vec<u32>> {
+    let mut values = vec![];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(Error::msg(format!("Invalid step in {:?}", field)));
+        }
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => {
+                (parse_value(start, min, max, names)?, parse_value(end, min, max, names)?)
+            }
+            None => {
+                let value = parse_value(range, min, max, names)?;
+                // `a/n` runs from a to the end of the range
+                (value, if part.contains('/') { max } else { value })
+            }
+        };
+        values.extend((start..=end).step_by(step as usize));
+    }
+    Ok(values)
+}
+
+/// Start times of the vault's stages, e.g. `"schedule": {"option_auction": "fri 08:05 UTC"}`.
+/// A scheduled stage starts at its first run at or after the time it could start at (e.g. the
+/// expiry of the previous options), the others after their fixed delay.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StageSchedule {
+    pub option_auction: Option<CronSchedule>,
+    pub spot_auction: Option<CronSchedule>,
+}
+
+impl StageSchedule {
+    pub fn option_auction_start(&self, earliest_sec: i64, delay_sec: i64) -> i64 {
+        scheduled_start(self.option_auction.as_ref(), earliest_sec, delay_sec)
+    }
+
+    pub fn spot_auction_start(&self, earliest_sec: i64, delay_sec: i64) -> i64 {
+        scheduled_start(self.spot_auction.as_ref(), earliest_sec, delay_sec)
+    }
+
+    /// Next run of each scheduled stage, served on `/status`
+    pub fn upcoming(&self, now: DateTime<Utc>) -> BTreeMap<String, i64> {
+        let stages =
+            [("option_auction", &self.option_auction), ("spot_auction", &self.spot_auction)];
+        stages
+            .into_iter()
+            .filter_map(|(stage, schedule)| {
+                let run = schedule.as_ref()?.next_after(now)?;
+                Some((stage.to_string(), run.timestamp()))
+            })
+            .collect()
+    }
+}
+
+fn scheduled_start(schedule: Option<&CronSchedule>, earliest_sec: i64, delay_sec: i64) -> i64 {
+    schedule
+        .and_then(|schedule| schedule.next_from_sec(earliest_sec))
+        .unwrap_or(earliest_sec + delay_sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expression: &str, after: &str) -> DateTime<Utc> {
+        CronSchedule::from_str(expression).unwrap().next_after(utc(after)).unwrap()
+    }
+
+    #[test]
+    fn test_weekly_time() {
+        // 2024-06-03 is a Monday
+        assert_eq!(next("fri 08:05 UTC", "2024-06-03T00:00:00Z"), utc("2024-06-07T08:05:00Z"));
+        assert_eq!(next("fri 08:05 UTC", "2024-06-07T08:05:00Z"), utc("2024-06-14T08:05:00Z"));
+        assert_eq!(next("Fridays 08:05", "2024-06-07T08:04:59Z"), utc("2024-06-07T08:05:00Z"));
+        assert_eq!(next("weekdays 23:00", "2024-06-07T23:00:00Z"), utc("2024-06-10T23:00:00Z"));
+        assert_eq!(next("daily 00:00", "2024-06-07T23:00:00Z"), utc("2024-06-08T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_local_time_across_dst() {
+        // London moves to BST on 2024-03-31 and back to GMT on 2024-10-27
+        let london = "5 8 * * FRI Europe/London";
+        assert_eq!(next(london, "2024-03-25T00:00:00Z"), utc("2024-03-29T08:05:00Z"));
+        assert_eq!(next(london, "2024-03-29T08:05:00Z"), utc("2024-04-05T07:05:00Z"));
+        assert_eq!(next(london, "2024-10-21T00:00:00Z"), utc("2024-10-25T07:05:00Z"));
+        assert_eq!(next(london, "2024-10-25T07:05:00Z"), utc("2024-11-01T08:05:00Z"));
+        assert_eq!(
+            next("fri 08:05 Europe/London", "2024-03-29T08:05:00Z"),
+            next(london, "2024-03-29T08:05:00Z")
+        );
+    }
+
+    #[test]
+    fn test_skipped_and_repeated_local_times() {
+        let schedule = "30 1 * * * Europe/London";
+        // 01:30 does not exist on 2024-03-31, the run happens at 02:30 BST instead
+        assert_eq!(next(schedule, "2024-03-30T02:00:00Z"), utc("2024-03-31T01:30:00Z"));
+        // 01:30 happens twice on 2024-10-27, the run happens on the first only
+        assert_eq!(next(schedule, "2024-10-26T02:00:00Z"), utc("2024-10-27T00:30:00Z"));
+        assert_eq!(next(schedule, "2024-10-27T00:30:00Z"), utc("2024-10-28T01:30:00Z"));
+    }
+
+    #[test]
+    fn test_steps_and_ranges() {
+        assert_eq!(next("*/15 * * * *", "2024-06-03T10:07:00Z"), utc("2024-06-03T10:15:00Z"));
+        assert_eq!(next("*/15 * * * *", "2024-06-03T10:45:00Z"), utc("2024-06-03T11:00:00Z"));
+        assert_eq!(next("10/20 9-17 * * *", "2024-06-03T17:50:00Z"), utc("2024-06-04T09:10:00Z"));
+        assert_eq!(next("0 8 1,15 * *", "2024-06-01T08:00:00Z"), utc("2024-06-15T08:00:00Z"));
+        assert_eq!(next("0 0 29 feb *", "2024-03-01T00:00:00Z"), utc("2028-02-29T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_sunday_as_7() {
+        // 2024-06-09 is a Sunday
+        assert_eq!(next("0 9 * * 7", "2024-06-03T00:00:00Z"), utc("2024-06-09T09:00:00Z"));
+        assert_eq!(next("0 9 * * 0", "2024-06-03T00:00:00Z"), utc("2024-06-09T09:00:00Z"));
+        assert_eq!(next("0 9 * * 5-7", "2024-06-08T09:00:00Z"), utc("2024-06-09T09:00:00Z"));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        let invalid = [
+            "",
+            "fri",
+            "fri 25:00",
+            "fri 08:60",
+            "blursday 08:00",
+            "fri 08:05 UTC extra",
+            "fri 08:05 Mars/Olympus",
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "* * * * * UTC extra",
+        ];
+        for expression in invalid {
+            assert!(CronSchedule::from_str(expression).is_err(), "{:?} parsed", expression);
+        }
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let schedule: StageSchedule =
+            serde_json::from_str(r#"{"option_auction": "fri 08:05 UTC"}"#).unwrap();
+        assert!(schedule.spot_auction.is_none());
+        let json = serde_json::to_value(&schedule).unwrap();
+        assert_eq!(json["option_auction"], "fri 08:05 UTC");
+        assert!(serde_json::from_str::<StageSchedule>(r#"{"option_auction": "fri"}"#).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::shared::context::VaultContext;
+use crate::web3::gas::env_or;
+use crate::web3::providers::get_lyra_provider;
+use crate::web3::{get_tsa_contract, send_with_retry, ProviderWithSigner, GAS_FACTOR, TSA};
+use anyhow::Result;
+use ethers::prelude::{Address, Middleware, Signer, SignerMiddleware};
+use ethers::types::TransactionRequest;
+use lyra_client::auth::load_signer;
+use lyra_client::config::LyraConfig;
+use lyra_client::error::LyraError;
+use lyra_client::session::{SessionKeyRegistrar, SessionKeyRotation, SessionKeyStore};
+use lyra_client::signer::LyraSigner;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+const DEFAULT_SESSION_KEY_LIFETIME_SEC: i64 = 30 * 24 * 3600;
+const DEFAULT_ACTIVATION_TIMEOUT_SEC: u64 = 300;
+/// Plain transfer gas, with GAS_FACTOR headroom when sweeping the old key
+const TRANSFER_GAS: u64 = 21_000;
+
+/// Adds and removes TSA signers as the TSA owner (the `{VAULT}_TSA_OWNER` signer)
+pub struct TsaSignerRegistrar {
+    tsa: TSA<ProviderWithSigner>,
+}
+
+impl TsaSignerRegistrar {
+    pub async fn new(vault_name: &str) -> Result<Self> {
+        let tsa = get_tsa_contract(vault_name, &format!("{vault_name}_TSA_OWNER")).await?;
+        Ok(Self { tsa })
+    }
+}
+
+impl SessionKeyRegistrar for TsaSignerRegistrar {
+    /// TSA signers do not expire, the lifetime is tracked by `SessionKeyLifetime`
+    async fn register(
+        &self,
+        session_key: Address,
+        _expiry_sec: i64,
+        _label: &str,
+    ) -> lyra_client::error::Result<()> {
+        send_with_retry(&self.tsa.client(), self.tsa.set_signer(session_key, true)).await?;
+        Ok(())
+    }
+
+    async fn is_active(&self, session_key: &LyraSigner) -> lyra_client::error::Result<bool> {
+        let call = self.tsa.is_signer(session_key.address());
+        call.call().await.map_err(LyraError::transport)
+    }
+
+    async fn revoke(&self, session_key: Address) -> lyra_client::error::Result<()> {
+        send_with_retry(&self.tsa.client(), self.tsa.set_signer(session_key, false)).await?;
+        Ok(())
+    }
+}
+
+/// When the current session key of a vault is due, kept in STATE_DIR next to the executor state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionKeyLifetime {
+    session_key: Address,
+    expiry_sec: i64,
+}
+
+impl SessionKeyLifetime {
+    fn path(vault_name: &str) -> PathBuf {
+        let dir = std::env::var("STATE_DIR").unwrap_or("./state".to_string());
+        PathBuf::from(dir).join(format!("{}_session_key.json", vault_name.to_lowercase()))
+    }
+
+    /// The stored lifetime of `session_key`, a new one starting now for an untracked key
+    async fn load(vault_name: &str, session_key: Address, lifetime_sec: i64) -> Result<Self> {
+        let path = Self::path(vault_name);
+        if tokio::fs::try_exists(&path).await? {
+            let contents = tokio::fs::read_to_string(&path).await?;
+            match serde_json::from_str::<SessionKeyLifetime>(&contents) {
+                Ok(lifetime) if lifetime.session_key == session_key => return Ok(lifetime),
+                Ok(_) => info!("Session key changed, tracking {:?} from now", session_key),
+                Err(e) => warn!("Ignoring corrupt session key state at {:?}: {:?}", path, e),
+            }
+        }
+        let expiry_sec = chrono::Utc::now().timestamp() + lifetime_sec;
+        let lifetime = SessionKeyLifetime { session_key, expiry_sec };
+        lifetime.save(vault_name).await?;
+        Ok(lifetime)
+    }
+
+    async fn save(&self, vault_name: &str) -> Result<()> {
+        let path = Self::path(vault_name);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&path, serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+}
+
+/// Rotates the vault's session key once it is within SESSION_ROTATE_BEFORE_SEC of its
+/// SESSION_KEY_LIFETIME_SEC. Disabled unless SESSION_ROTATE_BEFORE_SEC is set, since it needs
+/// the `{VAULT}_TSA_OWNER` signer. The new key is stored under the vault's AWS session key name
+/// and gets the gas balance of the old key.
+pub async fn rotate_session_key_if_expiring() -> Result<()> {
+    let Ok(rotate_before_sec) = std::env::var("SESSION_ROTATE_BEFORE_SEC") else {
+        return Ok(());
+    };
+    let rotate_before_sec: i64 = rotate_before_sec.parse()?;
+    let lifetime_sec = env_or("SESSION_KEY_LIFETIME_SEC", DEFAULT_SESSION_KEY_LIFETIME_SEC);
+    let vault_name = VaultContext::current().vault_name.clone();
+    let old_key = load_signer().await?;
+    if !matches!(old_key, LyraSigner::Local(_)) {
+        warn!("Session key of {} is not a local key, skipping rotation", vault_name);
+        return Ok(());
+    }
+    let lifetime = SessionKeyLifetime::load(&vault_name, old_key.address(), lifetime_sec).await?;
+    if chrono::Utc::now().timestamp() < lifetime.expiry_sec - rotate_before_sec {
+        return Ok(());
+    }
+    let activation_timeout_sec =
+        env_or("SESSION_ACTIVATION_TIMEOUT_SEC", DEFAULT_ACTIVATION_TIMEOUT_SEC);
+    let rotation = SessionKeyRotation {
+        lifetime_sec,
+        label: vault_name.to_lowercase(),
+        activation_timeout: Duration::from_secs(activation_timeout_sec),
+        store: SessionKeyStore::Aws(vault_name.to_lowercase()),
+    };
+    let registrar = TsaSignerRegistrar::new(&vault_name).await?;
+    let new_key = rotation.rotate(&registrar, &old_key).await?;
+    let expiry_sec = chrono::Utc::now().timestamp() + lifetime_sec;
+    SessionKeyLifetime { session_key: new_key.address(), expiry_sec }.save(&vault_name).await?;
+    sweep_gas(old_key, new_key.address()).await
+}
+
+/// Sends the ETH balance of the old session key (less the transfer gas) to the new one
+async fn sweep_gas(old_key: LyraSigner, new_key: Address) -> Result<()> {
+    let provider = get_lyra_provider().await?;
+    let old_key = old_key.with_chain_id(LyraConfig::current()?.chain_id);
+    let client = SignerMiddleware::new((*provider).clone(), old_key);
+    let balance = client.get_balance(client.address(), None).await?;
+    let gas_cost = client.get_gas_price().await? * TRANSFER_GAS * GAS_FACTOR;
+    if balance <= gas_cost {
+        info!("Nothing to sweep from the old session key, balance {}", balance);
+        return Ok(());
+    }
+    let tx = TransactionRequest::pay(new_key, balance - gas_cost);
+    let receipt = client.send_transaction(tx, None).await?.await?;
+    info!("Swept {} to the new session key: {:?}", balance - gas_cost, receipt);
+    Ok(())
+}
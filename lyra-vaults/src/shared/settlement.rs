@@ -0,0 +1,155 @@
+use crate::helpers::{get_single_balance, sync_subaccount};
+use crate::market::new_market_state;
+use crate::shared::context::VaultContext;
+use crate::web3::settlement::get_onchain_settlement_price;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, FromPrimitive, Zero};
+use lyra_client::auth::get_auth_headers;
+use lyra_client::json_rpc::http_rpc;
+use orderbook_types::generated::private_get_option_settlement_history::{
+    PrivateGetOptionSettlementHistoryParamsSchema, PrivateGetOptionSettlementHistoryResponseSchema,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::info;
+
+/// Tolerances of the settlement cross-check, see `verify_settlement`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettlementCheckParams {
+    #[serde(default = "default_max_price_diff")]
+    pub max_price_diff: f64, // Relative difference of the API and on-chain settlement prices
+    #[serde(default = "default_max_cash_diff")]
+    pub max_cash_diff: BigDecimal, // Received cash vs the payout at the on-chain price, in cash
+}
+
+fn default_max_price_diff() -> f64 {
+    0.001
+}
+
+fn default_max_cash_diff() -> BigDecimal {
+    BigDecimal::from(1)
+}
+
+impl Default for SettlementCheckParams {
+    fn default() -> Self {
+        Self { max_price_diff: default_max_price_diff(), max_cash_diff: default_max_cash_diff() }
+    }
+}
+
+/// Option positions and cash of the vault shortly before expiry, to compare the settlement with
+#[derive(Debug, Clone)]
+pub struct SettlementSnapshot {
+    pub positions: HashMap<String, BigDecimal>,
+    pub cash: BigDecimal,
+    pub timestamp_sec: i64,
+}
+
+impl SettlementSnapshot {
+    pub async fn take(subaccount_id: i64, option_names: &[String]) -> Result<Self> {
+        let market = new_market_state();
+        sync_subaccount(market.clone(), subaccount_id, vec![]).await?;
+        let reader = market.read().await;
+        let positions = option_names
+            .iter()
+            .map(|name| (name.clone(), reader.get_amount(name)))
+            .collect::<HashMap<_, _>>();
+        let cash = reader.get_amount(&VaultContext::current().cash_name);
+        Ok(Self { positions, cash, timestamp_sec: chrono::Utc::now().timestamp() })
+    }
+}
+
+/// Currency, strike and whether it is a call, from an option name like ETH-20240927-3000-C
+fn parse_option_name(option_name: &str) -> Result<(String, BigDecimal, bool)> {
+    let parts = option_name.split('-').collect::<Vec<_>>();
+    let &[currency, _, strike, option_type] = parts.as_slice() else {
+        return Err(Error::msg(format!("Invalid option name {}", option_name)));
+    };
+    Ok((currency.to_string(), BigDecimal::from_str(strike)?, option_type == "C"))
+}
+
+/// Value per contract of the option at a settlement price
+pub fn settled_value(option_name: &str, settlement_price: &BigDecimal) -> Result<BigDecimal> {
+    let (_, strike, is_call) = parse_option_name(option_name)?;
+    Ok(payoff(settlement_price, &strike, is_call))
+}
+
+fn payoff(settlement_price: &BigDecimal, strike: &BigDecimal, is_call: bool) -> BigDecimal {
+    let intrinsic = match is_call {
+        true => settlement_price - strike,
+        false => strike - settlement_price,
+    };
+    intrinsic.max(BigDecimal::zero())
+}
+
+/// Cross-checks the API settlement of the options with the on-chain settlement feed: the
+/// settlement prices, the settled amounts vs the `snapshot` positions and, if `check_cash`,
+/// the cash received since the snapshot vs the payout at the on-chain price.
+/// Returns the discrepancies above the tolerances, empty if the settlement checks out.
+pub async fn verify_settlement(
+    subaccount_id: i64,
+    option_names: &[String],
+    snapshot: Option<&SettlementSnapshot>,
+    check_cash: bool,
+    params: &SettlementCheckParams,
+) -> Result<Vec<String>> {
+    let headers = get_auth_headers().await?;
+    let history = http_rpc::<_, PrivateGetOptionSettlementHistoryResponseSchema>(
+        "private/get_option_settlement_history",
+        PrivateGetOptionSettlementHistoryParamsSchema { subaccount_id },
+        Some(headers),
+    )
+    .await?
+    .into_result()?;
+    let max_price_diff = BigDecimal::from_f64(params.max_price_diff).unwrap_or_default();
+    let mut discrepancies = vec![];
+    let mut expected_cash = BigDecimal::zero();
+    for option_name in option_names {
+        let held = snapshot.and_then(|s| s.positions.get(option_name)).cloned();
+        let settlement =
+            history.result.settlements.iter().find(|s| &s.instrument_name == option_name);
+        let Some(settlement) = settlement else {
+            if held.is_some_and(|amount| !amount.is_zero()) {
+                discrepancies.push(format!("{} settlement missing from the API", option_name));
+            }
+            continue;
+        };
+        let (currency, strike, is_call) = parse_option_name(option_name)?;
+        let chain_price = get_onchain_settlement_price(&currency, settlement.expiry).await?;
+        let Some(chain_price) = chain_price else {
+            discrepancies.push(format!("{} expiry not settled on-chain", option_name));
+            continue;
+        };
+        info!(
+            "{} settled {} at {} (on-chain {})",
+            option_name, settlement.amount, settlement.settlement_price, chain_price
+        );
+        let price_diff = (&settlement.settlement_price - &chain_price).abs();
+        if price_diff > &chain_price * &max_price_diff {
+            discrepancies.push(format!(
+                "{} settlement price {} differs from on-chain {}",
+                option_name, settlement.settlement_price, chain_price
+            ));
+        }
+        let amount = held.unwrap_or(settlement.amount.clone());
+        if snapshot.is_some() && amount.abs() != settlement.amount.abs() {
+            discrepancies.push(format!(
+                "{} settled amount {} differs from position {}",
+                option_name, settlement.amount, amount
+            ));
+        }
+        expected_cash += amount * payoff(&chain_price, &strike, is_call);
+    }
+    if let (Some(snapshot), true) = (snapshot, check_cash) {
+        let cash_name = VaultContext::current().cash_name.clone();
+        let received = get_single_balance(subaccount_id, &cash_name).await? - &snapshot.cash;
+        info!("Settlement received {} {}, expected {}", received, cash_name, expected_cash);
+        if (&received - &expected_cash).abs() > params.max_cash_diff {
+            discrepancies.push(format!(
+                "received {} {} since {}, expected {} at the on-chain price",
+                received, cash_name, snapshot.timestamp_sec, expected_cash
+            ));
+        }
+    }
+    Ok(discrepancies)
+}
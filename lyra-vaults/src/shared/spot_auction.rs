@@ -1,12 +1,13 @@
 use crate::shared::auction::{LimitOrderAuction, OrderStrategy};
+use crate::shared::lrt_rate::LrtRateSource;
 use crate::shared::params::SpotAuctionParams;
 use anyhow::{Error, Result};
-use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, ToPrimitive, Zero};
-use log::{debug, info};
+use bigdecimal::{BigDecimal, FromPrimitive, One, RoundingMode, ToPrimitive, Zero};
 use lyra_client::actions::Direction;
 use lyra_utils::black76::OptionContract;
 use orderbook_types::types::tickers::OptionType;
 use std::cmp::Ordering;
+use tracing::{debug, info, warn};
 impl OrderStrategy for SpotAuctionParams {
     async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal> {
         let market = &auction.market;
@@ -28,7 +29,23 @@ impl OrderStrategy for SpotAuctionParams {
             }
         };
 
-        let spread = self.get_spot_spread(auction.start_timestamp_sec);
+        let mut spread = self.get_spot_spread(auction.start_timestamp_sec);
+        if let Some(pricing) = &self.inventory_pricing {
+            let remaining = match ticker.mark_price.is_zero() {
+                true => zero.clone(),
+                false => (&cash_pos.amount / &ticker.mark_price).abs(),
+            };
+            let levels = pricing.levels as usize;
+            let depth = reader.get_visible_depth(&auction.instrument_name, &direction, levels);
+            let elapsed_sec = chrono::Utc::now().timestamp() - auction.start_timestamp_sec;
+            let progress = elapsed_sec as f64 / auction.auction_sec.max(1) as f64;
+            let inventory_spread = pricing.get_spread(&remaining, depth.clone(), progress);
+            debug!(
+                "SpotAuction remaining {} over depth {:?}, inventory spread {}",
+                remaining, depth, inventory_spread
+            );
+            spread += inventory_spread;
+        }
         let spot = ticker.mark_price.to_f64().ok_or(Error::msg("spot cast to f64 failed"))?;
 
         debug!("SpotAuction spot, spread: {}, {}", spot, spread);
@@ -37,11 +54,18 @@ impl OrderStrategy for SpotAuctionParams {
             Direction::Buy => spot * (1.0 + spread),
             Direction::Sell => spot * (1.0 - spread),
         };
+        let tick_digits = ticker.tick_size.fractional_digit_count();
+        let min_price = ticker.min_price.clone();
+        drop(reader);
 
-        let price = BigDecimal::from_f64(price)
-            .unwrap()
-            .round(ticker.tick_size.fractional_digit_count())
-            .max(ticker.min_price.clone());
+        let price = match self.lrt_rate_band {
+            Some(band) => {
+                bound_to_fair_price(&auction.instrument_name, direction, price, band).await
+            }
+            None => price,
+        };
+
+        let price = BigDecimal::from_f64(price).unwrap().round(tick_digits).max(min_price);
 
         Ok(price)
     }
@@ -49,6 +73,18 @@ impl OrderStrategy for SpotAuctionParams {
         &self,
         auction: &LimitOrderAuction,
         price: &BigDecimal,
+    ) -> Result<(Direction, BigDecimal)> {
+        let (direction, amount) = self.get_remaining_amount(auction, price).await?;
+        Ok((direction, auction.cap_to_depth(&direction, amount).await?))
+    }
+}
+
+impl SpotAuctionParams {
+    /// Amount left to trade for the cash to be within `max_cash`, zero once it is
+    pub async fn get_remaining_amount(
+        &self,
+        auction: &LimitOrderAuction,
+        price: &BigDecimal,
     ) -> Result<(Direction, BigDecimal)> {
         let market = &auction.market;
         let reader = market.read().await;
@@ -86,3 +122,96 @@ impl OrderStrategy for SpotAuctionParams {
         Ok((direction, amount))
     }
 }
+
+/// Spot auction of one of several collaterals sharing an underlying (e.g. weETH and rswETH).
+/// Negative cash is covered by every collateral in proportion to its amount, positive cash
+/// only buys the first (primary) collateral.
+#[derive(Debug, Clone)]
+pub struct CollateralSpotStrategy {
+    pub params: SpotAuctionParams,
+    pub collateral_name: String,
+    pub collateral_names: Vec<String>,
+}
+
+impl CollateralSpotStrategy {
+    fn is_primary(&self) -> bool {
+        self.collateral_names.first() == Some(&self.collateral_name)
+    }
+
+    /// Fraction of the cash balance this collateral's auction trades
+    async fn get_cash_share(&self, auction: &LimitOrderAuction) -> BigDecimal {
+        let reader = auction.market.read().await;
+        let primary_share = match self.is_primary() {
+            true => BigDecimal::one(),
+            false => BigDecimal::zero(),
+        };
+        if reader.get_amount(&self.params.cash_name) >= BigDecimal::zero() {
+            return primary_share;
+        }
+        let total = reader.get_total_amount(&self.collateral_names);
+        if total <= BigDecimal::zero() {
+            return primary_share;
+        }
+        reader.get_amount(&self.collateral_name) / total
+    }
+}
+
+/// Keeps the price within `band` of the LRT's fair price from its on-chain exchange rate, i.e.
+/// buys at most at `fair * (1 + band)` and sells at least at `fair * (1 - band)`. The price is
+/// left as is if the LRT has no rate source or the rate is unavailable.
+async fn bound_to_fair_price(
+    instrument_name: &str,
+    direction: Direction,
+    price: f64,
+    band: f64,
+) -> f64 {
+    let lrt_name = instrument_name.split('-').next().unwrap_or_default();
+    let fair_price = match LrtRateSource::from_env(lrt_name) {
+        Ok(Some(source)) => source.get_fair_price().await,
+        Ok(None) => return price,
+        Err(e) => Err(e),
+    };
+    let fair_price = match fair_price.map(|p| p.to_f64()) {
+        Ok(Some(fair_price)) => fair_price,
+        Ok(None) => return price,
+        Err(e) => {
+            warn!("Spot auction not bounded, no fair price of {}: {:#}", lrt_name, e);
+            return price;
+        }
+    };
+    let bounded = match direction {
+        Direction::Buy => price.min(fair_price * (1.0 + band)),
+        Direction::Sell => price.max(fair_price * (1.0 - band)),
+    };
+    if bounded != price {
+        info!("{} price {} bounded to {} by fair price {}", lrt_name, price, bounded, fair_price);
+    }
+    bounded
+}
+
+impl OrderStrategy for CollateralSpotStrategy {
+    async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal> {
+        if self.get_cash_share(auction).await.is_zero() {
+            return Ok(BigDecimal::zero());
+        }
+        self.params.get_desired_price(auction).await
+    }
+    async fn get_desired_amount(
+        &self,
+        auction: &LimitOrderAuction,
+        price: &BigDecimal,
+    ) -> Result<(Direction, BigDecimal)> {
+        let (direction, amount) = self.params.get_remaining_amount(auction, price).await?;
+        let share = self.get_cash_share(auction).await;
+        let reader = auction.market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let amount = (amount * share)
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down);
+        if amount < ticker.minimum_amount {
+            return Ok((Direction::Sell, BigDecimal::zero()));
+        }
+        drop(reader);
+        Ok((direction, auction.cap_to_depth(&direction, amount).await?))
+    }
+}
@@ -1,19 +1,36 @@
 use crate::helpers::{get_option_expiry, sync_subaccount};
 use crate::lrtc::params::LRTCParams;
-use crate::lrtc::selector::maybe_select_from_positions;
+use crate::lrtc::roll::ITMRollParams;
+use crate::lrtc::selector::{get_moneyness, maybe_select_from_positions};
 use crate::market::new_market_state;
-use crate::shared::auction::{LimitOrderAuctionExecutor, OrderStrategy};
+use crate::shared::alert::send_alert;
+use crate::shared::auction::{LimitOrderAuctionExecutor, MultiAuctionExecutor, OrderStrategy};
+use crate::shared::cash_yield::{swap_idle_cash, CashYieldParams};
+use crate::shared::context::VaultContext;
+use crate::shared::dry_run::DryRunRecorder;
+use crate::shared::hedger::{DeltaHedger, HedgerParams};
+use crate::shared::instruments::{wait_for_event, InstrumentEvent};
 use crate::shared::rfq::{RFQAuctionExecutor, RFQStrategy};
+use crate::shared::schedule::StageSchedule;
+use crate::shared::settlement::{verify_settlement, SettlementCheckParams, SettlementSnapshot};
+use crate::shared::withdrawals::update_withdrawal_reserve;
 use crate::web3::{
     get_tsa_contract, process_deposits_forever, process_deposits_once, process_withdrawals,
     ProviderWithSigner, TSA,
 };
 use anyhow::{Error, Result};
 use bigdecimal::{BigDecimal, Zero};
-use log::{error, info, warn};
+use futures::future::try_join_all;
+use lyra_client::actions::Direction;
+use lyra_client::error::LyraError;
 use lyra_client::json_rpc::{WsClient, WsClientExt};
+use lyra_client::tickers::TickerCache;
+use std::collections::HashSet;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use tokio::select;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
 
 pub trait ExecutorStage
 where
@@ -21,6 +38,8 @@ where
 {
     async fn run(&self) -> anyhow::Result<()>;
     async fn reconnect(&mut self) -> anyhow::Result<()>;
+    /// Short name for status reporting and order labels, e.g. the variant of an executor stage
+    fn name(&self) -> String;
     async fn reconnect_with_backoff(&mut self) -> anyhow::Result<()> {
         let mut backoff = 4;
         let max_backoff = 64;
@@ -29,45 +48,80 @@ where
             if res.is_ok() {
                 return Ok(());
             }
-            error!("{:#?} reconnect failed with {:#?}, reconnecting in {}", self, res, backoff);
+            error!(stage = ?self, result = ?res, backoff, "Stage reconnect failed, reconnecting");
             tokio::time::sleep(tokio::time::Duration::from_secs(backoff)).await;
             backoff = (backoff * 2).min(max_backoff);
         }
     }
+    /// Reconnects and reruns the stage on failure, except for client errors that would only
+    /// repeat (e.g. insufficient funds), which abort the stage
     async fn run_with_reconnect(&mut self) -> anyhow::Result<()> {
         loop {
             let res = self.run().await;
-            if res.is_ok() {
+            let Err(e) = res else {
                 return Ok(());
+            };
+            error!(stage = ?self, error = format!("{:#}", e), "Stage run failed");
+            if let Some(lyra_error) = e.downcast_ref::<LyraError>() {
+                if !lyra_error.is_retryable() {
+                    error!(stage = ?self, error = %lyra_error, "Stage aborted, not retryable");
+                    return Err(e);
+                }
             }
-            error!("{:#?} run failed with {:#?}", self, res);
             self.reconnect_with_backoff().await?;
         }
     }
 }
 
 impl<S: OrderStrategy + Debug> ExecutorStage for LimitOrderAuctionExecutor<S> {
+    fn name(&self) -> String {
+        "LimitOrderAuctionExecutor".to_string()
+    }
     async fn run(&self) -> anyhow::Result<()> {
         let market_task = self.run_market();
         let auction_task = self.run_auction();
-        let ping_task = self.auction.client.ping_interval(15);
+        let ping_task = self.auction.client()?.ping_interval(15);
         let res = select! {
             _ = market_task => {Err(Error::msg("Market task exited early"))},
             _ = ping_task => {Err(Error::msg("Ping task exited early"))},
             auction_res = auction_task => { auction_res },
         };
+        if res.is_ok() {
+            self.report_execution().await;
+        }
         res
     }
     async fn reconnect(&mut self) -> anyhow::Result<()> {
         self.auction.market = new_market_state();
-        self.auction.client = WsClient::new_client().await?;
-        self.auction.client.login().await?;
-        self.auction.client.enable_cancel_on_disconnect().await?;
+        let client = WsClient::new_client().await?;
+        client.login().await?;
+        self.auction.client = Some(client);
+        Ok(())
+    }
+}
+
+/// Completes once every auction has completed, any failure restarts all of them.
+/// Completed auctions return right away on restart since their remaining amount is zero.
+impl<S: OrderStrategy + Debug> ExecutorStage for MultiAuctionExecutor<S> {
+    fn name(&self) -> String {
+        "MultiAuctionExecutor".to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        try_join_all(self.executors.iter().map(|e| e.run())).await?;
+        Ok(())
+    }
+    async fn reconnect(&mut self) -> Result<()> {
+        for executor in self.executors.iter_mut() {
+            executor.reconnect().await?;
+        }
         Ok(())
     }
 }
 
 impl<S: RFQStrategy + Debug> ExecutorStage for RFQAuctionExecutor<S> {
+    fn name(&self) -> String {
+        "RFQAuctionExecutor".to_string()
+    }
     async fn run(&self) -> Result<()> {
         let remain_sec = self.auction.remain_sec();
         if remain_sec <= 0 {
@@ -101,35 +155,103 @@ impl<S: RFQStrategy + Debug> ExecutorStage for RFQAuctionExecutor<S> {
 #[derive(Debug)]
 pub struct TSACollateralOnly {
     pub tsa: TSA<ProviderWithSigner>,
+    pub dry_run: Option<DryRunRecorder>,
 }
 
 impl TSACollateralOnly {
     pub async fn new() -> Result<Self> {
         info!("Starting TSASpotOnly Stage");
-        let vault_name = std::env::var("VAULT_NAME").unwrap();
+        let vault_name = VaultContext::current().vault_name.clone();
         let tsa = get_tsa_contract(&vault_name, "SESSION").await?;
-        Ok(Self { tsa })
+        Ok(Self { tsa, dry_run: None })
     }
 }
 
 impl ExecutorStage for TSACollateralOnly {
+    fn name(&self) -> String {
+        "TSACollateralOnly".to_string()
+    }
     async fn run(&self) -> Result<()> {
         // todo might wanna rename the env to COLLATERAL_NAME for clarity
-        let asset_name = std::env::var("SPOT_NAME").unwrap();
+        let asset_name = VaultContext::current().spot_name.clone();
+        if let Some(dry_run) = &self.dry_run {
+            let payload = serde_json::json!({ "asset_name": asset_name });
+            return dry_run.record("process_deposits_and_withdrawals", payload).await;
+        }
         process_deposits_once(&self.tsa, asset_name.clone()).await?;
         process_withdrawals(&self.tsa, asset_name.clone()).await?;
         process_deposits_once(&self.tsa, asset_name.clone()).await?;
+        update_withdrawal_reserve(&self.tsa, &asset_name).await?;
         Ok(())
     }
     async fn reconnect(&mut self) -> Result<()> {
-        let vault_name = std::env::var("VAULT_NAME").unwrap();
+        let vault_name = VaultContext::current().vault_name.clone();
+        self.tsa = get_tsa_contract(&vault_name, "SESSION").await?;
+        Ok(())
+    }
+}
+
+/// - This stage returns funds to withdrawing depositors once the vault has no options open.
+/// - Initiates a signed withdrawal of the collateral from the orderbook to the TSA on L1,
+/// waits for it to settle and then processes the queued withdrawal requests on-chain in
+/// batches (see `process_withdrawal_requests`).
+/// - Completes once no withdrawal requests are pending or the TSA balance ran out, in which case
+/// the collateral still owed is reserved from the next option auction.
+#[derive(Debug)]
+pub struct TSAWithdrawals {
+    pub tsa: TSA<ProviderWithSigner>,
+    pub dry_run: Option<DryRunRecorder>,
+    pub before_auction: bool, // entered right before the option auction, which follows it
+    pub before_roll: Option<String>, // short call rolled right after it instead of the auction
+}
+
+impl TSAWithdrawals {
+    pub async fn new() -> Result<Self> {
+        info!("Starting TSAWithdrawals Stage");
+        let vault_name = VaultContext::current().vault_name.clone();
+        let tsa = get_tsa_contract(&vault_name, "SESSION").await?;
+        Ok(Self { tsa, dry_run: None, before_auction: false, before_roll: None })
+    }
+
+    /// True if the vault has withdrawal requests queued that need funds returned
+    pub async fn is_needed() -> Result<bool> {
+        let vault_name = VaultContext::current().vault_name.clone();
+        let tsa = get_tsa_contract(&vault_name, "SESSION").await?;
+        Ok(!tsa.total_pending_withdrawals().call().await?.is_zero())
+    }
+}
+
+impl ExecutorStage for TSAWithdrawals {
+    fn name(&self) -> String {
+        "TSAWithdrawals".to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        let asset_name = VaultContext::current().spot_name.clone();
+        if let Some(dry_run) = &self.dry_run {
+            let payload = serde_json::json!({ "asset_name": asset_name });
+            return dry_run.record("process_withdrawals", payload).await;
+        }
+        process_withdrawals(&self.tsa, asset_name.clone()).await?;
+        update_withdrawal_reserve(&self.tsa, &asset_name).await?;
+        Ok(())
+    }
+    async fn reconnect(&mut self) -> Result<()> {
+        let vault_name = VaultContext::current().vault_name.clone();
         self.tsa = get_tsa_contract(&vault_name, "SESSION").await?;
         Ok(())
     }
 }
 
 /// - This stage will wait for the options to be settled.
-/// - Assumes all options have the same expiry.
+/// - With multiple expiries (e.g. an option ladder) it waits for the latest one.
+/// - With an instrument watcher the wait is cut short once all options are delisted, and the
+/// pre-expiry snapshot is refreshed once they are about to expire.
+/// - Once settled, the settlement is cross-checked with the on-chain settlement feed and
+/// discrepancies are alerted before moving on to the next epoch.
+/// - With cash yield, the idle cash is swapped into the wrapper once settled and unwound right
+/// before the spot auction.
+/// - With an ITM roll, the stage completes early once the short call is deep in the money
+/// within the roll window (see `roll_requested`).
 #[derive(Debug)]
 pub struct TSAWaitForSettlement {
     pub subaccount_id: i64,
@@ -137,15 +259,66 @@ pub struct TSAWaitForSettlement {
     pub option_names: Vec<String>,
     pub option_expiry: i64,
     pub delay_min: i64,
+    /// Starts the spot auction at its scheduled run after expiry instead of after the delay
+    pub schedule: StageSchedule,
+    pub dry_run: Option<DryRunRecorder>,
+    /// Keeps the portfolio delta hedged in the perp while waiting
+    pub hedger: Option<HedgerParams>,
+    pub settlement_check: SettlementCheckParams,
+    /// Completes the stage early once the collateral deposited while waiting reaches this size,
+    /// so it can be sold against (see `topup_requested`). None leaves deposits idle till expiry.
+    pub min_topup_size: Option<BigDecimal>,
+    pub topup_until_sec: i64, // No top-ups past this time, e.g. too close to expiry to auction
+    pub cash_yield: Option<CashYieldParams>,
+    pub itm_roll: Option<ITMRollParams>, // Checked at every heartbeat for a single short call
+    /// Positions and cash before expiry, refreshed on every heartbeat until expiry
+    snapshot: Mutex<Option<SettlementSnapshot>>,
+    topup_requested: AtomicBool,
+    roll_requested: AtomicBool,
+    cash_deposited: AtomicBool,
+    delisted: Mutex<HashSet<String>>,
+    delisted_sec: AtomicI64, // Once all options are delisted, zero until then
 }
 
 impl TSAWaitForSettlement {
     pub async fn new(delay_min: i64, option_names: Vec<String>) -> Result<Self> {
-        let subaccount_id = std::env::var("SUBACCOUNT_ID").unwrap().parse().unwrap();
-        let vault_name = std::env::var("VAULT_NAME").unwrap();
+        // the options settle in the option book
+        let subaccount_id = VaultContext::current().option_subaccount_id()?;
+        let vault_name = VaultContext::current().vault_name.clone();
         let tsa = get_tsa_contract(&vault_name, "SESSION").await?;
-        let option_expiry = get_option_expiry(&option_names[0]).await?;
-        Ok(Self { subaccount_id, tsa, option_names, option_expiry, delay_min })
+        let mut option_expiry = 0;
+        for option_name in option_names.iter() {
+            option_expiry = option_expiry.max(get_option_expiry(option_name).await?);
+        }
+        Ok(Self {
+            subaccount_id,
+            tsa,
+            option_names,
+            option_expiry,
+            delay_min,
+            schedule: StageSchedule::default(),
+            dry_run: None,
+            hedger: None,
+            settlement_check: SettlementCheckParams::default(),
+            min_topup_size: None,
+            topup_until_sec: option_expiry,
+            cash_yield: None,
+            itm_roll: None,
+            snapshot: Mutex::new(None),
+            topup_requested: AtomicBool::new(false),
+            roll_requested: AtomicBool::new(false),
+            cash_deposited: AtomicBool::new(false),
+            delisted: Mutex::new(HashSet::new()),
+            delisted_sec: AtomicI64::new(0),
+        })
+    }
+    /// True if the stage completed for a top-up before the options settled
+    pub fn topup_requested(&self) -> bool {
+        self.topup_requested.load(Ordering::Relaxed)
+    }
+    /// True if the stage completed to roll the deep in-the-money short call before expiry
+    pub fn roll_requested(&self) -> bool {
+        self.roll_requested.load(Ordering::Relaxed)
     }
     pub async fn is_settled(&self) -> Result<bool> {
         // todo some of these might be cleaner to just use get_subaccount over REST...
@@ -160,17 +333,42 @@ impl TSAWaitForSettlement {
         let all_settled = option_positions.into_iter().all(|p| p.amount == zero);
         return Ok(all_settled);
     }
+    /// The expiry, or the time the options were delisted if that came first
+    fn effective_expiry(&self) -> i64 {
+        match self.delisted_sec.load(Ordering::Relaxed) {
+            0 => self.option_expiry,
+            delisted_sec => delisted_sec.min(self.option_expiry),
+        }
+    }
+    /// Until the spot auction, or the unwind of the cash yield before it
     fn sec_to_auction(&self) -> i64 {
-        let sec_to_expiry = self.option_expiry - chrono::Utc::now().timestamp();
-        let sec_to_auction = sec_to_expiry + self.delay_min * 60;
-        sec_to_auction
+        let auction_sec =
+            self.schedule.spot_auction_start(self.effective_expiry(), self.delay_min * 60);
+        let lead_sec = self.cash_yield.as_ref().map_or(0, |c| c.unwind_lead_sec());
+        auction_sec - lead_sec - chrono::Utc::now().timestamp()
     }
     async fn wait_for_auction(&self) -> Result<()> {
         let heartbeat_sec = 600;
         let mut sleep_sec = self.sec_to_auction().min(heartbeat_sec);
         while sleep_sec > 0 {
-            info!("AwaitSettlement heartbeat, {} seconds till auction", self.sec_to_auction());
-            tokio::time::sleep(tokio::time::Duration::from_secs(sleep_sec as u64)).await;
+            info!(sec_to_auction = self.sec_to_auction(), "AwaitSettlement heartbeat");
+            self.take_snapshot().await;
+            self.deposit_idle_cash().await;
+            if self.check_itm_roll().await {
+                return Ok(());
+            }
+            // the deposit auction may have run into the time to the auction
+            sleep_sec = self.sec_to_auction().min(heartbeat_sec);
+            if sleep_sec <= 0 {
+                break;
+            }
+            let feed = VaultContext::current().instrument_events.clone();
+            select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(sleep_sec as u64)) => {}
+                event = wait_for_event(feed.as_deref(), &self.option_names) => {
+                    self.on_instrument_event(event).await
+                }
+            }
             sleep_sec = self.sec_to_auction().min(heartbeat_sec);
         }
         loop {
@@ -181,23 +379,197 @@ impl TSAWaitForSettlement {
             tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
         }
     }
-}
 
-impl ExecutorStage for TSAWaitForSettlement {
-    async fn run(&self) -> Result<()> {
-        let wait_task = self.wait_for_auction();
-        let asset_name = std::env::var("SPOT_NAME").unwrap();
-        let deposit_task = process_deposits_forever(&self.tsa, asset_name);
+    async fn on_instrument_event(&self, event: InstrumentEvent) {
+        let InstrumentEvent::Delisted(instrument_name) = event else {
+            return;
+        };
+        let mut delisted = self.delisted.lock().await;
+        delisted.insert(instrument_name);
+        if self.option_names.iter().all(|name| delisted.contains(name)) {
+            info!(options = ?self.option_names, "AwaitSettlement options delisted");
+            let now = chrono::Utc::now().timestamp();
+            self.delisted_sec.store(now, Ordering::Relaxed);
+        }
+    }
+
+    /// Swaps the idle cash into the yield wrapper once, after the options settled. Failures
+    /// leave the cash idle, and so does a window too short for the deposit and its unwind.
+    async fn deposit_idle_cash(&self) {
+        let Some(cash_yield) = &self.cash_yield else {
+            return;
+        };
+        if self.cash_deposited.load(Ordering::Relaxed)
+            || chrono::Utc::now().timestamp() < self.effective_expiry()
+        {
+            return;
+        }
+        let sec_to_auction = self.sec_to_auction();
+        if sec_to_auction < 2 * cash_yield.auction_sec {
+            info!(sec_to_auction, "Too close to the auction to deposit the idle cash");
+            self.cash_deposited.store(true, Ordering::Relaxed);
+            return;
+        }
+        match self.is_settled().await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                warn!(error = format!("{:#}", e), "Failed to check the settlement");
+                return;
+            }
+        }
+        self.cash_deposited.store(true, Ordering::Relaxed);
+        let cash_name = VaultContext::current().cash_name.clone();
+        let res = swap_idle_cash(cash_yield, &cash_name, Direction::Buy, self.dry_run.clone());
+        if let Err(e) = res.await {
+            send_alert(&format!("Cash yield deposit failed, cash left idle: {:#}", e)).await;
+        }
+    }
+
+    /// Requests the roll if the short call is at or below the trigger moneyness within the
+    /// roll window. Failures to fetch the ticker are retried at the next heartbeat.
+    async fn check_itm_roll(&self) -> bool {
+        let Some(itm_roll) = &self.itm_roll else {
+            return false;
+        };
+        let now = chrono::Utc::now().timestamp();
+        if self.option_names.len() != 1 || !itm_roll.is_in_window(self.option_expiry, now) {
+            return false;
+        }
+        let option_name = &self.option_names[0];
+        let ticker = match TickerCache::global().get(option_name).await {
+            Ok(ticker) => ticker,
+            Err(e) => {
+                let error = format!("{:#}", e);
+                warn!(instrument = %option_name, error, "Failed to fetch the ticker");
+                return false;
+            }
+        };
+        let Some(moneyness) = get_moneyness(&ticker) else {
+            return false;
+        };
+        if !itm_roll.is_triggered(&moneyness) {
+            info!(instrument = %option_name, %moneyness, "Moneyness above the roll trigger");
+            return false;
+        }
+        warn!(instrument = %option_name, %moneyness, "Option deep in the money, rolling");
+        self.roll_requested.store(true, Ordering::Relaxed);
+        true
+    }
+
+    async fn take_snapshot(&self) {
+        if chrono::Utc::now().timestamp() >= self.effective_expiry() {
+            return;
+        }
+        match SettlementSnapshot::take(self.subaccount_id, &self.option_names).await {
+            Ok(snapshot) => *self.snapshot.lock().await = Some(snapshot),
+            Err(e) => {
+                warn!(error = format!("{:#}", e), "Failed to snapshot positions before expiry")
+            }
+        }
+    }
+
+    /// Discrepancies are alerted but do not hold the vault. Perp PnL of the hedger also lands
+    /// in cash, so the received cash is only checked without one.
+    async fn check_settlement(&self) {
+        let snapshot = self.snapshot.lock().await.clone();
+        let res = verify_settlement(
+            self.subaccount_id,
+            &self.option_names,
+            snapshot.as_ref(),
+            self.hedger.is_none(),
+            &self.settlement_check,
+        )
+        .await;
+        match res {
+            Ok(discrepancies) if discrepancies.is_empty() => {
+                info!(options = ?self.option_names, "Settlement verified on-chain")
+            }
+            Ok(discrepancies) => {
+                let message = format!("Settlement discrepancies: {}", discrepancies.join(", "));
+                send_alert(&message).await
+            }
+            Err(e) => send_alert(&format!("Failed to verify the settlement with {:#}", e)).await,
+        }
+    }
+
+    async fn wait_with_hedge(&self, hedger: Option<&DeltaHedger>) -> Result<()> {
+        let hedger = match hedger {
+            Some(hedger) => hedger,
+            None => return self.wait_for_auction().await,
+        };
+        select! {
+            w = self.wait_for_auction() => w,
+            h = hedger.run() => {
+                error!(result = ?h, "Hedger task unexpected early exit");
+                Err(Error::msg("Hedger task unexpected early exit"))
+            }
+        }
+    }
+
+    async fn wait_and_deposit(&self, hedger: Option<&DeltaHedger>) -> Result<()> {
+        let wait_task = self.wait_with_hedge(hedger);
+        if self.dry_run.is_some() {
+            return wait_task.await;
+        }
+        let asset_name = VaultContext::current().spot_name.clone();
+        let deposit_task = self.deposit_until_topup(asset_name);
         select! {
             w = wait_task => w,
-            d = deposit_task => {
-                error!("Deposit task unexpected early exit with {:#?}", d);
-                Err(Error::msg("Deposit task unexpected early exit"))
+            d = deposit_task => match d {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    error!(result = ?e, "Deposit task unexpected early exit");
+                    Err(Error::msg("Deposit task unexpected early exit"))
+                }
+            }
+        }
+    }
+
+    /// Processes deposits, only returns once the deposited collateral reaches `min_topup_size`
+    async fn deposit_until_topup(&self, asset_name: String) -> Result<()> {
+        let Some(min_topup_size) = &self.min_topup_size else {
+            return process_deposits_forever(&self.tsa, asset_name).await;
+        };
+        let mut deposited = BigDecimal::zero();
+        loop {
+            deposited += process_deposits_once(&self.tsa, asset_name.clone()).await?;
+            let is_topup_open = chrono::Utc::now().timestamp() < self.topup_until_sec;
+            if is_topup_open && &deposited >= min_topup_size {
+                info!(%deposited, asset = %asset_name, "Deposited mid-epoch, starting a top-up");
+                self.topup_requested.store(true, Ordering::Relaxed);
+                return Ok(());
             }
         }
     }
+}
+
+impl ExecutorStage for TSAWaitForSettlement {
+    fn name(&self) -> String {
+        "TSAWaitForSettlement".to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        let hedger = match &self.hedger {
+            Some(params) => Some(DeltaHedger::new(params.clone(), self.dry_run.clone()).await?),
+            None => None,
+        };
+        let res = self.wait_and_deposit(hedger.as_ref()).await;
+        if let Some(hedger) = &hedger {
+            hedger.cancel_hedge().await?;
+        }
+        res?;
+        if self.topup_requested() || self.roll_requested() {
+            return Ok(());
+        }
+        if let Some(cash_yield) = &self.cash_yield {
+            let cash_name = VaultContext::current().cash_name.clone();
+            swap_idle_cash(cash_yield, &cash_name, Direction::Sell, self.dry_run.clone()).await?;
+        }
+        self.check_settlement().await;
+        Ok(())
+    }
     async fn reconnect(&mut self) -> anyhow::Result<()> {
-        let vault_name = std::env::var("VAULT_NAME").unwrap();
+        let vault_name = VaultContext::current().vault_name.clone();
         self.tsa = get_tsa_contract(&vault_name, "SESSION").await?;
         Ok(())
     }
@@ -0,0 +1,124 @@
+use crate::shared::context::VaultContext;
+use anyhow::Result;
+use lyra_client::json_rpc::{WsClient, WsClientExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Snapshot of the executor state written on every stage transition and order update.
+/// Used to resume the executor after a crash without re-inferring everything from positions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutorSnapshot {
+    pub stage: String,
+    pub instrument_name: Option<String>,
+    pub auction_start_sec: Option<i64>,
+    #[serde(default)]
+    pub mini_auction: Option<u32>, // of the epoch, for option auctions split by their sizing
+    #[serde(default)]
+    pub open_orders: BTreeMap<String, Vec<String>>, // order ids by instrument
+    pub timestamp: i64,
+}
+
+impl ExecutorSnapshot {
+    pub fn new(
+        stage: &str,
+        instrument_name: Option<String>,
+        auction_start_sec: Option<i64>,
+    ) -> Self {
+        Self {
+            stage: stage.to_string(),
+            instrument_name,
+            auction_start_sec,
+            mini_auction: None,
+            open_orders: BTreeMap::new(),
+            timestamp: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+/// JSON snapshot store, one file per vault under STATE_DIR (defaults to ./state)
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    path: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(vault_name: &str) -> Self {
+        let dir = std::env::var("STATE_DIR").unwrap_or("./state".to_string());
+        let path = PathBuf::from(dir).join(format!("{}.json", vault_name.to_lowercase()));
+        Self { path }
+    }
+
+    pub async fn load(&self) -> Result<Option<ExecutorSnapshot>> {
+        if !tokio::fs::try_exists(&self.path).await? {
+            return Ok(None);
+        }
+        let contents = tokio::fs::read_to_string(&self.path).await?;
+        match serde_json::from_str::<ExecutorSnapshot>(&contents) {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(e) => {
+                warn!("Ignoring corrupt executor state at {:?}: {:?}", self.path, e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Writes to a temp file first so that a crash mid-write never leaves a partial snapshot
+    pub async fn save(&self, snapshot: &ExecutorSnapshot) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_string_pretty(snapshot)?).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+
+    /// Records the open orders of an instrument, an empty list once they are cancelled
+    pub async fn set_open_orders(
+        &self,
+        instrument_name: &str,
+        open_order_ids: Vec<String>,
+    ) -> Result<()> {
+        let snapshot = self.load().await?;
+        match snapshot {
+            Some(mut snapshot) => {
+                match open_order_ids.is_empty() {
+                    true => snapshot.open_orders.remove(instrument_name),
+                    false => {
+                        snapshot.open_orders.insert(instrument_name.to_string(), open_order_ids)
+                    }
+                };
+                snapshot.timestamp = chrono::Utc::now().timestamp();
+                self.save(&snapshot).await
+            }
+            None => {
+                warn!("Cannot record open orders {:?} without a stored stage", open_order_ids);
+                Ok(())
+            }
+        }
+    }
+
+    /// Cancels the orders a previous run left open, e.g. after a crash mid auction, so that a
+    /// resumed auction never has them fill next to its own orders
+    pub async fn cancel_stored_orders(&self) -> Result<()> {
+        let Some(snapshot) = self.load().await? else {
+            return Ok(());
+        };
+        if snapshot.open_orders.is_empty() {
+            return Ok(());
+        }
+        let ctx = VaultContext::current();
+        let client = WsClient::new_client().await?;
+        client.login().await?.into_result()?;
+        for (instrument_name, order_ids) in snapshot.open_orders.iter() {
+            let (subaccount_id, _) = ctx.stage_subaccount_ids(&snapshot.stage, instrument_name)?;
+            let res = client.cancel_by_instrument(subaccount_id, instrument_name.clone()).await?;
+            let cancelled = res.into_result()?.result.cancelled_orders;
+            info!("Cancelled {} stored orders {:?} of {}", cancelled, order_ids, instrument_name);
+            self.set_open_orders(instrument_name, vec![]).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use crate::market::Candle;
+use anyhow::{Error, Result};
+use bigdecimal::ToPrimitive;
+use lyra_client::json_rpc::http_rpc;
+use lyra_utils::black76::SEC_PER_YEAR;
+use orderbook_types::generated::public_get_spot_feed_history::{
+    PublicGetSpotFeedHistoryParamsSchema, PublicGetSpotFeedHistoryResponseSchema,
+    SpotFeedHistoryResponseSchema,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Init IV spread of option auctions derived from the IV-RV spread, see `VolStats`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RealizedVolParams {
+    pub lookback_days: u64, // Spot feed history the realized vol is computed over
+    #[serde(default = "default_period_sec")]
+    pub period_sec: i64, // Sampling period of the spot feed, e.g. 3600 for hourly returns
+    pub iv_rv_fraction: f64, // Init IV spread as a fraction of the IV-RV spread
+}
+
+fn default_period_sec() -> i64 {
+    3600
+}
+
+impl RealizedVolParams {
+    pub fn lookback_sec(&self) -> i64 {
+        self.lookback_days as i64 * 86400
+    }
+}
+
+/// Realized vol of a currency's spot feed, compared with an option's implied vol
+#[derive(Debug, Clone, Serialize)]
+pub struct VolStats {
+    pub currency: String,
+    pub lookback_sec: i64,
+    pub period_sec: i64,
+    pub num_returns: usize,
+    pub realized_vol: f64, // annualized, from the log returns of the spot feed
+    pub implied_vol: Option<f64>, // e.g. the mark IV of the option sold
+    pub iv_rv_spread: Option<f64>, // 1 - RV / IV, positive while options are rich
+}
+
+impl VolStats {
+    pub async fn fetch(
+        currency: &str,
+        lookback_sec: i64,
+        period_sec: i64,
+        implied_vol: Option<f64>,
+    ) -> Result<Self> {
+        let end_sec = chrono::Utc::now().timestamp();
+        let history =
+            get_spot_history(currency, end_sec - lookback_sec, end_sec, period_sec).await?;
+        let prices = history.iter().filter_map(|p| p.price.to_f64()).collect::<Vec<_>>();
+        let realized_vol = realized_vol(&prices, period_sec).ok_or(Error::msg(format!(
+            "Not enough {} spot feed history for realized vol: {} prices",
+            currency,
+            prices.len()
+        )))?;
+        let iv_rv_spread = implied_vol.filter(|iv| *iv > 0.0).map(|iv| 1.0 - realized_vol / iv);
+        let stats = Self {
+            currency: currency.to_string(),
+            lookback_sec,
+            period_sec,
+            num_returns: prices.len() - 1,
+            realized_vol,
+            implied_vol,
+            iv_rv_spread,
+        };
+        info!("Vol stats: {:?}", stats);
+        Ok(stats)
+    }
+}
+
+/// Spot feed prices of the currency sampled every `period_sec`, oldest first
+pub async fn get_spot_history(
+    currency: &str,
+    start_sec: i64,
+    end_sec: i64,
+    period_sec: i64,
+) -> Result<Vec<SpotFeedHistoryResponseSchema>> {
+    let params = PublicGetSpotFeedHistoryParamsSchema {
+        currency: currency.to_string(),
+        start_timestamp: start_sec,
+        end_timestamp: end_sec,
+        period: period_sec,
+    };
+    let mut history = http_rpc::<_, PublicGetSpotFeedHistoryResponseSchema>(
+        "public/get_spot_feed_history",
+        params,
+        None,
+    )
+    .await?
+    .into_result()?
+    .result
+    .spot_feed_history;
+    history.sort_by_key(|p| p.timestamp);
+    Ok(history)
+}
+
+/// Annualized standard deviation of the log returns of prices sampled every `period_sec`,
+/// None with fewer than two returns or non-positive prices
+pub fn realized_vol(prices: &[f64], period_sec: i64) -> Option<f64> {
+    if prices.iter().any(|p| *p <= 0.0) || period_sec <= 0 {
+        return None;
+    }
+    let returns = prices.windows(2).map(|w| (w[1] / w[0]).ln()).collect::<Vec<_>>();
+    if returns.len() < 2 {
+        return None;
+    }
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    Some((variance * SEC_PER_YEAR / period_sec as f64).sqrt())
+}
+
+/// Annualized realized vol of the candle closes, e.g. while an auction was quoting. Candles
+/// must be consecutive for the returns to be per interval, so a gap in trading returns None.
+pub fn candle_realized_vol(candles: &[Candle], interval_sec: i64) -> Option<f64> {
+    let interval_ms = interval_sec * 1000;
+    if candles.windows(2).any(|w| w[1].start_ms - w[0].start_ms != interval_ms) {
+        return None;
+    }
+    let closes = candles.iter().filter_map(|c| c.close.to_f64()).collect::<Vec<_>>();
+    realized_vol(&closes, interval_sec)
+}
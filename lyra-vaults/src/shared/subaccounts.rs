@@ -0,0 +1,76 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Subaccounts of the vault TSA the stages trade from besides the TSA subaccount the deposits
+/// land in, e.g. to keep the option book apart from the spot inventory. Stages listed in
+/// `stage_subaccount_ids` send all of their orders from that subaccount, the others are routed
+/// by instrument: options and perps to `option_subaccount_id`, spot pairs (e.g. WEETH-USDC) to
+/// `spot_subaccount_id`, each falling back to the TSA subaccount if unset. Moving collateral
+/// between the subaccounts (e.g. margin for the option book) is left to the operator.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SubaccountParams {
+    pub option_subaccount_id: Option<i64>,
+    pub spot_subaccount_id: Option<i64>,
+    // Subaccount by stage name, e.g. {"SpotAuction": 3}, ahead of the routing by instrument
+    #[serde(default)]
+    pub stage_subaccount_ids: BTreeMap<String, i64>,
+}
+
+impl SubaccountParams {
+    /// The TSA subaccount first, followed by the routed ones
+    pub fn all_ids(&self, tsa_subaccount_id: i64) -> Vec<i64> {
+        let mut ids = vec![tsa_subaccount_id];
+        let routed = [self.option_subaccount_id, self.spot_subaccount_id].into_iter().flatten();
+        for id in routed.chain(self.stage_subaccount_ids.values().copied()) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    pub fn option_id(&self, tsa_subaccount_id: i64) -> i64 {
+        self.option_subaccount_id.unwrap_or(tsa_subaccount_id)
+    }
+
+    /// Subaccount the orders of the instrument are sent from
+    pub fn order_id(&self, tsa_subaccount_id: i64, instrument_name: &str) -> i64 {
+        match is_spot_instrument(instrument_name) {
+            true => self.spot_subaccount_id.unwrap_or(tsa_subaccount_id),
+            false => self.option_id(tsa_subaccount_id),
+        }
+    }
+
+    /// Subaccount the stage sends all of its orders from, None if it is routed by instrument
+    pub fn stage_id(&self, stage: &str) -> Option<i64> {
+        self.stage_subaccount_ids.get(stage).copied()
+    }
+
+    /// Subaccounts synced into the market state of an auction of the instrument
+    pub fn synced_ids(&self, tsa_subaccount_id: i64, instrument_name: &str) -> Vec<i64> {
+        let order_id = self.order_id(tsa_subaccount_id, instrument_name);
+        self.synced_ids_from(tsa_subaccount_id, order_id, instrument_name)
+    }
+
+    /// Subaccounts synced into the market state of an auction of the instrument sent from
+    /// `order_id`. Options and spot pairs are sized against the collateral of the subaccount
+    /// they are sold from, perps hedge the positions of the whole vault.
+    pub fn synced_ids_from(
+        &self,
+        tsa_subaccount_id: i64,
+        order_id: i64,
+        instrument_name: &str,
+    ) -> Vec<i64> {
+        let mut ids = vec![order_id];
+        if instrument_name.ends_with("-PERP") {
+            let others = self.all_ids(tsa_subaccount_id).into_iter().filter(|id| *id != order_id);
+            ids.extend(others);
+        }
+        ids
+    }
+}
+
+/// Spot pairs are named like WEETH-USDC, perps like ETH-PERP and options like ETH-20240628-3000-C
+fn is_spot_instrument(instrument_name: &str) -> bool {
+    !instrument_name.ends_with("-PERP") && instrument_name.split('-').count() == 2
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use std::future::Future;
+use tracing::{error, info};
+
+const MIN_RESTART_BACKOFF_SEC: u64 = 5;
+const MAX_RESTART_BACKOFF_SEC: u64 = 600;
+/// A run that lasted this long counts as healthy and resets the backoff
+const HEALTHY_RUN_SEC: i64 = 3600;
+
+/// Runs `make_run()` forever, restarting it with exponential backoff whenever it fails.
+/// Restarts go through the executor's `initial_stage`, i.e. resume from positions / state.
+pub async fn supervise<F, Fut>(name: String, mut make_run: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut backoff = MIN_RESTART_BACKOFF_SEC;
+    loop {
+        let started = chrono::Utc::now().timestamp();
+        let res = make_run().await;
+        if chrono::Utc::now().timestamp() - started > HEALTHY_RUN_SEC {
+            backoff = MIN_RESTART_BACKOFF_SEC;
+        }
+        match res {
+            Ok(()) => info!("Executor {} exited, restarting in {} sec", name, backoff),
+            Err(e) => {
+                error!("Executor {} failed with {:?}, restarting in {} sec", name, e, backoff)
+            }
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(backoff)).await;
+        backoff = (backoff * 2).min(MAX_RESTART_BACKOFF_SEC);
+    }
+}
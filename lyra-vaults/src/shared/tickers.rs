@@ -0,0 +1,168 @@
+use crate::helpers::{stale_channel_threshold, TickerInterval};
+use crate::market::MarketState;
+use crate::shared::context::VaultContext;
+use anyhow::{Error, Result};
+use lyra_client::channels::Channel;
+use lyra_client::json_rpc::{Notification, WsClientExt};
+use lyra_client::tickers::TickerCache;
+use lyra_client::ws_mux::WsMux;
+use orderbook_types::types::tickers::result::TickerNotificationData;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::RwLock;
+use tokio::select;
+use tokio::sync::watch;
+use tracing::info;
+
+/// When a managed ticker was last received, both via the snapshot and the subscription
+#[derive(Debug, Clone)]
+pub struct TickerMeta {
+    pub last_received_ms: i64,    // local time of the last update
+    pub ticker_timestamp_ms: i64, // exchange timestamp of the last update
+    pub updates: u64,
+}
+
+impl TickerMeta {
+    pub fn age_ms(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis() - self.last_received_ms
+    }
+}
+
+/// Keeps the tickers of a changing set of instruments in the market state.
+/// `add` fetches a snapshot of the new tickers before returning, so readers never have to wait
+/// for the first notification, while `run` subscribes to the current set and resubscribes
+/// whenever instruments are added or removed.
+pub struct TickerManager {
+    market: MarketState,
+    interval: TickerInterval,
+    instruments: watch::Sender<BTreeSet<String>>,
+    meta: RwLock<HashMap<String, TickerMeta>>,
+}
+
+impl TickerManager {
+    pub fn new(market: MarketState, interval: TickerInterval) -> Self {
+        let instruments = watch::channel(BTreeSet::new()).0;
+        Self { market, interval, instruments, meta: RwLock::new(HashMap::new()) }
+    }
+
+    /// Adds the instruments to the subscription set once their tickers are in the market state
+    pub async fn add(&self, instrument_names: Vec<String>) -> Result<()> {
+        let current = self.instruments.borrow().clone();
+        let new_names = instrument_names
+            .into_iter()
+            .filter(|name| !current.contains(name))
+            .collect::<BTreeSet<_>>();
+        if new_names.is_empty() {
+            return Ok(());
+        }
+        // fetched in parallel, selectors re-adding the same expiry within the TTL hit the cache
+        let tickers = TickerCache::global().get_many(new_names.iter().map(String::as_str)).await?;
+        let mut writer = self.market.write().await;
+        for (name, ticker) in tickers {
+            record_update(&self.meta, &name, ticker.timestamp);
+            writer.insert_ticker(ticker);
+        }
+        drop(writer);
+        info!("Adding tickers: {:?}", new_names);
+        self.instruments.send_modify(|instruments| instruments.extend(new_names));
+        Ok(())
+    }
+
+    /// Removes the instruments from the subscription set and their tickers from the market state
+    pub async fn remove(&self, instrument_names: &[String]) {
+        info!("Removing tickers: {:?}", instrument_names);
+        self.instruments.send_if_modified(|instruments| {
+            instrument_names.iter().fold(false, |removed, name| instruments.remove(name) || removed)
+        });
+        let mut writer = self.market.write().await;
+        for name in instrument_names {
+            writer.remove_ticker(name);
+        }
+        drop(writer);
+        if let Ok(mut meta) = self.meta.write() {
+            meta.retain(|name, _| !instrument_names.contains(name));
+        }
+    }
+
+    pub fn instrument_names(&self) -> Vec<String> {
+        self.instruments.borrow().iter().cloned().collect()
+    }
+
+    pub fn get_meta(&self, instrument_name: &str) -> Option<TickerMeta> {
+        self.meta.read().ok()?.get(instrument_name).cloned()
+    }
+
+    /// Managed instruments without an update within `max_age_ms`
+    pub fn stale_instruments(&self, max_age_ms: i64) -> Vec<String> {
+        let meta = self.meta.read();
+        self.instrument_names()
+            .into_iter()
+            .filter(|name| {
+                let age_ms = meta.as_ref().ok().and_then(|m| m.get(name)).map(|m| m.age_ms());
+                age_ms.map_or(true, |age_ms| age_ms > max_age_ms)
+            })
+            .collect()
+    }
+
+    /// Keeps the subscription of the current set running, restarting it on every change
+    pub async fn run(&self) -> Result<()> {
+        let mut changes = self.instruments.subscribe();
+        loop {
+            let instrument_names = changes.borrow_and_update().iter().cloned().collect::<Vec<_>>();
+            if instrument_names.is_empty() {
+                changes.changed().await?;
+                continue;
+            }
+            select! {
+                res = self.subscribe(instrument_names) => {
+                    return Err(Error::msg(format!("Ticker subscription exited with {:?}", res)));
+                }
+                res = changes.changed() => {
+                    res?;
+                    info!("Ticker set changed, resubscribing");
+                }
+            }
+        }
+    }
+
+    async fn subscribe(&self, instrument_names: Vec<String>) -> Result<()> {
+        let channels: Vec<String> = instrument_names
+            .iter()
+            .map(|instrument_name| {
+                Channel::ticker(instrument_name, self.interval as u32).to_string()
+            })
+            .collect();
+        let mux = WsMux::shared().await?;
+        mux.client().set_stale_threshold(channels.clone(), stale_channel_threshold()).await;
+        let health = VaultContext::current().health.clone();
+        info!("Subscribing to tickers: {:?}", channels);
+        let subscription =
+            mux.subscribe(channels, |msg: Notification<TickerNotificationData>| async {
+                health.market_data_received();
+                let ticker = msg.params.data.instrument_ticker;
+                // notifications of removed instruments can arrive until the resubscription
+                if !self.instruments.borrow().contains(&ticker.instrument_name) {
+                    return Ok(());
+                }
+                record_update(&self.meta, &ticker.instrument_name, ticker.timestamp);
+                self.market.write().await.insert_ticker(ticker);
+                Ok(())
+            });
+        health.track_subscription(|h| &mut h.ticker_subscriptions, subscription).await?;
+        Ok(())
+    }
+}
+
+fn record_update(meta: &RwLock<HashMap<String, TickerMeta>>, instrument_name: &str, ts: i64) {
+    let Ok(mut meta) = meta.write() else {
+        return;
+    };
+    let now = chrono::Utc::now().timestamp_millis();
+    let entry = meta.entry(instrument_name.to_string()).or_insert(TickerMeta {
+        last_received_ms: now,
+        ticker_timestamp_ms: ts,
+        updates: 0,
+    });
+    entry.last_received_ms = now;
+    entry.ticker_timestamp_ms = ts;
+    entry.updates += 1;
+}
@@ -0,0 +1,203 @@
+use crate::helpers::sleep_till;
+use crate::shared::auction::{LimitOrderAuction, LimitOrderAuctionExecutor, OrderStrategy};
+use crate::shared::params::{SpotAuctionParams, TwapParams};
+use crate::shared::stages::ExecutorStage;
+use anyhow::{Error, Result};
+use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, Zero};
+use lyra_client::actions::Direction;
+use lyra_client::json_rpc::WsClientExt;
+use rand::Rng;
+use std::sync::Mutex;
+use tokio::select;
+use tracing::info;
+
+/// Child order of a TWAP, trades `child_amount` of the amount left at the start of the slice
+#[derive(Debug, Clone)]
+pub struct TwapSlice {
+    pub index: i64,
+    pub end_sec: Option<i64>, // None for the last slice, which runs until the auction completes
+    pub start_amount: BigDecimal,
+    pub child_amount: BigDecimal,
+    pub price_limit: BigDecimal,
+}
+
+/// Spot auction strategy limited to the current TWAP slice, same as `SpotAuctionParams` if
+/// `twap` is not set
+#[derive(Debug)]
+pub struct TwapStrategy {
+    pub params: SpotAuctionParams,
+    slice: Mutex<Option<TwapSlice>>,
+}
+
+impl TwapStrategy {
+    pub fn new(params: SpotAuctionParams) -> Self {
+        TwapStrategy { params, slice: Mutex::new(None) }
+    }
+
+    fn current_slice(&self) -> Option<TwapSlice> {
+        self.slice.lock().unwrap().clone()
+    }
+
+    /// Sizes and prices the child order of the slice the auction is in, None once the whole
+    /// amount is traded. Slices are timed from the auction start so resumed auctions keep them.
+    async fn start_slice(
+        &self,
+        twap: &TwapParams,
+        auction: &LimitOrderAuction,
+    ) -> Result<Option<TwapSlice>> {
+        *self.slice.lock().unwrap() = None;
+        let price = self.params.get_desired_price(auction).await?;
+        let (direction, amount) = self.params.get_remaining_amount(auction, &price).await?;
+        if amount.is_zero() {
+            return Ok(None);
+        }
+
+        let reader = auction.market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let num_slices = twap.num_slices.max(1);
+        let elapsed_sec = chrono::Utc::now().timestamp() - auction.start_timestamp_sec;
+        let index = (elapsed_sec / twap.slice_sec.max(1)).clamp(0, num_slices - 1);
+        let slices_left = num_slices - index;
+
+        let child_amount = match slices_left {
+            1 => amount.clone(),
+            _ => {
+                let jitter = twap.size_jitter * rand::thread_rng().gen_range(-1.0..=1.0);
+                let size = BigDecimal::from_f64(1.0 + jitter).unwrap_or(BigDecimal::from(1));
+                let child_amount = (&amount / BigDecimal::from(slices_left) * size)
+                    .with_scale_round(
+                        ticker.amount_step.fractional_digit_count(),
+                        RoundingMode::Down,
+                    );
+                child_amount.max(ticker.minimum_amount.clone()).min(amount.clone())
+            }
+        };
+
+        let price_limit = match direction {
+            Direction::Buy => {
+                &ticker.mark_price * BigDecimal::from_f64(1.0 + twap.max_slice_move).unwrap()
+            }
+            Direction::Sell => {
+                &ticker.mark_price * BigDecimal::from_f64(1.0 - twap.max_slice_move).unwrap()
+            }
+        };
+        let price_limit = price_limit
+            .round(ticker.tick_size.fractional_digit_count())
+            .max(ticker.min_price.clone())
+            .min(ticker.max_price.clone());
+
+        let end_sec = match slices_left {
+            1 => None,
+            _ => Some(auction.start_timestamp_sec + (index + 1) * twap.slice_sec),
+        };
+        let slice = TwapSlice { index, end_sec, start_amount: amount, child_amount, price_limit };
+        *self.slice.lock().unwrap() = Some(slice.clone());
+        Ok(Some(slice))
+    }
+}
+
+impl OrderStrategy for TwapStrategy {
+    async fn get_desired_price(&self, auction: &LimitOrderAuction) -> Result<BigDecimal> {
+        let price = self.params.get_desired_price(auction).await?;
+        let slice = self.current_slice();
+        if price.is_zero() || slice.is_none() {
+            return Ok(price);
+        }
+        let slice = slice.unwrap();
+        let reader = auction.market.read().await;
+        let cash = reader.get_amount(&self.params.cash_name);
+        // neg cash -> selling, the limit is a floor, otherwise a cap
+        match cash < BigDecimal::zero() {
+            true => Ok(price.max(slice.price_limit)),
+            false => Ok(price.min(slice.price_limit)),
+        }
+    }
+    async fn get_desired_amount(
+        &self,
+        auction: &LimitOrderAuction,
+        price: &BigDecimal,
+    ) -> Result<(Direction, BigDecimal)> {
+        let (direction, amount) = self.params.get_remaining_amount(auction, price).await?;
+        let slice = self.current_slice();
+        if amount.is_zero() || slice.is_none() {
+            return Ok((direction, auction.cap_to_depth(&direction, amount).await?));
+        }
+        let slice = slice.unwrap();
+        let reader = auction.market.read().await;
+        let ticker =
+            reader.get_ticker(&auction.instrument_name).ok_or(Error::msg("Ticker not found"))?;
+        let filled = &slice.start_amount - &amount;
+        let remaining = (&slice.child_amount - filled)
+            .with_scale_round(ticker.amount_step.fractional_digit_count(), RoundingMode::Down);
+        if remaining < ticker.minimum_amount {
+            return Ok((Direction::Sell, BigDecimal::zero()));
+        }
+        drop(reader);
+        Ok((direction, auction.cap_to_depth(&direction, remaining.min(amount)).await?))
+    }
+}
+
+/// Executes a spot auction as a TWAP: the amount is split into `num_slices` child orders with
+/// randomized sizes, each auctioned for `slice_sec` with its price kept within `max_slice_move`
+/// of the mark at the start of the slice. Runs a plain spot auction if `twap` is not set.
+#[derive(Debug)]
+pub struct TwapExecutor {
+    pub executor: LimitOrderAuctionExecutor<TwapStrategy>,
+}
+
+impl TwapExecutor {
+    pub fn new(auction: LimitOrderAuction, params: SpotAuctionParams) -> Self {
+        let strategy = TwapStrategy::new(params);
+        TwapExecutor { executor: LimitOrderAuctionExecutor { auction, strategy } }
+    }
+
+    async fn run_slices(&self, twap: &TwapParams) -> Result<()> {
+        let executor = &self.executor;
+        executor.wait_for_ticker().await;
+        loop {
+            executor.sync().await;
+            let slice = executor.strategy.start_slice(twap, &executor.auction).await?;
+            let Some(slice) = slice else {
+                return Ok(());
+            };
+            info!("TwapExecutor starting slice {:?}", slice);
+            let Some(end_sec) = slice.end_sec else {
+                return executor.run_auction().await;
+            };
+            let remain_sec = (end_sec - chrono::Utc::now().timestamp()).max(0) as u64;
+            select! {
+                res = executor.run_auction() => res?,
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(remain_sec)) => {},
+            }
+            // the unfilled part of the child order rolls over into the next slice
+            executor.cancel_all().await?;
+            sleep_till(end_sec).await;
+        }
+    }
+}
+
+impl ExecutorStage for TwapExecutor {
+    fn name(&self) -> String {
+        "TwapExecutor".to_string()
+    }
+    async fn run(&self) -> Result<()> {
+        let Some(twap) = &self.executor.strategy.params.twap else {
+            return self.executor.run().await;
+        };
+        let market_task = self.executor.run_market();
+        let ping_task = self.executor.auction.client()?.ping_interval(15);
+        let res = select! {
+            _ = market_task => {Err(Error::msg("Market task exited early"))},
+            _ = ping_task => {Err(Error::msg("Ping task exited early"))},
+            res = self.run_slices(twap) => { res },
+        };
+        if res.is_ok() {
+            self.executor.report_execution().await;
+        }
+        res
+    }
+    async fn reconnect(&mut self) -> Result<()> {
+        self.executor.reconnect().await
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::{Error, Result};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt::Display;
+
+/// Deserializes params, pointing errors at the offending field, e.g.
+/// `option_auction_params.init_iv_spread: invalid type: string "0.1", expected f64`
+pub fn parse_params<T: DeserializeOwned>(value: Value) -> Result<T> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        Error::msg(format!("{}: {}", path, e.into_inner()))
+    })
+}
+
+/// Range checks and invariants of params that parse, see `ValidateParams`
+pub trait ValidateParams {
+    fn validate(&self, check: &mut ParamsCheck);
+}
+
+/// Collects every violation of the params so that one run reports all of them, each with the
+/// path of its field, e.g. `option_auction_params.max_iv_spread = 1.5 is not within [0, 1]`
+#[derive(Debug, Default)]
+pub struct ParamsCheck {
+    prefix: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl ParamsCheck {
+    fn path(&self, field: &str) -> String {
+        let mut path = self.prefix.clone();
+        path.push(field.to_string());
+        path.join(".")
+    }
+
+    /// Validates nested params, their fields prefixed with `field`
+    pub fn nested(&mut self, field: &str, params: &impl ValidateParams) {
+        self.prefix.push(field.to_string());
+        params.validate(self);
+        self.prefix.pop();
+    }
+
+    pub fn within(&mut self, field: &str, value: f64, min: f64, max: f64) {
+        if !(min..=max).contains(&value) {
+            let path = self.path(field);
+            self.errors.push(format!("{} = {} is not within [{}, {}]", path, value, min, max));
+        }
+    }
+
+    /// Spreads, ratios and the like between 0 and 1
+    pub fn fraction(&mut self, field: &str, value: f64) {
+        self.within(field, value, 0.0, 1.0);
+    }
+
+    pub fn at_least<T: PartialOrd + Display>(&mut self, field: &str, value: &T, min: &T) {
+        if value < min {
+            self.errors.push(format!("{} = {} is below {}", self.path(field), value, min));
+        }
+    }
+
+    pub fn above<T: PartialOrd + Display>(&mut self, field: &str, value: &T, min: &T) {
+        if value <= min {
+            self.errors.push(format!("{} = {} must be above {}", self.path(field), value, min));
+        }
+    }
+
+    pub fn at_most<T: PartialOrd + Display>(&mut self, field: &str, value: &T, max: &T) {
+        if value > max {
+            self.errors.push(format!("{} = {} is above {}", self.path(field), value, max));
+        }
+    }
+
+    /// `field` may not exceed `max_field`, e.g. an initial spread and its max
+    pub fn not_above<T: PartialOrd + Display>(
+        &mut self,
+        field: &str,
+        value: &T,
+        max_field: &str,
+        max: &T,
+    ) {
+        if value > max {
+            let (path, max_path) = (self.path(field), self.path(max_field));
+            self.errors.push(format!("{} = {} is above {} = {}", path, value, max_path, max));
+        }
+    }
+
+    /// Cross-field invariants, `message` names the fields involved
+    pub fn require(&mut self, ok: bool, field: &str, message: &str) {
+        if !ok {
+            self.errors.push(format!("{} {}", self.path(field), message));
+        }
+    }
+
+    /// One line per violation, Ok if there are none
+    pub fn finish(self, kind: &str) -> Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        let lines = self.errors.iter().map(|e| format!("  {}", e)).collect::<Vec<_>>();
+        Err(Error::msg(format!("Invalid {} params:\n{}", kind, lines.join("\n"))))
+    }
+}
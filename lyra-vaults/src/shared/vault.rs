@@ -0,0 +1,230 @@
+use crate::helpers::log_portfolio_greeks;
+use crate::shared::accounting::Ledger;
+use crate::shared::alert::send_alert;
+use crate::shared::context::VaultContext;
+use crate::shared::nav::{log_vault_nav, VaultNav};
+use crate::shared::risk::RiskHalt;
+use crate::shared::schedule::StageSchedule;
+use crate::shared::session::rotate_session_key_if_expiring;
+use crate::shared::stages::ExecutorStage;
+use anyhow::{Error, Result};
+use std::sync::Arc;
+use tokio::select;
+use tracing::{info, info_span, warn, Instrument};
+
+/// Strategy specific part of a vault executor, i.e. which stage to start in and which stage
+/// (with which instruments and auction params) comes after each completed one.
+/// Implement this for a custom vault and run it with `VaultExecutor`.
+pub trait VaultStrategy {
+    type Stage: ExecutorStage;
+
+    /// Infers the stage to start in from the positions / market (e.g. after a restart)
+    async fn initial_stage(&self) -> Result<Self::Stage>;
+    /// Selects the instruments and builds the stage that follows the completed one
+    async fn next_stage(&self, completed: &Self::Stage) -> Result<Self::Stage>;
+    /// Called every time a stage is entered, before it runs (e.g. to persist it)
+    async fn on_stage_entered(&self, _stage: &mut Self::Stage) -> Result<()> {
+        Ok(())
+    }
+    /// True for the stage that starts a new epoch (e.g. the option auction), NAV changes are
+    /// logged from one epoch start to the next
+    fn is_epoch_start(&self, _stage: &Self::Stage) -> bool {
+        false
+    }
+    /// Scheduled start times of the stages, if any, reported on `/status`
+    fn schedule(&self) -> Option<&StageSchedule> {
+        None
+    }
+    /// Builds the stage the admin API forced by name, None if the strategy can not build it
+    /// without a completed stage to follow
+    async fn named_stage(&self, _name: &str) -> Result<Option<Self::Stage>> {
+        Ok(None)
+    }
+    /// Spot instruments the idle cash is parked in, their fills are booked as cash yield
+    fn yield_instruments(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// Resolves with the breach of the risk limits, never without them
+async fn breached(halt: &Option<Arc<RiskHalt>>) -> String {
+    match halt {
+        Some(halt) => halt.breached().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Generic stage machine, runs the current stage to completion and moves to the next one
+pub struct VaultExecutor<S: VaultStrategy> {
+    pub strategy: S,
+    pub stage: S::Stage,
+    epoch_nav: Option<VaultNav>, // NAV at the start of the current epoch
+    ledger: Option<Ledger>,      // kept if LEDGER_DIR is set
+}
+
+impl<S: VaultStrategy> VaultExecutor<S> {
+    pub async fn new(strategy: S) -> Result<Self> {
+        let stage = strategy.initial_stage().await?;
+        let ctx = VaultContext::current();
+        ctx.order_labels.restore().await?;
+        let ledger = Ledger::from_env(&ctx.vault_name)?
+            .map(|ledger| ledger.with_yield_instruments(strategy.yield_instruments()));
+        let schedule = strategy.schedule().cloned();
+        ctx.health.update(|h| h.schedule = schedule);
+        Ok(Self { strategy, stage, epoch_nav: None, ledger })
+    }
+
+    /// Logs the portfolio greeks, failures are not fatal for the executor
+    async fn log_risk(&self) {
+        let res = match VaultContext::current().subaccount_ids() {
+            Ok(subaccount_ids) => log_portfolio_greeks(&subaccount_ids).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            warn!(error = format!("{:#}", e), "Failed to get portfolio greeks");
+        }
+    }
+
+    /// Logs the NAV and its change over the last epoch, failures are not fatal for the executor
+    async fn log_nav(&mut self) {
+        let res = match VaultContext::current().subaccount_ids() {
+            Ok(subaccount_ids) => log_vault_nav(&subaccount_ids).await,
+            Err(e) => Err(e),
+        };
+        let nav = match res {
+            Ok(nav) => nav,
+            Err(e) => {
+                warn!(error = format!("{:#}", e), "Failed to get vault NAV");
+                return;
+            }
+        };
+        if !self.strategy.is_epoch_start(&self.stage) {
+            return;
+        }
+        if let Some(previous) = self.epoch_nav.replace(nav.clone()) {
+            nav.log_change(&previous);
+        }
+    }
+
+    /// Syncs the ledger and starts a new ledger epoch on epoch starts, failures are not fatal
+    /// for the executor (the next sync catches up)
+    async fn update_ledger(&self) {
+        let Some(ledger) = &self.ledger else {
+            return;
+        };
+        let res = match VaultContext::current().subaccount_ids() {
+            Ok(subaccount_ids) => ledger.sync(&subaccount_ids).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            warn!(error = format!("{:#}", e), "Failed to sync the ledger");
+        }
+        if !self.strategy.is_epoch_start(&self.stage) {
+            return;
+        }
+        // numbered like the order labels, so that both count an epoch once
+        let labels = &VaultContext::current().order_labels;
+        if let Err(e) = ledger.start_epoch(labels.epoch(), labels.epoch_start_sec() * 1000) {
+            warn!(error = format!("{:#}", e), "Failed to start a ledger epoch");
+        }
+    }
+
+    /// Rotates the session key between stages, before it expires. The old key stays valid if
+    /// the rotation fails, so failures are not fatal for the executor
+    async fn rotate_session_key(&self) {
+        if let Err(e) = rotate_session_key_if_expiring().await {
+            warn!(error = format!("{:#}", e), "Failed to rotate the session key");
+        }
+    }
+
+    /// Runs the current stage to completion. A risk limit breach or an admin pause stops the
+    /// stage (the guard or the admin commands cancel its orders) and holds the vault until the
+    /// limits hold again and trading is resumed, then reruns it. Returns the name of the stage
+    /// the admin forced meanwhile, if any.
+    async fn run_stage(&mut self) -> Result<Option<String>> {
+        let ctx = VaultContext::current();
+        let halt = ctx.risk_halt.clone();
+        loop {
+            let reason = select! {
+                biased;
+                name = ctx.admin.stage_forced() => return Ok(Some(name)),
+                breach = breached(&halt) => format!("risk breach: {}", breach),
+                _ = ctx.admin.paused() => "admin pause".to_string(),
+                res = self.stage.run_with_reconnect() => return res.map(|_| None),
+            };
+            warn!(stage = ?self.stage, %reason, "Stage halted");
+            let resumed = async {
+                ctx.admin.resumed().await;
+                if let Some(halt) = &halt {
+                    halt.cleared().await;
+                }
+            };
+            select! {
+                name = ctx.admin.stage_forced() => return Ok(Some(name)),
+                _ = resumed => {}
+            }
+            info!(stage = ?self.stage, "Resuming stage");
+            self.stage.reconnect_with_backoff().await?;
+        }
+    }
+
+    /// The stage forced by the admin API: `initial` infers it from the positions as on a
+    /// restart, `next` skips the current stage, other names are built by the strategy
+    async fn forced_stage(&self, name: &str) -> Result<S::Stage> {
+        match name {
+            "initial" => self.strategy.initial_stage().await,
+            "next" => self.strategy.next_stage(&self.stage).await,
+            _ => self
+                .strategy
+                .named_stage(name)
+                .await?
+                .ok_or(Error::msg(format!("Stage {} can not be forced", name))),
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        loop {
+            let ctx = VaultContext::current();
+            ctx.health.stage_entered(self.stage.name());
+            ctx.admin.stage_entered(format!("{:?}", self.stage));
+            let is_epoch_start = self.strategy.is_epoch_start(&self.stage);
+            if let Err(e) = ctx.order_labels.stage_entered(self.stage.name(), is_epoch_start).await
+            {
+                warn!(error = format!("{:#}", e), "Failed to store the epoch of the order labels");
+            }
+            // events of the stage carry the vault, epoch and stage, e.g. to correlate an auction
+            let span = info_span!(
+                "stage",
+                vault = %ctx.vault_name,
+                epoch = ctx.order_labels.epoch(),
+                stage = %self.stage.name()
+            );
+            async {
+                info!(stage = ?self.stage, "Stage entered");
+                self.rotate_session_key().await;
+                self.log_risk().await;
+                self.log_nav().await;
+                self.update_ledger().await;
+                self.strategy.on_stage_entered(&mut self.stage).await?;
+                let Some(forced) = self.run_stage().await? else {
+                    info!(stage = ?self.stage, "Stage completed");
+                    self.stage = self.strategy.next_stage(&self.stage).await?;
+                    return Ok::<_, Error>(());
+                };
+                warn!(stage = ?self.stage, %forced, "Stage stopped, admin forced another stage");
+                // the stopped stage leaves its orders resting, pull them before switching
+                ctx.admin.cancel_all_and_wait().await;
+                // a stage that fails to build leaves the current one to be entered again
+                match self.forced_stage(&forced).await {
+                    Ok(stage) => self.stage = stage,
+                    Err(e) => {
+                        send_alert(&format!("Forcing stage {} failed: {:#}", forced, e)).await
+                    }
+                }
+                Ok(())
+            }
+            .instrument(span)
+            .await?;
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::shared::context::VaultContext;
+use crate::web3::{get_balance_to_withdraw, get_pending_withdrawals, ProviderWithSigner, TSA};
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+/// Collateral (in spot_name) owed to queued withdrawal requests that the TSA balance does not
+/// cover yet. Option auctions only sell against the collateral above it.
+#[derive(Debug, Default)]
+pub struct WithdrawalReserve(RwLock<BigDecimal>);
+
+impl WithdrawalReserve {
+    pub fn get(&self) -> BigDecimal {
+        self.0.read().map(|reserve| reserve.clone()).unwrap_or_default()
+    }
+
+    pub fn set(&self, reserve: BigDecimal) {
+        if let Ok(mut current) = self.0.write() {
+            *current = reserve;
+        }
+    }
+}
+
+/// Reserves the collateral still needed by the pending withdrawal requests, zero if none are
+/// pending. Called after processing withdrawals so that the next auction leaves it free.
+pub async fn update_withdrawal_reserve(
+    tsa: &TSA<ProviderWithSigner>,
+    asset_name: &String,
+) -> Result<BigDecimal> {
+    let pending = get_pending_withdrawals(tsa).await?;
+    let reserve = match pending.is_empty() {
+        true => BigDecimal::zero(),
+        false => get_balance_to_withdraw(tsa, asset_name).await?,
+    };
+    if reserve > BigDecimal::zero() {
+        warn!("Reserving {} {} for {} pending withdrawals", reserve, asset_name, pending.len());
+    } else {
+        info!("No collateral reserved for withdrawals");
+    }
+    VaultContext::current().withdrawal_reserve.set(reserve.clone());
+    Ok(reserve)
+}
@@ -1,36 +1,38 @@
 use crate::helpers::{fetch_ticker, get_single_balance, sync_subaccount};
 use crate::market::new_market_state;
+use crate::web3::contracts::{get_erc20_on, get_provider_with_signer_on};
 pub use crate::web3::contracts::{
     get_provider_with_signer, get_tsa_contract, ProviderWithSigner, ERC20, TSA,
 };
-use crate::web3::{process_deposit_events, MAX_TO_PROCESS_PER_CALL};
-use crate::web3::{tsa, GAS_FACTOR};
+use crate::web3::providers::{lyra_chain_id, MAINNET_CHAIN_ID};
+use crate::web3::{process_deposit_events, process_withdrawal_requests, send_with_retry, tsa};
 use anyhow::{Error, Result};
 use bigdecimal::{BigDecimal, Zero};
 use ethers::abi::{AbiEncode, Address};
 use ethers::middleware::MiddlewareBuilder;
 use ethers::prelude::{
     abigen, Abigen, Bytes, Event, LocalWallet, Middleware, NonceManagerMiddleware, Signer,
-    SignerMiddleware, Wallet, I256, U64,
+    SignerMiddleware, Wallet, I256,
 };
 use ethers::prelude::{ProviderExt, U256};
 use ethers::providers::{Http, Provider};
-use log::{info, warn};
 use lyra_client::actions::order::TradeData;
 use lyra_client::actions::{
-    get_asset_decimals, ActionData, DepositData, DepositParams, ExecuteData, MarginType,
-    ModuleData, OrderArgs, QuoteData, WithdrawParams, WithdrawalData,
+    ActionData, CollateralAsset, DepositData, DepositParams, ExecuteData, MarginType, ModuleData,
+    OrderArgs, QuoteData, WithdrawParams, WithdrawalData,
 };
-use lyra_client::auth::{load_signer_by_name, sign_auth_header};
+use lyra_client::auth::{load_signer, sign_auth_header};
+use lyra_client::config::LyraConfig;
 use lyra_client::json_rpc::{http_rpc, WsClient, WsClientExt};
+use lyra_client::subaccounts::get_subaccount;
 use lyra_client::utils::{
     decimal_to_u256, decimal_to_u256_with_prec, u256_to_decimal, u256_to_decimal_with_prec,
 };
 use orderbook_types::generated::private_deposit::PrivateDepositResponseSchema;
 use orderbook_types::generated::private_withdraw::PrivateWithdrawResponseSchema;
 use std::collections::HashMap;
+use tracing::{info, warn};
 
-use crate::web3::contracts::GAS_PRICE;
 use bigdecimal::RoundingMode::Down;
 use orderbook_types::generated::private_get_subaccount::{
     PrivateGetSubaccountParamsSchema, PrivateGetSubaccountResponseSchema,
@@ -49,14 +51,35 @@ use uuid::Uuid;
 
 const BLOCK_SEC: u64 = 2;
 const WITHDRAW_BUFFER_FACTOR: &str = "1.01";
+const DEFAULT_DEPOSIT_GAS_LIMIT: u64 = 200_000;
+const DEFAULT_BRIDGE_TIMEOUT_SEC: u64 = 1800;
+const DEFAULT_CREDIT_TIMEOUT_SEC: u64 = 300;
+const DEPOSIT_POLL_SEC: u64 = 10;
 
+abigen!(
+    DepositWrapper,
+    r#"[
+        function depositToLyra(address token, address socketVault, bool isSCW, uint256 amount, uint256 gasLimit, address connector) external payable
+    ]"#,
+);
+
+abigen!(
+    SocketVault,
+    r#"[
+        function getMinFees(address connector, uint256 msgGasLimit) external view returns (uint256)
+        event TokensDeposited(address connector, address depositor, address receiver, uint256 depositAmount)
+    ]"#,
+);
+
+/// Signs the action for `subaccount_id`, i.e. the TSA subaccount or one of the routed ones
 pub async fn sign_action<T: AbiEncode + ModuleData + Clone>(
     tsa: &TSA<ProviderWithSigner>,
+    subaccount_id: i64,
     data: T,
     extra_data: Bytes,
 ) -> Result<ActionData> {
-    let subaccount_id: i64 = std::env::var("SUBACCOUNT_ID")?.parse()?;
-    let action_data = ActionData::new(data.clone(), subaccount_id, tsa.address())?;
+    let config = LyraConfig::current()?;
+    let action_data = ActionData::new(data.clone(), subaccount_id, tsa.address(), &config)?;
     let action = tsa::Action {
         subaccount_id: action_data.subaccount_id,
         nonce: action_data.nonce,
@@ -66,19 +89,9 @@ pub async fn sign_action<T: AbiEncode + ModuleData + Clone>(
         owner: action_data.owner,
         signer: action_data.signer,
     };
-    let call = tsa.sign_action(action.clone(), extra_data).gas_price(GAS_PRICE);
-    let gas = call.estimate_gas().await? * U256::from(GAS_FACTOR);
-    let call = call.gas(gas);
-    let pending_tx = call.send().await?;
-    let receipt = pending_tx.await?.ok_or(Error::msg("Failed"))?;
-    info!("Tx receipt: {}", serde_json::to_string(&receipt)?);
-    if let Some(status) = receipt.status {
-        if status == U64::zero() {
-            return Err(Error::msg("Transaction failed"));
-        }
-    }
-    let tx = tsa.client().get_transaction(receipt.transaction_hash).await?;
-    info!("Sent tx: {}\n", serde_json::to_string(&tx)?);
+    let receipt =
+        send_with_retry(&tsa.client(), tsa.sign_action(action.clone(), extra_data)).await?;
+    info!("Sign action tx: {:?}", receipt.transaction_hash);
     Ok(action_data)
 }
 
@@ -105,7 +118,7 @@ pub async fn get_balance_to_deposit(
     let balance = get_erc20_balance_of_tsa(tsa, asset_name).await?;
     let pending_deposits = tsa.total_pending_deposits().call().await?;
     let available_balance = balance - pending_deposits;
-    let decimals = get_asset_decimals(&asset_name);
+    let decimals = CollateralAsset::get(asset_name).await?.decimals;
     u256_to_decimal_with_prec(available_balance, decimals)
 }
 
@@ -131,7 +144,7 @@ pub async fn get_balance_to_withdraw(
     };
     let buffer = BigDecimal::from_str(WITHDRAW_BUFFER_FACTOR)?;
     info!("Extra balance needed: {}", extra_balance_needed);
-    let asset_decimals = get_asset_decimals(&asset_name);
+    let asset_decimals = CollateralAsset::get(asset_name).await?.decimals;
     let unround_amount = u256_to_decimal_with_prec(extra_balance_needed, asset_decimals)? * buffer;
     Ok(unround_amount.with_scale_round(asset_decimals as i64, Down))
 }
@@ -141,36 +154,45 @@ pub async fn sign_deposit(
     asset_name: &String,
     amount: &BigDecimal,
 ) -> Result<ActionData> {
-    let deposit_data = DepositData::new(&amount, &asset_name, MarginType::Sm)?;
+    let asset = CollateralAsset::get(asset_name).await?;
+    let config = LyraConfig::current()?;
+    let deposit_data = DepositData::new(amount, &asset, MarginType::Sm, &config)?;
     info!("Deposit data: {:?}", deposit_data);
-    let action_data = sign_action(tsa, deposit_data.clone(), Bytes::new()).await?;
+    let subaccount_id = config.subaccount_id()?;
+    let action_data = sign_action(tsa, subaccount_id, deposit_data.clone(), Bytes::new()).await?;
     Ok(action_data)
 }
 
+/// Processes the deposit requests and deposits the TSA balance into the subaccount.
+/// Returns the amount deposited, zero if there was nothing to deposit.
 pub async fn process_deposits_once(
     tsa: &TSA<ProviderWithSigner>,
     asset_name: String,
-) -> Result<()> {
+) -> Result<BigDecimal> {
     process_deposit_events(tsa).await?;
 
     let balance = get_balance_to_deposit(tsa, &asset_name).await?;
     if balance <= BigDecimal::zero() {
         // todo some magic numbers -> env (e.g. 5 sec wait time here)
         tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        return Ok(());
+        return Ok(BigDecimal::zero());
     }
 
     let action_data = sign_deposit(tsa, &asset_name, &balance).await?;
     let client = WsClient::new_client().await?;
     client.login().await?;
-    let session_signer = load_signer_by_name("SESSION").await;
-    let deposit = action_data.to_deposit_params(&session_signer, balance, asset_name.clone())?;
+    let session_signer = load_signer().await?;
+    let config = client.get_config().await;
+    let deposit = action_data
+        .to_deposit_params(&session_signer, &config, balance.clone(), asset_name.clone())
+        .await?;
     let deposit_res = client
         .send_rpc::<_, PrivateDepositResponseSchema>("private/deposit", deposit)
         .await?
         .into_result()?;
     info!("Deposit response: {:?}", deposit_res);
-    await_tx_settlement(deposit_res.result.transaction_id).await
+    await_tx_settlement(deposit_res.result.transaction_id).await?;
+    Ok(balance)
 }
 
 pub async fn process_deposits_forever(
@@ -182,45 +204,207 @@ pub async fn process_deposits_forever(
     }
 }
 
+/// Bridge route of a collateral from a wallet on another chain to the exchange wallet, read
+/// from `{ASSET}_DEPOSIT_CHAIN_ID` (mainnet by default), `{ASSET}_DEPOSIT_TOKEN`,
+/// `{ASSET}_DEPOSIT_WRAPPER`, `{ASSET}_DEPOSIT_VAULT` and `{ASSET}_DEPOSIT_CONNECTOR`
+#[derive(Debug, Clone)]
+pub struct DepositRoute {
+    pub chain_id: u64,
+    pub token: Address,        // the collateral on the source chain
+    pub wrapper: Address,      // deposit wrapper, pulls the token and bridges it
+    pub socket_vault: Address, // bridge vault of the token, charges the bridge fee
+    pub connector: Address,
+    pub gas_limit: U256, // gas of the bridge message on the Lyra chain
+}
+
+impl DepositRoute {
+    pub fn from_env(asset_name: &str) -> Result<Self> {
+        let address = |name: &str| -> Result<Address> {
+            let var = format!("{asset_name}_DEPOSIT_{name}");
+            let value = std::env::var(&var).map_err(|_| Error::msg(format!("{var} is not set")))?;
+            Ok(value.parse()?)
+        };
+        let chain_id = match std::env::var(format!("{asset_name}_DEPOSIT_CHAIN_ID")) {
+            Ok(chain_id) => chain_id.parse()?,
+            Err(_) => MAINNET_CHAIN_ID,
+        };
+        let gas_limit = std::env::var("DEPOSIT_GAS_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_DEPOSIT_GAS_LIMIT);
+        Ok(Self {
+            chain_id,
+            token: address("TOKEN")?,
+            wrapper: address("WRAPPER")?,
+            socket_vault: address("VAULT")?,
+            connector: address("CONNECTOR")?,
+            gas_limit: U256::from(gas_limit),
+        })
+    }
+}
+
+fn env_timeout(name: &str, default_sec: u64) -> tokio::time::Duration {
+    let sec = std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default_sec);
+    tokio::time::Duration::from_secs(sec)
+}
+
+/// Deposits collateral held by a wallet (the `signer_name` signer, e.g. OWNER) into the
+/// subaccount: bridges whatever the exchange wallet of the owner lacks along the
+/// `DepositRoute`, approving the wrapper first if needed, waits for the bridged funds to arrive
+/// and then deposits into the subaccount with `private/deposit`. Returns once the exchange
+/// credited the subaccount, with its new balance of the asset.
+pub async fn deposit_from_wallet(
+    signer_name: &str,
+    asset_name: &str,
+    amount: &BigDecimal,
+    subaccount_id: i64,
+) -> Result<BigDecimal> {
+    let owner = LyraConfig::current()?.owner()?;
+    let asset = CollateralAsset::get(asset_name).await?;
+    let token: Address = std::env::var(format!("{asset_name}_ADDRESS"))?.parse()?;
+    let lyra_token = get_erc20_on(lyra_chain_id()?, token).await?;
+    let wallet_balance =
+        u256_to_decimal_with_prec(lyra_token.balance_of(owner).call().await?, asset.decimals)?;
+    if wallet_balance < *amount {
+        let to_bridge = amount - &wallet_balance;
+        let route = DepositRoute::from_env(asset_name)?;
+        bridge_to_wallet(signer_name, &route, &to_bridge, owner).await?;
+        let deadline = tokio::time::Instant::now()
+            + env_timeout("DEPOSIT_BRIDGE_TIMEOUT_SEC", DEFAULT_BRIDGE_TIMEOUT_SEC);
+        loop {
+            let balance = lyra_token.balance_of(owner).call().await?;
+            let balance = u256_to_decimal_with_prec(balance, asset.decimals)?;
+            if balance >= *amount {
+                info!("Bridged {} {} arrived in {:?}", to_bridge, asset_name, owner);
+                break;
+            }
+            if tokio::time::Instant::now() > deadline {
+                let msg = format!("Bridged {} not arrived, wallet holds {}", asset_name, balance);
+                return Err(Error::msg(msg));
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(DEPOSIT_POLL_SEC)).await;
+        }
+    } else {
+        info!("Wallet holds {} {}, nothing to bridge", wallet_balance, asset_name);
+    }
+
+    let before = get_single_balance(subaccount_id, asset_name).await?;
+    let client = WsClient::new_client().await?;
+    client.login().await?.into_result()?;
+    let margin_type = get_subaccount(&client, subaccount_id).await?.margin_type;
+    let res = client
+        .deposit(subaccount_id, amount.clone(), asset_name.to_string(), margin_type)
+        .await?
+        .into_result()?;
+    info!("Deposit response: {:?}", res);
+    await_tx_settlement(res.result.transaction_id).await?;
+
+    // the tx settles before the exchange shows the balance
+    let deadline = tokio::time::Instant::now()
+        + env_timeout("DEPOSIT_CREDIT_TIMEOUT_SEC", DEFAULT_CREDIT_TIMEOUT_SEC);
+    loop {
+        let balance = get_single_balance(subaccount_id, asset_name).await?;
+        if balance >= &before + amount {
+            info!("Subaccount {} credited, {} balance {}", subaccount_id, asset_name, balance);
+            return Ok(balance);
+        }
+        if tokio::time::Instant::now() > deadline {
+            let msg = format!("Deposit not credited to {}, balance {}", subaccount_id, balance);
+            return Err(Error::msg(msg));
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(DEPOSIT_POLL_SEC)).await;
+    }
+}
+
+/// Sends the amount through the deposit wrapper of the route to the receiver's exchange wallet.
+/// Returns after the bridge vault emitted its deposit event, the funds arrive later.
+async fn bridge_to_wallet(
+    signer_name: &str,
+    route: &DepositRoute,
+    amount: &BigDecimal,
+    receiver: Address,
+) -> Result<()> {
+    let provider = get_provider_with_signer_on(route.chain_id, signer_name).await?;
+    let sender = provider.default_sender().ok_or(Error::msg("No tx sender"))?;
+    let token = ERC20::new(route.token, provider.clone());
+    let decimals = token.decimals().call().await?;
+    let amount = decimal_to_u256_with_prec(amount.clone(), decimals as u32)?;
+    let balance = token.balance_of(sender).call().await?;
+    if balance < amount {
+        let msg = format!("{:?} holds {} of {:?}, {} needed", sender, balance, route.token, amount);
+        return Err(Error::msg(msg));
+    }
+
+    let allowance = token.allowance(sender, route.wrapper).call().await?;
+    if allowance < amount {
+        info!("Approving {} of {:?} to the deposit wrapper", amount, route.token);
+        send_with_retry(&provider, token.approve(route.wrapper, amount)).await?;
+    }
+
+    let socket_vault = SocketVault::new(route.socket_vault, provider.clone());
+    let fees = socket_vault.get_min_fees(route.connector, route.gas_limit).call().await?;
+    let wrapper = DepositWrapper::new(route.wrapper, provider.clone());
+    // the wrapper bridges to the smart contract wallet of the sender unless it is the receiver
+    let is_scw = receiver != sender;
+    let call = wrapper
+        .deposit_to_lyra(
+            route.token,
+            route.socket_vault,
+            is_scw,
+            amount,
+            route.gas_limit,
+            route.connector,
+        )
+        .value(fees);
+    info!("Bridging {} of {:?} to {:?} with fees {}", amount, route.token, receiver, fees);
+    let receipt = send_with_retry(&provider, call).await?;
+    let deposited = receipt.logs.iter().find_map(|log| {
+        let event = ethers::contract::parse_log::<TokensDepositedFilter>(log.clone()).ok()?;
+        (log.address == route.socket_vault).then_some(event)
+    });
+    match deposited {
+        Some(event) => {
+            info!("Bridge deposit of {} to {:?} sent", event.deposit_amount, event.receiver);
+            Ok(())
+        }
+        None => {
+            Err(Error::msg(format!("Tx {:?} emitted no bridge deposit", receipt.transaction_hash)))
+        }
+    }
+}
+
 pub async fn sign_withdrawal(
     tsa: &TSA<ProviderWithSigner>,
     asset_name: &String,
     amount: &BigDecimal,
 ) -> Result<ActionData> {
-    let withdrawal_data = WithdrawalData::new(&amount, &asset_name)?;
+    let asset = CollateralAsset::get(asset_name).await?;
+    let withdrawal_data = WithdrawalData::new(amount, &asset)?;
     info!("Withdrawal data: {:?}", withdrawal_data);
-    let action_data = sign_action(tsa, withdrawal_data.clone(), Bytes::new()).await?;
+    let subaccount_id = LyraConfig::current()?.subaccount_id()?;
+    let action_data =
+        sign_action(tsa, subaccount_id, withdrawal_data.clone(), Bytes::new()).await?;
     Ok(action_data)
 }
 
 /// clears the total_pending_withdrawals using an existing LRT balance of the vault
-async fn process_withdrawals_onchain(
+pub async fn process_withdrawals_onchain(
     tsa: &TSA<ProviderWithSigner>,
     asset_name: String,
 ) -> Result<()> {
-    loop {
-        let balance = get_erc20_balance_of_tsa(tsa, &asset_name).await?;
-        let pending = tsa.total_pending_withdrawals().call().await?;
-        if balance == U256::from(0) || pending == U256::from(0) {
-            info!("Balance & pending withdrawals for {}: {} & {}", asset_name, balance, pending);
-            break;
-        }
-        let call = tsa
-            .process_withdrawal_requests(U256::from(MAX_TO_PROCESS_PER_CALL))
-            .gas_price(GAS_PRICE);
-        let gas = call.estimate_gas().await? * U256::from(GAS_FACTOR);
-        let call = call.gas(gas);
-        let pending_tx = call.send().await?;
-        let receipt = pending_tx.await?.ok_or(Error::msg("Failed"))?;
-        info!("Tx receipt: {}", serde_json::to_string(&receipt)?);
-        let tx = tsa.client().get_transaction(receipt.transaction_hash).await?;
-        info!("Sent tx: {}\n", serde_json::to_string(&tx)?);
+    let balance = get_erc20_balance_of_tsa(tsa, &asset_name).await?;
+    let pending = tsa.total_pending_withdrawals().call().await?;
+    info!("Balance & pending withdrawals for {}: {} & {}", asset_name, balance, pending);
+    if balance == U256::from(0) {
+        return Ok(());
     }
+    // the queue is read from the withdrawal requests, the pending total can lag behind it
+    process_withdrawal_requests(tsa).await?;
     Ok(())
 }
 
 pub async fn process_withdrawals(tsa: &TSA<ProviderWithSigner>, asset_name: String) -> Result<()> {
-    let subaccount_id: i64 = std::env::var("SUBACCOUNT_ID")?.parse()?;
+    let subaccount_id = LyraConfig::current()?.subaccount_id()?;
     let lrt_balance = get_single_balance(subaccount_id, &asset_name).await?;
     info!("Orderbook LRT balance for {}: {}", asset_name, lrt_balance);
     if lrt_balance == BigDecimal::zero() {
@@ -241,10 +425,12 @@ pub async fn process_withdrawals(tsa: &TSA<ProviderWithSigner>, asset_name: Stri
     }
 
     let action_data = sign_withdrawal(&tsa, &asset_name, &can_withdraw).await?;
-    let session_signer = load_signer_by_name("SESSION").await;
-    let headers = sign_auth_header(&session_signer).await;
-    let withdrawal =
-        action_data.to_withdraw_params(&session_signer, can_withdraw, asset_name.clone())?;
+    let session_signer = load_signer().await?;
+    let headers = sign_auth_header(&session_signer).await?;
+    let config = LyraConfig::current()?;
+    let withdrawal = action_data
+        .to_withdraw_params(&session_signer, &config, can_withdraw, asset_name.clone())
+        .await?;
     let withdrawal_res =
         http_rpc::<_, PrivateWithdrawResponseSchema>("private/withdraw", withdrawal, Some(headers))
             .await?
@@ -256,10 +442,10 @@ pub async fn process_withdrawals(tsa: &TSA<ProviderWithSigner>, asset_name: Stri
 
 pub async fn sign_order(
     tsa: &TSA<ProviderWithSigner>,
+    subaccount_id: i64,
     ticker: &InstrumentTicker,
     args: &OrderArgs,
 ) -> Result<ActionData> {
-    let subaccount_id: i64 = std::env::var("SUBACCOUNT_ID")?.parse()?;
     let order_data = TradeData::new(
         ticker,
         subaccount_id,
@@ -268,12 +454,13 @@ pub async fn sign_order(
         args.direction.is_bid(),
     )?;
     info!("Order data: {:?}", order_data);
-    let action_data = sign_action(tsa, order_data.clone(), Bytes::new()).await?;
+    let action_data = sign_action(tsa, subaccount_id, order_data.clone(), Bytes::new()).await?;
     Ok(action_data)
 }
 
 pub async fn sign_execute_quote(
     tsa: &TSA<ProviderWithSigner>,
+    subaccount_id: i64,
     tickers: &HashMap<String, InstrumentTicker>,
     quote: &QuoteResultPublic,
 ) -> Result<ActionData> {
@@ -281,7 +468,7 @@ pub async fn sign_execute_quote(
     let execute_data = quote_data.clone().into_execute();
     info!("Execute data: {:?}", execute_data);
     let extra_data = Bytes::from(quote_data.encoded_legs());
-    let action_data = sign_action(tsa, execute_data.clone(), extra_data).await?;
+    let action_data = sign_action(tsa, subaccount_id, execute_data.clone(), extra_data).await?;
     Ok(action_data)
 }
 
@@ -1,16 +1,21 @@
+use crate::shared::context::VaultContext;
+use crate::web3::providers::{get_provider, lyra_chain_id};
 pub use crate::web3::tsa::{Action, TSA};
 use anyhow::{Error, Result};
+use bigdecimal::BigDecimal;
 use ethers::abi::Address;
 use ethers::contract::abigen;
 use ethers::middleware::{NonceManagerMiddleware, SignerMiddleware};
-use ethers::prelude::{Abigen, Http, LocalWallet, MiddlewareBuilder, Provider, Signer};
-use log::{error, info};
-use lyra_client::auth::load_signer_by_name;
+use ethers::prelude::{Abigen, Http, MiddlewareBuilder, Provider, Signer};
+use lyra_client::auth::{load_signer, load_signer_by_name};
+use lyra_client::signer::LyraSigner;
+use lyra_client::utils::u256_to_decimal_with_prec;
 use std::env;
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use tracing::{error, info};
 
 abigen!(ERC20, "./abi/erc20.json");
 
@@ -47,7 +52,7 @@ impl<M: ::ethers::providers::Middleware> TSA<M> {
         action: Action,
         extra_data: ::ethers::core::types::Bytes,
     ) -> ::ethers::contract::builders::ContractCall<M, ()> {
-        let vault_name = std::env::var("VAULT_NAME").expect("VAULT_NAME is not set");
+        let vault_name = VaultContext::current().vault_name.clone();
         let tsa_type =
             env::var(format!("{vault_name}_TSA_SIGNING")).expect("TSA_SIGNING is not set");
         match tsa_type.as_str() {
@@ -63,20 +68,37 @@ impl<M: ::ethers::providers::Middleware> TSA<M> {
 // todo somehow minimize the deposit/withdrawal diffs? enum the two?
 // if enum, do impl on the enum to return things like client() and other shared methods w/ same name
 
-pub type ProviderWithSigner = NonceManagerMiddleware<SignerMiddleware<Provider<Http>, LocalWallet>>;
+pub type ProviderWithSigner = NonceManagerMiddleware<SignerMiddleware<Provider<Http>, LyraSigner>>;
 
 pub async fn get_provider_with_signer(
     signer_name: &str,
 ) -> anyhow::Result<Arc<ProviderWithSigner>> {
-    let provider_url = std::env::var("WEB3_PROVIDER").expect("WEB3_PROVIDER is not set");
-    let chain_id: u64 = std::env::var("CHAIN_ID").expect("CHAIN_ID is not set").parse().unwrap();
-    let signer = load_signer_by_name(signer_name).await.with_chain_id(chain_id);
+    get_provider_with_signer_on(lyra_chain_id()?, signer_name).await
+}
+
+/// Signing provider on another chain of the `ProviderRegistry`, e.g. Ethereum mainnet
+pub async fn get_provider_with_signer_on(
+    chain_id: u64,
+    signer_name: &str,
+) -> anyhow::Result<Arc<ProviderWithSigner>> {
+    let provider = get_provider(chain_id).await?;
+    // the session key can differ per vault, so it comes from the current config. Keys can be
+    // local, in AWS KMS or on a ledger, see `LyraSigner`
+    let signer = match signer_name {
+        "SESSION" => load_signer().await?,
+        _ => load_signer_by_name(signer_name).await?,
+    };
+    let signer = signer.with_chain_id(chain_id);
     let signer_addr = signer.address();
-    let provider =
-        Provider::<Http>::try_from(provider_url)?.with_signer(signer).nonce_manager(signer_addr);
+    let provider = (*provider).clone().with_signer(signer).nonce_manager(signer_addr);
     Ok(Arc::new(provider))
 }
 
+/// Read only ERC20 on any chain of the `ProviderRegistry`, e.g. an LRT on mainnet
+pub async fn get_erc20_on(chain_id: u64, address: Address) -> Result<ERC20<Provider<Http>>> {
+    Ok(ERC20::new(address, get_provider(chain_id).await?))
+}
+
 pub async fn get_tsa_contract(
     vault_name: &str,
     signer_name: &str,
@@ -87,5 +109,12 @@ pub async fn get_tsa_contract(
     Ok(TSA::new(tsa_address, provider.clone()))
 }
 
-pub const GAS_PRICE: u64 = 200000;
+/// Vault shares minted by the TSA, scaled by the share token decimals
+pub async fn get_tsa_total_supply(vault_name: &str) -> anyhow::Result<BigDecimal> {
+    let tsa = get_tsa_contract(vault_name, "SESSION").await?;
+    let total_supply = tsa.total_supply().call().await?;
+    let decimals = tsa.decimals().call().await?;
+    Ok(u256_to_decimal_with_prec(total_supply, decimals as u32)?)
+}
+
 pub const GAS_FACTOR: u64 = 2;
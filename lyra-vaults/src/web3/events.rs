@@ -1,15 +1,291 @@
+use crate::shared::alert::send_alert;
+use crate::shared::context::VaultContext;
 use crate::web3::contracts::get_tsa_contract;
-use crate::web3::{ProviderWithSigner, GAS_FACTOR, GAS_PRICE, TSA};
+use crate::web3::tsa::TSAEvents;
+use crate::web3::{send_with_retry, GasOracle, ProviderWithSigner, GAS_FACTOR, TSA};
 use anyhow::{Error, Result};
-use ethers::prelude::{Middleware, ValueOrArray, U256, U64};
-use log::{debug, info};
+use ethers::abi::Address;
+use ethers::contract::parse_log;
+use ethers::prelude::{Filter, Log, Middleware, Provider, StreamExt, ValueOrArray, Ws, U256, U64};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::select;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
 
 pub const MAX_TO_PROCESS_PER_CALL: usize = 32;
+pub const EVENT_LOOKBACK_BLOCKS: u64 = 100_000;
+/// Max wait for the event listener to see the block of our own processing tx
+const LISTENER_CATCH_UP_SEC: u64 = 30;
+const LISTENER_MAX_BACKOFF_SEC: u64 = 64;
+
+/// Pending deposit and withdrawal ids of the TSA, as maintained by `listen_tsa_events`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TsaPending {
+    pub block: u64, // Last block whose events are all included
+    pub deposits: Vec<U256>,
+    pub withdrawals: Vec<U256>,
+}
+
+impl TsaPending {
+    /// Applies a TSA log, returns false if it does not change the queues. Replaying a block is
+    /// harmless since ids are only added once and removals are idempotent.
+    fn apply(&mut self, log: &Log) -> bool {
+        let Ok(event) = parse_log::<TSAEvents>(log.clone()) else {
+            return false;
+        };
+        match event {
+            TSAEvents::DepositInitiatedFilter(e) if !self.deposits.contains(&e.deposit_id) => {
+                self.deposits.push(e.deposit_id)
+            }
+            TSAEvents::DepositProcessedFilter(e) => self.deposits.retain(|d| *d != e.deposit_id),
+            TSAEvents::WithdrawalRequestedFilter(e)
+                if !self.withdrawals.contains(&e.withdrawal_id) =>
+            {
+                self.withdrawals.push(e.withdrawal_id)
+            }
+            // partially processed requests emit an event with complete = false and stay queued
+            TSAEvents::WithdrawalProcessedFilter(e) if e.complete => {
+                self.withdrawals.retain(|w| *w != e.withdrawal_id)
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Undoes a log that was reorged out (`removed`), returns false if it changes nothing
+    fn revert(&mut self, log: &Log) -> bool {
+        let Ok(event) = parse_log::<TSAEvents>(log.clone()) else {
+            return false;
+        };
+        match event {
+            TSAEvents::DepositInitiatedFilter(e) if self.deposits.contains(&e.deposit_id) => {
+                self.deposits.retain(|d| *d != e.deposit_id)
+            }
+            TSAEvents::DepositProcessedFilter(e) if !self.deposits.contains(&e.deposit_id) => {
+                self.deposits.push(e.deposit_id)
+            }
+            TSAEvents::WithdrawalRequestedFilter(e)
+                if self.withdrawals.contains(&e.withdrawal_id) =>
+            {
+                self.withdrawals.retain(|w| *w != e.withdrawal_id)
+            }
+            TSAEvents::WithdrawalProcessedFilter(e)
+                if e.complete && !self.withdrawals.contains(&e.withdrawal_id) =>
+            {
+                self.withdrawals.push(e.withdrawal_id)
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Listener state saved under STATE_DIR so that a restart only backfills the missed blocks
+#[derive(Debug, Clone)]
+pub struct EventCheckpoint {
+    path: PathBuf,
+}
+
+impl EventCheckpoint {
+    pub fn new(vault_name: &str) -> Self {
+        let dir = std::env::var("STATE_DIR").unwrap_or("./state".to_string());
+        let file_name = format!("{}_events.json", vault_name.to_lowercase());
+        Self { path: PathBuf::from(dir).join(file_name) }
+    }
+
+    pub async fn load(&self) -> Result<Option<TsaPending>> {
+        if !tokio::fs::try_exists(&self.path).await? {
+            return Ok(None);
+        }
+        let contents = tokio::fs::read_to_string(&self.path).await?;
+        match serde_json::from_str::<TsaPending>(&contents) {
+            Ok(pending) => Ok(Some(pending)),
+            Err(e) => {
+                warn!("Ignoring corrupt event checkpoint at {:?}: {:?}", self.path, e);
+                Ok(None)
+            }
+        }
+    }
+
+    pub async fn save(&self, pending: &TsaPending) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_string(pending)?).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+}
+
+/// Receiving end of the event listener, read by the stages through the `VaultContext`
+#[derive(Debug, Default)]
+pub struct TsaEventFeed {
+    latest: Arc<RwLock<Option<TsaPending>>>,
+    min_block: AtomicU64, // Block of our last processing tx, older snapshots are stale
+}
+
+impl TsaEventFeed {
+    /// Keeps the latest snapshot sent by the listener until the channel closes
+    pub fn new(mut receiver: mpsc::Receiver<TsaPending>) -> Self {
+        let feed = TsaEventFeed::default();
+        let latest = feed.latest.clone();
+        tokio::spawn(async move {
+            while let Some(pending) = receiver.recv().await {
+                if let Ok(mut latest) = latest.write() {
+                    *latest = Some(pending);
+                }
+            }
+        });
+        feed
+    }
+
+    /// Makes `pending` wait for the listener to see the given block, e.g. a processing tx
+    pub fn processed_at(&self, block: Option<U64>) {
+        if let Some(block) = block {
+            self.min_block.fetch_max(block.as_u64(), Ordering::Relaxed);
+        }
+    }
+
+    /// Latest pending ids, None if the listener has not caught up (callers query logs instead)
+    pub async fn pending(&self) -> Option<TsaPending> {
+        let min_block = self.min_block.load(Ordering::Relaxed);
+        for _ in 0..LISTENER_CATCH_UP_SEC {
+            let latest = self.latest.read().ok()?.clone()?;
+            if latest.block >= min_block {
+                return Some(latest);
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        }
+        warn!("TSA event listener is behind block {}", min_block);
+        None
+    }
+}
+
+/// Streams the TSA logs over WEB3_WS_PROVIDER and sends the pending queues after every change
+/// (and every new block). Starts from the checkpoint, or `EVENT_LOOKBACK_BLOCKS` back.
+pub async fn listen_tsa_events(vault_name: &str, sender: &mpsc::Sender<TsaPending>) -> Result<()> {
+    let ws_url = std::env::var("WEB3_WS_PROVIDER")?;
+    let provider = Provider::<Ws>::connect(ws_url).await?;
+    let tsa_address: Address = std::env::var(format!("{vault_name}_TSA_ADDRESS"))?.parse()?;
+    let checkpoint = EventCheckpoint::new(vault_name);
+    let filter = Filter::new().address(tsa_address);
+
+    // subscribe before the backfill so that no block falls in between
+    let mut logs = provider.subscribe_logs(&filter).await?;
+    let mut blocks = provider.subscribe_blocks().await?;
+    let head = provider.get_block_number().await?.as_u64();
+    let mut pending = match checkpoint.load().await? {
+        Some(pending) => pending,
+        None => {
+            let block = head.saturating_sub(EVENT_LOOKBACK_BLOCKS);
+            TsaPending { block, ..Default::default() }
+        }
+    };
+    // the checkpoint block itself is replayed since it may have been saved mid block
+    info!("Backfilling TSA events from block {} to {}", pending.block, head);
+    let backfill = filter.clone().from_block(pending.block).to_block(head);
+    for log in provider.get_logs(&backfill).await? {
+        pending.apply(&log);
+    }
+    pending.block = pending.block.max(head);
+
+    loop {
+        debug!("TSA pending at block {}: {:?}", pending.block, pending);
+        checkpoint.save(&pending).await?;
+        sender.send(pending.clone()).await?;
+        // logs first, so that a block never advances the checkpoint before its logs are applied
+        select! {
+            biased;
+            log = logs.next() => {
+                let log = log.ok_or(Error::msg("TSA log subscription ended"))?;
+                let block = log.block_number.map_or(0, |b| b.as_u64());
+                if log.removed == Some(true) {
+                    warn!("TSA log of block {} reorged out: {:?}", block, log.transaction_hash);
+                    // the replacing block is replayed after a restart
+                    pending.block = pending.block.min(block.saturating_sub(1));
+                    pending.revert(&log);
+                    continue;
+                }
+                // already backfilled
+                if block <= head || !pending.apply(&log) {
+                    continue;
+                }
+            }
+            block = blocks.next() => {
+                let block = block.ok_or(Error::msg("Block subscription ended"))?;
+                // the logs of a block are delivered before the next head
+                let Some(number) = block.number else { continue };
+                pending.block = pending.block.max(number.as_u64().saturating_sub(1));
+            }
+        }
+    }
+}
+
+/// Reruns `listen_tsa_events` with backoff, only returns once the executor side is gone
+pub async fn listen_tsa_events_forever(vault_name: String, sender: mpsc::Sender<TsaPending>) {
+    let mut backoff = 4;
+    while !sender.is_closed() {
+        let res = listen_tsa_events(&vault_name, &sender).await;
+        error!("TSA event listener exited with {:?}, restarting in {} sec", res, backoff);
+        tokio::time::sleep(tokio::time::Duration::from_secs(backoff)).await;
+        backoff = (backoff * 2).min(LISTENER_MAX_BACKOFF_SEC);
+    }
+}
+
+/// The event feed and listener task of the vault if WEB3_WS_PROVIDER is set, otherwise the
+/// stages keep querying the logs of the last `EVENT_LOOKBACK_BLOCKS` blocks
+pub fn tsa_event_listener(
+    vault_name: &str,
+) -> Option<(Arc<TsaEventFeed>, impl Future<Output = ()>)> {
+    std::env::var("WEB3_WS_PROVIDER").ok()?;
+    let (sender, receiver) = mpsc::channel(16);
+    let feed = Arc::new(TsaEventFeed::new(receiver));
+    Some((feed, listen_tsa_events_forever(vault_name.to_string(), sender)))
+}
+
+/// Pending queues from the event listener if it runs and is up to date
+async fn listener_pending() -> Option<TsaPending> {
+    let feed = VaultContext::current().tsa_events.clone()?;
+    feed.pending().await
+}
+
+/// Marks the block of our own processing tx, so the next read waits for its events
+fn listener_processed_at(block: Option<U64>) {
+    if let Some(feed) = &VaultContext::current().tsa_events {
+        feed.processed_at(block);
+    }
+}
 
 pub async fn process_deposit_events(tsa: &TSA<ProviderWithSigner>) -> Result<()> {
+    let pending = match listener_pending().await {
+        Some(pending) => pending.deposits,
+        None => query_pending_deposits(tsa).await?,
+    };
+    info!("Pending deposits: {:?}", pending);
+    VaultContext::current().health.update(|h| h.pending_deposits = Some(pending.len()));
+    if pending.is_empty() {
+        info!("No pending deposits");
+        return Ok(());
+    }
+    let pending = pending.into_iter().take(MAX_TO_PROCESS_PER_CALL).collect();
+    info!("Processing subset of deposits: {:?}", pending);
+
+    let receipt = send_with_retry(&tsa.client(), tsa.process_deposits(pending)).await?;
+    info!("Process deposits tx: {:?}", receipt.transaction_hash);
+    listener_processed_at(receipt.block_number);
+    Ok(())
+}
+
+/// Deposits from the last `EVENT_LOOKBACK_BLOCKS` blocks that are not processed
+async fn query_pending_deposits(tsa: &TSA<ProviderWithSigner>) -> Result<Vec<U256>> {
     let block = tsa.client().get_block_number().await?;
     // assume all deposits outside of this range are already processed
-    let from = block - U64::from(100_000);
+    let from = block - U64::from(EVENT_LOOKBACK_BLOCKS);
     let addr = ValueOrArray::Value(tsa.address());
     let init_filter = tsa.deposit_initiated_filter().from_block(from).address(addr.clone());
     let proc_filter = tsa.deposit_processed_filter().from_block(from).address(addr);
@@ -20,22 +296,207 @@ pub async fn process_deposit_events(tsa: &TSA<ProviderWithSigner>) -> Result<()>
     let procs: Vec<U256> = proc_filter.query().await?.iter().map(|e| e.deposit_id).collect();
     info!("Deposits processed: {:?}", procs);
 
-    let pending: Vec<U256> = inits.into_iter().filter(|i| !procs.contains(i)).collect();
-    info!("Pending deposits: {:?}", pending);
+    Ok(inits.into_iter().filter(|i| !procs.contains(i)).collect())
+}
+
+/// Withdrawal requests that are not fully processed, from the event listener if it runs
+pub async fn get_pending_withdrawals(tsa: &TSA<ProviderWithSigner>) -> Result<Vec<U256>> {
+    let pending = match listener_pending().await {
+        Some(pending) => pending.withdrawals,
+        None => query_pending_withdrawals(tsa).await?,
+    };
+    info!("Pending withdrawals: {:?}", pending);
+    VaultContext::current().health.update(|h| h.pending_withdrawals = Some(pending.len()));
+    Ok(pending)
+}
+
+/// Withdrawal requests from the last `EVENT_LOOKBACK_BLOCKS` blocks that are not fully processed
+async fn query_pending_withdrawals(tsa: &TSA<ProviderWithSigner>) -> Result<Vec<U256>> {
+    let block = tsa.client().get_block_number().await?;
+    let from = block - U64::from(EVENT_LOOKBACK_BLOCKS);
+    let addr = ValueOrArray::Value(tsa.address());
+    let req_filter = tsa.withdrawal_requested_filter().from_block(from).address(addr.clone());
+    let proc_filter = tsa.withdrawal_processed_filter().from_block(from).address(addr);
+
+    info!("Running withdrawal queries");
+    let reqs: Vec<U256> = req_filter.query().await?.iter().map(|e| e.withdrawal_id).collect();
+    info!("Withdrawals requested: {:?}", reqs);
+    // partially processed requests emit an event with complete = false and stay in the queue
+    let procs: Vec<U256> =
+        proc_filter.query().await?.iter().filter(|e| e.complete).map(|e| e.withdrawal_id).collect();
+    info!("Withdrawals processed: {:?}", procs);
+
+    Ok(reqs.into_iter().filter(|r| !procs.contains(r)).collect())
+}
+
+/// Processes up to `MAX_TO_PROCESS_PER_CALL` queued withdrawal requests with the TSA balance.
+/// Returns the number of requests still pending afterwards.
+pub async fn process_withdrawal_events(tsa: &TSA<ProviderWithSigner>) -> Result<usize> {
+    let pending = get_pending_withdrawals(tsa).await?;
     if pending.is_empty() {
-        info!("No pending deposits");
-        return Ok(());
+        info!("No pending withdrawals");
+        return Ok(0);
     }
-    let pending = pending.into_iter().take(MAX_TO_PROCESS_PER_CALL).collect();
-    info!("Processing subset of deposits: {:?}", pending);
+    let limit = withdrawal_batch_limit(tsa, pending.len().min(MAX_TO_PROCESS_PER_CALL)).await?;
+    let call = tsa.process_withdrawal_requests(U256::from(limit));
+    let receipt = send_with_retry(&tsa.client(), call).await?;
+    info!("Process withdrawals tx: {:?}", receipt.transaction_hash);
+    listener_processed_at(receipt.block_number);
+    Ok(get_pending_withdrawals(tsa).await?.len())
+}
 
-    let call = tsa.process_deposits(pending).gas_price(GAS_PRICE);
-    let gas = call.estimate_gas().await? * U256::from(GAS_FACTOR);
-    let call = call.gas(gas);
-    let pending_tx = call.send().await?;
-    let receipt = pending_tx.await?.ok_or(Error::msg("Failed"))?;
-    info!("Tx receipt: {}", serde_json::to_string(&receipt)?);
-    let tx = tsa.client().get_transaction(receipt.transaction_hash).await?;
-    info!("Initiate deposit tx: {:?}", tx);
-    Ok(())
+/// Processes the queued withdrawal requests in batches until none are pending or a batch makes
+/// no progress (the TSA balance ran out). Returns the number of requests still pending.
+pub async fn process_withdrawal_requests(tsa: &TSA<ProviderWithSigner>) -> Result<usize> {
+    let mut pending = get_pending_withdrawals(tsa).await?.len();
+    while pending > 0 {
+        let remaining = process_withdrawal_events(tsa).await?;
+        if remaining >= pending {
+            info!("Withdrawal processing made no progress, {} requests pending", remaining);
+            return Ok(remaining);
+        }
+        pending = remaining;
+    }
+    Ok(0)
+}
+
+/// Largest batch (halving from `limit`) whose gas estimate succeeds, e.g. within the block gas
+/// limit, and whose cost at the current fees the signer can pay for
+async fn withdrawal_batch_limit(tsa: &TSA<ProviderWithSigner>, limit: usize) -> Result<usize> {
+    let client = tsa.client();
+    let mut limit = limit.max(1);
+    let gas = loop {
+        let call = tsa.process_withdrawal_requests(U256::from(limit));
+        match call.estimate_gas().await {
+            Ok(gas) => break gas * U256::from(GAS_FACTOR),
+            Err(e) if limit > 1 => {
+                warn!("Gas estimate of {} withdrawals failed with {}, halving", limit, e);
+                limit /= 2;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    let fees = GasOracle::from_env().fees(client.as_ref()).await?;
+    let cost = gas * fees.max_fee_per_gas;
+    let sender = client.default_sender().ok_or(Error::msg("No tx sender"))?;
+    let balance = client.get_balance(sender, None).await?;
+    if balance < cost {
+        let msg = format!("Signer gas balance {} below the withdrawal tx cost {}", balance, cost);
+        send_alert(&msg).await;
+        return Err(Error::msg(msg));
+    }
+    Ok(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web3::tsa::{
+        DepositInitiatedFilter, DepositProcessedFilter, WithdrawalProcessedFilter,
+        WithdrawalRequestedFilter,
+    };
+    use ethers::abi::{encode, Token};
+    use ethers::contract::EthEvent;
+    use ethers::types::H256;
+
+    fn tsa_log(signature: H256, id: u64, data: Vec<Token>) -> Log {
+        let topics = vec![signature, H256::from_low_u64_be(id), H256::zero()];
+        Log { topics, data: encode(&data).into(), ..Default::default() }
+    }
+
+    fn deposit_initiated(id: u64) -> Log {
+        tsa_log(DepositInitiatedFilter::signature(), id, vec![Token::Uint(U256::from(100))])
+    }
+
+    fn deposit_processed(id: u64) -> Log {
+        let data = vec![Token::Bool(true), Token::Uint(U256::from(90))];
+        tsa_log(DepositProcessedFilter::signature(), id, data)
+    }
+
+    fn withdrawal_requested(id: u64) -> Log {
+        tsa_log(WithdrawalRequestedFilter::signature(), id, vec![Token::Uint(U256::from(50))])
+    }
+
+    fn withdrawal_processed(id: u64, complete: bool) -> Log {
+        let amounts = [Token::Uint(U256::from(50)), Token::Uint(U256::from(55))];
+        let data = [vec![Token::Bool(complete)], amounts.to_vec()].concat();
+        tsa_log(WithdrawalProcessedFilter::signature(), id, data)
+    }
+
+    fn ids(ids: &[u64]) -> Vec<U256> {
+        ids.iter().map(|id| U256::from(*id)).collect()
+    }
+
+    #[test]
+    fn test_apply_tracks_queues() {
+        let mut pending = TsaPending::default();
+        assert!(pending.apply(&deposit_initiated(1)));
+        assert!(pending.apply(&deposit_initiated(2)));
+        assert!(pending.apply(&withdrawal_requested(7)));
+        assert_eq!(pending.deposits, ids(&[1, 2]));
+        assert_eq!(pending.withdrawals, ids(&[7]));
+
+        assert!(pending.apply(&deposit_processed(1)));
+        assert_eq!(pending.deposits, ids(&[2]));
+        // a partially processed withdrawal stays queued
+        assert!(!pending.apply(&withdrawal_processed(7, false)));
+        assert_eq!(pending.withdrawals, ids(&[7]));
+        assert!(pending.apply(&withdrawal_processed(7, true)));
+        assert!(pending.withdrawals.is_empty());
+    }
+
+    #[test]
+    fn test_apply_replayed_logs() {
+        let mut pending = TsaPending::default();
+        assert!(pending.apply(&deposit_initiated(1)));
+        assert!(!pending.apply(&deposit_initiated(1)));
+        assert!(pending.apply(&withdrawal_requested(7)));
+        assert!(!pending.apply(&withdrawal_requested(7)));
+        assert_eq!(pending.deposits, ids(&[1]));
+        assert_eq!(pending.withdrawals, ids(&[7]));
+    }
+
+    #[test]
+    fn test_revert_undoes_apply() {
+        let mut pending = TsaPending::default();
+        let logs = [
+            deposit_initiated(1),
+            deposit_initiated(2),
+            withdrawal_requested(7),
+            deposit_processed(1),
+            withdrawal_processed(7, true),
+        ];
+        for log in logs.iter() {
+            assert!(pending.apply(log));
+        }
+        assert_eq!(pending.deposits, ids(&[2]));
+        assert!(pending.withdrawals.is_empty());
+
+        // a reorg removes the processing txs, their requests are pending again
+        assert!(pending.revert(&withdrawal_processed(7, true)));
+        assert!(pending.revert(&deposit_processed(1)));
+        assert_eq!(pending.deposits, ids(&[2, 1]));
+        assert_eq!(pending.withdrawals, ids(&[7]));
+        // reverting twice, or a partial processing, changes nothing
+        assert!(!pending.revert(&deposit_processed(1)));
+        assert!(!pending.revert(&withdrawal_processed(7, false)));
+
+        // and removing the requests as well empties the queues
+        assert!(pending.revert(&withdrawal_requested(7)));
+        assert!(pending.revert(&deposit_initiated(1)));
+        assert!(pending.revert(&deposit_initiated(2)));
+        assert!(!pending.revert(&deposit_initiated(2)));
+        assert!(pending.deposits.is_empty());
+        assert!(pending.withdrawals.is_empty());
+    }
+
+    #[test]
+    fn test_ignores_other_logs() {
+        let mut pending = TsaPending::default();
+        let other = tsa_log(H256::repeat_byte(1), 1, vec![Token::Uint(U256::from(100))]);
+        assert!(!pending.apply(&other));
+        assert!(!pending.revert(&other));
+        assert!(!pending.apply(&Log::default()));
+        assert!(pending.deposits.is_empty());
+    }
 }
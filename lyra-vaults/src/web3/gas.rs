@@ -0,0 +1,118 @@
+use anyhow::{Error, Result};
+use ethers::contract::ContractCall;
+use ethers::prelude::{BlockNumber, Middleware, U256};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+/// Floor for the priority fee, the node suggestion is used if higher
+const DEFAULT_MIN_PRIORITY_FEE_WEI: u64 = 200_000;
+/// Cap for max fee per gas (10 gwei), escalation stops here
+const DEFAULT_MAX_FEE_WEI: u64 = 10_000_000_000;
+/// Max fee covers this many times the current base fee, so the tx survives base fee increases
+const DEFAULT_BASE_FEE_MULTIPLIER: u64 = 2;
+/// Bump of both fees per retry in percent, nodes require at least 10% to replace a pending tx
+const DEFAULT_ESCALATION_PCT: u64 = 25;
+
+/// EIP-1559 fees of a single transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasFees {
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+}
+
+impl GasFees {
+    /// Sets the fees on the call, legacy txs use the max fee as the gas price
+    pub fn apply<M, D>(&self, mut call: ContractCall<M, D>) -> ContractCall<M, D> {
+        match call.tx.as_eip1559_mut() {
+            Some(tx) => {
+                tx.max_fee_per_gas = Some(self.max_fee_per_gas);
+                tx.max_priority_fee_per_gas = Some(self.max_priority_fee_per_gas);
+            }
+            None => {
+                call.tx.set_gas_price(self.max_fee_per_gas);
+            }
+        }
+        call
+    }
+}
+
+/// Derives tx fees from the latest base fee and the node's priority fee suggestion.
+/// Caps and escalation can be overridden with the GAS_MAX_FEE_WEI, GAS_MIN_PRIORITY_FEE_WEI,
+/// GAS_BASE_FEE_MULTIPLIER and GAS_ESCALATION_PCT env vars.
+#[derive(Debug, Clone)]
+pub struct GasOracle {
+    pub max_fee: U256,
+    pub min_priority_fee: U256,
+    pub base_fee_multiplier: u64,
+    pub escalation_pct: u64,
+}
+
+pub(crate) fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name).ok().map(|v| v.parse::<T>()) {
+        Some(Ok(value)) => value,
+        Some(Err(_)) => {
+            warn!("Invalid {}, using the default", name);
+            default
+        }
+        None => default,
+    }
+}
+
+impl GasOracle {
+    pub fn from_env() -> Self {
+        Self {
+            max_fee: U256::from(env_or("GAS_MAX_FEE_WEI", DEFAULT_MAX_FEE_WEI)),
+            min_priority_fee: U256::from(env_or(
+                "GAS_MIN_PRIORITY_FEE_WEI",
+                DEFAULT_MIN_PRIORITY_FEE_WEI,
+            )),
+            base_fee_multiplier: env_or("GAS_BASE_FEE_MULTIPLIER", DEFAULT_BASE_FEE_MULTIPLIER),
+            escalation_pct: env_or("GAS_ESCALATION_PCT", DEFAULT_ESCALATION_PCT),
+        }
+    }
+
+    /// Current fees, max fee = base fee * multiplier + priority fee (both capped)
+    pub async fn fees<M: Middleware>(&self, client: &M) -> Result<GasFees>
+    where
+        M::Error: 'static,
+    {
+        let block = client
+            .get_block(BlockNumber::Latest)
+            .await?
+            .ok_or(Error::msg("Latest block not found"))?;
+        let base_fee = block.base_fee_per_gas.ok_or(Error::msg("Chain has no base fee"))?;
+        let suggested_priority_fee = client
+            .provider()
+            .request::<_, U256>("eth_maxPriorityFeePerGas", ())
+            .await
+            .unwrap_or_else(|e| {
+                warn!("eth_maxPriorityFeePerGas failed with {}, using the minimum", e);
+                U256::zero()
+            });
+        let priority_fee = suggested_priority_fee.max(self.min_priority_fee);
+        let max_fee = base_fee * U256::from(self.base_fee_multiplier) + priority_fee;
+        let fees = self
+            .capped(GasFees { max_fee_per_gas: max_fee, max_priority_fee_per_gas: priority_fee });
+        info!("Gas fees with base fee {}: {:?}", base_fee, fees);
+        Ok(fees)
+    }
+
+    /// Bumps both fees for a retry, None if the fees are already at the cap
+    pub fn escalate(&self, fees: GasFees) -> Option<GasFees> {
+        let bump = |fee: U256| fee * U256::from(100 + self.escalation_pct) / U256::from(100);
+        let escalated = self.capped(GasFees {
+            max_fee_per_gas: bump(fees.max_fee_per_gas),
+            max_priority_fee_per_gas: bump(fees.max_priority_fee_per_gas),
+        });
+        match escalated == fees {
+            true => None,
+            false => Some(escalated),
+        }
+    }
+
+    fn capped(&self, fees: GasFees) -> GasFees {
+        let max_fee_per_gas = fees.max_fee_per_gas.min(self.max_fee);
+        let max_priority_fee_per_gas = fees.max_priority_fee_per_gas.min(max_fee_per_gas);
+        GasFees { max_fee_per_gas, max_priority_fee_per_gas }
+    }
+}
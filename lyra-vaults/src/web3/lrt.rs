@@ -0,0 +1,59 @@
+use crate::web3::providers::get_provider;
+use anyhow::{Error, Result};
+use bigdecimal::BigDecimal;
+use ethers::abi::Address;
+use ethers::contract::abigen;
+use ethers::types::U256;
+use lyra_client::utils::u256_to_decimal;
+use serde::Deserialize;
+use std::str::FromStr;
+
+abigen!(
+    LrtRate,
+    r#"[
+        function getRate() external view returns (uint256)
+        function convertToAssets(uint256 shares) external view returns (uint256)
+        function rsETHPrice() external view returns (uint256)
+    ]"#,
+);
+
+/// View function of the LRT protocol returning the underlying per LRT with 18 decimals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LrtRateMethod {
+    #[default]
+    GetRate, // weETH, rswETH
+    ConvertToAssets, // ERC4626 vaults
+    RsEthPrice,      // Kelp rsETH oracle
+}
+
+impl FromStr for LrtRateMethod {
+    type Err = Error;
+
+    fn from_str(method: &str) -> Result<Self> {
+        match method {
+            "get_rate" => Ok(Self::GetRate),
+            "convert_to_assets" => Ok(Self::ConvertToAssets),
+            "rs_eth_price" => Ok(Self::RsEthPrice),
+            _ => Err(Error::msg(format!("Unknown LRT rate method {}", method))),
+        }
+    }
+}
+
+/// Exchange rate of the LRT into its underlying at the latest block of the chain
+pub async fn get_lrt_rate(
+    chain_id: u64,
+    address: Address,
+    method: LrtRateMethod,
+) -> Result<BigDecimal> {
+    let provider = get_provider(chain_id).await?;
+    let contract = LrtRate::new(address, provider);
+    let rate = match method {
+        LrtRateMethod::GetRate => contract.get_rate().call().await?,
+        LrtRateMethod::ConvertToAssets => {
+            contract.convert_to_assets(U256::exp10(18)).call().await?
+        }
+        LrtRateMethod::RsEthPrice => contract.rs_eth_price().call().await?,
+    };
+    u256_to_decimal(rate)
+}
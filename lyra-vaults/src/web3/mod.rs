@@ -1,10 +1,18 @@
 pub mod actions;
 pub mod contracts;
 pub mod events;
+pub mod gas;
+pub mod lrt;
+pub mod oracle;
+pub mod providers;
 pub mod scripts;
+pub mod settlement;
 pub mod tsa;
+pub mod tx;
 pub mod yields;
 
 pub use actions::*;
 pub use contracts::*;
 pub use events::*;
+pub use gas::*;
+pub use tx::*;
@@ -0,0 +1,40 @@
+use crate::web3::providers::get_provider;
+use anyhow::{Error, Result};
+use bigdecimal::BigDecimal;
+use ethers::abi::Address;
+use ethers::contract::abigen;
+use ethers::prelude::{Http, Provider};
+use lyra_client::utils::i256_to_decimal_with_prec;
+use std::env;
+use std::sync::Arc;
+
+abigen!(
+    AggregatorV3,
+    r#"[
+        function decimals() external view returns (uint8)
+        function latestRoundData() external view returns (uint80, int256, uint256, uint256, uint80)
+    ]"#,
+);
+
+/// Latest answer of a Chainlink aggregator on the chain of the `ProviderRegistry` or, without a
+/// chain id, of the `provider_env` RPC URL. Fails if it was not updated within `max_age_sec`.
+pub async fn get_chainlink_price(
+    chain_id: Option<u64>,
+    provider_env: &str,
+    address: Address,
+    max_age_sec: i64,
+) -> Result<BigDecimal> {
+    let provider = match chain_id {
+        Some(chain_id) => get_provider(chain_id).await?,
+        None => Arc::new(Provider::<Http>::try_from(env::var(provider_env)?)?),
+    };
+    let feed = AggregatorV3::new(address, provider);
+    let decimals = feed.decimals().call().await?;
+    let (_, answer, _, updated_at, _) = feed.latest_round_data().call().await?;
+    let age_sec = chrono::Utc::now().timestamp() - updated_at.as_u64() as i64;
+    if age_sec > max_age_sec {
+        let msg = format!("Chainlink feed {:?} not updated for {}s", address, age_sec);
+        return Err(Error::msg(msg));
+    }
+    i256_to_decimal_with_prec(answer, decimals as u32)
+}
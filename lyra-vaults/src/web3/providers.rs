@@ -0,0 +1,73 @@
+use anyhow::{Error, Result};
+use ethers::prelude::{Http, Middleware, Provider};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use tracing::info;
+
+/// Ethereum mainnet, e.g. for the LRT primary markets and exchange rates
+pub const MAINNET_CHAIN_ID: u64 = 1;
+
+static PROVIDER_REGISTRY: OnceLock<ProviderRegistry> = OnceLock::new();
+
+/// Chain id of the Lyra chain, from CHAIN_ID
+pub fn lyra_chain_id() -> Result<u64> {
+    let chain_id = std::env::var("CHAIN_ID").map_err(|_| Error::msg("CHAIN_ID is not set"))?;
+    Ok(chain_id.parse()?)
+}
+
+/// RPC URL of the chain: `WEB3_PROVIDER_{chain id}`, with WEB3_PROVIDER for the Lyra chain and
+/// MAINNET_PROVIDER for Ethereum mainnet as fallbacks
+fn provider_url(chain_id: u64) -> Result<String> {
+    if let Ok(url) = std::env::var(format!("WEB3_PROVIDER_{chain_id}")) {
+        return Ok(url);
+    }
+    let fallback = match chain_id {
+        MAINNET_CHAIN_ID => "MAINNET_PROVIDER",
+        _ if chain_id == lyra_chain_id()? => "WEB3_PROVIDER",
+        _ => return Err(Error::msg(format!("WEB3_PROVIDER_{chain_id} is not set"))),
+    };
+    std::env::var(fallback).map_err(|_| Error::msg(format!("{fallback} is not set")))
+}
+
+/// HTTP providers by chain id, created on first use once the node confirmed its chain id
+#[derive(Debug, Default)]
+pub struct ProviderRegistry {
+    providers: RwLock<HashMap<u64, Arc<Provider<Http>>>>,
+}
+
+impl ProviderRegistry {
+    pub fn global() -> &'static ProviderRegistry {
+        PROVIDER_REGISTRY.get_or_init(ProviderRegistry::default)
+    }
+
+    pub async fn get(&self, chain_id: u64) -> Result<Arc<Provider<Http>>> {
+        if let Some(provider) = self.providers.read().unwrap().get(&chain_id) {
+            return Ok(provider.clone());
+        }
+        let provider = Provider::<Http>::try_from(provider_url(chain_id)?)?;
+        let node_chain_id = provider.get_chainid().await?.as_u64();
+        if node_chain_id != chain_id {
+            let msg = format!("Provider of chain {} is on chain {}", chain_id, node_chain_id);
+            return Err(Error::msg(msg));
+        }
+        info!("Connected provider of chain {}", chain_id);
+        let provider = Arc::new(provider);
+        self.providers.write().unwrap().insert(chain_id, provider.clone());
+        Ok(provider)
+    }
+}
+
+/// Provider of the chain from the global registry
+pub async fn get_provider(chain_id: u64) -> Result<Arc<Provider<Http>>> {
+    ProviderRegistry::global().get(chain_id).await
+}
+
+/// Provider of the Lyra chain (CHAIN_ID)
+pub async fn get_lyra_provider() -> Result<Arc<Provider<Http>>> {
+    get_provider(lyra_chain_id()?).await
+}
+
+/// Provider of Ethereum mainnet
+pub async fn get_mainnet_provider() -> Result<Arc<Provider<Http>>> {
+    get_provider(MAINNET_CHAIN_ID).await
+}
@@ -1,21 +1,21 @@
 use crate::web3;
-use crate::web3::{get_provider_with_signer, get_tsa_contract, ERC20};
-use anyhow::Error;
+use crate::web3::{get_provider_with_signer, get_tsa_contract, send_with_retry, ERC20};
 use bigdecimal::BigDecimal;
 use ethers::abi::Address;
 use ethers::prelude::Middleware;
-use log::info;
 use lyra_client::actions::OrderArgs;
-use lyra_client::auth::{load_signer_by_name, sign_auth_header};
+use lyra_client::auth::{load_signer, load_signer_by_name, sign_auth_header};
+use lyra_client::config::LyraConfig;
 use lyra_client::json_rpc::{http_rpc, WsClient, WsClientExt};
 use lyra_client::utils::decimal_to_u256;
 use orderbook_types::types::orders::{Direction, OrderType, TimeInForce};
 use orderbook_types::types::tickers::TickerResponse;
 use serde_json::{json, Value};
 use std::str::FromStr;
+use tracing::info;
 
 pub async fn test_order() -> anyhow::Result<()> {
-    let subaccount_id: i64 = std::env::var("SUBACCOUNT_ID")?.parse()?;
+    let subaccount_id = LyraConfig::current()?.subaccount_id()?;
     let tsa_contract = get_tsa_contract("RSWETH", "SESSION").await?;
     let order_args = OrderArgs {
         amount: BigDecimal::from_str("1")?,
@@ -25,6 +25,7 @@ pub async fn test_order() -> anyhow::Result<()> {
         order_type: OrderType::Limit,
         label: String::from("test-vault"),
         mmp: false,
+        max_slippage_bps: None,
     };
     let ticker = http_rpc::<_, TickerResponse>(
         "public/get_ticker",
@@ -34,11 +35,12 @@ pub async fn test_order() -> anyhow::Result<()> {
     .await?
     .into_result()?
     .result;
-    let action_data = web3::sign_order(&tsa_contract, &ticker, &order_args).await?;
+    let action_data = web3::sign_order(&tsa_contract, subaccount_id, &ticker, &order_args).await?;
     let client = WsClient::new_client().await?;
     client.login().await?;
-    let session_signer = load_signer_by_name("SESSION").await;
-    let order = action_data.to_order_params(&session_signer, &ticker, order_args)?;
+    let session_signer = load_signer().await?;
+    let config = client.get_config().await;
+    let order = action_data.to_order_params(&session_signer, &config, &ticker, order_args).await?;
     let res = client.send_rpc::<_, Value>("private/order", order).await?;
     info!("Order response: {:?}", res);
     Ok(())
@@ -54,9 +56,7 @@ pub async fn test_mint() -> anyhow::Result<()> {
     let keeper_addr = erc20_contract.client().default_sender().unwrap();
     info!("Minting {} to {} by {}", amount, mint_to, keeper_addr);
     let call = erc20_contract.mint(mint_to, decimal_to_u256(amount)?);
-    let pending_tx = call.send().await?;
-    let receipt = pending_tx.await?.ok_or(Error::msg("Failed"))?;
-    info!("Tx receipt: {}", serde_json::to_string(&receipt)?);
+    let receipt = send_with_retry(&erc20_contract.client(), call).await?;
     let tx = erc20_contract.client().get_transaction(receipt.transaction_hash).await?;
     info!("Mint tx: {:?}", tx);
     Ok(())
@@ -69,8 +69,10 @@ pub async fn test_deposit() -> anyhow::Result<()> {
     let action_data = web3::sign_deposit(&tsa_contract, &asset_name, &amount).await?;
     let client = WsClient::new_client().await?;
     client.login().await?;
-    let session_signer = load_signer_by_name("SESSION").await;
-    let deposit = action_data.to_deposit_params(&session_signer, amount, asset_name)?;
+    let session_signer = load_signer().await?;
+    let config = client.get_config().await;
+    let deposit =
+        action_data.to_deposit_params(&session_signer, &config, amount, asset_name).await?;
     let res = client.send_rpc::<_, Value>("private/deposit", deposit).await?;
     info!("Deposit response: {:?}", res);
     Ok(())
@@ -83,8 +85,10 @@ pub async fn test_withdrawal() -> anyhow::Result<()> {
     let action_data = web3::sign_withdrawal(&tsa_contract, &asset_name, &amount).await?;
     let client = WsClient::new_client().await?;
     client.login().await?;
-    let session_signer = load_signer_by_name("SESSION").await;
-    let deposit = action_data.to_withdraw_params(&session_signer, amount, asset_name)?;
+    let session_signer = load_signer().await?;
+    let config = client.get_config().await;
+    let deposit =
+        action_data.to_withdraw_params(&session_signer, &config, amount, asset_name).await?;
     let res = client.send_rpc::<_, Value>("private/withdraw", deposit).await?;
     info!("Withdrawal response: {:?}", res);
 
@@ -92,9 +96,9 @@ pub async fn test_withdrawal() -> anyhow::Result<()> {
 }
 
 pub async fn test_header() -> anyhow::Result<()> {
-    let subaccount_id: i64 = std::env::var("SUBACCOUNT_ID")?.parse()?;
-    let wallet = load_signer_by_name("KEEPER").await;
-    let header = sign_auth_header(&wallet).await;
+    let subaccount_id = LyraConfig::current()?.subaccount_id()?;
+    let wallet = load_signer_by_name("KEEPER").await?;
+    let header = sign_auth_header(&wallet).await?;
     info!("Header: {:?}", header);
     http_rpc::<Value, Value>(
         "private/get_subaccount",
@@ -114,9 +118,7 @@ pub async fn test_initiate_deposit() -> anyhow::Result<()> {
     let call = tsa_contract.initiate_deposit(decimal_to_u256(amount)?, addr);
     let static_call = call.call().await?;
     info!("Initiate deposit call: {:?}", static_call);
-    let pending_tx = call.send().await?;
-    let receipt = pending_tx.await?.ok_or(Error::msg("Failed"))?;
-    info!("Tx receipt: {}", serde_json::to_string(&receipt)?);
+    let receipt = send_with_retry(&tsa_contract.client(), call).await?;
     let tx = tsa_contract.client().get_transaction(receipt.transaction_hash).await?;
     info!("Initiate deposit tx: {:?}", tx);
     Ok(())
@@ -130,9 +132,7 @@ pub async fn test_initiate_withdrawal() -> anyhow::Result<()> {
     let call = tsa_contract.request_withdrawal(decimal_to_u256(amount)?);
     let static_call = call.call().await?;
     info!("Initiate wd call: {:?}", static_call);
-    let pending_tx = call.send().await?;
-    let receipt = pending_tx.await?.ok_or(Error::msg("Failed"))?;
-    info!("Tx receipt: {}", serde_json::to_string(&receipt)?);
+    let receipt = send_with_retry(&tsa_contract.client(), call).await?;
     let tx = tsa_contract.client().get_transaction(receipt.transaction_hash).await?;
     info!("Initiate withdrawal tx: {:?}", tx);
     Ok(())
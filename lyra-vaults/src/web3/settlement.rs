@@ -0,0 +1,38 @@
+use crate::web3::providers::get_lyra_provider;
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use ethers::abi::Address;
+use ethers::contract::abigen;
+use lyra_client::utils::u256_to_decimal;
+use std::env;
+
+abigen!(
+    OptionAsset,
+    r#"[
+        function settlementFeed() external view returns (address)
+    ]"#,
+);
+
+abigen!(
+    SettlementFeed,
+    r#"[
+        function getSettlementPrice(uint64 expiry) external view returns (bool, uint256)
+    ]"#,
+);
+
+/// Settlement price of the currency's options at `expiry` per the settlement feed of the option
+/// asset (`{CURRENCY}_OPTION_ADDRESS`), None while the feed has not settled the expiry
+pub async fn get_onchain_settlement_price(
+    currency: &str,
+    expiry: i64,
+) -> Result<Option<BigDecimal>> {
+    let address: Address = env::var(format!("{currency}_OPTION_ADDRESS"))?.parse()?;
+    let provider = get_lyra_provider().await?;
+    let option = OptionAsset::new(address, provider.clone());
+    let feed = SettlementFeed::new(option.settlement_feed().call().await?, provider);
+    let (settled, price) = feed.get_settlement_price(expiry as u64).call().await?;
+    match settled {
+        true => Ok(Some(u256_to_decimal(price)?)),
+        false => Ok(None),
+    }
+}
@@ -0,0 +1,189 @@
+use crate::web3::gas::env_or;
+use crate::web3::{GasOracle, ProviderWithSigner, GAS_FACTOR};
+use anyhow::{Error, Result};
+use ethers::abi::Detokenize;
+use ethers::contract::ContractCall;
+use ethers::prelude::{BlockNumber, Middleware, TransactionReceipt, H256, U256, U64};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Attempts per transaction, every retry escalates the fees
+pub const MAX_TX_ATTEMPTS: usize = 5;
+/// Wait for a receipt before resending with escalated fees
+const DEFAULT_TX_TIMEOUT_SEC: u64 = 60;
+/// Blocks on top of the tx block (incl.) before it counts as final
+const DEFAULT_TX_CONFIRMATIONS: u64 = 1;
+const RECEIPT_POLL_MS: u64 = 2000;
+
+/// Node errors when sending a tx that the `TxManager` can recover from
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SendError {
+    /// The tx (or the one it replaces) needs higher fees
+    Underpriced,
+    /// The nonce was used already, i.e. a previous attempt (or another tx) was mined
+    NonceTooLow,
+    /// The very same tx is already in the mempool
+    AlreadyKnown,
+    Other,
+}
+
+impl SendError {
+    fn classify(error: &str) -> Self {
+        let error = error.to_lowercase();
+        let matches = |patterns: &[&str]| patterns.iter().any(|p| error.contains(p));
+        if matches(&[
+            "underpriced",
+            "replacement transaction",
+            "fee too low",
+            "max fee per gas less than",
+        ]) {
+            SendError::Underpriced
+        } else if matches(&["nonce too low", "nonce has already been used"]) {
+            SendError::NonceTooLow
+        } else if matches(&["already known", "already imported"]) {
+            SendError::AlreadyKnown
+        } else {
+            SendError::Other
+        }
+    }
+}
+
+/// Final state of a contract write
+#[derive(Debug, Clone)]
+pub enum TxOutcome {
+    /// Mined successfully with the required confirmations
+    Confirmed(TransactionReceipt),
+    /// Mined but reverted, resending would revert again
+    Reverted(TransactionReceipt),
+    /// Not mined after all attempts or once the fees reached the cap, a sent tx may still land
+    Dropped(Vec<H256>),
+}
+
+impl TxOutcome {
+    /// The receipt of a confirmed tx, an error otherwise
+    pub fn into_receipt(self) -> Result<TransactionReceipt> {
+        match self {
+            TxOutcome::Confirmed(receipt) => Ok(receipt),
+            TxOutcome::Reverted(receipt) => {
+                Err(Error::msg(format!("Tx {:?} reverted", receipt.transaction_hash)))
+            }
+            TxOutcome::Dropped(hashes) => {
+                Err(Error::msg(format!("Tx not mined, sent as {:?}", hashes)))
+            }
+        }
+    }
+}
+
+/// Sends contract writes until they are confirmed. Unmined txs are replaced (same nonce) with
+/// escalated `GasOracle` fees after TX_TIMEOUT_SEC, underpriced and nonce errors are recovered
+/// from, and receipts count once they have TX_CONFIRMATIONS blocks.
+#[derive(Debug, Clone)]
+pub struct TxManager {
+    pub oracle: GasOracle,
+    pub max_attempts: usize,
+    pub timeout: Duration,
+    pub confirmations: u64,
+}
+
+impl TxManager {
+    pub fn from_env() -> Self {
+        Self {
+            oracle: GasOracle::from_env(),
+            max_attempts: env_or("TX_MAX_ATTEMPTS", MAX_TX_ATTEMPTS),
+            timeout: Duration::from_secs(env_or("TX_TIMEOUT_SEC", DEFAULT_TX_TIMEOUT_SEC)),
+            confirmations: env_or("TX_CONFIRMATIONS", DEFAULT_TX_CONFIRMATIONS).max(1),
+        }
+    }
+
+    pub async fn send<D: Detokenize>(
+        &self,
+        client: &ProviderWithSigner,
+        call: ContractCall<ProviderWithSigner, D>,
+    ) -> Result<TxOutcome> {
+        let mut fees = self.oracle.fees(client).await?;
+        let gas = fees.apply(call.clone()).estimate_gas().await? * U256::from(GAS_FACTOR);
+        let mut call = call.gas(gas);
+        let mut sent: Vec<H256> = vec![];
+        for attempt in 1..=self.max_attempts {
+            match fees.apply(call.clone()).send().await {
+                Ok(pending_tx) => {
+                    let hash = pending_tx.tx_hash();
+                    info!("Tx {:?} sent (attempt {}) with {:?}", hash, attempt, fees);
+                    sent.push(hash);
+                    if call.tx.nonce().is_none() {
+                        // resends replace this tx, so they have to reuse its nonce
+                        let tx = client.get_transaction(hash).await?;
+                        let tx = tx.ok_or(Error::msg(format!("Sent tx {:?} not found", hash)))?;
+                        call.tx.set_nonce(tx.nonce);
+                    }
+                }
+                Err(e) => match SendError::classify(&e.to_string()) {
+                    SendError::Underpriced => warn!("Tx underpriced with {:?}: {}", fees, e),
+                    // the nonce manager is behind, e.g. after a tx sent by another process
+                    SendError::NonceTooLow if sent.is_empty() => {
+                        let sender = client.default_sender().ok_or(Error::msg("No tx sender"))?;
+                        let pending = Some(BlockNumber::Pending.into());
+                        let nonce = client.get_transaction_count(sender, pending).await?;
+                        warn!("Tx nonce too low ({}), resending with nonce {}", e, nonce);
+                        call.tx.set_nonce(nonce);
+                        continue;
+                    }
+                    // an earlier attempt was mined or is still pending, its receipt is awaited
+                    SendError::NonceTooLow | SendError::AlreadyKnown => {
+                        info!("Tx already sent ({}), awaiting {:?}", e, sent)
+                    }
+                    SendError::Other => return Err(e.into()),
+                },
+            }
+            if let Some(receipt) = self.await_receipt(client, &sent).await? {
+                info!("Tx receipt: {}", serde_json::to_string(&receipt)?);
+                return match receipt.status == Some(U64::zero()) {
+                    true => Ok(TxOutcome::Reverted(receipt)),
+                    false => Ok(TxOutcome::Confirmed(receipt)),
+                };
+            }
+            fees = match self.oracle.escalate(fees) {
+                Some(fees) => fees,
+                None => {
+                    warn!("Tx fees reached the cap at {:?}", fees);
+                    break;
+                }
+            };
+            warn!("Tx not mined, retrying with {:?}", fees);
+        }
+        Ok(TxOutcome::Dropped(sent))
+    }
+
+    /// Polls the receipts of all attempts until one is confirmed or the timeout passes
+    async fn await_receipt(
+        &self,
+        client: &ProviderWithSigner,
+        sent: &[H256],
+    ) -> Result<Option<TransactionReceipt>> {
+        if sent.is_empty() {
+            return Ok(None);
+        }
+        let deadline = Instant::now() + self.timeout;
+        while Instant::now() < deadline {
+            for hash in sent {
+                let receipt = client.get_transaction_receipt(*hash).await?;
+                let Some(receipt) = receipt else { continue };
+                let Some(block) = receipt.block_number else { continue };
+                let head = client.get_block_number().await?;
+                if head.as_u64() + 1 >= block.as_u64() + self.confirmations {
+                    return Ok(Some(receipt));
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(RECEIPT_POLL_MS)).await;
+        }
+        Ok(None)
+    }
+}
+
+/// Sends the call with the env configured `TxManager`, errors unless it is confirmed
+pub async fn send_with_retry<D: Detokenize>(
+    client: &ProviderWithSigner,
+    call: ContractCall<ProviderWithSigner, D>,
+) -> Result<TransactionReceipt> {
+    TxManager::from_env().send(client, call).await?.into_receipt()
+}
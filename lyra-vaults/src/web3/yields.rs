@@ -1,11 +1,12 @@
+use crate::shared::context::VaultContext;
+use crate::web3::providers::get_mainnet_provider;
 use anyhow::{Error, Result};
 use bigdecimal::{BigDecimal, One};
 use ethers::abi::Address;
 use ethers::contract::abigen;
 use ethers::prelude::{
-    Abigen, Http, JsonRpcClient, LocalWallet, Middleware, MiddlewareBuilder, Provider, Signer, U256,
+    Abigen, JsonRpcClient, LocalWallet, Middleware, MiddlewareBuilder, Provider, Signer, U256,
 };
-use log::{debug, error, info};
 use lyra_client::json_rpc::Response;
 use lyra_client::utils::u256_to_decimal;
 use reqwest::Client;
@@ -13,7 +14,7 @@ use serde_json::Value;
 use std::env;
 use std::ops::Deref;
 use std::str::FromStr;
-use std::sync::Arc;
+use tracing::{debug, error, info};
 
 abigen!(
     ERC4626,
@@ -58,7 +59,7 @@ pub async fn get_growth_between(
     to: i64,
     allowed_drawdown: &BigDecimal,
 ) -> Result<BigDecimal> {
-    let cash_name = env::var("CASH_NAME").unwrap();
+    let cash_name = VaultContext::current().cash_name.clone();
     let collat_price_now = get_price_at_timestamp(base, quote, to).await?;
     info!("Collat price now: {}", collat_price_now);
     let collat_price_last = get_price_at_timestamp(base, quote, from).await?;
@@ -79,10 +80,8 @@ pub async fn get_growth_between(
 }
 
 async fn get_susde_price_at_timestamp(timestamp: i64) -> Result<BigDecimal> {
-    let provider_url = env::var("MAINNET_PROVIDER")?;
     let address: Address = env::var("SUSDE_MAINNET_ADDRESS")?.parse()?;
-    let provider = Provider::<Http>::try_from(provider_url)?;
-    let provider = Arc::new(provider);
+    let provider = get_mainnet_provider().await?;
     let contract = ERC4626::new(address, provider);
     let block = get_block(timestamp, &contract.client()).await?;
     let one = U256::from(1e18 as u64);
@@ -91,10 +90,8 @@ async fn get_susde_price_at_timestamp(timestamp: i64) -> Result<BigDecimal> {
 }
 
 async fn get_eeth_rate_at_timestamp(timestamp: i64) -> Result<BigDecimal> {
-    let provider_url = env::var("MAINNET_PROVIDER")?;
     let address: Address = env::var("WEETH_MAINNET_ADDRESS")?.parse()?;
-    let provider = Provider::<Http>::try_from(provider_url)?;
-    let provider = Arc::new(provider);
+    let provider = get_mainnet_provider().await?;
     let contract = WeETH::new(address, provider);
     let block = get_block(timestamp, &contract.client()).await?;
     let rate = contract.get_rate().block(block).call().await?;
@@ -0,0 +1,33 @@
+use crate::types::shared::RPCId;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FundingRateHistoryParams {
+    pub instrument_name: String,
+    ///Start of the range in ms since epoch
+    pub start_timestamp: i64,
+    ///End of the range in ms since epoch
+    pub end_timestamp: i64,
+    ///Sampling period in seconds (900, 3600, 14400, 28800 or 86400)
+    pub period: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FundingRate {
+    ///Timestamp of the sample in ms since epoch
+    pub timestamp: i64,
+    ///Hourly funding rate
+    pub funding_rate: BigDecimal,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FundingRateHistoryResult {
+    pub funding_rate_history: Vec<FundingRate>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FundingRateHistoryResponse {
+    pub id: RPCId,
+    pub result: FundingRateHistoryResult,
+}
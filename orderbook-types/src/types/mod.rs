@@ -1,3 +1,4 @@
+pub mod funding;
 pub mod orders;
 pub mod rfqs;
 pub mod shared;